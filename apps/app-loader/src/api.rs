@@ -1,4 +1,4 @@
-use std::io::Read;
+use std::io::{Read, Write};
 
 use gam::{
     APP_MENU_0_APP_LOADER, APP_MENU_1_APP_LOADER, APP_NAME_APP_LOADER, Gam, MenuItem, MenuMatic,
@@ -9,6 +9,12 @@ use modals::Modals;
 use num_traits::ToPrimitive;
 
 use crate::SERVER_NAME_APP_LOADER;
+use crate::package::{self, Package};
+
+/// Installed apps, keyed by context name; each value is the whole signed package, checked again on load
+const APPS_DICT: &str = "apploader.apps";
+/// Keys the user approved to sign apps, keyed by the key in hex; each value is the app it was approved for
+const KEYS_DICT: &str = "apploader.keys";
 
 pub(crate) struct AppLoader {
     gam: Gam,
@@ -18,6 +24,7 @@ pub(crate) struct AppLoader {
     menu: MenuMatic,
     load_menu: MenuMatic,
     conn: xous::CID,
+    pddb: pddb::Pddb,
     apps: Vec<String>,
     possible_apps: Vec<(String, usize)>,
     server: Option<String>,
@@ -56,21 +63,35 @@ impl AppLoader {
 
         // the menu
         let set_server_item = MenuItem {
-            name: String::from(t!("apploader.menu.setserver", locales::LANG)),
+            name: String::from(t!("apploader.menu.setserver", locales::lang())),
             action_conn: Some(conn),
             action_opcode: Opcode::SetServer.to_u32().unwrap(),
             action_payload: gam::MenuPayload::Scalar([0, 0, 0, 0]),
             close_on_select: true,
         };
         let close_item = MenuItem {
-            name: String::from(t!("apploader.close", locales::LANG)),
+            name: String::from(t!("apploader.close", locales::lang())),
             action_conn: None,
             action_opcode: 0,
             action_payload: gam::MenuPayload::Scalar([0, 0, 0, 0]),
             close_on_select: true,
         };
+        let uninstall_item = MenuItem {
+            name: String::from(t!("apploader.menu.uninstall", locales::lang())),
+            action_conn: Some(conn),
+            action_opcode: Opcode::UninstallApp.to_u32().unwrap(),
+            action_payload: gam::MenuPayload::Scalar([0, 0, 0, 0]),
+            close_on_select: true,
+        };
+        let keys_item = MenuItem {
+            name: String::from(t!("apploader.menu.keys", locales::lang())),
+            action_conn: Some(conn),
+            action_opcode: Opcode::ForgetKey.to_u32().unwrap(),
+            action_payload: gam::MenuPayload::Scalar([0, 0, 0, 0]),
+            close_on_select: true,
+        };
         let menu = menu_matic(
-            vec![set_server_item, close_item.clone()],
+            vec![set_server_item, uninstall_item, keys_item, close_item.clone()],
             APP_MENU_0_APP_LOADER,
             Some(xous::create_server().unwrap()),
         )
@@ -87,6 +108,7 @@ impl AppLoader {
             ticktimer,
             menu,
             load_menu,
+            pddb: pddb::Pddb::new(),
             apps: Vec::new(),
             possible_apps: Vec::new(),
             server: None,
@@ -95,10 +117,10 @@ impl AppLoader {
     }
 
     pub(crate) fn add_app(&mut self, index: usize) {
-        let (name, menus) = self.possible_apps[index];
+        let name = self.possible_apps[index].0.clone();
 
         self.modals
-            .start_progress(t!("apploader.addapp.loading", locales::LANG), 0, 3, 0)
+            .start_progress(t!("apploader.addapp.loading", locales::lang()), 0, 2, 0)
             .expect("Couldn't set up progress bar");
 
         //////////////////////////
@@ -113,9 +135,10 @@ impl AppLoader {
         {
             Ok(response) => response,
             Err(e) => {
+                self.modals.finish_progress().expect("Couldn't close progressbar");
                 self.modals
                     .show_notification(
-                        &format!("{}{}", t!("apploader.addapp.server_error", locales::LANG), e),
+                        &format!("{}{}", t!("apploader.addapp.server_error", locales::lang()), e),
                         None,
                     )
                     .expect("Couldn't show modal");
@@ -126,13 +149,148 @@ impl AppLoader {
         let len = match response.header("Content-Length") {
             Some(len) => len.parse::<usize>().expect("Couldn't parse Content-Length header"),
             None => {
+                self.modals.finish_progress().expect("Couldn't close progressbar");
                 self.modals
-                    .show_notification(t!("apploader.addapp.content_length_error", locales::LANG), None)
+                    .show_notification(t!("apploader.addapp.content_length_error", locales::lang()), None)
                     .expect("Couldn't show modal");
                 return;
             }
         };
 
+        let mut data = vec![0u8; len];
+        response.into_reader().read_exact(&mut data).expect("Couldn't read");
+
+        self.modals.update_progress(1).expect("Couldn't update progress");
+        self.modals.finish_progress().expect("Couldn't close progressbar");
+
+        self.install(data);
+        let _ = self.gam.switch_to_app(APP_NAME_APP_LOADER, self.auth); // try to switch back to the menu
+    }
+
+    /// Checks a downloaded package, asks the user to approve its signer and what it asks for, then saves it
+    /// to the PDDB so that it's loaded again on every boot, and loads it now.
+    fn install(&mut self, data: Vec<u8>) {
+        let package = match Package::parse(&data) {
+            Ok(package) => package,
+            Err(e) => {
+                self.modals
+                    .show_notification(
+                        &format!("{}{}", t!("apploader.package.invalid", locales::lang()), e),
+                        None,
+                    )
+                    .expect("Couldn't show modal");
+                return;
+            }
+        };
+        if self.apps.contains(&package.manifest.name) {
+            // the running copy can't be replaced, as processes can't be stopped yet
+            self.modals
+                .show_notification(t!("apploader.package.already_loaded", locales::lang()), None)
+                .expect("Couldn't show modal");
+            return;
+        }
+
+        let new_signer = !self.is_trusted(&package.signer);
+        if new_signer {
+            let prompt = format!(
+                "{}\n{}",
+                t!("apploader.trust.prompt", locales::lang()),
+                package::fingerprint(&package.signer)
+            );
+            if !self.ask_yes_no(&prompt) {
+                return;
+            }
+        }
+
+        let permissions = if package.manifest.permissions.is_empty() {
+            t!("apploader.install.no_permissions", locales::lang()).to_string()
+        } else {
+            format!(
+                "{}{}",
+                t!("apploader.install.permissions", locales::lang()),
+                package.manifest.permissions.join(", ")
+            )
+        };
+        let prompt = format!(
+            "{}\n{} {}\n{}",
+            t!("apploader.install.prompt", locales::lang()),
+            package.manifest.name,
+            package.manifest.version,
+            permissions
+        );
+        if !self.ask_yes_no(&prompt) {
+            return;
+        }
+
+        // the signer is only trusted from now on if the app it signed was installed
+        if new_signer {
+            if let Err(e) = self.write_key(
+                KEYS_DICT,
+                &package::key_to_hex(&package.signer),
+                package.manifest.name.as_bytes(),
+            ) {
+                log::error!("couldn't save the signing key: {:?}", e);
+                self.modals
+                    .show_notification(
+                        &format!("{}{}", t!("apploader.install.save_error", locales::lang()), e),
+                        None,
+                    )
+                    .expect("Couldn't show modal");
+                return;
+            }
+        }
+        if let Err(e) = self.write_key(APPS_DICT, &package.manifest.name, &data) {
+            log::error!("couldn't save {}: {:?}", package.manifest.name, e);
+            self.modals
+                .show_notification(
+                    &format!("{}{}", t!("apploader.install.save_error", locales::lang()), e),
+                    None,
+                )
+                .expect("Couldn't show modal");
+            return;
+        }
+        if !self.load(&package) {
+            self.modals
+                .show_notification(t!("apploader.addapp.error", locales::lang()), None)
+                .expect("Couldn't show modal");
+        }
+    }
+
+    /// Loads every installed app whose signer is still trusted. Called once the PDDB is mounted.
+    pub(crate) fn load_installed(&mut self) {
+        let names = match self.pddb.list_keys(APPS_DICT, None) {
+            Ok(names) => names,
+            Err(_) => return, // nothing installed yet
+        };
+        for name in names {
+            let data = match self.read_key(APPS_DICT, &name) {
+                Ok(data) => data,
+                Err(e) => {
+                    log::error!("couldn't read installed app {}: {:?}", name, e);
+                    continue;
+                }
+            };
+            match Package::parse(&data) {
+                Ok(package) if !self.is_trusted(&package.signer) => {
+                    log::warn!("not loading {}: its signing key is no longer trusted", name)
+                }
+                Ok(package) if package.manifest.name != name => {
+                    log::warn!("not loading {}: the package is for {}", name, package.manifest.name)
+                }
+                Ok(package) => {
+                    if !self.load(&package) {
+                        log::error!("couldn't load installed app {}", name);
+                    }
+                }
+                Err(e) => log::error!("installed app {} is damaged: {}", name, e),
+            }
+        }
+    }
+
+    /// Spawns the app and adds it to the GAM and the menu. Returns false if it couldn't be started.
+    fn load(&mut self, package: &Package) -> bool {
+        let name = package.manifest.name.clone();
+        let len = package.elf.len();
         let mut memory = xous::map_memory(
             None,
             None,
@@ -140,15 +298,16 @@ impl AppLoader {
             xous::MemoryFlags::R | xous::MemoryFlags::W,
         )
         .expect("Couldn't map memory");
-        response
-            .into_reader()
-            .read_exact(&mut unsafe { memory.as_slice_mut() }[..len])
-            .expect("Couldn't read");
+        unsafe { memory.as_slice_mut() }
+        [..len].copy_from_slice(package.elf);
 
-        self.modals.update_progress(1).expect("Couldn't update progress");
-        //////////////////////
-        // The loading part //
-        //////////////////////
+        // add its name to GAM before it runs, so it can register its UX
+        self.gam.register_name(&name, self.auth).expect("Couldn't register name");
+        for menu in 0..package.manifest.submenus {
+            self.gam
+                .register_name(&format!("{} Submenu {}", name, menu), self.auth)
+                .expect("Couldn't register name");
+        }
 
         // create the spawn process
         let stub = include_bytes!("spawn.bin");
@@ -165,7 +324,15 @@ impl AppLoader {
             xous::send_message(spawn.cid, xous::Message::new_blocking_scalar(2, 1, 2, 3, 4)).unwrap();
         assert_eq!(xous::Result::Scalar1(2), result);
 
-        self.modals.update_progress(2).expect("Couldn't update progress");
+        // the spawn process becomes the app, so its capabilities go with its PID before the app is in it
+        let granted = self
+            .gam
+            .grant_capabilities(&name, &package.manifest.permissions, spawn.pid.get() as u32, self.auth)
+            .expect("Couldn't grant capabilities");
+        if !granted {
+            log::error!("GAM refused the capabilities of {}, not starting it", name);
+            return false;
+        }
 
         // load the app from the binary file
         let res = xous::send_message(spawn.cid, xous::Message::new_lend_mut(1, memory, None, None))
@@ -173,34 +340,14 @@ impl AppLoader {
         // we are just going to do some very basic error handling: if the "offset" is None, we are good,
         // otherwise there was a problem TODO: make this better. Perhaps Buffer::from_raw_parts?
         match res {
-            xous::Result::MemoryReturned(None, _) => {
-                self.modals.update_progress(3).expect("Couldn't update progress")
-            }
-            _ => {
-                self.modals.finish_progress().expect("Couldn't close progressbar");
-                self.modals
-                    .show_notification(t!("apploader.addapp.error", locales::LANG), None)
-                    .expect("Couldn't show modal");
-                return;
-            }
-        }
-
-        //////////////////////
-        // back to graphics //
-        //////////////////////
-
-        // add its name to GAM
-        self.gam.register_name(name.to_str(), self.auth).expect("Couldn't register name");
-        for menu in 0..menus {
-            self.gam
-                .register_name(&format!("{} Submenu {}", name, menu), self.auth)
-                .expect("Couldn't register name");
+            xous::Result::MemoryReturned(None, _) => (),
+            _ => return false,
         }
 
         // add it to the menu
         self.menu.insert_item(
             MenuItem {
-                name,
+                name: name.clone(),
                 action_conn: Some(self.conn),
                 action_opcode: Opcode::DispatchApp.to_u32().unwrap(),
                 action_payload: gam::MenuPayload::Scalar([self.apps.len().try_into().unwrap(), 0, 0, 0]),
@@ -208,12 +355,96 @@ impl AppLoader {
             },
             0,
         );
+        log::info!("Added app `{}'!", name);
         self.apps.push(name);
+        true
+    }
 
-        log::info!("Added app `{}'!", name);
+    pub(crate) fn uninstall_app(&mut self) {
+        let names = self.pddb.list_keys(APPS_DICT, None).unwrap_or_default();
+        if names.is_empty() {
+            self.modals
+                .show_notification(t!("apploader.uninstall.none", locales::lang()), None)
+                .expect("Couldn't show modal");
+            return;
+        }
+        for name in names.iter() {
+            self.modals.add_list_item(name).expect("Couldn't build list");
+        }
+        self.modals.add_list_item(t!("apploader.cancel", locales::lang())).expect("Couldn't build list");
+        let choice = self
+            .modals
+            .get_radiobutton(t!("apploader.uninstall.prompt", locales::lang()))
+            .expect("No choice");
+        if !names.contains(&choice) {
+            return;
+        }
+        self.pddb.delete_key(APPS_DICT, &choice, None).ok();
+        self.pddb.sync().ok();
+        self.modals
+            .show_notification(t!("apploader.uninstall.done", locales::lang()), None)
+            .expect("Couldn't show modal");
+    }
 
-        self.modals.finish_progress().expect("Couldn't close progressbar");
-        let _ = self.gam.switch_to_app(APP_NAME_APP_LOADER, self.auth); // try to switch back to the menu
+    pub(crate) fn forget_key(&mut self) {
+        let keys: Vec<(String, String)> = self
+            .pddb
+            .list_keys(KEYS_DICT, None)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|hex| {
+                let key = package::key_from_hex(&hex)?;
+                let approved_for = self.read_key(KEYS_DICT, &hex).unwrap_or_default();
+                let label =
+                    format!("{} ({})", package::fingerprint(&key), String::from_utf8_lossy(&approved_for));
+                Some((hex, label))
+            })
+            .collect();
+        if keys.is_empty() {
+            self.modals
+                .show_notification(t!("apploader.keys.none", locales::lang()), None)
+                .expect("Couldn't show modal");
+            return;
+        }
+        for (_, label) in keys.iter() {
+            self.modals.add_list_item(label).expect("Couldn't build list");
+        }
+        self.modals.add_list_item(t!("apploader.cancel", locales::lang())).expect("Couldn't build list");
+        let choice =
+            self.modals.get_radiobutton(t!("apploader.keys.prompt", locales::lang())).expect("No choice");
+        if let Some((hex, _)) = keys.iter().find(|(_, label)| *label == choice) {
+            self.pddb.delete_key(KEYS_DICT, hex, None).ok();
+            self.pddb.sync().ok();
+        }
+    }
+
+    fn is_trusted(&self, signer: &[u8; 32]) -> bool {
+        self.pddb
+            .list_keys(KEYS_DICT, None)
+            .map(|keys| keys.contains(&package::key_to_hex(signer)))
+            .unwrap_or(false)
+    }
+
+    fn ask_yes_no(&self, prompt: &str) -> bool {
+        self.modals
+            .add_list(vec![t!("apploader.yes", locales::lang()), t!("apploader.no", locales::lang())])
+            .expect("Couldn't build list");
+        self.modals.get_radiobutton(prompt).expect("No choice") == t!("apploader.yes", locales::lang())
+    }
+
+    fn read_key(&self, dict: &str, key: &str) -> std::io::Result<Vec<u8>> {
+        let mut record = self.pddb.get(dict, key, None, false, false, None, None::<fn()>)?;
+        let mut data = Vec::new();
+        record.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn write_key(&self, dict: &str, key: &str, data: &[u8]) -> std::io::Result<()> {
+        // a shorter record written over a longer one would keep the longer one's tail
+        self.pddb.delete_key(dict, key, None).ok();
+        let mut record = self.pddb.get(dict, key, None, true, true, Some(data.len()), None::<fn()>)?;
+        record.write_all(data)?;
+        self.pddb.sync()
     }
 
     pub(crate) fn set_server(&mut self) {
@@ -226,7 +457,7 @@ impl AppLoader {
                     Ok(_) => None,
                     Err(e) => Some(String::from(&format!(
                         "{}{}",
-                        t!("apploader.setserver.error", locales::LANG),
+                        t!("apploader.setserver.error", locales::lang()),
                         e
                     ))),
                 }),
@@ -237,7 +468,7 @@ impl AppLoader {
         if self.server.is_none() && payload.is_some() {
             self.menu.insert_item(
                 MenuItem {
-                    name: String::from(t!("apploader.menu.reloadapplist", locales::LANG)),
+                    name: String::from(t!("apploader.menu.reloadapplist", locales::lang())),
                     action_conn: Some(self.conn),
                     action_opcode: Opcode::ReloadAppList.to_u32().unwrap(),
                     action_payload: gam::MenuPayload::Scalar([0, 0, 0, 0]),
@@ -247,7 +478,7 @@ impl AppLoader {
             );
             self.menu.insert_item(
                 MenuItem {
-                    name: String::from(t!("apploader.menu.addapp", locales::LANG)),
+                    name: String::from(t!("apploader.menu.addapp", locales::lang())),
                     action_conn: Some(self.conn),
                     action_opcode: Opcode::AddAppMenu.to_u32().unwrap(),
                     action_payload: gam::MenuPayload::Scalar([0, 0, 0, 0]),
@@ -269,7 +500,7 @@ impl AppLoader {
     pub(crate) fn reload_app_list(&mut self) {
         // without a path, the server responds with a JSON list of strings representing the list of app names
         self.modals
-            .start_progress(t!("apploader.reloadapplist.loading", locales::LANG), 0, 3, 0)
+            .start_progress(t!("apploader.reloadapplist.loading", locales::lang()), 0, 3, 0)
             .expect("Couldn't start progressbar");
 
         let old = self.possible_apps.clone();
@@ -288,7 +519,7 @@ impl AppLoader {
             Err(e) => {
                 self.modals
                     .show_notification(
-                        &format!("{}{}", t!("apploader.reloadapplist.connection_error", locales::LANG), e),
+                        &format!("{}{}", t!("apploader.reloadapplist.connection_error", locales::lang()), e),
                         None,
                     )
                     .expect("Couldn't show modal");
@@ -304,7 +535,7 @@ impl AppLoader {
             Err(e) => {
                 self.modals
                     .show_notification(
-                        &format!("{}{}", t!("apploader.reloadapplist.json_error", locales::LANG), e),
+                        &format!("{}{}", t!("apploader.reloadapplist.json_error", locales::lang()), e),
                         None,
                     )
                     .expect("Couldn't show modal");
@@ -312,7 +543,7 @@ impl AppLoader {
             }
         }
         .iter()
-        .map(|(name, menus)| (String::from(&name), *menus))
+        .map(|(name, menus)| (name.clone(), *menus))
         .collect();
 
         for (old_name, _) in old {
@@ -322,7 +553,7 @@ impl AppLoader {
         for (i, (app, _)) in self.possible_apps.iter().enumerate() {
             self.load_menu.insert_item(
                 MenuItem {
-                    name: String::from(app.to_str()),
+                    name: app.clone(),
                     action_conn: Some(self.conn),
                     action_opcode: Opcode::AddApp.to_u32().unwrap(),
                     action_payload: gam::MenuPayload::Scalar([i.try_into().unwrap(), 0, 0, 0]),
@@ -338,7 +569,7 @@ impl AppLoader {
 
     pub(crate) fn dispatch_app(&self, index: usize) {
         if index < self.apps.len() {
-            let name: String = self.apps[index];
+            let name = &self.apps[index];
             log::info!("Switching to app `{}'", name);
             self.gam
                 .switch_to_app(name.as_ref(), self.auth)
@@ -382,4 +613,13 @@ pub(crate) enum Opcode {
 
     /// Redraw the UI
     Redraw,
+
+    /// load the apps installed in the PDDB, once it's mounted
+    LoadInstalled,
+
+    /// pick an installed app and remove it from the PDDB
+    UninstallApp,
+
+    /// pick a trusted signing key and forget it
+    ForgetKey,
 }
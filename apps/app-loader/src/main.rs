@@ -1,6 +1,7 @@
 mod api;
+mod package;
 use api::*;
-use num_traits::FromPrimitive;
+use num_traits::{FromPrimitive, ToPrimitive};
 
 const SERVER_NAME_APP_LOADER: &str = "_App Loader_";
 
@@ -15,6 +16,19 @@ fn main() -> ! {
     // start off by adding hello world
     let mut app_loader = AppLoader::new(&xns, &sid);
 
+    // installed apps live in the PDDB, so they can only be loaded once it's mounted
+    std::thread::spawn({
+        let conn = xous::connect(sid).unwrap();
+        move || {
+            pddb::Pddb::new().is_mounted_blocking();
+            xous::send_message(
+                conn,
+                xous::Message::new_scalar(Opcode::LoadInstalled.to_usize().unwrap(), 0, 0, 0, 0),
+            )
+            .ok();
+        }
+    });
+
     loop {
         let msg = xous::receive_message(sid).unwrap();
         match FromPrimitive::from_usize(msg.body.id()).expect("Couldn't load message") {
@@ -38,6 +52,15 @@ fn main() -> ! {
             Opcode::Redraw => {
                 app_loader.redraw();
             }
+            Opcode::LoadInstalled => {
+                app_loader.load_installed();
+            }
+            Opcode::UninstallApp => {
+                app_loader.uninstall_app();
+            }
+            Opcode::ForgetKey => {
+                app_loader.forget_key();
+            }
         }
     }
 }
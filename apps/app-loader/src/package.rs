@@ -0,0 +1,271 @@
+//! The signed app package format.
+//!
+//! A package is a manifest and an ELF, signed together with an ed25519 key:
+//!
+//! ```text
+//! "XAPP" | format version: u32 | manifest length: u32 | ELF length: u32 | manifest | ELF | signer's public key (32) | signature (64)
+//! ```
+//!
+//! Integers are little-endian. The signature covers every byte before it, including the signer's key.
+//! The manifest is UTF-8 text with one `key: value` field per line:
+//!
+//! ```text
+//! name: Hello World
+//! version: 0.1.0
+//! submenus: 0
+//! permission: network
+//! ```
+//!
+//! `name` is the app's context name in the GAM, and `permission` may be repeated. Lines starting with `#`
+//! and fields this version doesn't know about are ignored, so that newer packages still install.
+//!
+//! This file is also built into `tools/src/bin/sign-app.rs`, so it only depends on std and the crypto
+//! crates.
+
+use std::convert::TryInto;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use sha2::{Digest, Sha512};
+
+pub const MAGIC: &[u8; 4] = b"XAPP";
+pub const FORMAT_VERSION: u32 = 1;
+const HEADER_LEN: usize = 16;
+const TRAILER_LEN: usize = 32 + 64;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PackageError {
+    /// The package is shorter than its header says it is.
+    Truncated,
+    /// It isn't a package at all.
+    BadMagic,
+    /// It's a package for a newer app loader.
+    UnsupportedVersion(u32),
+    /// The manifest is missing a required field, or has one that can't be parsed.
+    BadManifest(&'static str),
+    /// The signature doesn't match the contents, or the key isn't a valid key.
+    BadSignature,
+}
+
+impl std::fmt::Display for PackageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackageError::Truncated => write!(f, "package is truncated"),
+            PackageError::BadMagic => write!(f, "not an app package"),
+            PackageError::UnsupportedVersion(v) => write!(f, "unsupported package version {}", v),
+            PackageError::BadManifest(field) => write!(f, "bad manifest field: {}", field),
+            PackageError::BadSignature => write!(f, "signature check failed"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Manifest {
+    pub name: String,
+    pub version: String,
+    pub submenus: usize,
+    pub permissions: Vec<String>,
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Manifest, PackageError> {
+        let mut name = None;
+        let mut version = None;
+        let mut submenus = 0;
+        let mut permissions = Vec::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(PackageError::BadManifest("line without a field name")),
+            };
+            match key {
+                "name" => name = Some(value.to_string()),
+                "version" => version = Some(value.to_string()),
+                "submenus" => submenus = value.parse().map_err(|_| PackageError::BadManifest("submenus"))?,
+                "permission" => permissions.push(value.to_string()),
+                _ => log::debug!("ignoring manifest field {}", key),
+            }
+        }
+        let name = name.filter(|n| !n.is_empty()).ok_or(PackageError::BadManifest("name"))?;
+        Ok(Manifest { name, version: version.unwrap_or_default(), submenus, permissions })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text =
+            format!("name: {}\nversion: {}\nsubmenus: {}\n", self.name, self.version, self.submenus);
+        for permission in self.permissions.iter() {
+            text.push_str(&format!("permission: {}\n", permission));
+        }
+        text
+    }
+}
+
+/// A package whose signature has been checked. Whether the signer is trusted is up to the caller.
+pub struct Package<'a> {
+    pub manifest: Manifest,
+    pub elf: &'a [u8],
+    pub signer: [u8; 32],
+}
+
+impl<'a> Package<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Package<'a>, PackageError> {
+        if data.len() < HEADER_LEN {
+            return Err(PackageError::Truncated);
+        }
+        if &data[..4] != MAGIC {
+            return Err(PackageError::BadMagic);
+        }
+        let word = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+        if word(4) != FORMAT_VERSION {
+            return Err(PackageError::UnsupportedVersion(word(4)));
+        }
+        let manifest_len = word(8) as usize;
+        let elf_len = word(12) as usize;
+        let elf_start = HEADER_LEN.checked_add(manifest_len).ok_or(PackageError::Truncated)?;
+        let elf_end = elf_start.checked_add(elf_len).ok_or(PackageError::Truncated)?;
+        let signed_len = elf_end.checked_add(32).ok_or(PackageError::Truncated)?;
+        if Some(data.len()) != signed_len.checked_add(64) {
+            return Err(PackageError::Truncated);
+        }
+
+        // nothing in the package is looked at until it's known to be what the signer signed
+        let signer_start = signed_len.checked_sub(32).ok_or(PackageError::Truncated)?;
+        let signer: [u8; 32] = data[signer_start..signed_len].try_into().unwrap();
+        let signature = Signature::from_bytes(data[signed_len..].try_into().unwrap());
+        VerifyingKey::from_bytes(&signer)
+            .and_then(|key| key.verify_strict(&data[..signed_len], &signature))
+            .map_err(|_| PackageError::BadSignature)?;
+
+        let manifest = core::str::from_utf8(&data[HEADER_LEN..elf_start])
+            .map_err(|_| PackageError::BadManifest("not UTF-8"))?;
+        Ok(Package { manifest: Manifest::parse(manifest)?, elf: &data[elf_start..elf_end], signer })
+    }
+}
+
+/// Builds a package out of `manifest` and `elf`, signed with `key`.
+pub fn build(manifest: &Manifest, elf: &[u8], key: &SigningKey) -> Vec<u8> {
+    let manifest = manifest.to_text();
+    let mut data = Vec::with_capacity(HEADER_LEN + manifest.len() + elf.len() + TRAILER_LEN);
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    data.extend_from_slice(&(manifest.len() as u32).to_le_bytes());
+    data.extend_from_slice(&(elf.len() as u32).to_le_bytes());
+    data.extend_from_slice(manifest.as_bytes());
+    data.extend_from_slice(elf);
+    data.extend_from_slice(key.verifying_key().as_bytes());
+    let signature = key.sign(&data);
+    data.extend_from_slice(&signature.to_bytes());
+    data
+}
+
+/// A short form of a signing key for people to compare: the first 8 bytes of its SHA-512, in hex.
+pub fn fingerprint(key: &[u8; 32]) -> String {
+    let digest = Sha512::digest(key);
+    digest[..8].iter().map(|b| format!("{:02x}", b)).collect::<Vec<String>>().join(":")
+}
+
+/// The full key in hex, which is how trusted keys are named in the PDDB.
+pub fn key_to_hex(key: &[u8; 32]) -> String { key.iter().map(|b| format!("{:02x}", b)).collect() }
+
+pub fn key_from_hex(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest() -> Manifest {
+        Manifest {
+            name: "Hello World".to_string(),
+            version: "0.1.0".to_string(),
+            submenus: 1,
+            permissions: vec!["network".to_string(), "pddb-dict:hello".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let elf = b"\x7fELF not really an elf".to_vec();
+        let data = build(&manifest(), &elf, &key);
+
+        let package = Package::parse(&data).unwrap();
+        assert_eq!(package.manifest, manifest());
+        assert_eq!(package.elf, &elf[..]);
+        assert_eq!(&package.signer, key.verifying_key().as_bytes());
+    }
+
+    #[test]
+    fn test_tampering() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let data = build(&manifest(), b"\x7fELF not really an elf", &key);
+
+        // any change to what was signed, including the signer's key, is caught
+        for offset in [0, 5, 20, data.len() - 80, data.len() - 1] {
+            let mut bad = data.clone();
+            bad[offset] ^= 1;
+            assert!(Package::parse(&bad).is_err(), "flipped byte {} went unnoticed", offset);
+        }
+        // so is a package signed by someone else and then claiming to be from this key
+        let other = build(&manifest(), b"\x7fELF something else", &SigningKey::from_bytes(&[8u8; 32]));
+        let mut forged = other[..other.len() - TRAILER_LEN].to_vec();
+        forged.extend_from_slice(key.verifying_key().as_bytes());
+        forged.extend_from_slice(&other[other.len() - 64..]);
+        assert_eq!(Package::parse(&forged).err(), Some(PackageError::BadSignature));
+
+        assert_eq!(Package::parse(&data[..data.len() - 1]).err(), Some(PackageError::Truncated));
+        assert_eq!(Package::parse(b"\x7fELF").err(), Some(PackageError::Truncated));
+        assert_eq!(Package::parse(&[0u8; 200]).err(), Some(PackageError::BadMagic));
+    }
+
+    #[test]
+    fn test_oversized_lengths() {
+        // lengths that wrap around when added up are refused, rather than overflowing
+        for (manifest_len, elf_len) in
+            [(u32::MAX, u32::MAX), (u32::MAX, 0), (0, u32::MAX), (16, u32::MAX - 60)]
+        {
+            let mut data = MAGIC.to_vec();
+            data.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+            data.extend_from_slice(&manifest_len.to_le_bytes());
+            data.extend_from_slice(&elf_len.to_le_bytes());
+            data.extend_from_slice(&[0u8; 128]);
+            assert_eq!(Package::parse(&data).err(), Some(PackageError::Truncated));
+        }
+    }
+
+    #[test]
+    fn test_manifest() {
+        let text = "# made by hand\nname: Ball\nversion: 1.2\nicon: ball.png\npermission: audio\n";
+        let parsed = Manifest::parse(text).unwrap();
+        assert_eq!(parsed.name, "Ball");
+        assert_eq!(parsed.version, "1.2");
+        assert_eq!(parsed.submenus, 0);
+        assert_eq!(parsed.permissions, vec!["audio".to_string()]);
+        assert_eq!(Manifest::parse(&parsed.to_text()).unwrap(), parsed);
+
+        assert_eq!(Manifest::parse("version: 1").err(), Some(PackageError::BadManifest("name")));
+        assert_eq!(
+            Manifest::parse("name: x\nsubmenus: many").err(),
+            Some(PackageError::BadManifest("submenus"))
+        );
+    }
+
+    #[test]
+    fn test_key_hex() {
+        let key = *SigningKey::from_bytes(&[7u8; 32]).verifying_key().as_bytes();
+        assert_eq!(key_from_hex(&key_to_hex(&key)), Some(key));
+        assert_eq!(key_from_hex("00"), None);
+        assert_eq!(fingerprint(&key).len(), 8 * 3 - 1);
+    }
+}
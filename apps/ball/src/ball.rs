@@ -178,18 +178,18 @@ impl Ball {
         write!(
             note,
             "{}'{}'.\n\n{}",
-            t!("ballapp.notification_a", locales::LANG),
+            t!("ballapp.notification_a", locales::lang()),
             keys[0],
-            t!("ballapp.notification_b", locales::LANG),
+            t!("ballapp.notification_b", locales::lang()),
         )
         .unwrap();
         self.modals.show_notification(&note, None).unwrap();
-        self.modals.add_list_item(t!("ballapp.random", locales::LANG)).unwrap();
-        self.modals.add_list_item(t!("ballapp.tilt", locales::LANG)).unwrap();
-        let mode = self.modals.get_radiobutton(t!("ballapp.mode_prompt", locales::LANG)).unwrap();
-        if mode == t!("ballapp.random", locales::LANG) {
+        self.modals.add_list_item(t!("ballapp.random", locales::lang())).unwrap();
+        self.modals.add_list_item(t!("ballapp.tilt", locales::lang())).unwrap();
+        let mode = self.modals.get_radiobutton(t!("ballapp.mode_prompt", locales::lang())).unwrap();
+        if mode == t!("ballapp.random", locales::lang()) {
             self.mode = BallMode::Random;
-        } else if mode == t!("ballapp.tilt", locales::LANG) {
+        } else if mode == t!("ballapp.tilt", locales::lang()) {
             self.mode = BallMode::Tilt;
         } else {
             log::warn!("got an unexpected response from the radio button function: {}", mode);
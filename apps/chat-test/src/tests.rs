@@ -4,6 +4,8 @@ pub fn test_ui(chat: &Chat) {
     let pddb = pddb::Pddb::new();
     // nuke any existing test dictionary, if it exists
     pddb.delete_dict("tests.ui", None).ok();
+    pddb.delete_dict("tests.ui.attach", None).ok();
+    pddb.delete_dict("tests.ui.extras", None).ok();
     pddb.sync().ok();
 
     // re-create the test room from scratch every time
@@ -17,6 +19,9 @@ pub fn test_ui(chat: &Chat) {
         chat.post_add("bob", 1_700_000_006 + i * 4, &format!("bob sez {}", i), None).ok();
         chat.post_add("trent", 1_700_000_007 + i * 4, &format!("trent sez {}", i), None).ok();
     }
+    let reply_to = chat::ReplyTo { author: String::from("alice"), timestamp: 1_700_000_000 };
+    chat.post_reply("trent", 1_700_000_900, "hello alice, and the world", reply_to).ok();
+    chat.post_attach("bob", 1_700_000_950, "the notes", "notes.txt", b"remember the milk").ok();
     chat.post_add("alice", 1_700_001_000, "eom", None).ok();
 
     log::info!("triggering save");
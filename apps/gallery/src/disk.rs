@@ -0,0 +1,54 @@
+//! Sectors of the transient disk, read from the app that holds it in RAM, the same way the USB mass
+//! storage class reads them for the host.
+
+use std::io::{Error, ErrorKind, Result};
+
+use crate::fat::{BlockDevice, SECTOR_SIZE};
+
+/// These match the server name and `TradOp::Read` of apps/transientdisk.
+const SERVER_NAME_TRANSIENTDISK: &str = "_Transient Disk_";
+const TRANSIENTDISK_OP_READ: usize = 2;
+
+/// The transient disk fills the first sector's worth of a page lent to it.
+#[repr(C, align(4096))]
+struct Page {
+    raw: [u8; 4096],
+}
+
+pub struct TransientDisk {
+    conn: xous::CID,
+}
+
+impl TransientDisk {
+    /// None if the transient disk app isn't running.
+    pub fn new(xns: &xous_names::XousNames) -> Option<Self> {
+        match xns.request_connection(SERVER_NAME_TRANSIENTDISK) {
+            Ok(conn) => Some(Self { conn }),
+            Err(e) => {
+                log::info!("no transient disk: {:?}", e);
+                None
+            }
+        }
+    }
+}
+
+impl BlockDevice for TransientDisk {
+    fn read_sector(&self, lba: u32, sector: &mut [u8; SECTOR_SIZE]) -> Result<()> {
+        let mut page = Page { raw: [0u8; 4096] };
+        let buf = unsafe {
+            xous::MemoryRange::new(&mut page as *mut Page as usize, core::mem::size_of::<Page>()).unwrap()
+        };
+        xous::send_message(
+            self.conn,
+            xous::Message::new_lend_mut(
+                TRANSIENTDISK_OP_READ,
+                buf,
+                xous::MemoryAddress::new(lba as usize),
+                None,
+            ),
+        )
+        .map_err(|e| Error::new(ErrorKind::Other, format!("transient disk read failed: {:?}", e)))?;
+        sector.copy_from_slice(&page.raw[..SECTOR_SIZE]);
+        Ok(())
+    }
+}
@@ -0,0 +1,473 @@
+//! Just enough of FAT12 and FAT16 to list and read the files a host put on the transient disk. The host
+//! formats the disk, so it may or may not carry a partition table; FAT32 is not used at this size.
+
+use std::cell::RefCell;
+use std::io::{Error, ErrorKind, Read, Result};
+
+pub const SECTOR_SIZE: usize = 512;
+
+/// Directories are followed this deep, so a loop on a corrupt disk ends.
+const MAX_DEPTH: usize = 4;
+
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_LONG_NAME: u8 = 0x0F;
+const LAST_LONG_ENTRY: u8 = 0x40;
+const DELETED: u8 = 0xE5;
+
+/// Somewhere to read 512 byte sectors from, by logical block address.
+pub trait BlockDevice {
+    fn read_sector(&self, lba: u32, sector: &mut [u8; SECTOR_SIZE]) -> Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FatType {
+    Fat12,
+    Fat16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    /// the long file name if there is one, otherwise the 8.3 name
+    pub name: String,
+    /// the path of the directory holding the entry, "/" separated
+    pub dir: String,
+    pub size: u32,
+    pub is_dir: bool,
+    first_cluster: u32,
+}
+
+impl DirEntry {
+    pub fn path(&self) -> String {
+        if self.dir.is_empty() { self.name.clone() } else { format!("{}/{}", self.dir, self.name) }
+    }
+}
+
+pub struct Fat<D: BlockDevice> {
+    device: D,
+    fat_type: FatType,
+    sectors_per_cluster: u32,
+    fat_start: u32,
+    root_start: u32,
+    root_sectors: u32,
+    data_start: u32,
+    cluster_count: u32,
+    /// the last sector of the FAT read, as following a cluster chain reads the same one many times
+    fat_cache: RefCell<Option<(u32, [u8; SECTOR_SIZE])>>,
+}
+
+impl<D: BlockDevice> Fat<D> {
+    pub fn new(device: D) -> Result<Self> {
+        let mut sector = [0u8; SECTOR_SIZE];
+        device.read_sector(0, &mut sector)?;
+        if sector[510..512] != [0x55, 0xAA] {
+            return Err(Error::new(ErrorKind::InvalidData, "disk is not formatted"));
+        }
+        // a boot sector starts with a jump; otherwise take the first partition of the MBR
+        let volume_start = if sector[0] == 0xEB || sector[0] == 0xE9 {
+            0
+        } else {
+            let start = u32::from_le_bytes(sector[0x1C6..0x1CA].try_into().unwrap());
+            if sector[0x1C2] == 0 || start == 0 {
+                return Err(Error::new(ErrorKind::InvalidData, "no FAT volume on the disk"));
+            }
+            device.read_sector(start, &mut sector)?;
+            start
+        };
+
+        let u16_at = |offset: usize| u16::from_le_bytes([sector[offset], sector[offset + 1]]) as u32;
+        let bytes_per_sector = u16_at(11);
+        let sectors_per_cluster = sector[13] as u32;
+        let reserved = u16_at(14);
+        let fats = sector[16] as u32;
+        let root_entries = u16_at(17);
+        let total_sectors = match u16_at(19) {
+            0 => u32::from_le_bytes(sector[32..36].try_into().unwrap()),
+            total => total,
+        };
+        let fat_size = u16_at(22);
+        if bytes_per_sector != SECTOR_SIZE as u32 || sectors_per_cluster == 0 || fats == 0 || fat_size == 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "unsupported FAT volume"));
+        }
+        let fat_start = volume_start + reserved;
+        let root_start = fat_start + fats * fat_size;
+        let root_sectors = (root_entries * 32 + SECTOR_SIZE as u32 - 1) / SECTOR_SIZE as u32;
+        let data_start = root_start + root_sectors;
+        let data_sectors = total_sectors.saturating_sub(data_start - volume_start);
+        let cluster_count = data_sectors / sectors_per_cluster;
+        // the FAT type follows from the number of clusters alone
+        let fat_type = match cluster_count {
+            0..=4084 => FatType::Fat12,
+            4085..=65524 => FatType::Fat16,
+            _ => return Err(Error::new(ErrorKind::InvalidData, "FAT32 is not supported")),
+        };
+        log::info!("{:?} volume at sector {} with {} clusters", fat_type, volume_start, cluster_count);
+        Ok(Self {
+            device,
+            fat_type,
+            sectors_per_cluster,
+            fat_start,
+            root_start,
+            root_sectors,
+            data_start,
+            cluster_count,
+            fat_cache: RefCell::new(None),
+        })
+    }
+
+    /// All the files on the volume for which `wanted` is true, in directory order.
+    pub fn find_files(&self, wanted: impl Fn(&DirEntry) -> bool) -> Result<Vec<DirEntry>> {
+        let mut found = Vec::new();
+        let mut dirs = vec![(None, String::new(), 0)];
+        while let Some((dir, path, depth)) = dirs.pop() {
+            let entries = match dir {
+                None => self.root()?,
+                Some(ref dir) => self.read_dir(dir, &path)?,
+            };
+            for entry in entries {
+                if entry.is_dir {
+                    if depth < MAX_DEPTH {
+                        let path = entry.path();
+                        dirs.push((Some(entry), path, depth + 1));
+                    }
+                } else if wanted(&entry) {
+                    found.push(entry);
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    pub fn root(&self) -> Result<Vec<DirEntry>> {
+        let mut data = vec![0u8; self.root_sectors as usize * SECTOR_SIZE];
+        for (i, chunk) in data.chunks_mut(SECTOR_SIZE).enumerate() {
+            self.device.read_sector(self.root_start + i as u32, chunk.try_into().unwrap())?;
+        }
+        Ok(parse_dir(&data, ""))
+    }
+
+    pub fn read_dir(&self, dir: &DirEntry, path: &str) -> Result<Vec<DirEntry>> {
+        let mut data = Vec::new();
+        self.open(dir).read_to_end(&mut data)?;
+        Ok(parse_dir(&data, path))
+    }
+
+    /// Reads a file, or a directory to the end of its clusters.
+    pub fn open<'a>(&'a self, entry: &DirEntry) -> FatFile<'a, D> {
+        FatFile {
+            fat: self,
+            cluster: entry.first_cluster,
+            remaining: if entry.is_dir { u32::MAX } else { entry.size },
+            offset: 0,
+            sector: [0u8; SECTOR_SIZE],
+        }
+    }
+
+    fn cluster_bytes(&self) -> u32 { self.sectors_per_cluster * SECTOR_SIZE as u32 }
+
+    fn cluster_sector(&self, cluster: u32) -> u32 {
+        self.data_start + (cluster - 2) * self.sectors_per_cluster
+    }
+
+    fn is_valid(&self, cluster: u32) -> bool { cluster >= 2 && cluster < self.cluster_count + 2 }
+
+    /// The cluster after `cluster` in its chain, or None at the end.
+    fn next_cluster(&self, cluster: u32) -> Result<Option<u32>> {
+        let next = match self.fat_type {
+            FatType::Fat16 => self.fat_u16(cluster * 2)?,
+            FatType::Fat12 => {
+                // 12 bit entries, packed two to three bytes, may straddle sectors
+                let offset = cluster * 3 / 2;
+                let low = self.fat_byte(offset)? as u32;
+                let high = self.fat_byte(offset + 1)? as u32;
+                let pair = low | high << 8;
+                if cluster & 1 == 0 { pair & 0xFFF } else { pair >> 4 }
+            }
+        };
+        Ok(if self.is_valid(next) { Some(next) } else { None })
+    }
+
+    fn fat_u16(&self, offset: u32) -> Result<u32> {
+        Ok(self.fat_byte(offset)? as u32 | (self.fat_byte(offset + 1)? as u32) << 8)
+    }
+
+    fn fat_byte(&self, offset: u32) -> Result<u8> {
+        let lba = self.fat_start + offset / SECTOR_SIZE as u32;
+        let mut cache = self.fat_cache.borrow_mut();
+        match *cache {
+            Some((cached, ref sector)) if cached == lba => Ok(sector[offset as usize % SECTOR_SIZE]),
+            _ => {
+                let mut sector = [0u8; SECTOR_SIZE];
+                self.device.read_sector(lba, &mut sector)?;
+                *cache = Some((lba, sector));
+                Ok(sector[offset as usize % SECTOR_SIZE])
+            }
+        }
+    }
+}
+
+pub struct FatFile<'a, D: BlockDevice> {
+    fat: &'a Fat<D>,
+    cluster: u32,
+    remaining: u32,
+    /// the position within the cluster
+    offset: u32,
+    sector: [u8; SECTOR_SIZE],
+}
+
+impl<'a, D: BlockDevice> Read for FatFile<'a, D> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.remaining == 0 || buf.is_empty() || !self.fat.is_valid(self.cluster) {
+            return Ok(0);
+        }
+        if self.offset >= self.fat.cluster_bytes() {
+            match self.fat.next_cluster(self.cluster)? {
+                Some(next) => {
+                    self.cluster = next;
+                    self.offset = 0;
+                }
+                None => {
+                    if self.remaining != u32::MAX {
+                        log::warn!("file ends {} bytes early", self.remaining);
+                    }
+                    self.remaining = 0;
+                    return Ok(0);
+                }
+            }
+        }
+        let within = self.offset as usize % SECTOR_SIZE;
+        if within == 0 {
+            let lba = self.fat.cluster_sector(self.cluster) + self.offset / SECTOR_SIZE as u32;
+            self.fat.device.read_sector(lba, &mut self.sector)?;
+        }
+        let count = buf.len().min(SECTOR_SIZE - within).min(self.remaining as usize);
+        buf[..count].copy_from_slice(&self.sector[within..within + count]);
+        self.offset += count as u32;
+        if self.remaining != u32::MAX {
+            self.remaining -= count as u32;
+        }
+        Ok(count)
+    }
+}
+
+/// The entries of a directory, with long names put together from the entries before each.
+fn parse_dir(data: &[u8], path: &str) -> Vec<DirEntry> {
+    let mut entries = Vec::new();
+    let mut long_name: Vec<u16> = Vec::new();
+    let mut long_checksum: Option<u8> = None;
+    for raw in data.chunks_exact(32) {
+        match raw[0] {
+            0 => break,
+            DELETED => {
+                long_checksum = None;
+                continue;
+            }
+            _ => (),
+        }
+        let attributes = raw[11];
+        if attributes & ATTR_LONG_NAME == ATTR_LONG_NAME {
+            // long name entries come last part first, 13 UTF-16 units each
+            if raw[0] & LAST_LONG_ENTRY != 0 {
+                long_name.clear();
+                long_checksum = Some(raw[13]);
+            }
+            let mut part: Vec<u16> = [1..11, 14..26, 28..32]
+                .iter()
+                .flat_map(|range| raw[range.clone()].chunks(2).map(|c| u16::from_le_bytes([c[0], c[1]])))
+                .take_while(|&unit| unit != 0 && unit != 0xFFFF)
+                .collect();
+            part.extend_from_slice(&long_name);
+            long_name = part;
+            continue;
+        }
+        let long = long_checksum.take().filter(|&checksum| checksum == short_checksum(&raw[..11]));
+        if attributes & ATTR_VOLUME_ID != 0 || raw[0] == b'.' {
+            continue;
+        }
+        let name = match long {
+            Some(_) => String::from_utf16_lossy(&long_name),
+            None => short_name(raw),
+        };
+        entries.push(DirEntry {
+            name,
+            dir: path.to_string(),
+            size: u32::from_le_bytes(raw[28..32].try_into().unwrap()),
+            is_dir: attributes & ATTR_DIRECTORY != 0,
+            first_cluster: u16::from_le_bytes([raw[26], raw[27]]) as u32,
+        });
+    }
+    entries
+}
+
+/// The 8.3 name, lower case where Windows flagged it so.
+fn short_name(raw: &[u8]) -> String {
+    let case = |bytes: &[u8], lower: bool| {
+        let text = String::from_utf8_lossy(bytes).trim_end().to_string();
+        if lower { text.to_lowercase() } else { text }
+    };
+    let base = case(&raw[..8], raw[12] & 0x08 != 0);
+    let extension = case(&raw[8..11], raw[12] & 0x10 != 0);
+    if extension.is_empty() { base } else { format!("{}.{}", base, extension) }
+}
+
+/// The checksum of a short name that its long name entries carry.
+fn short_checksum(name: &[u8]) -> u8 {
+    name.iter().fold(0u8, |sum, &byte| (sum >> 1 | sum << 7).wrapping_add(byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Memory(Vec<u8>);
+
+    impl BlockDevice for Memory {
+        fn read_sector(&self, lba: u32, sector: &mut [u8; SECTOR_SIZE]) -> Result<()> {
+            let start = lba as usize * SECTOR_SIZE;
+            sector.copy_from_slice(&self.0[start..start + SECTOR_SIZE]);
+            Ok(())
+        }
+    }
+
+    const SECTORS: usize = 2880;
+    const FAT_SECTORS: usize = 9;
+    const ROOT_START: usize = 1 + 2 * FAT_SECTORS;
+    const DATA_START: usize = ROOT_START + 14;
+
+    /// A blank 1.44MB floppy, as mkfs.fat makes it.
+    fn floppy() -> Vec<u8> {
+        let mut disk = vec![0u8; SECTORS * SECTOR_SIZE];
+        disk[0..3].copy_from_slice(&[0xEB, 0x3C, 0x90]);
+        disk[11..13].copy_from_slice(&512u16.to_le_bytes());
+        disk[13] = 1;
+        disk[14..16].copy_from_slice(&1u16.to_le_bytes());
+        disk[16] = 2;
+        disk[17..19].copy_from_slice(&224u16.to_le_bytes());
+        disk[19..21].copy_from_slice(&(SECTORS as u16).to_le_bytes());
+        disk[22..24].copy_from_slice(&(FAT_SECTORS as u16).to_le_bytes());
+        disk[510..512].copy_from_slice(&[0x55, 0xAA]);
+        disk[SECTOR_SIZE..SECTOR_SIZE + 3].copy_from_slice(&[0xF0, 0xFF, 0xFF]);
+        disk
+    }
+
+    fn set_fat12(disk: &mut [u8], cluster: usize, value: u16) {
+        let offset = SECTOR_SIZE + cluster * 3 / 2;
+        if cluster & 1 == 0 {
+            disk[offset] = value as u8;
+            disk[offset + 1] = (disk[offset + 1] & 0xF0) | (value >> 8) as u8;
+        } else {
+            disk[offset] = (disk[offset] & 0x0F) | (value << 4) as u8;
+            disk[offset + 1] = (value >> 4) as u8;
+        }
+    }
+
+    fn short_entry(name: &[u8; 11], attributes: u8, cluster: u16, size: u32) -> [u8; 32] {
+        let mut entry = [0u8; 32];
+        entry[..11].copy_from_slice(name);
+        entry[11] = attributes;
+        entry[26..28].copy_from_slice(&cluster.to_le_bytes());
+        entry[28..32].copy_from_slice(&size.to_le_bytes());
+        entry
+    }
+
+    /// The long name entries for `name`, last part first, then the short entry.
+    fn long_entries(name: &str, short: [u8; 32]) -> Vec<u8> {
+        let mut units: Vec<u16> = name.encode_utf16().collect();
+        units.push(0);
+        while units.len() % 13 != 0 {
+            units.push(0xFFFF);
+        }
+        let parts: Vec<&[u16]> = units.chunks(13).collect();
+        let mut out = Vec::new();
+        for (i, part) in parts.iter().enumerate().rev() {
+            let mut entry = [0u8; 32];
+            entry[0] = (i + 1) as u8 | if i == parts.len() - 1 { LAST_LONG_ENTRY } else { 0 };
+            entry[11] = ATTR_LONG_NAME;
+            entry[13] = short_checksum(&short[..11]);
+            let bytes: Vec<u8> = part.iter().flat_map(|unit| unit.to_le_bytes()).collect();
+            entry[1..11].copy_from_slice(&bytes[0..10]);
+            entry[14..26].copy_from_slice(&bytes[10..22]);
+            entry[28..32].copy_from_slice(&bytes[22..26]);
+            out.extend_from_slice(&entry);
+        }
+        out.extend_from_slice(&short);
+        out
+    }
+
+    #[test]
+    fn test_floppy() {
+        let mut disk = floppy();
+        // a file over three clusters, out of order, so the chain crosses odd and even FAT12 entries
+        let photo: Vec<u8> = (0..1200u32).map(|i| (i * 7 % 251) as u8).collect();
+        for (cluster, next) in [(2, 5), (5, 3), (3, 0xFFF), (4, 0xFFF), (6, 0xFFF)] {
+            set_fat12(&mut disk, cluster, next);
+        }
+        for (i, cluster) in [2, 5, 3].iter().enumerate() {
+            let start = (DATA_START + cluster - 2) * SECTOR_SIZE;
+            let part = &photo[i * 512..((i + 1) * 512).min(photo.len())];
+            disk[start..start + part.len()].copy_from_slice(part);
+        }
+        let mut root = Vec::new();
+        root.extend_from_slice(&short_entry(b"NO NAME    ", ATTR_VOLUME_ID, 0, 0));
+        root.extend(long_entries("Holiday snap 01.jpg", short_entry(b"HOLIDA~1JPG", 0x20, 2, 1200)));
+        let mut deleted = short_entry(b"OLD     GIF", 0x20, 0, 10);
+        deleted[0] = DELETED;
+        root.extend_from_slice(&deleted);
+        let mut lower = short_entry(b"CAT     PNG", 0x20, 4, 3);
+        lower[12] = 0x18;
+        root.extend_from_slice(&lower);
+        root.extend_from_slice(&short_entry(b"PICS       ", ATTR_DIRECTORY, 6, 0));
+        let start = ROOT_START * SECTOR_SIZE;
+        disk[start..start + root.len()].copy_from_slice(&root);
+        // a directory holding itself, its parent, and a file
+        let mut pics = Vec::new();
+        pics.extend_from_slice(&short_entry(b".          ", ATTR_DIRECTORY, 6, 0));
+        pics.extend_from_slice(&short_entry(b"..         ", ATTR_DIRECTORY, 0, 0));
+        pics.extend_from_slice(&short_entry(b"DOG     GIF", 0x20, 4, 3));
+        let start = (DATA_START + 4) * SECTOR_SIZE;
+        disk[start..start + pics.len()].copy_from_slice(&pics);
+        disk[(DATA_START + 2) * SECTOR_SIZE..][..3].copy_from_slice(b"cat");
+
+        let fat = Fat::new(Memory(disk)).unwrap();
+        assert_eq!(fat.fat_type, FatType::Fat12);
+        let names: Vec<String> = fat.root().unwrap().iter().map(|entry| entry.name.clone()).collect();
+        assert_eq!(names, vec!["Holiday snap 01.jpg", "cat.png", "PICS"]);
+
+        let files = fat.find_files(|entry| !entry.name.ends_with(".png")).unwrap();
+        let paths: Vec<String> = files.iter().map(|entry| entry.path()).collect();
+        assert_eq!(paths, vec!["Holiday snap 01.jpg", "PICS/DOG.GIF"]);
+
+        let mut data = Vec::new();
+        fat.open(&files[0]).read_to_end(&mut data).unwrap();
+        assert_eq!(data, photo);
+        let mut data = Vec::new();
+        fat.open(&files[1]).read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"cat");
+    }
+
+    #[test]
+    fn test_partitioned() {
+        // the same volume, behind an MBR
+        let mut disk = vec![0u8; 63 * SECTOR_SIZE];
+        disk[0x1C2] = 0x01;
+        disk[0x1C6..0x1CA].copy_from_slice(&63u32.to_le_bytes());
+        disk[510..512].copy_from_slice(&[0x55, 0xAA]);
+        let mut volume = floppy();
+        volume[ROOT_START * SECTOR_SIZE..][..32].copy_from_slice(&short_entry(b"A       JPG", 0x20, 0, 0));
+        disk.extend(volume);
+        let fat = Fat::new(Memory(disk)).unwrap();
+        assert_eq!(fat.root_start, 63 + ROOT_START as u32);
+        assert_eq!(fat.root().unwrap()[0].name, "A.JPG");
+        // an empty file reads as empty
+        let mut data = Vec::new();
+        fat.open(&fat.root().unwrap()[0]).read_to_end(&mut data).unwrap();
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn test_unformatted() {
+        // the transient disk starts out filled with a counting pattern
+        let disk: Vec<u8> = (0..(SECTORS * SECTOR_SIZE / 4) as u32).flat_map(|i| i.to_le_bytes()).collect();
+        assert!(Fat::new(Memory(disk)).is_err());
+    }
+}
@@ -0,0 +1,248 @@
+//! The pictures on offer, from the PDDB or the transient disk, and turning them into bitmaps.
+
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result};
+
+use gam::{Bitmap, DecodeGif, DecodeJpeg, DecodePng, Dithering, MAX_PIXELS};
+use graphics_server::Point;
+
+use crate::disk::TransientDisk;
+use crate::fat::{DirEntry, Fat};
+
+pub const GALLERY_DICT: &str = "gallery.images";
+/// The longest PDDB key name
+const MAX_KEY_NAME: usize = 95;
+/// Animations stop after this many frames, each a bitmap up to the size of the screen.
+const MAX_FRAMES: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Pddb,
+    Disk,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Png,
+    Jpeg,
+    Gif,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let extension = name.rsplit_once('.')?.1.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(Format::Png),
+            "jpg" | "jpeg" | "jpe" => Some(Format::Jpeg),
+            "gif" => Some(Format::Gif),
+            _ => None,
+        }
+    }
+
+    pub fn from_magic(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&[137, 80, 78, 71]) {
+            Some(Format::Png)
+        } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Format::Jpeg)
+        } else if bytes.starts_with(b"GIF8") {
+            Some(Format::Gif)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Picture {
+    /// a key in the gallery dictionary
+    Pddb(String),
+    Disk(DirEntry),
+}
+
+impl Picture {
+    pub fn name(&self) -> String {
+        match self {
+            Picture::Pddb(key) => key.clone(),
+            Picture::Disk(entry) => entry.path(),
+        }
+    }
+}
+
+pub struct Frame {
+    pub bitmap: Bitmap,
+    /// how long to show the frame of an animation, 0 for a still picture
+    pub delay_ms: u32,
+}
+
+pub struct Library {
+    xns: xous_names::XousNames,
+    pddb: pddb::Pddb,
+    disk: Option<Fat<TransientDisk>>,
+    pub source: Source,
+    pub pictures: Vec<Picture>,
+}
+
+impl Library {
+    pub fn new() -> Self {
+        Self {
+            xns: xous_names::XousNames::new().unwrap(),
+            pddb: pddb::Pddb::new(),
+            disk: None,
+            source: Source::Pddb,
+            pictures: Vec::new(),
+        }
+    }
+
+    /// Lists the pictures in `source`, reading the disk afresh as the host may have changed it.
+    pub fn scan(&mut self, source: Source) -> Result<()> {
+        self.source = source;
+        self.pictures.clear();
+        match source {
+            Source::Pddb => {
+                self.pddb.is_mounted_blocking();
+                let mut keys = match self.pddb.list_keys(GALLERY_DICT, None) {
+                    Ok(keys) => keys,
+                    Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+                    Err(e) => return Err(e),
+                };
+                keys.sort();
+                self.pictures = keys.into_iter().map(Picture::Pddb).collect();
+            }
+            Source::Disk => {
+                self.disk = None;
+                let device = TransientDisk::new(&self.xns)
+                    .ok_or(Error::new(ErrorKind::NotFound, "the transient disk is not running"))?;
+                let fat = Fat::new(device)?;
+                let files = fat.find_files(|entry| Format::from_name(&entry.name).is_some())?;
+                self.pictures = files.into_iter().map(Picture::Disk).collect();
+                self.disk = Some(fat);
+            }
+        }
+        log::info!("{} pictures in {:?}", self.pictures.len(), source);
+        Ok(())
+    }
+
+    pub fn load(&self, index: usize, fit: Point, dithering: Dithering) -> Result<Vec<Frame>> {
+        match &self.pictures[index] {
+            Picture::Pddb(key) => {
+                let key = self.pddb.get(GALLERY_DICT, key, None, false, false, None, None::<fn()>)?;
+                decode(key, fit, dithering)
+            }
+            Picture::Disk(entry) => decode(self.fat()?.open(entry), fit, dithering),
+        }
+    }
+
+    /// Copies a picture from the disk into the PDDB, under its file name.
+    pub fn save(&self, index: usize) -> Result<()> {
+        let entry = match &self.pictures[index] {
+            Picture::Disk(entry) => entry,
+            Picture::Pddb(_) => return Ok(()),
+        };
+        if entry.name.len() > MAX_KEY_NAME {
+            return Err(Error::new(ErrorKind::InvalidInput, "file name too long"));
+        }
+        let mut key = self.pddb.get(
+            GALLERY_DICT,
+            &entry.name,
+            None,
+            true,
+            true,
+            Some(entry.size as usize),
+            None::<fn()>,
+        )?;
+        std::io::copy(&mut self.fat()?.open(entry), &mut key)?;
+        self.pddb.sync()
+    }
+
+    pub fn delete(&mut self, index: usize) -> Result<()> {
+        if let Picture::Pddb(key) = &self.pictures[index] {
+            self.pddb.delete_key(GALLERY_DICT, key, None)?;
+            self.pddb.sync()?;
+            self.pictures.remove(index);
+        }
+        Ok(())
+    }
+
+    fn fat(&self) -> Result<&Fat<TransientDisk>> {
+        self.disk.as_ref().ok_or(Error::new(ErrorKind::NotFound, "the transient disk is not open"))
+    }
+}
+
+/// Decodes a PNG, JPEG or GIF, telling them apart by their first bytes, into dithered bitmaps that fit.
+pub fn decode<R: Read>(reader: R, fit: Point, dithering: Dithering) -> Result<Vec<Frame>> {
+    let mut reader = BufReader::new(reader);
+    let format = Format::from_magic(reader.fill_buf()?)
+        .ok_or(Error::new(ErrorKind::InvalidData, "not a PNG, JPEG or GIF image"))?;
+    let frames = match format {
+        Format::Png => {
+            let mut png = DecodePng::new(reader)?;
+            check_size(png.width(), png.height())?;
+            vec![Frame { bitmap: Bitmap::from_png_dithered(&mut png, Some(fit), dithering), delay_ms: 0 }]
+        }
+        Format::Jpeg => {
+            let mut jpeg = DecodeJpeg::new(reader)?;
+            check_size(jpeg.width(), jpeg.height())?;
+            vec![Frame { bitmap: Bitmap::from_jpeg(&mut jpeg, Some(fit), dithering), delay_ms: 0 }]
+        }
+        Format::Gif => {
+            let mut gif = DecodeGif::new(reader)?;
+            check_size(gif.width(), gif.height())?;
+            let mut frames = Vec::new();
+            loop {
+                match gif.next_frame() {
+                    Ok(Some(_)) if frames.len() >= MAX_FRAMES => {
+                        log::warn!("animation cut short at {} frames", MAX_FRAMES);
+                        break;
+                    }
+                    Ok(Some(frame)) => frames.push(Frame {
+                        bitmap: Bitmap::from_gif_frame(&frame, Some(fit), dithering),
+                        delay_ms: frame.delay_ms,
+                    }),
+                    Ok(None) => break,
+                    // show what there is of a damaged animation
+                    Err(e) if !frames.is_empty() => {
+                        log::warn!("gif frame {} failed: {:?}", frames.len(), e);
+                        break;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            if frames.len() == 1 {
+                frames[0].delay_ms = 0;
+            }
+            frames
+        }
+    };
+    Ok(frames)
+}
+
+/// Refuses pictures whose header claims more pixels than there is memory and time to decode.
+fn check_size(width: u32, height: u32) -> Result<()> {
+    if width as u64 * height as u64 > MAX_PIXELS {
+        return Err(Error::new(ErrorKind::InvalidData, "picture too large"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Format::from_name("Holiday snap.JPG"), Some(Format::Jpeg));
+        assert_eq!(Format::from_name("cat.gif"), Some(Format::Gif));
+        assert_eq!(Format::from_name("notes.txt"), None);
+        assert_eq!(Format::from_name("png"), None);
+        assert_eq!(Format::from_magic(b"\x89PNG\r\n\x1a\n"), Some(Format::Png));
+        assert_eq!(Format::from_magic(b"\xFF\xD8\xFF\xE0"), Some(Format::Jpeg));
+        assert_eq!(Format::from_magic(b"GIF89a"), Some(Format::Gif));
+        assert_eq!(Format::from_magic(b"BM"), None);
+    }
+
+    #[test]
+    fn test_check_size() {
+        assert!(check_size(2048, 2048).is_ok());
+        assert!(check_size(2048, 2049).is_err());
+        assert!(check_size(u16::MAX as u32, u16::MAX as u32).is_err());
+    }
+}
@@ -0,0 +1,417 @@
+#![cfg_attr(target_os = "none", no_std)]
+#![cfg_attr(target_os = "none", no_main)]
+
+//! Shows the pictures saved in the PDDB, or copied onto the transient USB disk from a computer, dithered
+//! for the memory LCD. Animated GIFs play. The arrow keys step through the pictures, and any other key
+//! opens the options: where to look, how to dither, and saving or deleting pictures.
+
+mod disk;
+mod fat;
+mod library;
+
+use core::fmt::Write;
+
+use gam::Dithering;
+use graphics_server::api::GlyphStyle;
+use graphics_server::{DrawStyle, Gid, PixelColor, Point, Rectangle, TextBounds, TextView};
+use library::{Frame, Library, Source};
+use locales::t;
+use num_traits::*;
+use xous::Message;
+
+pub(crate) const SERVER_NAME_GALLERY: &str = "_Picture gallery_";
+
+/// Room under the picture for its name
+const CAPTION_HEIGHT: i16 = 24;
+
+/// Top level application events.
+#[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub(crate) enum GalleryOp {
+    /// Redraw the screen
+    Redraw = 0,
+
+    /// Keys pressed
+    Rawkeys,
+
+    /// Enter/exit app
+    FocusChange,
+
+    /// The next frame of an animation is due; the argument is the generation it was scheduled for
+    NextFrame,
+
+    /// Quit the application
+    Quit,
+}
+
+/// Opcodes of the animation timer thread
+#[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub(crate) enum TimerOp {
+    /// Sends NextFrame back after a number of milliseconds
+    Schedule,
+    Quit,
+}
+
+struct Gallery {
+    content: Gid,
+    gam: gam::Gam,
+    _gam_token: [u32; 4],
+    screensize: Point,
+    modals: modals::Modals,
+    timer: xous::CID,
+    library: Library,
+    scanned: bool,
+    index: usize,
+    dithering: Dithering,
+    frames: Vec<Frame>,
+    frame: usize,
+    /// bumped whenever the picture changes, so that timers for the last one are ignored
+    generation: usize,
+    /// why the current picture can't be shown
+    error: Option<String>,
+    visible: bool,
+}
+
+impl Gallery {
+    fn new(xns: &xous_names::XousNames, sid: xous::SID, timer: xous::CID) -> Self {
+        let gam = gam::Gam::new(&xns).expect("Can't connect to GAM");
+        let gam_token = gam
+            .register_ux(gam::UxRegistration {
+                app_name: String::from(gam::APP_NAME_GALLERY),
+                ux_type: gam::UxType::Framebuffer,
+                predictor: None,
+                listener: sid.to_array(),
+                redraw_id: GalleryOp::Redraw.to_u32().unwrap(),
+                gotinput_id: None,
+                audioframe_id: None,
+                rawkeys_id: Some(GalleryOp::Rawkeys.to_u32().unwrap()),
+                focuschange_id: Some(GalleryOp::FocusChange.to_u32().unwrap()),
+            })
+            .expect("Could not register GAM UX")
+            .unwrap();
+
+        let content = gam.request_content_canvas(gam_token).expect("Could not get content canvas");
+        let screensize = gam.get_canvas_bounds(content).expect("Could not get canvas dimensions");
+        Self {
+            gam,
+            _gam_token: gam_token,
+            content,
+            screensize,
+            modals: modals::Modals::new(&xns).expect("Can't connect to Modals"),
+            timer,
+            library: Library::new(),
+            scanned: false,
+            index: 0,
+            dithering: Dithering::Burkes,
+            frames: Vec::new(),
+            frame: 0,
+            generation: 0,
+            error: None,
+            visible: false,
+        }
+    }
+
+    fn focus(&mut self, visible: bool) {
+        self.visible = visible;
+        if visible {
+            if !self.scanned {
+                self.scanned = true;
+                self.scan(Source::Pddb);
+            } else {
+                // pick up anything new, and restart an animation
+                self.scan(self.library.source);
+            }
+        }
+    }
+
+    fn scan(&mut self, source: Source) {
+        let name = self.library.pictures.get(self.index).map(|picture| picture.name());
+        if let Err(e) = self.library.scan(source) {
+            log::warn!("couldn't list pictures in {:?}: {:?}", source, e);
+            self.modals
+                .show_notification(&format!("{}\n{}", t!("gallery.source_error", locales::lang()), e), None)
+                .ok();
+        }
+        // stay on the same picture if it is still there
+        self.index = name
+            .and_then(|name| self.library.pictures.iter().position(|picture| picture.name() == name))
+            .unwrap_or(0);
+        self.show();
+    }
+
+    /// Loads the current picture, and draws its first frame.
+    fn show(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        self.frames.clear();
+        self.frame = 0;
+        self.error = None;
+        if self.index < self.library.pictures.len() {
+            let fit = Point::new(self.screensize.x, self.screensize.y - CAPTION_HEIGHT);
+            match self.library.load(self.index, fit, self.dithering) {
+                Ok(mut frames) => {
+                    for frame in frames.iter_mut() {
+                        let (width, height) = frame.bitmap.size();
+                        frame.bitmap.translate(Point::new(
+                            (fit.x - width as i16).max(0) / 2,
+                            (fit.y - height as i16).max(0) / 2,
+                        ));
+                    }
+                    self.frames = frames;
+                }
+                Err(e) => {
+                    log::warn!("couldn't show {}: {:?}", self.library.pictures[self.index].name(), e);
+                    self.error = Some(e.to_string());
+                }
+            }
+        }
+        self.redraw();
+        self.schedule();
+    }
+
+    fn next_frame(&mut self, generation: usize) {
+        if generation != self.generation || !self.visible || self.frames.len() < 2 {
+            return;
+        }
+        self.frame = (self.frame + 1) % self.frames.len();
+        self.redraw();
+        self.schedule();
+    }
+
+    /// Asks the timer for the next frame of an animation.
+    fn schedule(&self) {
+        if self.visible && self.frames.len() > 1 {
+            xous::send_message(
+                self.timer,
+                Message::new_scalar(
+                    TimerOp::Schedule.to_usize().unwrap(),
+                    self.frames[self.frame].delay_ms as usize,
+                    self.generation,
+                    0,
+                    0,
+                ),
+            )
+            .expect("couldn't schedule the next frame");
+        }
+    }
+
+    /// Clear the entire screen.
+    fn clear_area(&self) {
+        self.gam
+            .draw_rectangle(
+                self.content,
+                Rectangle::new_with_style(
+                    Point::new(0, 0),
+                    self.screensize,
+                    DrawStyle { fill_color: Some(PixelColor::Light), stroke_color: None, stroke_width: 0 },
+                ),
+            )
+            .expect("can't clear content area");
+    }
+
+    fn redraw(&mut self) {
+        self.clear_area();
+        let count = self.library.pictures.len();
+        if let Some(frame) = self.frames.get(self.frame) {
+            self.gam.draw_bitmap(self.content, &frame.bitmap).expect("couldn't draw picture");
+        } else {
+            let mut text_view = TextView::new(
+                self.content,
+                TextBounds::GrowableFromTl(Point::new(8, 8), (self.screensize.x - 16) as u16),
+            );
+            text_view.border_width = 1;
+            text_view.draw_border = true;
+            text_view.clear_area = true;
+            text_view.rounded_border = Some(3);
+            text_view.style = GlyphStyle::Regular;
+            match (&self.error, count, self.library.source) {
+                (Some(error), _, _) => {
+                    write!(text_view.text, "{}\n{}", t!("gallery.load_error", locales::lang()), error).ok()
+                }
+                (None, 0, Source::Pddb) => {
+                    write!(text_view.text, "{}", t!("gallery.empty_pddb", locales::lang())).ok()
+                }
+                (None, 0, Source::Disk) => {
+                    write!(text_view.text, "{}", t!("gallery.empty_disk", locales::lang())).ok()
+                }
+                _ => None,
+            };
+            self.gam.post_textview(&mut text_view).expect("Could not render text view");
+        }
+
+        let mut caption = TextView::new(
+            self.content,
+            TextBounds::BoundingBox(Rectangle::new(
+                Point::new(0, self.screensize.y - CAPTION_HEIGHT),
+                self.screensize,
+            )),
+        );
+        caption.style = GlyphStyle::Small;
+        caption.clear_area = true;
+        caption.draw_border = false;
+        match self.library.pictures.get(self.index) {
+            Some(picture) => write!(caption.text, "{}/{} {}", self.index + 1, count, picture.name()).ok(),
+            None => write!(caption.text, "{}", t!("gallery.help", locales::lang())).ok(),
+        };
+        self.gam.post_textview(&mut caption).expect("Could not render caption");
+        self.gam.redraw().expect("Could not redraw screen");
+    }
+
+    fn rawkeys(&mut self, keys: [char; 4]) {
+        let count = self.library.pictures.len();
+        match keys[0] {
+            '\u{0000}' => (),
+            '←' | '↑' if count > 0 => {
+                self.index = (self.index + count - 1) % count;
+                self.show();
+            }
+            '→' | '↓' if count > 0 => {
+                self.index = (self.index + 1) % count;
+                self.show();
+            }
+            '←' | '↑' | '→' | '↓' => (),
+            _ => self.options(),
+        }
+    }
+
+    fn options(&mut self) {
+        let has_picture = self.index < self.library.pictures.len();
+        self.modals.add_list_item(t!("gallery.source_pddb", locales::lang())).unwrap();
+        self.modals.add_list_item(t!("gallery.source_disk", locales::lang())).unwrap();
+        self.modals.add_list_item(t!("gallery.dithering", locales::lang())).unwrap();
+        match self.library.source {
+            Source::Disk if has_picture => {
+                self.modals.add_list_item(t!("gallery.save", locales::lang())).unwrap();
+            }
+            Source::Pddb if has_picture => {
+                self.modals.add_list_item(t!("gallery.delete", locales::lang())).unwrap();
+            }
+            _ => (),
+        }
+        self.modals.add_list_item(t!("gallery.cancel", locales::lang())).unwrap();
+        let choice = self.modals.get_radiobutton(t!("gallery.options", locales::lang())).unwrap();
+        if choice == t!("gallery.source_pddb", locales::lang()) {
+            self.index = 0;
+            self.scan(Source::Pddb);
+        } else if choice == t!("gallery.source_disk", locales::lang()) {
+            self.index = 0;
+            self.scan(Source::Disk);
+        } else if choice == t!("gallery.dithering", locales::lang()) {
+            self.choose_dithering();
+        } else if choice == t!("gallery.save", locales::lang()) {
+            let note = match self.library.save(self.index) {
+                Ok(()) => t!("gallery.saved", locales::lang()).to_string(),
+                Err(e) => format!("{}\n{}", t!("gallery.save_error", locales::lang()), e),
+            };
+            self.modals.show_notification(&note, None).ok();
+        } else if choice == t!("gallery.delete", locales::lang()) {
+            if let Err(e) = self.library.delete(self.index) {
+                log::warn!("couldn't delete picture: {:?}", e);
+            }
+            self.index = self.index.min(self.library.pictures.len().saturating_sub(1));
+            self.show();
+        }
+    }
+
+    fn choose_dithering(&mut self) {
+        let choices = [
+            (t!("gallery.burkes", locales::lang()), Dithering::Burkes),
+            (t!("gallery.floyd_steinberg", locales::lang()), Dithering::FloydSteinberg),
+            (t!("gallery.ordered", locales::lang()), Dithering::Ordered),
+        ];
+        for (name, _) in choices.iter() {
+            self.modals.add_list_item(name).unwrap();
+        }
+        let choice = self.modals.get_radiobutton(t!("gallery.dithering_prompt", locales::lang())).unwrap();
+        if let Some((_, dithering)) = choices.iter().find(|(name, _)| *name == choice) {
+            if *dithering != self.dithering {
+                self.dithering = *dithering;
+                self.show();
+            }
+        }
+    }
+}
+
+/// Sleeps on behalf of the main loop, so that it can go on taking keys while an animation waits for
+/// its next frame.
+fn timer_thread(cid_to_main: xous::CID, sid: xous::SID) {
+    std::thread::spawn(move || {
+        let tt = ticktimer_server::Ticktimer::new().unwrap();
+        loop {
+            let msg = xous::receive_message(sid).unwrap();
+            match FromPrimitive::from_usize(msg.body.id()) {
+                Some(TimerOp::Schedule) => xous::msg_scalar_unpack!(msg, delay_ms, generation, _, _, {
+                    tt.sleep_ms(delay_ms).unwrap();
+                    xous::send_message(
+                        cid_to_main,
+                        Message::new_scalar(GalleryOp::NextFrame.to_usize().unwrap(), generation, 0, 0, 0),
+                    )
+                    .expect("couldn't send the next frame to the main loop");
+                }),
+                Some(TimerOp::Quit) => {
+                    xous::return_scalar(msg.sender, 1).expect("couldn't ack the quit message");
+                    break;
+                }
+                _ => log::error!("Got unrecognized message: {:?}", msg),
+            }
+        }
+        xous::destroy_server(sid).ok();
+    });
+}
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    let sid = xns.register_name(SERVER_NAME_GALLERY, None).expect("can't register server");
+
+    let timer_sid = xous::create_server().unwrap();
+    let timer = xous::connect(timer_sid).unwrap();
+    timer_thread(xous::connect(sid).unwrap(), timer_sid);
+
+    let mut gallery = Gallery::new(&xns, sid, timer);
+
+    loop {
+        let msg = xous::receive_message(sid).unwrap();
+        log::debug!("Got message: {:?}", msg);
+
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(GalleryOp::Redraw) => {
+                if gallery.visible {
+                    gallery.redraw();
+                }
+            }
+            Some(GalleryOp::Rawkeys) => xous::msg_scalar_unpack!(msg, k1, k2, k3, k4, {
+                let keys = [
+                    core::char::from_u32(k1 as u32).unwrap_or('\u{0000}'),
+                    core::char::from_u32(k2 as u32).unwrap_or('\u{0000}'),
+                    core::char::from_u32(k3 as u32).unwrap_or('\u{0000}'),
+                    core::char::from_u32(k4 as u32).unwrap_or('\u{0000}'),
+                ];
+                gallery.rawkeys(keys);
+            }),
+            Some(GalleryOp::FocusChange) => xous::msg_scalar_unpack!(msg, new_state_code, _, _, _, {
+                match gam::FocusState::convert_focus_change(new_state_code) {
+                    gam::FocusState::Background => gallery.focus(false),
+                    gam::FocusState::Foreground => gallery.focus(true),
+                }
+            }),
+            Some(GalleryOp::NextFrame) => xous::msg_scalar_unpack!(msg, generation, _, _, _, {
+                gallery.next_frame(generation);
+            }),
+            Some(GalleryOp::Quit) => {
+                log::info!("Quitting application");
+                break;
+            }
+            _ => log::error!("couldn't convert opcode: {:?}", msg),
+        }
+    }
+
+    xous::send_message(timer, Message::new_blocking_scalar(TimerOp::Quit.to_usize().unwrap(), 0, 0, 0, 0))
+        .expect("couldn't stop the timer thread");
+    unsafe { xous::disconnect(timer).ok() };
+    xns.unregister_server(sid).unwrap();
+    xous::destroy_server(sid).unwrap();
+    log::info!("Quitting");
+    xous::terminate_process(0)
+}
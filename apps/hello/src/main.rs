@@ -98,10 +98,10 @@ impl Hello {
         text_view.clear_area = true;
         text_view.rounded_border = Some(3);
         text_view.style = GlyphStyle::Regular;
-        write!(text_view.text, "{}", t!("helloworld.hello", locales::LANG))
+        write!(text_view.text, "{}", t!("helloworld.hello", locales::lang()))
             .expect("Could not write to text view");
         #[cfg(feature = "tts")]
-        self.tts.tts_simple(t!("helloworld.hello", locales::LANG)).unwrap();
+        self.tts.tts_simple(t!("helloworld.hello", locales::lang())).unwrap();
 
         self.gam.post_textview(&mut text_view).expect("Could not render text view");
         self.gam.redraw().expect("Could not redraw screen");
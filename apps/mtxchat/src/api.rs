@@ -21,12 +21,20 @@ pub enum MenuOp {
     Logout,
     Noop,
     Room,
+    Verify,
+    Rooms,
+    Leave,
 }
 
 #[allow(dead_code)]
+#[derive(Debug)]
 pub struct Msg {
     pub type_: String,
     pub body: Option<String>,
     pub sender: Option<String>,
     pub ts: Option<u64>,
+    /// what read receipts refer to
+    pub event_id: Option<String>,
+    /// the content of an `m.room.encrypted` event, to decrypt
+    pub encrypted: Option<ureq::serde_json::Value>,
 }
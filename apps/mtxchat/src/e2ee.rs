@@ -0,0 +1,1426 @@
+//! End-to-end encryption of rooms, as in the "End-to-End Encryption" module of the Matrix client-server
+//! API.
+//!
+//! Our device has an Olm account (see `olm.rs`) whose identity keys, and a supply of one-time keys, are
+//! uploaded to the homeserver so that other devices can start Olm sessions with it. Room messages are
+//! encrypted with Megolm (see `megolm.rs`), and each device in the room is sent the Megolm session over
+//! Olm. All of it is kept in its own PDDB basis, so the keys can only be read while the user has it
+//! unlocked.
+//!
+//! A device is trusted the first time its keys are seen, and marked verified once the user has compared
+//! emoji with it (see `sas.rs`). Keys for room history are only accepted from, and given to, our own
+//! verified devices.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+use rand_core::{CryptoRng, RngCore};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use ureq::Agent;
+use ureq::serde_json::{self, Map, Value, json};
+use url::Url;
+
+use crate::megolm::{InboundGroupSession, OutboundGroupSession};
+use crate::olm::{self, Account, Session};
+use crate::sas::{self, ShortCode, Step, Verification};
+use crate::{Msg, web};
+
+/// The PDDB basis the keys are kept in, and its dictionary
+pub const BASIS: &str = "mtxchat";
+const DICT: &str = "mtxchat.e2ee";
+
+pub const OLM: &str = "m.olm.v1.curve25519-aes-sha2";
+pub const MEGOLM: &str = "m.megolm.v1.aes-sha2";
+pub const ENCRYPTED: &str = "m.room.encrypted";
+pub const ENCRYPTION: &str = "m.room.encryption";
+const ROOM_KEY: &str = "m.room_key";
+const FORWARDED_ROOM_KEY: &str = "m.forwarded_room_key";
+const ROOM_KEY_REQUEST: &str = "m.room_key_request";
+const SIGNED_CURVE25519: &str = "signed_curve25519";
+
+/// Megolm sessions are replaced after this many messages, or this long, unless the room says otherwise
+const ROTATION_MSGS: u64 = 100;
+const ROTATION_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+/// undecryptable events kept until their keys arrive
+const MAX_PENDING: usize = 200;
+
+/// Another device, with the keys it published.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Device {
+    pub user_id: String,
+    pub device_id: String,
+    pub curve25519: String,
+    pub ed25519: String,
+    /// the user compared emoji with it
+    pub verified: bool,
+}
+
+/// Our account, and whose it is.
+#[derive(Serialize, Deserialize)]
+struct Identity {
+    user_id: String,
+    device_id: String,
+    account: Account,
+    /// whether the homeserver has our device keys
+    published: bool,
+}
+
+/// A Megolm session others encrypt with, or we do.
+#[derive(Serialize, Deserialize)]
+struct GroupSession {
+    room_id: String,
+    /// the Curve25519 and Ed25519 keys of the device that created the session
+    sender_key: String,
+    sender_ed25519: String,
+    session: InboundGroupSession,
+}
+
+/// The Megolm session we encrypt a room's messages with.
+#[derive(Serialize, Deserialize)]
+struct RoomSession {
+    session: OutboundGroupSession,
+    /// when it was made, in ms since the epoch
+    created: u64,
+    /// the devices that were sent it, as user and device ID
+    shared_with: Vec<(String, String)>,
+}
+
+/// A room event whose key hasn't arrived yet.
+#[derive(Serialize, Deserialize)]
+struct PendingEvent {
+    room_id: String,
+    sender: String,
+    ts: Option<u64>,
+    content: Value,
+}
+
+/// What the device has to do about to-device events, besides updating its keys.
+#[derive(Debug)]
+pub enum Outcome {
+    /// Send to-device events of this type, given as `{user: {device: content}}`.
+    Send(&'static str, Value),
+    /// An event in a room that could be decrypted now that its key arrived.
+    Decrypted(String, Msg),
+    /// Ask the user whether to verify with another device: transaction, user and device.
+    Ask(String, String, String),
+    /// Show the short code of a verification with a user and ask whether it matches theirs.
+    Compare(String, String, ShortCode),
+    /// A device was verified: user and device.
+    Verified(String, String),
+    /// A verification ended without verifying; the reason is for the log.
+    Cancelled(String),
+}
+
+pub struct E2ee {
+    identity: Identity,
+    /// Olm sessions by the other device's Curve25519 key, the one to encrypt with first
+    sessions: HashMap<String, Vec<Session>>,
+    /// Megolm sessions by session ID
+    inbound: HashMap<String, GroupSession>,
+    /// our Megolm session for each room
+    outbound: HashMap<String, RoomSession>,
+    /// devices by user
+    devices: HashMap<String, Vec<Device>>,
+    pending: Vec<PendingEvent>,
+    /// users whose devices have to be fetched again
+    outdated: Vec<String>,
+    /// the `m.room.encryption` content of rooms known to be encrypted
+    rooms: HashMap<String, Value>,
+    /// sessions whose keys we asked our other devices for
+    requested: Vec<String>,
+    /// keys our verified devices asked for: the device and the session
+    forward: Vec<(Device, String)>,
+    verifications: Vec<Verification>,
+    /// whether there's anything to save
+    changed: bool,
+}
+
+fn read<T: DeserializeOwned>(pddb: &pddb::Pddb, key: &str) -> Option<T> {
+    let mut entry = pddb.get(DICT, key, Some(BASIS), false, false, None, None::<fn()>).ok()?;
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data).ok()?;
+    match serde_json::from_slice(&data) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            log::warn!("couldn't parse {}:{} {:?}", DICT, key, e);
+            None
+        }
+    }
+}
+
+fn write<T: Serialize>(pddb: &pddb::Pddb, key: &str, value: &T) {
+    let data = serde_json::to_vec(value).expect("couldn't serialize");
+    // delete the key first so nothing of a longer value is left
+    pddb.delete_key(DICT, key, Some(BASIS)).ok();
+    match pddb.get(DICT, key, Some(BASIS), true, true, Some(data.len()), None::<fn()>) {
+        Ok(mut entry) => {
+            if let Err(e) = entry.write_all(&data) {
+                log::warn!("Error writing {}:{} {:?}", DICT, key, e);
+            }
+        }
+        Err(e) => log::warn!("failed to set pddb {}:{} {:?}", DICT, key, e),
+    }
+}
+
+/// The form signatures are made over: keys sorted, no whitespace.
+pub fn canonical_json(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let fields: Vec<String> = keys
+                .iter()
+                .map(|key| {
+                    format!("{}:{}", Value::String(key.to_string()), canonical_json(&map[key.as_str()]))
+                })
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(items) => {
+            format!("[{}]", items.iter().map(canonical_json).collect::<Vec<String>>().join(","))
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// Checks the signature by `user_id`'s key `ed25519:{key_id}` on a signed object.
+fn verify_signed(value: &Value, user_id: &str, key_id: &str, key: &str) -> bool {
+    let signature = match value["signatures"][user_id][format!("ed25519:{}", key_id)].as_str() {
+        Some(signature) => signature,
+        None => return false,
+    };
+    let mut unsigned = value.clone();
+    if let Some(object) = unsigned.as_object_mut() {
+        object.remove("signatures");
+        object.remove("unsigned");
+    }
+    olm::verify_signature(key, canonical_json(&unsigned).as_bytes(), signature).is_ok()
+}
+
+/// A device from its entry in a `keys/query` response, if its keys are signed by itself.
+fn device_from_keys(user_id: &str, device_id: &str, keys: &Value) -> Option<Device> {
+    if keys["user_id"].as_str() != Some(user_id) || keys["device_id"].as_str() != Some(device_id) {
+        return None;
+    }
+    let curve25519 = keys["keys"][format!("curve25519:{}", device_id)].as_str()?;
+    let ed25519 = keys["keys"][format!("ed25519:{}", device_id)].as_str()?;
+    if !verify_signed(keys, user_id, device_id, ed25519) {
+        return None;
+    }
+    Some(Device {
+        user_id: user_id.to_string(),
+        device_id: device_id.to_string(),
+        curve25519: curve25519.to_string(),
+        ed25519: ed25519.to_string(),
+        verified: false,
+    })
+}
+
+fn random_id(rng: &mut (impl RngCore + CryptoRng)) -> String {
+    let mut bytes = [0u8; 12];
+    rng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl E2ee {
+    pub fn new(rng: &mut (impl RngCore + CryptoRng), user_id: &str, device_id: &str) -> Self {
+        E2ee {
+            identity: Identity {
+                user_id: user_id.to_string(),
+                device_id: device_id.to_string(),
+                account: Account::new(rng),
+                published: false,
+            },
+            sessions: HashMap::new(),
+            inbound: HashMap::new(),
+            outbound: HashMap::new(),
+            devices: HashMap::new(),
+            pending: Vec::new(),
+            outdated: Vec::new(),
+            rooms: HashMap::new(),
+            requested: Vec::new(),
+            forward: Vec::new(),
+            verifications: Vec::new(),
+            changed: true,
+        }
+    }
+
+    /// Loads the keys from the PDDB, which must have the basis open. A new device ID, after logging in
+    /// again, is a new device with new keys.
+    pub fn load(
+        pddb: &pddb::Pddb,
+        rng: &mut (impl RngCore + CryptoRng),
+        user_id: &str,
+        device_id: &str,
+    ) -> Self {
+        match read::<Identity>(pddb, "identity") {
+            Some(identity) if identity.user_id == user_id && identity.device_id == device_id => E2ee {
+                identity,
+                sessions: read(pddb, "sessions").unwrap_or_default(),
+                inbound: read(pddb, "inbound").unwrap_or_default(),
+                outbound: read(pddb, "outbound").unwrap_or_default(),
+                devices: read(pddb, "devices").unwrap_or_default(),
+                pending: read(pddb, "pending").unwrap_or_default(),
+                changed: false,
+                ..E2ee::new(rng, user_id, device_id)
+            },
+            Some(identity) => {
+                log::warn!("keys are for {} {}, making new ones", identity.user_id, identity.device_id);
+                E2ee::new(rng, user_id, device_id)
+            }
+            None => E2ee::new(rng, user_id, device_id),
+        }
+    }
+
+    pub fn save(&mut self, pddb: &pddb::Pddb) {
+        if !self.changed {
+            return;
+        }
+        write(pddb, "identity", &self.identity);
+        write(pddb, "sessions", &self.sessions);
+        write(pddb, "inbound", &self.inbound);
+        write(pddb, "outbound", &self.outbound);
+        write(pddb, "devices", &self.devices);
+        write(pddb, "pending", &self.pending);
+        pddb.sync().ok();
+        self.changed = false;
+    }
+
+    pub fn user_id(&self) -> &str { &self.identity.user_id }
+
+    pub fn device_id(&self) -> &str { &self.identity.device_id }
+
+    pub fn ed25519_key(&self) -> String { self.identity.account.ed25519_key() }
+
+    fn signed(&self, mut value: Value) -> Value {
+        let signature = self.identity.account.sign(canonical_json(&value).as_bytes());
+        value["signatures"] = json!({
+            self.identity.user_id.as_str(): {format!("ed25519:{}", self.identity.device_id): signature}
+        });
+        value
+    }
+
+    pub fn device_keys(&self) -> Value {
+        let device_id = &self.identity.device_id;
+        self.signed(json!({
+            "user_id": self.identity.user_id,
+            "device_id": device_id,
+            "algorithms": [OLM, MEGOLM],
+            "keys": {
+                format!("curve25519:{}", device_id): self.identity.account.curve25519_key(),
+                format!("ed25519:{}", device_id): self.identity.account.ed25519_key(),
+            },
+        }))
+    }
+
+    /// The body of a `keys/upload` request, if there is something to upload. `count` is the number of our
+    /// one-time keys the homeserver has left, when a sync said.
+    pub fn keys_to_upload(
+        &mut self,
+        rng: &mut (impl RngCore + CryptoRng),
+        count: Option<u64>,
+    ) -> Option<Value> {
+        let wanted = olm::MAX_ONE_TIME_KEYS / 2;
+        let unpublished = self.identity.account.unpublished_one_time_keys().len();
+        let on_server = match count {
+            Some(count) => count as usize,
+            None if self.identity.published => wanted,
+            None => 0,
+        };
+        if on_server + unpublished < wanted {
+            self.identity.account.generate_one_time_keys(rng, wanted - on_server - unpublished);
+            self.changed = true;
+        }
+        let keys = self.identity.account.unpublished_one_time_keys();
+        if self.identity.published && keys.is_empty() {
+            return None;
+        }
+        let mut one_time_keys = Map::new();
+        for (key_id, key) in keys {
+            one_time_keys
+                .insert(format!("{}:{}", SIGNED_CURVE25519, key_id), self.signed(json!({ "key": key })));
+        }
+        let mut body = json!({ "one_time_keys": one_time_keys });
+        if !self.identity.published {
+            body["device_keys"] = self.device_keys();
+        }
+        Some(body)
+    }
+
+    /// The homeserver took the keys from `keys_to_upload`.
+    pub fn keys_uploaded(&mut self) {
+        self.identity.account.mark_keys_as_published();
+        self.identity.published = true;
+        self.changed = true;
+    }
+
+    /// Those of `users` whose devices have to be fetched.
+    pub fn users_to_query(&self, users: &[String]) -> Vec<String> {
+        users
+            .iter()
+            .filter(|user| !self.devices.contains_key(*user) || self.outdated.contains(user))
+            .cloned()
+            .collect()
+    }
+
+    /// Users whose devices have to be fetched again because they changed, or a verification involves them.
+    pub fn outdated_users(&self) -> Vec<String> { self.outdated.clone() }
+
+    /// The `device_lists` of a sync.
+    pub fn device_lists_changed(&mut self, changed: &[String], left: &[String]) {
+        for user in changed {
+            if self.devices.contains_key(user) && !self.outdated.contains(user) {
+                self.outdated.push(user.to_string());
+            }
+        }
+        for user in left {
+            if self.devices.remove(user).is_some() {
+                self.changed = true;
+            }
+        }
+    }
+
+    /// Takes in a `keys/query` response. Devices keep the keys they were first seen with: a device that
+    /// shows up with other keys is ignored.
+    pub fn add_devices(&mut self, response: &Value) {
+        let users = match response["device_keys"].as_object() {
+            Some(users) => users,
+            None => return,
+        };
+        for (user_id, devices) in users {
+            let known = self.devices.remove(user_id).unwrap_or_default();
+            let mut current = Vec::new();
+            for (device_id, keys) in devices.as_object().into_iter().flatten() {
+                if *user_id == self.identity.user_id && *device_id == self.identity.device_id {
+                    continue;
+                }
+                let device = match device_from_keys(user_id, device_id, keys) {
+                    Some(device) => device,
+                    None => {
+                        log::warn!("ignoring device {} of {}: bad keys or signature", device_id, user_id);
+                        continue;
+                    }
+                };
+                match known.iter().find(|old| old.device_id == device.device_id) {
+                    Some(old) if old.curve25519 != device.curve25519 || old.ed25519 != device.ed25519 => {
+                        log::warn!(
+                            "device {} of {} changed its keys, keeping the old ones",
+                            device_id,
+                            user_id
+                        );
+                        current.push(old.clone());
+                    }
+                    Some(old) => current.push(old.clone()),
+                    None => current.push(device),
+                }
+            }
+            self.devices.insert(user_id.to_string(), current);
+            self.outdated.retain(|user| user != user_id);
+        }
+        self.changed = true;
+    }
+
+    pub fn devices(&self, user_id: &str) -> Vec<Device> {
+        self.devices.get(user_id).cloned().unwrap_or_default()
+    }
+
+    fn device_by_key(&self, curve25519: &str) -> Option<&Device> {
+        self.devices.values().flatten().find(|device| device.curve25519 == curve25519)
+    }
+
+    /// The body of a `keys/claim` request for the `devices` we have no Olm session with, if any.
+    pub fn sessions_to_claim(&self, devices: &[Device]) -> Option<Value> {
+        let mut users = Map::new();
+        for device in devices.iter().filter(|device| !self.sessions.contains_key(&device.curve25519)) {
+            let user = users.entry(device.user_id.clone()).or_insert_with(|| json!({}));
+            user[device.device_id.as_str()] = json!(SIGNED_CURVE25519);
+        }
+        if users.is_empty() { None } else { Some(json!({ "one_time_keys": users })) }
+    }
+
+    /// Starts Olm sessions with the one-time keys of a `keys/claim` response.
+    pub fn add_claimed(&mut self, rng: &mut (impl RngCore + CryptoRng), response: &Value) {
+        for (user_id, devices) in response["one_time_keys"].as_object().into_iter().flatten() {
+            for (device_id, keys) in devices.as_object().into_iter().flatten() {
+                let device = match self
+                    .devices
+                    .get(user_id)
+                    .and_then(|devices| devices.iter().find(|device| device.device_id == *device_id))
+                {
+                    Some(device) => device.clone(),
+                    None => continue,
+                };
+                for (_key_id, key) in keys.as_object().into_iter().flatten() {
+                    if !verify_signed(key, user_id, device_id, &device.ed25519) {
+                        log::warn!("one-time key of {} {} isn't signed by it", user_id, device_id);
+                        continue;
+                    }
+                    let one_time_key = key["key"].as_str().and_then(|key| olm::decode_key(key).ok());
+                    if let (Some(one_time_key), Ok(identity_key)) =
+                        (one_time_key, olm::decode_key(&device.curve25519))
+                    {
+                        let session =
+                            self.identity.account.create_outbound_session(rng, &identity_key, &one_time_key);
+                        self.sessions.entry(device.curve25519.clone()).or_default().insert(0, session);
+                        self.changed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// The content of an `m.room.encrypted` to-device event carrying `event_type` and `content` to
+    /// `device`, if there's an Olm session with it.
+    fn encrypt_for(
+        &mut self,
+        rng: &mut (impl RngCore + CryptoRng),
+        device: &Device,
+        event_type: &str,
+        content: Value,
+    ) -> Option<Value> {
+        let payload = json!({
+            "type": event_type,
+            "content": content,
+            "sender": self.identity.user_id,
+            "sender_device": self.identity.device_id,
+            "keys": {"ed25519": self.identity.account.ed25519_key()},
+            "recipient": device.user_id,
+            "recipient_keys": {"ed25519": device.ed25519},
+        });
+        let session = self.sessions.get_mut(&device.curve25519)?.first_mut()?;
+        let (message_type, body) = session.encrypt(rng, payload.to_string().as_bytes());
+        self.changed = true;
+        Some(json!({
+            "algorithm": OLM,
+            "sender_key": self.identity.account.curve25519_key(),
+            "ciphertext": {device.curve25519.as_str(): {"type": message_type, "body": olm::encode(&body)}},
+        }))
+    }
+
+    /// Encrypts for each of `devices` that we have an Olm session with, as the `messages` of a
+    /// `sendToDevice` request, and returns the devices left out.
+    fn encrypt_for_devices(
+        &mut self,
+        rng: &mut (impl RngCore + CryptoRng),
+        devices: &[Device],
+        event_type: &str,
+        content: &Value,
+    ) -> (Value, Vec<Device>) {
+        let mut messages = json!({});
+        let mut left_out = Vec::new();
+        for device in devices {
+            match self.encrypt_for(rng, device, event_type, content.clone()) {
+                Some(encrypted) => messages[device.user_id.as_str()][device.device_id.as_str()] = encrypted,
+                None => left_out.push(device.clone()),
+            }
+        }
+        (messages, left_out)
+    }
+
+    /// Marks a room as encrypted, with the content of its `m.room.encryption` state event.
+    pub fn set_room_encryption(&mut self, room_id: &str, encryption: Value) {
+        self.rooms.insert(room_id.to_string(), encryption);
+    }
+
+    /// Whether our session for `room_id` can't be used any more: it's too old, has encrypted too many
+    /// messages, or was shared with someone no longer in the room.
+    fn needs_rotation(&self, room_id: &str, members: &[String], now: u64) -> bool {
+        let current = match self.outbound.get(room_id) {
+            Some(current) => current,
+            None => return true,
+        };
+        let encryption = self.rooms.get(room_id);
+        let max_msgs = encryption.and_then(|e| e["rotation_period_msgs"].as_u64()).unwrap_or(ROTATION_MSGS);
+        let max_ms = encryption.and_then(|e| e["rotation_period_ms"].as_u64()).unwrap_or(ROTATION_MS);
+        current.session.message_index() as u64 >= max_msgs
+            || now.saturating_sub(current.created) >= max_ms
+            || current.shared_with.iter().any(|(user, _)| !members.contains(user))
+    }
+
+    /// Gets the session for `room_id` ready to encrypt with, and returns the devices of `members` that
+    /// haven't been sent it yet. Those have to be sent it with `share_room_key` first.
+    pub fn devices_without_room_key(
+        &mut self,
+        rng: &mut (impl RngCore + CryptoRng),
+        room_id: &str,
+        members: &[String],
+        now: u64,
+    ) -> Vec<Device> {
+        if self.needs_rotation(room_id, members, now) {
+            let session = OutboundGroupSession::new(rng);
+            let ours = GroupSession {
+                room_id: room_id.to_string(),
+                sender_key: self.identity.account.curve25519_key(),
+                sender_ed25519: self.identity.account.ed25519_key(),
+                session: InboundGroupSession::new(&session.session_key()).expect("our own session key"),
+            };
+            self.inbound.insert(session.session_id(), ours);
+            self.outbound
+                .insert(room_id.to_string(), RoomSession { session, created: now, shared_with: Vec::new() });
+            self.changed = true;
+        }
+        let shared_with = &self.outbound[room_id].shared_with;
+        members
+            .iter()
+            .flat_map(|user| self.devices.get(user).into_iter().flatten())
+            .filter(|device| !shared_with.contains(&(device.user_id.clone(), device.device_id.clone())))
+            .cloned()
+            .collect()
+    }
+
+    /// The `messages` of a `sendToDevice` request sending the session for `room_id` to `devices`, and the
+    /// devices it was encrypted for. Call `room_key_shared` with them once it's sent.
+    pub fn share_room_key(
+        &mut self,
+        rng: &mut (impl RngCore + CryptoRng),
+        room_id: &str,
+        devices: &[Device],
+    ) -> (Value, Vec<Device>) {
+        let session = &self.outbound[room_id].session;
+        let content = json!({
+            "algorithm": MEGOLM,
+            "room_id": room_id,
+            "session_id": session.session_id(),
+            "session_key": session.session_key(),
+        });
+        let (messages, left_out) = self.encrypt_for_devices(rng, devices, ROOM_KEY, &content);
+        for device in left_out.iter() {
+            log::warn!(
+                "no Olm session with {} {}, it won't be able to decrypt",
+                device.user_id,
+                device.device_id
+            );
+        }
+        (messages, devices.iter().filter(|device| !left_out.contains(device)).cloned().collect())
+    }
+
+    pub fn room_key_shared(&mut self, room_id: &str, devices: &[Device]) {
+        if let Some(current) = self.outbound.get_mut(room_id) {
+            current.shared_with.extend(devices.iter().map(|d| (d.user_id.clone(), d.device_id.clone())));
+            self.changed = true;
+        }
+    }
+
+    /// The content of an `m.room.encrypted` event for `room_id`, whose session must have been shared.
+    pub fn encrypt(&mut self, room_id: &str, event_type: &str, content: Value) -> Value {
+        let payload = json!({"type": event_type, "content": content, "room_id": room_id});
+        let current = self.outbound.get_mut(room_id).expect("no session for the room");
+        let ciphertext = current.session.encrypt(payload.to_string().as_bytes());
+        self.changed = true;
+        json!({
+            "algorithm": MEGOLM,
+            "sender_key": self.identity.account.curve25519_key(),
+            "ciphertext": ciphertext,
+            "session_id": current.session.session_id(),
+            "device_id": self.identity.device_id,
+        })
+    }
+
+    /// The type and content of an `m.room.encrypted` event in `room_id`, if we have its key.
+    pub fn decrypt(&mut self, room_id: &str, content: &Value) -> Option<(String, Value)> {
+        if content["algorithm"].as_str() != Some(MEGOLM) {
+            return None;
+        }
+        let group = self.inbound.get_mut(content["session_id"].as_str()?)?;
+        if group.room_id != room_id {
+            log::warn!("a session of {} was used in {}", group.room_id, room_id);
+            return None;
+        }
+        let (plaintext, _index) = match group.session.decrypt(content["ciphertext"].as_str()?) {
+            Ok(decrypted) => decrypted,
+            Err(e) => {
+                log::warn!("couldn't decrypt: {:?}", e);
+                return None;
+            }
+        };
+        self.changed = true;
+        let payload: Value = serde_json::from_slice(&plaintext).ok()?;
+        if payload["room_id"].as_str() != Some(room_id) {
+            return None;
+        }
+        Some((payload["type"].as_str()?.to_string(), payload["content"].clone()))
+    }
+
+    /// Keeps an event `decrypt` couldn't decrypt, to decrypt once its key arrives. Asks our other devices
+    /// for the key, unless they were asked already.
+    pub fn keep_for_later(
+        &mut self,
+        rng: &mut (impl RngCore + CryptoRng),
+        room_id: &str,
+        sender: &str,
+        ts: Option<u64>,
+        content: &Value,
+    ) -> Option<Outcome> {
+        if self.pending.len() >= MAX_PENDING {
+            self.pending.remove(0);
+        }
+        self.pending.push(PendingEvent {
+            room_id: room_id.to_string(),
+            sender: sender.to_string(),
+            ts,
+            content: content.clone(),
+        });
+        self.changed = true;
+        let session_id = content["session_id"].as_str()?;
+        if self.requested.iter().any(|requested| requested == session_id) {
+            return None;
+        }
+        self.requested.push(session_id.to_string());
+        let request = json!({
+            "action": "request",
+            "body": {
+                "algorithm": MEGOLM,
+                "room_id": room_id,
+                "sender_key": content["sender_key"],
+                "session_id": session_id,
+            },
+            "request_id": random_id(rng),
+            "requesting_device_id": self.identity.device_id,
+        });
+        Some(Outcome::Send(ROOM_KEY_REQUEST, json!({ self.identity.user_id.as_str(): {"*": request} })))
+    }
+
+    /// Decrypts the events kept for `session_id`.
+    fn retry_pending(&mut self, session_id: &str) -> Vec<Outcome> {
+        let (ready, rest): (Vec<_>, Vec<_>) = self
+            .pending
+            .drain(..)
+            .partition(|event| event.content["session_id"].as_str() == Some(session_id));
+        self.pending = rest;
+        let mut outcomes = Vec::new();
+        for event in ready {
+            match self.decrypt(&event.room_id, &event.content) {
+                Some((event_type, content)) => outcomes.push(Outcome::Decrypted(
+                    event.room_id,
+                    Msg {
+                        type_: event_type,
+                        body: content["body"].as_str().map(String::from),
+                        sender: Some(event.sender),
+                        ts: event.ts,
+                        event_id: None,
+                        encrypted: None,
+                    },
+                )),
+                None => self.pending.push(event),
+            }
+        }
+        outcomes
+    }
+
+    fn add_group_session(&mut self, group: GroupSession) -> Vec<Outcome> {
+        let session_id = group.session.session_id();
+        match self.inbound.get_mut(&session_id) {
+            Some(known) if known.room_id == group.room_id && known.sender_key == group.sender_key => {
+                known.session.merge(group.session)
+            }
+            Some(_) => {
+                log::warn!("ignoring a key for session {} from another room or sender", session_id);
+                return Vec::new();
+            }
+            None => {
+                self.inbound.insert(session_id.clone(), group);
+            }
+        }
+        self.requested.retain(|requested| *requested != session_id);
+        self.changed = true;
+        self.retry_pending(&session_id)
+    }
+
+    /// Decrypts the content of an Olm-encrypted to-device event from `sender`, and returns the sender's
+    /// Curve25519 key and the payload.
+    fn decrypt_olm(&mut self, sender: &str, content: &Value) -> Option<(String, Value)> {
+        if content["algorithm"].as_str() != Some(OLM) {
+            return None;
+        }
+        let sender_key = content["sender_key"].as_str()?;
+        let message = &content["ciphertext"][self.identity.account.curve25519_key()];
+        let message_type = message["type"].as_u64()? as u8;
+        let body = olm::decode(message["body"].as_str()?).ok()?;
+        let mut plaintext = None;
+        if let Some(sessions) = self.sessions.get_mut(sender_key) {
+            for i in 0..sessions.len() {
+                if let Ok(decrypted) = sessions[i].decrypt(message_type, &body) {
+                    let session = sessions.remove(i);
+                    sessions.insert(0, session);
+                    plaintext = Some(decrypted);
+                    break;
+                }
+            }
+        }
+        if plaintext.is_none() && message_type == 0 {
+            match self.identity.account.create_inbound_session(&olm::decode_key(sender_key).ok()?, &body) {
+                Ok((session, decrypted)) => {
+                    self.sessions.entry(sender_key.to_string()).or_default().insert(0, session);
+                    plaintext = Some(decrypted);
+                }
+                Err(e) => log::warn!("couldn't start a session with {}: {:?}", sender, e),
+            }
+        }
+        self.changed = true;
+        let payload: Value = serde_json::from_slice(&plaintext?).ok()?;
+        // The payload says who it's from and for, so that it can't be passed off as someone else's or
+        // replayed to another device.
+        let ours = &self.identity;
+        if payload["sender"].as_str() != Some(sender)
+            || payload["recipient"].as_str() != Some(ours.user_id.as_str())
+            || payload["recipient_keys"]["ed25519"].as_str() != Some(ours.account.ed25519_key().as_str())
+        {
+            log::warn!("dropping a message from {} that isn't for us", sender);
+            return None;
+        }
+        if let Some(device) = self.device_by_key(sender_key) {
+            if payload["keys"]["ed25519"].as_str() != Some(device.ed25519.as_str()) {
+                log::warn!("dropping a message from {} signed by another device", sender);
+                return None;
+            }
+        }
+        Some((sender_key.to_string(), payload))
+    }
+
+    fn receive_encrypted(&mut self, sender: &str, content: &Value) -> Vec<Outcome> {
+        let (sender_key, payload) = match self.decrypt_olm(sender, content) {
+            Some(decrypted) => decrypted,
+            None => return Vec::new(),
+        };
+        let content = &payload["content"];
+        if content["algorithm"].as_str() != Some(MEGOLM) {
+            return Vec::new();
+        }
+        let (room_id, session_key) = match (content["room_id"].as_str(), content["session_key"].as_str()) {
+            (Some(room_id), Some(session_key)) => (room_id.to_string(), session_key),
+            _ => return Vec::new(),
+        };
+        match payload["type"].as_str() {
+            Some(ROOM_KEY) => match InboundGroupSession::new(session_key) {
+                Ok(session) if Some(session.session_id().as_str()) == content["session_id"].as_str() => self
+                    .add_group_session(GroupSession {
+                        room_id,
+                        sender_key,
+                        sender_ed25519: payload["keys"]["ed25519"].as_str().unwrap_or("").to_string(),
+                        session,
+                    }),
+                _ => {
+                    log::warn!("bad room key from {}", sender);
+                    Vec::new()
+                }
+            },
+            Some(FORWARDED_ROOM_KEY) => {
+                let from_us = sender == self.identity.user_id
+                    && self.device_by_key(&sender_key).map(|device| device.verified).unwrap_or(false);
+                if !from_us {
+                    log::warn!("ignoring a forwarded key from {}, not one of our verified devices", sender);
+                    return Vec::new();
+                }
+                match InboundGroupSession::import(session_key) {
+                    Ok(session) if Some(session.session_id().as_str()) == content["session_id"].as_str() => {
+                        self.add_group_session(GroupSession {
+                            room_id,
+                            sender_key: content["sender_key"].as_str().unwrap_or("").to_string(),
+                            sender_ed25519: content["sender_claimed_ed25519_key"]
+                                .as_str()
+                                .unwrap_or("")
+                                .to_string(),
+                            session,
+                        })
+                    }
+                    _ => {
+                        log::warn!("bad forwarded key from {}", sender);
+                        Vec::new()
+                    }
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn receive_key_request(&mut self, sender: &str, content: &Value) {
+        if sender != self.identity.user_id || content["action"].as_str() != Some("request") {
+            return;
+        }
+        let (device_id, session_id) =
+            match (content["requesting_device_id"].as_str(), content["body"]["session_id"].as_str()) {
+                (Some(device_id), Some(session_id)) => (device_id, session_id),
+                _ => return,
+            };
+        if device_id == self.identity.device_id || !self.inbound.contains_key(session_id) {
+            return;
+        }
+        match self.devices(sender).into_iter().find(|device| device.device_id == device_id) {
+            Some(device) if device.verified => self.forward.push((device, session_id.to_string())),
+            _ => log::info!("not sharing keys with {}, it isn't verified", device_id),
+        }
+    }
+
+    /// The devices that asked for keys and need Olm sessions to be sent them.
+    pub fn devices_to_forward_to(&self) -> Vec<Device> {
+        self.forward.iter().map(|(device, _)| device.clone()).collect()
+    }
+
+    /// The `m.forwarded_room_key` events answering the requests of our verified devices, as the
+    /// `messages` of a `sendToDevice` request.
+    pub fn forward_keys(&mut self, rng: &mut (impl RngCore + CryptoRng)) -> Option<Value> {
+        let mut messages = json!({});
+        for (device, session_id) in std::mem::take(&mut self.forward) {
+            let group = &self.inbound[&session_id];
+            let content = json!({
+                "algorithm": MEGOLM,
+                "room_id": group.room_id,
+                "sender_key": group.sender_key,
+                "sender_claimed_ed25519_key": group.sender_ed25519,
+                "session_id": session_id,
+                "session_key": group.session.export_at(group.session.first_known_index()),
+                "forwarding_curve25519_key_chain": [],
+            });
+            let (encrypted, _) =
+                self.encrypt_for_devices(rng, std::slice::from_ref(&device), FORWARDED_ROOM_KEY, &content);
+            if let Some(message) = encrypted[device.user_id.as_str()].get(&device.device_id) {
+                messages[device.user_id.as_str()][device.device_id.as_str()] = message.clone();
+            }
+        }
+        if messages.as_object().map(|m| m.is_empty()).unwrap_or(true) { None } else { Some(messages) }
+    }
+
+    /// Handles the `to_device` events of a sync.
+    pub fn receive_to_device(
+        &mut self,
+        rng: &mut (impl RngCore + CryptoRng),
+        events: &[Value],
+    ) -> Vec<Outcome> {
+        let mut outcomes = Vec::new();
+        for event in events {
+            let (sender, event_type) = match (event["sender"].as_str(), event["type"].as_str()) {
+                (Some(sender), Some(event_type)) => (sender, event_type),
+                _ => continue,
+            };
+            let content = &event["content"];
+            match event_type {
+                ENCRYPTED => outcomes.extend(self.receive_encrypted(sender, content)),
+                ROOM_KEY_REQUEST => self.receive_key_request(sender, content),
+                _ if event_type.starts_with("m.key.verification.") => {
+                    outcomes.extend(self.receive_verification(rng, sender, event_type, content))
+                }
+                _ => log::debug!("ignoring to-device {}", event_type),
+            }
+        }
+        outcomes
+    }
+
+    /// Starts verifying with `their_user`'s devices, or our own other devices. Returns the request to send.
+    pub fn request_verification(
+        &mut self,
+        rng: &mut (impl RngCore + CryptoRng),
+        their_user: &str,
+        now: u64,
+    ) -> Outcome {
+        let transaction_id = random_id(rng);
+        let ours = (self.identity.user_id.as_str(), self.identity.device_id.as_str());
+        let ed25519 = self.identity.account.ed25519_key();
+        let (verification, request) =
+            Verification::request(rng, &transaction_id, (ours.0, ours.1, &ed25519), their_user, now);
+        self.verifications.push(verification);
+        if !self.outdated.iter().any(|user| user == their_user) {
+            self.outdated.push(their_user.to_string());
+        }
+        Outcome::Send(sas::REQUEST, json!({ their_user: {"*": request} }))
+    }
+
+    fn receive_verification(
+        &mut self,
+        rng: &mut (impl RngCore + CryptoRng),
+        sender: &str,
+        event_type: &str,
+        content: &Value,
+    ) -> Vec<Outcome> {
+        let from_device = content["from_device"].as_str();
+        if sender == self.identity.user_id && from_device == Some(self.identity.device_id.as_str()) {
+            // our own request, sent to all our devices
+            return Vec::new();
+        }
+        let transaction_id = match content["transaction_id"].as_str() {
+            Some(transaction_id) => transaction_id,
+            None => return Vec::new(),
+        };
+        let index = self
+            .verifications
+            .iter()
+            .position(|v| v.transaction_id == transaction_id && v.their_user == sender);
+        match index {
+            Some(index) => {
+                self.fill_in_key(index);
+                let steps = self.verifications[index].receive(event_type, content);
+                self.outcomes(index, steps)
+            }
+            None => {
+                let ed25519 = self.identity.account.ed25519_key();
+                let ours =
+                    (self.identity.user_id.as_str(), self.identity.device_id.as_str(), ed25519.as_str());
+                match Verification::incoming(rng, ours, sender, event_type, content) {
+                    Some((verification, steps)) => {
+                        self.verifications.push(verification);
+                        if !self.outdated.iter().any(|user| user == sender) {
+                            self.outdated.push(sender.to_string());
+                        }
+                        self.outcomes(self.verifications.len() - 1, steps)
+                    }
+                    None => Vec::new(),
+                }
+            }
+        }
+    }
+
+    /// Gives a verification the key of the device it is with, once both are known. Their MAC has to cover
+    /// that key.
+    fn fill_in_key(&mut self, index: usize) {
+        let verification = &self.verifications[index];
+        if verification.their_key.is_some() {
+            return;
+        }
+        let key = verification.their_device.as_ref().and_then(|device_id| {
+            self.devices(&verification.their_user)
+                .into_iter()
+                .find(|device| device.device_id == *device_id)
+                .map(|device| device.ed25519)
+        });
+        self.verifications[index].their_key = key;
+    }
+
+    fn outcomes(&mut self, index: usize, steps: Vec<Step>) -> Vec<Outcome> {
+        let verification = &self.verifications[index];
+        let transaction_id = verification.transaction_id.clone();
+        let user = verification.their_user.clone();
+        let device = verification.their_device.clone().unwrap_or_else(|| "*".to_string());
+        let mut outcomes = Vec::new();
+        for step in steps {
+            outcomes.push(match step {
+                Step::Send(event_type, content) => {
+                    Outcome::Send(event_type, json!({ user.as_str(): { device.as_str(): content } }))
+                }
+                Step::Ask => Outcome::Ask(transaction_id.clone(), user.clone(), device.clone()),
+                Step::Compare(code) => Outcome::Compare(transaction_id.clone(), user.clone(), code),
+                Step::Verified => {
+                    if let Some(known) = self
+                        .devices
+                        .get_mut(&user)
+                        .and_then(|devices| devices.iter_mut().find(|known| known.device_id == device))
+                    {
+                        known.verified = true;
+                        self.changed = true;
+                    }
+                    Outcome::Verified(user.clone(), device.clone())
+                }
+                Step::Cancelled(reason) => Outcome::Cancelled(reason),
+            });
+        }
+        self.verifications.retain(|verification| !verification.is_finished());
+        outcomes
+    }
+
+    /// The user's answer to `Outcome::Ask`.
+    pub fn answer_verification(&mut self, transaction_id: &str, accept: bool) -> Vec<Outcome> {
+        match self.verifications.iter().position(|v| v.transaction_id == transaction_id) {
+            Some(index) => {
+                let verification = &mut self.verifications[index];
+                let steps = if accept { verification.accept() } else { verification.decline() };
+                self.outcomes(index, steps)
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// The user's answer to `Outcome::Compare`.
+    pub fn confirm_verification(&mut self, transaction_id: &str, matches: bool) -> Vec<Outcome> {
+        match self.verifications.iter().position(|v| v.transaction_id == transaction_id) {
+            Some(index) => {
+                self.fill_in_key(index);
+                let steps = self.verifications[index].confirm(matches);
+                self.outcomes(index, steps)
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+/// The homeserver, for the requests encryption makes.
+pub struct Homeserver<'a> {
+    pub url: Url,
+    pub token: &'a str,
+    pub agent: &'a mut Agent,
+}
+
+impl<'a> Homeserver<'a> {
+    fn post(&mut self, path: &str, body: &Value) -> Option<Value> {
+        web::post_json_auth(&mut self.url.clone(), path, body, self.token, self.agent)
+    }
+
+    /// Sends to-device events; `messages` is `{user: {device: content}}`.
+    pub fn send_to_device(
+        &mut self,
+        rng: &mut (impl RngCore + CryptoRng),
+        event_type: &str,
+        messages: &Value,
+    ) -> bool {
+        web::send_to_device(
+            &mut self.url.clone(),
+            event_type,
+            &random_id(rng),
+            messages,
+            self.token,
+            self.agent,
+        )
+    }
+}
+
+/// Uploads our device keys if the homeserver doesn't have them, and one-time keys when it's running low.
+/// `count` is the number of one-time keys it has, if a sync said.
+pub fn upload_keys(
+    e2ee: &mut E2ee,
+    rng: &mut (impl RngCore + CryptoRng),
+    hs: &mut Homeserver,
+    count: Option<u64>,
+) {
+    if let Some(body) = e2ee.keys_to_upload(rng, count) {
+        match hs.post("_matrix/client/v3/keys/upload", &body) {
+            Some(_) => e2ee.keys_uploaded(),
+            None => log::warn!("couldn't upload keys"),
+        }
+    }
+}
+
+/// Fetches the devices of `users` that aren't known, or changed.
+pub fn update_devices(e2ee: &mut E2ee, hs: &mut Homeserver, users: &[String]) {
+    let users = e2ee.users_to_query(users);
+    if users.is_empty() {
+        return;
+    }
+    let mut device_keys = Map::new();
+    for user in users {
+        device_keys.insert(user, json!([]));
+    }
+    match hs.post("_matrix/client/v3/keys/query", &json!({ "device_keys": device_keys })) {
+        Some(response) => e2ee.add_devices(&response),
+        None => log::warn!("couldn't query keys"),
+    }
+}
+
+/// Starts Olm sessions with those of `devices` we have none with.
+fn claim_sessions(
+    e2ee: &mut E2ee,
+    rng: &mut (impl RngCore + CryptoRng),
+    hs: &mut Homeserver,
+    devices: &[Device],
+) {
+    if let Some(body) = e2ee.sessions_to_claim(devices) {
+        match hs.post("_matrix/client/v3/keys/claim", &body) {
+            Some(response) => e2ee.add_claimed(rng, &response),
+            None => log::warn!("couldn't claim one-time keys"),
+        }
+    }
+}
+
+/// Encrypts an event for `room_id`, sending the room key first to the devices of `members` that don't
+/// have it. Returns the `m.room.encrypted` content to send.
+pub fn encrypt_for_room(
+    e2ee: &mut E2ee,
+    rng: &mut (impl RngCore + CryptoRng),
+    hs: &mut Homeserver,
+    room_id: &str,
+    members: &[String],
+    event_type: &str,
+    content: Value,
+) -> Value {
+    update_devices(e2ee, hs, members);
+    let devices = e2ee.devices_without_room_key(rng, room_id, members, crate::now_ms());
+    if !devices.is_empty() {
+        claim_sessions(e2ee, rng, hs, &devices);
+        let (messages, shared) = e2ee.share_room_key(rng, room_id, &devices);
+        if !shared.is_empty() && hs.send_to_device(rng, ENCRYPTED, &messages) {
+            e2ee.room_key_shared(room_id, &shared);
+        }
+    }
+    e2ee.encrypt(room_id, event_type, content)
+}
+
+/// Sends our verified devices the keys they asked for.
+pub fn forward_keys(e2ee: &mut E2ee, rng: &mut (impl RngCore + CryptoRng), hs: &mut Homeserver) {
+    let devices = e2ee.devices_to_forward_to();
+    if devices.is_empty() {
+        return;
+    }
+    claim_sessions(e2ee, rng, hs, &devices);
+    if let Some(messages) = e2ee.forward_keys(rng) {
+        hs.send_to_device(rng, ENCRYPTED, &messages);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rng() -> impl RngCore + CryptoRng { rand_core::OsRng }
+
+    /// A device with its keys on the "server", and the other device knowing them.
+    fn introduce(a: &mut E2ee, b: &mut E2ee) {
+        let mut rng = rng();
+        let upload = b.keys_to_upload(&mut rng, Some(0)).unwrap();
+        b.keys_uploaded();
+        let query = json!({"device_keys": {b.user_id(): {b.device_id(): b.device_keys()}}});
+        a.add_devices(&query);
+        let (key_id, key) = upload["one_time_keys"].as_object().unwrap().iter().next().unwrap();
+        a.add_claimed(
+            &mut rng,
+            &json!({"one_time_keys": {b.user_id(): {b.device_id(): {key_id.as_str(): key}}}}),
+        );
+    }
+
+    fn to_device(sender: &str, event_type: &str, messages: &Value, user: &str, device: &str) -> Vec<Value> {
+        vec![json!({"sender": sender, "type": event_type, "content": messages[user][device]})]
+    }
+
+    #[test]
+    fn test_canonical_json() {
+        let value = json!({"b": [1, {"d": "x", "c": null}], "a": "é\"", "aa": true});
+        assert_eq!(canonical_json(&value), r#"{"a":"é\"","aa":true,"b":[1,{"c":null,"d":"x"}]}"#);
+    }
+
+    #[test]
+    fn test_room_messages() {
+        let mut rng = rng();
+        let mut alice = E2ee::new(&mut rng, "@alice:example.org", "ALICE");
+        let mut bob = E2ee::new(&mut rng, "@bob:example.org", "BOB");
+        introduce(&mut alice, &mut bob);
+        assert_eq!(alice.devices("@bob:example.org")[0].device_id, "BOB");
+        let members = vec!["@alice:example.org".to_string(), "@bob:example.org".to_string()];
+
+        let room = "!room:example.org";
+        let devices = alice.devices_without_room_key(&mut rng, room, &members, 1000);
+        assert_eq!(devices.len(), 1);
+        let (messages, shared) = alice.share_room_key(&mut rng, room, &devices);
+        alice.room_key_shared(room, &shared);
+        let first = alice.encrypt(room, "m.room.message", json!({"msgtype": "m.text", "body": "hi"}));
+
+        // the message overtakes the key
+        assert!(bob.decrypt(room, &first).is_none());
+        let request = bob.keep_for_later(&mut rng, room, "@alice:example.org", Some(5), &first);
+        assert!(matches!(request, Some(Outcome::Send(ROOM_KEY_REQUEST, _))));
+        let outcomes = bob.receive_to_device(
+            &mut rng,
+            &to_device(alice.user_id(), ENCRYPTED, &messages, bob.user_id(), "BOB"),
+        );
+        match &outcomes[..] {
+            [Outcome::Decrypted(room_id, msg)] => {
+                assert_eq!(room_id, room);
+                assert_eq!(msg.body.as_deref(), Some("hi"));
+                assert_eq!(msg.ts, Some(5));
+            }
+            _ => panic!("{:?}", outcomes),
+        }
+        let second = alice.encrypt(room, "m.room.message", json!({"msgtype": "m.text", "body": "again"}));
+        let (event_type, content) = bob.decrypt(room, &second).unwrap();
+        assert_eq!(event_type, "m.room.message");
+        assert_eq!(content["body"], "again");
+        // not in another room
+        assert!(bob.decrypt("!other:example.org", &second).is_none());
+
+        // the key is only shared once, and a new one is made when bob leaves
+        assert!(alice.devices_without_room_key(&mut rng, room, &members, 2000).is_empty());
+        let session_id = second["session_id"].clone();
+        assert!(alice.devices_without_room_key(&mut rng, room, &members[..1], 3000).is_empty());
+        assert_ne!(alice.encrypt(room, "m.room.message", json!({}))["session_id"], session_id);
+    }
+
+    #[test]
+    fn test_devices() {
+        let mut rng = rng();
+        let mut alice = E2ee::new(&mut rng, "@alice:example.org", "ALICE");
+        let bob = E2ee::new(&mut rng, "@bob:example.org", "BOB");
+        let mut keys = bob.device_keys();
+        // a forged signature
+        keys["keys"]["curve25519:BOB"] = json!(alice.identity.account.curve25519_key());
+        alice.add_devices(&json!({"device_keys": {"@bob:example.org": {"BOB": keys}}}));
+        assert!(alice.devices("@bob:example.org").is_empty());
+
+        alice.add_devices(&json!({"device_keys": {"@bob:example.org": {"BOB": bob.device_keys()}}}));
+        let known = alice.devices("@bob:example.org");
+        assert_eq!(known.len(), 1);
+        // new keys for a known device are ignored
+        let mut impostor = E2ee::new(&mut rng, "@bob:example.org", "BOB");
+        alice.add_devices(&json!({"device_keys": {"@bob:example.org": {"BOB": impostor.device_keys()}}}));
+        assert_eq!(alice.devices("@bob:example.org"), known);
+        assert!(impostor.keys_to_upload(&mut rng, None).is_some());
+        // listed under another user
+        alice.add_devices(&json!({"device_keys": {"@mallory:example.org": {"BOB": bob.device_keys()}}}));
+        assert!(alice.devices("@mallory:example.org").is_empty());
+
+        alice.device_lists_changed(&["@bob:example.org".to_string()], &[]);
+        assert_eq!(
+            alice.users_to_query(&["@bob:example.org".to_string()]),
+            vec!["@bob:example.org".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_one_time_keys() {
+        let mut rng = rng();
+        let mut alice = E2ee::new(&mut rng, "@alice:example.org", "ALICE");
+        let upload = alice.keys_to_upload(&mut rng, None).unwrap();
+        assert_eq!(upload["one_time_keys"].as_object().unwrap().len(), olm::MAX_ONE_TIME_KEYS / 2);
+        assert!(upload.get("device_keys").is_some());
+        alice.keys_uploaded();
+        assert!(alice.keys_to_upload(&mut rng, None).is_none());
+        assert!(alice.keys_to_upload(&mut rng, Some(25)).is_none());
+        let more = alice.keys_to_upload(&mut rng, Some(20)).unwrap();
+        assert_eq!(more["one_time_keys"].as_object().unwrap().len(), 5);
+        assert!(more.get("device_keys").is_none());
+    }
+
+    #[test]
+    fn test_forwarded_keys() {
+        let mut rng = rng();
+        // two devices of ours, and someone else in a room with us
+        let mut phone = E2ee::new(&mut rng, "@alice:example.org", "PHONE");
+        let mut precursor = E2ee::new(&mut rng, "@alice:example.org", "PRECURSOR");
+        let mut bob = E2ee::new(&mut rng, "@bob:example.org", "BOB");
+        introduce(&mut bob, &mut phone);
+        introduce(&mut phone, &mut precursor);
+        introduce(&mut precursor, &mut phone);
+        let room = "!room:example.org";
+        let devices = bob.devices_without_room_key(&mut rng, room, &["@alice:example.org".to_string()], 0);
+        let (messages, _) = bob.share_room_key(&mut rng, room, &devices);
+        phone.receive_to_device(
+            &mut rng,
+            &to_device(bob.user_id(), ENCRYPTED, &messages, phone.user_id(), "PHONE"),
+        );
+        let message = bob.encrypt(room, "m.room.message", json!({"body": "history"}));
+
+        // the precursor asks the phone, which only answers devices it verified
+        assert!(precursor.decrypt(room, &message).is_none());
+        let request = match precursor.keep_for_later(&mut rng, room, "@bob:example.org", None, &message) {
+            Some(Outcome::Send(ROOM_KEY_REQUEST, messages)) => messages,
+            other => panic!("{:?}", other),
+        };
+        let request = to_device(precursor.user_id(), ROOM_KEY_REQUEST, &request, "@alice:example.org", "*");
+        phone.receive_to_device(&mut rng, &request);
+        assert!(phone.forward_keys(&mut rng).is_none());
+        phone.devices.get_mut("@alice:example.org").unwrap()[0].verified = true;
+        phone.receive_to_device(&mut rng, &request);
+        let forwarded = phone.forward_keys(&mut rng).unwrap();
+        let forwarded = to_device(phone.user_id(), ENCRYPTED, &forwarded, "@alice:example.org", "PRECURSOR");
+
+        // and the precursor only takes keys from devices it verified
+        assert!(precursor.receive_to_device(&mut rng, &forwarded).is_empty());
+        assert!(precursor.decrypt(room, &message).is_none());
+        precursor.devices.get_mut("@alice:example.org").unwrap()[0].verified = true;
+        phone.receive_to_device(&mut rng, &request);
+        let forwarded = phone.forward_keys(&mut rng).unwrap();
+        let forwarded = to_device(phone.user_id(), ENCRYPTED, &forwarded, "@alice:example.org", "PRECURSOR");
+        match &precursor.receive_to_device(&mut rng, &forwarded)[..] {
+            [Outcome::Decrypted(_, msg)] => assert_eq!(msg.body.as_deref(), Some("history")),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    /// Hands `events` to `to`, answering yes to everything, and returns what it sends back.
+    fn deliver(
+        to: &mut E2ee,
+        sender: &str,
+        events: Vec<(&'static str, Value)>,
+        codes: &mut Vec<ShortCode>,
+        verified: &mut Vec<String>,
+    ) -> Vec<(&'static str, Value)> {
+        let mut rng = rng();
+        let mut replies = Vec::new();
+        for (event_type, content) in events {
+            let event = json!({"sender": sender, "type": event_type, "content": content});
+            let mut outcomes = to.receive_to_device(&mut rng, &[event]);
+            while !outcomes.is_empty() {
+                match outcomes.remove(0) {
+                    Outcome::Send(event_type, messages) => {
+                        let devices = messages.as_object().unwrap().values().next().unwrap();
+                        replies
+                            .push((event_type, devices.as_object().unwrap().values().next().unwrap().clone()))
+                    }
+                    Outcome::Ask(txn, _, _) => outcomes.extend(to.answer_verification(&txn, true)),
+                    Outcome::Compare(txn, _, code) => {
+                        codes.push(code);
+                        outcomes.extend(to.confirm_verification(&txn, true));
+                    }
+                    Outcome::Verified(_, device) => verified.push(device),
+                    other => panic!("{:?}", other),
+                }
+            }
+        }
+        replies
+    }
+
+    #[test]
+    fn test_verification() {
+        let mut rng = rng();
+        let mut alice = E2ee::new(&mut rng, "@alice:example.org", "ALICE");
+        let mut bob = E2ee::new(&mut rng, "@bob:example.org", "BOB");
+        introduce(&mut alice, &mut bob);
+        introduce(&mut bob, &mut alice);
+
+        let mut to_bob = match alice.request_verification(&mut rng, "@bob:example.org", 0) {
+            Outcome::Send(event_type, messages) => {
+                vec![(event_type, messages["@bob:example.org"]["*"].clone())]
+            }
+            other => panic!("{:?}", other),
+        };
+        let (mut codes, mut verified) = (Vec::new(), Vec::new());
+        while !to_bob.is_empty() {
+            let to_alice = deliver(&mut bob, "@alice:example.org", to_bob, &mut codes, &mut verified);
+            to_bob = deliver(&mut alice, "@bob:example.org", to_alice, &mut codes, &mut verified);
+        }
+        assert_eq!(codes.len(), 2);
+        assert_eq!(codes[0], codes[1]);
+        verified.sort();
+        assert_eq!(verified, vec!["ALICE".to_string(), "BOB".to_string()]);
+        assert!(alice.devices("@bob:example.org")[0].verified);
+        assert!(bob.devices("@alice:example.org")[0].verified);
+    }
+
+    #[test]
+    fn test_declined_verification() {
+        let mut rng = rng();
+        let mut alice = E2ee::new(&mut rng, "@alice:example.org", "ALICE");
+        let mut bob = E2ee::new(&mut rng, "@bob:example.org", "BOB");
+        let request = match alice.request_verification(&mut rng, "@bob:example.org", 0) {
+            Outcome::Send(_, messages) => messages["@bob:example.org"]["*"].clone(),
+            other => panic!("{:?}", other),
+        };
+        let event = json!({"sender": "@alice:example.org", "type": sas::REQUEST, "content": request});
+        let txn = match &bob.receive_to_device(&mut rng, &[event])[..] {
+            [Outcome::Ask(txn, user, device)] => {
+                assert_eq!((user.as_str(), device.as_str()), ("@alice:example.org", "ALICE"));
+                txn.clone()
+            }
+            other => panic!("{:?}", other),
+        };
+        let cancel = match &bob.answer_verification(&txn, false)[..] {
+            [Outcome::Send(sas::CANCEL, messages), Outcome::Cancelled(_)] => {
+                messages["@alice:example.org"]["ALICE"].clone()
+            }
+            other => panic!("{:?}", other),
+        };
+        let event = json!({"sender": "@bob:example.org", "type": sas::CANCEL, "content": cancel});
+        assert!(matches!(&alice.receive_to_device(&mut rng, &[event])[..], [Outcome::Cancelled(_)]));
+        assert!(alice.verifications.is_empty() && bob.verifications.is_empty());
+    }
+
+    #[test]
+    fn test_saved_state() {
+        let mut rng = rng();
+        let mut alice = E2ee::new(&mut rng, "@alice:example.org", "ALICE");
+        let mut bob = E2ee::new(&mut rng, "@bob:example.org", "BOB");
+        introduce(&mut alice, &mut bob);
+        // what `save` writes to the PDDB and `load` reads back
+        let identity: Identity =
+            serde_json::from_slice(&serde_json::to_vec(&alice.identity).unwrap()).unwrap();
+        let devices: HashMap<String, Vec<Device>> =
+            serde_json::from_slice(&serde_json::to_vec(&alice.devices).unwrap()).unwrap();
+        assert_eq!(identity.account.ed25519_key(), alice.ed25519_key());
+        assert_eq!(identity.device_id, "ALICE");
+        assert_eq!(devices["@bob:example.org"], alice.devices("@bob:example.org"));
+    }
+}
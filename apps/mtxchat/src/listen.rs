@@ -1,62 +1,257 @@
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
 
 use chat::ChatOp;
 use locales::t;
+use modals::Modals;
 use tls::xtls::TlsConnector;
 use url::Url;
 use xous::CID;
 use xous_ipc::Buffer;
 
-use crate::{MTX_LONG_TIMEOUT_MS, get_username, web};
+use crate::e2ee::{self, E2ee, Homeserver, Outcome};
+use crate::rooms::Rooms;
+use crate::{MTX_LONG_TIMEOUT_MS, Msg, get_username, web};
 
-pub fn listen(
-    url: &mut Url,
-    token: &str,
+/// how long to wait before syncing again after a sync failed
+const SYNC_RETRY_MS: usize = 10_000;
+
+/// What the listening thread needs.
+pub struct Listener {
+    pub url: Url,
+    pub token: String,
+    pub user_id: String,
+    /// the room shown; the others only have their list entries kept up to date
+    pub room_id: String,
+    pub since: Option<String>,
+    pub filter: String,
+    pub dialogue_id: String,
+    pub chat_cid: CID,
+    pub e2ee: Option<Arc<Mutex<E2ee>>>,
+    pub rooms: Arc<Mutex<Rooms>>,
+    /// the listener stops once this is no longer `generation`
+    pub current: Arc<AtomicU32>,
+    pub generation: u32,
+}
+
+fn post(chat_cid: CID, dialogue_id: &str, sender: &str, text: &str, ts: Option<u64>) {
+    let post = chat::Post {
+        dialogue_id: String::from(dialogue_id),
+        author: String::from(&get_username(sender)),
+        timestamp: ts.unwrap_or(0),
+        text: String::from(text),
+        attach_url: None,
+    };
+    match Buffer::into_buf(post) {
+        Ok(buf) => buf.send(chat_cid, ChatOp::PostAdd as u32).map(|_| ()),
+        Err(_) => Err(xous::Error::InternalError),
+    }
+    .expect("failed to convert post into buffer");
+}
+
+/// The text to show for a message, decrypted if need be, or `None` if it isn't shown. An encrypted
+/// message whose key hasn't arrived is kept to show later, and the key asked for.
+pub(crate) fn msg_text(
+    msg: &Msg,
     room_id: &str,
-    since: Option<&str>,
-    filter: &str,
-    dialogue_id: &str,
-    chat_cid: CID,
-) {
-    log::info!("client_sync for {} ms...", MTX_LONG_TIMEOUT_MS);
+    e2ee: Option<&Arc<Mutex<E2ee>>>,
+    trng: &mut trng::Trng,
+    outcomes: &mut impl Extend<Outcome>,
+) -> Option<String> {
+    let undecryptable = || t!("mtxchat.e2ee.undecryptable", locales::lang()).to_string();
+    match (&msg.encrypted, e2ee) {
+        (None, _) => Some(msg.body.clone().unwrap_or("...".to_string())),
+        (Some(content), Some(e2ee)) => {
+            let mut e2ee = e2ee.lock().unwrap();
+            match e2ee.decrypt(room_id, content) {
+                Some((event_type, content)) if event_type == "m.room.message" => {
+                    Some(content["body"].as_str().unwrap_or("...").to_string())
+                }
+                // reactions, edits and the like
+                Some(_) => None,
+                None => {
+                    let sender = msg.sender.as_deref().unwrap_or("anon");
+                    outcomes.extend(e2ee.keep_for_later(trng, room_id, sender, msg.ts, content));
+                    Some(undecryptable())
+                }
+            }
+        }
+        (Some(_), None) => Some(undecryptable()),
+    }
+}
+
+/// Asks the user a yes or no question.
+pub(crate) fn ask(modals: &Modals, prompt: &str) -> bool {
+    let yes = t!("mtxchat.yes", locales::lang());
+    modals.add_list(vec![yes, t!("mtxchat.no", locales::lang())]).expect("couldn't build list");
+    matches!(modals.get_radiobutton(prompt), Ok(choice) if choice == yes)
+}
 
+/// Syncs until told to stop, posting the room's messages to the chat UI. With encryption, it also
+/// decrypts them, keeps our keys up to date, and takes the user through verifications other devices start.
+pub fn listen(listener: Listener) {
+    let Listener {
+        url,
+        token,
+        user_id,
+        room_id,
+        mut since,
+        filter,
+        dialogue_id,
+        chat_cid,
+        e2ee,
+        rooms,
+        current,
+        generation,
+    } = listener;
+    let xns = xous_names::XousNames::new().unwrap();
+    let mut trng = trng::Trng::new(&xns).unwrap();
+    let modals = Modals::new(&xns).expect("can't connect to Modals server");
+    let tt = ticktimer_server::Ticktimer::new().unwrap();
+    let pddb = pddb::Pddb::new();
     let mut agent = ureq::builder().tls_connector(Arc::new(TlsConnector {})).build();
-    if let Some((_since, events)) =
-        web::client_sync(url, filter, since, MTX_LONG_TIMEOUT_MS, &room_id, &token, &mut agent)
-    {
-        // TODO utilize "since"
-        // and you probably want to have a look at Dialogue::MAX_BYTES
+
+    while current.load(Ordering::SeqCst) == generation {
+        log::info!("client_sync for {} ms...", MTX_LONG_TIMEOUT_MS);
+        let sync = web::client_sync(
+            &mut url.clone(),
+            &filter,
+            since.as_deref(),
+            MTX_LONG_TIMEOUT_MS,
+            &token,
+            &mut agent,
+        );
+        if current.load(Ordering::SeqCst) != generation {
+            break;
+        }
+        let sync = match sync {
+            Some(sync) => sync,
+            None => {
+                tt.sleep_ms(SYNC_RETRY_MS).unwrap();
+                continue;
+            }
+        };
+        since = Some(sync.next_batch.clone());
+        rooms.lock().unwrap().update(&sync, &user_id);
+
+        let mut outcomes = VecDeque::new();
+        if let Some(e2ee) = &e2ee {
+            let mut e2ee = e2ee.lock().unwrap();
+            let mut hs = Homeserver { url: url.clone(), token: &token, agent: &mut agent };
+            e2ee.device_lists_changed(&sync.changed, &sync.left);
+            outcomes.extend(e2ee.receive_to_device(&mut trng, &sync.to_device));
+            e2ee::upload_keys(&mut e2ee, &mut trng, &mut hs, sync.one_time_keys);
+            let outdated = e2ee.outdated_users();
+            e2ee::update_devices(&mut e2ee, &mut hs, &outdated);
+            e2ee::forward_keys(&mut e2ee, &mut trng, &mut hs);
+        }
+
+        let shown = sync.joined.into_iter().find(|room| room.room_id == room_id);
+        let (msgs, typing) = match shown {
+            Some(room) => (room.msgs, room.typing),
+            None => (Vec::new(), None),
+        };
+        if typing.is_some() {
+            let others: Vec<String> = match rooms.lock().unwrap().get(&room_id) {
+                Some(room) => room.typing.iter().map(|user| get_username(user)).collect(),
+                None => Vec::new(),
+            };
+            if others.is_empty() {
+                chat::cf_set_status_text(chat_cid, t!("mtxchat.status.default", locales::lang()));
+            } else {
+                chat::cf_set_status_text(
+                    chat_cid,
+                    &t!("mtxchat.typing", locales::lang()).replace("{users}", &others.join(", ")),
+                );
+            }
+        }
 
         // TODO resolve suspected race condition
         // This progress modal is masking a bug by slowing the loop down
         // Precursor "Guru Mediation" `voilated: nonNull::new_unchecked`
-        chat::cf_set_status_text(chat_cid, t!("mtxchat.busy.rx_events", locales::LANG));
-        chat::cf_set_busy_state(chat_cid, true);
-        let mut event_count = 0;
-        for event in events {
-            let sender = event.sender.unwrap_or("anon".to_string());
-            let body = event.body.unwrap_or("...".to_string());
-            let post = chat::Post {
-                dialogue_id: String::from(dialogue_id),
-                author: String::from(&get_username(&sender)),
-                timestamp: event.ts.unwrap_or(0),
-                text: String::from(&body),
-                attach_url: None,
-            };
-            match Buffer::into_buf(post) {
-                Ok(buf) => buf.send(chat_cid, ChatOp::PostAdd as u32).map(|_| ()),
-                Err(_) => Err(xous::Error::InternalError),
+        let event_count = msgs.len();
+        if event_count > 0 {
+            chat::cf_set_status_text(chat_cid, t!("mtxchat.busy.rx_events", locales::lang()));
+            chat::cf_set_busy_state(chat_cid, true);
+        }
+        let mut read_up_to = None;
+        for (i, msg) in msgs.iter().enumerate() {
+            read_up_to = msg.event_id.as_deref().or(read_up_to);
+            if let Some(text) = msg_text(msg, &room_id, e2ee.as_ref(), &mut trng, &mut outcomes) {
+                post(chat_cid, &dialogue_id, msg.sender.as_deref().unwrap_or("anon"), &text, msg.ts);
             }
-            .expect("failed to convert post into buffer");
-            event_count += 1;
             chat::cf_set_status_text(
                 chat_cid,
-                &format!("{} {}", t!("mtxchat.busy.rx_events", locales::LANG), event_count),
+                &format!("{} {}", t!("mtxchat.busy.rx_events", locales::lang()), i + 1),
             );
         }
+        // the user sees the room, so it's read
+        if let Some(event_id) = read_up_to {
+            if web::send_receipt(&mut url.clone(), &room_id, event_id, &token, &mut agent) {
+                rooms.lock().unwrap().read(&room_id);
+            }
+        }
+
+        let mut posted = event_count > 0;
+        while let Some(outcome) = outcomes.pop_front() {
+            let e2ee = match &e2ee {
+                Some(e2ee) => e2ee,
+                None => break,
+            };
+            match outcome {
+                Outcome::Send(event_type, messages) => {
+                    let mut hs = Homeserver { url: url.clone(), token: &token, agent: &mut agent };
+                    hs.send_to_device(&mut trng, event_type, &messages);
+                }
+                Outcome::Decrypted(event_room, msg) => {
+                    if event_room == room_id && msg.type_ == "m.room.message" {
+                        let sender = msg.sender.unwrap_or("anon".to_string());
+                        post(chat_cid, &dialogue_id, &sender, msg.body.as_deref().unwrap_or("..."), msg.ts);
+                        posted = true;
+                    }
+                }
+                Outcome::Ask(transaction_id, user, device) => {
+                    let prompt = t!("mtxchat.verify.ask", locales::lang())
+                        .replace("{user}", &user)
+                        .replace("{device}", &device);
+                    let accept = ask(&modals, &prompt);
+                    outcomes.extend(e2ee.lock().unwrap().answer_verification(&transaction_id, accept));
+                }
+                Outcome::Compare(transaction_id, user, code) => {
+                    let prompt = t!("mtxchat.verify.compare", locales::lang())
+                        .replace("{user}", &user)
+                        .replace("{code}", &code.describe());
+                    let matches = ask(&modals, &prompt);
+                    outcomes.extend(e2ee.lock().unwrap().confirm_verification(&transaction_id, matches));
+                }
+                Outcome::Verified(user, device) => {
+                    let note = t!("mtxchat.verify.done", locales::lang())
+                        .replace("{user}", &user)
+                        .replace("{device}", &device);
+                    modals.show_notification(&note, None).expect("notification failed");
+                }
+                Outcome::Cancelled(reason) => {
+                    log::info!("verification cancelled: {}", reason);
+                    modals
+                        .show_notification(t!("mtxchat.verify.cancelled", locales::lang()), None)
+                        .expect("notification failed");
+                }
+            }
+        }
+        if let Some(e2ee) = &e2ee {
+            e2ee.lock().unwrap().save(&pddb);
+        }
+
+        chat::cf_set_busy_state(chat_cid, false);
+        if posted {
+            // trigger the chat ui to save the dialogue to the pddb
+            xous::send_message(
+                chat_cid,
+                xous::Message::new_scalar(ChatOp::DialogueSave as usize, 0, 0, 0, 0),
+            )
+            .expect("failed to send new inbound msgs");
+        }
     }
-    chat::cf_set_busy_state(chat_cid, false);
-    // trigger the chat ui to save the dialogue to the pddb
-    xous::send_message(chat_cid, xous::Message::new_scalar(ChatOp::DialogueSave as usize, 0, 0, 0, 0))
-        .expect("failed to send new inbound msgs");
+    log::info!("stopped listening to {}", room_id);
 }
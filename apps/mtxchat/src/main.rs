@@ -53,7 +53,15 @@ fn wrapped_main() -> ! {
 
     let cid = xous::connect(sid).unwrap();
     chat.menu_add(MenuItem {
-        name: String::from(t!("mtxchat.room.item", locales::LANG)),
+        name: String::from(t!("mtxchat.rooms.item", locales::lang())),
+        action_conn: Some(cid),
+        action_opcode: MtxchatOp::Menu as u32,
+        action_payload: MenuPayload::Scalar([MenuOp::Rooms as u32, 0, 0, 0]),
+        close_on_select: true,
+    })
+    .expect("failed add menu");
+    chat.menu_add(MenuItem {
+        name: String::from(t!("mtxchat.room.item", locales::lang())),
         action_conn: Some(cid),
         action_opcode: MtxchatOp::Menu as u32,
         action_payload: MenuPayload::Scalar([MenuOp::Room as u32, 0, 0, 0]),
@@ -61,7 +69,15 @@ fn wrapped_main() -> ! {
     })
     .expect("failed add menu");
     chat.menu_add(MenuItem {
-        name: String::from(t!("mtxchat.login.item", locales::LANG)),
+        name: String::from(t!("mtxchat.leave.item", locales::lang())),
+        action_conn: Some(cid),
+        action_opcode: MtxchatOp::Menu as u32,
+        action_payload: MenuPayload::Scalar([MenuOp::Leave as u32, 0, 0, 0]),
+        close_on_select: true,
+    })
+    .expect("failed add menu");
+    chat.menu_add(MenuItem {
+        name: String::from(t!("mtxchat.login.item", locales::lang())),
         action_conn: Some(cid),
         action_opcode: MtxchatOp::Menu as u32,
         action_payload: MenuPayload::Scalar([MenuOp::Login as u32, 0, 0, 0]),
@@ -69,7 +85,7 @@ fn wrapped_main() -> ! {
     })
     .expect("failed add menu");
     chat.menu_add(MenuItem {
-        name: String::from(t!("mtxchat.logout.item", locales::LANG)),
+        name: String::from(t!("mtxchat.logout.item", locales::lang())),
         action_conn: Some(cid),
         action_opcode: MtxchatOp::Menu as u32,
         action_payload: MenuPayload::Scalar([MenuOp::Logout as u32, 0, 0, 0]),
@@ -77,7 +93,15 @@ fn wrapped_main() -> ! {
     })
     .expect("failed add menu");
     chat.menu_add(MenuItem {
-        name: String::from(t!("mtxchat.close.item", locales::LANG)),
+        name: String::from(t!("mtxchat.verify.item", locales::lang())),
+        action_conn: Some(cid),
+        action_opcode: MtxchatOp::Menu as u32,
+        action_payload: MenuPayload::Scalar([MenuOp::Verify as u32, 0, 0, 0]),
+        close_on_select: true,
+    })
+    .expect("failed add menu");
+    chat.menu_add(MenuItem {
+        name: String::from(t!("mtxchat.close.item", locales::lang())),
         action_conn: Some(cid),
         action_opcode: MtxchatOp::Menu as u32,
         action_payload: MenuPayload::Scalar([MenuOp::Noop as u32, 0, 0, 0]),
@@ -88,6 +112,7 @@ fn wrapped_main() -> ! {
     let mut mtxchat = MtxChat::new(&chat);
     let mut first_focus = true;
     let mut user_post: Option<String> = None;
+    let mut typed = false;
     loop {
         let msg = xous::receive_message(sid).unwrap();
         log::debug!("got message {:?}", msg);
@@ -119,13 +144,12 @@ fn wrapped_main() -> ! {
                             mtxchat.connect();
                         }
                         Some(MenuOp::Noop) => {}
-                        Some(MenuOp::Room) => {
-                            if let Some(room) = mtxchat.get_room_id() {
-                                mtxchat.listen_over("");
-                                mtxchat.dialogue_set(Some(room.as_str()));
-                                mtxchat.listen();
-                            }
+                        Some(MenuOp::Verify) => {
+                            mtxchat.verify();
                         }
+                        Some(MenuOp::Room) => mtxchat.room(),
+                        Some(MenuOp::Rooms) => mtxchat.room_list(),
+                        Some(MenuOp::Leave) => mtxchat.leave(),
                         _ => (),
                     }
                 });
@@ -139,7 +163,11 @@ fn wrapped_main() -> ! {
                     user_post = Some(s.to_string());
                 }
             }
-            Some(MtxchatOp::Rawkeys) => log::info!("got mtxchat rawkeys"),
+            Some(MtxchatOp::Rawkeys) => {
+                log::info!("got mtxchat rawkeys");
+                // as with posts, the keystroke is released before telling the room
+                typed = true;
+            }
             Some(MtxchatOp::Quit) => {
                 log::error!("got Quit");
                 mtxchat.listen_over("");
@@ -147,6 +175,10 @@ fn wrapped_main() -> ! {
             }
             _ => (),
         }
+        if typed {
+            mtxchat.typing();
+            typed = false;
+        }
         if let Some(post) = user_post {
             mtxchat.post(&post);
             user_post = None;
@@ -0,0 +1,351 @@
+//! Megolm, the ratchet Matrix uses to encrypt room messages, as described in
+//! <https://gitlab.matrix.org/matrix-org/olm/-/blob/master/docs/megolm.md>.
+//!
+//! The sender shares its ratchet, as a session key, with every device in the room over Olm. Anyone with
+//! the ratchet at some index can decrypt the messages from then on, but not the ones before.
+
+use std::convert::{TryFrom, TryInto};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::olm::{Cipher, Error, Field};
+use crate::olm::{decode, encode, hmac_sha256, parse_fields, push_bytes, push_int, start_message};
+
+const MAC_LEN: usize = 8;
+const SIGNATURE_LEN: usize = 64;
+const SESSION_KEY_VERSION: u8 = 2;
+const EXPORT_VERSION: u8 = 1;
+const RATCHET_LEN: usize = 4 * 32;
+
+/// The four part ratchet, R(i) in the spec: the parts are rehashed at different rates so that any index
+/// can be reached in at most 1020 hashes.
+#[derive(Clone, Serialize, Deserialize)]
+struct Ratchet {
+    parts: [[u8; 32]; 4],
+    counter: u32,
+}
+
+impl Ratchet {
+    fn from_bytes(counter: u32, bytes: &[u8]) -> Self {
+        let mut ratchet = Ratchet { parts: [[0; 32]; 4], counter };
+        for (part, chunk) in ratchet.parts.iter_mut().zip(bytes.chunks(32)) {
+            part.copy_from_slice(chunk);
+        }
+        ratchet
+    }
+
+    fn to_bytes(&self) -> [u8; RATCHET_LEN] {
+        let mut bytes = [0u8; RATCHET_LEN];
+        for (chunk, part) in bytes.chunks_mut(32).zip(self.parts.iter()) {
+            chunk.copy_from_slice(part);
+        }
+        bytes
+    }
+
+    /// R(to) = HMAC(R(from), to), the part number being the input.
+    fn rehash(&mut self, from: usize, to: usize) {
+        self.parts[to] = hmac_sha256(&self.parts[from], &[to as u8]);
+    }
+
+    fn advance(&mut self) {
+        self.counter = self.counter.wrapping_add(1);
+        // the lowest part whose counter byte rolled over, and every part after it, is rehashed from it
+        let mut mask = 0x00ff_ffffu32;
+        let mut from = 0;
+        while from < 4 && self.counter & mask != 0 {
+            from += 1;
+            mask >>= 8;
+        }
+        for to in (from..4).rev() {
+            self.rehash(from, to);
+        }
+    }
+
+    fn advance_to(&mut self, index: u32) {
+        for part in 0..4 {
+            let shift = (3 - part) * 8;
+            let mask = !0u32 << shift;
+            let mut steps = ((index >> shift).wrapping_sub(self.counter >> shift)) & 0xff;
+            if steps == 0 {
+                // the counter wrapped around
+                if index < self.counter {
+                    steps = 0x100;
+                } else {
+                    continue;
+                }
+            }
+            // only the last step needs to rehash the later parts
+            for _ in 1..steps {
+                self.rehash(part, part);
+            }
+            for to in (part..4).rev() {
+                self.rehash(part, to);
+            }
+            self.counter = index & mask;
+        }
+    }
+
+    fn cipher(&self) -> Cipher { Cipher::new(&self.to_bytes(), b"MEGOLM_KEYS") }
+}
+
+/// The session a device encrypts its messages to a room with.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OutboundGroupSession {
+    ratchet: Ratchet,
+    signing_secret: [u8; 32],
+}
+
+impl OutboundGroupSession {
+    pub fn new(rng: &mut (impl RngCore + CryptoRng)) -> Self {
+        let mut bytes = [0u8; RATCHET_LEN];
+        rng.fill_bytes(&mut bytes);
+        let mut signing_secret = [0u8; 32];
+        rng.fill_bytes(&mut signing_secret);
+        OutboundGroupSession { ratchet: Ratchet::from_bytes(0, &bytes), signing_secret }
+    }
+
+    /// The session's public signing key.
+    pub fn session_id(&self) -> String {
+        encode(SigningKey::from_bytes(&self.signing_secret).verifying_key().as_bytes())
+    }
+
+    /// The index the next message will be encrypted at.
+    pub fn message_index(&self) -> u32 { self.ratchet.counter }
+
+    /// The ratchet at the current index, signed, for sharing in an `m.room_key` event.
+    pub fn session_key(&self) -> String {
+        let signing_key = SigningKey::from_bytes(&self.signing_secret);
+        let mut key = vec![SESSION_KEY_VERSION];
+        key.extend_from_slice(&self.ratchet.counter.to_be_bytes());
+        key.extend_from_slice(&self.ratchet.to_bytes());
+        key.extend_from_slice(signing_key.verifying_key().as_bytes());
+        let signature = signing_key.sign(&key);
+        key.extend_from_slice(&signature.to_bytes());
+        encode(&key)
+    }
+
+    /// Returns the base64 ciphertext of an `m.megolm.v1.aes-sha2` event.
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> String {
+        let cipher = self.ratchet.cipher();
+        let mut message = start_message();
+        push_int(&mut message, 1, self.ratchet.counter as u64);
+        push_bytes(&mut message, 2, &cipher.encrypt(plaintext));
+        let mac = cipher.mac(&message);
+        message.extend_from_slice(&mac);
+        let signature = SigningKey::from_bytes(&self.signing_secret).sign(&message);
+        message.extend_from_slice(&signature.to_bytes());
+        self.ratchet.advance();
+        encode(&message)
+    }
+}
+
+/// What a device knows of another device's outbound session.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InboundGroupSession {
+    /// the earliest ratchet we have, to decrypt messages that arrive out of order
+    initial: Ratchet,
+    /// the ratchet at the latest index decrypted, so that new messages don't hash from the start
+    latest: Ratchet,
+    signing_key: [u8; 32],
+    /// Whether the key was signed by the session itself. Keys forwarded by other devices aren't.
+    pub signed: bool,
+}
+
+impl InboundGroupSession {
+    /// Takes the `session_key` of an `m.room_key` event.
+    pub fn new(session_key: &str) -> Result<Self, Error> {
+        let key = decode(session_key)?;
+        if key.len() != 1 + 4 + RATCHET_LEN + 32 + SIGNATURE_LEN {
+            return Err(Error::Decode);
+        }
+        if key[0] != SESSION_KEY_VERSION {
+            return Err(Error::Version);
+        }
+        let (signed, signature) = key.split_at(key.len() - SIGNATURE_LEN);
+        let signing_key: [u8; 32] = signed[signed.len() - 32..].try_into().unwrap();
+        VerifyingKey::from_bytes(&signing_key)
+            .and_then(|verifier| verifier.verify_strict(signed, &Signature::from_slice(signature)?))
+            .or(Err(Error::Signature))?;
+        let ratchet =
+            Ratchet::from_bytes(u32::from_be_bytes(key[1..5].try_into().unwrap()), &key[5..5 + RATCHET_LEN]);
+        Ok(InboundGroupSession { initial: ratchet.clone(), latest: ratchet, signing_key, signed: true })
+    }
+
+    /// Takes the `session_key` of an `m.forwarded_room_key` event, or a key exported by `export_at`.
+    pub fn import(exported_key: &str) -> Result<Self, Error> {
+        let key = decode(exported_key)?;
+        if key.len() != 1 + 4 + RATCHET_LEN + 32 {
+            return Err(Error::Decode);
+        }
+        if key[0] != EXPORT_VERSION {
+            return Err(Error::Version);
+        }
+        let ratchet =
+            Ratchet::from_bytes(u32::from_be_bytes(key[1..5].try_into().unwrap()), &key[5..5 + RATCHET_LEN]);
+        Ok(InboundGroupSession {
+            initial: ratchet.clone(),
+            latest: ratchet,
+            signing_key: key[5 + RATCHET_LEN..].try_into().unwrap(),
+            signed: false,
+        })
+    }
+
+    pub fn session_id(&self) -> String { encode(&self.signing_key) }
+
+    pub fn first_known_index(&self) -> u32 { self.initial.counter }
+
+    /// The ratchet from `index` on, unsigned, to forward to another device. Returns `None` if the
+    /// session doesn't go back that far.
+    pub fn export_at(&self, index: u32) -> Option<String> {
+        if index < self.initial.counter {
+            return None;
+        }
+        let mut ratchet = self.initial.clone();
+        ratchet.advance_to(index);
+        let mut key = vec![EXPORT_VERSION];
+        key.extend_from_slice(&ratchet.counter.to_be_bytes());
+        key.extend_from_slice(&ratchet.to_bytes());
+        key.extend_from_slice(&self.signing_key);
+        Some(encode(&key))
+    }
+
+    /// Keeps the earlier of the two ratchets of the same session.
+    pub fn merge(&mut self, other: InboundGroupSession) {
+        if other.signing_key == self.signing_key && other.initial.counter < self.initial.counter {
+            *self = InboundGroupSession { signed: self.signed || other.signed, ..other };
+        }
+    }
+
+    /// Decrypts the base64 ciphertext of an `m.megolm.v1.aes-sha2` event, and returns the plaintext and
+    /// its message index.
+    pub fn decrypt(&mut self, ciphertext: &str) -> Result<(Vec<u8>, u32), Error> {
+        let message = decode(ciphertext)?;
+        if message.len() < 1 + MAC_LEN + SIGNATURE_LEN {
+            return Err(Error::Decode);
+        }
+        let (signed, signature) = message.split_at(message.len() - SIGNATURE_LEN);
+        VerifyingKey::from_bytes(&self.signing_key)
+            .and_then(|verifier| verifier.verify_strict(signed, &Signature::from_slice(signature)?))
+            .or(Err(Error::Signature))?;
+        let (authenticated, mac) = signed.split_at(signed.len() - MAC_LEN);
+        let (mut index, mut ciphertext) = (None, None);
+        for (field, value) in parse_fields(authenticated)? {
+            match (field, value) {
+                (1, Field::Int(i)) => index = Some(u32::try_from(i).or(Err(Error::Decode))?),
+                (2, Field::Bytes(bytes)) => ciphertext = Some(bytes),
+                _ => (),
+            }
+        }
+        let (index, ciphertext) = match (index, ciphertext) {
+            (Some(index), Some(ciphertext)) => (index, ciphertext),
+            _ => return Err(Error::Decode),
+        };
+        if index < self.initial.counter {
+            return Err(Error::MessageIndex);
+        }
+        let advance_latest = index >= self.latest.counter;
+        let mut ratchet = if advance_latest { self.latest.clone() } else { self.initial.clone() };
+        ratchet.advance_to(index);
+        let cipher = ratchet.cipher();
+        cipher.verify_mac(authenticated, mac)?;
+        let plaintext = cipher.decrypt(ciphertext)?;
+        if advance_latest {
+            self.latest = ratchet;
+        }
+        Ok((plaintext, index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advance_to() {
+        let mut bytes = [0u8; RATCHET_LEN];
+        for (i, b) in bytes.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        // one step at a time, and straight there, across each part's rollover
+        for target in [1u32, 255, 256, 257, 0x1_0000, 0x1_0001, 0x1_0100] {
+            let mut stepped = Ratchet::from_bytes(0, &bytes);
+            for _ in 0..target {
+                stepped.advance();
+            }
+            let mut jumped = Ratchet::from_bytes(0, &bytes);
+            jumped.advance_to(target);
+            assert_eq!(stepped.counter, target);
+            assert_eq!(jumped.counter, target);
+            assert_eq!(stepped.parts, jumped.parts, "index {}", target);
+        }
+    }
+
+    /// The ratchet at a few indices, as vodozemac 0.9 exports it, from a session key with the ratchet
+    /// 0, 1, .., 127 at index 0 and the signing key of RFC 8032's first test.
+    #[test]
+    fn test_known_answers() {
+        let bytes: Vec<u8> = (0..RATCHET_LEN as u8).collect();
+        let signing_secret = [
+            0x9d, 0x61, 0xb1, 0x9d, 0xef, 0xfd, 0x5a, 0x60, 0xba, 0x84, 0x4a, 0xf4, 0x92, 0xec, 0x2c, 0xc4,
+            0x44, 0x49, 0xc5, 0x69, 0x7b, 0x32, 0x69, 0x19, 0x70, 0x3b, 0xac, 0x03, 0x1c, 0xae, 0x7f, 0x60,
+        ];
+        let outbound = OutboundGroupSession { ratchet: Ratchet::from_bytes(0, &bytes), signing_secret };
+        assert_eq!(outbound.session_id(), "11qYAYKxCrfVS/7TyWQHOg7hcvPapiMlrwIaaPcHURo");
+        let inbound = InboundGroupSession::new(&outbound.session_key()).unwrap();
+        assert_eq!(inbound.session_id(), outbound.session_id());
+        assert!(inbound.signed);
+        for (index, ratchet) in [
+            (
+                1,
+                "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJygpKissLS4vMDEyMzQ1Njc4OTo7PD0+P0BBQkNERUZHSElKS0xNTk9QUVJTVFVWV1hZWltcXV5fVQdE4zQRX6H9c9O3EXbUFXKIYxyzcEWkn9Ys0GCMj10",
+            ),
+            (
+                0x100,
+                "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8gISIjJCUmJygpKissLS4vMDEyMzQ1Njc4OTo7PD0+P/C977utPPCX3MsD8shxWfdgjiSAVD7vd0HI8ufyJuePB+kNxBFAbBuoa4mENbBRL3AUZmo+zTr5PxPDLMrxMFA",
+            ),
+            (
+                0x1_0203,
+                "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh95fyGtl1FcPtXLH45uwozYNieqjcTO0HFxIKu7AzlxWSAaD7iE9zSQzV8HPalSFtc+ArnS+w/Bm64yZhWGk7gOYOhH+4lpNwQ6D3CVxSqd7yw7+fsjRCv7a+PMJTruVdM",
+            ),
+        ] {
+            let exported = decode(&inbound.export_at(index).unwrap()).unwrap();
+            assert_eq!(exported[1..5], index.to_be_bytes());
+            assert_eq!(encode(&exported[5..5 + RATCHET_LEN]), ratchet, "index {}", index);
+        }
+    }
+
+    #[test]
+    fn test_group_session() {
+        let mut outbound = OutboundGroupSession::new(&mut rand_core::OsRng);
+        let mut inbound = InboundGroupSession::new(&outbound.session_key()).unwrap();
+        let first = outbound.encrypt(b"first");
+        assert_eq!(inbound.session_id(), outbound.session_id());
+        assert_eq!(inbound.decrypt(&first).unwrap(), (b"first".to_vec(), 0));
+
+        let _skipped = outbound.encrypt(b"skipped");
+        let third = outbound.encrypt(b"third");
+        assert_eq!(inbound.decrypt(&third).unwrap(), (b"third".to_vec(), 2));
+        assert_eq!(inbound.decrypt(&first).unwrap(), (b"first".to_vec(), 0));
+
+        // a key shared later can't decrypt what came before it
+        let mut late = InboundGroupSession::new(&outbound.session_key()).unwrap();
+        assert_eq!(late.decrypt(&first), Err(Error::MessageIndex));
+        assert!(late.signed);
+
+        // forwarding from index 1 decrypts index 2 but not 0
+        let mut forwarded = InboundGroupSession::import(&inbound.export_at(1).unwrap()).unwrap();
+        assert!(!forwarded.signed);
+        assert_eq!(forwarded.first_known_index(), 1);
+        assert_eq!(forwarded.decrypt(&third).unwrap().1, 2);
+        assert_eq!(forwarded.decrypt(&first), Err(Error::MessageIndex));
+        late.merge(forwarded);
+        assert_eq!(late.first_known_index(), 1);
+        assert!(late.signed);
+
+        let mut tampered = decode(&third).unwrap();
+        tampered[4] ^= 1;
+        assert_eq!(inbound.decrypt(&encode(&tampered)), Err(Error::Signature));
+    }
+}
@@ -0,0 +1,121 @@
+//! A stand-in homeserver for tests: it answers with canned JSON over plain HTTP on localhost, and records
+//! what it was asked.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use ureq::serde_json::{Value, json};
+use url::Url;
+
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    /// the path and query, as sent
+    pub path: String,
+    pub body: String,
+}
+
+/// A canned answer to requests whose method matches and whose path starts with `path`.
+pub struct Route {
+    pub method: &'static str,
+    pub path: &'static str,
+    pub status: u16,
+    pub body: Value,
+}
+
+pub fn route(method: &'static str, path: &'static str, body: Value) -> Route {
+    Route { method, path, status: 200, body }
+}
+
+pub struct MockHomeserver {
+    pub url: Url,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockHomeserver {
+    /// Starts serving `routes`, the first that matches answering. Anything else gets a 404.
+    pub fn start(routes: Vec<Route>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("couldn't bind");
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let routes = Arc::new(routes);
+        let recorded = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let (routes, recorded) = (routes.clone(), recorded.clone());
+                match stream {
+                    Ok(stream) => {
+                        thread::spawn(move || serve(stream, &routes, &recorded));
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+        MockHomeserver { url, requests }
+    }
+
+    /// What was asked so far.
+    pub fn requests(&self) -> Vec<Request> { self.requests.lock().unwrap().clone() }
+
+    /// The requests with this method to paths starting with `path`.
+    pub fn requests_to(&self, method: &str, path: &str) -> Vec<Request> {
+        self.requests().into_iter().filter(|r| r.method == method && r.path.starts_with(path)).collect()
+    }
+}
+
+/// Answers the requests on a connection until the client closes it.
+fn serve(stream: TcpStream, routes: &[Route], recorded: &Mutex<Vec<Request>>) {
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+            return;
+        }
+        let mut parts = line.split_whitespace();
+        let (method, path) = match (parts.next(), parts.next()) {
+            (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+            _ => return,
+        };
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).unwrap_or(0) == 0 {
+                return;
+            }
+            if header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap_or(0);
+                }
+            }
+        }
+        let mut body = vec![0; length];
+        if reader.read_exact(&mut body).is_err() {
+            return;
+        }
+        let (status, answer) =
+            match routes.iter().find(|route| route.method == method && path.starts_with(route.path)) {
+                Some(route) => (route.status, route.body.to_string()),
+                None => (404, json!({"errcode": "M_UNRECOGNIZED"}).to_string()),
+            };
+        recorded.lock().unwrap().push(Request {
+            method,
+            path,
+            body: String::from_utf8_lossy(&body).to_string(),
+        });
+        let response = format!(
+            "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            answer.len(),
+            answer
+        );
+        if writer.write_all(response.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
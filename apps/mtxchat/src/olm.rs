@@ -0,0 +1,768 @@
+//! Olm, the double ratchet Matrix devices use to send each other keys, as described in
+//! <https://gitlab.matrix.org/matrix-org/olm/-/blob/master/docs/olm.md>.
+//!
+//! Megolm (see `megolm.rs`) shares the cipher and the message encoding defined here.
+
+use std::convert::{TryFrom, TryInto};
+
+use aes::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit, block_padding::Pkcs7};
+use base64::Engine as _;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use ed25519_dalek::{Signer, SigningKey};
+use hmac::{Hmac, Mac};
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// Matrix encodes keys, signatures and ciphertexts as base64 without padding. Padding is accepted when
+/// decoding, as some clients send it.
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &base64::alphabet::STANDARD,
+    GeneralPurposeConfig::new()
+        .with_encode_padding(false)
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+const VERSION: u8 = 3;
+const MAC_LEN: usize = 8;
+/// message keys kept for messages that arrive out of order
+const MAX_SKIPPED_KEYS: usize = 40;
+/// how far ahead of its chain a message may be
+const MAX_MESSAGE_GAP: u32 = 2000;
+const MAX_RECEIVER_CHAINS: usize = 5;
+/// one-time keys kept by an account; the server is given more when it runs low
+pub const MAX_ONE_TIME_KEYS: usize = 50;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// the message or key couldn't be parsed
+    Decode,
+    Version,
+    /// the message was tampered with, or isn't for this session
+    Mac,
+    Signature,
+    /// a pre-key message used a one-time key this account doesn't have
+    UnknownOneTimeKey,
+    /// the message is too far ahead of, or too far behind, what the session can decrypt
+    MessageIndex,
+    Padding,
+}
+
+pub fn encode(bytes: &[u8]) -> String { BASE64.encode(bytes) }
+
+pub fn decode(text: &str) -> Result<Vec<u8>, Error> { BASE64.decode(text).or(Err(Error::Decode)) }
+
+/// Decodes a base64 Curve25519 or Ed25519 public key.
+pub fn decode_key(text: &str) -> Result<[u8; 32], Error> { decode(text)?.try_into().or(Err(Error::Decode)) }
+
+pub(crate) fn hkdf(salt: Option<&[u8]>, secret: &[u8], info: &[u8], out: &mut [u8]) {
+    hkdf::Hkdf::<Sha256>::new(salt, secret).expand(info, out).expect("HKDF output too long");
+}
+
+pub(crate) fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+/// AES-256-CBC for the ciphertext and a truncated HMAC-SHA-256 over the message, with keys derived from a
+/// message key. `info` is `OLM_KEYS` for Olm and `MEGOLM_KEYS` for Megolm.
+pub(crate) struct Cipher {
+    aes_key: [u8; 32],
+    mac_key: [u8; 32],
+    iv: [u8; 16],
+}
+impl Cipher {
+    pub(crate) fn new(message_key: &[u8], info: &[u8]) -> Self {
+        let mut keys = [0u8; 80];
+        hkdf(None, message_key, info, &mut keys);
+        let mut cipher = Cipher { aes_key: [0; 32], mac_key: [0; 32], iv: [0; 16] };
+        cipher.aes_key.copy_from_slice(&keys[..32]);
+        cipher.mac_key.copy_from_slice(&keys[32..64]);
+        cipher.iv.copy_from_slice(&keys[64..]);
+        cipher
+    }
+
+    pub(crate) fn encrypt(&self, plaintext: &[u8]) -> Vec<u8> {
+        cbc::Encryptor::<aes::Aes256>::new(&self.aes_key.into(), &self.iv.into())
+            .encrypt_padded_vec_mut::<Pkcs7>(plaintext)
+    }
+
+    pub(crate) fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, Error> {
+        cbc::Decryptor::<aes::Aes256>::new(&self.aes_key.into(), &self.iv.into())
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+            .or(Err(Error::Padding))
+    }
+
+    pub(crate) fn mac(&self, message: &[u8]) -> [u8; MAC_LEN] {
+        let mut mac = [0u8; MAC_LEN];
+        mac.copy_from_slice(&hmac_sha256(&self.mac_key, message)[..MAC_LEN]);
+        mac
+    }
+
+    pub(crate) fn verify_mac(&self, message: &[u8], mac: &[u8]) -> Result<(), Error> {
+        let mut hmac = Hmac::<Sha256>::new_from_slice(&self.mac_key).unwrap();
+        hmac.update(message);
+        hmac.verify_truncated_left(mac).or(Err(Error::Mac))
+    }
+}
+
+/// Olm and Megolm messages are a version byte followed by protobuf fields: a varint key of
+/// `field << 3 | wire type`, then a varint, or a varint length and that many bytes.
+pub(crate) enum Field<'a> {
+    Int(u64),
+    Bytes(&'a [u8]),
+}
+
+pub(crate) fn push_int(out: &mut Vec<u8>, field: u8, value: u64) {
+    out.push(field << 3);
+    push_varint(out, value);
+}
+
+pub(crate) fn push_bytes(out: &mut Vec<u8>, field: u8, value: &[u8]) {
+    out.push(field << 3 | 2);
+    push_varint(out, value.len() as u64);
+    out.extend_from_slice(value);
+}
+
+fn push_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(input: &[u8], pos: &mut usize) -> Result<u64, Error> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *input.get(*pos).ok_or(Error::Decode)?;
+        *pos += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::Decode)
+}
+
+/// Checks the version byte and splits the rest of `message` into fields.
+pub(crate) fn parse_fields(message: &[u8]) -> Result<Vec<(u64, Field<'_>)>, Error> {
+    match message.first() {
+        Some(&VERSION) => (),
+        Some(_) => return Err(Error::Version),
+        None => return Err(Error::Decode),
+    }
+    let mut fields = Vec::new();
+    let mut pos = 1;
+    while pos < message.len() {
+        let key = read_varint(message, &mut pos)?;
+        let value = match key & 7 {
+            0 => Field::Int(read_varint(message, &mut pos)?),
+            2 => {
+                let len = read_varint(message, &mut pos)? as usize;
+                let end = pos.checked_add(len).filter(|&end| end <= message.len()).ok_or(Error::Decode)?;
+                let bytes = &message[pos..end];
+                pos = end;
+                Field::Bytes(bytes)
+            }
+            _ => return Err(Error::Decode),
+        };
+        fields.push((key >> 3, value));
+    }
+    Ok(fields)
+}
+
+pub(crate) fn start_message() -> Vec<u8> { vec![VERSION] }
+
+fn public_key(secret: &[u8; 32]) -> [u8; 32] { PublicKey::from(&StaticSecret::from(*secret)).to_bytes() }
+
+fn diffie_hellman(secret: &[u8; 32], public: &[u8; 32]) -> [u8; 32] {
+    StaticSecret::from(*secret).diffie_hellman(&PublicKey::from(*public)).to_bytes()
+}
+
+fn random_secret(rng: &mut (impl RngCore + CryptoRng)) -> [u8; 32] {
+    let mut secret = [0u8; 32];
+    rng.fill_bytes(&mut secret);
+    secret
+}
+
+/// A normal message: the sender's ratchet key, its index in the sender's chain, and the ciphertext.
+struct Message<'a> {
+    ratchet_key: [u8; 32],
+    index: u32,
+    ciphertext: &'a [u8],
+    /// what the MAC covers
+    authenticated: &'a [u8],
+    mac: &'a [u8],
+}
+impl<'a> Message<'a> {
+    fn encode(ratchet_key: &[u8; 32], index: u32, ciphertext: &[u8], cipher: &Cipher) -> Vec<u8> {
+        let mut message = start_message();
+        push_bytes(&mut message, 1, ratchet_key);
+        push_int(&mut message, 2, index as u64);
+        push_bytes(&mut message, 4, ciphertext);
+        let mac = cipher.mac(&message);
+        message.extend_from_slice(&mac);
+        message
+    }
+
+    fn decode(message: &'a [u8]) -> Result<Self, Error> {
+        if message.len() < 1 + MAC_LEN {
+            return Err(Error::Decode);
+        }
+        let (authenticated, mac) = message.split_at(message.len() - MAC_LEN);
+        let (mut ratchet_key, mut index, mut ciphertext) = (None, None, None);
+        for (field, value) in parse_fields(authenticated)? {
+            match (field, value) {
+                (1, Field::Bytes(key)) => ratchet_key = Some(key.try_into().or(Err(Error::Decode))?),
+                (2, Field::Int(i)) => index = Some(u32::try_from(i).or(Err(Error::Decode))?),
+                (4, Field::Bytes(text)) => ciphertext = Some(text),
+                _ => (),
+            }
+        }
+        match (ratchet_key, index, ciphertext) {
+            (Some(ratchet_key), Some(index), Some(ciphertext)) => {
+                Ok(Message { ratchet_key, index, ciphertext, authenticated, mac })
+            }
+            _ => Err(Error::Decode),
+        }
+    }
+}
+
+/// The first messages of an outbound session carry what the recipient needs to set it up: which of its
+/// one-time keys was used, the sender's base key and identity key, and a normal message.
+pub struct PreKeyMessage<'a> {
+    pub one_time_key: [u8; 32],
+    pub base_key: [u8; 32],
+    pub identity_key: [u8; 32],
+    message: &'a [u8],
+}
+impl<'a> PreKeyMessage<'a> {
+    fn encode(header: &PreKeyHeader, message: &[u8]) -> Vec<u8> {
+        let mut pre_key = start_message();
+        push_bytes(&mut pre_key, 1, &header.one_time_key);
+        push_bytes(&mut pre_key, 2, &header.base_key);
+        push_bytes(&mut pre_key, 3, &header.identity_key);
+        push_bytes(&mut pre_key, 4, message);
+        pre_key
+    }
+
+    pub fn decode(message: &'a [u8]) -> Result<Self, Error> {
+        let (mut one_time_key, mut base_key, mut identity_key, mut inner) = (None, None, None, None);
+        for (field, value) in parse_fields(message)? {
+            match (field, value) {
+                (1, Field::Bytes(key)) => one_time_key = Some(key.try_into().or(Err(Error::Decode))?),
+                (2, Field::Bytes(key)) => base_key = Some(key.try_into().or(Err(Error::Decode))?),
+                (3, Field::Bytes(key)) => identity_key = Some(key.try_into().or(Err(Error::Decode))?),
+                (4, Field::Bytes(bytes)) => inner = Some(bytes),
+                _ => (),
+            }
+        }
+        match (one_time_key, base_key, identity_key, inner) {
+            (Some(one_time_key), Some(base_key), Some(identity_key), Some(message)) => {
+                Ok(PreKeyMessage { one_time_key, base_key, identity_key, message })
+            }
+            _ => Err(Error::Decode),
+        }
+    }
+
+    fn session_id(&self) -> [u8; 32] { session_id(&self.identity_key, &self.base_key, &self.one_time_key) }
+}
+
+fn session_id(identity_key: &[u8; 32], base_key: &[u8; 32], one_time_key: &[u8; 32]) -> [u8; 32] {
+    let mut hash = Sha256::new();
+    hash.update(identity_key);
+    hash.update(base_key);
+    hash.update(one_time_key);
+    hash.finalize().into()
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ChainKey {
+    key: [u8; 32],
+    index: u32,
+}
+impl ChainKey {
+    fn message_key(&self) -> [u8; 32] { hmac_sha256(&self.key, b"\x01") }
+
+    fn advance(&mut self) {
+        self.key = hmac_sha256(&self.key, b"\x02");
+        self.index += 1;
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct SenderChain {
+    ratchet_secret: [u8; 32],
+    chain: ChainKey,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ReceiverChain {
+    ratchet_key: [u8; 32],
+    chain: ChainKey,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct SkippedKey {
+    ratchet_key: [u8; 32],
+    index: u32,
+    message_key: [u8; 32],
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct PreKeyHeader {
+    one_time_key: [u8; 32],
+    base_key: [u8; 32],
+    identity_key: [u8; 32],
+}
+
+/// One side of an Olm session between two devices.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Session {
+    id: [u8; 32],
+    root_key: [u8; 32],
+    sender: Option<SenderChain>,
+    /// newest first
+    receivers: Vec<ReceiverChain>,
+    skipped: Vec<SkippedKey>,
+    /// Sessions we started send pre-key messages until the other side replies.
+    pre_key: Option<PreKeyHeader>,
+}
+
+impl Session {
+    /// Starts a session with a device, given its identity key and one of its one-time keys.
+    pub fn new_outbound(
+        rng: &mut (impl RngCore + CryptoRng),
+        identity_secret: &[u8; 32],
+        their_identity_key: &[u8; 32],
+        their_one_time_key: &[u8; 32],
+    ) -> Self {
+        let base_secret = random_secret(rng);
+        let ratchet_secret = random_secret(rng);
+        let mut shared = [0u8; 96];
+        shared[..32].copy_from_slice(&diffie_hellman(identity_secret, their_one_time_key));
+        shared[32..64].copy_from_slice(&diffie_hellman(&base_secret, their_identity_key));
+        shared[64..].copy_from_slice(&diffie_hellman(&base_secret, their_one_time_key));
+        let mut keys = [0u8; 64];
+        hkdf(None, &shared, b"OLM_ROOT", &mut keys);
+        let header = PreKeyHeader {
+            one_time_key: *their_one_time_key,
+            base_key: public_key(&base_secret),
+            identity_key: public_key(identity_secret),
+        };
+        Session {
+            id: session_id(&header.identity_key, &header.base_key, &header.one_time_key),
+            root_key: keys[..32].try_into().unwrap(),
+            sender: Some(SenderChain {
+                ratchet_secret,
+                chain: ChainKey { key: keys[32..].try_into().unwrap(), index: 0 },
+            }),
+            receivers: Vec::new(),
+            skipped: Vec::new(),
+            pre_key: Some(header),
+        }
+    }
+
+    /// Sets up the session a pre-key message started, and decrypts the message.
+    fn new_inbound(
+        identity_secret: &[u8; 32],
+        one_time_secret: &[u8; 32],
+        pre_key: &PreKeyMessage,
+    ) -> Result<(Self, Vec<u8>), Error> {
+        let mut shared = [0u8; 96];
+        shared[..32].copy_from_slice(&diffie_hellman(one_time_secret, &pre_key.identity_key));
+        shared[32..64].copy_from_slice(&diffie_hellman(identity_secret, &pre_key.base_key));
+        shared[64..].copy_from_slice(&diffie_hellman(one_time_secret, &pre_key.base_key));
+        let mut keys = [0u8; 64];
+        hkdf(None, &shared, b"OLM_ROOT", &mut keys);
+        let message = Message::decode(pre_key.message)?;
+        let mut session = Session {
+            id: pre_key.session_id(),
+            root_key: keys[..32].try_into().unwrap(),
+            sender: None,
+            receivers: vec![ReceiverChain {
+                ratchet_key: message.ratchet_key,
+                chain: ChainKey { key: keys[32..].try_into().unwrap(), index: 0 },
+            }],
+            skipped: Vec::new(),
+            pre_key: None,
+        };
+        let plaintext = session.decrypt_message(&message)?;
+        Ok((session, plaintext))
+    }
+
+    pub fn session_id(&self) -> String { encode(&self.id) }
+
+    /// Whether a pre-key message belongs to this session.
+    pub fn matches(&self, pre_key: &PreKeyMessage) -> bool { pre_key.session_id() == self.id }
+
+    /// Returns the Olm message type, 0 for pre-key messages and 1 for normal ones, and the message.
+    pub fn encrypt(&mut self, rng: &mut (impl RngCore + CryptoRng), plaintext: &[u8]) -> (u8, Vec<u8>) {
+        if self.sender.is_none() {
+            // our turn to advance the root ratchet
+            let ratchet_secret = random_secret(rng);
+            let (root_key, chain_key) =
+                advance_root(&self.root_key, &ratchet_secret, &self.receivers[0].ratchet_key);
+            self.root_key = root_key;
+            self.sender = Some(SenderChain { ratchet_secret, chain: ChainKey { key: chain_key, index: 0 } });
+        }
+        let sender = self.sender.as_mut().unwrap();
+        let cipher = Cipher::new(&sender.chain.message_key(), b"OLM_KEYS");
+        let message = Message::encode(
+            &public_key(&sender.ratchet_secret),
+            sender.chain.index,
+            &cipher.encrypt(plaintext),
+            &cipher,
+        );
+        sender.chain.advance();
+        match &self.pre_key {
+            Some(header) => (0, PreKeyMessage::encode(header, &message)),
+            None => (1, message),
+        }
+    }
+
+    /// Decrypts a message of Olm type `message_type`. A failed decryption leaves the session as it was.
+    pub fn decrypt(&mut self, message_type: u8, message: &[u8]) -> Result<Vec<u8>, Error> {
+        let message = match message_type {
+            0 => {
+                let pre_key = PreKeyMessage::decode(message)?;
+                if !self.matches(&pre_key) {
+                    return Err(Error::Mac);
+                }
+                Message::decode(pre_key.message)?
+            }
+            _ => Message::decode(message)?,
+        };
+        self.decrypt_message(&message)
+    }
+
+    fn decrypt_message(&mut self, message: &Message) -> Result<Vec<u8>, Error> {
+        let plaintext = match self.receivers.iter().position(|r| r.ratchet_key == message.ratchet_key) {
+            Some(i) if message.index < self.receivers[i].chain.index => {
+                let skipped = self
+                    .skipped
+                    .iter()
+                    .position(|k| k.ratchet_key == message.ratchet_key && k.index == message.index)
+                    .ok_or(Error::MessageIndex)?;
+                let plaintext = open(&self.skipped[skipped].message_key, message)?;
+                self.skipped.remove(skipped);
+                plaintext
+            }
+            Some(i) => {
+                let mut chain = self.receivers[i].chain.clone();
+                let mut skipped = Vec::new();
+                let plaintext = open_at(&mut chain, message, &mut skipped)?;
+                self.receivers[i].chain = chain;
+                self.remember_skipped(skipped);
+                plaintext
+            }
+            None => {
+                // the other side advanced the root ratchet
+                let sender = self.sender.as_ref().ok_or(Error::Mac)?;
+                let (root_key, chain_key) =
+                    advance_root(&self.root_key, &sender.ratchet_secret, &message.ratchet_key);
+                let mut chain = ChainKey { key: chain_key, index: 0 };
+                let mut skipped = Vec::new();
+                let plaintext = open_at(&mut chain, message, &mut skipped)?;
+                self.root_key = root_key;
+                self.receivers.insert(0, ReceiverChain { ratchet_key: message.ratchet_key, chain });
+                self.receivers.truncate(MAX_RECEIVER_CHAINS);
+                self.sender = None;
+                self.remember_skipped(skipped);
+                plaintext
+            }
+        };
+        // the other side has the session, so there's no need to send pre-key messages any more
+        self.pre_key = None;
+        Ok(plaintext)
+    }
+
+    fn remember_skipped(&mut self, skipped: Vec<SkippedKey>) {
+        self.skipped.extend(skipped);
+        let excess = self.skipped.len().saturating_sub(MAX_SKIPPED_KEYS);
+        self.skipped.drain(..excess);
+    }
+}
+
+fn advance_root(root_key: &[u8; 32], our_secret: &[u8; 32], their_key: &[u8; 32]) -> ([u8; 32], [u8; 32]) {
+    let mut keys = [0u8; 64];
+    hkdf(Some(root_key), &diffie_hellman(our_secret, their_key), b"OLM_RATCHET", &mut keys);
+    (keys[..32].try_into().unwrap(), keys[32..].try_into().unwrap())
+}
+
+fn open(message_key: &[u8; 32], message: &Message) -> Result<Vec<u8>, Error> {
+    let cipher = Cipher::new(message_key, b"OLM_KEYS");
+    cipher.verify_mac(message.authenticated, message.mac)?;
+    cipher.decrypt(message.ciphertext)
+}
+
+/// Advances `chain` to the message, keeping the keys it skips, and decrypts the message.
+fn open_at(chain: &mut ChainKey, message: &Message, skipped: &mut Vec<SkippedKey>) -> Result<Vec<u8>, Error> {
+    if message.index - chain.index > MAX_MESSAGE_GAP {
+        return Err(Error::MessageIndex);
+    }
+    while chain.index < message.index {
+        skipped.push(SkippedKey {
+            ratchet_key: message.ratchet_key,
+            index: chain.index,
+            message_key: chain.message_key(),
+        });
+        chain.advance();
+    }
+    let plaintext = open(&chain.message_key(), message)?;
+    chain.advance();
+    Ok(plaintext)
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct OneTimeKey {
+    id: u32,
+    secret: [u8; 32],
+    published: bool,
+}
+
+/// A device's long-term keys: an Ed25519 key to sign with and a Curve25519 identity key, and the
+/// one-time keys others use to start sessions with it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Account {
+    signing_secret: [u8; 32],
+    identity_secret: [u8; 32],
+    one_time_keys: Vec<OneTimeKey>,
+    next_key_id: u32,
+}
+
+impl Account {
+    pub fn new(rng: &mut (impl RngCore + CryptoRng)) -> Self {
+        Account {
+            signing_secret: random_secret(rng),
+            identity_secret: random_secret(rng),
+            one_time_keys: Vec::new(),
+            next_key_id: 1,
+        }
+    }
+
+    pub fn ed25519_key(&self) -> String {
+        encode(SigningKey::from_bytes(&self.signing_secret).verifying_key().as_bytes())
+    }
+
+    pub fn curve25519_key(&self) -> String { encode(&public_key(&self.identity_secret)) }
+
+    pub fn sign(&self, message: &[u8]) -> String {
+        encode(&SigningKey::from_bytes(&self.signing_secret).sign(message).to_bytes())
+    }
+
+    /// Makes one-time keys until there are `MAX_ONE_TIME_KEYS`, dropping the oldest published ones if the
+    /// server asks for more than that.
+    pub fn generate_one_time_keys(&mut self, rng: &mut (impl RngCore + CryptoRng), count: usize) {
+        for _ in 0..count {
+            self.one_time_keys.push(OneTimeKey {
+                id: self.next_key_id,
+                secret: random_secret(rng),
+                published: false,
+            });
+            self.next_key_id = self.next_key_id.wrapping_add(1);
+        }
+        let excess = self.one_time_keys.len().saturating_sub(MAX_ONE_TIME_KEYS);
+        self.one_time_keys.drain(..excess);
+    }
+
+    /// The one-time keys that haven't been uploaded yet, as key ID and public key.
+    pub fn unpublished_one_time_keys(&self) -> Vec<(String, String)> {
+        self.one_time_keys
+            .iter()
+            .filter(|key| !key.published)
+            .map(|key| (encode(&key.id.to_be_bytes()), encode(&public_key(&key.secret))))
+            .collect()
+    }
+
+    pub fn mark_keys_as_published(&mut self) {
+        for key in self.one_time_keys.iter_mut() {
+            key.published = true;
+        }
+    }
+
+    pub fn create_outbound_session(
+        &self,
+        rng: &mut (impl RngCore + CryptoRng),
+        their_identity_key: &[u8; 32],
+        their_one_time_key: &[u8; 32],
+    ) -> Session {
+        Session::new_outbound(rng, &self.identity_secret, their_identity_key, their_one_time_key)
+    }
+
+    /// Sets up the session a pre-key message from the device with identity key `their_identity_key`
+    /// started, and returns it with the decrypted message. The one-time key it used is used up.
+    pub fn create_inbound_session(
+        &mut self,
+        their_identity_key: &[u8; 32],
+        message: &[u8],
+    ) -> Result<(Session, Vec<u8>), Error> {
+        let pre_key = PreKeyMessage::decode(message)?;
+        if &pre_key.identity_key != their_identity_key {
+            return Err(Error::Mac);
+        }
+        let index = self
+            .one_time_keys
+            .iter()
+            .position(|key| public_key(&key.secret) == pre_key.one_time_key)
+            .ok_or(Error::UnknownOneTimeKey)?;
+        let result =
+            Session::new_inbound(&self.identity_secret, &self.one_time_keys[index].secret, &pre_key)?;
+        self.one_time_keys.remove(index);
+        Ok(result)
+    }
+}
+
+/// Checks an Ed25519 `signature` of `message`, both base64 as in Matrix.
+pub fn verify_signature(key: &str, message: &[u8], signature: &str) -> Result<(), Error> {
+    let key = ed25519_dalek::VerifyingKey::from_bytes(&decode_key(key)?).or(Err(Error::Signature))?;
+    let signature: [u8; 64] = decode(signature)?.try_into().or(Err(Error::Decode))?;
+    key.verify_strict(message, &ed25519_dalek::Signature::from_bytes(&signature)).or(Err(Error::Signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rng() -> rand_core::OsRng { rand_core::OsRng }
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_varint() {
+        for value in [0u64, 1, 127, 128, 300, 0xffff_ffff, u64::MAX] {
+            let mut out = Vec::new();
+            push_varint(&mut out, value);
+            let mut pos = 0;
+            assert_eq!(read_varint(&out, &mut pos), Ok(value));
+            assert_eq!(pos, out.len());
+        }
+        assert_eq!(read_varint(&[0x80], &mut 0), Err(Error::Decode));
+    }
+
+    /// The published vectors of the primitives, as libolm's crypto tests use them: RFC 5869 test case 1,
+    /// RFC 4231 test case 2, RFC 7748 section 6.1 and RFC 8032 test 1.
+    #[test]
+    fn test_primitives() {
+        let mut okm = [0u8; 42];
+        hkdf(Some(&hex("000102030405060708090a0b0c")), &[0x0b; 22], &hex("f0f1f2f3f4f5f6f7f8f9"), &mut okm);
+        assert_eq!(
+            okm.to_vec(),
+            hex("3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865")
+        );
+        assert_eq!(
+            hmac_sha256(b"Jefe", b"what do ya want for nothing?").to_vec(),
+            hex("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")
+        );
+
+        let alice: [u8; 32] =
+            hex("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a").try_into().unwrap();
+        let bob: [u8; 32] =
+            hex("5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb").try_into().unwrap();
+        assert_eq!(
+            public_key(&alice).to_vec(),
+            hex("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a")
+        );
+        assert_eq!(
+            public_key(&bob).to_vec(),
+            hex("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f")
+        );
+        let shared = hex("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742");
+        assert_eq!(diffie_hellman(&alice, &public_key(&bob)).to_vec(), shared);
+        assert_eq!(diffie_hellman(&bob, &public_key(&alice)).to_vec(), shared);
+
+        let key = encode(&hex("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a"));
+        let signature = encode(&hex(
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+        ));
+        assert_eq!(verify_signature(&key, b"", &signature), Ok(()));
+        assert_eq!(verify_signature(&key, b"\x72", &signature), Err(Error::Signature));
+    }
+
+    /// vodozemac's message vectors.
+    #[test]
+    fn test_messages() {
+        let pre_key = decode(
+            "AwoghAEuxPZ+w7M3pgUae4tDNiggUpOsQ/zci457VAtiAEYSIO3xOKRDBWKicIfxjSmYCYZ9DD4RMLjvvclbMlE5yIEWGiApLrCr\
+             853CKlPpW4Bi7S8ykRcejJ0lq7AfYLXKCjKdHSJPAwoghw3+P+cajhWj9Qzp5g87h+tbpiuh5wEaeUppqmWqug4QASIgRhZ2cgZc\
+             IWQbIa23R7U4y1Mo1R/tLCaMU+xjzRV5smGsCrJ6AHwktg",
+        )
+        .unwrap();
+        let pre_key = PreKeyMessage::decode(&pre_key).unwrap();
+        let message = Message::decode(pre_key.message).unwrap();
+        assert_eq!(
+            message.ciphertext,
+            [
+                70, 22, 118, 114, 6, 92, 33, 100, 27, 33, 173, 183, 71, 181, 56, 203, 83, 40, 213, 31, 237,
+                44, 38, 140, 83, 236, 99, 205, 21, 121, 178, 97
+            ]
+        );
+        assert_eq!(message.index, 1);
+
+        let message =
+            decode("AwogI7JhE/UsMZqXKb3xV6kUZWoJc6jTm2+AIgWYmaETIR0QASIQ+X2zb7kEX/3JvoLspcNBcLWOFXYpV0nS")
+                .unwrap();
+        let message = Message::decode(&message).unwrap();
+        assert_eq!(
+            message.ciphertext,
+            [249, 125, 179, 111, 185, 4, 95, 253, 201, 190, 130, 236, 165, 195, 65, 112]
+        );
+        assert_eq!(message.index, 1);
+    }
+
+    #[test]
+    fn test_session_round_trip() {
+        let alice = Account::new(&mut rng());
+        let mut bob = Account::new(&mut rng());
+        bob.generate_one_time_keys(&mut rng(), 2);
+        let (_, one_time_key) = bob.unpublished_one_time_keys().remove(0);
+        bob.mark_keys_as_published();
+        let bob_identity = decode_key(&bob.curve25519_key()).unwrap();
+        let alice_identity = decode_key(&alice.curve25519_key()).unwrap();
+
+        let mut outbound =
+            alice.create_outbound_session(&mut rng(), &bob_identity, &decode_key(&one_time_key).unwrap());
+        let (kind, first) = outbound.encrypt(&mut rng(), b"first");
+        assert_eq!(kind, 0);
+        let (kind, second) = outbound.encrypt(&mut rng(), b"second");
+        assert_eq!(kind, 0);
+
+        let (mut inbound, plaintext) = bob.create_inbound_session(&alice_identity, &first).unwrap();
+        assert_eq!(plaintext, b"first");
+        assert_eq!(inbound.session_id(), outbound.session_id());
+        // the one-time key is used up
+        assert_eq!(bob.create_inbound_session(&alice_identity, &first).err(), Some(Error::UnknownOneTimeKey));
+        assert!(inbound.matches(&PreKeyMessage::decode(&second).unwrap()));
+        assert_eq!(inbound.decrypt(0, &second).unwrap(), b"second");
+
+        // replies advance the ratchet, and out of order messages still decrypt
+        let (kind, reply) = inbound.encrypt(&mut rng(), b"reply");
+        assert_eq!(kind, 1);
+        assert_eq!(outbound.decrypt(1, &reply).unwrap(), b"reply");
+        let (kind, late) = outbound.encrypt(&mut rng(), b"late");
+        assert_eq!(kind, 1);
+        let (_, early) = outbound.encrypt(&mut rng(), b"early");
+        assert_eq!(inbound.decrypt(1, &early).unwrap(), b"early");
+        assert_eq!(inbound.decrypt(1, &late).unwrap(), b"late");
+        // a message only decrypts once
+        assert_eq!(inbound.decrypt(1, &late), Err(Error::MessageIndex));
+
+        let mut tampered = reply.clone();
+        let middle = tampered.len() / 2;
+        tampered[middle] ^= 1;
+        assert!(outbound.decrypt(1, &tampered).is_err());
+    }
+
+    #[test]
+    fn test_signatures() {
+        let account = Account::new(&mut rng());
+        let signature = account.sign(b"message");
+        assert_eq!(verify_signature(&account.ed25519_key(), b"message", &signature), Ok(()));
+        assert_eq!(verify_signature(&account.ed25519_key(), b"massage", &signature), Err(Error::Signature));
+    }
+}
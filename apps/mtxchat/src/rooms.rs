@@ -0,0 +1,196 @@
+//! The rooms the user is in or invited to, as syncs tell of them. The listener keeps this up to date, and
+//! the menus read it for the room list.
+
+use std::collections::BTreeMap;
+
+use crate::web::SyncResponse;
+
+#[derive(Debug, Default)]
+pub struct Room {
+    pub room_id: String,
+    pub name: Option<String>,
+    pub alias: Option<String>,
+    /// notifications the user hasn't read
+    pub unread: u64,
+    /// the other users typing
+    pub typing: Vec<String>,
+    /// who invited the user, while it's an invite
+    pub inviter: Option<String>,
+    pub invited: bool,
+}
+
+impl Room {
+    /// What the room is called in lists.
+    pub fn title(&self) -> &str { self.name.as_deref().or(self.alias.as_deref()).unwrap_or(&self.room_id) }
+
+    /// The `libs/chat` Dialogue the room is kept in: its canonical alias, so that the Dialogues made by
+    /// naming the room stay the same, or else its ID.
+    pub fn dialogue_key(&self) -> &str { self.alias.as_deref().unwrap_or(&self.room_id) }
+}
+
+#[derive(Default)]
+pub struct Rooms {
+    rooms: BTreeMap<String, Room>,
+    /// where the next sync starts
+    pub since: Option<String>,
+}
+
+impl Rooms {
+    pub fn new() -> Self { Rooms::default() }
+
+    fn entry(&mut self, room_id: &str) -> &mut Room {
+        self.rooms
+            .entry(room_id.to_string())
+            .or_insert_with(|| Room { room_id: room_id.to_string(), ..Default::default() })
+    }
+
+    /// Takes in what a sync says about rooms; `user_id` isn't counted as typing.
+    pub fn update(&mut self, sync: &SyncResponse, user_id: &str) {
+        self.since = Some(sync.next_batch.clone());
+        for joined in &sync.joined {
+            let room = self.entry(&joined.room_id);
+            room.invited = false;
+            room.inviter = None;
+            if joined.name.is_some() {
+                room.name = joined.name.clone();
+            }
+            if joined.alias.is_some() {
+                room.alias = joined.alias.clone();
+            }
+            if let Some(unread) = joined.unread {
+                room.unread = unread;
+            }
+            if let Some(typing) = &joined.typing {
+                room.typing = typing.iter().filter(|user| *user != user_id).cloned().collect();
+            }
+        }
+        for invite in &sync.invited {
+            let room = self.entry(&invite.room_id);
+            room.invited = true;
+            room.inviter = invite.inviter.clone();
+            if invite.name.is_some() {
+                room.name = invite.name.clone();
+            }
+        }
+        for room_id in &sync.parted {
+            self.rooms.remove(room_id);
+        }
+    }
+
+    pub fn get(&self, room_id: &str) -> Option<&Room> { self.rooms.get(room_id) }
+
+    pub fn is_joined(&self, room_id: &str) -> bool { self.get(room_id).is_some_and(|room| !room.invited) }
+
+    /// Records that the user joined a room, by the alias they gave if the room has none yet.
+    pub fn joined(&mut self, room_id: &str, alias: Option<&str>) {
+        let room = self.entry(room_id);
+        room.invited = false;
+        room.inviter = None;
+        if room.alias.is_none() {
+            room.alias = alias.map(String::from);
+        }
+    }
+
+    pub fn remove(&mut self, room_id: &str) { self.rooms.remove(room_id); }
+
+    /// The user has read the room.
+    pub fn read(&mut self, room_id: &str) {
+        if let Some(room) = self.rooms.get_mut(room_id) {
+            room.unread = 0;
+        }
+    }
+
+    /// Invites first, then rooms with unread notifications, then the rest, by title.
+    pub fn list(&self) -> Vec<&Room> {
+        let mut rooms: Vec<&Room> = self.rooms.values().collect();
+        rooms.sort_by(|a, b| {
+            (!a.invited, a.unread == 0, a.title().to_lowercase()).cmp(&(
+                !b.invited,
+                b.unread == 0,
+                b.title().to_lowercase(),
+            ))
+        });
+        rooms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web::{Invite, JoinedRoom};
+
+    fn joined(room_id: &str, name: Option<&str>, unread: Option<u64>) -> JoinedRoom {
+        JoinedRoom {
+            room_id: room_id.to_string(),
+            msgs: Vec::new(),
+            name: name.map(String::from),
+            alias: None,
+            unread,
+            typing: None,
+        }
+    }
+
+    fn sync(joined: Vec<JoinedRoom>, invited: Vec<Invite>, parted: Vec<&str>) -> SyncResponse {
+        SyncResponse {
+            next_batch: "s1".to_string(),
+            joined,
+            invited,
+            parted: parted.into_iter().map(String::from).collect(),
+            to_device: Vec::new(),
+            one_time_keys: None,
+            changed: Vec::new(),
+            left: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_update() {
+        let mut rooms = Rooms::new();
+        let invite = Invite {
+            room_id: "!c:x".to_string(),
+            name: Some("Cats".to_string()),
+            inviter: Some("@bob:x".to_string()),
+        };
+        rooms.update(
+            &sync(
+                vec![joined("!a:x", Some("Apples"), Some(0)), joined("!b:x", None, Some(3))],
+                vec![invite],
+                vec![],
+            ),
+            "@me:x",
+        );
+        assert_eq!(rooms.since.as_deref(), Some("s1"));
+        let titles: Vec<&str> = rooms.list().iter().map(|room| room.title()).collect();
+        assert_eq!(titles, ["Cats", "!b:x", "Apples"]);
+        assert!(rooms.is_joined("!a:x"));
+        assert!(!rooms.is_joined("!c:x"));
+
+        // a later sync only tells what changed
+        let mut typing = joined("!a:x", None, None);
+        typing.typing = Some(vec!["@me:x".to_string(), "@bob:x".to_string()]);
+        rooms.update(&sync(vec![typing, joined("!c:x", None, Some(1))], vec![], vec!["!b:x"]), "@me:x");
+        let a = rooms.get("!a:x").unwrap();
+        assert_eq!((a.title(), a.unread), ("Apples", 0));
+        assert_eq!(a.typing, ["@bob:x"]);
+        assert!(rooms.get("!b:x").is_none());
+        let c = rooms.get("!c:x").unwrap();
+        assert!(!c.invited && c.inviter.is_none());
+        assert_eq!((c.title(), c.unread), ("Cats", 1));
+        rooms.read("!c:x");
+        assert_eq!(rooms.get("!c:x").unwrap().unread, 0);
+    }
+
+    #[test]
+    fn test_dialogue_key() {
+        let mut rooms = Rooms::new();
+        rooms.joined("!a:x", None);
+        assert_eq!(rooms.get("!a:x").unwrap().dialogue_key(), "!a:x");
+        rooms.joined("!a:x", Some("#apples:x"));
+        assert_eq!(rooms.get("!a:x").unwrap().dialogue_key(), "#apples:x");
+        let mut named = joined("!a:x", None, None);
+        named.alias = Some("#pommes:x".to_string());
+        rooms.update(&sync(vec![named], vec![], vec![]), "@me:x");
+        rooms.joined("!a:x", Some("#apples:x"));
+        assert_eq!(rooms.get("!a:x").unwrap().dialogue_key(), "#pommes:x");
+    }
+}
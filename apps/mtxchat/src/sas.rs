@@ -0,0 +1,636 @@
+//! Device verification by short authentication string, the `m.sas.v1` method: both devices agree on a
+//! key, and their users compare seven emoji derived from it. If they match, each device tells the other,
+//! with a MAC under that key, what its own signing key is.
+//!
+//! Only the methods current clients all offer are supported: `curve25519-hkdf-sha256` key agreement and
+//! `hkdf-hmac-sha256.v2` MACs.
+
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+use ureq::serde_json::{Value, json};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use crate::e2ee::canonical_json;
+use crate::olm::{decode_key, encode, hkdf, hmac_sha256};
+
+pub const REQUEST: &str = "m.key.verification.request";
+pub const READY: &str = "m.key.verification.ready";
+pub const START: &str = "m.key.verification.start";
+pub const ACCEPT: &str = "m.key.verification.accept";
+pub const KEY: &str = "m.key.verification.key";
+pub const MAC: &str = "m.key.verification.mac";
+pub const DONE: &str = "m.key.verification.done";
+pub const CANCEL: &str = "m.key.verification.cancel";
+
+const METHOD: &str = "m.sas.v1";
+const KEY_AGREEMENT: &str = "curve25519-hkdf-sha256";
+const HASH: &str = "sha256";
+const MAC_METHOD: &str = "hkdf-hmac-sha256.v2";
+const SHORT_CODES: [&str; 2] = ["decimal", "emoji"];
+
+/// The emoji of the spec, by index, with their English names, which are what users read out.
+pub const EMOJI: [(&str, &str); 64] = [
+    ("🐶", "Dog"),
+    ("🐱", "Cat"),
+    ("🦁", "Lion"),
+    ("🐎", "Horse"),
+    ("🦄", "Unicorn"),
+    ("🐷", "Pig"),
+    ("🐘", "Elephant"),
+    ("🐰", "Rabbit"),
+    ("🐼", "Panda"),
+    ("🐓", "Rooster"),
+    ("🐧", "Penguin"),
+    ("🐢", "Turtle"),
+    ("🐟", "Fish"),
+    ("🐙", "Octopus"),
+    ("🦋", "Butterfly"),
+    ("🌷", "Flower"),
+    ("🌳", "Tree"),
+    ("🌵", "Cactus"),
+    ("🍄", "Mushroom"),
+    ("🌏", "Globe"),
+    ("🌙", "Moon"),
+    ("☁️", "Cloud"),
+    ("🔥", "Fire"),
+    ("🍌", "Banana"),
+    ("🍎", "Apple"),
+    ("🍓", "Strawberry"),
+    ("🌽", "Corn"),
+    ("🍕", "Pizza"),
+    ("🎂", "Cake"),
+    ("❤️", "Heart"),
+    ("😀", "Smiley"),
+    ("🤖", "Robot"),
+    ("🎩", "Hat"),
+    ("👓", "Glasses"),
+    ("🔧", "Spanner"),
+    ("🎅", "Santa"),
+    ("👍", "Thumbs Up"),
+    ("☂️", "Umbrella"),
+    ("⌛", "Hourglass"),
+    ("⏰", "Clock"),
+    ("🎁", "Gift"),
+    ("💡", "Light Bulb"),
+    ("📕", "Book"),
+    ("✏️", "Pencil"),
+    ("📎", "Paperclip"),
+    ("✂️", "Scissors"),
+    ("🔒", "Lock"),
+    ("🔑", "Key"),
+    ("🔨", "Hammer"),
+    ("☎️", "Telephone"),
+    ("🏁", "Flag"),
+    ("🚂", "Train"),
+    ("🚲", "Bicycle"),
+    ("✈️", "Aeroplane"),
+    ("🚀", "Rocket"),
+    ("🏆", "Trophy"),
+    ("⚽", "Ball"),
+    ("🎸", "Guitar"),
+    ("🎺", "Trumpet"),
+    ("🔔", "Bell"),
+    ("⚓", "Anchor"),
+    ("🎧", "Headphones"),
+    ("📁", "Folder"),
+    ("📌", "Pin"),
+];
+
+/// What the users compare.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ShortCode {
+    Emoji([usize; 7]),
+    Decimal([u16; 3]),
+}
+
+impl ShortCode {
+    fn new(bytes: &[u8; 6], emoji: bool) -> Self {
+        if emoji {
+            let bits = bytes.iter().fold(0u64, |bits, &byte| bits << 8 | byte as u64);
+            let mut indices = [0usize; 7];
+            for (i, index) in indices.iter_mut().enumerate() {
+                *index = (bits >> (42 - 6 * i) & 0x3f) as usize;
+            }
+            ShortCode::Emoji(indices)
+        } else {
+            let b: Vec<u16> = bytes.iter().map(|&b| b as u16).collect();
+            ShortCode::Decimal([
+                (b[0] << 5 | b[1] >> 3) + 1000,
+                ((b[1] & 0x7) << 10 | b[2] << 2 | b[3] >> 6) + 1000,
+                ((b[3] & 0x3f) << 7 | b[4] >> 1) + 1000,
+            ])
+        }
+    }
+
+    /// One emoji and its name per line, or the three numbers.
+    pub fn describe(&self) -> String {
+        match self {
+            ShortCode::Emoji(indices) => indices
+                .iter()
+                .map(|&i| format!("{} {}", EMOJI[i].0, EMOJI[i].1))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            ShortCode::Decimal(numbers) => format!("{} {} {}", numbers[0], numbers[1], numbers[2]),
+        }
+    }
+}
+
+/// What the device has to do next for a verification.
+#[derive(Debug)]
+pub enum Step {
+    /// Send a to-device event of this type to the other device.
+    Send(&'static str, Value),
+    /// Ask the user whether to verify with the other device, and tell `accept()` or `decline()`.
+    Ask,
+    /// Show the short code and ask the user if the other device shows the same, and tell `confirm()`.
+    Compare(ShortCode),
+    /// The other device's signing key is verified.
+    Verified,
+    /// The verification ended. The reason is for the log.
+    Cancelled(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// we sent a request
+    Requested,
+    /// the other device sent a request or a start, and the user hasn't answered yet
+    Asking,
+    /// we sent ready
+    Ready,
+    /// we sent start
+    Started,
+    /// we sent accept, or sent our key as the starter
+    KeysSent,
+    Comparing,
+    /// the user confirmed and we sent our MAC
+    Confirmed,
+    Done,
+    Cancelled,
+}
+
+/// One verification with one of another user's devices -- or one of our own.
+pub struct Verification {
+    pub transaction_id: String,
+    our_user: String,
+    our_device: String,
+    /// our device's Ed25519 key, what we vouch for
+    our_key: String,
+    pub their_user: String,
+    /// not known until they answer, if we made the request
+    pub their_device: Option<String>,
+    /// The Ed25519 key their device published. Their MAC must cover this key for the verification to
+    /// succeed.
+    pub their_key: Option<String>,
+    state: State,
+    /// our ephemeral Curve25519 key
+    secret: [u8; 32],
+    their_public: Option<[u8; 32]>,
+    /// the `start` content, whoever sent it
+    start: Option<Value>,
+    we_started: bool,
+    commitment: Option<String>,
+    emoji: bool,
+    /// a MAC that came before the user confirmed
+    their_mac: Option<Value>,
+}
+
+impl Verification {
+    fn new(
+        rng: &mut (impl RngCore + CryptoRng),
+        transaction_id: &str,
+        ours: (&str, &str, &str),
+        their_user: &str,
+        state: State,
+    ) -> Self {
+        let mut secret = [0u8; 32];
+        rng.fill_bytes(&mut secret);
+        Verification {
+            transaction_id: transaction_id.to_string(),
+            our_user: ours.0.to_string(),
+            our_device: ours.1.to_string(),
+            our_key: ours.2.to_string(),
+            their_user: their_user.to_string(),
+            their_device: None,
+            their_key: None,
+            state,
+            secret,
+            their_public: None,
+            start: None,
+            we_started: false,
+            commitment: None,
+            emoji: true,
+            their_mac: None,
+        }
+    }
+
+    /// Starts verifying with another user's devices. `ours` is our user, device and Ed25519 key. The
+    /// request goes to all of their devices; the first to answer is the one verified.
+    pub fn request(
+        rng: &mut (impl RngCore + CryptoRng),
+        transaction_id: &str,
+        ours: (&str, &str, &str),
+        their_user: &str,
+        timestamp: u64,
+    ) -> (Self, Value) {
+        let verification = Verification::new(rng, transaction_id, ours, their_user, State::Requested);
+        let request = json!({
+            "from_device": ours.1,
+            "methods": [METHOD],
+            "timestamp": timestamp,
+            "transaction_id": transaction_id,
+        });
+        (verification, request)
+    }
+
+    /// A verification another device started with a request, or with a start. Returns `None` for other
+    /// events, which belong to verifications we don't know.
+    pub fn incoming(
+        rng: &mut (impl RngCore + CryptoRng),
+        ours: (&str, &str, &str),
+        their_user: &str,
+        event_type: &str,
+        content: &Value,
+    ) -> Option<(Self, Vec<Step>)> {
+        if event_type != REQUEST && event_type != START {
+            return None;
+        }
+        let transaction_id = content.get("transaction_id")?.as_str()?;
+        let mut verification = Verification::new(rng, transaction_id, ours, their_user, State::Asking);
+        verification.their_device = content.get("from_device").and_then(|d| d.as_str()).map(String::from);
+        if event_type == START {
+            verification.start = Some(content.clone());
+        }
+        Some((verification, vec![Step::Ask]))
+    }
+
+    pub fn is_finished(&self) -> bool { self.state == State::Done || self.state == State::Cancelled }
+
+    fn public_key(&self) -> String { encode(PublicKey::from(&StaticSecret::from(self.secret)).as_bytes()) }
+
+    fn cancel(&mut self, code: &str, reason: &str) -> Vec<Step> {
+        self.state = State::Cancelled;
+        vec![
+            Step::Send(
+                CANCEL,
+                json!({"code": code, "reason": reason, "transaction_id": self.transaction_id}),
+            ),
+            Step::Cancelled(format!("{}: {}", code, reason)),
+        ]
+    }
+
+    pub fn accept(&mut self) -> Vec<Step> {
+        if self.state != State::Asking {
+            return Vec::new();
+        }
+        match self.start.clone() {
+            Some(start) => self.accept_start(start),
+            None => {
+                self.state = State::Ready;
+                vec![Step::Send(
+                    READY,
+                    json!({"from_device": self.our_device, "methods": [METHOD], "transaction_id": self.transaction_id}),
+                )]
+            }
+        }
+    }
+
+    pub fn decline(&mut self) -> Vec<Step> { self.cancel("m.user", "The user declined") }
+
+    /// The user's answer to whether the short codes match.
+    pub fn confirm(&mut self, matches: bool) -> Vec<Step> {
+        if self.state != State::Comparing {
+            return Vec::new();
+        }
+        if !matches {
+            return self.cancel("m.mismatched_sas", "The short codes don't match");
+        }
+        self.state = State::Confirmed;
+        let key_id = format!("ed25519:{}", self.our_device);
+        let info = format!(
+            "MATRIX_KEY_VERIFICATION_MAC{}{}{}{}{}",
+            self.our_user,
+            self.our_device,
+            self.their_user,
+            self.their_device.as_deref().unwrap_or(""),
+            self.transaction_id
+        );
+        let mac = json!({
+            "keys": self.mac(&format!("{}KEY_IDS", info), &key_id),
+            "mac": {key_id.as_str(): self.mac(&format!("{}{}", info, key_id), &self.our_key)},
+            "transaction_id": self.transaction_id,
+        });
+        let mut steps = vec![Step::Send(MAC, mac)];
+        if let Some(their_mac) = self.their_mac.take() {
+            steps.extend(self.check_mac(&their_mac));
+        }
+        steps
+    }
+
+    /// Handles an event of the verification from the other device.
+    pub fn receive(&mut self, event_type: &str, content: &Value) -> Vec<Step> {
+        if self.is_finished() {
+            return Vec::new();
+        }
+        let from_device = content.get("from_device").and_then(|d| d.as_str());
+        match (event_type, self.state) {
+            (CANCEL, _) => {
+                self.state = State::Cancelled;
+                let code = content.get("code").and_then(|c| c.as_str()).unwrap_or("");
+                let reason = content.get("reason").and_then(|r| r.as_str()).unwrap_or("");
+                vec![Step::Cancelled(format!("{}: {}", code, reason))]
+            }
+            (READY, State::Requested) => {
+                if !offers(content, "methods", METHOD) {
+                    return self.cancel("m.unknown_method", "Only m.sas.v1 is supported");
+                }
+                self.their_device = from_device.map(String::from);
+                let start = json!({
+                    "from_device": self.our_device,
+                    "method": METHOD,
+                    "key_agreement_protocols": [KEY_AGREEMENT],
+                    "hashes": [HASH],
+                    "message_authentication_codes": [MAC_METHOD],
+                    "short_authentication_string": SHORT_CODES,
+                    "transaction_id": self.transaction_id,
+                });
+                self.start = Some(start.clone());
+                self.we_started = true;
+                self.state = State::Started;
+                vec![Step::Send(START, start)]
+            }
+            (START, State::Requested) | (START, State::Ready) => self.accept_start(content.clone()),
+            (START, State::Started) => {
+                // Both sides started; the one with the lower user ID, then device ID, goes ahead.
+                let theirs = (self.their_user.as_str(), self.their_device.as_deref().unwrap_or(""));
+                if theirs < (self.our_user.as_str(), self.our_device.as_str()) {
+                    self.we_started = false;
+                    self.accept_start(content.clone())
+                } else {
+                    Vec::new()
+                }
+            }
+            (ACCEPT, State::Started) => {
+                let agreed = content.get("key_agreement_protocol").and_then(|v| v.as_str())
+                    == Some(KEY_AGREEMENT)
+                    && content.get("hash").and_then(|v| v.as_str()) == Some(HASH)
+                    && content.get("message_authentication_code").and_then(|v| v.as_str())
+                        == Some(MAC_METHOD);
+                let emoji = offers(content, "short_authentication_string", "emoji");
+                if !agreed || !(emoji || offers(content, "short_authentication_string", "decimal")) {
+                    return self.cancel("m.unknown_method", "The accepted methods weren't offered");
+                }
+                self.emoji = emoji;
+                self.commitment = content.get("commitment").and_then(|c| c.as_str()).map(String::from);
+                self.state = State::KeysSent;
+                vec![Step::Send(
+                    KEY,
+                    json!({"key": self.public_key(), "transaction_id": self.transaction_id}),
+                )]
+            }
+            (KEY, State::KeysSent) => {
+                let key = match content.get("key").and_then(|k| k.as_str()) {
+                    Some(key) => key,
+                    None => return self.cancel("m.unexpected_message", "The key is missing"),
+                };
+                let their_public = match decode_key(key) {
+                    Ok(public) => public,
+                    Err(_) => return self.cancel("m.unexpected_message", "The key is malformed"),
+                };
+                let mut steps = Vec::new();
+                if self.we_started {
+                    let commitment = commitment(key, self.start.as_ref().unwrap());
+                    if self.commitment.as_deref() != Some(commitment.as_str()) {
+                        return self
+                            .cancel("m.mismatched_commitment", "The key doesn't match the commitment");
+                    }
+                } else {
+                    steps.push(Step::Send(
+                        KEY,
+                        json!({"key": self.public_key(), "transaction_id": self.transaction_id}),
+                    ));
+                }
+                self.their_public = Some(their_public);
+                self.state = State::Comparing;
+                steps.push(Step::Compare(self.short_code()));
+                steps
+            }
+            (MAC, State::Comparing) => {
+                self.their_mac = Some(content.clone());
+                Vec::new()
+            }
+            (MAC, State::Confirmed) => self.check_mac(content),
+            (DONE, _) => Vec::new(),
+            _ => self.cancel("m.unexpected_message", &format!("Unexpected {}", event_type)),
+        }
+    }
+
+    /// As the accepter of `start`.
+    fn accept_start(&mut self, start: Value) -> Vec<Step> {
+        if start.get("method").and_then(|m| m.as_str()) != Some(METHOD)
+            || !offers(&start, "key_agreement_protocols", KEY_AGREEMENT)
+            || !offers(&start, "hashes", HASH)
+            || !offers(&start, "message_authentication_codes", MAC_METHOD)
+        {
+            return self.cancel("m.unknown_method", "Only m.sas.v1 with current methods is supported");
+        }
+        let short_codes: Vec<&str> = SHORT_CODES
+            .iter()
+            .copied()
+            .filter(|code| offers(&start, "short_authentication_string", code))
+            .collect();
+        if short_codes.is_empty() {
+            return self.cancel("m.unknown_method", "No short code method in common");
+        }
+        if let Some(device) = start.get("from_device").and_then(|d| d.as_str()) {
+            self.their_device = Some(device.to_string());
+        }
+        self.emoji = short_codes.contains(&"emoji");
+        let accept = json!({
+            "method": METHOD,
+            "key_agreement_protocol": KEY_AGREEMENT,
+            "hash": HASH,
+            "message_authentication_code": MAC_METHOD,
+            "short_authentication_string": short_codes,
+            "commitment": commitment(&self.public_key(), &start),
+            "transaction_id": self.transaction_id,
+        });
+        self.start = Some(start);
+        self.state = State::KeysSent;
+        vec![Step::Send(ACCEPT, accept)]
+    }
+
+    fn shared_secret(&self) -> [u8; 32] {
+        StaticSecret::from(self.secret)
+            .diffie_hellman(&PublicKey::from(self.their_public.unwrap()))
+            .to_bytes()
+    }
+
+    fn short_code(&self) -> ShortCode {
+        let ours = (self.our_user.as_str(), self.our_device.as_str(), self.public_key());
+        let theirs = (
+            self.their_user.as_str(),
+            self.their_device.as_deref().unwrap_or(""),
+            encode(self.their_public.as_ref().unwrap()),
+        );
+        let (starter, accepter) = if self.we_started { (ours, theirs) } else { (theirs, ours) };
+        let info = format!(
+            "MATRIX_KEY_VERIFICATION_SAS|{}|{}|{}|{}|{}|{}|{}",
+            starter.0, starter.1, starter.2, accepter.0, accepter.1, accepter.2, self.transaction_id
+        );
+        let mut bytes = [0u8; 6];
+        hkdf(None, &self.shared_secret(), info.as_bytes(), &mut bytes);
+        ShortCode::new(&bytes, self.emoji)
+    }
+
+    fn mac(&self, info: &str, input: &str) -> String {
+        let mut key = [0u8; 32];
+        hkdf(None, &self.shared_secret(), info.as_bytes(), &mut key);
+        encode(&hmac_sha256(&key, input.as_bytes()))
+    }
+
+    fn check_mac(&mut self, content: &Value) -> Vec<Step> {
+        let their_device = self.their_device.clone().unwrap_or_default();
+        let info = format!(
+            "MATRIX_KEY_VERIFICATION_MAC{}{}{}{}{}",
+            self.their_user, their_device, self.our_user, self.our_device, self.transaction_id
+        );
+        let macs = match content.get("mac").and_then(|m| m.as_object()) {
+            Some(macs) => macs,
+            None => return self.cancel("m.unexpected_message", "The MACs are missing"),
+        };
+        let mut key_ids: Vec<&str> = macs.keys().map(|k| k.as_str()).collect();
+        key_ids.sort();
+        if content.get("keys").and_then(|k| k.as_str())
+            != Some(self.mac(&format!("{}KEY_IDS", info), &key_ids.join(",")).as_str())
+        {
+            return self.cancel("m.key_mismatch", "The key list MAC doesn't match");
+        }
+        // Other keys, like cross-signing keys, aren't tracked, so only the device key is checked.
+        let key_id = format!("ed25519:{}", their_device);
+        let their_key = match (&self.their_key, macs.get(&key_id).and_then(|m| m.as_str())) {
+            (Some(key), Some(mac)) if self.mac(&format!("{}{}", info, key_id), key) == mac => key,
+            (None, _) => return self.cancel("m.key_mismatch", "The device's key isn't known"),
+            _ => return self.cancel("m.key_mismatch", "The device key MAC doesn't match"),
+        };
+        log::info!("verified {} {} with key {}", self.their_user, their_device, their_key);
+        self.state = State::Done;
+        vec![Step::Send(DONE, json!({"transaction_id": self.transaction_id})), Step::Verified]
+    }
+}
+
+/// Whether the list `field` of an event includes `value`.
+fn offers(content: &Value, field: &str, value: &str) -> bool {
+    content
+        .get(field)
+        .and_then(|v| v.as_array())
+        .is_some_and(|list| list.iter().any(|v| v.as_str() == Some(value)))
+}
+
+/// The accepter's commitment to its key, made before it sees the starter's.
+fn commitment(public_key: &str, start: &Value) -> String {
+    let mut hash = Sha256::new();
+    hash.update(public_key.as_bytes());
+    hash.update(canonical_json(start).as_bytes());
+    encode(&hash.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sent(steps: &[Step]) -> Vec<(&'static str, Value)> {
+        steps
+            .iter()
+            .filter_map(|step| match step {
+                Step::Send(event_type, content) => Some((*event_type, content.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn compared(steps: &[Step]) -> Option<&ShortCode> {
+        steps.iter().find_map(|step| match step {
+            Step::Compare(code) => Some(code),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_short_codes() {
+        let bytes = [0xff, 0x00, 0xff, 0x00, 0xff, 0x00];
+        assert_eq!(ShortCode::new(&bytes, true), ShortCode::Emoji([63, 48, 3, 63, 0, 15, 60]));
+        assert_eq!(ShortCode::new(&bytes, false), ShortCode::Decimal([9160, 2020, 1127]));
+        assert!(ShortCode::Emoji([0, 1, 2, 3, 4, 5, 63]).describe().ends_with("📌 Pin"));
+        // vodozemac's vectors
+        assert_eq!(ShortCode::new(&[0; 6], true), ShortCode::Emoji([0; 7]));
+        assert_eq!(ShortCode::new(&[0; 6], false), ShortCode::Decimal([1000; 3]));
+        assert_eq!(ShortCode::new(&[0xff; 6], true), ShortCode::Emoji([63; 7]));
+        assert_eq!(ShortCode::new(&[0xff; 6], false), ShortCode::Decimal([9191; 3]));
+    }
+
+    #[test]
+    fn test_verification() {
+        let mut rng = rand_core::OsRng;
+        let alice = ("@alice:example.org", "ALICEDEVICE", "alicekey");
+        let bob = ("@bob:example.org", "BOBDEVICE", "bobkey");
+        let (mut requester, request) = Verification::request(&mut rng, "txn", alice, bob.0, 0);
+        let (mut responder, steps) =
+            Verification::incoming(&mut rng, bob, alice.0, REQUEST, &request).unwrap();
+        assert!(matches!(steps[..], [Step::Ask]));
+        responder.their_key = Some(alice.2.to_string());
+        requester.their_key = Some(bob.2.to_string());
+
+        let mut to_requester = sent(&responder.accept());
+        let mut codes = (None, None);
+        let mut verified = (false, false);
+        // pass the events back and forth until neither side has anything to send
+        while !to_requester.is_empty() {
+            let mut to_responder = Vec::new();
+            for (event_type, content) in to_requester.drain(..) {
+                let steps = requester.receive(event_type, &content);
+                if let Some(code) = compared(&steps) {
+                    codes.0 = Some(code.describe());
+                    to_responder.extend(sent(&steps));
+                    let steps = requester.confirm(true);
+                    verified.0 |= steps.iter().any(|s| matches!(s, Step::Verified));
+                    to_responder.extend(sent(&steps));
+                } else {
+                    verified.0 |= steps.iter().any(|s| matches!(s, Step::Verified));
+                    to_responder.extend(sent(&steps));
+                }
+            }
+            for (event_type, content) in to_responder {
+                let steps = responder.receive(event_type, &content);
+                if let Some(code) = compared(&steps) {
+                    codes.1 = Some(code.describe());
+                    to_requester.extend(sent(&steps));
+                    let steps = responder.confirm(true);
+                    verified.1 |= steps.iter().any(|s| matches!(s, Step::Verified));
+                    to_requester.extend(sent(&steps));
+                } else {
+                    verified.1 |= steps.iter().any(|s| matches!(s, Step::Verified));
+                    to_requester.extend(sent(&steps));
+                }
+            }
+        }
+        assert!(codes.0.is_some());
+        assert_eq!(codes.0, codes.1);
+        assert_eq!(verified, (true, true));
+        assert!(requester.is_finished() && responder.is_finished());
+    }
+
+    #[test]
+    fn test_mismatch() {
+        let mut rng = rand_core::OsRng;
+        let alice = ("@alice:example.org", "ALICEDEVICE", "alicekey");
+        let bob = ("@bob:example.org", "BOBDEVICE", "bobkey");
+        let (_, request) = Verification::request(&mut rng, "txn", alice, bob.0, 0);
+        let (mut responder, _) = Verification::incoming(&mut rng, bob, alice.0, REQUEST, &request).unwrap();
+        responder.accept();
+        // a key before any start is out of order
+        let steps = responder.receive(KEY, &json!({"key": "x", "transaction_id": "txn"}));
+        assert!(matches!(&steps[..], [Step::Send(CANCEL, _), Step::Cancelled(_)]));
+        assert!(responder.is_finished());
+    }
+}
@@ -1,9 +1,9 @@
 use serde::{Deserialize, Serialize};
-use ureq::serde_json::{Map, Value};
+use ureq::serde_json::{self, Map, Value};
 use ureq::{Agent, ErrorKind};
 use url::Url;
 
-use crate::Msg;
+use crate::{Msg, e2ee};
 
 const ACCEPT: &str = "Accept";
 const ACCEPT_JSON: &str = "application/json";
@@ -107,16 +107,21 @@ pub fn put_string_auth(
 
 // --------------------------------
 
-pub fn whoami(url: &mut Url, token: &str, agent: &mut Agent) -> Option<String> {
+/// The user ID and device ID the token is for.
+pub fn whoami(url: &mut Url, token: &str, agent: &mut Agent) -> Option<(String, Option<String>)> {
     url.set_path("_matrix/client/r0/account/whoami");
     if let Some(value) = handle_response(get_json_auth(&url, token, agent)) {
         if let Value::Object(body) = value {
-            if let Some(Value::String(device_id)) = body.get("device_id") {
-                log::info!("device_id = {}", device_id);
-            }
+            let device_id = match body.get("device_id") {
+                Some(Value::String(device_id)) => {
+                    log::info!("device_id = {}", device_id);
+                    Some(device_id.to_string())
+                }
+                _ => None,
+            };
             if let Some(Value::String(user_id)) = body.get("user_id") {
                 log::info!("user_id = {}", user_id);
-                return Some(user_id.to_string());
+                return Some((user_id.to_string(), device_id));
             }
         }
     }
@@ -165,16 +170,22 @@ impl AuthRequest {
     }
 }
 
-// fn authenticate_user() -> Result<String, ureq::Error> {
-pub fn authenticate_user(url: &mut Url, user: &str, password: &str, agent: &mut Agent) -> Option<String> {
-    let mut maybe_token: Option<String> = None;
+/// The access token and device ID of a new login.
+pub fn authenticate_user(
+    url: &mut Url,
+    user: &str,
+    password: &str,
+    agent: &mut Agent,
+) -> Option<(String, Option<String>)> {
+    let mut maybe_token: Option<(String, Option<String>)> = None;
     url.set_path("_matrix/client/r0/login");
     let auth_request = AuthRequest::new(user, password);
     if let Some(request_body) = serialize(&auth_request) {
         if let Some(value) = handle_response(post_string(&url, &request_body, agent)) {
             if let Value::Object(body) = value {
                 if let Some(Value::String(access_token)) = body.get("access_token") {
-                    maybe_token = Some(access_token.to_string())
+                    let device_id = body.get("device_id").and_then(|d| d.as_str()).map(String::from);
+                    maybe_token = Some((access_token.to_string(), device_id))
                 }
             }
         }
@@ -223,37 +234,34 @@ impl EventFilter {
 struct RoomEventFilter {
     limit: i32,
     types: Vec<String>,
-    rooms: Vec<String>,
 }
 
 impl RoomEventFilter {
-    pub fn new(limit: i32, room_id: &str, type_0: &str) -> Self {
-        let mut types = Vec::new();
-        types.push(type_0.to_string());
-        let mut rooms: Vec<String> = Vec::new();
-        rooms.push(room_id.to_string());
-        RoomEventFilter { limit, types, rooms }
+    pub fn new(limit: i32, types: &[&str]) -> Self {
+        let types = types.iter().map(|t| t.to_string()).collect();
+        RoomEventFilter { limit, types }
     }
 }
 
+/// The state events that name a room.
+const ROOM_NAMES: [&str; 2] = ["m.room.name", "m.room.canonical_alias"];
+
 #[derive(Serialize, Deserialize)]
 struct RoomFilter {
     account_data: EventFilter, // Should be RoomEventFilter
-    ephemeral: EventFilter,
-    rooms: Vec<String>,
-    state: EventFilter, // Should be StateFilter
+    ephemeral: RoomEventFilter,
+    state: RoomEventFilter, // Should be StateFilter
     timeline: RoomEventFilter,
 }
 
 impl RoomFilter {
-    pub fn new(room_id: &str) -> Self {
+    pub fn new() -> Self {
         let account_data = EventFilter::new(0);
-        let ephemeral = EventFilter::new(0);
-        let mut rooms: Vec<String> = Vec::new();
-        rooms.push(room_id.to_string());
-        let state = EventFilter::new(0);
-        let timeline = RoomEventFilter::new(10, room_id, "m.room.message");
-        RoomFilter { account_data, ephemeral, rooms, state, timeline }
+        let ephemeral = RoomEventFilter::new(10, &["m.typing"]);
+        let state = RoomEventFilter::new(10, &ROOM_NAMES);
+        let timeline =
+            RoomEventFilter::new(10, &["m.room.message", e2ee::ENCRYPTED, ROOM_NAMES[0], ROOM_NAMES[1]]);
+        RoomFilter { account_data, ephemeral, state, timeline }
     }
 }
 
@@ -266,32 +274,30 @@ struct FilterRequest {
 }
 
 impl FilterRequest {
-    pub fn new(room_id: &str) -> Self {
+    pub fn new() -> Self {
         let account_data = EventFilter::new(0);
         let mut event_fields: Vec<String> = Vec::new();
         event_fields.push("type".to_string());
+        event_fields.push("event_id".to_string());
         event_fields.push("sender".to_string());
-        event_fields.push("content.body".to_string());
+        event_fields.push("state_key".to_string());
+        // all of the content, which encrypted events need to be decrypted
+        event_fields.push("content".to_string());
         event_fields.push("origin_server_ts".to_string());
         let presence = EventFilter::new(0);
-        let room = RoomFilter::new(room_id);
+        let room = RoomFilter::new();
         FilterRequest { account_data, event_fields, presence, room }
     }
 }
 
-pub fn get_filter(
-    user: &str,
-    url: &mut Url,
-    room_id: &str,
-    token: &str,
-    agent: &mut Agent,
-) -> Option<String> {
+/// A filter for syncing all of the user's rooms.
+pub fn get_filter(user: &str, url: &mut Url, token: &str, agent: &mut Agent) -> Option<String> {
     let mut path = String::from("_matrix/client/v3/user/");
     path.push_str(&user);
     path.push_str("/filter");
     url.set_path(&path);
     log::info!("get_filter = {}", url.as_str());
-    let filter_request = FilterRequest::new(room_id);
+    let filter_request = FilterRequest::new();
     if let Some(request_body) = serialize(&filter_request) {
         if let Some(value) = handle_response(post_string_auth(url, &request_body, token, agent)) {
             if let Value::Object(body) = value {
@@ -316,47 +322,164 @@ pub fn get_filter(
     }
 }
 
-fn get_messages(body: Map<String, Value>, room_id: &str) -> Vec<Msg> {
-    log::info!("heap usage: {}", crate::heap_usage());
+fn get_messages(events: &[Value]) -> Vec<Msg> {
     let mut msgs = Vec::<Msg>::new();
-    if let Some(Value::Object(rooms)) = body.get("rooms") {
-        if let Some(Value::Object(join)) = rooms.get("join") {
-            if let Some(Value::Object(room)) = join.get(room_id) {
-                if let Some(Value::Object(timeline)) = room.get("timeline") {
-                    if let Some(Value::Array(events)) = timeline.get("events") {
-                        for event in events.iter() {
-                            log::trace!("{:?}", event);
-                            if let Some(Value::String(type_)) = event.get("type") {
-                                if type_.eq("m.room.message") {
-                                    msgs.push(Msg {
-                                        type_: type_.to_string(),
-                                        body: event
-                                            .get("content")
-                                            .map(|c| c.get("body").map(|b| b.to_string()))
-                                            .flatten(),
-                                        sender: event.get("sender").map(|s| s.to_string()),
-                                        ts: event.get("origin_server_ts").map(|t| t.as_u64()).flatten(),
-                                    });
-                                }
-                            }
-                        } // event
-                    }
-                }
+    for event in events.iter() {
+        log::trace!("{:?}", event);
+        if let Some(Value::String(type_)) = event.get("type") {
+            if type_.eq("m.room.message") || type_.eq(e2ee::ENCRYPTED) {
+                let encrypted = type_.eq(e2ee::ENCRYPTED);
+                msgs.push(Msg {
+                    type_: type_.to_string(),
+                    body: event["content"]["body"].as_str().map(String::from),
+                    sender: event["sender"].as_str().map(String::from),
+                    ts: event["origin_server_ts"].as_u64(),
+                    event_id: event["event_id"].as_str().map(String::from),
+                    encrypted: if encrypted { event.get("content").cloned() } else { None },
+                });
             }
         }
     }
     msgs
 }
 
+/// The last value of a state event's `field` among `events`.
+fn state_field(events: &[Value], event_type: &str, field: &str) -> Option<String> {
+    events
+        .iter()
+        .rev()
+        .find(|event| event["type"].as_str() == Some(event_type))
+        .and_then(|event| event["content"][field].as_str())
+        .map(String::from)
+}
+
+fn events(value: &Value) -> &[Value] {
+    match value["events"].as_array() {
+        Some(events) => events,
+        None => &[],
+    }
+}
+
+/// What a sync brought for a room we're in.
+pub struct JoinedRoom {
+    pub room_id: String,
+    pub msgs: Vec<Msg>,
+    /// the room's name and canonical alias, if they changed
+    pub name: Option<String>,
+    pub alias: Option<String>,
+    /// notifications the user hasn't read
+    pub unread: Option<u64>,
+    /// the users typing, if that changed
+    pub typing: Option<Vec<String>>,
+}
+
+impl JoinedRoom {
+    fn new(room_id: &str, room: &Value) -> Self {
+        let (state, timeline) = (events(&room["state"]), events(&room["timeline"]));
+        // the timeline is the more recent
+        let latest = |event_type: &str, field: &str| {
+            state_field(timeline, event_type, field).or_else(|| state_field(state, event_type, field))
+        };
+        let typing = events(&room["ephemeral"])
+            .iter()
+            .rev()
+            .find(|event| event["type"].as_str() == Some("m.typing"))
+            .map(|event| strings(event["content"].get("user_ids")));
+        JoinedRoom {
+            room_id: room_id.to_string(),
+            msgs: get_messages(timeline),
+            name: latest(ROOM_NAMES[0], "name"),
+            alias: latest(ROOM_NAMES[1], "alias"),
+            unread: room["unread_notifications"]["notification_count"].as_u64(),
+            typing,
+        }
+    }
+}
+
+/// A room the user was invited to.
+pub struct Invite {
+    pub room_id: String,
+    pub name: Option<String>,
+    pub inviter: Option<String>,
+}
+
+impl Invite {
+    fn new(room_id: &str, room: &Value) -> Self {
+        let state = events(&room["invite_state"]);
+        Invite {
+            room_id: room_id.to_string(),
+            name: state_field(state, ROOM_NAMES[0], "name")
+                .or_else(|| state_field(state, ROOM_NAMES[1], "alias")),
+            inviter: state
+                .iter()
+                .find(|event| {
+                    event["type"].as_str() == Some("m.room.member")
+                        && event["content"]["membership"].as_str() == Some("invite")
+                })
+                .and_then(|event| event["sender"].as_str())
+                .map(String::from),
+        }
+    }
+}
+
+/// What a sync brought.
+pub struct SyncResponse {
+    pub next_batch: String,
+    pub joined: Vec<JoinedRoom>,
+    pub invited: Vec<Invite>,
+    /// rooms the user left, or was removed from
+    pub parted: Vec<String>,
+    /// events sent to this device, for encryption and verification
+    pub to_device: Vec<Value>,
+    /// our one-time keys the server has left
+    pub one_time_keys: Option<u64>,
+    /// users whose devices changed, and users we no longer share a room with
+    pub changed: Vec<String>,
+    pub left: Vec<String>,
+}
+
+impl SyncResponse {
+    fn new(next_batch: &str, body: &Map<String, Value>) -> Self {
+        let rooms = |section: &str| body.get("rooms").and_then(|rooms| rooms[section].as_object());
+        let device_lists = body.get("device_lists");
+        SyncResponse {
+            next_batch: next_batch.to_string(),
+            joined: rooms("join")
+                .map(|rooms| rooms.iter().map(|(id, room)| JoinedRoom::new(id, room)).collect())
+                .unwrap_or_default(),
+            invited: rooms("invite")
+                .map(|rooms| rooms.iter().map(|(id, room)| Invite::new(id, room)).collect())
+                .unwrap_or_default(),
+            parted: rooms("leave").map(|rooms| rooms.keys().cloned().collect()).unwrap_or_default(),
+            to_device: match body.get("to_device").and_then(|t| t.get("events")) {
+                Some(Value::Array(events)) => events.clone(),
+                _ => Vec::new(),
+            },
+            one_time_keys: body
+                .get("device_one_time_keys_count")
+                .and_then(|counts| counts.get("signed_curve25519"))
+                .and_then(|count| count.as_u64()),
+            changed: strings(device_lists.and_then(|lists| lists.get("changed"))),
+            left: strings(device_lists.and_then(|lists| lists.get("left"))),
+        }
+    }
+}
+
+fn strings(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::Array(items)) => items.iter().filter_map(|s| s.as_str()).map(String::from).collect(),
+        _ => Vec::new(),
+    }
+}
+
 pub fn client_sync(
     url: &mut Url,
     filter: &str,
     since: Option<&str>,
     timeout: i32,
-    room_id: &str,
     token: &str,
     agent: &mut Agent,
-) -> Option<(String, Vec<Msg>)> {
+) -> Option<SyncResponse> {
     log::info!("heap usage: {}", crate::heap_usage());
     url.set_path("_matrix/client/r0/sync");
     url.query_pairs_mut().append_pair("filter", &filter);
@@ -368,7 +491,7 @@ pub fn client_sync(
     if let Some(value) = handle_response(get_json_auth(&url, token, agent)) {
         if let Value::Object(body) = value {
             if let Some(Value::String(next_batch)) = body.get("next_batch") {
-                Some((next_batch.to_string(), get_messages(body, room_id)))
+                Some(SyncResponse::new(next_batch, &body))
             } else {
                 log::info!("invalid response for client_sync");
                 None
@@ -429,3 +552,319 @@ pub fn send_message(
         false
     }
 }
+
+/// Sends an event of any type to a room.
+pub fn send_event(
+    url: &mut Url,
+    room_id: &str,
+    event_type: &str,
+    content: &Value,
+    txn_id: &str,
+    token: &str,
+    agent: &mut Agent,
+) -> bool {
+    let path = format!("_matrix/client/v3/rooms/{}/send/{}/{}", room_id, event_type, txn_id);
+    url.set_path(&path);
+    log::info!("send_event = {}", url);
+    match handle_response(put_string_auth(url, &content.to_string(), token, agent)) {
+        Some(Value::Object(_body)) => true,
+        _ => {
+            log::info!("Error for send_event");
+            false
+        }
+    }
+}
+
+/// Sends events to devices; `messages` is `{user: {device: content}}`.
+pub fn send_to_device(
+    url: &mut Url,
+    event_type: &str,
+    txn_id: &str,
+    messages: &Value,
+    token: &str,
+    agent: &mut Agent,
+) -> bool {
+    url.set_path(&format!("_matrix/client/v3/sendToDevice/{}/{}", event_type, txn_id));
+    log::info!("send_to_device = {}", url);
+    let request_body = serde_json::json!({ "messages": messages }).to_string();
+    match handle_response(put_string_auth(url, &request_body, token, agent)) {
+        Some(Value::Object(_body)) => true,
+        _ => {
+            log::info!("Error for send_to_device");
+            false
+        }
+    }
+}
+
+/// POSTs a JSON body to `path`, and returns the response.
+pub fn post_json_auth(
+    url: &mut Url,
+    path: &str,
+    body: &Value,
+    token: &str,
+    agent: &mut Agent,
+) -> Option<Value> {
+    url.set_path(path);
+    log::info!("post_json_auth = {}", url);
+    handle_response(post_string_auth(url, &body.to_string(), token, agent))
+}
+
+/// The content of a room's state event, `Ok(None)` if the room has none.
+pub fn get_state(
+    url: &mut Url,
+    room_id: &str,
+    event_type: &str,
+    token: &str,
+    agent: &mut Agent,
+) -> Result<Option<Value>, ()> {
+    url.set_path(&format!("_matrix/client/v3/rooms/{}/state/{}/", room_id, event_type));
+    log::info!("get_state = {}", url);
+    match get_json_auth(url, token, agent) {
+        Err(ureq::Error::Status(404, _)) => Ok(None),
+        response => handle_response(response).map(Some).ok_or(()),
+    }
+}
+
+/// The users in a room.
+pub fn joined_members(url: &mut Url, room_id: &str, token: &str, agent: &mut Agent) -> Option<Vec<String>> {
+    url.set_path(&format!("_matrix/client/v3/rooms/{}/joined_members", room_id));
+    log::info!("joined_members = {}", url);
+    match handle_response(get_json_auth(url, token, agent)) {
+        Some(Value::Object(body)) => match body.get("joined") {
+            Some(Value::Object(joined)) => Some(joined.keys().cloned().collect()),
+            _ => None,
+        },
+        _ => {
+            log::info!("Error for joined_members");
+            None
+        }
+    }
+}
+
+/// Joins a room by ID or alias, or accepts an invite to it, and returns its ID.
+pub fn join_room(url: &mut Url, room: &str, token: &str, agent: &mut Agent) -> Option<String> {
+    url.set_path(&format!("_matrix/client/v3/join/{}", room));
+    log::info!("join_room = {}", url);
+    match handle_response(post_string_auth(url, "{}", token, agent)) {
+        Some(body) => body["room_id"].as_str().map(String::from),
+        None => {
+            log::info!("Error for join_room");
+            None
+        }
+    }
+}
+
+/// Leaves a room, or declines an invite to it.
+pub fn leave_room(url: &mut Url, room_id: &str, token: &str, agent: &mut Agent) -> bool {
+    url.set_path(&format!("_matrix/client/v3/rooms/{}/leave", room_id));
+    log::info!("leave_room = {}", url);
+    match handle_response(post_string_auth(url, "{}", token, agent)) {
+        Some(Value::Object(_body)) => true,
+        _ => {
+            log::info!("Error for leave_room");
+            false
+        }
+    }
+}
+
+/// The latest events in a room, oldest first.
+pub fn room_messages(
+    url: &mut Url,
+    room_id: &str,
+    limit: usize,
+    token: &str,
+    agent: &mut Agent,
+) -> Option<Vec<Msg>> {
+    url.set_path(&format!("_matrix/client/v3/rooms/{}/messages", room_id));
+    url.query_pairs_mut().append_pair("dir", "b").append_pair("limit", &limit.to_string());
+    log::info!("room_messages = {}", url);
+    match handle_response(get_json_auth(url, token, agent)) {
+        Some(body) => {
+            let mut msgs = get_messages(match body["chunk"].as_array() {
+                Some(chunk) => chunk,
+                None => &[],
+            });
+            msgs.reverse();
+            Some(msgs)
+        }
+        None => {
+            log::info!("Error for room_messages");
+            None
+        }
+    }
+}
+
+/// Tells the room the user is typing, for `timeout` ms, or has stopped.
+pub fn send_typing(
+    url: &mut Url,
+    room_id: &str,
+    user_id: &str,
+    typing: Option<u32>,
+    token: &str,
+    agent: &mut Agent,
+) -> bool {
+    url.set_path(&format!("_matrix/client/v3/rooms/{}/typing/{}", room_id, user_id));
+    let request_body = match typing {
+        Some(timeout) => serde_json::json!({ "typing": true, "timeout": timeout }),
+        None => serde_json::json!({ "typing": false }),
+    };
+    match handle_response(put_string_auth(url, &request_body.to_string(), token, agent)) {
+        Some(Value::Object(_body)) => true,
+        _ => {
+            log::info!("Error for send_typing");
+            false
+        }
+    }
+}
+
+/// Marks the room read up to an event.
+pub fn send_receipt(url: &mut Url, room_id: &str, event_id: &str, token: &str, agent: &mut Agent) -> bool {
+    url.set_path(&format!("_matrix/client/v3/rooms/{}/receipt/m.read/{}", room_id, event_id));
+    log::info!("send_receipt = {}", url);
+    match handle_response(post_string_auth(url, "{}", token, agent)) {
+        Some(Value::Object(_body)) => true,
+        _ => {
+            log::info!("Error for send_receipt");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::mock::{MockHomeserver, route};
+
+    #[test]
+    fn test_client_sync() {
+        let hs = MockHomeserver::start(vec![route(
+            "GET",
+            "/_matrix/client/r0/sync",
+            json!({
+                "next_batch": "s2",
+                "rooms": {
+                    "join": {
+                        "!a:x": {
+                            "state": {"events": [
+                                {"type": "m.room.name", "content": {"name": "Apples"}},
+                                {"type": "m.room.canonical_alias", "content": {"alias": "#apples:x"}},
+                            ]},
+                            "timeline": {"events": [
+                                {"type": "m.room.message", "event_id": "$1", "sender": "@bob:x",
+                                    "origin_server_ts": 1000, "content": {"msgtype": "m.text", "body": "hi"}},
+                                {"type": "m.room.name", "content": {"name": "Green apples"}},
+                                {"type": e2ee::ENCRYPTED, "event_id": "$2", "sender": "@bob:x",
+                                    "origin_server_ts": 2000, "content": {"algorithm": e2ee::MEGOLM}},
+                            ]},
+                            "ephemeral": {"events": [{"type": "m.typing", "content": {"user_ids": ["@bob:x"]}}]},
+                            "unread_notifications": {"notification_count": 2},
+                        },
+                        "!b:x": {"timeline": {"events": []}},
+                    },
+                    "invite": {
+                        "!c:x": {"invite_state": {"events": [
+                            {"type": "m.room.name", "sender": "@carol:x", "content": {"name": "Cats"}},
+                            {"type": "m.room.member", "sender": "@carol:x", "state_key": "@me:x",
+                                "content": {"membership": "invite"}},
+                        ]}},
+                    },
+                    "leave": {"!d:x": {}},
+                },
+                "device_lists": {"changed": ["@bob:x"]},
+                "device_one_time_keys_count": {"signed_curve25519": 20},
+            }),
+        )]);
+        let sync =
+            client_sync(&mut hs.url.clone(), "f1", Some("s1"), 0, "token", &mut ureq::agent()).unwrap();
+        assert_eq!(sync.next_batch, "s2");
+        assert_eq!(sync.joined.len(), 2);
+        let a = sync.joined.iter().find(|room| room.room_id == "!a:x").unwrap();
+        assert_eq!(a.name.as_deref(), Some("Green apples"));
+        assert_eq!(a.alias.as_deref(), Some("#apples:x"));
+        assert_eq!(a.unread, Some(2));
+        assert_eq!(a.typing.as_deref(), Some(&["@bob:x".to_string()][..]));
+        assert_eq!(a.msgs.len(), 2);
+        assert_eq!(a.msgs[0].body.as_deref(), Some("hi"));
+        assert_eq!(a.msgs[0].sender.as_deref(), Some("@bob:x"));
+        assert_eq!(a.msgs[0].event_id.as_deref(), Some("$1"));
+        assert!(a.msgs[0].encrypted.is_none());
+        assert!(a.msgs[1].encrypted.is_some());
+        let b = sync.joined.iter().find(|room| room.room_id == "!b:x").unwrap();
+        assert!(b.name.is_none() && b.unread.is_none() && b.typing.is_none());
+        assert_eq!(sync.invited.len(), 1);
+        assert_eq!(sync.invited[0].name.as_deref(), Some("Cats"));
+        assert_eq!(sync.invited[0].inviter.as_deref(), Some("@carol:x"));
+        assert_eq!(sync.parted, ["!d:x"]);
+        assert_eq!(sync.changed, ["@bob:x"]);
+        assert_eq!(sync.one_time_keys, Some(20));
+
+        let requests = hs.requests();
+        assert!(requests[0].path.contains("filter=f1"));
+        assert!(requests[0].path.contains("since=s1"));
+    }
+
+    #[test]
+    fn test_filter() {
+        let hs = MockHomeserver::start(vec![route(
+            "POST",
+            "/_matrix/client/v3/user/",
+            json!({"filter_id": "f1"}),
+        )]);
+        assert_eq!(
+            get_filter("@me:x", &mut hs.url.clone(), "token", &mut ureq::agent()).as_deref(),
+            Some("f1")
+        );
+        let filter: Value = serde_json::from_str(&hs.requests()[0].body).unwrap();
+        // every room, and what read receipts need
+        assert!(filter["room"].get("rooms").is_none());
+        assert!(filter["event_fields"].as_array().unwrap().contains(&json!("event_id")));
+        assert_eq!(filter["room"]["ephemeral"]["types"], json!(["m.typing"]));
+    }
+
+    #[test]
+    fn test_rooms() {
+        let hs = MockHomeserver::start(vec![
+            route("POST", "/_matrix/client/v3/join/", json!({"room_id": "!a:x"})),
+            route("POST", "/_matrix/client/v3/rooms/!a:x/leave", json!({})),
+            route(
+                "GET",
+                "/_matrix/client/v3/rooms/!a:x/messages",
+                json!({"chunk": [
+                    {"type": "m.room.message", "event_id": "$2", "sender": "@bob:x", "content": {"body": "two"}},
+                    {"type": "m.room.member", "event_id": "$m", "sender": "@bob:x", "content": {}},
+                    {"type": "m.room.message", "event_id": "$1", "sender": "@bob:x", "content": {"body": "one"}},
+                ]}),
+            ),
+        ]);
+        let mut agent = ureq::agent();
+        assert_eq!(join_room(&mut hs.url.clone(), "#apples:x", "token", &mut agent).as_deref(), Some("!a:x"));
+        let msgs = room_messages(&mut hs.url.clone(), "!a:x", 20, "token", &mut agent).unwrap();
+        let bodies: Vec<&str> = msgs.iter().filter_map(|msg| msg.body.as_deref()).collect();
+        assert_eq!(bodies, ["one", "two"]);
+        assert!(leave_room(&mut hs.url.clone(), "!a:x", "token", &mut agent));
+        assert!(!leave_room(&mut hs.url.clone(), "!b:x", "token", &mut agent));
+
+        assert_eq!(hs.requests_to("POST", "/_matrix/client/v3/join/%23apples:x").len(), 1);
+        let backfill = &hs.requests_to("GET", "/_matrix/client/v3/rooms/")[0];
+        assert!(backfill.path.contains("dir=b") && backfill.path.contains("limit=20"));
+    }
+
+    #[test]
+    fn test_typing_and_receipts() {
+        let hs = MockHomeserver::start(vec![
+            route("PUT", "/_matrix/client/v3/rooms/!a:x/typing/", json!({})),
+            route("POST", "/_matrix/client/v3/rooms/!a:x/receipt/m.read/", json!({})),
+        ]);
+        let mut agent = ureq::agent();
+        assert!(send_typing(&mut hs.url.clone(), "!a:x", "@me:x", Some(30000), "token", &mut agent));
+        assert!(send_typing(&mut hs.url.clone(), "!a:x", "@me:x", None, "token", &mut agent));
+        assert!(send_receipt(&mut hs.url.clone(), "!a:x", "$1", "token", &mut agent));
+
+        let typing = hs.requests_to("PUT", "/_matrix/client/v3/rooms/!a:x/typing/@me:x");
+        let bodies: Vec<Value> = typing.iter().map(|r| serde_json::from_str(&r.body).unwrap()).collect();
+        assert_eq!(bodies, [json!({"typing": true, "timeout": 30000}), json!({"typing": false})]);
+        assert_eq!(hs.requests_to("POST", "/_matrix/client/v3/rooms/!a:x/receipt/m.read/$1").len(), 1);
+    }
+}
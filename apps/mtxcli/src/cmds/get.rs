@@ -20,7 +20,7 @@ impl<'a> ShellCmdApi<'a> for Get {
         if let Some(key) = tokens.next() {
             match key {
                 "" => {
-                    write!(ret, "{}", t!("mtxcli.get.help", locales::LANG)).unwrap();
+                    write!(ret, "{}", t!("mtxcli.get.help", locales::lang())).unwrap();
                 }
                 _ => match env.get(key) {
                     Ok(None) => {
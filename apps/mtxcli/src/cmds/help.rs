@@ -23,42 +23,42 @@ impl<'a> ShellCmdApi<'a> for Help {
             let cmd = if slashcmd.starts_with("/") { &slashcmd[1..] } else { slashcmd };
             match cmd {
                 "get" => {
-                    write!(ret, "{}", t!("mtxcli.get.help", locales::LANG)).unwrap();
+                    write!(ret, "{}", t!("mtxcli.get.help", locales::lang())).unwrap();
                 }
                 "heap" => {
-                    write!(ret, "{}", t!("mtxcli.heap.help", locales::LANG)).unwrap();
+                    write!(ret, "{}", t!("mtxcli.heap.help", locales::lang())).unwrap();
                 }
                 "help" => {
-                    write!(ret, "{}", t!("mtxcli.help.help", locales::LANG)).unwrap();
+                    write!(ret, "{}", t!("mtxcli.help.help", locales::lang())).unwrap();
                 }
                 "login" => {
-                    write!(ret, "{}", t!("mtxcli.login.help", locales::LANG)).unwrap();
+                    write!(ret, "{}", t!("mtxcli.login.help", locales::lang())).unwrap();
                 }
                 "logout" => {
-                    write!(ret, "{}", t!("mtxcli.logout.help", locales::LANG)).unwrap();
+                    write!(ret, "{}", t!("mtxcli.logout.help", locales::lang())).unwrap();
                 }
                 "set" => {
-                    write!(ret, "{}", t!("mtxcli.set.help", locales::LANG)).unwrap();
+                    write!(ret, "{}", t!("mtxcli.set.help", locales::lang())).unwrap();
                 }
                 "status" => {
-                    write!(ret, "{}", t!("mtxcli.status.help", locales::LANG)).unwrap();
+                    write!(ret, "{}", t!("mtxcli.status.help", locales::lang())).unwrap();
                 }
                 "unset" => {
-                    write!(ret, "{}", t!("mtxcli.unset.help", locales::LANG)).unwrap();
+                    write!(ret, "{}", t!("mtxcli.unset.help", locales::lang())).unwrap();
                 }
                 "" => {
-                    write!(ret, "{}\n", t!("mtxcli.help.overview", locales::LANG)).unwrap();
-                    write!(ret, "{}\n", t!("mtxcli.get.help", locales::LANG)).unwrap();
-                    write!(ret, "{}\n", t!("mtxcli.heap.help", locales::LANG)).unwrap();
-                    write!(ret, "{}\n", t!("mtxcli.help.help", locales::LANG)).unwrap();
-                    write!(ret, "{}\n", t!("mtxcli.login.help", locales::LANG)).unwrap();
-                    write!(ret, "{}\n", t!("mtxcli.logout.help", locales::LANG)).unwrap();
-                    write!(ret, "{}\n", t!("mtxcli.set.help", locales::LANG)).unwrap();
-                    write!(ret, "{}\n", t!("mtxcli.status.help", locales::LANG)).unwrap();
-                    write!(ret, "{}", t!("mtxcli.unset.help", locales::LANG)).unwrap();
+                    write!(ret, "{}\n", t!("mtxcli.help.overview", locales::lang())).unwrap();
+                    write!(ret, "{}\n", t!("mtxcli.get.help", locales::lang())).unwrap();
+                    write!(ret, "{}\n", t!("mtxcli.heap.help", locales::lang())).unwrap();
+                    write!(ret, "{}\n", t!("mtxcli.help.help", locales::lang())).unwrap();
+                    write!(ret, "{}\n", t!("mtxcli.login.help", locales::lang())).unwrap();
+                    write!(ret, "{}\n", t!("mtxcli.logout.help", locales::lang())).unwrap();
+                    write!(ret, "{}\n", t!("mtxcli.set.help", locales::lang())).unwrap();
+                    write!(ret, "{}\n", t!("mtxcli.status.help", locales::lang())).unwrap();
+                    write!(ret, "{}", t!("mtxcli.unset.help", locales::lang())).unwrap();
                 }
                 _ => {
-                    write!(ret, "{}: {}", t!("mtxcli.unknown.help", locales::LANG), cmd).unwrap();
+                    write!(ret, "{}: {}", t!("mtxcli.unknown.help", locales::lang()), cmd).unwrap();
                 }
             }
         }
@@ -76,58 +76,58 @@ impl<'a> ShellCmdApi<'a> for Help {
                 let mut ret = String::new();
                 let warning = match *async_msg_id {
                     CLOCK_NOT_SET_ID => {
-                        t!("mtxcli.clock.warning", locales::LANG)
+                        t!("mtxcli.clock.warning", locales::lang())
                     }
                     PDDB_NOT_MOUNTED_ID => {
-                        t!("mtxcli.pddb.warning", locales::LANG)
+                        t!("mtxcli.pddb.warning", locales::lang())
                     }
                     WIFI_NOT_CONNECTED_ID => {
-                        t!("mtxcli.wifi.warning", locales::LANG)
+                        t!("mtxcli.wifi.warning", locales::lang())
                     }
                     MTXCLI_INITIALIZED_ID => {
-                        t!("mtxcli.initialized", locales::LANG)
+                        t!("mtxcli.initialized", locales::lang())
                     }
                     WIFI_CONNECTED_ID => {
-                        t!("mtxcli.wifi.connected", locales::LANG)
+                        t!("mtxcli.wifi.connected", locales::lang())
                     }
                     SET_USER_ID => {
-                        t!("mtxcli.please.set.user", locales::LANG)
+                        t!("mtxcli.please.set.user", locales::lang())
                     }
                     SET_PASSWORD_ID => {
-                        t!("mtxcli.please.set.password", locales::LANG)
+                        t!("mtxcli.please.set.password", locales::lang())
                     }
                     LOGGED_IN_ID => {
-                        t!("mtxcli.logged.in", locales::LANG)
+                        t!("mtxcli.logged.in", locales::lang())
                     }
                     LOGIN_FAILED_ID => {
-                        t!("mtxcli.login.failed", locales::LANG)
+                        t!("mtxcli.login.failed", locales::lang())
                     }
                     SET_ROOM_ID => {
-                        t!("mtxcli.please.set.room", locales::LANG)
+                        t!("mtxcli.please.set.room", locales::lang())
                     }
                     ROOMID_FAILED_ID => {
-                        t!("mtxcli.roomid.failed", locales::LANG)
+                        t!("mtxcli.roomid.failed", locales::lang())
                     }
                     FILTER_FAILED_ID => {
-                        t!("mtxcli.filter.failed", locales::LANG)
+                        t!("mtxcli.filter.failed", locales::lang())
                     }
                     SET_SERVER_ID => {
-                        t!("mtxcli.please.set.server", locales::LANG)
+                        t!("mtxcli.please.set.server", locales::lang())
                     }
                     LOGGING_IN_ID => {
-                        t!("mtxcli.logging.in", locales::LANG)
+                        t!("mtxcli.logging.in", locales::lang())
                     }
                     LOGGED_OUT_ID => {
-                        t!("mtxcli.logged.out", locales::LANG)
+                        t!("mtxcli.logged.out", locales::lang())
                     }
                     NOT_CONNECTED_ID => {
-                        t!("mtxcli.not.connected", locales::LANG)
+                        t!("mtxcli.not.connected", locales::lang())
                     }
                     FAILED_TO_SEND_ID => {
-                        t!("mtxcli.send.failed", locales::LANG)
+                        t!("mtxcli.send.failed", locales::lang())
                     }
                     PLEASE_LOGIN_ID => {
-                        t!("mtxcli.please.login", locales::LANG)
+                        t!("mtxcli.please.login", locales::lang())
                     }
                     _ => "unknown async_msg_id",
                 };
@@ -20,7 +20,7 @@ impl<'a> ShellCmdApi<'a> for Unset {
         if let Some(key) = tokens.next() {
             match key {
                 "" => {
-                    write!(ret, "{}", t!("mtxcli.unset.help", locales::LANG)).unwrap();
+                    write!(ret, "{}", t!("mtxcli.unset.help", locales::lang())).unwrap();
                 }
                 _ => {
                     match env.unset(key) {
@@ -0,0 +1,1074 @@
+//! An OpenPGP card (version 3.4 of the specification) with Curve25519 keys: Ed25519 for signing and
+//! authentication, X25519 for decryption. This is the part of the card that GnuPG uses; RSA, secure
+//! messaging, the KDF-DO, private DOs, cardholder certificates and the resetting code are not
+//! implemented.
+
+use std::convert::TryInto;
+
+use ed25519_dalek::{Signer, SigningKey};
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+use crate::store::Store;
+use crate::tlv;
+
+const SW_OK: u16 = 0x9000;
+const SW_MORE_DATA: u16 = 0x6100;
+const SW_TERMINATED: u16 = 0x6285;
+const SW_PIN_RETRIES: u16 = 0x63C0;
+const SW_MEMORY_FAILURE: u16 = 0x6581;
+const SW_WRONG_LENGTH: u16 = 0x6700;
+const SW_LAST_COMMAND_EXPECTED: u16 = 0x6883;
+const SW_SECURITY_STATUS: u16 = 0x6982;
+const SW_PIN_BLOCKED: u16 = 0x6983;
+const SW_CONDITIONS: u16 = 0x6985;
+const SW_WRONG_DATA: u16 = 0x6A80;
+const SW_FILE_NOT_FOUND: u16 = 0x6A82;
+const SW_DATA_NOT_FOUND: u16 = 0x6A88;
+const SW_WRONG_P1P2: u16 = 0x6B00;
+const SW_INS_NOT_SUPPORTED: u16 = 0x6D00;
+const SW_CLA_NOT_SUPPORTED: u16 = 0x6E00;
+
+const CLA_CHAINING: u8 = 0x10;
+
+const INS_SELECT: u8 = 0xA4;
+const INS_GET_DATA: u8 = 0xCA;
+const INS_VERIFY: u8 = 0x20;
+const INS_CHANGE_REFERENCE_DATA: u8 = 0x24;
+const INS_RESET_RETRY_COUNTER: u8 = 0x2C;
+const INS_PUT_DATA: u8 = 0xDA;
+const INS_PUT_DATA_ODD: u8 = 0xDB;
+const INS_GENERATE_ASYMMETRIC_KEY_PAIR: u8 = 0x47;
+const INS_PSO: u8 = 0x2A;
+const INS_INTERNAL_AUTHENTICATE: u8 = 0x88;
+const INS_GET_RESPONSE: u8 = 0xC0;
+const INS_GET_CHALLENGE: u8 = 0x84;
+const INS_TERMINATE_DF: u8 = 0xE6;
+const INS_ACTIVATE_FILE: u8 = 0x44;
+
+/// RID, PIX application, version 3.4, manufacturer 0xFF00 (GnuPG's "unmanaged S/N range")
+const AID_PREFIX: [u8; 10] = [0xD2, 0x76, 0x00, 0x01, 0x24, 0x01, 0x03, 0x04, 0xFF, 0x00];
+/// What GnuPG selects the application by
+const AID_SELECT_LEN: usize = 6;
+/// Category indicator, card service data, card capabilities (command chaining), status indicator and
+/// status word
+const HISTORICAL_BYTES: [u8; 10] = [0x00, 0x31, 0x84, 0x73, 0x80, 0x01, 0x80, 0x05, 0x90, 0x00];
+/// Extended capabilities: GET CHALLENGE, key import, changeable PW status and algorithm attributes;
+/// challenges and special DOs up to 255 bytes
+const EXTENDED_CAPABILITIES: [u8; 10] = [0x74, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00];
+/// EdDSA with the Ed25519 OID
+const ATTRIBUTES_ED25519: [u8; 10] = [0x16, 0x2B, 0x06, 0x01, 0x04, 0x01, 0xDA, 0x47, 0x0F, 0x01];
+/// ECDH with the Curve25519 OID
+const ATTRIBUTES_CV25519: [u8; 11] = [0x12, 0x2B, 0x06, 0x01, 0x04, 0x01, 0x97, 0x55, 0x01, 0x05, 0x01];
+
+/// Largest response sent in one go; the rest waits for GET RESPONSE
+const MAX_RESPONSE: usize = 256;
+/// Largest command assembled from a chain. Key imports are the biggest thing GnuPG chains.
+const MAX_CHAIN: usize = 1024;
+const MAX_CHALLENGE: usize = 255;
+const MAX_SPECIAL_DO: usize = 255;
+
+pub const PW1_DEFAULT: &str = "123456";
+pub const PW3_DEFAULT: &str = "12345678";
+const PW_RETRIES: u8 = 3;
+const PW_MAX_LEN: usize = 127;
+const PW_SALT_LEN: usize = 16;
+
+const KEY_SERIAL: &str = "serial";
+const KEY_RETRIES: &str = "retries";
+const KEY_PW1_VALIDITY: &str = "pw1.validity";
+const KEY_SIGNATURES: &str = "signatures";
+const KEY_TERMINATED: &str = "terminated";
+
+const ORIGIN_GENERATED: u8 = 1;
+const ORIGIN_IMPORTED: u8 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeySlot {
+    Sig,
+    Dec,
+    Aut,
+}
+
+impl KeySlot {
+    const ALL: [KeySlot; 3] = [KeySlot::Sig, KeySlot::Dec, KeySlot::Aut];
+
+    /// Looks up the slot named by a control reference template
+    fn from_crt(tag: u16) -> Option<Self> {
+        match tag {
+            0xB6 => Some(KeySlot::Sig),
+            0xB8 => Some(KeySlot::Dec),
+            0xA4 => Some(KeySlot::Aut),
+            _ => None,
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            KeySlot::Sig => 0,
+            KeySlot::Dec => 1,
+            KeySlot::Aut => 2,
+        }
+    }
+
+    fn store_key(self) -> &'static str {
+        match self {
+            KeySlot::Sig => "key.sig",
+            KeySlot::Dec => "key.dec",
+            KeySlot::Aut => "key.aut",
+        }
+    }
+
+    fn attributes(self) -> &'static [u8] {
+        match self {
+            KeySlot::Dec => &ATTRIBUTES_CV25519,
+            _ => &ATTRIBUTES_ED25519,
+        }
+    }
+
+    fn public_key(self, secret: &[u8; 32]) -> [u8; 32] {
+        match self {
+            KeySlot::Dec => {
+                x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::from(*secret)).to_bytes()
+            }
+            _ => SigningKey::from_bytes(secret).verifying_key().to_bytes(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Pin {
+    /// PW1, the user PIN
+    User,
+    /// PW3, the admin PIN
+    Admin,
+}
+
+impl Pin {
+    fn store_key(self) -> &'static str {
+        match self {
+            Pin::User => "pw1",
+            Pin::Admin => "pw3",
+        }
+    }
+
+    fn min_len(self) -> usize {
+        match self {
+            Pin::User => 6,
+            Pin::Admin => 8,
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Pin::User => 0,
+            Pin::Admin => 1,
+        }
+    }
+}
+
+/// What the status screen shows about the card.
+pub struct Summary {
+    /// the serial number, as GnuPG shows it
+    pub serial: String,
+    pub signatures: u32,
+    /// per key slot (signing, decryption, authentication): `None` if there is no key, otherwise the
+    /// fingerprint the host recorded for it, which is all zeros until it does
+    pub keys: [Option<[u8; 20]>; 3],
+    pub pw1_retries: u8,
+    pub pw3_retries: u8,
+}
+
+struct Command<'a> {
+    cla: u8,
+    ins: u8,
+    p1: u8,
+    p2: u8,
+    data: &'a [u8],
+    /// expected response length; 0 if absent
+    le: usize,
+}
+
+/// Splits a short APDU into its parts. Extended lengths are not offered in the historical bytes, so
+/// they are not accepted either.
+fn parse_command(raw: &[u8]) -> Result<Command<'_>, u16> {
+    if raw.len() < 4 {
+        return Err(SW_WRONG_LENGTH);
+    }
+    let le_byte = |b: u8| if b == 0 { 256 } else { b as usize };
+    let (data, le) = match raw.len() {
+        4 => (&raw[4..], 0),
+        5 => (&raw[5..], le_byte(raw[4])),
+        _ => {
+            let lc = raw[4] as usize;
+            if lc == 0 {
+                return Err(SW_WRONG_LENGTH);
+            }
+            if raw.len() == 5 + lc {
+                (&raw[5..], 0)
+            } else if raw.len() == 6 + lc {
+                (&raw[5..5 + lc], le_byte(raw[5 + lc]))
+            } else {
+                return Err(SW_WRONG_LENGTH);
+            }
+        }
+    };
+    Ok(Command { cla: raw[0], ins: raw[1], p1: raw[2], p2: raw[3], data, le })
+}
+
+/// The answer to reset: T=1 only, IFSC 254, followed by the historical bytes and the check byte.
+pub fn atr() -> Vec<u8> {
+    let mut atr = vec![0x3B, 0x80 | HISTORICAL_BYTES.len() as u8, 0x81, 0x31, 0xFE, 0x45];
+    atr.extend_from_slice(&HISTORICAL_BYTES);
+    let tck = atr[1..].iter().fold(0, |acc, b| acc ^ b);
+    atr.push(tck);
+    atr
+}
+
+fn pin_hash(salt: &[u8], pin: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(pin);
+    hasher.finalize().into()
+}
+
+pub struct Card<S: Store, R: RngCore + CryptoRng> {
+    store: S,
+    rng: R,
+    /// PW1 verified for PSO:COMPUTE DIGITAL SIGNATURE (VERIFY with P2=81)
+    pw1_sign: bool,
+    /// PW1 verified for decryption and authentication (VERIFY with P2=82)
+    pw1_other: bool,
+    /// PW3 verified
+    pw3: bool,
+    terminated: bool,
+    /// INS P1 P2 of a command chain in progress, and its data so far
+    chain: Option<([u8; 3], Vec<u8>)>,
+    /// the part of the last response waiting on GET RESPONSE
+    pending: Vec<u8>,
+}
+
+impl<S: Store, R: RngCore + CryptoRng> Card<S, R> {
+    pub fn new(store: S, rng: R) -> Self {
+        let mut card = Card {
+            store,
+            rng,
+            pw1_sign: false,
+            pw1_other: false,
+            pw3: false,
+            terminated: false,
+            chain: None,
+            pending: Vec::new(),
+        };
+        if card.store.get(KEY_SERIAL).is_none() {
+            card.factory_reset();
+        }
+        card.terminated = card.store.get(KEY_TERMINATED).is_some();
+        card
+    }
+
+    /// Power cycle: drops all security state, as well as any half-finished command or response.
+    pub fn reset(&mut self) {
+        self.pw1_sign = false;
+        self.pw1_other = false;
+        self.pw3 = false;
+        self.chain = None;
+        self.pending.clear();
+    }
+
+    pub fn summary(&self) -> Summary {
+        let fingerprints = self.blob(0xC5, 60);
+        let mut keys = [None; 3];
+        for slot in KeySlot::ALL {
+            if self.key(slot).is_some() {
+                let start = slot.index() * 20;
+                keys[slot.index()] = Some(fingerprints[start..start + 20].try_into().unwrap());
+            }
+        }
+        Summary {
+            serial: self.serial().iter().map(|b| format!("{:02X}", b)).collect(),
+            signatures: self.signatures(),
+            keys,
+            pw1_retries: self.retries(Pin::User),
+            pw3_retries: self.retries(Pin::Admin),
+        }
+    }
+
+    /// Handles one command APDU and returns the response APDU, status word included.
+    pub fn process(&mut self, apdu: &[u8]) -> Vec<u8> {
+        match self.dispatch(apdu) {
+            Ok(data) => self.respond(data),
+            Err(sw) => sw.to_be_bytes().to_vec(),
+        }
+    }
+
+    /// Sends what fits of `data`, keeping the rest for GET RESPONSE.
+    fn respond(&mut self, mut data: Vec<u8>) -> Vec<u8> {
+        let sw = if data.len() > MAX_RESPONSE {
+            self.pending = data.split_off(MAX_RESPONSE);
+            // 0x00 stands for "256 or more"
+            SW_MORE_DATA | (self.pending.len().min(0x100) & 0xFF) as u16
+        } else {
+            SW_OK
+        };
+        data.extend_from_slice(&sw.to_be_bytes());
+        data
+    }
+
+    fn dispatch(&mut self, apdu: &[u8]) -> Result<Vec<u8>, u16> {
+        let cmd = parse_command(apdu)?;
+        if cmd.cla & !CLA_CHAINING != 0 {
+            return Err(SW_CLA_NOT_SUPPORTED);
+        }
+        if cmd.ins == INS_GET_RESPONSE {
+            return Ok(std::mem::take(&mut self.pending));
+        }
+        self.pending.clear();
+
+        let header = [cmd.ins, cmd.p1, cmd.p2];
+        let data = match self.chain.take() {
+            Some((chained, mut data)) if chained == header => {
+                data.extend_from_slice(cmd.data);
+                data
+            }
+            Some(_) => return Err(SW_LAST_COMMAND_EXPECTED),
+            None => cmd.data.to_vec(),
+        };
+        if cmd.cla & CLA_CHAINING != 0 {
+            if data.len() > MAX_CHAIN {
+                return Err(SW_WRONG_LENGTH);
+            }
+            self.chain = Some((header, data));
+            return Ok(Vec::new());
+        }
+
+        if self.terminated && cmd.ins != INS_SELECT && cmd.ins != INS_ACTIVATE_FILE {
+            return Err(SW_CONDITIONS);
+        }
+        let p1p2 = u16::from_be_bytes([cmd.p1, cmd.p2]);
+        match cmd.ins {
+            INS_SELECT => self.select(cmd.p1, &data),
+            INS_GET_DATA => self.get_data(p1p2),
+            INS_VERIFY => self.verify(cmd.p1, cmd.p2, &data),
+            INS_CHANGE_REFERENCE_DATA => self.change_reference_data(cmd.p1, cmd.p2, &data),
+            INS_RESET_RETRY_COUNTER => self.reset_retry_counter(cmd.p1, cmd.p2, &data),
+            INS_PUT_DATA => self.put_data(p1p2, &data),
+            INS_PUT_DATA_ODD if p1p2 == 0x3FFF => self.import_key(&data),
+            INS_PUT_DATA_ODD => Err(SW_WRONG_P1P2),
+            INS_GENERATE_ASYMMETRIC_KEY_PAIR => self.generate(cmd.p1, &data),
+            INS_PSO => match p1p2 {
+                0x9E9A => self.sign(&data),
+                0x8086 => self.decipher(&data),
+                _ => Err(SW_WRONG_P1P2),
+            },
+            INS_INTERNAL_AUTHENTICATE => self.authenticate(p1p2, &data),
+            INS_GET_CHALLENGE => self.challenge(cmd.le),
+            INS_TERMINATE_DF => self.terminate(),
+            INS_ACTIVATE_FILE => self.activate(),
+            _ => Err(SW_INS_NOT_SUPPORTED),
+        }
+    }
+
+    fn select(&mut self, p1: u8, name: &[u8]) -> Result<Vec<u8>, u16> {
+        // the host selects by a prefix of the AID, as it doesn't know the serial number yet
+        if p1 != 0x04 || name.len() < AID_SELECT_LEN || !self.aid().starts_with(name) {
+            return Err(SW_FILE_NOT_FOUND);
+        }
+        if self.terminated {
+            return Err(SW_TERMINATED);
+        }
+        Ok(Vec::new())
+    }
+
+    fn get_data(&self, tag: u16) -> Result<Vec<u8>, u16> {
+        match tag {
+            0x004F => Ok(self.aid()),
+            0x5F52 => Ok(HISTORICAL_BYTES.to_vec()),
+            0x005B | 0x5F2D | 0x5F35 | 0x5F50 | 0x005E => Ok(self.data_object(tag)),
+            0x0065 => {
+                let mut cardholder = tlv::encode(0x5B, &self.data_object(0x5B));
+                cardholder.extend(tlv::encode(0x5F2D, &self.data_object(0x5F2D)));
+                cardholder.extend(tlv::encode(0x5F35, &self.data_object(0x5F35)));
+                Ok(cardholder)
+            }
+            0x006E => {
+                let mut discretionary = Vec::new();
+                for tag in [0xC0, 0xC1, 0xC2, 0xC3, 0xC4, 0xC5, 0xC6, 0xCD, 0xDE] {
+                    discretionary.extend(tlv::encode(tag, &self.get_data(tag)?));
+                }
+                let mut related = tlv::encode(0x4F, &self.aid());
+                related.extend(tlv::encode(0x5F52, &HISTORICAL_BYTES));
+                related.extend(tlv::encode(0x73, &discretionary));
+                Ok(related)
+            }
+            0x007A => Ok(tlv::encode(0x93, &self.signatures().to_be_bytes()[1..])),
+            0x00C0 => Ok(EXTENDED_CAPABILITIES.to_vec()),
+            0x00C1 => Ok(KeySlot::Sig.attributes().to_vec()),
+            0x00C2 => Ok(KeySlot::Dec.attributes().to_vec()),
+            0x00C3 => Ok(KeySlot::Aut.attributes().to_vec()),
+            0x00C4 => Ok(vec![
+                self.pw1_validity(),
+                PW_MAX_LEN as u8,
+                0, // no resetting code
+                PW_MAX_LEN as u8,
+                self.retries(Pin::User),
+                0,
+                self.retries(Pin::Admin),
+            ]),
+            0x00C5 => Ok(self.blob(0xC5, 60)),
+            0x00C6 => Ok(self.blob(0xC6, 60)),
+            0x00CD => Ok(self.blob(0xCD, 12)),
+            0x00DE => {
+                let mut status = Vec::new();
+                for slot in KeySlot::ALL {
+                    status.push(slot.index() as u8 + 1);
+                    status
+                        .push(self.store.get(slot.store_key()).and_then(|k| k.first().copied()).unwrap_or(0));
+                }
+                Ok(status)
+            }
+            0x00FA => {
+                let mut algorithms = tlv::encode(0xC1, KeySlot::Sig.attributes());
+                algorithms.extend(tlv::encode(0xC2, KeySlot::Dec.attributes()));
+                algorithms.extend(tlv::encode(0xC3, KeySlot::Aut.attributes()));
+                Ok(algorithms)
+            }
+            _ => Err(SW_DATA_NOT_FOUND),
+        }
+    }
+
+    fn put_data(&mut self, tag: u16, data: &[u8]) -> Result<Vec<u8>, u16> {
+        if !self.pw3 {
+            return Err(SW_SECURITY_STATUS);
+        }
+        match tag {
+            0x005B | 0x5F2D | 0x5F35 | 0x5F50 | 0x005E => {
+                let max = match tag {
+                    0x005B => 39,
+                    0x5F2D => 8,
+                    0x5F35 => 1,
+                    _ => MAX_SPECIAL_DO,
+                };
+                if data.len() > max {
+                    return Err(SW_WRONG_LENGTH);
+                }
+                self.store.set(&format!("do.{:x}", tag), data);
+            }
+            0x00C1..=0x00C3 => {
+                // there is one algorithm per key, but GnuPG writes it out anyway when asked to
+                // change it; a trailing 0xFF is the import format "with public key"
+                let attributes = KeySlot::ALL[(tag - 0xC1) as usize].attributes();
+                let data = data.strip_suffix(&[0xFF]).unwrap_or(data);
+                if data != attributes {
+                    return Err(SW_WRONG_DATA);
+                }
+            }
+            0x00C4 => match data {
+                // only the first byte can be changed, but the whole DO may be written back
+                [validity] | [validity, _, _, _] if *validity <= 1 => {
+                    self.store.set(KEY_PW1_VALIDITY, &[*validity]);
+                }
+                _ => return Err(SW_WRONG_DATA),
+            },
+            0x00C7..=0x00C9 => self.put_blob_slice(0xC5, 60, (tag - 0xC7) as usize * 20, data)?,
+            0x00CA..=0x00CC => self.put_blob_slice(0xC6, 60, (tag - 0xCA) as usize * 20, data)?,
+            0x00CE..=0x00D0 => self.put_blob_slice(0xCD, 12, (tag - 0xCE) as usize * 4, data)?,
+            _ => return Err(SW_DATA_NOT_FOUND),
+        }
+        Ok(Vec::new())
+    }
+
+    /// Key import, as an extended header list: 4D { CRT, 7F48 { tags and lengths }, 5F48 { values } }
+    fn import_key(&mut self, data: &[u8]) -> Result<Vec<u8>, u16> {
+        if !self.pw3 {
+            return Err(SW_SECURITY_STATUS);
+        }
+        let list = tlv::find(data, 0x4D).ok_or(SW_WRONG_DATA)?;
+        let (crt, _, rest) = tlv::parse(list).ok_or(SW_WRONG_DATA)?;
+        let slot = KeySlot::from_crt(crt).ok_or(SW_WRONG_DATA)?;
+        let mut template = tlv::find(rest, 0x7F48).ok_or(SW_WRONG_DATA)?;
+        let values = tlv::find(rest, 0x5F48).ok_or(SW_WRONG_DATA)?;
+        let mut offset = 0;
+        let private = loop {
+            let (tag, len, next) = tlv::parse_header(template).ok_or(SW_WRONG_DATA)?;
+            if tag == 0x92 {
+                break values.get(offset..offset + len).ok_or(SW_WRONG_DATA)?;
+            }
+            offset += len;
+            template = next;
+        };
+        if private.is_empty() || private.len() > 32 {
+            return Err(SW_WRONG_DATA);
+        }
+        // the value is a big-endian integer, possibly without its leading zeros
+        let mut secret = [0u8; 32];
+        secret[32 - private.len()..].copy_from_slice(private);
+        if slot == KeySlot::Dec {
+            // X25519 scalars are little-endian on the wire everywhere else
+            secret.reverse();
+        }
+        self.set_key(slot, ORIGIN_IMPORTED, &secret);
+        Ok(Vec::new())
+    }
+
+    fn generate(&mut self, p1: u8, data: &[u8]) -> Result<Vec<u8>, u16> {
+        let (crt, _, _) = tlv::parse(data).ok_or(SW_WRONG_DATA)?;
+        let slot = KeySlot::from_crt(crt).ok_or(SW_WRONG_DATA)?;
+        match p1 {
+            0x80 => {
+                if !self.pw3 {
+                    return Err(SW_SECURITY_STATUS);
+                }
+                let mut secret = [0u8; 32];
+                self.rng.fill_bytes(&mut secret);
+                self.set_key(slot, ORIGIN_GENERATED, &secret);
+            }
+            0x81 => (),
+            _ => return Err(SW_WRONG_P1P2),
+        }
+        let secret = self.key(slot).ok_or(SW_DATA_NOT_FOUND)?;
+        Ok(tlv::encode(0x7F49, &tlv::encode(0x86, &slot.public_key(&secret))))
+    }
+
+    fn sign(&mut self, data: &[u8]) -> Result<Vec<u8>, u16> {
+        if !self.pw1_sign {
+            return Err(SW_SECURITY_STATUS);
+        }
+        let secret = self.key(KeySlot::Sig).ok_or(SW_DATA_NOT_FOUND)?;
+        if self.pw1_validity() == 0 {
+            self.pw1_sign = false;
+        }
+        let count = (self.signatures() + 1).min(0xFF_FFFF);
+        self.store.set(KEY_SIGNATURES, &count.to_be_bytes());
+        Ok(SigningKey::from_bytes(&secret).sign(data).to_bytes().to_vec())
+    }
+
+    /// ECDH with the sender's ephemeral key: A6 { 7F49 { 86 { public key } } }
+    fn decipher(&mut self, data: &[u8]) -> Result<Vec<u8>, u16> {
+        if !self.pw1_other {
+            return Err(SW_SECURITY_STATUS);
+        }
+        let secret = self.key(KeySlot::Dec).ok_or(SW_DATA_NOT_FOUND)?;
+        let public = tlv::find(data, 0xA6)
+            .and_then(|t| tlv::find(t, 0x7F49))
+            .and_then(|t| tlv::find(t, 0x86))
+            .ok_or(SW_WRONG_DATA)?;
+        // OpenPGP prefixes native Curve25519 points with 0x40
+        let public: [u8; 32] = match public {
+            [0x40, point @ ..] if point.len() == 32 => point.try_into().unwrap(),
+            point if point.len() == 32 => point.try_into().unwrap(),
+            _ => return Err(SW_WRONG_DATA),
+        };
+        let shared =
+            x25519_dalek::StaticSecret::from(secret).diffie_hellman(&x25519_dalek::PublicKey::from(public));
+        if !shared.was_contributory() {
+            return Err(SW_WRONG_DATA);
+        }
+        Ok(shared.to_bytes().to_vec())
+    }
+
+    fn authenticate(&mut self, p1p2: u16, data: &[u8]) -> Result<Vec<u8>, u16> {
+        if p1p2 != 0 {
+            return Err(SW_WRONG_P1P2);
+        }
+        if !self.pw1_other {
+            return Err(SW_SECURITY_STATUS);
+        }
+        let secret = self.key(KeySlot::Aut).ok_or(SW_DATA_NOT_FOUND)?;
+        Ok(SigningKey::from_bytes(&secret).sign(data).to_bytes().to_vec())
+    }
+
+    fn challenge(&mut self, len: usize) -> Result<Vec<u8>, u16> {
+        if len == 0 || len > MAX_CHALLENGE {
+            return Err(SW_WRONG_LENGTH);
+        }
+        let mut challenge = vec![0u8; len];
+        self.rng.fill_bytes(&mut challenge);
+        Ok(challenge)
+    }
+
+    fn verify(&mut self, p1: u8, p2: u8, data: &[u8]) -> Result<Vec<u8>, u16> {
+        let pin = match p2 {
+            0x81 | 0x82 => Pin::User,
+            0x83 => Pin::Admin,
+            _ => return Err(SW_WRONG_P1P2),
+        };
+        match p1 {
+            0x00 => (),
+            0xFF => {
+                self.set_verified(p2, false);
+                return Ok(Vec::new());
+            }
+            _ => return Err(SW_WRONG_P1P2),
+        }
+        if data.is_empty() {
+            // a status query
+            return match (self.is_verified(p2), self.retries(pin)) {
+                (true, _) => Ok(Vec::new()),
+                (false, 0) => Err(SW_PIN_BLOCKED),
+                (false, retries) => Err(SW_PIN_RETRIES | retries as u16),
+            };
+        }
+        self.set_verified(p2, false);
+        self.check_pin(pin, data)?;
+        self.set_verified(p2, true);
+        Ok(Vec::new())
+    }
+
+    /// The old PIN followed by the new one
+    fn change_reference_data(&mut self, p1: u8, p2: u8, data: &[u8]) -> Result<Vec<u8>, u16> {
+        let pin = match (p1, p2) {
+            (0x00, 0x81) => Pin::User,
+            (0x00, 0x83) => Pin::Admin,
+            _ => return Err(SW_WRONG_P1P2),
+        };
+        let record = self.store.get(pin.store_key()).ok_or(SW_MEMORY_FAILURE)?;
+        let old_len = record[0] as usize;
+        if data.len() <= old_len {
+            // still counts as a guess at the old PIN
+            self.check_pin(pin, data)?;
+            return Err(SW_WRONG_LENGTH);
+        }
+        let (old, new) = data.split_at(old_len);
+        self.check_pin(pin, old)?;
+        Self::check_new_pin(pin, new)?;
+        self.set_pin(pin, new);
+        Ok(Vec::new())
+    }
+
+    /// Only the variant that sets a new PW1 under PW3 is offered, as there is no resetting code.
+    fn reset_retry_counter(&mut self, p1: u8, p2: u8, data: &[u8]) -> Result<Vec<u8>, u16> {
+        match (p1, p2) {
+            (0x02, 0x81) => {
+                if !self.pw3 {
+                    return Err(SW_SECURITY_STATUS);
+                }
+                Self::check_new_pin(Pin::User, data)?;
+                self.set_pin(Pin::User, data);
+                Ok(Vec::new())
+            }
+            (0x00, 0x81) => Err(SW_PIN_BLOCKED),
+            _ => Err(SW_WRONG_P1P2),
+        }
+    }
+
+    /// Allowed once PW3 is verified, or blocked, so that a card with a forgotten admin PIN can be
+    /// recovered.
+    fn terminate(&mut self) -> Result<Vec<u8>, u16> {
+        if !self.pw3 && self.retries(Pin::Admin) != 0 {
+            return Err(SW_SECURITY_STATUS);
+        }
+        self.store.set(KEY_TERMINATED, &[1]);
+        self.terminated = true;
+        self.reset();
+        Ok(Vec::new())
+    }
+
+    fn activate(&mut self) -> Result<Vec<u8>, u16> {
+        if self.terminated {
+            self.factory_reset();
+        }
+        Ok(Vec::new())
+    }
+
+    /// Wipes keys, data objects and PINs back to the defaults. The serial number survives, as it would
+    /// on a physical card.
+    fn factory_reset(&mut self) {
+        let serial = match self.store.get(KEY_SERIAL) {
+            Some(serial) if serial.len() == 4 => serial,
+            _ => {
+                let mut serial = vec![0u8; 4];
+                self.rng.fill_bytes(&mut serial);
+                serial
+            }
+        };
+        self.store.clear();
+        self.store.set(KEY_SERIAL, &serial);
+        self.store.set(KEY_RETRIES, &[PW_RETRIES, PW_RETRIES]);
+        self.set_pin(Pin::User, PW1_DEFAULT.as_bytes());
+        self.set_pin(Pin::Admin, PW3_DEFAULT.as_bytes());
+        self.terminated = false;
+        self.reset();
+    }
+
+    fn check_pin(&mut self, pin: Pin, candidate: &[u8]) -> Result<(), u16> {
+        let retries = self.retries(pin);
+        if retries == 0 {
+            return Err(SW_PIN_BLOCKED);
+        }
+        let record = self.store.get(pin.store_key()).ok_or(SW_MEMORY_FAILURE)?;
+        if record.len() != 1 + PW_SALT_LEN + 32 {
+            return Err(SW_MEMORY_FAILURE);
+        }
+        // count the attempt before checking it, so that pulling the plug doesn't give a free guess
+        self.set_retries(pin, retries - 1);
+        let hash = pin_hash(&record[1..1 + PW_SALT_LEN], candidate);
+        if bool::from(hash[..].ct_eq(&record[1 + PW_SALT_LEN..])) {
+            self.set_retries(pin, PW_RETRIES);
+            Ok(())
+        } else {
+            Err(SW_PIN_RETRIES | (retries - 1) as u16)
+        }
+    }
+
+    fn check_new_pin(pin: Pin, new: &[u8]) -> Result<(), u16> {
+        if new.len() < pin.min_len() || new.len() > PW_MAX_LEN { Err(SW_WRONG_LENGTH) } else { Ok(()) }
+    }
+
+    /// Stores `value` as length, salt and salted hash, and resets its retry counter.
+    fn set_pin(&mut self, pin: Pin, value: &[u8]) {
+        let mut record = vec![value.len() as u8];
+        let mut salt = [0u8; PW_SALT_LEN];
+        self.rng.fill_bytes(&mut salt);
+        record.extend_from_slice(&salt);
+        record.extend_from_slice(&pin_hash(&salt, value));
+        self.store.set(pin.store_key(), &record);
+        self.set_retries(pin, PW_RETRIES);
+    }
+
+    fn retries(&self, pin: Pin) -> u8 {
+        self.store.get(KEY_RETRIES).and_then(|r| r.get(pin.index()).copied()).unwrap_or(0)
+    }
+
+    fn set_retries(&mut self, pin: Pin, retries: u8) {
+        let mut all = self.store.get(KEY_RETRIES).unwrap_or_default();
+        all.resize(2, 0);
+        all[pin.index()] = retries;
+        self.store.set(KEY_RETRIES, &all);
+    }
+
+    fn is_verified(&self, p2: u8) -> bool {
+        match p2 {
+            0x81 => self.pw1_sign,
+            0x82 => self.pw1_other,
+            _ => self.pw3,
+        }
+    }
+
+    fn set_verified(&mut self, p2: u8, verified: bool) {
+        match p2 {
+            0x81 => self.pw1_sign = verified,
+            0x82 => self.pw1_other = verified,
+            _ => self.pw3 = verified,
+        }
+    }
+
+    /// 0 if a PW1 verification is good for a single signature, 1 if it lasts until reset
+    fn pw1_validity(&self) -> u8 { self.store.get(KEY_PW1_VALIDITY).map(|v| v[0]).unwrap_or(1) }
+
+    fn serial(&self) -> [u8; 4] {
+        self.store.get(KEY_SERIAL).and_then(|s| s.try_into().ok()).unwrap_or_default()
+    }
+
+    fn aid(&self) -> Vec<u8> {
+        let mut aid = AID_PREFIX.to_vec();
+        aid.extend_from_slice(&self.serial());
+        aid.extend_from_slice(&[0, 0]);
+        aid
+    }
+
+    fn signatures(&self) -> u32 {
+        match self.store.get(KEY_SIGNATURES) {
+            Some(count) if count.len() == 4 => u32::from_be_bytes(count.try_into().unwrap()),
+            _ => 0,
+        }
+    }
+
+    fn key(&self, slot: KeySlot) -> Option<[u8; 32]> {
+        self.store.get(slot.store_key()).and_then(|k| k.get(1..).and_then(|s| s.try_into().ok()))
+    }
+
+    fn set_key(&mut self, slot: KeySlot, origin: u8, secret: &[u8; 32]) {
+        let mut record = vec![origin];
+        record.extend_from_slice(secret);
+        self.store.set(slot.store_key(), &record);
+        if slot == KeySlot::Sig {
+            self.store.remove(KEY_SIGNATURES);
+        }
+    }
+
+    fn data_object(&self, tag: u16) -> Vec<u8> {
+        self.store.get(&format!("do.{:x}", tag)).unwrap_or_default()
+    }
+
+    /// A fixed-size DO that is all zeros until written
+    fn blob(&self, tag: u16, len: usize) -> Vec<u8> {
+        match self.store.get(&format!("do.{:x}", tag)) {
+            Some(blob) if blob.len() == len => blob,
+            _ => vec![0u8; len],
+        }
+    }
+
+    /// Writes one of the fingerprints or timestamps inside a combined DO
+    fn put_blob_slice(&mut self, tag: u16, len: usize, offset: usize, data: &[u8]) -> Result<(), u16> {
+        let part = if tag == 0xCD { 4 } else { 20 };
+        if data.len() != part {
+            return Err(SW_WRONG_LENGTH);
+        }
+        let mut blob = self.blob(tag, len);
+        blob[offset..offset + part].copy_from_slice(data);
+        self.store.set(&format!("do.{:x}", tag), &blob);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MemStore(HashMap<String, Vec<u8>>);
+
+    impl Store for MemStore {
+        fn get(&self, key: &str) -> Option<Vec<u8>> { self.0.get(key).cloned() }
+
+        fn set(&mut self, key: &str, value: &[u8]) { self.0.insert(key.to_string(), value.to_vec()); }
+
+        fn remove(&mut self, key: &str) { self.0.remove(key); }
+
+        fn clear(&mut self) { self.0.clear(); }
+    }
+
+    /// Not random at all, which is the point
+    struct CountingRng(u8);
+
+    impl RngCore for CountingRng {
+        fn next_u32(&mut self) -> u32 { rand_core::impls::next_u32_via_fill(self) }
+
+        fn next_u64(&mut self) -> u64 { rand_core::impls::next_u64_via_fill(self) }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for b in dest.iter_mut() {
+                self.0 = self.0.wrapping_add(1);
+                *b = self.0;
+            }
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    impl CryptoRng for CountingRng {}
+
+    fn card() -> Card<MemStore, CountingRng> { Card::new(MemStore::default(), CountingRng(0)) }
+
+    fn apdu(cla: u8, ins: u8, p1: u8, p2: u8, data: &[u8]) -> Vec<u8> {
+        let mut apdu = vec![cla, ins, p1, p2];
+        if !data.is_empty() {
+            apdu.push(data.len() as u8);
+            apdu.extend_from_slice(data);
+        }
+        apdu.push(0);
+        apdu
+    }
+
+    /// Runs a command and splits off the status word
+    fn run(card: &mut Card<MemStore, CountingRng>, ins: u8, p1: u8, p2: u8, data: &[u8]) -> (Vec<u8>, u16) {
+        let mut response = card.process(&apdu(0, ins, p1, p2, data));
+        let sw = response.split_off(response.len() - 2);
+        (response, u16::from_be_bytes([sw[0], sw[1]]))
+    }
+
+    fn admin(card: &mut Card<MemStore, CountingRng>) {
+        assert_eq!(run(card, INS_VERIFY, 0, 0x83, PW3_DEFAULT.as_bytes()).1, SW_OK);
+    }
+
+    #[test]
+    fn test_atr() {
+        let atr = atr();
+        assert_eq!(atr.len(), 6 + HISTORICAL_BYTES.len() + 1);
+        // T0 through TCK XOR to zero
+        assert_eq!(atr[1..].iter().fold(0, |acc, b| acc ^ b), 0);
+    }
+
+    #[test]
+    fn test_select_and_get_data() {
+        let mut card = card();
+        assert_eq!(run(&mut card, INS_SELECT, 0x04, 0, &AID_PREFIX[..6]).1, SW_OK);
+        assert_eq!(run(&mut card, INS_SELECT, 0x04, 0, &[0xA0, 0, 0, 0, 0x03, 0x08]).1, SW_FILE_NOT_FOUND);
+
+        let (aid, sw) = run(&mut card, INS_GET_DATA, 0, 0x4F, &[]);
+        assert_eq!(sw, SW_OK);
+        assert_eq!(aid.len(), 16);
+        assert_eq!(aid[..10], AID_PREFIX);
+        assert_eq!(card.summary().serial.len(), 8);
+
+        let (related, sw) = run(&mut card, INS_GET_DATA, 0, 0x6E, &[]);
+        assert_eq!(sw, SW_OK);
+        let discretionary = tlv::find(&related, 0x73).unwrap();
+        assert_eq!(tlv::find(discretionary, 0xC4).unwrap(), &[1, 127, 0, 127, 3, 0, 3]);
+        assert_eq!(tlv::find(discretionary, 0xC2).unwrap(), &ATTRIBUTES_CV25519);
+        assert_eq!(tlv::find(discretionary, 0xDE).unwrap(), &[1, 0, 2, 0, 3, 0]);
+        assert_eq!(run(&mut card, INS_GET_DATA, 0x01, 0x01, &[]).1, SW_DATA_NOT_FOUND);
+    }
+
+    #[test]
+    fn test_verify() {
+        let mut card = card();
+        assert_eq!(run(&mut card, INS_VERIFY, 0, 0x81, &[]).1, SW_PIN_RETRIES | 3);
+        assert_eq!(run(&mut card, INS_VERIFY, 0, 0x81, b"654321").1, SW_PIN_RETRIES | 2);
+        assert_eq!(run(&mut card, INS_VERIFY, 0, 0x82, b"654321").1, SW_PIN_RETRIES | 1);
+        assert_eq!(run(&mut card, INS_VERIFY, 0, 0x81, PW1_DEFAULT.as_bytes()).1, SW_OK);
+        assert_eq!(run(&mut card, INS_VERIFY, 0, 0x81, &[]).1, SW_OK);
+        // P2=81 and P2=82 are verified separately, but share a retry counter
+        assert_eq!(run(&mut card, INS_VERIFY, 0, 0x82, &[]).1, SW_PIN_RETRIES | 3);
+        assert_eq!(run(&mut card, INS_VERIFY, 0xFF, 0x81, &[]).1, SW_OK);
+        assert_eq!(run(&mut card, INS_VERIFY, 0, 0x81, &[]).1, SW_PIN_RETRIES | 3);
+
+        for retries in (0..3).rev() {
+            assert_eq!(run(&mut card, INS_VERIFY, 0, 0x83, b"wrongpin").1, SW_PIN_RETRIES | retries);
+        }
+        assert_eq!(run(&mut card, INS_VERIFY, 0, 0x83, PW3_DEFAULT.as_bytes()).1, SW_PIN_BLOCKED);
+        assert_eq!(card.summary().pw3_retries, 0);
+        // a power cycle doesn't help
+        card.reset();
+        assert_eq!(run(&mut card, INS_VERIFY, 0, 0x83, &[]).1, SW_PIN_BLOCKED);
+    }
+
+    #[test]
+    fn test_change_pins() {
+        let mut card = card();
+        let mut data = PW1_DEFAULT.as_bytes().to_vec();
+        data.extend_from_slice(b"12345");
+        assert_eq!(run(&mut card, INS_CHANGE_REFERENCE_DATA, 0, 0x81, &data).1, SW_WRONG_LENGTH);
+        data.extend_from_slice(b"6789");
+        assert_eq!(run(&mut card, INS_CHANGE_REFERENCE_DATA, 0, 0x81, &data).1, SW_OK);
+        assert_eq!(run(&mut card, INS_VERIFY, 0, 0x82, PW1_DEFAULT.as_bytes()).1, SW_PIN_RETRIES | 2);
+        assert_eq!(run(&mut card, INS_VERIFY, 0, 0x82, b"123456789").1, SW_OK);
+
+        // setting PW1 under PW3
+        assert_eq!(run(&mut card, INS_RESET_RETRY_COUNTER, 0x02, 0x81, b"000000").1, SW_SECURITY_STATUS);
+        admin(&mut card);
+        assert_eq!(run(&mut card, INS_RESET_RETRY_COUNTER, 0x02, 0x81, b"000000").1, SW_OK);
+        assert_eq!(run(&mut card, INS_VERIFY, 0, 0x81, b"000000").1, SW_OK);
+        assert_eq!(run(&mut card, INS_RESET_RETRY_COUNTER, 0x00, 0x81, b"000000").1, SW_PIN_BLOCKED);
+    }
+
+    #[test]
+    fn test_generate_and_sign() {
+        let mut card = card();
+        let crt = [0xB6, 0x00];
+        assert_eq!(run(&mut card, INS_GENERATE_ASYMMETRIC_KEY_PAIR, 0x80, 0, &crt).1, SW_SECURITY_STATUS);
+        assert_eq!(run(&mut card, INS_GENERATE_ASYMMETRIC_KEY_PAIR, 0x81, 0, &crt).1, SW_DATA_NOT_FOUND);
+        admin(&mut card);
+        let (generated, sw) = run(&mut card, INS_GENERATE_ASYMMETRIC_KEY_PAIR, 0x80, 0, &crt);
+        assert_eq!(sw, SW_OK);
+        let (read, _) = run(&mut card, INS_GENERATE_ASYMMETRIC_KEY_PAIR, 0x81, 0, &crt);
+        assert_eq!(generated, read);
+        let public = tlv::find(tlv::find(&read, 0x7F49).unwrap(), 0x86).unwrap();
+        let public = VerifyingKey::from_bytes(public.try_into().unwrap()).unwrap();
+        assert_eq!(card.summary().keys, [Some([0; 20]), None, None]);
+
+        let digest = [0x5Au8; 32];
+        assert_eq!(run(&mut card, INS_PSO, 0x9E, 0x9A, &digest).1, SW_SECURITY_STATUS);
+        // PW1 for other purposes doesn't unlock signing
+        assert_eq!(run(&mut card, INS_VERIFY, 0, 0x82, PW1_DEFAULT.as_bytes()).1, SW_OK);
+        assert_eq!(run(&mut card, INS_PSO, 0x9E, 0x9A, &digest).1, SW_SECURITY_STATUS);
+        assert_eq!(run(&mut card, INS_VERIFY, 0, 0x81, PW1_DEFAULT.as_bytes()).1, SW_OK);
+        let (signature, sw) = run(&mut card, INS_PSO, 0x9E, 0x9A, &digest);
+        assert_eq!(sw, SW_OK);
+        public.verify(&digest, &Signature::from_bytes(&signature.try_into().unwrap())).unwrap();
+        assert_eq!(run(&mut card, INS_PSO, 0x9E, 0x9A, &digest).1, SW_OK);
+        assert_eq!(card.summary().signatures, 2);
+        assert_eq!(run(&mut card, INS_GET_DATA, 0, 0x7A, &[]).0, [0x93, 3, 0, 0, 2]);
+
+        // single-use PW1 for signing
+        assert_eq!(run(&mut card, INS_PUT_DATA, 0, 0xC4, &[0]).1, SW_OK);
+        assert_eq!(run(&mut card, INS_PSO, 0x9E, 0x9A, &digest).1, SW_OK);
+        assert_eq!(run(&mut card, INS_PSO, 0x9E, 0x9A, &digest).1, SW_SECURITY_STATUS);
+
+        // a new key restarts the count
+        assert_eq!(run(&mut card, INS_GENERATE_ASYMMETRIC_KEY_PAIR, 0x80, 0, &crt).1, SW_OK);
+        assert_eq!(card.summary().signatures, 0);
+    }
+
+    #[test]
+    fn test_import_and_decipher() {
+        let mut card = card();
+        admin(&mut card);
+        // a cv25519 key as GnuPG sends it: big-endian
+        let secret = [7u8; 32];
+        let mut private = secret;
+        private.reverse();
+        let mut list = vec![0xB8, 0x00];
+        list.extend(tlv::encode(0x7F48, &[0x92, 0x20]));
+        list.extend(tlv::encode(0x5F48, &private));
+        let import = tlv::encode(0x4D, &list);
+        // sent as a chain, the way GnuPG sends key imports
+        let (first, second) = import.split_at(20);
+        assert_eq!(card.process(&apdu(CLA_CHAINING, INS_PUT_DATA_ODD, 0x3F, 0xFF, first)), [0x90, 0]);
+        assert_eq!(run(&mut card, INS_PUT_DATA_ODD, 0x3F, 0xFF, second).1, SW_OK);
+        assert_eq!(run(&mut card, INS_GET_DATA, 0, 0xDE, &[]).0, [1, 0, 2, 2, 3, 0]);
+
+        let ours = x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::from(secret));
+        let (read, _) = run(&mut card, INS_GENERATE_ASYMMETRIC_KEY_PAIR, 0x81, 0, &[0xB8, 0x00]);
+        assert_eq!(tlv::find(tlv::find(&read, 0x7F49).unwrap(), 0x86).unwrap(), ours.as_bytes());
+
+        let ephemeral = x25519_dalek::StaticSecret::from([9u8; 32]);
+        let mut point = vec![0x40];
+        point.extend_from_slice(x25519_dalek::PublicKey::from(&ephemeral).as_bytes());
+        let cipher = tlv::encode(0xA6, &tlv::encode(0x7F49, &tlv::encode(0x86, &point)));
+        assert_eq!(run(&mut card, INS_PSO, 0x80, 0x86, &cipher).1, SW_SECURITY_STATUS);
+        assert_eq!(run(&mut card, INS_VERIFY, 0, 0x82, PW1_DEFAULT.as_bytes()).1, SW_OK);
+        let (shared, sw) = run(&mut card, INS_PSO, 0x80, 0x86, &cipher);
+        assert_eq!(sw, SW_OK);
+        assert_eq!(shared, ephemeral.diffie_hellman(&ours).as_bytes());
+
+        // a low-order point would give away nothing useful, but is refused all the same
+        let low_order = tlv::encode(0xA6, &tlv::encode(0x7F49, &tlv::encode(0x86, &[0u8; 32])));
+        assert_eq!(run(&mut card, INS_PSO, 0x80, 0x86, &low_order).1, SW_WRONG_DATA);
+    }
+
+    #[test]
+    fn test_chaining_and_get_response() {
+        let mut card = card();
+        admin(&mut card);
+        let url = [b'u'; 200];
+        assert_eq!(card.process(&apdu(CLA_CHAINING, INS_PUT_DATA, 0x5F, 0x50, &url[..100])), [0x90, 0]);
+        // the chain has to continue with the same command
+        assert_eq!(run(&mut card, INS_GET_DATA, 0x5F, 0x50, &[]).1, SW_LAST_COMMAND_EXPECTED);
+        assert_eq!(card.process(&apdu(CLA_CHAINING, INS_PUT_DATA, 0x5F, 0x50, &url[..100])), [0x90, 0]);
+        assert_eq!(run(&mut card, INS_PUT_DATA, 0x5F, 0x50, &url[100..]).1, SW_OK);
+        assert_eq!(run(&mut card, INS_GET_DATA, 0x5F, 0x50, &[]).0, url);
+
+        let mut response = card.respond(vec![0xAB; 300]);
+        assert_eq!(response.len(), MAX_RESPONSE + 2);
+        assert_eq!(response.split_off(MAX_RESPONSE), [0x61, 44]);
+        let (rest, sw) = run(&mut card, INS_GET_RESPONSE, 0, 0, &[]);
+        assert_eq!((rest.len(), sw), (44, SW_OK));
+    }
+
+    #[test]
+    fn test_terminate_and_activate() {
+        let mut card = card();
+        let serial = card.summary().serial;
+        admin(&mut card);
+        assert_eq!(run(&mut card, INS_GENERATE_ASYMMETRIC_KEY_PAIR, 0x80, 0, &[0xA4, 0x00]).1, SW_OK);
+        assert_eq!(run(&mut card, INS_PUT_DATA, 0, 0x5B, b"Doe<<Jane").1, SW_OK);
+        card.reset();
+        assert_eq!(run(&mut card, INS_TERMINATE_DF, 0, 0, &[]).1, SW_SECURITY_STATUS);
+        // a blocked admin PIN is reason enough, which is how a forgotten one is recovered from
+        for _ in 0..3 {
+            run(&mut card, INS_VERIFY, 0, 0x83, b"forgotten");
+        }
+        assert_eq!(run(&mut card, INS_TERMINATE_DF, 0, 0, &[]).1, SW_OK);
+        assert_eq!(run(&mut card, INS_SELECT, 0x04, 0, &AID_PREFIX[..6]).1, SW_TERMINATED);
+        assert_eq!(run(&mut card, INS_GET_DATA, 0, 0x4F, &[]).1, SW_CONDITIONS);
+        assert_eq!(run(&mut card, INS_ACTIVATE_FILE, 0, 0, &[]).1, SW_OK);
+
+        assert_eq!(run(&mut card, INS_SELECT, 0x04, 0, &AID_PREFIX[..6]).1, SW_OK);
+        let summary = card.summary();
+        assert_eq!(summary.serial, serial);
+        assert_eq!(summary.keys, [None, None, None]);
+        assert_eq!((summary.pw1_retries, summary.pw3_retries), (3, 3));
+        assert!(run(&mut card, INS_GET_DATA, 0, 0x5B, &[]).0.is_empty());
+        admin(&mut card);
+    }
+}
@@ -0,0 +1,321 @@
+#![cfg_attr(target_os = "none", no_std)]
+#![cfg_attr(target_os = "none", no_main)]
+
+//! An OpenPGP smart card on the CCID reader of the USB stack. PINs entered through the host's pinpad
+//! support are typed on the device; everything else is driven from the host, e.g. `gpg --card-edit`.
+
+mod card;
+mod store;
+mod tlv;
+
+use core::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+use card::Card;
+use gam::TextEntryPayload;
+use graphics_server::api::GlyphStyle;
+use graphics_server::{DrawStyle, Gid, PixelColor, Point, Rectangle, TextBounds, TextView};
+use locales::t;
+use num_traits::*;
+use store::PddbStore;
+use usb_device_xous::{CcidEvent, UsbDeviceType, UsbHid};
+
+pub(crate) const SERVER_NAME_PGPCARD: &str = "_OpenPGP card_";
+
+/// Status words of a pinpad operation that ended on the device rather than on the card
+const SW_PINPAD_CANCELLED: [u8; 2] = [0x64, 0x01];
+const SW_PINPAD_MISMATCH: [u8; 2] = [0x64, 0x02];
+const INS_CHANGE_REFERENCE_DATA: u8 = 0x24;
+const INS_SELECT: u8 = 0xA4;
+const INS_GET_DATA: u8 = 0xCA;
+const INS_GET_RESPONSE: u8 = 0xC0;
+
+type PgpCard = Card<PddbStore, trng::Trng>;
+
+/// Top level application events.
+#[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub(crate) enum PgpCardOp {
+    /// Redraw the screen
+    Redraw = 0,
+
+    /// Enter/exit app
+    FocusChange,
+
+    /// Quit the application
+    Quit,
+}
+
+struct PgpCardUx {
+    content: Gid,
+    gam: gam::Gam,
+    _gam_token: [u32; 4],
+    screensize: Point,
+}
+
+impl PgpCardUx {
+    fn new(xns: &xous_names::XousNames, sid: xous::SID) -> Self {
+        let gam = gam::Gam::new(&xns).expect("Can't connect to GAM");
+        let gam_token = gam
+            .register_ux(gam::UxRegistration {
+                app_name: String::from(gam::APP_NAME_PGPCARD),
+                ux_type: gam::UxType::Chat,
+                predictor: None,
+                listener: sid.to_array(),
+                redraw_id: PgpCardOp::Redraw.to_u32().unwrap(),
+                gotinput_id: None,
+                audioframe_id: None,
+                rawkeys_id: None,
+                focuschange_id: Some(PgpCardOp::FocusChange.to_u32().unwrap()),
+            })
+            .expect("Could not register GAM UX")
+            .unwrap();
+
+        let content = gam.request_content_canvas(gam_token).expect("Could not get content canvas");
+        let screensize = gam.get_canvas_bounds(content).expect("Could not get canvas dimensions");
+        Self { gam, _gam_token: gam_token, content, screensize }
+    }
+
+    /// Clear the entire screen.
+    fn clear_area(&self) {
+        self.gam
+            .draw_rectangle(
+                self.content,
+                Rectangle::new_with_style(
+                    Point::new(0, 0),
+                    self.screensize,
+                    DrawStyle { fill_color: Some(PixelColor::Light), stroke_color: None, stroke_width: 0 },
+                ),
+            )
+            .expect("can't clear content area");
+    }
+
+    /// Shows the serial number, the keys, the signature count and the PIN retry counters.
+    fn redraw(&mut self, card: &PgpCard) {
+        self.clear_area();
+        let summary = card.summary();
+
+        let mut text_view = TextView::new(
+            self.content,
+            TextBounds::GrowableFromTl(Point::new(8, 8), (self.screensize.x - 16) as u16),
+        );
+        text_view.border_width = 1;
+        text_view.draw_border = true;
+        text_view.clear_area = true;
+        text_view.rounded_border = Some(3);
+        text_view.style = GlyphStyle::Regular;
+
+        writeln!(text_view.text, "{} {}", t!("pgpcard.serial", locales::lang()), summary.serial).ok();
+        let labels = [
+            t!("pgpcard.key_sig", locales::lang()),
+            t!("pgpcard.key_dec", locales::lang()),
+            t!("pgpcard.key_aut", locales::lang()),
+        ];
+        for (label, key) in labels.iter().zip(summary.keys.iter()) {
+            match key {
+                None => writeln!(text_view.text, "{} {}", label, t!("pgpcard.no_key", locales::lang())).ok(),
+                Some(fingerprint) if fingerprint.iter().all(|&b| b == 0) => {
+                    writeln!(text_view.text, "{} {}", label, t!("pgpcard.no_fingerprint", locales::lang()))
+                        .ok()
+                }
+                // the last 8 bytes are what GnuPG shows as the long key ID
+                Some(fingerprint) => writeln!(
+                    text_view.text,
+                    "{} {}",
+                    label,
+                    fingerprint[12..].iter().map(|b| format!("{:02X}", b)).collect::<String>()
+                )
+                .ok(),
+            };
+        }
+        writeln!(text_view.text, "{} {}", t!("pgpcard.signatures", locales::lang()), summary.signatures).ok();
+        write!(
+            text_view.text,
+            "{} {}/{}",
+            t!("pgpcard.retries", locales::lang()),
+            summary.pw1_retries,
+            summary.pw3_retries
+        )
+        .ok();
+
+        self.gam.post_textview(&mut text_view).expect("Could not render text view");
+        self.gam.redraw().expect("Could not redraw screen");
+    }
+}
+
+fn pin_validator(input: &TextEntryPayload) -> Option<String> {
+    // empty is how a PIN entry is cancelled; the card enforces the real minimum per PIN
+    let len = input.as_str().len();
+    if len == 0 || (6..=127).contains(&len) {
+        None
+    } else {
+        Some(String::from(t!("pgpcard.pin_length", locales::lang())))
+    }
+}
+
+/// Asks for one PIN. `None` if the user cancelled by entering nothing.
+fn prompt_pin(modals: &modals::Modals, prompt: &str) -> Option<String> {
+    match modals.alert_builder(prompt).field(None, Some(pin_validator)).build() {
+        Ok(entry) => {
+            let pin = entry.content()[0].content.to_string();
+            if pin.is_empty() { None } else { Some(pin) }
+        }
+        Err(e) => {
+            log::error!("PIN entry failed: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Asks for a new PIN twice. `Err` carries the pinpad status to send back instead.
+fn prompt_new_pin(modals: &modals::Modals, admin: bool) -> Result<String, [u8; 2]> {
+    let prompt = if admin {
+        t!("pgpcard.new_admin_pin", locales::lang())
+    } else {
+        t!("pgpcard.new_user_pin", locales::lang())
+    };
+    let pin = prompt_pin(modals, prompt).ok_or(SW_PINPAD_CANCELLED)?;
+    let again = prompt_pin(modals, t!("pgpcard.repeat_pin", locales::lang())).ok_or(SW_PINPAD_CANCELLED)?;
+    if pin != again {
+        modals.show_notification(t!("pgpcard.pin_mismatch", locales::lang()), None).ok();
+        return Err(SW_PINPAD_MISMATCH);
+    }
+    Ok(pin)
+}
+
+/// Completes a pinpad command by asking for the PINs on the device. `header` is the CLA INS P1 P2 the
+/// host sent, and the result is the APDU to run on the card.
+fn pinpad_apdu(modals: &modals::Modals, header: [u8; 4], modify: bool) -> Result<Vec<u8>, [u8; 2]> {
+    let admin = header[3] == 0x83;
+    let mut data = Vec::new();
+    // setting PW1 under a verified PW3 is the one modify that doesn't need the old PIN
+    if !modify || header[1] == INS_CHANGE_REFERENCE_DATA {
+        let prompt = match (modify, header[3]) {
+            (false, 0x81) => t!("pgpcard.user_pin_sign", locales::lang()),
+            (_, 0x83) => t!("pgpcard.admin_pin", locales::lang()),
+            _ => t!("pgpcard.user_pin", locales::lang()),
+        };
+        data.extend_from_slice(prompt_pin(modals, prompt).ok_or(SW_PINPAD_CANCELLED)?.as_bytes());
+    }
+    if modify {
+        data.extend_from_slice(prompt_new_pin(modals, admin)?.as_bytes());
+    }
+    if data.len() > 255 {
+        return Err(SW_PINPAD_CANCELLED);
+    }
+    let mut apdu = header.to_vec();
+    apdu.push(data.len() as u8);
+    apdu.extend_from_slice(&data);
+    Ok(apdu)
+}
+
+/// Answers the host for as long as the card is in the reader.
+fn card_thread(card: Arc<Mutex<PgpCard>>, redraw_cid: xous::CID) {
+    let xns = xous_names::XousNames::new().unwrap();
+    let modals = modals::Modals::new(&xns).expect("can't connect to Modals server");
+    let usb = UsbHid::new();
+    loop {
+        let event = match usb.ccid_wait_event() {
+            Ok(event) => event,
+            Err(e) => {
+                log::error!("CCID slot unavailable, stopping the card: {:?}", e);
+                return;
+            }
+        };
+        // reads don't change anything on the status screen
+        let mut changed = true;
+        let response = match event {
+            CcidEvent::Apdu(apdu) => {
+                changed = !matches!(apdu.get(1), Some(&INS_SELECT | &INS_GET_DATA | &INS_GET_RESPONSE));
+                card.lock().unwrap().process(&apdu)
+            }
+            CcidEvent::VerifyPin(header) => match pinpad_apdu(&modals, header, false) {
+                Ok(apdu) => card.lock().unwrap().process(&apdu),
+                Err(sw) => sw.to_vec(),
+            },
+            CcidEvent::ModifyPin(header) => match pinpad_apdu(&modals, header, true) {
+                Ok(apdu) => card.lock().unwrap().process(&apdu),
+                Err(sw) => sw.to_vec(),
+            },
+            CcidEvent::Reset => {
+                card.lock().unwrap().reset();
+                continue;
+            }
+        };
+        if let Err(e) = usb.ccid_respond(&response) {
+            log::error!("couldn't answer the host: {:?}", e);
+        }
+        if changed {
+            xous::send_message(
+                redraw_cid,
+                xous::Message::new_scalar(PgpCardOp::Redraw.to_usize().unwrap(), 0, 0, 0, 0),
+            )
+            .ok();
+        }
+    }
+}
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("OpenPGP card PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+
+    let mut allow_redraw = true;
+
+    // Register the server with xous
+    let sid = xns.register_name(SERVER_NAME_PGPCARD, None).expect("can't register server");
+
+    let mut ux = PgpCardUx::new(&xns, sid);
+
+    let trng = trng::Trng::new(&xns).expect("can't connect to TRNG");
+    let card = Arc::new(Mutex::new(Card::new(PddbStore::new(), trng)));
+
+    let usbd = UsbHid::new();
+    // the slot belongs to whoever touches it first, so claim it before anything else can
+    usbd.ccid_insert_card(&card::atr()).expect("couldn't insert the card into the CCID reader");
+    let redraw_cid = xous::connect(sid).unwrap();
+    std::thread::spawn({
+        let card = card.clone();
+        move || card_thread(card, redraw_cid)
+    });
+
+    loop {
+        let msg = xous::receive_message(sid).unwrap();
+        log::debug!("Got message: {:?}", msg);
+
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(PgpCardOp::Redraw) => {
+                if !allow_redraw {
+                    continue;
+                }
+
+                ux.redraw(&card.lock().unwrap());
+            }
+            Some(PgpCardOp::Quit) => {
+                log::info!("Quitting application");
+                break;
+            }
+            Some(PgpCardOp::FocusChange) => xous::msg_scalar_unpack!(msg, new_state_code, _, _, _, {
+                let new_state = gam::FocusState::convert_focus_change(new_state_code);
+                log::info!("focus change: {:?}", new_state);
+                match new_state {
+                    // the card stays in the reader, so that gpg keeps working while other apps are up
+                    gam::FocusState::Background => allow_redraw = false,
+                    gam::FocusState::Foreground => {
+                        allow_redraw = true;
+                        usbd.ensure_core(UsbDeviceType::Ccid).unwrap();
+                    }
+                }
+            }),
+            _ => {
+                log::error!("Got unknown message");
+            }
+        }
+    }
+
+    log::info!("Quitting");
+    usbd.ccid_remove_card().ok();
+    usbd.switch_to_core(UsbDeviceType::Debug).unwrap();
+    xous::terminate_process(0)
+}
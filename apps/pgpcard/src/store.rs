@@ -0,0 +1,66 @@
+use std::io::{Read, Write};
+
+/// PDDB dictionary holding the card's keys, PINs and data objects
+pub const PGPCARD_DICT: &str = "pgpcard";
+
+/// Persistent storage for the card. Everything is a small blob under a short name.
+pub trait Store {
+    fn get(&self, key: &str) -> Option<Vec<u8>>;
+    fn set(&mut self, key: &str, value: &[u8]);
+    fn remove(&mut self, key: &str);
+    /// Forgets everything, for a factory reset.
+    fn clear(&mut self);
+}
+
+pub struct PddbStore {
+    pddb: pddb::Pddb,
+}
+
+impl PddbStore {
+    pub fn new() -> Self {
+        let pddb = pddb::Pddb::new();
+        pddb.is_mounted_blocking();
+        PddbStore { pddb }
+    }
+}
+
+impl Store for PddbStore {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        match self.pddb.get(PGPCARD_DICT, key, None, true, false, None, None::<fn()>) {
+            Ok(mut entry) => {
+                let mut value = Vec::new();
+                match entry.read_to_end(&mut value) {
+                    Ok(_) => Some(value),
+                    Err(e) => {
+                        log::error!("couldn't read {}: {:?}", key, e);
+                        None
+                    }
+                }
+            }
+            Err(_) => None,
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &[u8]) {
+        self.pddb.delete_key(PGPCARD_DICT, key, None).ok();
+        match self.pddb.get(PGPCARD_DICT, key, None, true, true, Some(value.len()), None::<fn()>) {
+            Ok(mut entry) => {
+                if let Err(e) = entry.write_all(value) {
+                    log::error!("couldn't write {}: {:?}", key, e);
+                }
+            }
+            Err(e) => log::error!("couldn't create {}: {:?}", key, e),
+        }
+        self.pddb.sync().ok();
+    }
+
+    fn remove(&mut self, key: &str) {
+        self.pddb.delete_key(PGPCARD_DICT, key, None).ok();
+        self.pddb.sync().ok();
+    }
+
+    fn clear(&mut self) {
+        self.pddb.delete_dict(PGPCARD_DICT, None).ok();
+        self.pddb.sync().ok();
+    }
+}
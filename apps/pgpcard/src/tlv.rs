@@ -0,0 +1,112 @@
+//! The subset of BER-TLV used by the OpenPGP card: one- and two-byte tags, and lengths up to 0xFFFF.
+
+/// Splits one TLV off the front of `data`, returning its tag, value and whatever follows it.
+pub fn parse(data: &[u8]) -> Option<(u16, &[u8], &[u8])> {
+    let (tag, len, rest) = parse_header(data)?;
+    if rest.len() < len {
+        return None;
+    }
+    Some((tag, &rest[..len], &rest[len..]))
+}
+
+/// Splits a tag and length off the front of `data`, without looking for the value. Templates such as
+/// the 7F48 one in a key import list tags and lengths only.
+pub fn parse_header(data: &[u8]) -> Option<(u16, usize, &[u8])> {
+    let (&first, rest) = data.split_first()?;
+    let (tag, rest) = if first & 0x1F == 0x1F {
+        let (&second, rest) = rest.split_first()?;
+        (u16::from_be_bytes([first, second]), rest)
+    } else {
+        (first as u16, rest)
+    };
+    let (&len, rest) = rest.split_first()?;
+    match len {
+        0..=0x7F => Some((tag, len as usize, rest)),
+        0x81 => {
+            let (&len, rest) = rest.split_first()?;
+            Some((tag, len as usize, rest))
+        }
+        0x82 => {
+            if rest.len() < 2 {
+                return None;
+            }
+            Some((tag, u16::from_be_bytes([rest[0], rest[1]]) as usize, &rest[2..]))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the value of the first TLV tagged `tag` at the top level of `data`.
+pub fn find(mut data: &[u8], tag: u16) -> Option<&[u8]> {
+    while !data.is_empty() {
+        let (t, value, rest) = parse(data)?;
+        if t == tag {
+            return Some(value);
+        }
+        data = rest;
+    }
+    None
+}
+
+pub fn encode(tag: u16, value: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(value.len() + 5);
+    if tag > 0xFF {
+        out.extend_from_slice(&tag.to_be_bytes());
+    } else {
+        out.push(tag as u8);
+    }
+    match value.len() {
+        0..=0x7F => out.push(value.len() as u8),
+        0x80..=0xFF => out.extend_from_slice(&[0x81, value.len() as u8]),
+        _ => {
+            out.push(0x82);
+            out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        }
+    }
+    out.extend_from_slice(value);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for len in [0usize, 1, 0x7F, 0x80, 0xFF, 0x100, 0x1234] {
+            let value = vec![0xA5u8; len];
+            for tag in [0x4Fu16, 0x5F52, 0x7F49] {
+                let encoded = encode(tag, &value);
+                let (t, v, rest) = parse(&encoded).unwrap();
+                assert_eq!(t, tag);
+                assert_eq!(v, &value[..]);
+                assert!(rest.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_find() {
+        let mut data = encode(0xC1, &[1, 2]);
+        data.extend(encode(0x5F2D, b"en"));
+        data.extend(encode(0xC5, &[]));
+        assert_eq!(find(&data, 0x5F2D), Some(&b"en"[..]));
+        assert_eq!(find(&data, 0xC5), Some(&[][..]));
+        assert_eq!(find(&data, 0xC2), None);
+        // a truncated TLV ends the search rather than reading past the end
+        assert_eq!(find(&data[..data.len() - 1], 0xC5), None);
+        assert_eq!(parse(&[0x5F]), None);
+        assert_eq!(parse(&[0x4F, 0x83, 0, 0, 1]), None);
+    }
+
+    #[test]
+    fn test_header_only() {
+        // the private key template of an ECC key import: 92 20 99 20
+        let template = [0x92, 0x20, 0x99, 0x20];
+        let (tag, len, rest) = parse_header(&template).unwrap();
+        assert_eq!((tag, len), (0x92, 32));
+        let (tag, len, rest) = parse_header(rest).unwrap();
+        assert_eq!((tag, len), (0x99, 32));
+        assert!(rest.is_empty());
+    }
+}
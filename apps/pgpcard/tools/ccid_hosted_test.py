@@ -0,0 +1,193 @@
+#!/usr/bin/env python3
+"""A minimal CCID driver for the hosted USB backend, to exercise the OpenPGP card app end to end.
+
+  cargo xtask run pgpcard          # then open "OpenPGP card" from the app menu
+  ccid_hosted_test.py [--pinpad]
+
+Hosted mode has no USB bus, so usb-device-xous serves the reader's bulk messages on
+127.0.0.1:35964 (CCID_HOSTED_PORT) instead. This script speaks them directly: power on, select the
+application, generate a signing key, verify PW1 and sign. With --pinpad, PW1 is asked for on the
+device through a PC_to_RDR_Secure, the way a pinpad-aware host would.
+
+The signature is checked if the `cryptography` package is installed. Generating a key replaces the
+card's signing key, so don't point this at a card holding one you care about.
+"""
+
+import argparse
+import hashlib
+import socket
+import struct
+import sys
+
+PC_TO_RDR_ICC_POWER_ON = 0x62
+PC_TO_RDR_ICC_POWER_OFF = 0x63
+PC_TO_RDR_GET_SLOT_STATUS = 0x65
+PC_TO_RDR_SECURE = 0x69
+PC_TO_RDR_XFR_BLOCK = 0x6F
+HEADER_LEN = 10
+STATUS_TIME_EXTENSION = 0x80
+ICC_NOT_PRESENT = 2
+
+OPENPGP_AID = bytes.fromhex("D27600012401")
+
+
+class CcidError(Exception):
+    pass
+
+
+class Reader:
+    def __init__(self, host, port):
+        self.sock = socket.create_connection((host, port))
+        self.seq = 0
+
+    def recv_exact(self, n):
+        data = b""
+        while len(data) < n:
+            chunk = self.sock.recv(n - len(data))
+            if not chunk:
+                raise CcidError("reader hung up")
+            data += chunk
+        return data
+
+    def command(self, ty, data=b"", params=b"\x00\x00\x00"):
+        """Sends one PC_to_RDR message and returns (status, error, data) of the answer."""
+        self.seq = (self.seq + 1) & 0xFF
+        self.sock.sendall(struct.pack("<BIBB", ty, len(data), 0, self.seq) + params + data)
+        while True:
+            header = self.recv_exact(HEADER_LEN)
+            (length,) = struct.unpack("<I", header[1:5])
+            payload = self.recv_exact(length)
+            if header[6] != self.seq:
+                continue
+            status, error = header[7], header[8]
+            # the card is still working, e.g. waiting on a PIN typed on the device
+            if status & 0xC0 == STATUS_TIME_EXTENSION:
+                continue
+            return status, error, payload
+
+    def transmit(self, apdu, pinpad=None):
+        """Runs one APDU, following 61xx with GET RESPONSE. Returns (data, sw)."""
+        if pinpad is None:
+            _, error, response = self.command(PC_TO_RDR_XFR_BLOCK, apdu)
+        else:
+            _, error, response = self.command(PC_TO_RDR_SECURE, pinpad)
+        if len(response) < 2:
+            raise CcidError("no response APDU, error 0x{:02x}".format(error))
+        data, sw = response[:-2], response[-2:]
+        while sw[0] == 0x61:
+            _, _, response = self.command(PC_TO_RDR_XFR_BLOCK, bytes([0, 0xC0, 0, 0, sw[1]]))
+            data, sw = data + response[:-2], response[-2:]
+        return data, (sw[0] << 8) | sw[1]
+
+
+def apdu(ins, p1, p2, data=b"", le=None):
+    cmd = bytes([0, ins, p1, p2])
+    if data:
+        cmd += bytes([len(data)]) + data
+    if le is not None:
+        cmd += bytes([le])
+    return cmd
+
+
+def verify_pinpad(header):
+    """abData of a PC_to_RDR_Secure PIN verification: the 15-byte structure, then the APDU header."""
+    structure = bytes([
+        0x00,  # bPINOperation: verify
+        0x00,  # bTimeOut
+        0x82,  # bmFormatString: ASCII, bytes
+        0x00,  # bmPINBlockString
+        0x00,  # bmPINLengthFormat
+        0x7F, 0x06,  # wPINMaxExtraDigit: 6 to 127
+        0x02,  # bEntryValidationCondition: validation key
+        0x01,  # bNumberMessage
+        0x09, 0x04,  # wLangId
+        0x00,  # bMsgIndex
+        0x00, 0x00, 0x00,  # bTeoPrologue
+    ])
+    return structure + header
+
+
+def find_tlv(data, tag):
+    """Returns the value of `tag` at the top level of BER-TLV `data`, for one- and two-byte tags."""
+    while data:
+        if data[0] & 0x1F == 0x1F:
+            t, data = (data[0] << 8) | data[1], data[2:]
+        else:
+            t, data = data[0], data[1:]
+        length, data = data[0], data[1:]
+        if length == 0x81:
+            length, data = data[0], data[1:]
+        elif length == 0x82:
+            length, data = (data[0] << 8) | data[1], data[2:]
+        if t == tag:
+            return data[:length]
+        data = data[length:]
+    return None
+
+
+def check(what, sw, expected=0x9000):
+    print("{:<28} {:04X}".format(what, sw))
+    if sw != expected:
+        raise CcidError("{} failed".format(what))
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__, formatter_class=argparse.RawDescriptionHelpFormatter)
+    parser.add_argument("--host", default="127.0.0.1")
+    parser.add_argument("--port", type=int, default=35964)
+    parser.add_argument("--pin", default="123456", help="PW1, unless --pinpad is given")
+    parser.add_argument("--admin-pin", default="12345678", help="PW3")
+    parser.add_argument("--pinpad", action="store_true", help="enter PW1 on the device")
+    args = parser.parse_args()
+
+    reader = Reader(args.host, args.port)
+    try:
+        status, _, _ = reader.command(PC_TO_RDR_GET_SLOT_STATUS)
+        if status & 0x03 == ICC_NOT_PRESENT:
+            raise CcidError("no card in the slot; is the pgpcard app running?")
+        _, error, atr = reader.command(PC_TO_RDR_ICC_POWER_ON)
+        if not atr:
+            raise CcidError("power on failed, error 0x{:02x}".format(error))
+        print("ATR", atr.hex())
+
+        check("SELECT", reader.transmit(apdu(0xA4, 0x04, 0x00, OPENPGP_AID))[1])
+        aid, sw = reader.transmit(apdu(0xCA, 0x00, 0x4F, le=0))
+        check("GET DATA 4F", sw)
+        print("serial", aid[10:14].hex().upper())
+
+        check("VERIFY PW3", reader.transmit(apdu(0x20, 0x00, 0x83, args.admin_pin.encode()))[1])
+        key, sw = reader.transmit(apdu(0x47, 0x80, 0x00, bytes([0xB6, 0x00]), le=0))
+        check("GENERATE (signing)", sw)
+        public = find_tlv(find_tlv(key, 0x7F49) or b"", 0x86)
+        if public is None or len(public) != 32:
+            raise CcidError("unexpected public key: " + key.hex())
+        print("public key", public.hex())
+
+        if args.pinpad:
+            print("enter PW1 on the device...")
+            sw = reader.transmit(None, pinpad=verify_pinpad(bytes([0x00, 0x20, 0x00, 0x81])))[1]
+        else:
+            sw = reader.transmit(apdu(0x20, 0x00, 0x81, args.pin.encode()))[1]
+        check("VERIFY PW1 (signing)", sw)
+
+        digest = hashlib.sha256(b"hosted CCID test").digest()
+        signature, sw = reader.transmit(apdu(0x2A, 0x9E, 0x9A, digest, le=0))
+        check("PSO:COMPUTE DIGITAL SIG", sw)
+        print("signature", signature.hex())
+        try:
+            from cryptography.hazmat.primitives.asymmetric.ed25519 import Ed25519PublicKey
+        except ImportError:
+            print("(install `cryptography` to check the signature)")
+        else:
+            Ed25519PublicKey.from_public_bytes(public).verify(signature, digest)
+            print("signature verified")
+
+        reader.command(PC_TO_RDR_ICC_POWER_OFF)
+    except CcidError as e:
+        print("FAILED:", e)
+        return 1
+    return 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())
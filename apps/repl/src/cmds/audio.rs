@@ -62,7 +62,7 @@ impl<'a> ShellCmdApi<'a> for Audio {
 
     fn process(&mut self, args: String, env: &mut CommonEnv) -> Result<Option<String>, xous::Error> {
         let mut ret = String::new();
-        let helpstring = t!("replapp.audio.help", locales::LANG);
+        let helpstring = t!("replapp.audio.help", locales::lang());
         let mut tokens = args.split(' ');
 
         if let Some(sub_cmd) = tokens.next() {
@@ -114,7 +114,7 @@ impl<'a> ShellCmdApi<'a> for Audio {
                                 .unwrap();
                         }
                     });
-                    write!(ret, "{}", t!("replapp.audio.start", locales::LANG)).unwrap();
+                    write!(ret, "{}", t!("replapp.audio.start", locales::lang())).unwrap();
                 }
                 _ => {
                     write!(ret, "{}", helpstring).unwrap();
@@ -173,9 +173,9 @@ impl<'a> ShellCmdApi<'a> for Audio {
                     write!(
                         ret,
                         "{} {} {}.",
-                        t!("replapp.audio.completion_a", locales::LANG),
+                        t!("replapp.audio.completion_a", locales::lang()),
                         self.framecount,
-                        t!("replapp.audio.completion_b", locales::LANG),
+                        t!("replapp.audio.completion_b", locales::lang()),
                     )
                     .unwrap();
                     self.framecount = 0;
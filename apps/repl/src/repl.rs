@@ -69,7 +69,7 @@ impl Repl {
             input: None,
             msg: None,
             history: vec![History {
-                text: String::from(t!("replapp.greeting", locales::LANG)),
+                text: String::from(t!("replapp.greeting", locales::lang())),
                 is_input: false,
             }],
             history_len: 10,
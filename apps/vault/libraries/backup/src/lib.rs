@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-use cbor::{self, cbor_array_vec, cbor_int, cbor_map, cbor_unsigned, destructure_cbor_map};
+use cbor::{self, cbor_array_vec, cbor_int, cbor_map, cbor_map_options, cbor_unsigned, destructure_cbor_map};
 use serde::{Deserialize, Serialize};
 
 pub const CONTINUE_RESPONSE: &[u8] = &[42, 43, 44, 45];
@@ -98,16 +98,27 @@ pub struct TotpEntry {
     pub name: String,
     #[serde(default)] // if hotp is missing from the JSON representation, it's assumed to be false.
     pub hotp: bool,
+    /// How codes are derived from the secret: `STEAM`, `YANDEX` or `MOTP`. Empty for standard RFC 6238
+    /// and RFC 4226 codes, which is all that backups made before these schemes existed contain.
+    #[serde(default)]
+    pub scheme: String,
+    /// The PIN of the schemes that mix one into the code
+    #[serde(default)]
+    pub pin: String,
 }
 
 impl From<TotpEntry> for cbor::Value {
     fn from(te: TotpEntry) -> Self {
-        cbor_map! {
+        // RFC entries are written exactly as before the scheme was added, so older tools can still read them
+        let non_empty = |s: String| if s.is_empty() { None } else { Some(s) };
+        cbor_map_options! {
             cbor_int!(1) => te.step_seconds as i64,
             cbor_int!(2) => te.shared_secret,
             cbor_int!(3) => te.digit_count as i64,
             cbor_int!(4) => te.algorithm,
             cbor_int!(5) => te.name,
+            cbor_int!(7) => non_empty(te.scheme),
+            cbor_int!(8) => non_empty(te.pin),
         }
     }
 }
@@ -129,6 +140,8 @@ impl TryFrom<cbor::Value> for TotpEntry {
                 4 => algorithm,
                 5 => name,
                 6 => hotp,
+                7 => scheme,
+                8 => pin,
             } = rawmap;
         }
 
@@ -140,7 +153,10 @@ impl TryFrom<cbor::Value> for TotpEntry {
         let hotp =
             extract_bool(hotp.unwrap_or(cbor::Value::Simple(cbor::SimpleValue::FalseValue))).unwrap_or(false);
 
-        Ok(TotpEntry { step_seconds, shared_secret, digit_count, algorithm, name, hotp })
+        let scheme = scheme.map(extract_string).transpose()?.unwrap_or_default();
+        let pin = pin.map(extract_string).transpose()?.unwrap_or_default();
+
+        Ok(TotpEntry { step_seconds, shared_secret, digit_count, algorithm, name, hotp, scheme, pin })
     }
 }
 
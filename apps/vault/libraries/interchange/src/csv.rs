@@ -0,0 +1,162 @@
+//! CSV exports from other password managers.
+//!
+//! There is no standard layout, but every exporter writes a header row, and the column names only vary
+//! within a small set of synonyms. Columns are matched by name, case-insensitively; anything that isn't
+//! recognized is ignored. A TOTP column, where present, may hold an `otpauth://` URI or a bare base32
+//! secret, and yields an OTP entry next to the login.
+
+use crate::{Collection, Error, OtpEntry, PasswordEntry};
+
+const TITLE: &[&str] = &["title", "name", "account", "description", "item name", "account name"];
+const USERNAME: &[&str] =
+    &["username", "login_username", "user name", "login", "user", "email", "login name"];
+const PASSWORD: &[&str] = &["password", "login_password", "pass"];
+const URL: &[&str] = &["url", "login_uri", "website", "web site", "uri", "hostname", "origin"];
+const NOTES: &[&str] = &["notes", "note", "extra", "comments", "comment"];
+const OTP: &[&str] = &["totp", "login_totp", "otpauth", "otp", "one-time password", "2fa"];
+
+/// Splits RFC4180 CSV into rows of fields. Quoted fields may contain commas, doubled quotes and line
+/// breaks; both `\n` and `\r\n` end a row.
+pub fn split_rows(text: &str) -> Result<Vec<Vec<String>>, Error> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                c => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                if !(row.len() == 1 && row[0].is_empty()) {
+                    rows.push(std::mem::take(&mut row));
+                }
+                row.clear();
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(Error::Malformed("unterminated quote in CSV"));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn column(header: &[String], names: &[&str]) -> Option<usize> {
+    // earlier synonyms are preferred, so "name" doesn't shadow an explicit "title"
+    names.iter().find_map(|name| header.iter().position(|h| h.trim().eq_ignore_ascii_case(name)))
+}
+
+/// Reads a password manager CSV export.
+pub fn parse(text: &str) -> Result<Collection, Error> {
+    let mut rows = split_rows(text)?.into_iter();
+    let header = rows.next().ok_or(Error::Malformed("empty CSV"))?;
+    let password = column(&header, PASSWORD).ok_or(Error::Malformed("CSV has no password column"))?;
+    let title = column(&header, TITLE);
+    let username = column(&header, USERNAME);
+    let url = column(&header, URL);
+    let notes = column(&header, NOTES);
+    let otp = column(&header, OTP);
+
+    let mut collection = Collection::default();
+    for row in rows {
+        let get = |index: Option<usize>| {
+            index.and_then(|i| row.get(i)).map(|s| s.trim().to_string()).unwrap_or_default()
+        };
+        let mut entry = PasswordEntry {
+            title: get(title),
+            username: get(username),
+            password: row.get(password).cloned().unwrap_or_default(),
+            url: get(url),
+            notes: get(notes),
+        };
+        if entry.title.is_empty() {
+            entry.title = host_of(&entry.url).to_string();
+        }
+
+        let otp = get(otp);
+        if !otp.is_empty() {
+            let code = if otp.starts_with("otpauth://") {
+                crate::otpauth::parse(&otp)
+            } else {
+                let mut code = OtpEntry::default();
+                code.set_secret(&otp).map(|_| code)
+            };
+            match code {
+                Ok(mut code) => {
+                    if code.issuer.is_empty() && code.account.is_empty() {
+                        code.issuer = entry.title.clone();
+                        code.account = entry.username.clone();
+                    }
+                    collection.otp.push(code);
+                }
+                Err(_) => collection.skipped.push(entry.title.clone()),
+            }
+        }
+        // some exports list TOTP-only or note-only items alongside logins
+        if !entry.password.is_empty() || !entry.username.is_empty() {
+            collection.passwords.push(entry);
+        }
+    }
+    Ok(collection)
+}
+
+/// The host part of a URL, for naming entries that only have a URL.
+fn host_of(url: &str) -> &str {
+    let rest = url.split_once("://").map(|(_, r)| r).unwrap_or(url);
+    let host = rest.split(&['/', '?', '#'][..]).next().unwrap_or(rest);
+    host.rsplit_once('@').map(|(_, h)| h).unwrap_or(host)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn quoting() {
+        let rows = split_rows("a,\"b,\"\"c\"\"\",\"multi\nline\"\r\n\r\nd,,e").unwrap();
+        assert_eq!(rows, vec![vec!["a", "b,\"c\"", "multi\nline"], vec!["d", "", "e"]]);
+        assert!(split_rows("\"open").is_err());
+    }
+
+    #[test]
+    fn bitwarden_export() {
+        let text = "folder,favorite,type,name,notes,fields,reprompt,login_uri,login_username,login_password,login_totp\n\
+            ,,login,Example,,,0,https://example.com,alice,hunter2,JBSW Y3DP\n\
+            ,,login,,,,0,https://user@other.org/path,,secret,\n\
+            ,,note,Just a note,text,,0,,,,\n\
+            ,,login,Bad OTP,,,0,,bob,pw,!!!\n";
+        let collection = parse(text).unwrap();
+        assert_eq!(collection.passwords.len(), 3);
+        assert_eq!(collection.passwords[0].url, "https://example.com");
+        assert_eq!(collection.passwords[1].title, "other.org");
+        assert_eq!(collection.otp.len(), 1);
+        assert_eq!(
+            (collection.otp[0].issuer.as_str(), collection.otp[0].secret.as_str()),
+            ("Example", "JBSWY3DP")
+        );
+        assert_eq!(collection.skipped, vec!["Bad OTP"]);
+    }
+
+    #[test]
+    fn needs_password_column() {
+        assert!(parse("name,url\nx,y\n").is_err());
+    }
+}
@@ -0,0 +1,313 @@
+//! The XML document inside a KDBX payload.
+//!
+//! Values marked `Protected="True"` are base64 of the plaintext XORed with the inner stream cipher. The
+//! keystream runs through the whole document in order, so every protected value has to be decrypted as
+//! it is met, including ones in history entries and the recycle bin that are thrown away afterwards.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chacha20::ChaCha20;
+use chacha20::cipher::StreamCipher;
+use quick_xml::events::{BytesStart, Event};
+use rand_core::RngCore;
+
+use crate::{Collection, Error, OtpAlgorithm, OtpEntry, PasswordEntry};
+
+/// One entry as found in the document: its string fields, and the UUIDs of the groups it sits in.
+struct RawEntry {
+    fields: Vec<(String, String)>,
+    groups: Vec<String>,
+}
+
+impl RawEntry {
+    fn get(&self, key: &str) -> &str {
+        self.fields.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str()).unwrap_or("")
+    }
+}
+
+pub(super) fn parse(xml: &str, stream: &mut ChaCha20) -> Result<Collection, Error> {
+    let mut reader = quick_xml::Reader::from_str(xml);
+    let mut path: Vec<String> = Vec::new();
+    let mut groups: Vec<String> = Vec::new();
+    let mut recycle_bin = String::new();
+    let mut recycle_bin_enabled = true;
+    let mut entries = Vec::new();
+    let mut current: Option<RawEntry> = None;
+    let mut protected = false;
+    let mut text = String::new();
+    let mut key = String::new();
+    let mut value = String::new();
+
+    loop {
+        let event = reader.read_event().map_err(|_| Error::Malformed("database XML"))?;
+        let (start, end) = match &event {
+            Event::Start(e) => (Some(e), false),
+            Event::Empty(e) => (Some(e), true),
+            Event::End(_) => (None, true),
+            Event::Text(t) => {
+                text.push_str(&t.unescape().map_err(|_| Error::Malformed("database XML"))?);
+                continue;
+            }
+            Event::CData(t) => {
+                text.push_str(&String::from_utf8_lossy(t));
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+        if let Some(e) = start {
+            let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+            match name.as_str() {
+                "Group" => groups.push(String::new()),
+                // history entries nest inside an entry; only the top-level ones are kept
+                "Entry" if path.last().map(String::as_str) == Some("Group") => {
+                    current = Some(RawEntry { fields: Vec::new(), groups: groups.clone() })
+                }
+                "Value" => protected = is_protected(e),
+                _ => {}
+            }
+            path.push(name);
+            text.clear();
+        }
+        if !end {
+            continue;
+        }
+
+        let name = path.pop().ok_or(Error::Malformed("database XML"))?;
+        let parent = path.last().map(String::as_str).unwrap_or("");
+        match (parent, name.as_str()) {
+            ("String", "Key") => key = std::mem::take(&mut text),
+            ("String", "Value") => {
+                value = if protected { unprotect(&text, stream)? } else { std::mem::take(&mut text) };
+            }
+            ("Entry", "String") => {
+                let top_level = path.len() >= 2 && path[path.len() - 2] == "Group";
+                if let (true, Some(entry)) = (top_level, current.as_mut()) {
+                    entry.fields.push((std::mem::take(&mut key), std::mem::take(&mut value)));
+                }
+            }
+            ("Group", "Entry") => entries.extend(current.take()),
+            ("Group", "UUID") => {
+                if let Some(uuid) = groups.last_mut() {
+                    *uuid = text.trim().to_string();
+                }
+            }
+            (_, "Group") => {
+                groups.pop();
+            }
+            ("Meta", "RecycleBinUUID") => recycle_bin = text.trim().to_string(),
+            ("Meta", "RecycleBinEnabled") => recycle_bin_enabled = text.trim().eq_ignore_ascii_case("true"),
+            _ => {}
+        }
+        text.clear();
+    }
+
+    let mut collection = Collection::default();
+    for entry in entries {
+        if recycle_bin_enabled && !recycle_bin.is_empty() && entry.groups.contains(&recycle_bin) {
+            continue;
+        }
+        let password = PasswordEntry {
+            title: entry.get("Title").to_string(),
+            username: entry.get("UserName").to_string(),
+            password: entry.get("Password").to_string(),
+            url: entry.get("URL").to_string(),
+            notes: entry.get("Notes").to_string(),
+        };
+        let has_otp = match otp_of(&entry) {
+            Ok(Some(mut otp)) => {
+                if otp.issuer.is_empty() && otp.account.is_empty() {
+                    otp.issuer = password.title.clone();
+                    otp.account = password.username.clone();
+                }
+                collection.otp.push(otp);
+                true
+            }
+            Ok(None) => false,
+            Err(_) => {
+                collection.skipped.push(password.title.clone());
+                true
+            }
+        };
+        // an entry holding only a username next to its OTP secret is an OTP entry, not a login
+        if !password.password.is_empty() || (!password.username.is_empty() && !has_otp) {
+            collection.passwords.push(password);
+        }
+    }
+    Ok(collection)
+}
+
+fn is_protected(e: &BytesStart) -> bool {
+    e.attributes().flatten().any(|a| a.key.as_ref() == b"Protected" && a.value.eq_ignore_ascii_case(b"true"))
+}
+
+fn unprotect(text: &str, stream: &mut ChaCha20) -> Result<String, Error> {
+    let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut bytes = BASE64.decode(text).map_err(|_| Error::Malformed("protected value is not base64"))?;
+    stream.apply_keystream(&mut bytes);
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn protect(text: &str, stream: &mut ChaCha20) -> String {
+    let mut bytes = text.as_bytes().to_vec();
+    stream.apply_keystream(&mut bytes);
+    BASE64.encode(bytes)
+}
+
+/// Finds an OTP credential in any of the layouts KeePass clients and plugins use: KeePassXC's `otp`
+/// field (an otpauth URI, or the older `key=...&step=...` form), KeePass 2.47+ `TimeOtp-*`/`HmacOtp-*`
+/// fields, and the KeeTrayTOTP `TOTP Seed`/`TOTP Settings` pair.
+fn otp_of(entry: &RawEntry) -> Result<Option<OtpEntry>, Error> {
+    let otp = entry.get("otp").trim();
+    if otp.starts_with("otpauth://") {
+        return crate::otpauth::parse(otp).map(Some);
+    }
+    let mut code = OtpEntry::default();
+    if !otp.is_empty() {
+        for pair in otp.split('&') {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            let v = crate::percent_decode(v);
+            match k {
+                "key" => code.set_secret(&v)?,
+                "step" => code.period = v.parse().map_err(|_| Error::Malformed("OTP period"))?,
+                "size" => code.digits = v.parse().map_err(|_| Error::Malformed("OTP digits"))?,
+                "otpHashMode" => {
+                    code.algorithm = OtpAlgorithm::parse(&v).ok_or(Error::Unsupported("OTP algorithm"))?
+                }
+                _ => {}
+            }
+        }
+        return if code.secret.is_empty() {
+            Err(Error::Malformed("OTP field has no key"))
+        } else {
+            Ok(Some(code))
+        };
+    }
+    if let Some(secret) = secret_of(entry, "TimeOtp-Secret")? {
+        code.secret = secret;
+        let number = |key: &str, default: u64| match entry.get(key).trim() {
+            "" => Ok(default),
+            v => v.parse().map_err(|_| Error::Malformed("OTP parameter")),
+        };
+        code.digits = number("TimeOtp-Length", 6)? as u32;
+        code.period = number("TimeOtp-Period", 30)?;
+        if !entry.get("TimeOtp-Algorithm").is_empty() {
+            code.algorithm = OtpAlgorithm::parse(entry.get("TimeOtp-Algorithm"))
+                .ok_or(Error::Unsupported("OTP algorithm"))?;
+        }
+        return Ok(Some(code));
+    }
+    if let Some(secret) = secret_of(entry, "HmacOtp-Secret")? {
+        code.secret = secret;
+        code.hotp = true;
+        code.counter = match entry.get("HmacOtp-Counter").trim() {
+            "" => 0,
+            v => v.parse().map_err(|_| Error::Malformed("HOTP counter"))?,
+        };
+        return Ok(Some(code));
+    }
+    if !entry.get("TOTP Seed").is_empty() {
+        code.set_secret(entry.get("TOTP Seed"))?;
+        // "period;digits", where digits may be "S" for Steam codes
+        if let Some((period, digits)) = entry.get("TOTP Settings").split_once(';') {
+            code.period = period.trim().parse().map_err(|_| Error::Malformed("OTP period"))?;
+            code.digits = digits.trim().parse().map_err(|_| Error::Unsupported("OTP digits"))?;
+        }
+        return Ok(Some(code));
+    }
+    Ok(None)
+}
+
+/// KeePass stores the `*Otp-Secret` in one of four encodings, told apart by the field name suffix.
+fn secret_of(entry: &RawEntry, prefix: &str) -> Result<Option<String>, Error> {
+    let encode = |bytes: &[u8]| base32::encode(base32::Alphabet::RFC4648 { padding: false }, bytes);
+    let base32 = entry.get(&format!("{}-Base32", prefix));
+    if !base32.is_empty() {
+        let mut code = OtpEntry::default();
+        code.set_secret(base32)?;
+        return Ok(Some(code.secret));
+    }
+    let hex = entry.get(&format!("{}-Hex", prefix)).trim();
+    if !hex.is_empty() {
+        let bytes: Option<Vec<u8>> = (0..hex.len())
+            .step_by(2)
+            .map(|i| hex.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+            .collect();
+        return bytes.map(|b| Some(encode(&b))).ok_or(Error::Malformed("OTP secret is not hex"));
+    }
+    let base64 = entry.get(&format!("{}-Base64", prefix)).trim();
+    if !base64.is_empty() {
+        let bytes = BASE64.decode(base64).map_err(|_| Error::Malformed("OTP secret is not base64"))?;
+        return Ok(Some(encode(&bytes)));
+    }
+    let raw = entry.get(prefix);
+    Ok(if raw.is_empty() { None } else { Some(encode(raw.as_bytes())) })
+}
+
+pub(super) fn write<R: RngCore>(collection: &Collection, stream: &mut ChaCha20, rng: &mut R) -> String {
+    let mut uuid = || {
+        let mut bytes = [0u8; 16];
+        rng.fill_bytes(&mut bytes);
+        BASE64.encode(bytes)
+    };
+    let mut xml =
+        String::from("<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n<KeePassFile>\n");
+    xml.push_str("\t<Meta>\n\t\t<Generator>Xous vault</Generator>\n\t\t<DatabaseName>Vault</DatabaseName>\n");
+    xml.push_str("\t\t<MemoryProtection>\n\t\t\t<ProtectPassword>True</ProtectPassword>\n");
+    xml.push_str("\t\t</MemoryProtection>\n\t\t<RecycleBinEnabled>False</RecycleBinEnabled>\n\t</Meta>\n");
+    xml.push_str(&format!(
+        "\t<Root>\n\t\t<Group>\n\t\t\t<UUID>{}</UUID>\n\t\t\t<Name>Vault</Name>\n",
+        uuid()
+    ));
+    for entry in &collection.passwords {
+        xml.push_str(&format!("\t\t\t<Entry>\n\t\t\t\t<UUID>{}</UUID>\n", uuid()));
+        string_field(&mut xml, 4, "Title", &entry.title, None);
+        string_field(&mut xml, 4, "UserName", &entry.username, None);
+        string_field(&mut xml, 4, "Password", &entry.password, Some(&mut *stream));
+        string_field(&mut xml, 4, "URL", &entry.url, None);
+        string_field(&mut xml, 4, "Notes", &entry.notes, None);
+        xml.push_str("\t\t\t</Entry>\n");
+    }
+    xml.push_str(&format!("\t\t\t<Group>\n\t\t\t\t<UUID>{}</UUID>\n\t\t\t\t<Name>TOTP</Name>\n", uuid()));
+    for entry in &collection.otp {
+        xml.push_str(&format!("\t\t\t\t<Entry>\n\t\t\t\t\t<UUID>{}</UUID>\n", uuid()));
+        string_field(&mut xml, 5, "Title", &entry.name(), None);
+        string_field(&mut xml, 5, "UserName", &entry.account, None);
+        string_field(&mut xml, 5, "otp", &crate::otpauth::to_uri(entry), Some(&mut *stream));
+        xml.push_str("\t\t\t\t</Entry>\n");
+    }
+    xml.push_str("\t\t\t</Group>\n\t\t</Group>\n\t</Root>\n</KeePassFile>\n");
+    xml
+}
+
+fn string_field(xml: &mut String, depth: usize, key: &str, value: &str, protect_with: Option<&mut ChaCha20>) {
+    let indent = "\t".repeat(depth);
+    xml.push_str(&format!("{0}<String>\n{0}\t<Key>{1}</Key>\n", indent, escape(key)));
+    match protect_with {
+        Some(stream) => xml.push_str(&format!(
+            "{}\t<Value Protected=\"True\">{}</Value>\n",
+            indent,
+            protect(value, stream)
+        )),
+        None => xml.push_str(&format!("{}\t<Value>{}</Value>\n", indent, escape(value))),
+    }
+    xml.push_str(&format!("{}</String>\n", indent));
+}
+
+/// Escapes markup characters and drops the control characters XML 1.0 can't represent at all.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if (c as u32) < 0x20 => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,252 @@
+//! Conversion between the vault's records and the formats other password managers use.
+//!
+//! Everything here works on byte buffers and plain structs, so it can be exercised on the host; moving
+//! the buffers on and off the device and turning the structs into PDDB records is up to the vault.
+//!
+//! Supported formats:
+//!   - KeePass KDBX 4.x, read and write (AES-256 or ChaCha20, Argon2d/Argon2id or AES-KDF)
+//!   - CSV exports from common password managers (Bitwarden, 1Password, LastPass, KeePassXC, browsers)
+//!   - `otpauth://` URIs, one per line, and Google Authenticator `otpauth-migration://` payloads
+
+use std::fmt::Display;
+
+pub mod csv;
+pub mod kdbx;
+pub mod migration;
+pub mod otpauth;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The data isn't in the expected format, or is truncated.
+    Malformed(&'static str),
+    /// The data is well-formed but uses a feature this crate doesn't implement.
+    Unsupported(&'static str),
+    /// The password (or key file) doesn't open the database.
+    BadCredentials,
+    /// The database integrity checks failed.
+    Corrupt,
+    /// Deriving the key would need more memory than the caller allows, in bytes.
+    KdfTooExpensive { required: u64, limit: u64 },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Malformed(what) => write!(f, "malformed input: {}", what),
+            Error::Unsupported(what) => write!(f, "unsupported: {}", what),
+            Error::BadCredentials => write!(f, "wrong password"),
+            Error::Corrupt => write!(f, "database is corrupt"),
+            Error::KdfTooExpensive { required, limit } => write!(
+                f,
+                "key derivation needs {} KiB of memory, only {} KiB available",
+                required / 1024,
+                limit / 1024
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OtpAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl OtpAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OtpAlgorithm::Sha1 => "SHA1",
+            OtpAlgorithm::Sha256 => "SHA256",
+            OtpAlgorithm::Sha512 => "SHA512",
+        }
+    }
+
+    /// Accepts the spellings used by otpauth URIs (`SHA256`) and KeePass (`HMAC-SHA-256`).
+    pub fn parse(s: &str) -> Option<Self> {
+        let s: String =
+            s.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_ascii_uppercase();
+        match s.trim_start_matches("HMAC") {
+            "SHA1" => Some(OtpAlgorithm::Sha1),
+            "SHA256" => Some(OtpAlgorithm::Sha256),
+            "SHA512" => Some(OtpAlgorithm::Sha512),
+            _ => None,
+        }
+    }
+}
+
+/// A TOTP or HOTP credential.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OtpEntry {
+    pub issuer: String,
+    pub account: String,
+    /// RFC4648 base32, upper case, no padding
+    pub secret: String,
+    pub algorithm: OtpAlgorithm,
+    pub digits: u32,
+    /// Time step in seconds; unused for HOTP
+    pub period: u64,
+    pub hotp: bool,
+    /// Next HOTP counter value; unused for TOTP
+    pub counter: u64,
+}
+
+impl Default for OtpEntry {
+    fn default() -> Self {
+        OtpEntry {
+            issuer: String::new(),
+            account: String::new(),
+            secret: String::new(),
+            algorithm: OtpAlgorithm::Sha1,
+            digits: 6,
+            period: 30,
+            hotp: false,
+            counter: 0,
+        }
+    }
+}
+
+impl OtpEntry {
+    /// The name shown in the vault, `issuer:account` like the authenticator apps display it.
+    pub fn name(&self) -> String {
+        match (self.issuer.is_empty(), self.account.is_empty()) {
+            (true, _) => self.account.clone(),
+            (false, true) => self.issuer.clone(),
+            (false, false) if self.account.starts_with(&format!("{}:", self.issuer)) => self.account.clone(),
+            (false, false) => format!("{}:{}", self.issuer, self.account),
+        }
+    }
+
+    /// Splits a vault name back into issuer and account.
+    pub fn set_name(&mut self, name: &str) {
+        match name.split_once(':') {
+            Some((issuer, account)) => {
+                self.issuer = issuer.trim().to_string();
+                self.account = account.trim().to_string();
+            }
+            None => {
+                self.issuer.clear();
+                self.account = name.trim().to_string();
+            }
+        }
+    }
+
+    /// Normalizes a base32 secret as typed by people or other apps (spaces, lower case, padding) and
+    /// checks that it decodes.
+    pub fn set_secret(&mut self, secret: &str) -> Result<(), Error> {
+        let secret: String = secret
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '-' && *c != '=')
+            .collect::<String>()
+            .to_ascii_uppercase();
+        match base32::decode(base32::Alphabet::RFC4648 { padding: false }, &secret) {
+            Some(bytes) if !bytes.is_empty() => {
+                self.secret = secret;
+                Ok(())
+            }
+            _ => Err(Error::Malformed("OTP secret is not base32")),
+        }
+    }
+}
+
+/// A website or application login.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PasswordEntry {
+    pub title: String,
+    pub username: String,
+    pub password: String,
+    pub url: String,
+    pub notes: String,
+}
+
+/// Everything recovered from, or to be written to, one file.
+#[derive(Debug, Default)]
+pub struct Collection {
+    pub passwords: Vec<PasswordEntry>,
+    pub otp: Vec<OtpEntry>,
+    /// Items that were read but couldn't be converted, by title, so one odd entry doesn't sink an import
+    /// of hundreds.
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Kdbx,
+    OtpAuth,
+    Csv,
+}
+
+/// Guesses the format of an uploaded file from its contents.
+pub fn detect(data: &[u8]) -> Option<Format> {
+    if data.starts_with(&kdbx::SIGNATURE) {
+        return Some(Format::Kdbx);
+    }
+    let text = std::str::from_utf8(data).ok()?;
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    if text.starts_with("otpauth://") || text.starts_with("otpauth-migration://") {
+        Some(Format::OtpAuth)
+    } else if text.lines().next().map(|l| l.contains(',')).unwrap_or(false) {
+        Some(Format::Csv)
+    } else {
+        None
+    }
+}
+
+/// Percent-decodes a URI component. `+` is left alone: labels are often e-mail addresses, where it is
+/// significant.
+pub(crate) fn percent_decode(s: &str) -> String {
+    let hex = |b: u8| (b as char).to_digit(16);
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1).and_then(|&b| hex(b)), bytes.get(i + 2).and_then(|&b| hex(b))) {
+            (b'%', Some(hi), Some(lo)) => {
+                out.push((hi * 16 + lo) as u8);
+                i += 3;
+                continue;
+            }
+            (b, ..) => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Percent-encodes everything outside the RFC3986 unreserved set.
+pub(crate) fn percent_encode(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detection() {
+        assert_eq!(detect(&kdbx::SIGNATURE), Some(Format::Kdbx));
+        assert_eq!(detect(b"\xef\xbb\xbf otpauth://totp/x?secret=A"), Some(Format::OtpAuth));
+        assert_eq!(detect(b"name,password\n"), Some(Format::Csv));
+        assert_eq!(detect(b"\x00\x01binary"), None);
+    }
+
+    #[test]
+    fn names() {
+        let mut entry = OtpEntry::default();
+        entry.set_name("GitHub: octocat");
+        assert_eq!((entry.issuer.as_str(), entry.account.as_str()), ("GitHub", "octocat"));
+        assert_eq!(entry.name(), "GitHub:octocat");
+        assert_eq!(OtpAlgorithm::parse("HMAC-SHA-256"), Some(OtpAlgorithm::Sha256));
+    }
+}
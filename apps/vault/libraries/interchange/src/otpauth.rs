@@ -0,0 +1,146 @@
+//! `otpauth://` key URIs, as described by the Google Authenticator wiki and used by nearly every
+//! authenticator app and password manager:
+//!
+//! `otpauth://totp/Issuer:account?secret=BASE32&issuer=Issuer&algorithm=SHA1&digits=6&period=30`
+
+use crate::{Error, OtpAlgorithm, OtpEntry, percent_decode, percent_encode};
+
+const SCHEME: &str = "otpauth://";
+
+/// Parses a single `otpauth://` URI.
+pub fn parse(uri: &str) -> Result<OtpEntry, Error> {
+    let rest = uri.trim().strip_prefix(SCHEME).ok_or(Error::Malformed("not an otpauth URI"))?;
+    let (kind, rest) = rest.split_once('/').ok_or(Error::Malformed("otpauth URI has no label"))?;
+    let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+    let mut entry = OtpEntry::default();
+    match kind.to_ascii_lowercase().as_str() {
+        "totp" => entry.hotp = false,
+        "hotp" => entry.hotp = true,
+        _ => return Err(Error::Unsupported("otpauth type other than totp or hotp")),
+    }
+    entry.set_name(&percent_decode(label));
+
+    let mut secret = None;
+    let mut counter = None;
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = percent_decode(value);
+        match key.to_ascii_lowercase().as_str() {
+            "secret" => secret = Some(value),
+            // the issuer parameter is authoritative; the label prefix is only a fallback
+            "issuer" if !value.is_empty() => {
+                if entry.issuer.is_empty() && entry.account.starts_with(&format!("{}:", value)) {
+                    entry.account = entry.account[value.len() + 1..].trim().to_string();
+                }
+                entry.issuer = value;
+            }
+            "algorithm" => {
+                entry.algorithm = OtpAlgorithm::parse(&value).ok_or(Error::Unsupported("OTP algorithm"))?
+            }
+            "digits" => {
+                entry.digits = value.parse().map_err(|_| Error::Malformed("OTP digits is not a number"))?
+            }
+            "period" => {
+                entry.period = value.parse().map_err(|_| Error::Malformed("OTP period is not a number"))?
+            }
+            "counter" => {
+                counter = Some(value.parse().map_err(|_| Error::Malformed("HOTP counter is not a number"))?)
+            }
+            // image, color and friends are cosmetic
+            _ => {}
+        }
+    }
+
+    entry.set_secret(&secret.ok_or(Error::Malformed("otpauth URI has no secret"))?)?;
+    if entry.hotp {
+        entry.counter = counter.ok_or(Error::Malformed("HOTP URI has no counter"))?;
+    }
+    if !(6..=10).contains(&entry.digits) || entry.period == 0 {
+        return Err(Error::Unsupported("OTP digits or period out of range"));
+    }
+    Ok(entry)
+}
+
+/// Parses a text file holding one `otpauth://` or `otpauth-migration://` URI per line. Blank lines and
+/// lines starting with `#` are skipped.
+pub fn parse_all(text: &str) -> Result<Vec<OtpEntry>, Error> {
+    let mut entries = Vec::new();
+    for line in text.trim_start_matches('\u{feff}').lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with(crate::migration::SCHEME) {
+            entries.extend(crate::migration::parse(line)?);
+        } else {
+            entries.push(parse(line)?);
+        }
+    }
+    Ok(entries)
+}
+
+/// Formats `entry` as an `otpauth://` URI.
+pub fn to_uri(entry: &OtpEntry) -> String {
+    let mut uri = String::from(SCHEME);
+    uri.push_str(if entry.hotp { "hotp/" } else { "totp/" });
+    if !entry.issuer.is_empty() {
+        uri.push_str(&percent_encode(&entry.issuer));
+        uri.push(':');
+    }
+    uri.push_str(&percent_encode(&entry.account));
+    uri.push_str("?secret=");
+    uri.push_str(&entry.secret);
+    if !entry.issuer.is_empty() {
+        uri.push_str("&issuer=");
+        uri.push_str(&percent_encode(&entry.issuer));
+    }
+    uri.push_str(&format!("&algorithm={}&digits={}", entry.algorithm.as_str(), entry.digits));
+    if entry.hotp {
+        uri.push_str(&format!("&counter={}", entry.counter));
+    } else {
+        uri.push_str(&format!("&period={}", entry.period));
+    }
+    uri
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_totp() {
+        let entry = parse(
+            "otpauth://totp/ACME%20Co:john.doe+2fa@email.com?secret=hxdm%20vjec&issuer=ACME%20Co&digits=8",
+        )
+        .unwrap();
+        assert_eq!(entry.issuer, "ACME Co");
+        assert_eq!(entry.account, "john.doe+2fa@email.com");
+        assert_eq!(entry.secret, "HXDMVJEC");
+        assert_eq!(entry.digits, 8);
+        assert_eq!(entry.period, 30);
+        assert!(!entry.hotp);
+    }
+
+    #[test]
+    fn hotp_needs_counter() {
+        assert!(parse("otpauth://hotp/x?secret=JBSWY3DP").is_err());
+        assert_eq!(parse("otpauth://hotp/x?secret=JBSWY3DP&counter=5").unwrap().counter, 5);
+    }
+
+    #[test]
+    fn round_trip() {
+        let entry = OtpEntry {
+            issuer: "A&B".to_string(),
+            account: "me:you".to_string(),
+            secret: "JBSWY3DPEHPK3PXP".to_string(),
+            algorithm: OtpAlgorithm::Sha256,
+            ..Default::default()
+        };
+        assert_eq!(parse(&to_uri(&entry)).unwrap(), entry);
+    }
+
+    #[test]
+    fn rejects_bad_secret() {
+        assert!(matches!(parse("otpauth://totp/x?secret=not-base32!"), Err(Error::Malformed(_))));
+    }
+}
@@ -0,0 +1,180 @@
+#!/usr/bin/env python3
+"""Writes kdbx4-argon2d-chacha20.kdbx, a known-answer fixture for the KDBX reader.
+
+The file is built here from the KDBX 4.0 spec, independently of the interchange crate, and copies what
+KeePassXC 2.7 writes by default: Argon2d, ChaCha20, gzip, a protected-stream key and an attachment in
+the inner header, protected Password and `otp` fields, entry history and a recycle bin. It is not an
+export from KeePassXC itself. Output is deterministic; the password is below.
+
+Needs the `cryptography` package (41 or later, for Argon2).
+"""
+import base64, datetime, gzip, hashlib, hmac, os, random, struct
+from cryptography.hazmat.primitives.kdf.argon2 import Argon2d
+from cryptography.hazmat.primitives.ciphers import Cipher, algorithms
+
+rng = random.Random(20240301)
+rb = lambda n: bytes(rng.getrandbits(8) for _ in range(n))
+PASSWORD = 'correct horse battery staple'
+
+def ts(y, mo, d, h=12, mi=0, s=0):
+    t = datetime.datetime(y, mo, d, h, mi, s)
+    secs = int((t - datetime.datetime(1, 1, 1)).total_seconds())
+    return base64.b64encode(struct.pack('<q', secs)).decode()
+
+def uid():
+    return base64.b64encode(rb(16)).decode()
+
+ZERO = base64.b64encode(bytes(16)).decode()
+
+# inner stream
+stream_key = rb(64)
+sk = hashlib.sha512(stream_key).digest()
+inner = Cipher(algorithms.ChaCha20(sk[:32], b'\0' * 4 + sk[32:44]), None).encryptor()
+def prot(s):
+    return base64.b64encode(inner.update(s.encode())).decode()
+
+def esc(s):
+    return s.replace('&', '&amp;').replace('<', '&lt;').replace('>', '&gt;').replace('"', '&quot;')
+
+def times(t, ind):
+    p = '\t' * ind
+    return (f'{p}<Times>\n'
+            f'{p}\t<LastModificationTime>{t}</LastModificationTime>\n'
+            f'{p}\t<CreationTime>{t}</CreationTime>\n'
+            f'{p}\t<LastAccessTime>{t}</LastAccessTime>\n'
+            f'{p}\t<ExpiryTime>{t}</ExpiryTime>\n'
+            f'{p}\t<Expires>False</Expires>\n'
+            f'{p}\t<UsageCount>0</UsageCount>\n'
+            f'{p}\t<LocationChanged>{t}</LocationChanged>\n'
+            f'{p}</Times>\n')
+
+def entry(ind, t, fields, protected, binaries=(), history=None, u=None):
+    p = '\t' * ind
+    x = (f'{p}<Entry>\n{p}\t<UUID>{u or uid()}</UUID>\n{p}\t<IconID>0</IconID>\n'
+         f'{p}\t<ForegroundColor/>\n{p}\t<BackgroundColor/>\n{p}\t<OverrideURL/>\n{p}\t<Tags/>\n')
+    x += times(t, ind + 1)
+    # KeePassXC keeps attributes in a sorted map
+    for k in sorted(fields):
+        v = fields[k]
+        x += f'{p}\t<String>\n{p}\t\t<Key>{esc(k)}</Key>\n'
+        if k in protected:
+            x += f'{p}\t\t<Value Protected="True">{prot(v)}</Value>\n'
+        elif v == '':
+            x += f'{p}\t\t<Value/>\n'
+        else:
+            x += f'{p}\t\t<Value>{esc(v)}</Value>\n'
+        x += f'{p}\t</String>\n'
+    for name, ref in binaries:
+        x += f'{p}\t<Binary>\n{p}\t\t<Key>{esc(name)}</Key>\n{p}\t\t<Value Ref="{ref}"/>\n{p}\t</Binary>\n'
+    x += (f'{p}\t<AutoType>\n{p}\t\t<Enabled>True</Enabled>\n'
+          f'{p}\t\t<DataTransferObfuscation>0</DataTransferObfuscation>\n{p}\t\t<DefaultSequence/>\n'
+          f'{p}\t</AutoType>\n')
+    if history is not None:
+        x += f'{p}\t<History>\n' + ''.join(history(ind + 2)) + f'{p}\t</History>\n'
+    return x + f'{p}</Entry>\n'
+
+def group_head(ind, u, name, icon, t):
+    p = '\t' * ind
+    return (f'{p}<Group>\n{p}\t<UUID>{u}</UUID>\n{p}\t<Name>{esc(name)}</Name>\n{p}\t<Notes/>\n'
+            f'{p}\t<IconID>{icon}</IconID>\n' + times(t, ind + 1) +
+            f'{p}\t<IsExpanded>True</IsExpanded>\n{p}\t<DefaultAutoTypeSequence/>\n'
+            f'{p}\t<EnableAutoType>null</EnableAutoType>\n{p}\t<EnableSearching>null</EnableSearching>\n'
+            f'{p}\t<LastTopVisibleEntry>{ZERO}</LastTopVisibleEntry>\n')
+
+T0 = ts(2024, 3, 1, 9, 30)
+T1 = ts(2024, 3, 2, 18, 5)
+root, bin_uuid, work = uid(), uid(), uid()
+
+meta = f'''\t<Meta>
+\t\t<Generator>KeePassXC</Generator>
+\t\t<DatabaseName>Passwords</DatabaseName>
+\t\t<DatabaseNameChanged>{T0}</DatabaseNameChanged>
+\t\t<DatabaseDescription/>
+\t\t<DatabaseDescriptionChanged>{T0}</DatabaseDescriptionChanged>
+\t\t<DefaultUserName/>
+\t\t<DefaultUserNameChanged>{T0}</DefaultUserNameChanged>
+\t\t<MaintenanceHistoryDays>365</MaintenanceHistoryDays>
+\t\t<Color/>
+\t\t<MasterKeyChanged>{T0}</MasterKeyChanged>
+\t\t<MasterKeyChangeRec>-1</MasterKeyChangeRec>
+\t\t<MasterKeyChangeForce>-1</MasterKeyChangeForce>
+\t\t<MemoryProtection>
+\t\t\t<ProtectTitle>False</ProtectTitle>
+\t\t\t<ProtectUserName>False</ProtectUserName>
+\t\t\t<ProtectPassword>True</ProtectPassword>
+\t\t\t<ProtectURL>False</ProtectURL>
+\t\t\t<ProtectNotes>False</ProtectNotes>
+\t\t</MemoryProtection>
+\t\t<CustomIcons/>
+\t\t<RecycleBinEnabled>True</RecycleBinEnabled>
+\t\t<RecycleBinUUID>{bin_uuid}</RecycleBinUUID>
+\t\t<RecycleBinChanged>{T1}</RecycleBinChanged>
+\t\t<EntryTemplatesGroup>{ZERO}</EntryTemplatesGroup>
+\t\t<EntryTemplatesGroupChanged>{T0}</EntryTemplatesGroupChanged>
+\t\t<LastSelectedGroup>{ZERO}</LastSelectedGroup>
+\t\t<LastTopVisibleGroup>{ZERO}</LastTopVisibleGroup>
+\t\t<HistoryMaxItems>10</HistoryMaxItems>
+\t\t<HistoryMaxSize>6291456</HistoryMaxSize>
+\t\t<SettingsChanged>{T0}</SettingsChanged>
+\t\t<CustomData>
+\t\t\t<Item>
+\t\t\t\t<Key>KPXC_DECRYPTION_TIME_PREFERENCE</Key>
+\t\t\t\t<Value>1000</Value>
+\t\t\t</Item>
+\t\t</CustomData>
+\t</Meta>
+'''
+
+body = group_head(2, root, 'Passwords', 48, T0)
+# a login whose password was changed once: the old one sits in its history
+body += entry(3, T1, {'Title': 'Forum', 'UserName': 'bob', 'Password': 'n3w & <improved>', 'URL': 'https://forum.example.org/', 'Notes': 'signed up in 2019\nsecurity question: "pets"'}, {'Password'},
+              history=lambda ind: [entry(ind, T0, {'Title': 'Forum', 'UserName': 'bob', 'Password': 'old-password', 'URL': 'https://forum.example.org/', 'Notes': ''}, {'Password'})])
+# KeePassXC's own TOTP setup: a protected otpauth URI in `otp`
+body += entry(3, T0, {'Title': 'Mail', 'UserName': 'bob@example.org', 'Password': 'пароль-密码', 'URL': 'https://mail.example.org', 'Notes': '',
+                      'otp': 'otpauth://totp/Example%20Mail:bob%40example.org?secret=JBSWY3DPEHPK3PXP&period=30&digits=6&issuer=Example%20Mail'},
+              {'Password', 'otp'}, binaries=[('recovery-codes.txt', 0)])
+body += group_head(3, work, 'Work', 1, T0)
+body += entry(4, T0, {'Title': 'VPN', 'UserName': 'bob.smith', 'Password': 'hunter2', 'URL': '', 'Notes': ''}, {'Password'})
+body += '\t\t\t</Group>\n'
+body += group_head(3, bin_uuid, 'Recycle Bin', 43, T1)
+body += entry(4, T1, {'Title': 'Deleted', 'UserName': 'gone', 'Password': 'deleted-password', 'URL': '', 'Notes': ''}, {'Password'})
+body += '\t\t\t</Group>\n'
+body += '\t\t</Group>\n'
+
+xml = ('<?xml version="1.0" encoding="UTF-8" standalone="yes"?>\n<KeePassFile>\n' + meta +
+       '\t<Root>\n' + body + '\t\t<DeletedObjects/>\n\t</Root>\n</KeePassFile>\n')
+
+def f(i, b):
+    return bytes([i]) + struct.pack('<I', len(b)) + b
+
+attachment = b'1. 2f8a-91cd\n2. 77b0-4e13\n'
+payload = (f(1, struct.pack('<I', 3)) + f(2, stream_key) + f(3, b'\x00' + attachment) + f(0, b'') +
+           xml.encode())
+
+# outer header; KeePassXC's variant map is a QMap, so its keys come out sorted
+salt, seed, iv = rb(32), rb(32), rb(12)
+M, I, P = 1024 * 1024, 2, 2
+def vd(items):
+    o = struct.pack('<H', 0x100)
+    for t, k, val in items:
+        o += bytes([t]) + struct.pack('<I', len(k)) + k.encode() + struct.pack('<I', len(val)) + val
+    return o + b'\0'
+kdfp = vd([(0x42, '$UUID', bytes.fromhex('ef636ddf8c29444b91f7a9a403e30a0c')), (0x05, 'I', struct.pack('<Q', I)),
+           (0x05, 'M', struct.pack('<Q', M)), (0x04, 'P', struct.pack('<I', P)), (0x42, 'S', salt),
+           (0x04, 'V', struct.pack('<I', 0x13))])
+hdr = (bytes.fromhex('03d9a29a67fb4bb5') + struct.pack('<HH', 0, 4) +
+       f(2, bytes.fromhex('d6038a2b8b6f4cb5a524339a31dbb59a')) + f(3, struct.pack('<I', 1)) + f(4, seed) +
+       f(7, iv) + f(11, kdfp) + f(0, b'\r\n\r\n'))
+
+comp = hashlib.sha256(hashlib.sha256(PASSWORD.encode()).digest()).digest()
+tk = Argon2d(salt=salt, length=32, iterations=I, lanes=P, memory_cost=M // 1024).derive(comp)
+hb = hashlib.sha512(seed + tk + b'\x01').digest()
+bk = lambda i: hashlib.sha512(struct.pack('<Q', i) + hb).digest()
+key = hashlib.sha256(seed + tk).digest()
+ct = Cipher(algorithms.ChaCha20(key, b'\0' * 4 + iv), None).encryptor().update(gzip.compress(payload, mtime=0))
+out = hdr + hashlib.sha256(hdr).digest() + hmac.new(bk(2**64 - 1), hdr, hashlib.sha256).digest()
+for i, blk in enumerate([ct, b'']):
+    lb = struct.pack('<I', len(blk))
+    out += hmac.new(bk(i), struct.pack('<Q', i) + lb + blk, hashlib.sha256).digest() + lb + blk
+with open(os.path.join(os.path.dirname(os.path.abspath(__file__)), 'kdbx4-argon2d-chacha20.kdbx'), 'wb') as out_file:
+    out_file.write(out)
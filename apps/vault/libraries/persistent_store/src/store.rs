@@ -215,6 +215,14 @@ impl<S: Storage> Store<S> {
         })
     }
 
+    /// Re-reads the list of entries from the PDDB.
+    ///
+    /// The list is otherwise only refreshed by this store's own transactions, so this has to be called
+    /// after entries were added or removed through another `Store` instance.
+    pub fn reload(&mut self) {
+        self.entries = self.pddb.list_keys(crate::store::OPENSK2_DICT, None).ok();
+    }
+
     /// Extracts the storage.
     pub fn extract_storage(self) -> S {
         self.storage
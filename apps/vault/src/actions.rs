@@ -8,22 +8,29 @@ use std::sync::{
 };
 
 use gam::TextEntryPayload;
+use interchange::Format;
 use locales::t;
 use num_traits::*;
-use passwords::PasswordGenerator;
 use pddb::BasisRetentionPolicy;
 #[cfg(feature = "vaultperf")]
 use perflib::*;
+use persistent_store::Store;
 use persistent_store::store::OPENSK2_DICT;
-use vault::env::xous::U2F_APP_DICT;
+use vault::api::customization::{Customization, DEFAULT_CUSTOMIZATION};
+use vault::ctap::status_code::Ctap2StatusCode;
+use vault::env::xous::{U2F_APP_DICT, XousStorage};
 use vault::{
     AppInfo, VAULT_ALLOC_HINT, VAULT_PASSWORD_DICT, VAULT_TOTP_DICT, atime_to_str, basis_change,
-    ctap::data_formats::PublicKeyCredentialSource, deserialize_app_info, serialize_app_info, utc_now,
+    ctap::data_formats::{PublicKeyCredentialSource, SignatureAlgorithm},
+    deserialize_app_info, serialize_app_info, utc_now,
 };
 use xous::{Message, send_message};
 
+use crate::import_export::{self, Received};
+use crate::passgen::{self, CharClasses, Preset, Profile, Style};
 use crate::storage::{self, PasswordRecord, StorageContent};
-use crate::totp::TotpAlgorithm;
+use crate::strength::{self, Strength};
+use crate::totp::{OtpScheme, TotpAlgorithm, TotpEntry, hotp_resync};
 use crate::{ItemLists, SelectedEntry, VaultMode};
 use crate::{ListItem, ListKey, storage::TotpRecord};
 #[cfg(feature = "vaultperf")]
@@ -31,6 +38,10 @@ const FILE_ID_APPS_VAULT_SRC_ACTIONS: u32 = 1;
 
 const VAULT_PASSWORD_REC_VERSION: u32 = 1;
 const VAULT_TOTP_REC_VERSION: u32 = 1;
+/// Last generator profile used for each site, keyed by `passgen::site_key`
+const VAULT_PASSGEN_DICT: &'static str = "vault.passgen";
+/// Typed passwords scoring below this ask for confirmation before they are saved
+const ACCEPTABLE_STRENGTH_SCORE: u8 = 3;
 /// time allowed between dialog box swaps for background operations to redraw
 #[cfg(feature = "ux-swap-delay")]
 const SWAP_DELAY_MS: usize = 300;
@@ -51,14 +62,22 @@ pub enum ActionOp {
     Quit,
     #[cfg(feature = "vault-testing")]
     /// Testing
-    GenerateTests,
+    GenerateTests = 10,
+    /// More menu items. Numbered explicitly, so they don't move with the `cfg` above
+    MenuImport = 11,
+    MenuScanQr,
+    MenuExport,
+    MenuHotpResyncStage2,
+    MenuManagePasskeys,
+    #[cfg(feature = "ed25519")]
+    MenuSshKeys,
 }
 
 pub struct ActionManager<'a> {
     modals: modals::Modals,
     storage: RefCell<storage::Manager>,
-
-    #[cfg(feature = "vault-testing")]
+    /// owns the BIP39 word list used by the word generator
+    gam: gam::Gam,
     trng: RefCell<trng::Trng>,
 
     mode: Arc<Mutex<VaultMode>>,
@@ -67,6 +86,9 @@ pub struct ActionManager<'a> {
     tt: ticktimer_server::Ticktimer,
     action_active: Arc<AtomicBool>,
     opensk_mutex: Arc<Mutex<i32>>,
+    /// shared with the main loop, which leaves the USB core alone while this is set
+    ssh_agent_running: Arc<AtomicBool>,
+    ssh_agent_thread: Option<std::thread::JoinHandle<()>>,
     mode_cache: VaultMode,
     main_conn: xous::CID,
     #[cfg(feature = "vaultperf")]
@@ -85,6 +107,7 @@ impl<'a> ActionManager<'a> {
         item_lists: Arc<Mutex<ItemLists>>,
         action_active: Arc<AtomicBool>,
         opensk_mutex: Arc<Mutex<i32>>,
+        ssh_agent_running: Arc<AtomicBool>,
     ) -> ActionManager<'a> {
         let xns = xous_names::XousNames::new().unwrap();
         let storage_manager = storage::Manager::new(&xns);
@@ -107,8 +130,7 @@ impl<'a> ActionManager<'a> {
         ActionManager {
             modals: modals::Modals::new(&xns).unwrap(),
             storage: RefCell::new(storage_manager),
-
-            #[cfg(feature = "vault-testing")]
+            gam: gam::Gam::new(&xns).unwrap(),
             trng: RefCell::new(trng::Trng::new(&xns).unwrap()),
 
             mode_cache: mc,
@@ -118,6 +140,8 @@ impl<'a> ActionManager<'a> {
             tt: ticktimer_server::Ticktimer::new().unwrap(),
             action_active,
             opensk_mutex,
+            ssh_agent_running,
+            ssh_agent_thread: None,
             main_conn,
             #[cfg(feature = "vaultperf")]
             perfbuf,
@@ -160,7 +184,7 @@ impl<'a> ActionManager<'a> {
             VaultMode::Password => {
                 let description = match self
                     .modals
-                    .alert_builder(t!("vault.newitem.name", locales::LANG))
+                    .alert_builder(t!("vault.newitem.name", locales::lang()))
                     .field(None, Some(password_validator))
                     .build()
                 {
@@ -175,7 +199,7 @@ impl<'a> ActionManager<'a> {
                 self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
                 let username = match self
                     .modals
-                    .alert_builder(t!("vault.newitem.username", locales::LANG))
+                    .alert_builder(t!("vault.newitem.username", locales::lang()))
                     .field(None, Some(password_validator))
                     .build()
                 {
@@ -188,163 +212,14 @@ impl<'a> ActionManager<'a> {
                 };
                 #[cfg(feature = "ux-swap-delay")]
                 self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
-                let mut approved = false;
-                let mut bip39 = false;
-                // Security note about PasswordGenerator. This is a 3rd party crate. It relies on `rand`'s
-                // implementation of ThreadRng to generate passwords. As of the version
-                // committed to the lockfile, I have evidenced the ThreadRng to request 8
-                // bytes of entropy from our TRNG to seed its state. If the docs are to be trusted,
-                // its thread-local RNG is a ChaCha CSPRNG, although the number of rounds used in it is not
-                // clear; code says 12 rounds, code comments say 20 and reference an issue
-                // about how this should be reduced. Audit path
-                // Cargo.lock is at:
-                //  rand-0.8.5
-                //  rand_core 0.6.3
-                //  getrandom 0.2.6 -> xous fork via Patch in top level Cargo.toml to map crates-io.getrandom
-                // to imports/getrandom  rand_chacha 0.3.1
-                //  passwords 3.1.9
-                //  random-pick 1.2.15
-                //  random-number 0.1.7
-                //  random-number-macro-mipl 0.1.6
-                //  proc-macro-hack : 0.5.19...and more (syn/quote also pulled in)
-                // - PasswordGenerator
-                //   - PasswordGeneratorIter::generate()
-                //   - random_pick::pick_multiple_from_multiple_slices()
-                //     - random_pick::gen_multiple_usize_with_weights()
-                //       - rng = random_number::rand::thread_rng()
-                //         - ThreadRng::thread_rng()
-                //         - Some crazy unsafe refcell construction that returns a clone of a
-                //           ReseedingRng<Core, OsRng>
-                //           - rand-0.8.5::std line 13: pub(crate) use rand_chacha::ChaCha12Core as Core;
-                //           - confirm no feature flags gating this, it is always used
-                //           - OsRng::try_fill_bytes()
-                //             - getrandom() -> to Xous code
-                //               - getrandom Xous fork - imp::getrandom_inner()
-                //                 - getrandom Xous fork - ensure_trng_conn() then fill_bytes() native Xous
-                //                   call
-                //       - random_number::random!(0..high, rng)
-                //         - random_number::random_with_rng
-                //           - random_number::random_inclusively_with_rng()
-                //             - Uniform::new_inclusive().sample()
-                //               - dead end at Distribution Trait and UniformSampler Trait, let's hope this is
-                //                 correct?
-                let pg = PasswordGenerator {
-                    length: 20,
-                    numbers: true,
-                    lowercase_letters: true,
-                    uppercase_letters: true,
-                    symbols: true,
-                    spaces: false,
-                    exclude_similar_characters: true,
-                    strict: true,
-                };
-                let mut password = pg.generate_one().unwrap();
-                while !approved {
-                    let maybe_password = match self
-                        .modals
-                        .alert_builder(t!("vault.newitem.password", locales::LANG))
-                        .field(Some(password), Some(password_validator))
-                        .build()
-                    {
-                        Ok(text) => &text.content()[0].content,
-                        _ => {
-                            log::error!("Name entry failed");
+                let (password, bip39) =
+                    match self.prompt_password(&description.to_string(), &username.to_string(), true) {
+                        Some(result) => result,
+                        None => {
                             self.action_active.store(false, Ordering::SeqCst);
                             return;
                         }
                     };
-                    #[cfg(feature = "ux-swap-delay")]
-                    self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
-                    password = if maybe_password.len() == 0 {
-                        let length = match self
-                            .modals
-                            .alert_builder(t!("vault.newitem.configure_length", locales::LANG))
-                            .field(Some("20".to_string()), Some(length_validator))
-                            .build()
-                        {
-                            Ok(entry) => &(entry.content()[0].content).parse::<u32>().unwrap(),
-                            _ => {
-                                log::error!("Length entry failed");
-                                self.action_active.store(false, Ordering::SeqCst);
-                                return;
-                            }
-                        };
-                        #[cfg(feature = "ux-swap-delay")]
-                        self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
-                        let mut upper = false;
-                        let mut number = false;
-                        let mut symbol = false;
-                        let mut lower = false;
-                        while !upper && !number && !symbol && !lower {
-                            self.modals
-                                .add_list(vec![
-                                    t!("vault.newitem.lowercase", locales::LANG),
-                                    t!("vault.newitem.uppercase", locales::LANG),
-                                    t!("vault.newitem.numbers", locales::LANG),
-                                    t!("vault.newitem.symbols", locales::LANG),
-                                ])
-                                .expect("couldn't create configuration modal");
-                            match self
-                                .modals
-                                .get_checkbox(t!("vault.newitem.configure_generator", locales::LANG))
-                            {
-                                Ok(options) => {
-                                    for opt in options {
-                                        if opt == t!("vault.newitem.lowercase", locales::LANG) {
-                                            lower = true;
-                                        }
-                                        if opt == t!("vault.newitem.uppercase", locales::LANG) {
-                                            upper = true;
-                                        }
-                                        if opt == t!("vault.newitem.numbers", locales::LANG) {
-                                            number = true;
-                                        }
-                                        if opt == t!("vault.newitem.symbols", locales::LANG) {
-                                            symbol = true;
-                                        }
-                                    }
-                                }
-                                _ => {
-                                    log::error!("Modal selection error");
-                                    self.action_active.store(false, Ordering::SeqCst);
-                                    return;
-                                }
-                            }
-                            if upper == false && lower == false && symbol == false && number == false {
-                                self.modals
-                                    .show_notification(
-                                        t!("vault.error.nothing_selected", locales::LANG),
-                                        None,
-                                    )
-                                    .ok();
-                            }
-                        }
-                        #[cfg(feature = "ux-swap-delay")]
-                        self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
-                        let pg2 = PasswordGenerator {
-                            length: *length as usize,
-                            numbers: number,
-                            lowercase_letters: lower,
-                            uppercase_letters: upper,
-                            symbols: symbol,
-                            spaces: false,
-                            exclude_similar_characters: upper || lower,
-                            strict: true,
-                        };
-                        approved = false;
-                        pg2.generate_one().unwrap()
-                    } else if maybe_password == "bip39" {
-                        bip39 = true;
-                        approved = true;
-                        match self.modals.input_bip39(Some(t!("vault.bip39.input", locales::LANG))) {
-                            Ok(data) => hex::encode(data),
-                            _ => "".to_string(),
-                        }
-                    } else {
-                        approved = true;
-                        maybe_password.to_string()
-                    };
-                }
                 let mut record = storage::PasswordRecord {
                     version: VAULT_PASSWORD_REC_VERSION,
                     description: description.to_string(),
@@ -353,7 +228,7 @@ impl<'a> ActionManager<'a> {
                     notes: if bip39 {
                         "bip39".to_string()
                     } else {
-                        t!("vault.notes", locales::LANG).to_string()
+                        t!("vault.notes", locales::lang()).to_string()
                     },
                     ctime: 0,
                     atime: 0,
@@ -364,7 +239,7 @@ impl<'a> ActionManager<'a> {
                     Ok(_) => (),
                     Err(error) => {
                         log::error!("internal error");
-                        self.report_err(t!("vault.error.internal_error", locales::LANG), Some(error));
+                        self.report_err(t!("vault.error.internal_error", locales::lang()), Some(error));
                     }
                 };
                 // update the ux cache
@@ -372,13 +247,13 @@ impl<'a> ActionManager<'a> {
                 self.item_lists.lock().unwrap().insert_unique(self.mode_cache, li);
             }
             VaultMode::Fido => {
-                self.report_err(t!("vault.error.add_fido2", locales::LANG), None::<std::io::Error>);
+                self.report_err(t!("vault.error.add_fido2", locales::lang()), None::<std::io::Error>);
                 // no DB entry update because it's an error to even get here
             }
             VaultMode::Totp => {
                 let description = match self
                     .modals
-                    .alert_builder(t!("vault.newitem.name", locales::LANG))
+                    .alert_builder(t!("vault.newitem.name", locales::lang()))
                     .field(None, Some(password_validator))
                     .build()
                 {
@@ -394,32 +269,48 @@ impl<'a> ActionManager<'a> {
                 self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
                 self.modals
                     .add_list(vec![
-                        t!("vault.newitem.totp", locales::LANG),
-                        t!("vault.newitem.hotp", locales::LANG),
+                        t!("vault.newitem.totp", locales::lang()),
+                        t!("vault.newitem.hotp", locales::lang()),
+                        t!("vault.newitem.steam", locales::lang()),
+                        t!("vault.newitem.yandex", locales::lang()),
+                        t!("vault.newitem.motp", locales::lang()),
                     ])
                     .expect("couldn't create configuration modal");
-                let is_totp: bool;
-                match self.modals.get_radiobutton(t!("vault.newitem.is_t_or_h_otp", locales::LANG)) {
-                    Ok(response) => {
-                        if &response == t!("vault.newitem.totp", locales::LANG) {
-                            is_totp = true;
-                        } else {
-                            is_totp = false;
+                let (is_totp, scheme) =
+                    match self.modals.get_radiobutton(t!("vault.newitem.is_t_or_h_otp", locales::lang())) {
+                        Ok(response) => {
+                            if &response == t!("vault.newitem.hotp", locales::lang()) {
+                                (false, OtpScheme::Rfc)
+                            } else if &response == t!("vault.newitem.steam", locales::lang()) {
+                                (true, OtpScheme::Steam)
+                            } else if &response == t!("vault.newitem.yandex", locales::lang()) {
+                                (true, OtpScheme::Yandex)
+                            } else if &response == t!("vault.newitem.motp", locales::lang()) {
+                                (true, OtpScheme::Motp)
+                            } else {
+                                (true, OtpScheme::Rfc)
+                            }
                         }
-                    }
-                    _ => {
-                        log::error!("Modal selection error");
-                        self.action_active.store(false, Ordering::SeqCst);
-                        return;
-                    }
-                }
+                        _ => {
+                            log::error!("Modal selection error");
+                            self.action_active.store(false, Ordering::SeqCst);
+                            return;
+                        }
+                    };
 
                 #[cfg(feature = "ux-swap-delay")]
                 self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
                 let secret = match self
                     .modals
-                    .alert_builder(t!("vault.newitem.totp_ss", locales::LANG))
-                    .field(None, Some(totp_ss_validator))
+                    .alert_builder(if scheme == OtpScheme::Motp {
+                        t!("vault.newitem.motp_ss", locales::lang())
+                    } else {
+                        t!("vault.newitem.totp_ss", locales::lang())
+                    })
+                    .field(
+                        None,
+                        Some(if scheme == OtpScheme::Motp { motp_ss_validator } else { totp_ss_validator }),
+                    )
                     .build()
                 {
                     Ok(text) => &text.content()[0].content,
@@ -432,9 +323,10 @@ impl<'a> ActionManager<'a> {
                 #[cfg(feature = "ux-swap-delay")]
                 self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
                 let ss = secret.to_uppercase();
-                let ss_vec = if let Some(ss) =
-                    base32::decode(base32::Alphabet::RFC4648 { padding: false }, &ss)
-                {
+                let ss_vec = if scheme == OtpScheme::Motp {
+                    // mOTP hashes the secret as text, so keep the hex digits themselves
+                    secret.to_lowercase().into_bytes()
+                } else if let Some(ss) = base32::decode(base32::Alphabet::RFC4648 { padding: false }, &ss) {
                     ss
                 } else {
                     if let Some(ss) = base32::decode(base32::Alphabet::RFC4648 { padding: true }, &ss) {
@@ -450,13 +342,34 @@ impl<'a> ActionManager<'a> {
                 };
                 let validated_secret = base32::encode(base32::Alphabet::RFC4648 { padding: false }, &ss_vec);
 
+                let pin = if scheme.needs_pin() {
+                    #[cfg(feature = "ux-swap-delay")]
+                    self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+                    match self
+                        .modals
+                        .alert_builder(t!("vault.newitem.otp_pin", locales::lang()))
+                        .field(None, Some(pin_validator))
+                        .build()
+                    {
+                        Ok(entry) => entry.content()[0].content.to_string(),
+                        _ => {
+                            log::error!("PIN entry failed");
+                            self.action_active.store(false, Ordering::SeqCst);
+                            return;
+                        }
+                    }
+                } else {
+                    String::new()
+                };
+
+                let (digits, default_timestep) = scheme.defaults();
                 let timestep = if !is_totp {
                     // get the initial count if it's an HOTP record
                     #[cfg(feature = "ux-swap-delay")]
                     self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
                     match self
                         .modals
-                        .alert_builder(t!("vault.hotp.count", locales::LANG))
+                        .alert_builder(t!("vault.hotp.count", locales::lang()))
                         .field(Some("0".to_string()), Some(count_validator))
                         .build()
                     {
@@ -468,7 +381,7 @@ impl<'a> ActionManager<'a> {
                         }
                     }
                 } else {
-                    30 // default TOTP timestep otherwise
+                    default_timestep
                 };
 
                 // time, hash, etc. are all the "expected defaults" -- if you want to change them, edit the
@@ -477,19 +390,25 @@ impl<'a> ActionManager<'a> {
                     version: VAULT_TOTP_REC_VERSION,
                     name: description.to_string(),
                     secret: validated_secret,
-                    algorithm: TotpAlgorithm::HmacSha1,
-                    digits: 6,
+                    algorithm: if scheme == OtpScheme::Yandex {
+                        TotpAlgorithm::HmacSha256
+                    } else {
+                        TotpAlgorithm::HmacSha1
+                    },
+                    digits,
                     timestep,
                     ctime: 0,
                     is_hotp: !is_totp,
-                    notes: t!("vault.notes", locales::LANG).to_string(),
+                    notes: t!("vault.notes", locales::lang()).to_string(),
+                    scheme,
+                    pin,
                 };
 
                 match self.storage.borrow_mut().new_record(&mut totp, None, true) {
                     Ok(_) => (),
                     Err(error) => {
                         log::error!("internal error");
-                        self.report_err(t!("vault.error.internal_error", locales::LANG), Some(error));
+                        self.report_err(t!("vault.error.internal_error", locales::lang()), Some(error));
                     }
                 };
                 let li = make_totp_item_from_record(&storage::hex(totp.hash()), totp);
@@ -501,7 +420,7 @@ impl<'a> ActionManager<'a> {
     pub(crate) fn menu_delete(&mut self, entry: SelectedEntry) {
         if self.yes_no_approval(&format!(
             "{}\n{}",
-            t!("vault.delete.confirm", locales::LANG),
+            t!("vault.delete.confirm", locales::lang()),
             entry.description
         )) {
             let choice = match entry.mode {
@@ -539,17 +458,21 @@ impl<'a> ActionManager<'a> {
                             Some(&attr.basis),
                         ) {
                             Ok(_) => {
+                                if dictionary == OPENSK2_DICT {
+                                    // OpenSK caches the list of keys; this tells it to re-read it
+                                    *self.opensk_mutex.lock().unwrap() += 1;
+                                }
                                 self.modals
-                                    .show_notification(t!("vault.completed", locales::LANG), None)
+                                    .show_notification(t!("vault.completed", locales::lang()), None)
                                     .ok();
                             }
                             Err(e) => {
-                                self.report_err(t!("vault.error.internal_error", locales::LANG), Some(e))
+                                self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e))
                             }
                         }
                     }
                     Err(e) => {
-                        self.report_err(t!("vault.error.not_found", locales::LANG), Some(e));
+                        self.report_err(t!("vault.error.not_found", locales::lang()), Some(e));
                     }
                 }
             } else {
@@ -564,7 +487,7 @@ impl<'a> ActionManager<'a> {
                     let pw: storage::PasswordRecord = match storage.get_record(&choice, guid) {
                         Ok(record) => record,
                         Err(error) => {
-                            self.report_err(t!("vault.error.internal_error", locales::LANG), Some(error));
+                            self.report_err(t!("vault.error.internal_error", locales::lang()), Some(error));
                             return;
                         }
                     };
@@ -585,10 +508,10 @@ impl<'a> ActionManager<'a> {
                 match self.storage.borrow_mut().delete(choice, guid) {
                     Ok(_) => self
                         .modals
-                        .show_notification(t!("vault.completed", locales::LANG), None)
+                        .show_notification(t!("vault.completed", locales::lang()), None)
                         .ok()
                         .unwrap(),
-                    Err(e) => self.report_err(t!("vault.error.internal_error", locales::LANG), Some(e)),
+                    Err(e) => self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e)),
                 }
             }
             self.pddb.borrow().sync().ok();
@@ -608,7 +531,7 @@ impl<'a> ActionManager<'a> {
                 let pw: storage::PasswordRecord = match storage.get_record(&choice, guid) {
                     Ok(record) => record,
                     Err(error) => {
-                        self.report_err(t!("vault.error.internal_error", locales::LANG), Some(error));
+                        self.report_err(t!("vault.error.internal_error", locales::lang()), Some(error));
                         return;
                     }
                 };
@@ -650,9 +573,9 @@ impl<'a> ActionManager<'a> {
                     let maybe_update = match record.read_to_end(&mut data) {
                         Ok(_len) => {
                             if let Some(mut ai) = deserialize_app_info(data) {
-                                let edit_data = if ai.notes != t!("vault.notes", locales::LANG) {
+                                let edit_data = if ai.notes != t!("vault.notes", locales::lang()) {
                                     self.modals
-                                        .alert_builder(t!("vault.edit_dialog", locales::LANG))
+                                        .alert_builder(t!("vault.edit_dialog", locales::lang()))
                                         .field_placeholder_persist(Some(ai.name), Some(password_validator))
                                         .field_placeholder_persist(Some(ai.notes), Some(password_validator))
                                         .field_placeholder_persist(Some(hex::encode(ai.id)), None)
@@ -660,7 +583,7 @@ impl<'a> ActionManager<'a> {
                                         .expect("modals error in edit")
                                 } else {
                                     self.modals
-                                        .alert_builder(t!("vault.edit_dialog", locales::LANG))
+                                        .alert_builder(t!("vault.edit_dialog", locales::lang()))
                                         .field_placeholder_persist(Some(ai.name), Some(password_validator))
                                         .field(Some(ai.notes), Some(password_validator))
                                         .field_placeholder_persist(Some(hex::encode(ai.id)), None)
@@ -673,14 +596,14 @@ impl<'a> ActionManager<'a> {
                                 ai
                             } else {
                                 self.report_err(
-                                    t!("vault.error.record_error", locales::LANG),
+                                    t!("vault.error.record_error", locales::lang()),
                                     None::<std::io::Error>,
                                 );
                                 return;
                             }
                         }
                         Err(e) => {
-                            self.report_err(t!("vault.error.internal_error", locales::LANG), Some(e));
+                            self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e));
                             return;
                         }
                     };
@@ -689,16 +612,16 @@ impl<'a> ActionManager<'a> {
                 Err(e) => {
                     match e.kind() {
                         std::io::ErrorKind::NotFound => {
-                            self.report_err(t!("vault.error.fido2", locales::LANG), None::<std::io::Error>)
+                            self.report_err(t!("vault.error.fido2", locales::lang()), None::<std::io::Error>)
                         }
-                        _ => self.report_err(t!("vault.error.internal_error", locales::LANG), Some(e)),
+                        _ => self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e)),
                     }
                     return;
                 }
             };
             if let Some((update, basis)) = maybe_update {
                 self.pddb.borrow().delete_key(dict, entry.key_guid.as_str(), Some(&basis)).unwrap_or_else(
-                    |e| self.report_err(t!("vault.error.internal_error", locales::LANG), Some(e)),
+                    |e| self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e)),
                 );
                 match self.pddb.borrow().get(
                     dict,
@@ -712,14 +635,14 @@ impl<'a> ActionManager<'a> {
                     Ok(mut record) => {
                         let ser = serialize_app_info(&update);
                         record.write(&ser).unwrap_or_else(|e| {
-                            self.report_err(t!("vault.error.internal_error", locales::LANG), Some(e));
+                            self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e));
                             0
                         });
                         // update the item cache so it appears on the screen
                         let li = make_u2f_item_from_record(entry.key_guid.as_str(), update);
                         self.item_lists.lock().unwrap().insert_unique(self.mode_cache, li);
                     }
-                    Err(e) => self.report_err(t!("vault.error.internal_error", locales::LANG), Some(e)),
+                    Err(e) => self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e)),
                 }
             }
             self.pddb.borrow().sync().ok();
@@ -735,14 +658,14 @@ impl<'a> ActionManager<'a> {
                 let mut pw: storage::TotpRecord = match storage.get_record(&choice, key_guid) {
                     Ok(record) => record,
                     Err(error) => {
-                        self.report_err(t!("vault.error.internal_error", locales::LANG), Some(error));
+                        self.report_err(t!("vault.error.internal_error", locales::lang()), Some(error));
                         return;
                     }
                 };
 
-                let edit_data = if pw.notes != t!("vault.notes", locales::LANG) {
+                let edit_data = if pw.notes != t!("vault.notes", locales::lang()) {
                     self.modals
-                        .alert_builder(t!("vault.edit_dialog", locales::LANG))
+                        .alert_builder(t!("vault.edit_dialog", locales::lang()))
                         .field_placeholder_persist(Some(pw.name), Some(password_validator))
                         .field_placeholder_persist(Some(pw.secret), Some(password_validator))
                         .field_placeholder_persist(Some(pw.notes), Some(password_validator))
@@ -757,7 +680,7 @@ impl<'a> ActionManager<'a> {
                         .expect("modals error in edit")
                 } else {
                     self.modals
-                        .alert_builder(t!("vault.edit_dialog", locales::LANG))
+                        .alert_builder(t!("vault.edit_dialog", locales::lang()))
                         .field_placeholder_persist(Some(pw.name), Some(password_validator))
                         .field_placeholder_persist(Some(pw.secret), Some(password_validator))
                         .field(Some(pw.notes), Some(password_validator))
@@ -801,7 +724,7 @@ impl<'a> ActionManager<'a> {
                 let mut pw: storage::PasswordRecord = match storage.get_record(&choice, key_guid) {
                     Ok(record) => record,
                     Err(error) => {
-                        self.report_err(t!("vault.error.internal_error", locales::LANG), Some(error));
+                        self.report_err(t!("vault.error.internal_error", locales::lang()), Some(error));
                         return;
                     }
                 };
@@ -819,9 +742,9 @@ impl<'a> ActionManager<'a> {
                 );
 
                 // display previous data for edit
-                let edit_data = if pw.notes != t!("vault.notes", locales::LANG) {
+                let edit_data = if pw.notes != t!("vault.notes", locales::lang()) {
                     self.modals
-                        .alert_builder(t!("vault.edit_dialog", locales::LANG))
+                        .alert_builder(t!("vault.edit_dialog", locales::lang()))
                         .field_placeholder_persist(Some(pw.description), Some(password_validator))
                         .field_placeholder_persist(Some(pw.username), Some(password_validator))
                         .field_placeholder_persist(Some(pw.password), Some(password_validator))
@@ -832,7 +755,7 @@ impl<'a> ActionManager<'a> {
                 } else {
                     // note is placeholder text, treat it as such
                     self.modals
-                        .alert_builder(t!("vault.edit_dialog", locales::LANG))
+                        .alert_builder(t!("vault.edit_dialog", locales::lang()))
                         .field_placeholder_persist(Some(pw.description), Some(password_validator))
                         .field_placeholder_persist(Some(pw.username), Some(password_validator))
                         .field_placeholder_persist(Some(pw.password), Some(password_validator))
@@ -851,7 +774,7 @@ impl<'a> ActionManager<'a> {
                 // display/edit the password field
                 if pw.notes.to_ascii_lowercase().starts_with("bip39") {
                     if pw.password.len() == 0 {
-                        match self.modals.input_bip39(Some(t!("vault.bip39.input", locales::LANG))) {
+                        match self.modals.input_bip39(Some(t!("vault.bip39.input", locales::lang()))) {
                             Ok(data) => {
                                 pw.password = hex::encode(data);
                             }
@@ -862,13 +785,13 @@ impl<'a> ActionManager<'a> {
                             Ok(data) => {
                                 match self
                                     .modals
-                                    .show_bip39(Some(t!("vault.bip39.output", locales::LANG)), &data)
+                                    .show_bip39(Some(t!("vault.bip39.output", locales::lang())), &data)
                                 {
                                     Ok(_) => {}
                                     Err(_) => {
                                         self.modals
                                             .show_notification(
-                                                t!("vault.bip39.output_error", locales::LANG),
+                                                t!("vault.bip39.output_error", locales::lang()),
                                                 None,
                                             )
                                             .unwrap();
@@ -877,125 +800,20 @@ impl<'a> ActionManager<'a> {
                             }
                             Err(_) => {
                                 self.modals
-                                    .show_notification(t!("vault.bip39.output_error", locales::LANG), None)
+                                    .show_notification(t!("vault.bip39.output_error", locales::lang()), None)
                                     .unwrap();
                             }
                         }
                     }
                 } else if pw.password.len() == 0 && !pw.notes.to_ascii_lowercase().starts_with("bip39") {
                     // if the password is empty, prompt to generate a new password
-                    let pg = PasswordGenerator {
-                        length: 20,
-                        numbers: true,
-                        lowercase_letters: true,
-                        uppercase_letters: true,
-                        symbols: true,
-                        spaces: false,
-                        exclude_similar_characters: true,
-                        strict: true,
-                    };
-                    let mut password = pg.generate_one().unwrap();
-                    let mut approved = false;
-                    while !approved {
-                        let maybe_password = match self
-                            .modals
-                            .alert_builder(t!("vault.newitem.password", locales::LANG))
-                            .field(Some(password), Some(password_validator))
-                            .build()
-                        {
-                            Ok(text) => text.content()[0].content.as_str().to_string(),
-                            _ => {
-                                log::error!("Name entry failed");
-                                self.action_active.store(false, Ordering::SeqCst);
-                                return;
-                            }
-                        };
-                        #[cfg(feature = "ux-swap-delay")]
-                        self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
-                        password = if maybe_password.len() == 0 {
-                            let length = match self
-                                .modals
-                                .alert_builder(t!("vault.newitem.configure_length", locales::LANG))
-                                .field(Some("20".to_string()), Some(length_validator))
-                                .build()
-                            {
-                                Ok(entry) => entry.content()[0].content.as_str().parse::<u32>().unwrap(),
-                                _ => {
-                                    log::error!("Length entry failed");
-                                    self.action_active.store(false, Ordering::SeqCst);
-                                    return;
-                                }
-                            };
-                            #[cfg(feature = "ux-swap-delay")]
-                            self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
-                            let mut upper = false;
-                            let mut number = false;
-                            let mut symbol = false;
-                            let mut lower = false;
-                            while !upper && !number && !symbol && !lower {
-                                self.modals
-                                    .add_list(vec![
-                                        t!("vault.newitem.lowercase", locales::LANG),
-                                        t!("vault.newitem.uppercase", locales::LANG),
-                                        t!("vault.newitem.numbers", locales::LANG),
-                                        t!("vault.newitem.symbols", locales::LANG),
-                                    ])
-                                    .expect("couldn't create configuration modal");
-                                match self
-                                    .modals
-                                    .get_checkbox(t!("vault.newitem.configure_generator", locales::LANG))
-                                {
-                                    Ok(options) => {
-                                        for opt in options {
-                                            if opt == t!("vault.newitem.lowercase", locales::LANG) {
-                                                lower = true;
-                                            }
-                                            if opt == t!("vault.newitem.uppercase", locales::LANG) {
-                                                upper = true;
-                                            }
-                                            if opt == t!("vault.newitem.numbers", locales::LANG) {
-                                                number = true;
-                                            }
-                                            if opt == t!("vault.newitem.symbols", locales::LANG) {
-                                                symbol = true;
-                                            }
-                                        }
-                                    }
-                                    _ => {
-                                        log::error!("Modal selection error");
-                                        self.action_active.store(false, Ordering::SeqCst);
-                                        return;
-                                    }
-                                }
-                                if upper == false && lower == false && symbol == false && number == false {
-                                    self.modals
-                                        .show_notification(
-                                            t!("vault.error.nothing_selected", locales::LANG),
-                                            None,
-                                        )
-                                        .ok();
-                                }
-                            }
-                            #[cfg(feature = "ux-swap-delay")]
-                            self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
-                            let pg2 = PasswordGenerator {
-                                length: length as usize,
-                                numbers: number,
-                                lowercase_letters: lower,
-                                uppercase_letters: upper,
-                                symbols: symbol,
-                                spaces: false,
-                                exclude_similar_characters: upper || lower,
-                                strict: true,
-                            };
-                            approved = false;
-                            pg2.generate_one().unwrap()
-                        } else {
-                            approved = true;
-                            maybe_password
-                        };
+                    match self.prompt_password(&pw.description, &pw.username, false) {
+                        Some((password, _)) => pw.password = password,
+                        None => {
+                            self.action_active.store(false, Ordering::SeqCst);
+                            return;
+                        }
                     }
-                    pw.password = password;
                 }
 
                 // note the edit access, this counts as an access since the password was revealed
@@ -1010,21 +828,202 @@ impl<'a> ActionManager<'a> {
                 }
                 ret
             }
+            // SSH keys have no list view; they are managed from their own menu
+            storage::ContentKind::SshKey => Ok(()),
         };
 
         match maybe_edited {
             Ok(_) => {}
-            Err(e) => self.report_err(t!("vault.error.internal_error", locales::LANG), Some(e)),
+            Err(e) => self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e)),
+        }
+    }
+
+    /// Offers a generated password for the entry `description` along with its strength. The user can
+    /// take it, type their own, or clear the field to pick another generator profile. Typing "bip39"
+    /// switches to BIP39 entry when `allow_bip39` is set. Returns the password and whether it came from
+    /// BIP39 entry, or `None` if a dialog failed.
+    fn prompt_password(
+        &self,
+        description: &str,
+        username: &str,
+        allow_bip39: bool,
+    ) -> Option<(String, bool)> {
+        let site = passgen::site_key(description);
+        let mut profile = self.remembered_profile(&site).unwrap_or_default();
+        let mut password = self.generate_password(&profile)?;
+        let mut generated = true;
+        loop {
+            let strength = if generated {
+                Strength::from_entropy(profile.entropy_bits())
+            } else {
+                strength::estimate(&password, &[description, username])
+            };
+            let prompt =
+                format!("{}\n{}", t!("vault.newitem.password", locales::lang()), strength_summary(&strength));
+            let entered = match self
+                .modals
+                .alert_builder(&prompt)
+                .field(Some(password.clone()), Some(password_validator))
+                .build()
+            {
+                Ok(text) => text.content()[0].content.as_str().to_string(),
+                _ => {
+                    log::error!("Password entry failed");
+                    return None;
+                }
+            };
+            #[cfg(feature = "ux-swap-delay")]
+            self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+            if entered.len() == 0 {
+                profile = self.choose_profile()?;
+                password = self.generate_password(&profile)?;
+                generated = true;
+            } else if allow_bip39 && entered == "bip39" {
+                return match self.modals.input_bip39(Some(t!("vault.bip39.input", locales::lang()))) {
+                    Ok(data) => Some((hex::encode(data), true)),
+                    _ => Some(("".to_string(), true)),
+                };
+            } else if generated && entered == password {
+                self.remember_profile(&site, &profile);
+                return Some((entered, false));
+            } else {
+                let strength = strength::estimate(&entered, &[description, username]);
+                if strength.score >= ACCEPTABLE_STRENGTH_SCORE
+                    || self.yes_no_approval(&format!(
+                        "{}\n{}",
+                        strength_summary(&strength),
+                        t!("vault.newitem.weak_confirm", locales::lang())
+                    ))
+                {
+                    return Some((entered, false));
+                }
+                // go around again with their password in the field, so it can be fixed up
+                password = entered;
+                generated = false;
+            }
+        }
+    }
+
+    /// Lets the user pick a preset generator profile, or build a custom one from character classes.
+    fn choose_profile(&self) -> Option<Profile> {
+        let labels: Vec<&str> = Preset::ALL.iter().map(|p| preset_label(*p)).collect();
+        let mut list = labels.clone();
+        list.push(t!("vault.passgen.custom", locales::lang()));
+        self.modals.add_list(list).expect("couldn't create configuration modal");
+        let choice = match self.modals.get_radiobutton(t!("vault.passgen.choose_profile", locales::lang())) {
+            Ok(choice) => choice,
+            _ => {
+                log::error!("Modal selection error");
+                return None;
+            }
+        };
+        #[cfg(feature = "ux-swap-delay")]
+        self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+        if let Some(index) = labels.iter().position(|l| *l == choice) {
+            return Some(Preset::ALL[index].profile());
+        }
+
+        let length = match self
+            .modals
+            .alert_builder(t!("vault.newitem.configure_length", locales::lang()))
+            .field(Some("20".to_string()), Some(length_validator))
+            .build()
+        {
+            Ok(entry) => entry.content()[0].content.as_str().parse::<usize>().unwrap(),
+            _ => {
+                log::error!("Length entry failed");
+                return None;
+            }
+        };
+        #[cfg(feature = "ux-swap-delay")]
+        self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+        let mut classes = CharClasses { lower: false, upper: false, numbers: false, symbols: false };
+        while !classes.any() {
+            self.modals
+                .add_list(vec![
+                    t!("vault.newitem.lowercase", locales::lang()),
+                    t!("vault.newitem.uppercase", locales::lang()),
+                    t!("vault.newitem.numbers", locales::lang()),
+                    t!("vault.newitem.symbols", locales::lang()),
+                ])
+                .expect("couldn't create configuration modal");
+            match self.modals.get_checkbox(t!("vault.newitem.configure_generator", locales::lang())) {
+                Ok(options) => {
+                    for opt in options {
+                        if opt == t!("vault.newitem.lowercase", locales::lang()) {
+                            classes.lower = true;
+                        }
+                        if opt == t!("vault.newitem.uppercase", locales::lang()) {
+                            classes.upper = true;
+                        }
+                        if opt == t!("vault.newitem.numbers", locales::lang()) {
+                            classes.numbers = true;
+                        }
+                        if opt == t!("vault.newitem.symbols", locales::lang()) {
+                            classes.symbols = true;
+                        }
+                    }
+                }
+                _ => {
+                    log::error!("Modal selection error");
+                    return None;
+                }
+            }
+            if !classes.any() {
+                self.modals.show_notification(t!("vault.error.nothing_selected", locales::lang()), None).ok();
+            }
+        }
+        #[cfg(feature = "ux-swap-delay")]
+        self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+        Some(Profile { style: Style::Characters(classes), length })
+    }
+
+    /// Makes a password with every random choice drawn from the TRNG.
+    fn generate_password(&self, profile: &Profile) -> Option<String> {
+        let gam = &self.gam;
+        let password =
+            profile.generate(&mut *self.trng.borrow_mut(), &|bytes| gam.bytes_to_bip39(bytes).ok());
+        if password.is_none() {
+            log::error!("couldn't generate a password for {:?}", profile);
+        }
+        password
+    }
+
+    fn remembered_profile(&self, site: &str) -> Option<Profile> {
+        match self.pddb.borrow().get(VAULT_PASSGEN_DICT, site, None, false, false, None, None::<fn()>) {
+            Ok(mut key) => {
+                let mut stored = String::new();
+                key.read_to_string(&mut stored).ok()?;
+                Profile::try_from(stored.as_str()).ok()
+            }
+            _ => None,
+        }
+    }
+
+    fn remember_profile(&self, site: &str, profile: &Profile) {
+        if site.len() == 0 || self.remembered_profile(site) == Some(*profile) {
+            return;
+        }
+        // stored profiles differ in length, so replace the key rather than writing over it
+        self.pddb.borrow().delete_key(VAULT_PASSGEN_DICT, site, None).ok();
+        match self.pddb.borrow().get(VAULT_PASSGEN_DICT, site, None, true, true, Some(64), Some(basis_change))
+        {
+            Ok(mut key) => {
+                if let Err(e) = key.write_all(profile.to_string().as_bytes()) {
+                    log::error!("couldn't remember generator profile: {:?}", e);
+                }
+            }
+            Err(e) => log::error!("couldn't remember generator profile: {:?}", e),
         }
     }
 
     fn yes_no_approval(&self, query: &str) -> bool {
         self.modals
-            .add_list(vec![t!("vault.yes", locales::LANG), t!("vault.no", locales::LANG)])
+            .add_list(vec![t!("vault.yes", locales::lang()), t!("vault.no", locales::lang())])
             .expect("couldn't build confirmation dialog");
         match self.modals.get_radiobutton(query) {
             Ok(response) => {
-                if &response == t!("vault.yes", locales::LANG) {
+                if &response == t!("vault.yes", locales::lang()) {
                     true
                 } else {
                     false
@@ -1077,7 +1076,7 @@ impl<'a> ActionManager<'a> {
         match self.mode_cache {
             VaultMode::Password => {
                 self.modals
-                    .dynamic_notification(Some(t!("vault.reloading_database", locales::LANG)), None)
+                    .dynamic_notification(Some(t!("vault.reloading_database", locales::lang())), None)
                     .ok();
                 let start = self.tt.elapsed_ms();
                 #[cfg(feature = "vaultperf")]
@@ -1128,7 +1127,8 @@ impl<'a> ActionManager<'a> {
                                             // note this code is duplicated in make_pw_item_from_record()
                                             extra.push_str(&human_time);
                                             extra.push_str("; ");
-                                            extra.push_str(t!("vault.u2f.appinfo.authcount", locales::LANG));
+                                            extra
+                                                .push_str(t!("vault.u2f.appinfo.authcount", locales::lang()));
                                             extra.push_str(&pw_rec.count.to_string());
                                             prev_entry.extra.clear();
                                             prev_entry.extra.push_str(&extra);
@@ -1156,7 +1156,7 @@ impl<'a> ActionManager<'a> {
 
                                         extra.push_str(&human_time);
                                         extra.push_str("; ");
-                                        extra.push_str(t!("vault.u2f.appinfo.authcount", locales::LANG));
+                                        extra.push_str(t!("vault.u2f.appinfo.authcount", locales::lang()));
                                         extra.push_str(&pw_rec.count.to_string());
 
                                         let li = ListItem::new(
@@ -1433,7 +1433,7 @@ impl<'a> ActionManager<'a> {
     pub(crate) fn unlock_basis(&mut self) {
         let name = match self
             .modals
-            .alert_builder(t!("vault.basis.name", locales::LANG))
+            .alert_builder(t!("vault.basis.name", locales::lang()))
             .field(None, Some(name_validator))
             .build()
         {
@@ -1453,9 +1453,11 @@ impl<'a> ActionManager<'a> {
                 self.item_lists.lock().unwrap().clear_all();
             }
             Err(e) => match e.kind() {
-                ErrorKind::PermissionDenied => self
-                    .report_err(t!("vault.error.basis_unlock_error", locales::LANG), None::<std::io::Error>),
-                _ => self.report_err(t!("vault.error.internal_error", locales::LANG), Some(e)),
+                ErrorKind::PermissionDenied => self.report_err(
+                    t!("vault.error.basis_unlock_error", locales::lang()),
+                    None::<std::io::Error>,
+                ),
+                _ => self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e)),
             },
         }
     }
@@ -1466,7 +1468,7 @@ impl<'a> ActionManager<'a> {
         let b: Vec<&str> = bases.iter().map(AsRef::as_ref).collect();
         if bases.len() > 0 {
             self.modals.add_list(b).expect("couldn't create unmount modal");
-            match self.modals.get_checkbox(t!("vault.basis.unmount", locales::LANG)) {
+            match self.modals.get_checkbox(t!("vault.basis.unmount", locales::lang())) {
                 Ok(unmount) => {
                     for b in unmount {
                         match self.pddb.borrow().lock_basis(&b) {
@@ -1476,24 +1478,24 @@ impl<'a> ActionManager<'a> {
                                 self.item_lists.lock().unwrap().clear_all();
                             }
                             Err(e) => {
-                                self.report_err(t!("vault.error.internal_error", locales::LANG), Some(e))
+                                self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e))
                             }
                         }
                     }
                 }
-                Err(e) => self.report_err(t!("vault.error.internal_error", locales::LANG), Some(e)),
+                Err(e) => self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e)),
             }
         } else {
-            if self.yes_no_approval(t!("vault.basis.none", locales::LANG)) {
+            if self.yes_no_approval(t!("vault.basis.none", locales::lang())) {
                 let name = match self
                     .modals
-                    .alert_builder(t!("vault.basis.create", locales::LANG))
+                    .alert_builder(t!("vault.basis.create", locales::lang()))
                     .field(None, Some(name_validator))
                     .build()
                 {
                     Ok(text) => &text.content()[0].content,
                     Err(e) => {
-                        self.report_err(t!("vault.error.internal_error", locales::LANG), Some(e));
+                        self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e));
                         return;
                     }
                 };
@@ -1501,7 +1503,7 @@ impl<'a> ActionManager<'a> {
                 self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
                 match self.pddb.borrow().create_basis(&name) {
                     Ok(_) => {
-                        if self.yes_no_approval(t!("vault.basis.created_mount", locales::LANG)) {
+                        if self.yes_no_approval(t!("vault.basis.created_mount", locales::lang())) {
                             match self.pddb.borrow().unlock_basis(&name, Some(BasisRetentionPolicy::Persist))
                             {
                                 Ok(_) => {
@@ -1511,11 +1513,13 @@ impl<'a> ActionManager<'a> {
                                 }
                                 Err(e) => match e.kind() {
                                     ErrorKind::PermissionDenied => self.report_err(
-                                        t!("vault.error.basis_unlock_error", locales::LANG),
+                                        t!("vault.error.basis_unlock_error", locales::lang()),
                                         None::<std::io::Error>,
                                     ),
-                                    _ => self
-                                        .report_err(t!("vault.error.internal_error", locales::LANG), Some(e)),
+                                    _ => self.report_err(
+                                        t!("vault.error.internal_error", locales::lang()),
+                                        Some(e),
+                                    ),
                                 },
                             }
                         } else {
@@ -1525,10 +1529,10 @@ impl<'a> ActionManager<'a> {
                     Err(e) => match e.kind() {
                         ErrorKind::AlreadyExists => {
                             self.modals
-                                .show_notification(t!("vault.basis.already_exists", locales::LANG), None)
+                                .show_notification(t!("vault.basis.already_exists", locales::lang()), None)
                                 .ok();
                         }
-                        _ => self.report_err(t!("vault.error.internal_error", locales::LANG), Some(e)),
+                        _ => self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e)),
                     },
                 }
             } else {
@@ -1537,6 +1541,657 @@ impl<'a> ActionManager<'a> {
         }
     }
 
+    /// Brings the stored count of an HOTP record back in step with its token, after codes were generated
+    /// on the token without being used here. The user types two consecutive codes from the token, which
+    /// are searched for ahead of the stored count.
+    pub(crate) fn menu_hotp_resync(&mut self, entry: SelectedEntry) {
+        if entry.mode != VaultMode::Totp {
+            self.report_err(t!("vault.hotp.resync_not_hotp", locales::lang()), None::<std::io::Error>);
+            return;
+        }
+        let choice = storage::ContentKind::TOTP;
+        let key_guid = entry.key_guid.as_str();
+        let mut storage = self.storage.borrow_mut();
+        let mut record: storage::TotpRecord = match storage.get_record(&choice, key_guid) {
+            Ok(record) => record,
+            Err(error) => {
+                self.report_err(t!("vault.error.internal_error", locales::lang()), Some(error));
+                return;
+            }
+        };
+        if !record.is_hotp || record.scheme != OtpScheme::Rfc {
+            self.report_err(t!("vault.hotp.resync_not_hotp", locales::lang()), None::<std::io::Error>);
+            return;
+        }
+
+        let codes = match self
+            .modals
+            .alert_builder(t!("vault.hotp.resync_prompt", locales::lang()))
+            .field(Some(t!("vault.hotp.resync_first", locales::lang()).to_string()), Some(count_validator))
+            .field(Some(t!("vault.hotp.resync_second", locales::lang()).to_string()), Some(count_validator))
+            .build()
+        {
+            Ok(codes) => codes,
+            _ => {
+                log::error!("HOTP code entry failed");
+                return;
+            }
+        };
+        let totp = TotpEntry {
+            step_seconds: 1,
+            shared_secret: base32::decode(base32::Alphabet::RFC4648 { padding: false }, &record.secret)
+                .unwrap_or(vec![]),
+            digit_count: record.digits as u8,
+            algorithm: record.algorithm,
+            scheme: record.scheme,
+            pin: record.pin.clone(),
+        };
+        match hotp_resync(
+            &totp,
+            record.timestep,
+            codes.content()[0].content.as_str(),
+            codes.content()[1].content.as_str(),
+        ) {
+            Some(count) => {
+                log::info!("HOTP count for {} resynced from {} to {}", record.name, record.timestep, count);
+                record.timestep = count;
+                match storage.update(&choice, key_guid, &mut record) {
+                    Ok(_) => {
+                        let li = make_totp_item_from_record(key_guid, record);
+                        self.item_lists.lock().unwrap().insert_unique(self.mode_cache, li);
+                        self.pddb.borrow().sync().ok();
+                        self.modals.show_notification(t!("vault.hotp.resynced", locales::lang()), None).ok();
+                    }
+                    Err(e) => self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e)),
+                }
+            }
+            None => {
+                self.modals.show_notification(t!("vault.hotp.resync_failed", locales::lang()), None).ok();
+            }
+        }
+    }
+
+    /// Reads the resident FIDO2 credentials, the signature counter and the size of the largeBlob array
+    /// straight from the OpenSK store. Done under `opensk_mutex`, as OpenSK may be writing to it.
+    fn passkey_snapshot(
+        &self,
+    ) -> Result<(Vec<(usize, PublicKeyCredentialSource)>, u32, usize), Ctap2StatusCode> {
+        let _mutex = self.opensk_mutex.lock().unwrap();
+        let store = Store::new(XousStorage {}).map_err(|(e, _)| Ctap2StatusCode::from(e))?;
+        let mut iter_result = Ok(());
+        let credentials: Vec<(usize, PublicKeyCredentialSource)> =
+            vault::ctap::storage::iter_credentials_in(&store, &mut iter_result)?.collect();
+        iter_result?;
+        let counter = vault::ctap::storage::global_signature_counter_in(&store)?;
+        let large_blob_len = vault::ctap::storage::large_blob_array_len_in(&store)?;
+        Ok((credentials, counter, large_blob_len))
+    }
+
+    /// Lets the user audit the resident FIDO2 credentials (passkeys) on the device: they are listed by
+    /// relying party, then by user, and any one of them can be inspected and deleted.
+    pub(crate) fn manage_passkeys(&mut self) {
+        loop {
+            let (credentials, counter, large_blob_len) = match self.passkey_snapshot() {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e));
+                    return;
+                }
+            };
+            if credentials.is_empty() {
+                self.modals.show_notification(t!("vault.passkeys.none", locales::lang()), None).ok();
+                return;
+            }
+            let summary = format!(
+                "{}{}/{}\n{}{}\n{}{}/{}",
+                t!("vault.passkeys.count", locales::lang()),
+                credentials.len(),
+                DEFAULT_CUSTOMIZATION.max_supported_resident_keys(),
+                t!("vault.passkeys.signature_counter", locales::lang()),
+                counter,
+                t!("vault.passkeys.large_blob", locales::lang()),
+                large_blob_len,
+                DEFAULT_CUSTOMIZATION.max_large_blob_array_size(),
+            );
+
+            // relying parties, with the number of credentials each
+            let mut rps = std::collections::BTreeMap::<&str, usize>::new();
+            for (_, credential) in credentials.iter() {
+                *rps.entry(credential.rp_id.as_str()).or_insert(0) += 1;
+            }
+            let rp_labels: Vec<String> =
+                rps.iter().map(|(rp, count)| format!("{} ({})", rp, count)).collect();
+            let mut list: Vec<&str> = rp_labels.iter().map(|l| l.as_str()).collect();
+            list.push(t!("vault.passkeys.done", locales::lang()));
+            self.modals.add_list(list).expect("couldn't build passkey list");
+            let rp = match self.modals.get_radiobutton(&summary) {
+                Ok(response) => match rp_labels.iter().position(|l| *l == response) {
+                    Some(index) => *rps.keys().nth(index).unwrap(),
+                    None => return,
+                },
+                _ => {
+                    log::error!("get passkey RP failed");
+                    return;
+                }
+            };
+
+            #[cfg(feature = "ux-swap-delay")]
+            self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+            let users: Vec<&(usize, PublicKeyCredentialSource)> =
+                credentials.iter().filter(|(_, credential)| credential.rp_id == rp).collect();
+            // the creation order is unique, and tells apart accounts that share a name
+            let user_labels: Vec<String> = users
+                .iter()
+                .map(|(_, credential)| {
+                    format!("{} #{}", passkey_user_name(credential), credential.creation_order)
+                })
+                .collect();
+            let mut list: Vec<&str> = user_labels.iter().map(|l| l.as_str()).collect();
+            list.push(t!("vault.passkeys.back", locales::lang()));
+            self.modals.add_list(list).expect("couldn't build passkey list");
+            let (key, credential) = match self.modals.get_radiobutton(rp) {
+                Ok(response) => match user_labels.iter().position(|l| *l == response) {
+                    Some(index) => users[index],
+                    None => continue,
+                },
+                _ => {
+                    log::error!("get passkey user failed");
+                    return;
+                }
+            };
+
+            #[cfg(feature = "ux-swap-delay")]
+            self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+            if self.yes_no_approval(&format!(
+                "{}\n\n{}",
+                passkey_details(credential),
+                t!("vault.passkeys.delete_confirm", locales::lang())
+            )) {
+                let deleted = {
+                    let mut mutex = self.opensk_mutex.lock().unwrap();
+                    let result = Store::new(XousStorage {})
+                        .map_err(|(e, _)| Ctap2StatusCode::from(e))
+                        .and_then(|mut store| vault::ctap::storage::delete_credential_in(&mut store, *key));
+                    // OpenSK caches the list of keys; this tells it to re-read it
+                    *mutex += 1;
+                    result
+                };
+                match deleted {
+                    Ok(_) => {
+                        self.pddb.borrow().sync().ok();
+                        self.modals.show_notification(t!("vault.completed", locales::lang()), None).ok();
+                    }
+                    Err(e) => self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e)),
+                }
+            }
+            #[cfg(feature = "ux-swap-delay")]
+            self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+        }
+    }
+
+    /// Lists the SSH keys in the open bases, and makes new ones. The agent that serves them over USB
+    /// serial is started and stopped from here too; see `ssh_agent` for the protocol.
+    #[cfg(feature = "ed25519")]
+    pub(crate) fn manage_ssh_keys(&mut self) {
+        loop {
+            let records: Vec<storage::SshKeyRecord> =
+                self.storage.borrow().all(storage::ContentKind::SshKey).unwrap_or_default();
+            let running = self.ssh_agent_running.load(Ordering::SeqCst);
+            let summary = format!(
+                "{}{}\n{}{}",
+                t!("vault.ssh.agent", locales::lang()),
+                if running {
+                    t!("vault.ssh.running", locales::lang())
+                } else {
+                    t!("vault.ssh.stopped", locales::lang())
+                },
+                t!("vault.ssh.count", locales::lang()),
+                records.len()
+            );
+            let toggle = if running {
+                t!("vault.ssh.stop_agent", locales::lang())
+            } else {
+                t!("vault.ssh.start_agent", locales::lang())
+            };
+            let mut list: Vec<&str> = records.iter().map(|r| r.comment.as_str()).collect();
+            list.push(t!("vault.ssh.new_key", locales::lang()));
+            list.push(toggle);
+            list.push(t!("vault.passkeys.done", locales::lang()));
+            self.modals.add_list(list).expect("couldn't build ssh key list");
+            let response = match self.modals.get_radiobutton(&summary) {
+                Ok(response) => response,
+                _ => {
+                    log::error!("get ssh key failed");
+                    return;
+                }
+            };
+            #[cfg(feature = "ux-swap-delay")]
+            self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+            if response == t!("vault.ssh.new_key", locales::lang()) {
+                self.new_ssh_key();
+            } else if response == toggle {
+                if running {
+                    self.stop_ssh_agent();
+                } else {
+                    self.start_ssh_agent();
+                }
+            } else if let Some(record) = records.into_iter().find(|r| r.comment == response) {
+                self.ssh_key_details(record);
+            } else {
+                return;
+            }
+            #[cfg(feature = "ux-swap-delay")]
+            self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+        }
+    }
+
+    #[cfg(feature = "ed25519")]
+    fn new_ssh_key(&mut self) {
+        let comment = match self
+            .modals
+            .alert_builder(t!("vault.ssh.key_name", locales::lang()))
+            .field(Some("precursor".to_string()), Some(ssh_comment_validator))
+            .build()
+        {
+            Ok(text) => text.content()[0].content.as_str().to_string(),
+            _ => {
+                log::error!("ssh key name entry failed");
+                return;
+            }
+        };
+        let mut seed = [0u8; 32];
+        self.trng.borrow_mut().fill_bytes_via_next(&mut seed);
+        let mut record = storage::SshKeyRecord {
+            version: storage::VAULT_SSH_REC_VERSION,
+            comment,
+            key_type: crate::ssh_agent::KEY_TYPE.to_string(),
+            seed: hex::encode(seed),
+            ctime: 0,
+            atime: 0,
+            count: 0,
+        };
+        match self.storage.borrow_mut().new_record(&mut record, None, false) {
+            Ok(_) => {}
+            Err(storage::Error::KeyExists) => {
+                self.modals.show_notification(t!("vault.ssh.name_exists", locales::lang()), None).ok();
+                return;
+            }
+            Err(e) => {
+                self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e));
+                return;
+            }
+        }
+        #[cfg(feature = "ux-swap-delay")]
+        self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+        self.ssh_key_details(record);
+    }
+
+    /// Shows the public half of a key, ready for `authorized_keys`, and offers to delete it.
+    #[cfg(feature = "ed25519")]
+    fn ssh_key_details(&mut self, record: storage::SshKeyRecord) {
+        let blob = match crate::ssh_agent::record_key_blob(&record) {
+            Some(blob) => blob,
+            None => {
+                self.report_err(t!("vault.error.internal_error", locales::lang()), None::<xous::Error>);
+                return;
+            }
+        };
+        let details = format!(
+            "{}\n{}\n\n{}\n\n{}{}\n{}{}",
+            record.comment,
+            crate::ssh_agent::fingerprint(&blob),
+            crate::ssh_agent::authorized_keys_line(&blob, &record.comment),
+            t!("vault.ssh.uses", locales::lang()),
+            record.count,
+            t!("vault.ssh.last_used", locales::lang()),
+            if record.count > 0 { atime_to_str(record.atime) } else { "-".to_string() },
+        );
+        self.modals
+            .add_list(vec![
+                t!("vault.passkeys.back", locales::lang()),
+                t!("vault.ssh.delete", locales::lang()),
+            ])
+            .expect("couldn't build ssh key dialog");
+        match self.modals.get_radiobutton(&details) {
+            Ok(response) if response == t!("vault.ssh.delete", locales::lang()) => {}
+            _ => return,
+        }
+        #[cfg(feature = "ux-swap-delay")]
+        self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+        if self.yes_no_approval(t!("vault.ssh.delete_confirm", locales::lang())) {
+            match self.storage.borrow_mut().delete(storage::ContentKind::SshKey, &storage::hex(record.hash()))
+            {
+                Ok(_) => {
+                    self.pddb.borrow().sync().ok();
+                    self.modals.show_notification(t!("vault.completed", locales::lang()), None).ok();
+                }
+                Err(e) => self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e)),
+            }
+        }
+    }
+
+    #[cfg(feature = "ed25519")]
+    fn start_ssh_agent(&mut self) {
+        let usb = usb_device_xous::UsbHid::new();
+        if usb.ensure_core(usb_device_xous::UsbDeviceType::Serial).is_err() {
+            self.report_err(t!("vault.interchange.usb_fail", locales::lang()), None::<xous::Error>);
+            return;
+        }
+        // a previous agent that lost the port to another listener has already exited
+        if let Some(thread) = self.ssh_agent_thread.take() {
+            thread.join().ok();
+        }
+        self.ssh_agent_running.store(true, Ordering::SeqCst);
+        self.ssh_agent_thread = Some(std::thread::spawn({
+            let running = self.ssh_agent_running.clone();
+            move || crate::ssh_agent::serve(running)
+        }));
+        self.modals.show_notification(t!("vault.ssh.started", locales::lang()), None).ok();
+    }
+
+    /// Stops the SSH agent if it is running, and hands the USB port back to the HID core.
+    fn stop_ssh_agent(&mut self) {
+        let thread = match self.ssh_agent_thread.take() {
+            Some(thread) => thread,
+            None => return,
+        };
+        self.ssh_agent_running.store(false, Ordering::SeqCst);
+        // dropping the listener unblocks the agent thread
+        usb_device_xous::UsbHid::new().serial_clear_input_hooks();
+        thread.join().ok();
+        send_message(
+            self.main_conn,
+            Message::new_scalar(crate::VaultOp::RestoreHid.to_usize().unwrap(), 0, 0, 0, 0),
+        )
+        .ok();
+    }
+
+    /// Receives a KeePass, CSV or otpauth file from the host over USB serial and adds its contents to
+    /// the vault. The wire format is described in `import_export`.
+    pub(crate) fn menu_import(&mut self) {
+        // the import needs the serial listener for itself
+        self.stop_ssh_agent();
+        let usb = usb_device_xous::UsbHid::new();
+        if usb.ensure_core(usb_device_xous::UsbDeviceType::Serial).is_err() {
+            self.report_err(t!("vault.interchange.usb_fail", locales::lang()), None::<xous::Error>);
+            return;
+        }
+        // no need to switch back: the HID core is restored when the vault returns to the foreground
+        self.modals
+            .dynamic_notification(
+                Some(t!("vault.interchange.waiting", locales::lang())),
+                Some(t!("vault.interchange.cancel_hint", locales::lang())),
+            )
+            .ok();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        // a keypress cancels by dropping the serial listener, which unblocks the receiver thread
+        let _ = std::thread::spawn({
+            let token = self.modals.token().clone();
+            let conn = self.modals.conn().clone();
+            let cancelled = cancelled.clone();
+            move || {
+                if let Ok(Some(_)) = modals::dynamic_notification_blocking_listener(token, conn) {
+                    cancelled.store(true, Ordering::SeqCst);
+                    usb_device_xous::UsbHid::new().serial_clear_input_hooks();
+                }
+            }
+        });
+        let (tx, rx) = std::sync::mpsc::channel();
+        let _ = std::thread::spawn({
+            let cancelled = cancelled.clone();
+            move || import_export::receive_frame(tx, cancelled)
+        });
+        let mut last_percent = 0;
+        let received = loop {
+            match rx.recv() {
+                Ok(Received::Progress(received, total)) => {
+                    let percent = received * 100 / total;
+                    if percent != last_percent {
+                        self.modals
+                            .dynamic_notification_update(
+                                Some(t!("vault.interchange.receiving", locales::lang())),
+                                Some(&format!("{}%", percent)),
+                            )
+                            .ok();
+                        last_percent = percent;
+                    }
+                }
+                Ok(other) => break other,
+                Err(_) => break Received::Cancelled,
+            }
+        };
+        self.modals.dynamic_notification_close().ok();
+        let (data, format) = match received {
+            Received::File(data, format) => (data, format),
+            Received::BadFrame => {
+                self.modals.show_notification(t!("vault.interchange.bad_frame", locales::lang()), None).ok();
+                return;
+            }
+            _ => {
+                self.modals.show_notification(t!("vault.interchange.cancelled", locales::lang()), None).ok();
+                return;
+            }
+        };
+        #[cfg(feature = "ux-swap-delay")]
+        self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+
+        let format = match format.or_else(|| interchange::detect(&data)) {
+            Some(format) => format,
+            None => {
+                self.modals
+                    .show_notification(t!("vault.interchange.unknown_format", locales::lang()), None)
+                    .ok();
+                return;
+            }
+        };
+        let text =
+            || std::str::from_utf8(&data).map_err(|_| interchange::Error::Malformed("file is not UTF-8"));
+        let parsed = match format {
+            Format::Kdbx => {
+                let password = match self
+                    .modals
+                    .alert_builder(t!("vault.interchange.kdbx_password", locales::lang()))
+                    .field(None, Some(password_validator))
+                    .build()
+                {
+                    Ok(text) => text.content()[0].content.to_string(),
+                    _ => return,
+                };
+                self.modals
+                    .dynamic_notification(Some(t!("vault.interchange.unlocking", locales::lang())), None)
+                    .ok();
+                let parsed = interchange::kdbx::read(&data, &password, import_export::KDF_MEMORY_LIMIT);
+                self.modals.dynamic_notification_close().ok();
+                parsed
+            }
+            Format::Csv => text().and_then(interchange::csv::parse),
+            Format::OtpAuth => text()
+                .and_then(interchange::otpauth::parse_all)
+                .map(|otp| interchange::Collection { otp, ..Default::default() }),
+        };
+        self.import_collection(parsed);
+    }
+
+    /// Imports the one-time codes in an `otpauth://` QR code read by the camera.
+    pub(crate) fn menu_scan_qr(&mut self) {
+        let xns = xous_names::XousNames::new().unwrap();
+        let qr = qr::Qr::new(&xns).unwrap();
+        self.modals.dynamic_notification(Some(t!("vault.qr.scanning", locales::lang())), None).ok();
+        let scanned = qr.scan_text();
+        self.modals.dynamic_notification_close().ok();
+        #[cfg(feature = "ux-swap-delay")]
+        self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+        let text = match scanned {
+            Ok(text) => text,
+            Err(qr::ScanError::NoCamera) => {
+                self.modals.show_notification(t!("vault.qr.no_camera", locales::lang()), None).ok();
+                return;
+            }
+            Err(e) => {
+                log::warn!("scan failed: {:?}", e);
+                self.modals.show_notification(t!("vault.qr.failed", locales::lang()), None).ok();
+                return;
+            }
+        };
+        if !text.starts_with("otpauth://") {
+            self.modals.show_notification(t!("vault.qr.not_otpauth", locales::lang()), None).ok();
+            return;
+        }
+        let parsed = interchange::otpauth::parse_all(&text)
+            .map(|otp| interchange::Collection { otp, ..Default::default() });
+        self.import_collection(parsed);
+    }
+
+    /// Writes the records of an import to the open bases and sums up what was imported, or reports why
+    /// the import failed.
+    fn import_collection(&mut self, parsed: Result<interchange::Collection, interchange::Error>) {
+        let collection = match parsed {
+            Ok(collection) => collection,
+            Err(interchange::Error::KdfTooExpensive { .. }) => {
+                self.modals
+                    .show_notification(t!("vault.interchange.kdf_too_expensive", locales::lang()), None)
+                    .ok();
+                return;
+            }
+            Err(e) => {
+                log::warn!("import failed: {:?}", e);
+                self.modals
+                    .show_notification(
+                        &format!("{}{}", t!("vault.interchange.failed", locales::lang()), e),
+                        None,
+                    )
+                    .ok();
+                return;
+            }
+        };
+
+        let mut duplicates = 0;
+        match self.storage.borrow_mut().new_records(import_export::to_records(&collection), None, false) {
+            Ok(()) => {}
+            // non-fatal: everything else was still written
+            Err(storage::Error::DupesExist(dupes)) => duplicates = dupes.len(),
+            Err(e) => {
+                self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e));
+                return;
+            }
+        }
+        let mut summary = format!(
+            "{}{}\n{}{}",
+            t!("vault.interchange.imported_passwords", locales::lang()),
+            collection.passwords.len(),
+            t!("vault.interchange.imported_otp", locales::lang()),
+            collection.otp.len()
+        );
+        if duplicates > 0 {
+            summary.push_str(&format!(
+                "\n{}{}",
+                t!("vault.interchange.duplicates", locales::lang()),
+                duplicates
+            ));
+        }
+        if !collection.skipped.is_empty() {
+            summary.push_str(&format!(
+                "\n{}{}",
+                t!("vault.interchange.skipped", locales::lang()),
+                collection.skipped.join(", ")
+            ));
+        }
+        self.modals.show_notification(&summary, None).ok();
+    }
+
+    /// Writes every password and OTP record in the open bases to the host over USB serial, as a KDBX 4
+    /// database protected by a password chosen here.
+    pub(crate) fn menu_export(&mut self) {
+        let password = match self
+            .modals
+            .alert_builder(t!("vault.interchange.export_password", locales::lang()))
+            .field(None, Some(password_validator))
+            .build()
+        {
+            Ok(text) => text.content()[0].content.to_string(),
+            _ => return,
+        };
+        #[cfg(feature = "ux-swap-delay")]
+        self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
+        let confirm = match self
+            .modals
+            .alert_builder(t!("vault.interchange.export_confirm", locales::lang()))
+            .field(None, Some(password_validator))
+            .build()
+        {
+            Ok(text) => text.content()[0].content.to_string(),
+            _ => return,
+        };
+        if password != confirm {
+            self.modals.show_notification(t!("vault.interchange.mismatch", locales::lang()), None).ok();
+            return;
+        }
+        let usb = usb_device_xous::UsbHid::new();
+        if usb.ensure_core(usb_device_xous::UsbDeviceType::Serial).is_err() {
+            self.report_err(t!("vault.interchange.usb_fail", locales::lang()), None::<xous::Error>);
+            return;
+        }
+
+        self.modals
+            .dynamic_notification(Some(t!("vault.interchange.encrypting", locales::lang())), None)
+            .ok();
+        let records = {
+            let manager = self.storage.borrow();
+            manager.all::<PasswordRecord>(storage::ContentKind::Password).and_then(|passwords| {
+                manager.all::<TotpRecord>(storage::ContentKind::TOTP).map(|totp| (passwords, totp))
+            })
+        };
+        let (passwords, totp) = match records {
+            Ok(records) => records,
+            Err(e) => {
+                self.modals.dynamic_notification_close().ok();
+                self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e));
+                return;
+            }
+        };
+        let collection = import_export::from_records(&passwords, &totp);
+        let xns = xous_names::XousNames::new().unwrap();
+        let mut trng = trng::Trng::new(&xns).unwrap();
+        let settings =
+            interchange::kdbx::KdfSettings { memory: import_export::KDF_MEMORY_LIMIT, ..Default::default() };
+        let file = interchange::kdbx::write(&collection, &password, &settings, &mut trng);
+        self.modals.dynamic_notification_close().ok();
+        let file = match file {
+            Ok(file) => file,
+            Err(e) => {
+                self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e));
+                return;
+            }
+        };
+
+        // the host tool has to be listening before anything is written, or the start of the armor is lost
+        self.modals.show_notification(t!("vault.interchange.sending", locales::lang()), None).ok();
+        let (lines, digest) = import_export::armor("vault.kdbx", &file);
+        self.modals.dynamic_notification(Some(t!("vault.menu_export", locales::lang())), None).ok();
+        for (i, line) in lines.iter().enumerate() {
+            if i % 64 == 0 {
+                self.modals
+                    .dynamic_notification_update(None, Some(&format!("{}%", i * 100 / lines.len())))
+                    .ok();
+            }
+            if let Err(e) = usb.serial_send(&format!("{}\r\n", line)) {
+                self.modals.dynamic_notification_close().ok();
+                self.report_err(t!("vault.error.internal_error", locales::lang()), Some(e));
+                return;
+            }
+        }
+        self.modals.dynamic_notification_close().ok();
+        let mut summary = format!("{}{}", t!("vault.interchange.exported", locales::lang()), digest);
+        if !collection.skipped.is_empty() {
+            summary.push_str(&format!(
+                "\n{}{}",
+                t!("vault.interchange.skipped", locales::lang()),
+                collection.skipped.join(", ")
+            ));
+        }
+        self.modals.show_notification(&summary, None).ok();
+    }
+
     #[cfg(feature = "vault-testing")]
     pub(crate) fn populate_tests(&mut self) {
         self.modals.dynamic_notification(Some("Creating test entries..."), None).ok();
@@ -1602,17 +2257,7 @@ impl<'a> ActionManager<'a> {
                 );
                 let username = random_pick::pick_from_slice(&words, &weights).unwrap().to_string();
                 let notes = random_pick::pick_from_slice(&words, &weights).unwrap().to_string();
-                let pg = PasswordGenerator {
-                    length: 20,
-                    numbers: true,
-                    lowercase_letters: true,
-                    uppercase_letters: true,
-                    symbols: true,
-                    spaces: false,
-                    exclude_similar_characters: true,
-                    strict: true,
-                };
-                let password = pg.generate_one().unwrap();
+                let password = self.generate_password(&Profile::default()).unwrap();
                 let mut record = storage::PasswordRecord {
                     version: VAULT_PASSWORD_REC_VERSION,
                     description,
@@ -1750,6 +2395,8 @@ impl<'a> ActionManager<'a> {
                     timestep: 30,
                     ctime: 0,
                     is_hotp: false,
+                    scheme: OtpScheme::Rfc,
+                    pin: String::new(),
                 };
 
                 match self.storage.borrow_mut().new_record(&mut record, None, true) {
@@ -1768,6 +2415,8 @@ impl<'a> ActionManager<'a> {
                 timestep: 30,
                 ctime: 0,
                 is_hotp: false,
+                scheme: OtpScheme::Rfc,
+                pin: String::new(),
             };
 
             match self.storage.borrow_mut().new_record(&mut record, None, true) {
@@ -1796,39 +2445,65 @@ pub(crate) fn totp_ss_validator(input: &TextEntryPayload) -> Option<String> {
         if ss.len() > 0 {
             return None;
         } else {
-            return Some(String::from(t!("vault.illegal_totp", locales::LANG)));
+            return Some(String::from(t!("vault.illegal_totp", locales::lang())));
         }
     }
     if let Some(ss) = base32::decode(base32::Alphabet::RFC4648 { padding: true }, &proposed_ss) {
         if ss.len() > 0 {
             return None;
         } else {
-            return Some(String::from(t!("vault.illegal_totp", locales::LANG)));
+            return Some(String::from(t!("vault.illegal_totp", locales::lang())));
         }
     }
     if let Some(ss) = base32::decode(base32::Alphabet::Crockford, &proposed_ss) {
         if ss.len() > 0 {
             return None;
         } else {
-            return Some(String::from(t!("vault.illegal_totp", locales::LANG)));
+            return Some(String::from(t!("vault.illegal_totp", locales::lang())));
         }
     }
-    Some(String::from(t!("vault.illegal_totp", locales::LANG)))
+    Some(String::from(t!("vault.illegal_totp", locales::lang())))
 }
 pub(crate) fn name_validator(input: &TextEntryPayload) -> Option<String> {
     let proposed_name = input.as_str();
     if proposed_name.contains(['\n', ':']) {
         // the '\n' is reserved as the delimiter to end the name field, and ':' is the path separator
-        Some(String::from(t!("vault.illegal_char", locales::LANG)))
+        Some(String::from(t!("vault.illegal_char", locales::lang())))
     } else {
         None
     }
 }
+fn preset_label(preset: Preset) -> &'static str {
+    match preset {
+        Preset::Strong => t!("vault.passgen.strong", locales::lang()),
+        Preset::Alphanumeric => t!("vault.passgen.alphanumeric", locales::lang()),
+        Preset::Pin => t!("vault.passgen.pin", locales::lang()),
+        Preset::Pronounceable => t!("vault.passgen.pronounceable", locales::lang()),
+        Preset::Words => t!("vault.passgen.words", locales::lang()),
+    }
+}
+/// One line for the password dialog, e.g. "Strength: strong (~72 bits)"
+fn strength_summary(strength: &Strength) -> String {
+    let rating = match strength.score {
+        0 => t!("vault.strength.very_weak", locales::lang()),
+        1 => t!("vault.strength.weak", locales::lang()),
+        2 => t!("vault.strength.fair", locales::lang()),
+        3 => t!("vault.strength.strong", locales::lang()),
+        _ => t!("vault.strength.very_strong", locales::lang()),
+    };
+    format!(
+        "{}{} (~{} {})",
+        t!("vault.strength", locales::lang()),
+        rating,
+        strength.bits() as u32,
+        t!("vault.strength.bits", locales::lang())
+    )
+}
 pub(crate) fn password_validator(input: &TextEntryPayload) -> Option<String> {
     let proposed_name = input.as_str();
     if proposed_name.contains(['\n']) {
         // the '\n' is reserved as the delimiter to end the name field
-        Some(String::from(t!("vault.illegal_char", locales::LANG)))
+        Some(String::from(t!("vault.illegal_char", locales::lang())))
     } else {
         None
     }
@@ -1838,19 +2513,46 @@ fn length_validator(input: &TextEntryPayload) -> Option<String> {
     match text_str.parse::<u32>() {
         Ok(input_int) => {
             if input_int < 1 || input_int > 128 {
-                Some(String::from(t!("vault.illegal_number", locales::LANG)))
+                Some(String::from(t!("vault.illegal_number", locales::lang())))
             } else {
                 None
             }
         }
-        _ => Some(String::from(t!("vault.illegal_number", locales::LANG))),
+        _ => Some(String::from(t!("vault.illegal_number", locales::lang()))),
+    }
+}
+/// SSH key comments name the key on the device and in `ssh-add -l`
+#[cfg(feature = "ed25519")]
+fn ssh_comment_validator(input: &TextEntryPayload) -> Option<String> {
+    let comment = input.as_str();
+    if comment.is_empty() || comment.contains(['\n', '\r']) {
+        Some(String::from(t!("vault.illegal_char", locales::lang())))
+    } else {
+        None
+    }
+}
+/// mOTP secrets are 16 or 32 hex digits, depending on the token
+fn motp_ss_validator(input: &TextEntryPayload) -> Option<String> {
+    let text_str = input.as_str();
+    if (text_str.len() == 16 || text_str.len() == 32) && text_str.chars().all(|c| c.is_ascii_hexdigit()) {
+        None
+    } else {
+        Some(String::from(t!("vault.illegal_motp", locales::lang())))
+    }
+}
+fn pin_validator(input: &TextEntryPayload) -> Option<String> {
+    let text_str = input.as_str();
+    if text_str.len() >= 4 && text_str.len() <= 16 && text_str.chars().all(|c| c.is_ascii_digit()) {
+        None
+    } else {
+        Some(String::from(t!("vault.illegal_otp_pin", locales::lang())))
     }
 }
 fn count_validator(input: &TextEntryPayload) -> Option<String> {
     let text_str = input.as_str();
     match text_str.parse::<u64>() {
         Ok(_input_int) => None,
-        _ => Some(String::from(t!("vault.illegal_count", locales::LANG))),
+        _ => Some(String::from(t!("vault.illegal_count", locales::lang()))),
     }
 }
 
@@ -1905,12 +2607,52 @@ fn make_u2f_item_from_record(guid: &str, ai: AppInfo) -> ListItem {
     let extra = format!(
         "{}; {}{}",
         atime_to_str(ai.atime),
-        t!("vault.u2f.appinfo.authcount", locales::LANG),
+        t!("vault.u2f.appinfo.authcount", locales::lang()),
         ai.count,
     );
     let desc: String = format!("{} (U2F)", ai.name);
     ListItem::new(desc, extra, true, guid.to_owned(), ai.count, ai.atime)
 }
+/// The most descriptive name a credential has for its user.
+fn passkey_user_name(credential: &PublicKeyCredentialSource) -> String {
+    if let Some(name) = credential.user_name.as_ref().filter(|n| !n.is_empty()) {
+        name.to_string()
+    } else if let Some(name) = credential.user_display_name.as_ref().filter(|n| !n.is_empty()) {
+        name.to_string()
+    } else {
+        // user handles are opaque bytes, usually not printable
+        hex::encode(&credential.user_handle[..credential.user_handle.len().min(8)])
+    }
+}
+fn passkey_details(credential: &PublicKeyCredentialSource) -> String {
+    let mut details = format!(
+        "{}\n{}{}\n{}{}\n{}{}",
+        credential.rp_id,
+        t!("vault.passkeys.user", locales::lang()),
+        passkey_user_name(credential),
+        t!("vault.passkeys.display_name", locales::lang()),
+        credential.user_display_name.as_deref().unwrap_or("-"),
+        t!("vault.passkeys.created", locales::lang()),
+        credential.creation_order,
+    );
+    let algorithm = match credential.private_key.signature_algorithm() {
+        SignatureAlgorithm::Es256 => "ES256",
+        #[cfg(feature = "ed25519")]
+        SignatureAlgorithm::Eddsa => "EdDSA",
+        _ => "-",
+    };
+    details.push_str(&format!("\n{}{}", t!("vault.passkeys.algorithm", locales::lang()), algorithm));
+    if let Some(policy) = credential.cred_protect_policy.as_ref() {
+        details.push_str(&format!("\n{}{:?}", t!("vault.passkeys.cred_protect", locales::lang()), policy));
+    }
+    if credential.large_blob_key.is_some() {
+        details.push_str(&format!("\n{}", t!("vault.passkeys.has_large_blob", locales::lang())));
+    }
+    if let Some(blob) = credential.cred_blob.as_ref() {
+        details.push_str(&format!("\n{}{}", t!("vault.passkeys.cred_blob", locales::lang()), blob.len()));
+    }
+    details
+}
 fn make_fido_item_from_record(guid: &str, result: PublicKeyCredentialSource) -> ListItem {
     let name = if let Some(display_name) = result.user_display_name {
         display_name
@@ -1927,12 +2669,14 @@ fn make_fido_item_from_record(guid: &str, result: PublicKeyCredentialSource) ->
 }
 fn make_totp_item_from_record(guid: &str, totp: TotpRecord) -> ListItem {
     let extra = format!(
-        "{}:{}:{}:{}:{}",
+        "{}:{}:{}:{}:{}:{}:{}",
         totp.secret,
         totp.digits,
         totp.timestep,
         totp.algorithm,
-        if totp.is_hotp { "HOTP" } else { "TOTP" }
+        if totp.is_hotp { "HOTP" } else { "TOTP" },
+        totp.scheme,
+        totp.pin
     );
     let desc = format!("{}", totp.name);
     ListItem::new(desc, extra, true, guid.to_owned(), 0, 0)
@@ -1945,7 +2689,7 @@ fn make_pw_item_from_record(guid: &str, pw: PasswordRecord) -> ListItem {
     let human_time = atime_to_str(pw.atime);
     extra.push_str(&human_time);
     extra.push_str("; ");
-    extra.push_str(t!("vault.u2f.appinfo.authcount", locales::LANG));
+    extra.push_str(t!("vault.u2f.appinfo.authcount", locales::lang()));
     extra.push_str(&pw.count.to_string());
     ListItem::new(
         desc.to_string(), // these allocs will be slow, but we do it only once on boot
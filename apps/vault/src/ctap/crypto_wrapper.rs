@@ -83,7 +83,7 @@ pub enum PrivateKey {
     // store both if we believe deriving the key is done more than once and costly.
     Ecdsa([u8; 32]),
     #[cfg(feature = "ed25519")]
-    Ed25519(ed25519_compact::SecretKey),
+    Ed25519(ed25519_dalek::SigningKey),
 }
 
 impl PrivateKey {
@@ -126,8 +126,7 @@ impl PrivateKey {
         if bytes.len() != 32 {
             return None;
         }
-        let seed = ed25519_compact::Seed::from_slice(bytes).unwrap();
-        Some(Self::Ed25519(ed25519_compact::KeyPair::from_seed(seed).sk))
+        Some(Self::Ed25519(ed25519_dalek::SigningKey::from_bytes(array_ref!(bytes, 0, 32))))
     }
 
     /// Returns the ECDSA private key.
@@ -146,7 +145,7 @@ impl PrivateKey {
                 CoseKey::from(ecdsa_key_from_seed(env, ecdsa_seed)?.genpk())
             }
             #[cfg(feature = "ed25519")]
-            PrivateKey::Ed25519(ed25519_key) => CoseKey::from(ed25519_key.public_key()),
+            PrivateKey::Ed25519(ed25519_key) => CoseKey::from(ed25519_key.verifying_key()),
         })
     }
 
@@ -161,7 +160,10 @@ impl PrivateKey {
                 .sign_rfc6979::<Sha256>(message)
                 .to_asn1_der(),
             #[cfg(feature = "ed25519")]
-            PrivateKey::Ed25519(ed25519_key) => ed25519_key.sign(message, None).to_vec(),
+            PrivateKey::Ed25519(ed25519_key) => {
+                use ed25519_dalek::Signer;
+                ed25519_key.sign(message).to_bytes().to_vec()
+            }
         })
     }
 
@@ -179,7 +181,7 @@ impl PrivateKey {
         match self {
             PrivateKey::Ecdsa(ecdsa_seed) => ecdsa_seed.to_vec(),
             #[cfg(feature = "ed25519")]
-            PrivateKey::Ed25519(ed25519_key) => ed25519_key.seed().to_vec(),
+            PrivateKey::Ed25519(ed25519_key) => ed25519_key.to_bytes().to_vec(),
         }
     }
 }
@@ -201,7 +201,7 @@ impl Ctap1Command {
                     return Err(Ctap1StatusCode::SW_COND_USE_NOT_SATISFIED);
                 }
                 #[cfg(feature="xous")]
-                let regstr = String::from(t!("vault.u2f.register", locales::LANG));
+                let regstr = String::from(t!("vault.u2f.register", locales::lang()));
                 #[cfg(feature="xous")]
                 if !ctap_state.u2f_up_state.consume_up(env, regstr, application) {
                     log::debug!("still waiting...");
@@ -224,7 +224,7 @@ impl Ctap1Command {
                     return Err(Ctap1StatusCode::SW_COND_USE_NOT_SATISFIED);
                 }
                 #[cfg(feature="xous")]
-                let authstr = String::from(t!("vault.u2f.authenticate", locales::LANG));
+                let authstr = String::from(t!("vault.u2f.authenticate", locales::lang()));
                 // The order is important due to side effects of checking user presence.
                 #[cfg(feature="xous")]
                 if flags == Ctap1Flags::EnforceUpAndSign
@@ -851,10 +851,10 @@ impl From<ecdsa::PubKey> for CoseKey {
 }
 
 #[cfg(feature = "ed25519")]
-impl From<ed25519_compact::PublicKey> for CoseKey {
-    fn from(pk: ed25519_compact::PublicKey) -> Self {
+impl From<ed25519_dalek::VerifyingKey> for CoseKey {
+    fn from(pk: ed25519_dalek::VerifyingKey) -> Self {
         CoseKey {
-            x_bytes: *pk,
+            x_bytes: pk.to_bytes(),
             y_bytes: [0u8; 32],
             key_type: CoseKey::OKP_KEY_TYPE,
             curve: CoseKey::ED25519_CURVE,
@@ -129,8 +129,8 @@ pub const U2F_VERSION_STRING: &str = "U2F_V2";
 // TODO(#106) change to final string when ready
 pub const FIDO2_1_VERSION_STRING: &str = "FIDO_2_1_PRE";
 
-// We currently only support one algorithm for signatures: ES256.
-// This algorithm is requested in MakeCredential and advertized in GetInfo.
+// We support two algorithms for signatures: ES256, and EdDSA with the ed25519 feature.
+// These algorithms are requested in MakeCredential and advertized in GetInfo, in order of preference.
 pub const ES256_CRED_PARAM: PublicKeyCredentialParameter = PublicKeyCredentialParameter {
     cred_type: PublicKeyCredentialType::PublicKey,
     alg: SignatureAlgorithm::Es256,
@@ -712,7 +712,7 @@ impl CtapState {
             if auth_param.is_empty() {
                 check_user_presence(env, channel, Some(
                     format!("{}\n{:x?}",
-                        t!("vault.fido.pin_uv_auth", locales::LANG),
+                        t!("vault.fido.pin_uv_auth", locales::lang()),
                         channel
                     )
                 ))?;
@@ -845,7 +845,7 @@ impl CtapState {
                     // without user interaction.
                     #[cfg(feature="xous")]
                     {
-                        let mut desc = String::from(t!("vault.fido.exclude_list", locales::LANG));
+                        let mut desc = String::from(t!("vault.fido.exclude_list", locales::lang()));
                         desc.push_str(
                             &format!("\n\nRelying Party: {}\nUser name: {}",
                                 if let Some(name) = rp.rp_name {name.to_string()} else {rp_id.to_string()},
@@ -863,7 +863,7 @@ impl CtapState {
 
         #[cfg(feature="xous")]
         {
-            let mut make_cred_desc = String::from(t!("vault.fido.make_credential", locales::LANG));
+            let mut make_cred_desc = String::from(t!("vault.fido.make_credential", locales::lang()));
             let alt_name = user.user_name.as_deref().unwrap_or("*Unspecified*");
             make_cred_desc.push_str(
                 &format!("\n\nRelying Party: {}\nUser name: {}",
@@ -989,7 +989,8 @@ impl CtapState {
         } else {
             None
         };
-        let (signature, x5c) = match attestation_id {
+        // Batch and enterprise attestation keys are always ES256, self attestation uses the credential key.
+        let (alg, signature, x5c) = match attestation_id {
             Some(id) => {
                 let Attestation {
                     private_key,
@@ -1000,16 +1001,21 @@ impl CtapState {
                     .ok_or(Ctap2StatusCode::CTAP2_ERR_VENDOR_INTERNAL_ERROR)?;
                 let attestation_key = ecdsa::SecKey::from_bytes(&private_key).unwrap();
                 (
+                    SignatureAlgorithm::Es256,
                     attestation_key
                         .sign_rfc6979::<Sha256>(&signature_data)
                         .to_asn1_der(),
                     Some(vec![certificate]),
                 )
             }
-            None => (private_key.sign_and_encode(env, &signature_data)?, None),
+            None => (
+                private_key.signature_algorithm(),
+                private_key.sign_and_encode(env, &signature_data)?,
+                None,
+            ),
         };
         let attestation_statement = PackedAttestationStatement {
-            alg: SignatureAlgorithm::Es256 as i64,
+            alg: alg as i64,
             sig: signature,
             x5c,
             ecdaa_key_id: None,
@@ -1258,7 +1264,7 @@ impl CtapState {
         if options.up {
             #[cfg(feature="xous")]
             {
-                let mut desc = String::from(t!("vault.fido.get_assertion", locales::LANG));
+                let mut desc = String::from(t!("vault.fido.get_assertion", locales::lang()));
                 desc.push_str(
                     &format!("\n\nRelying Party: {}\nClient data hash: {}",
                         rp_id,
@@ -1398,7 +1404,7 @@ impl CtapState {
         {
             check_user_presence(env, channel, Some(
                 format!("{}\n{:x?}",
-                    t!("vault.u2f.factoryreset", locales::LANG),
+                    t!("vault.u2f.factoryreset", locales::lang()),
                     channel
                 )
             ))?;
@@ -1426,7 +1432,7 @@ impl CtapState {
         {
             check_user_presence(env, channel, Some(
                 format!("{}\n{:x?}",
-                    t!("vault.u2f.authenticator_selection", locales::LANG),
+                    t!("vault.u2f.authenticator_selection", locales::lang()),
                     channel
                 )
             ))?;
@@ -1447,7 +1453,7 @@ impl CtapState {
             {
                 check_user_presence(env, channel, Some(
                     format!("{}\n{:x?}",
-                        t!("vault.u2f.vendor_configure", locales::LANG),
+                        t!("vault.u2f.vendor_configure", locales::lang()),
                         channel
                     )
                 ))?;
@@ -1816,6 +1822,41 @@ mod test {
         );
     }
 
+    #[test]
+    #[cfg(feature = "ed25519")]
+    fn test_process_make_credential_ed25519() {
+        use ed25519_dalek::Verifier;
+
+        let mut env = TestEnv::new();
+        let mut ctap_state = CtapState::new(&mut env, Instant::new(0));
+
+        let mut make_credential_params = create_minimal_make_credential_parameters();
+        make_credential_params.pub_key_cred_params = vec![EDDSA_CRED_PARAM];
+        let make_credential_response =
+            ctap_state.process_make_credential(&mut env, make_credential_params, DUMMY_CHANNEL);
+        let (auth_data, att_stmt) = match make_credential_response.unwrap() {
+            ResponseData::AuthenticatorMakeCredential(make_credential_response) => {
+                (make_credential_response.auth_data, make_credential_response.att_stmt)
+            }
+            _ => panic!("Invalid response type"),
+        };
+        // Self attestation has to name the algorithm of the credential key it is signed with.
+        assert_eq!(att_stmt.alg, SignatureAlgorithm::Eddsa as i64);
+
+        let mut iter_result = Ok(());
+        let iter = storage::iter_credentials(&mut env, &mut iter_result).unwrap();
+        let (_, stored_credential) = iter.last().unwrap();
+        iter_result.unwrap();
+        let verifying_key = match stored_credential.private_key {
+            PrivateKey::Ed25519(ed25519_key) => ed25519_key.verifying_key(),
+            _ => panic!("Invalid key type"),
+        };
+        let mut signature_data = auth_data;
+        signature_data.push(0xCD);
+        let signature = ed25519_dalek::Signature::from_slice(&att_stmt.sig).unwrap();
+        assert!(verifying_key.verify(&signature_data, &signature).is_ok());
+    }
+
     #[test]
     fn test_process_make_credential_unsupported_algorithm() {
         let mut env = TestEnv::new();
@@ -34,7 +34,7 @@ use std::vec::Vec;
 use arrayref::array_ref;
 use core::cmp;
 use core::convert::TryInto;
-use persistent_store::{fragment, StoreUpdate};
+use persistent_store::{fragment, Storage, Store, StoreUpdate};
 use ctap_crypto::rng256::Rng256;
 use cbor::cbor_array_vec;
 
@@ -184,7 +184,22 @@ pub fn store_credential(
 /// Returns `CTAP2_ERR_NO_CREDENTIALS` if the credential is not found.
 pub fn delete_credential(env: &mut impl Env, credential_id: &[u8]) -> Result<(), Ctap2StatusCode> {
     let (key, _) = find_credential_item(env, credential_id)?;
-    Ok(env.store().remove(key)?)
+    delete_credential_in(env.store(), key)
+}
+
+/// Deletes the credential at the given key of a store.
+///
+/// This is for callers that hold a store but not the `Env` owning it, like the on-device credential
+/// manager.
+///
+/// # Errors
+///
+/// Returns `CTAP2_ERR_VENDOR_INTERNAL_ERROR` if the key is not in the credential range.
+pub fn delete_credential_in<S: Storage>(store: &mut Store<S>, key: usize) -> Result<(), Ctap2StatusCode> {
+    if !key::CREDENTIALS.contains(&key) {
+        return Err(Ctap2StatusCode::CTAP2_ERR_VENDOR_INTERNAL_ERROR);
+    }
+    Ok(store.remove(key)?)
 }
 
 /// Updates a credential's user information.
@@ -228,8 +243,18 @@ pub fn remaining_credentials(env: &mut impl Env) -> Result<usize, Ctap2StatusCod
 pub fn iter_credentials<'a, E: Env>(
     env: &'a mut E,
     result: &'a mut Result<(), Ctap2StatusCode>,
-) -> Result<IterCredentials<'a, E>, Ctap2StatusCode> {
-    IterCredentials::new(env.store(), result)
+) -> Result<IterCredentials<'a, E::Storage>, Ctap2StatusCode> {
+    iter_credentials_in(env.store(), result)
+}
+
+/// Iterates through the credentials of a store.
+///
+/// Same as `iter_credentials`, for callers that hold a store but not the `Env` owning it.
+pub fn iter_credentials_in<'a, S: Storage>(
+    store: &'a Store<S>,
+    result: &'a mut Result<(), Ctap2StatusCode>,
+) -> Result<IterCredentials<'a, S>, Ctap2StatusCode> {
+    IterCredentials::new(store, result)
 }
 
 /// Returns the next creation order.
@@ -243,7 +268,12 @@ pub fn new_creation_order(env: &mut impl Env) -> Result<u64, Ctap2StatusCode> {
 
 /// Returns the global signature counter.
 pub fn global_signature_counter(env: &mut impl Env) -> Result<u32, Ctap2StatusCode> {
-    match env.store().find(key::GLOBAL_SIGNATURE_COUNTER)? {
+    global_signature_counter_in(env.store())
+}
+
+/// Returns the global signature counter of a store.
+pub fn global_signature_counter_in<S: Storage>(store: &Store<S>) -> Result<u32, Ctap2StatusCode> {
+    match store.find(key::GLOBAL_SIGNATURE_COUNTER)? {
         None => Ok(INITIAL_SIGNATURE_COUNTER),
         Some(value) if value.len() == 4 => Ok(u32::from_ne_bytes(*array_ref!(&value, 0, 4))),
         Some(_) => Err(Ctap2StatusCode::CTAP2_ERR_VENDOR_INTERNAL_ERROR),
@@ -422,6 +452,13 @@ pub fn get_large_blob_array(
     }))
 }
 
+/// Returns the length of the serialized large blobs array of a store, including its hash.
+///
+/// An empty store counts as holding the 17 byte empty array, as in `get_large_blob_array`.
+pub fn large_blob_array_len_in<S: Storage>(store: &Store<S>) -> Result<usize, Ctap2StatusCode> {
+    Ok(fragment::read(store, &key::LARGE_BLOB_SHARDS)?.map_or(17, |array| array.len()))
+}
+
 /// Sets a byte vector as the serialized large blobs array.
 pub fn commit_large_blob_array(
     env: &mut impl Env,
@@ -553,9 +590,9 @@ impl From<persistent_store::StoreError> for Ctap2StatusCode {
 }
 
 /// Iterator for credentials.
-pub struct IterCredentials<'a, E: Env> {
+pub struct IterCredentials<'a, S: Storage> {
     /// The store being iterated.
-    store: &'a persistent_store::Store<E::Storage>,
+    store: &'a persistent_store::Store<S>,
 
     /// The store iterator.
     iter: persistent_store::StoreIter<'a>,
@@ -567,10 +604,10 @@ pub struct IterCredentials<'a, E: Env> {
     result: &'a mut Result<(), Ctap2StatusCode>,
 }
 
-impl<'a, E: Env> IterCredentials<'a, E> {
+impl<'a, S: Storage> IterCredentials<'a, S> {
     /// Creates a credential iterator.
     fn new(
-        store: &'a persistent_store::Store<E::Storage>,
+        store: &'a persistent_store::Store<S>,
         result: &'a mut Result<(), Ctap2StatusCode>,
     ) -> Result<Self, Ctap2StatusCode> {
         let iter = store.iter()?;
@@ -594,7 +631,7 @@ impl<'a, E: Env> IterCredentials<'a, E> {
     }
 }
 
-impl<'a, E: Env> Iterator for IterCredentials<'a, E> {
+impl<'a, S: Storage> Iterator for IterCredentials<'a, S> {
     type Item = (usize, PublicKeyCredentialSource);
 
     fn next(&mut self) -> Option<(usize, PublicKeyCredentialSource)> {
@@ -709,6 +746,30 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_iter_delete_credential_in() {
+        let mut env = TestEnv::new();
+        for i in 0..3u32 {
+            let credential_source =
+                create_credential_source(&mut env, "example.com", i.to_ne_bytes().to_vec());
+            assert!(store_credential(&mut env, credential_source).is_ok());
+        }
+        let mut iter_result = Ok(());
+        let keys: Vec<usize> = iter_credentials_in(env.store(), &mut iter_result)
+            .unwrap()
+            .map(|(key, _)| key)
+            .collect();
+        assert!(iter_result.is_ok());
+        assert_eq!(keys.len(), 3);
+        assert!(delete_credential_in(env.store(), keys[0]).is_ok());
+        assert_eq!(count_credentials(&mut env).unwrap(), 2);
+        // keys outside the credential range are refused
+        assert_eq!(
+            delete_credential_in(env.store(), key::GLOBAL_SIGNATURE_COUNTER),
+            Err(Ctap2StatusCode::CTAP2_ERR_VENDOR_INTERNAL_ERROR)
+        );
+    }
+
     #[test]
     fn test_update_credential() {
         let mut env = TestEnv::new();
@@ -1052,6 +1113,14 @@ mod test {
         assert_eq!(Vec::<u8>::new(), restored_large_blob_array);
     }
 
+    #[test]
+    fn test_large_blob_array_len_in() {
+        let mut env = TestEnv::new();
+        assert_eq!(large_blob_array_len_in(env.store()).unwrap(), 17);
+        assert!(commit_large_blob_array(&mut env, &[0x5A; 40]).is_ok());
+        assert_eq!(large_blob_array_len_in(env.store()).unwrap(), 40);
+    }
+
     #[test]
     fn test_commit_get_large_blob_array_overwrite() {
         let mut env = TestEnv::new();
@@ -1098,6 +1167,7 @@ mod test {
             counter_value += increment;
             assert_eq!(global_signature_counter(&mut env).unwrap(), counter_value);
         }
+        assert_eq!(global_signature_counter_in(env.store()).unwrap(), counter_value);
     }
 
     #[test]
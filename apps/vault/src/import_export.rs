@@ -0,0 +1,205 @@
+//! Glue between the vault's PDDB records and the `interchange` formats, plus the framing used to move
+//! files over the USB serial port.
+//!
+//! Import: the host sends a 16-byte header -- `VAULTIMP`, the file length as a u32 LE, and a u32 LE
+//! format hint (0 = guess, 1 = KDBX, 2 = otpauth, 3 = CSV) -- then the file, zero-padded so the whole
+//! transfer is a multiple of `SERIAL_BINARY_BUFLEN`. The padding is needed because the USB stack only
+//! hands binary data to listeners in full buffers.
+//!
+//! Export: the device writes the KDBX file as ASCII armor between `-----BEGIN VAULT EXPORT vault.kdbx-----`
+//! and `-----END VAULT EXPORT <sha256 hex>-----`, 76 base64 characters per line. Log output can share
+//! the port, so the host discards lines inside the armor that aren't pure base64, and checks the hash.
+//!
+//! `tools/serial_transfer.py` implements the host side of both.
+
+use std::convert::TryInto;
+
+use interchange::{Collection, Format, OtpAlgorithm, OtpEntry, PasswordEntry};
+use locales::t;
+use sha2::{Digest, Sha256};
+
+use crate::storage::{PasswordRecord, StorageContent, TotpRecord};
+use crate::totp::{OtpScheme, TotpAlgorithm};
+
+pub(crate) const FRAME_MAGIC: &[u8; 8] = b"VAULTIMP";
+pub(crate) const FRAME_HEADER_LEN: usize = 16;
+/// Large enough for a few thousand entries, small enough to leave the vault's heap usable.
+pub(crate) const MAX_IMPORT_LEN: usize = 2 * 1024 * 1024;
+/// Argon2 memory the vault is willing to spend opening a KDBX; it is also what exports are written with.
+pub(crate) const KDF_MEMORY_LIMIT: u64 = 4 * 1024 * 1024;
+
+const ARMOR_LINE_LEN: usize = 76;
+
+/// Checks the import header, returning the file length and format hint.
+pub(crate) fn parse_frame_header(header: &[u8]) -> Option<(usize, Option<Format>)> {
+    if header.len() < FRAME_HEADER_LEN || &header[..8] != FRAME_MAGIC {
+        return None;
+    }
+    let len = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+    let format = match u32::from_le_bytes(header[12..16].try_into().unwrap()) {
+        0 => None,
+        1 => Some(Format::Kdbx),
+        2 => Some(Format::OtpAuth),
+        3 => Some(Format::Csv),
+        _ => return None,
+    };
+    if len == 0 || len > MAX_IMPORT_LEN { None } else { Some((len, format)) }
+}
+
+/// Records are stored as `tag:value` lines, so every field has to fit on one line.
+fn single_line(s: &str) -> String {
+    s.split(|c| c == '\n' || c == '\r')
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+fn to_totp_algorithm(algorithm: OtpAlgorithm) -> TotpAlgorithm {
+    match algorithm {
+        OtpAlgorithm::Sha1 => TotpAlgorithm::HmacSha1,
+        OtpAlgorithm::Sha256 => TotpAlgorithm::HmacSha256,
+        OtpAlgorithm::Sha512 => TotpAlgorithm::HmacSha512,
+    }
+}
+
+/// Converts an imported collection into records ready for `storage::Manager::new_records()`.
+pub(crate) fn to_records(collection: &Collection) -> Vec<Box<dyn StorageContent>> {
+    let mut records: Vec<Box<dyn StorageContent>> = Vec::new();
+    for entry in collection.passwords.iter() {
+        // there is no URL field on the device; keep it with the notes so it isn't lost
+        let notes = match (entry.url.is_empty(), entry.notes.is_empty()) {
+            (true, _) => single_line(&entry.notes),
+            (false, true) => single_line(&entry.url),
+            (false, false) => format!("{} / {}", single_line(&entry.url), single_line(&entry.notes)),
+        };
+        let description = if entry.title.is_empty() { &entry.username } else { &entry.title };
+        records.push(Box::new(PasswordRecord {
+            version: crate::storage::VAULT_PASSWORD_REC_VERSION,
+            description: single_line(description),
+            username: single_line(&entry.username),
+            password: single_line(&entry.password),
+            notes,
+            ctime: 0,
+            atime: 0,
+            count: 0,
+        }));
+    }
+    for entry in collection.otp.iter() {
+        records.push(Box::new(TotpRecord {
+            version: crate::storage::VAULT_TOTP_REC_VERSION,
+            secret: entry.secret.clone(),
+            name: single_line(&entry.name()),
+            algorithm: to_totp_algorithm(entry.algorithm),
+            notes: t!("vault.notes", locales::lang()).to_string(),
+            digits: entry.digits,
+            // HOTP records keep the counter in the timestep field
+            timestep: if entry.hotp { entry.counter } else { entry.period },
+            ctime: 0, // filled in by storage::new_records()
+            is_hotp: entry.hotp,
+            scheme: OtpScheme::Rfc,
+            pin: String::new(),
+        }));
+    }
+    records
+}
+
+/// Collects the vault's records for export.
+pub(crate) fn from_records(passwords: &[PasswordRecord], totp: &[TotpRecord]) -> Collection {
+    let mut collection = Collection::default();
+    for record in passwords {
+        collection.passwords.push(PasswordEntry {
+            title: record.description.clone(),
+            username: record.username.clone(),
+            password: record.password.clone(),
+            url: String::new(),
+            notes: record.notes.clone(),
+        });
+    }
+    for record in totp {
+        if record.scheme != OtpScheme::Rfc {
+            // KDBX and otpauth only describe RFC 6238/4226 codes
+            collection.skipped.push(record.name.clone());
+            continue;
+        }
+        let mut entry = OtpEntry::default();
+        entry.set_name(&record.name);
+        entry.secret = record.secret.clone();
+        entry.algorithm = match record.algorithm {
+            TotpAlgorithm::HmacSha256 => OtpAlgorithm::Sha256,
+            TotpAlgorithm::HmacSha512 => OtpAlgorithm::Sha512,
+            _ => OtpAlgorithm::Sha1,
+        };
+        entry.digits = record.digits;
+        entry.hotp = record.is_hotp;
+        if record.is_hotp {
+            entry.counter = record.timestep;
+        } else {
+            entry.period = record.timestep;
+        }
+        collection.otp.push(entry);
+    }
+    collection
+}
+
+/// Wraps an exported file in ASCII armor, one line per entry, ready to be written to serial.
+pub(crate) fn armor(name: &str, data: &[u8]) -> (Vec<String>, String) {
+    use base64::Engine;
+    let digest: String = Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect();
+    let encoded = base64::engine::general_purpose::STANDARD.encode(data);
+    let mut lines = vec![format!("-----BEGIN VAULT EXPORT {}-----", name)];
+    // base64 output is ASCII, so slicing on byte boundaries is safe
+    lines.extend(encoded.as_bytes().chunks(ARMOR_LINE_LEN).map(|l| String::from_utf8_lossy(l).into_owned()));
+    lines.push(format!("-----END VAULT EXPORT {}-----", digest));
+    (lines, digest)
+}
+
+/// What the serial receiver thread reports back to the action thread.
+pub(crate) enum Received {
+    Progress(usize, usize),
+    File(Vec<u8>, Option<Format>),
+    BadFrame,
+    Cancelled,
+}
+
+/// Runs on its own thread: collects one import frame from serial, reporting progress along the way.
+/// Clearing the serial input hooks from elsewhere unblocks it and counts as a cancel.
+pub(crate) fn receive_frame(
+    tx: std::sync::mpsc::Sender<Received>,
+    cancelled: std::sync::Arc<std::sync::atomic::AtomicBool>,
+) {
+    let usb = usb_device_xous::UsbHid::new();
+    let mut data = Vec::new();
+    let mut expected = None;
+    loop {
+        if cancelled.load(std::sync::atomic::Ordering::SeqCst) {
+            tx.send(Received::Cancelled).ok();
+            return;
+        }
+        let chunk = usb.serial_wait_binary();
+        if chunk.is_empty() {
+            tx.send(Received::Cancelled).ok();
+            return;
+        }
+        data.extend_from_slice(&chunk);
+        if expected.is_none() && data.len() >= FRAME_HEADER_LEN {
+            match parse_frame_header(&data) {
+                Some(header) => expected = Some(header),
+                None => {
+                    tx.send(Received::BadFrame).ok();
+                    return;
+                }
+            }
+        }
+        if let Some((len, format)) = expected {
+            let received = data.len() - FRAME_HEADER_LEN;
+            if received >= len {
+                data.drain(..FRAME_HEADER_LEN);
+                data.truncate(len);
+                tx.send(Received::File(data, format)).ok();
+                return;
+            }
+            tx.send(Received::Progress(received, len)).ok();
+        }
+    }
+}
@@ -2,10 +2,15 @@
 #![cfg_attr(target_os = "none", no_main)]
 
 mod actions;
+mod import_export;
 mod itemcache;
 mod migration_v1;
+mod passgen;
 mod prereqs;
+#[cfg(feature = "ed25519")]
+mod ssh_agent;
 mod storage;
+mod strength;
 mod submenu;
 mod totp;
 mod ux;
@@ -71,6 +76,8 @@ UI concept:
   Tap once to switch to the sub-function.
   Once on the sub-function, tap the corresponding F-key again to raise
   the menu for that sub-function.
+  While there is text in the list filter, the F-key slots instead offer
+  completions of the word being typed, and F1-F4 pick them.
 
   List filter:
     - Any regular keys hit here appear in the search input. It automatically
@@ -124,10 +131,12 @@ fn main() -> ! {
     let allow_host = Arc::new(AtomicBool::new(false));
     let allow_totp_rendering = Arc::new(AtomicBool::new(true));
     // Protects access to the openSK PDDB entries from simultaneous readout on the UX while OpenSK is updating
-    // it
+    // it. The value is bumped whenever the UX removes entries, so OpenSK knows to reload its key list.
     let opensk_mutex = Arc::new(Mutex::new(0));
     // storage for lefty mode
     let lefty_mode = Arc::new(AtomicBool::new(false));
+    // set while the SSH agent holds the USB serial port, so foregrounding the vault leaves the core alone
+    let ssh_agent_running = Arc::new(AtomicBool::new(false));
 
     // spawn the actions server. This is responsible for grooming the UX elements. It
     // has to be in its own thread because it uses blocking modal calls that would cause
@@ -141,9 +150,16 @@ fn main() -> ! {
         let item_lists = item_lists.clone();
         let action_active = action_active.clone();
         let opensk_mutex = opensk_mutex.clone();
+        let ssh_agent_running = ssh_agent_running.clone();
         move || {
-            let mut manager =
-                crate::actions::ActionManager::new(main_conn, mode, item_lists, action_active, opensk_mutex);
+            let mut manager = crate::actions::ActionManager::new(
+                main_conn,
+                mode,
+                item_lists,
+                action_active,
+                opensk_mutex,
+                ssh_agent_running,
+            );
             loop {
                 let msg = xous::receive_message(sid).unwrap();
                 let opcode: Option<ActionOp> = FromPrimitive::from_usize(msg.body.id());
@@ -185,6 +201,46 @@ fn main() -> ! {
                         manager.retrieve_db();
                         manager.deactivate();
                     }
+                    Some(ActionOp::MenuImport) => {
+                        manager.activate();
+                        manager.menu_import();
+                        manager.item_lists.lock().unwrap().clear(VaultMode::Password); // clear the cached item list for passwords
+                        manager.retrieve_db();
+                        manager.deactivate();
+                    }
+                    Some(ActionOp::MenuScanQr) => {
+                        manager.activate();
+                        manager.menu_scan_qr();
+                        manager.item_lists.lock().unwrap().clear(VaultMode::Password); // clear the cached item list for passwords
+                        manager.retrieve_db();
+                        manager.deactivate();
+                    }
+                    Some(ActionOp::MenuExport) => {
+                        manager.activate();
+                        manager.menu_export();
+                        manager.deactivate();
+                    }
+                    Some(ActionOp::MenuManagePasskeys) => {
+                        manager.activate();
+                        manager.manage_passkeys();
+                        manager.item_lists.lock().unwrap().clear(VaultMode::Fido); // deleted passkeys have to go from the list
+                        manager.retrieve_db();
+                        manager.deactivate();
+                    }
+                    #[cfg(feature = "ed25519")]
+                    Some(ActionOp::MenuSshKeys) => {
+                        manager.activate();
+                        manager.manage_ssh_keys();
+                        manager.deactivate();
+                    }
+                    Some(ActionOp::MenuHotpResyncStage2) => {
+                        let buffer =
+                            unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                        let entry = buffer.to_original::<SelectedEntry, _>().unwrap();
+                        manager.activate();
+                        manager.menu_hotp_resync(entry); // this is responsible for updating the item cache
+                        manager.deactivate();
+                    }
                     Some(ActionOp::MenuClose) => {
                         // dummy activate/de-activate cycle because we have to trigger a redraw of the
                         // underlying UX
@@ -307,7 +363,7 @@ fn main() -> ! {
                     let modals = modals::Modals::new(&xns).unwrap();
                     modals
                         .show_notification(
-                            &format!("{}\n{:?}", t!("vault.migration_error", locales::LANG), e),
+                            &format!("{}\n{:?}", t!("vault.migration_error", locales::lang()), e),
                             None,
                         )
                         .ok();
@@ -318,11 +374,17 @@ fn main() -> ! {
             // only run the main loop if the SoC is compatible
             if env.is_soc_compatible() {
                 let mut ctap = vault::Ctap::new(env, Instant::now());
+                let mut store_generation = 0;
                 loop {
                     match ctap.env().main_hid_connection().u2f_wait_incoming() {
                         Ok(msg) => {
                             ctap.update_timeouts(Instant::now());
                             let mutex = opensk_mutex.lock().unwrap();
+                            if *mutex != store_generation {
+                                // the UX deleted credentials behind our back
+                                ctap.env().store().reload();
+                                store_generation = *mutex;
+                            }
                             log::trace!("Received U2F packet");
                             let typed_reply =
                                 ctap.process_hid_packet(&msg.packet, Transport::MainHid, Instant::now());
@@ -593,6 +655,9 @@ fn main() -> ! {
                     vaultux.redraw().expect("Vault couldn't redraw");
                 }
             }
+            Some(VaultOp::RestoreHid) => {
+                vaultux.ensure_hid();
+            }
             Some(VaultOp::ReloadDbAndFullRedraw) => {
                 send_message(
                     actions_conn,
@@ -626,8 +691,11 @@ fn main() -> ! {
                         allow_redraw = false;
                     }
                     gam::FocusState::Foreground => {
-                        // HID is always selected if the vault is foregrounded
-                        vaultux.ensure_hid();
+                        // HID is always selected if the vault is foregrounded, unless the SSH agent is
+                        // serving
+                        if !ssh_agent_running.load(Ordering::SeqCst) {
+                            vaultux.ensure_hid();
+                        }
                         allow_redraw = true;
                         if first_time {
                             // Populate the initial fields, just the first time
@@ -653,7 +721,7 @@ fn main() -> ! {
                     modals.add_list_item(item).expect("couldn't build radio item list");
                 }
                 allow_totp_rendering.store(false, Ordering::SeqCst);
-                match modals.get_radiobutton(t!("vault.select_font", locales::LANG)) {
+                match modals.get_radiobutton(t!("vault.select_font", locales::lang())) {
                     Ok(style) => {
                         vaultux.set_glyph_style(name_to_style(&style).unwrap_or(DEFAULT_FONT));
                     }
@@ -664,13 +732,13 @@ fn main() -> ! {
             }
             Some(VaultOp::MenuAutotype) => msg_scalar_unpack!(msg, select_username, _, _, _, {
                 allow_totp_rendering.store(false, Ordering::SeqCst);
-                modals.dynamic_notification(Some(t!("vault.autotyping", locales::LANG)), None).ok();
+                modals.dynamic_notification(Some(t!("vault.autotyping", locales::lang())), None).ok();
                 match vaultux.autotype(select_username == 1) {
                     Err(xous::Error::UseBeforeInit) => {
                         // USB not plugged in
                         modals
                             .dynamic_notification_update(
-                                Some(t!("vault.error.usb_error", locales::LANG)),
+                                Some(t!("vault.error.usb_error", locales::lang())),
                                 None,
                             )
                             .ok();
@@ -680,7 +748,7 @@ fn main() -> ! {
                         // deserialzation error
                         modals
                             .dynamic_notification_update(
-                                Some(t!("vault.error.record_error", locales::LANG)),
+                                Some(t!("vault.error.record_error", locales::lang())),
                                 None,
                             )
                             .ok();
@@ -690,7 +758,7 @@ fn main() -> ! {
                         // key or dictionary not found
                         modals
                             .dynamic_notification_update(
-                                Some(t!("vault.error.not_found", locales::LANG)),
+                                Some(t!("vault.error.not_found", locales::lang())),
                                 None,
                             )
                             .ok();
@@ -700,7 +768,7 @@ fn main() -> ! {
                         // nothing was selected
                         modals
                             .dynamic_notification_update(
-                                Some(t!("vault.error.nothing_selected", locales::LANG)),
+                                Some(t!("vault.error.nothing_selected", locales::lang())),
                                 None,
                             )
                             .ok();
@@ -710,7 +778,7 @@ fn main() -> ! {
                         // trouble updating the key
                         modals
                             .dynamic_notification_update(
-                                Some(t!("vault.error.update_error", locales::LANG)),
+                                Some(t!("vault.error.update_error", locales::lang())),
                                 None,
                             )
                             .ok();
@@ -723,7 +791,7 @@ fn main() -> ! {
                             .dynamic_notification(
                                 Some(&format!(
                                     "{}\n{:?}",
-                                    t!("vault.error.internal_error", locales::LANG),
+                                    t!("vault.error.internal_error", locales::lang()),
                                     e
                                 )),
                                 None,
@@ -751,7 +819,7 @@ fn main() -> ! {
                 } else {
                     allow_totp_rendering.store(false, Ordering::SeqCst);
                     // this will block redraws
-                    modals.show_notification(t!("vault.error.nothing_selected", locales::LANG), None).ok();
+                    modals.show_notification(t!("vault.error.nothing_selected", locales::lang()), None).ok();
                     allow_totp_rendering.store(true, Ordering::SeqCst);
                 }
             }
@@ -766,22 +834,36 @@ fn main() -> ! {
                 } else {
                     // this will block redraws
                     allow_totp_rendering.store(false, Ordering::SeqCst);
-                    modals.show_notification(t!("vault.error.nothing_selected", locales::LANG), None).ok();
+                    modals.show_notification(t!("vault.error.nothing_selected", locales::lang()), None).ok();
+                    allow_totp_rendering.store(true, Ordering::SeqCst);
+                }
+            }
+            Some(VaultOp::MenuHotpResyncStage1) => {
+                // stage 1 happens here because the filtered list and selection entry are in the responsive UX
+                // section.
+                if let Some(entry) = vaultux.selected_entry() {
+                    let buf = Buffer::into_buf(entry).expect("IPC error");
+                    buf.send(actions_conn, ActionOp::MenuHotpResyncStage2.to_u32().unwrap())
+                        .expect("messaging error");
+                } else {
+                    // this will block redraws
+                    allow_totp_rendering.store(false, Ordering::SeqCst);
+                    modals.show_notification(t!("vault.error.nothing_selected", locales::lang()), None).ok();
                     allow_totp_rendering.store(true, Ordering::SeqCst);
                 }
             }
             Some(VaultOp::MenuReadoutMode) => {
-                modals.dynamic_notification(Some(t!("vault.readout_switchover", locales::LANG)), None).ok();
+                modals.dynamic_notification(Some(t!("vault.readout_switchover", locales::lang())), None).ok();
                 vaultux.readout_mode(true);
                 modals.dynamic_notification_close().ok();
 
                 allow_host.store(true, Ordering::SeqCst);
                 allow_totp_rendering.store(false, Ordering::SeqCst);
-                modals.show_notification(t!("vault.readout_active", locales::LANG), None).ok();
+                modals.show_notification(t!("vault.readout_active", locales::lang()), None).ok();
                 allow_host.store(false, Ordering::SeqCst);
                 allow_totp_rendering.store(true, Ordering::SeqCst);
 
-                modals.dynamic_notification(Some(t!("vault.readout_switchover", locales::LANG)), None).ok();
+                modals.dynamic_notification(Some(t!("vault.readout_switchover", locales::lang())), None).ok();
                 vaultux.readout_mode(false);
                 modals.dynamic_notification_close().ok();
 
@@ -800,12 +882,12 @@ fn main() -> ! {
                     rate
                 };
                 let raw = modals
-                    .alert_builder(t!("prefs.autotype_rate_in_ms", locales::LANG))
+                    .alert_builder(t!("prefs.autotype_rate_in_ms", locales::lang()))
                     .field(
                         Some(cv.to_string()),
                         Some(|tf| match tf.as_str().parse::<usize>() {
                             Ok(_) => None,
-                            Err(_) => Some(String::from(t!("prefs.autobacklight_err", locales::LANG))),
+                            Err(_) => Some(String::from(t!("prefs.autobacklight_err", locales::lang()))),
                         }),
                     )
                     .build()
@@ -817,13 +899,15 @@ fn main() -> ! {
             Some(VaultOp::MenuLeftyMode) => {
                 let cv = prefs.lefty_mode_or_default().unwrap();
 
-                modals.add_list(vec![t!("prefs.yes", locales::LANG), t!("prefs.no", locales::LANG)]).unwrap();
+                modals
+                    .add_list(vec![t!("prefs.yes", locales::lang()), t!("prefs.no", locales::lang())])
+                    .unwrap();
                 allow_totp_rendering.store(false, Ordering::SeqCst);
                 let mode = yes_no_to_bool(
                     modals
                         .get_radiobutton(&format!(
                             "{} {}",
-                            t!("prefs.current_setting", locales::LANG),
+                            t!("prefs.current_setting", locales::lang()),
                             bool_to_yes_no(cv)
                         ))
                         .unwrap()
@@ -854,14 +938,14 @@ fn main() -> ! {
 
 fn bool_to_yes_no(val: bool) -> String {
     match val {
-        true => t!("prefs.yes", locales::LANG).to_owned(),
-        false => t!("prefs.no", locales::LANG).to_owned(),
+        true => t!("prefs.yes", locales::lang()).to_owned(),
+        false => t!("prefs.no", locales::lang()).to_owned(),
     }
 }
 fn yes_no_to_bool(val: &str) -> bool {
-    if val == t!("prefs.yes", locales::LANG) {
+    if val == t!("prefs.yes", locales::lang()) {
         true
-    } else if val == t!("prefs.no", locales::LANG) {
+    } else if val == t!("prefs.no", locales::lang()) {
         false
     } else {
         unreachable!("cannot go here!");
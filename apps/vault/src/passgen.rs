@@ -0,0 +1,428 @@
+//! Password generator profiles.
+//!
+//! Every random choice is drawn from the `RngCore` handed in by the caller, which on the device is the
+//! `trng` service. Word passwords use the BIP39 English list; the GAM owns that list, so this module
+//! only packs random indices into bytes and lets the caller turn them into words.
+
+use core::convert::TryFrom;
+
+use rand_core::RngCore;
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const NUMBERS: &str = "0123456789";
+const SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+/// Characters that are hard to tell apart on the display, left out whenever letters are in use
+const SIMILAR: &str = "iIlL1oO0|`'\"";
+const CONSONANTS: &str = "bdfghjkmnprstvz";
+const VOWELS: &str = "aeiou";
+
+pub const WORD_SEPARATOR: char = '-';
+/// Size of the BIP39 word list
+const BIP39_WORDS: u32 = 2048;
+/// 32 bytes of entropy give 24 words; the last one carries checksum bits, so only 23 are usable.
+const WORDS_PER_BLOCK: usize = 23;
+
+pub const MAX_LENGTH: usize = 128;
+pub const MAX_WORDS: usize = 24;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CharClasses {
+    pub lower: bool,
+    pub upper: bool,
+    pub numbers: bool,
+    pub symbols: bool,
+}
+impl CharClasses {
+    pub const ALL: CharClasses = CharClasses { lower: true, upper: true, numbers: true, symbols: true };
+
+    pub fn any(&self) -> bool { self.lower || self.upper || self.numbers || self.symbols }
+
+    /// The character sets that are switched on, with look-alike characters already removed.
+    fn sets(&self) -> Vec<Vec<char>> {
+        let exclude_similar = self.lower || self.upper;
+        let mut sets = Vec::new();
+        for (enabled, set) in [
+            (self.lower, LOWERCASE),
+            (self.upper, UPPERCASE),
+            (self.numbers, NUMBERS),
+            (self.symbols, SYMBOLS),
+        ] {
+            if enabled {
+                sets.push(set.chars().filter(|c| !(exclude_similar && SIMILAR.contains(*c))).collect());
+            }
+        }
+        sets
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Style {
+    /// Random characters drawn from the selected classes, with every class used at least once
+    Characters(CharClasses),
+    /// Alternating consonants and vowels, which are easier to read out and type on a small keyboard
+    Pronounceable,
+    /// BIP39 words joined with `WORD_SEPARATOR`
+    Words,
+}
+
+/// A generator setting. `length` counts characters, or words for `Style::Words`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub style: Style,
+    pub length: usize,
+}
+impl Default for Profile {
+    fn default() -> Self { Preset::Strong.profile() }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Preset {
+    Strong,
+    Alphanumeric,
+    Pin,
+    Pronounceable,
+    Words,
+}
+impl Preset {
+    pub const ALL: [Preset; 5] =
+        [Preset::Strong, Preset::Alphanumeric, Preset::Pin, Preset::Pronounceable, Preset::Words];
+
+    pub fn profile(&self) -> Profile {
+        match self {
+            Preset::Strong => Profile { style: Style::Characters(CharClasses::ALL), length: 20 },
+            Preset::Alphanumeric => Profile {
+                style: Style::Characters(CharClasses { symbols: false, ..CharClasses::ALL }),
+                length: 20,
+            },
+            Preset::Pin => Profile {
+                style: Style::Characters(CharClasses {
+                    lower: false,
+                    upper: false,
+                    numbers: true,
+                    symbols: false,
+                }),
+                length: 6,
+            },
+            Preset::Pronounceable => Profile { style: Style::Pronounceable, length: 24 },
+            Preset::Words => Profile { style: Style::Words, length: 6 },
+        }
+    }
+}
+
+impl Profile {
+    /// Bits of entropy in a password made with this profile. This is what an attacker who knows the
+    /// profile is up against, and is exact apart from the small loss from requiring every class.
+    pub fn entropy_bits(&self) -> f64 {
+        match self.style {
+            Style::Characters(classes) => {
+                let alphabet: usize = classes.sets().iter().map(|s| s.len()).sum();
+                self.length as f64 * (alphabet.max(1) as f64).log2()
+            }
+            Style::Pronounceable => {
+                let consonants = (self.length + 1) / 2;
+                let vowels = self.length / 2;
+                consonants as f64 * (CONSONANTS.len() as f64).log2()
+                    + vowels as f64 * (VOWELS.len() as f64).log2()
+            }
+            Style::Words => self.length as f64 * (BIP39_WORDS as f64).log2(),
+        }
+    }
+
+    /// Makes a password. `bytes_to_words` turns 32 bytes of entropy into 24 BIP39 words, and is only
+    /// called for `Style::Words`. Returns `None` if the profile can't be satisfied or the lookup fails.
+    pub fn generate<R: RngCore>(
+        &self,
+        rng: &mut R,
+        bytes_to_words: &dyn Fn(&Vec<u8>) -> Option<Vec<String>>,
+    ) -> Option<String> {
+        if self.length == 0 {
+            return None;
+        }
+        match self.style {
+            Style::Characters(classes) => {
+                let sets = classes.sets();
+                if sets.is_empty() || self.length > MAX_LENGTH {
+                    return None;
+                }
+                let alphabet: Vec<char> = sets.iter().flatten().copied().collect();
+                loop {
+                    let candidate: Vec<char> =
+                        (0..self.length).map(|_| alphabet[uniform(rng, alphabet.len())]).collect();
+                    // only insist on every class when there is room for all of them
+                    if self.length < sets.len()
+                        || sets.iter().all(|s| candidate.iter().any(|c| s.contains(c)))
+                    {
+                        return Some(candidate.into_iter().collect());
+                    }
+                }
+            }
+            Style::Pronounceable => {
+                if self.length > MAX_LENGTH {
+                    return None;
+                }
+                let consonants: Vec<char> = CONSONANTS.chars().collect();
+                let vowels: Vec<char> = VOWELS.chars().collect();
+                Some(
+                    (0..self.length)
+                        .map(|i| {
+                            let set = if i % 2 == 0 { &consonants } else { &vowels };
+                            set[uniform(rng, set.len())]
+                        })
+                        .collect(),
+                )
+            }
+            Style::Words => {
+                if self.length > MAX_WORDS {
+                    return None;
+                }
+                let mut words = Vec::new();
+                while words.len() < self.length {
+                    let indices: Vec<u32> =
+                        (0..WORDS_PER_BLOCK).map(|_| uniform(rng, BIP39_WORDS as usize) as u32).collect();
+                    let block = bytes_to_words(&pack_word_indices(&indices))?;
+                    if block.len() < WORDS_PER_BLOCK {
+                        return None;
+                    }
+                    words.extend(block.into_iter().take(WORDS_PER_BLOCK));
+                }
+                words.truncate(self.length);
+                Some(words.join(&WORD_SEPARATOR.to_string()))
+            }
+        }
+    }
+}
+
+/// Picks a number in `0..n` with no modulo bias, by throwing away draws from the ragged top of the range.
+fn uniform<R: RngCore>(rng: &mut R, n: usize) -> usize {
+    let n = n as u32;
+    let limit = u32::MAX - (u32::MAX % n);
+    loop {
+        let r = rng.next_u32();
+        if r < limit {
+            return (r % n) as usize;
+        }
+    }
+}
+
+/// Packs 11-bit word indices MSB-first into 32 bytes, so that BIP39 encoding hands back exactly those
+/// words. The bits left over at the end become part of the 24th word, which callers discard.
+fn pack_word_indices(indices: &[u32]) -> Vec<u8> {
+    let mut bytes = vec![0u8; 32];
+    let mut bit = 0;
+    for &index in indices {
+        for b in (0..11).rev() {
+            if bit / 8 < bytes.len() && (index >> b) & 1 == 1 {
+                bytes[bit / 8] |= 0x80 >> (bit % 8);
+            }
+            bit += 1;
+        }
+    }
+    bytes
+}
+
+/// Stored form of a profile, used to remember the last choice for each site:
+/// `chars:<len>:<classes>`, `pron:<len>` or `words:<count>`, where classes is any of `luns`.
+impl std::fmt::Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.style {
+            Style::Characters(c) => {
+                write!(f, "chars:{}:", self.length)?;
+                for (enabled, tag) in [(c.lower, 'l'), (c.upper, 'u'), (c.numbers, 'n'), (c.symbols, 's')] {
+                    if enabled {
+                        write!(f, "{}", tag)?;
+                    }
+                }
+                Ok(())
+            }
+            Style::Pronounceable => write!(f, "pron:{}", self.length),
+            Style::Words => write!(f, "words:{}", self.length),
+        }
+    }
+}
+impl TryFrom<&str> for Profile {
+    type Error = ();
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let mut fields = s.trim().split(':');
+        let kind = fields.next().ok_or(())?;
+        let length = fields.next().ok_or(())?.parse::<usize>().or(Err(()))?;
+        let style = match kind {
+            "chars" => {
+                let tags = fields.next().ok_or(())?;
+                if !tags.chars().all(|c| "luns".contains(c)) {
+                    return Err(());
+                }
+                let classes = CharClasses {
+                    lower: tags.contains('l'),
+                    upper: tags.contains('u'),
+                    numbers: tags.contains('n'),
+                    symbols: tags.contains('s'),
+                };
+                if !classes.any() || length > MAX_LENGTH {
+                    return Err(());
+                }
+                Style::Characters(classes)
+            }
+            "pron" if length <= MAX_LENGTH => Style::Pronounceable,
+            "words" if length <= MAX_WORDS => Style::Words,
+            _ => return Err(()),
+        };
+        if length == 0 || fields.next().is_some() {
+            return Err(());
+        }
+        Ok(Profile { style, length })
+    }
+}
+
+/// Reduces an entry description to the site it names, so `https://www.Example.com/login` and
+/// `example.com` share a remembered profile. The result fits in a PDDB key name.
+pub fn site_key(description: &str) -> String {
+    let mut site = description.trim().to_lowercase();
+    for prefix in ["https://", "http://"] {
+        if let Some(rest) = site.strip_prefix(prefix) {
+            site = rest.to_string();
+        }
+    }
+    if let Some(rest) = site.strip_prefix("www.") {
+        site = rest.to_string();
+    }
+    if let Some(end) = site.find('/') {
+        site.truncate(end);
+    }
+    let mut end = site.len().min(64);
+    while !site.is_char_boundary(end) {
+        end -= 1;
+    }
+    site.truncate(end);
+    site
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// xorshift, so the tests don't need a TRNG
+    struct TestRng(u64);
+    impl RngCore for TestRng {
+        fn next_u32(&mut self) -> u32 { self.next_u64() as u32 }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) { rand_core::impls::fill_bytes_via_next(self, dest) }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            Ok(self.fill_bytes(dest))
+        }
+    }
+
+    fn no_words(_: &Vec<u8>) -> Option<Vec<String>> { None }
+
+    /// stands in for the GAM: unpacks the 11-bit groups and names each word after its index
+    fn fake_words(bytes: &Vec<u8>) -> Option<Vec<String>> {
+        let mut words = Vec::new();
+        for w in 0..24 {
+            let mut index = 0u32;
+            for b in 0..11 {
+                let bit = w * 11 + b;
+                let set = bit < 256 && bytes[bit / 8] & (0x80 >> (bit % 8)) != 0;
+                index = (index << 1) | set as u32;
+            }
+            words.push(format!("w{}", index));
+        }
+        Some(words)
+    }
+
+    #[test]
+    fn test_characters_use_every_class() {
+        let mut rng = TestRng(0x1234_5678_9abc_def1);
+        let profile = Preset::Strong.profile();
+        for _ in 0..50 {
+            let pw = profile.generate(&mut rng, &no_words).unwrap();
+            assert_eq!(pw.chars().count(), 20);
+            assert!(pw.chars().any(|c| c.is_ascii_lowercase()));
+            assert!(pw.chars().any(|c| c.is_ascii_uppercase()));
+            assert!(pw.chars().any(|c| c.is_ascii_digit()));
+            assert!(pw.chars().any(|c| SYMBOLS.contains(c)));
+            assert!(!pw.chars().any(|c| SIMILAR.contains(c)));
+        }
+    }
+
+    #[test]
+    fn test_pin_keeps_all_digits() {
+        let mut rng = TestRng(42);
+        let pin = Preset::Pin.profile().generate(&mut rng, &no_words).unwrap();
+        assert_eq!(pin.len(), 6);
+        assert!(pin.chars().all(|c| c.is_ascii_digit()));
+        // with no letters there is nothing to confuse 0 and 1 with
+        let profile = Preset::Pin.profile();
+        assert!((profile.entropy_bits() - 6.0 * 10f64.log2()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_pronounceable() {
+        let mut rng = TestRng(7);
+        let pw = Preset::Pronounceable.profile().generate(&mut rng, &no_words).unwrap();
+        for (i, c) in pw.chars().enumerate() {
+            if i % 2 == 0 {
+                assert!(CONSONANTS.contains(c));
+            } else {
+                assert!(VOWELS.contains(c));
+            }
+        }
+    }
+
+    #[test]
+    fn test_words_round_trip_indices() {
+        let indices: Vec<u32> = (0..23).map(|i| (i * 89 + 5) % 2048).collect();
+        let words = fake_words(&pack_word_indices(&indices)).unwrap();
+        for (i, index) in indices.iter().enumerate() {
+            assert_eq!(words[i], format!("w{}", index));
+        }
+
+        let mut rng = TestRng(99);
+        let profile = Profile { style: Style::Words, length: 24 };
+        let pw = profile.generate(&mut rng, &fake_words).unwrap();
+        assert_eq!(pw.split(WORD_SEPARATOR).count(), 24);
+        assert_eq!(profile.entropy_bits(), 24.0 * 11.0);
+        assert!(profile.generate(&mut rng, &no_words).is_none());
+    }
+
+    #[test]
+    fn test_uniform_has_no_bias() {
+        let mut rng = TestRng(3);
+        let mut counts = [0u32; 3];
+        for _ in 0..30_000 {
+            counts[uniform(&mut rng, 3)] += 1;
+        }
+        for count in counts {
+            assert!(count > 9_500 && count < 10_500);
+        }
+    }
+
+    #[test]
+    fn test_profile_storage_format() {
+        for preset in Preset::ALL {
+            let profile = preset.profile();
+            assert_eq!(Profile::try_from(profile.to_string().as_str()), Ok(profile));
+        }
+        assert_eq!(Preset::Alphanumeric.profile().to_string(), "chars:20:lun");
+        assert!(Profile::try_from("chars:20:").is_err());
+        assert!(Profile::try_from("chars:20:x").is_err());
+        assert!(Profile::try_from("words:0").is_err());
+        assert!(Profile::try_from("words:99").is_err());
+        assert!(Profile::try_from("pron:12:extra").is_err());
+    }
+
+    #[test]
+    fn test_site_key() {
+        assert_eq!(site_key("https://www.Example.com/login"), "example.com");
+        assert_eq!(site_key("  Bank "), "bank");
+        assert!(site_key(&"é".repeat(100)).len() <= 64);
+    }
+}
@@ -0,0 +1,383 @@
+//! An ssh-agent served over the USB serial port, signing with Ed25519 keys kept in the vault.
+//!
+//! Requests: the host sends `SSHAGENT`, the message length as a u32 LE, a u32 LE sequence number, then
+//! one ssh-agent message (draft-miller-ssh-agent) without its own length prefix, zero-padded so the
+//! whole transfer is a multiple of `SERIAL_BINARY_BUFLEN`. This is the same constraint as imports: the
+//! USB stack only hands binary data to listeners in full buffers.
+//!
+//! Replies: one line, `SSHAGENT <seq> <base64 message>`. Log output can share the port, so the host
+//! ignores lines without the tag or with a stale sequence number.
+//!
+//! Only listing keys and signing are served. Keys are made on the device and never leave it, and every
+//! signature needs a yes on the device. `tools/ssh_agent_bridge.py` is the host side.
+
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use base64::Engine;
+use ed25519_dalek::{Signer, SigningKey};
+use locales::t;
+use sha2::{Digest, Sha256};
+use usb_device_xous::SERIAL_BINARY_BUFLEN;
+
+use crate::storage::{self, ContentKind, SshKeyRecord, StorageContent};
+
+pub(crate) const FRAME_MAGIC: &[u8; 8] = b"SSHAGENT";
+pub(crate) const FRAME_HEADER_LEN: usize = 16;
+/// Sign requests carry a session hash and a little metadata, so this is plenty.
+const MAX_REQUEST_LEN: usize = 16 * 1024;
+pub(crate) const KEY_TYPE: &str = "ssh-ed25519";
+
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+const SSH_MSG_USERAUTH_REQUEST: u8 = 50;
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Request {
+    Identities,
+    Sign {
+        key_blob: Vec<u8>,
+        data: Vec<u8>,
+    },
+    /// Anything else, including adding keys from the host, which this agent never does
+    Unsupported(u8),
+}
+
+/// Walks the SSH wire encoding: big-endian u32s and length-prefixed strings.
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+impl<'a> Reader<'a> {
+    fn u8(&mut self) -> Option<u8> {
+        let (&b, rest) = self.buf.split_first()?;
+        self.buf = rest;
+        Some(b)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        if self.buf.len() < 4 {
+            return None;
+        }
+        let (n, rest) = self.buf.split_at(4);
+        self.buf = rest;
+        Some(u32::from_be_bytes(n.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Option<&'a [u8]> {
+        let len = self.u32()? as usize;
+        if self.buf.len() < len {
+            return None;
+        }
+        let (s, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Some(s)
+    }
+}
+
+fn put_string(out: &mut Vec<u8>, s: &[u8]) {
+    out.extend_from_slice(&(s.len() as u32).to_be_bytes());
+    out.extend_from_slice(s);
+}
+
+/// Checks a request header, returning the message length and sequence number.
+pub(crate) fn parse_frame_header(header: &[u8]) -> Option<(usize, u32)> {
+    if header.len() < FRAME_HEADER_LEN || &header[..8] != FRAME_MAGIC {
+        return None;
+    }
+    let len = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+    let seq = u32::from_le_bytes(header[12..16].try_into().unwrap());
+    if len == 0 || len > MAX_REQUEST_LEN { None } else { Some((len, seq)) }
+}
+
+/// Bytes a request of `len` takes on the wire, padding included.
+pub(crate) fn frame_len(len: usize) -> usize {
+    let total = FRAME_HEADER_LEN + len;
+    total + (SERIAL_BINARY_BUFLEN - total % SERIAL_BINARY_BUFLEN) % SERIAL_BINARY_BUFLEN
+}
+
+pub(crate) fn parse_request(msg: &[u8]) -> Option<Request> {
+    let mut r = Reader { buf: msg };
+    match r.u8()? {
+        SSH_AGENTC_REQUEST_IDENTITIES => Some(Request::Identities),
+        SSH_AGENTC_SIGN_REQUEST => {
+            let key_blob = r.string()?.to_vec();
+            let data = r.string()?.to_vec();
+            // the flags only pick RSA hash variants, so they don't matter for Ed25519
+            r.u32()?;
+            Some(Request::Sign { key_blob, data })
+        }
+        other => Some(Request::Unsupported(other)),
+    }
+}
+
+/// The SSH public key blob: the key type name, then the 32-byte key.
+pub(crate) fn public_key_blob(public_key: &[u8; 32]) -> Vec<u8> {
+    let mut blob = Vec::new();
+    put_string(&mut blob, KEY_TYPE.as_bytes());
+    put_string(&mut blob, public_key);
+    blob
+}
+
+/// `SHA256:...` as printed by `ssh-add -l` and `ssh-keygen -l`.
+pub(crate) fn fingerprint(key_blob: &[u8]) -> String {
+    format!("SHA256:{}", base64::engine::general_purpose::STANDARD_NO_PAD.encode(Sha256::digest(key_blob)))
+}
+
+/// The line that goes into `authorized_keys`.
+pub(crate) fn authorized_keys_line(key_blob: &[u8], comment: &str) -> String {
+    format!("{} {} {}", KEY_TYPE, base64::engine::general_purpose::STANDARD.encode(key_blob), comment)
+}
+
+pub(crate) fn identities_answer(keys: &[(Vec<u8>, String)]) -> Vec<u8> {
+    let mut msg = vec![SSH_AGENT_IDENTITIES_ANSWER];
+    msg.extend_from_slice(&(keys.len() as u32).to_be_bytes());
+    for (blob, comment) in keys {
+        put_string(&mut msg, blob);
+        put_string(&mut msg, comment.as_bytes());
+    }
+    msg
+}
+
+pub(crate) fn sign_response(signature: &[u8; 64]) -> Vec<u8> {
+    let mut sig = Vec::new();
+    put_string(&mut sig, KEY_TYPE.as_bytes());
+    put_string(&mut sig, signature);
+    let mut msg = vec![SSH_AGENT_SIGN_RESPONSE];
+    put_string(&mut msg, &sig);
+    msg
+}
+
+pub(crate) fn failure() -> Vec<u8> { vec![SSH_AGENT_FAILURE] }
+
+/// The user name a login is for, if `data` is an SSH userauth request as signed during public key
+/// authentication. Anything else gets signed too, but the user only sees the key name.
+pub(crate) fn userauth_user(data: &[u8]) -> Option<String> {
+    let mut r = Reader { buf: data };
+    r.string()?; // session identifier
+    if r.u8()? != SSH_MSG_USERAUTH_REQUEST {
+        return None;
+    }
+    let user = r.string()?;
+    String::from_utf8(user.to_vec()).ok()
+}
+
+pub(crate) fn reply_line(seq: u32, msg: &[u8]) -> String {
+    format!("SSHAGENT {} {}\r\n", seq, base64::engine::general_purpose::STANDARD.encode(msg))
+}
+
+pub(crate) fn signing_key(record: &SshKeyRecord) -> Option<SigningKey> {
+    record.seed_bytes().map(|seed| SigningKey::from_bytes(&seed))
+}
+
+pub(crate) fn record_key_blob(record: &SshKeyRecord) -> Option<Vec<u8>> {
+    signing_key(record).map(|key| public_key_blob(&key.verifying_key().to_bytes()))
+}
+
+/// Runs on its own thread while the agent is on, answering requests until `running` is cleared and the
+/// serial input hooks are dropped, or another listener takes the port. Clears `running` on the way out,
+/// so the rest of the vault can tell the agent is gone.
+pub(crate) fn serve(running: Arc<AtomicBool>) {
+    let xns = xous_names::XousNames::new().unwrap();
+    let usb = usb_device_xous::UsbHid::new();
+    let modals = modals::Modals::new(&xns).unwrap();
+    let mut storage = storage::Manager::new(&xns);
+    let mut data = Vec::new();
+    log::info!("ssh agent started");
+    while running.load(Ordering::SeqCst) {
+        let chunk = usb.serial_wait_binary();
+        if chunk.is_empty() {
+            break;
+        }
+        data.extend_from_slice(&chunk);
+        while data.len() >= FRAME_HEADER_LEN {
+            let (len, seq) = match parse_frame_header(&data) {
+                Some(header) => header,
+                None => {
+                    // out of step with the host: drop what we have, its next frame starts a fresh buffer
+                    log::warn!("ssh agent: bad frame, discarding {} bytes", data.len());
+                    data.clear();
+                    break;
+                }
+            };
+            if data.len() < FRAME_HEADER_LEN + len {
+                break;
+            }
+            let msg = data[FRAME_HEADER_LEN..FRAME_HEADER_LEN + len].to_vec();
+            data.drain(..frame_len(len).min(data.len()));
+            let reply = handle(&msg, &mut storage, &modals);
+            if let Err(e) = usb.serial_send(&reply_line(seq, &reply)) {
+                log::error!("ssh agent: couldn't send reply: {:?}", e);
+            }
+        }
+    }
+    running.store(false, Ordering::SeqCst);
+    log::info!("ssh agent stopped");
+}
+
+fn handle(msg: &[u8], storage: &mut storage::Manager, modals: &modals::Modals) -> Vec<u8> {
+    // keys live in whichever bases are open, so look them up fresh for every request
+    let records: Vec<SshKeyRecord> = storage.all(ContentKind::SshKey).unwrap_or_default();
+    match parse_request(msg) {
+        Some(Request::Identities) => {
+            let keys: Vec<(Vec<u8>, String)> =
+                records.iter().filter_map(|r| Some((record_key_blob(r)?, r.comment.clone()))).collect();
+            identities_answer(&keys)
+        }
+        Some(Request::Sign { key_blob, data }) => {
+            let record = match records
+                .into_iter()
+                .find(|r| record_key_blob(r).as_deref() == Some(key_blob.as_slice()))
+            {
+                Some(record) => record,
+                None => return failure(),
+            };
+            let key = match signing_key(&record) {
+                Some(key) => key,
+                None => return failure(),
+            };
+            let query = match userauth_user(&data) {
+                Some(user) => format!(
+                    "{}{}\n{}{}\n{}",
+                    t!("vault.ssh.sign_login", locales::lang()),
+                    user,
+                    t!("vault.ssh.key", locales::lang()),
+                    record.comment,
+                    fingerprint(&key_blob)
+                ),
+                None => format!(
+                    "{}{}\n{}",
+                    t!("vault.ssh.sign_data", locales::lang()),
+                    record.comment,
+                    fingerprint(&key_blob)
+                ),
+            };
+            modals.add_list(vec![t!("vault.yes", locales::lang()), t!("vault.no", locales::lang())]).ok();
+            match modals.get_radiobutton(&query) {
+                Ok(response) if response == t!("vault.yes", locales::lang()) => {}
+                _ => return failure(),
+            }
+            let signature = key.sign(&data).to_bytes();
+            let mut record = record;
+            record.count += 1;
+            record.atime = vault::utc_now().timestamp() as u64;
+            if let Err(e) = storage.update(&ContentKind::SshKey, &storage::hex(record.hash()), &mut record) {
+                log::warn!("ssh agent: couldn't update key usage: {:?}", e);
+            }
+            sign_response(&signature)
+        }
+        Some(Request::Unsupported(code)) => {
+            log::info!("ssh agent: unsupported request {}", code);
+            failure()
+        }
+        None => failure(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ed25519_dalek::Verifier;
+
+    use super::*;
+
+    fn request_identities() -> Vec<u8> { vec![SSH_AGENTC_REQUEST_IDENTITIES] }
+
+    fn sign_request(blob: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut msg = vec![SSH_AGENTC_SIGN_REQUEST];
+        put_string(&mut msg, blob);
+        put_string(&mut msg, data);
+        msg.extend_from_slice(&0u32.to_be_bytes());
+        msg
+    }
+
+    #[test]
+    fn test_frame_header() {
+        let mut header = FRAME_MAGIC.to_vec();
+        header.extend_from_slice(&5u32.to_le_bytes());
+        header.extend_from_slice(&7u32.to_le_bytes());
+        assert_eq!(parse_frame_header(&header), Some((5, 7)));
+        assert_eq!(frame_len(5), SERIAL_BINARY_BUFLEN);
+        assert_eq!(frame_len(SERIAL_BINARY_BUFLEN - FRAME_HEADER_LEN), SERIAL_BINARY_BUFLEN);
+        assert_eq!(frame_len(SERIAL_BINARY_BUFLEN), 2 * SERIAL_BINARY_BUFLEN);
+        header[0] = b'X';
+        assert_eq!(parse_frame_header(&header), None);
+    }
+
+    #[test]
+    fn test_parse_requests() {
+        assert_eq!(parse_request(&request_identities()), Some(Request::Identities));
+        assert_eq!(
+            parse_request(&sign_request(b"blob", b"data")),
+            Some(Request::Sign { key_blob: b"blob".to_vec(), data: b"data".to_vec() })
+        );
+        // SSH_AGENTC_ADD_IDENTITY
+        assert_eq!(parse_request(&[17, 0, 0]), Some(Request::Unsupported(17)));
+        // truncated string
+        assert_eq!(parse_request(&[SSH_AGENTC_SIGN_REQUEST, 0, 0, 0, 9, 1]), None);
+        assert_eq!(parse_request(&[]), None);
+    }
+
+    #[test]
+    fn test_key_blob_and_fingerprint() {
+        // RFC 8032 test 1
+        let seed: [u8; 32] = hex::decode("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let key = SigningKey::from_bytes(&seed);
+        let blob = public_key_blob(&key.verifying_key().to_bytes());
+        assert_eq!(&blob[..15], b"\x00\x00\x00\x0bssh-ed25519");
+        assert_eq!(blob.len(), 4 + 11 + 4 + 32);
+        assert!(fingerprint(&blob).starts_with("SHA256:"));
+        assert_eq!(fingerprint(&blob).len(), 7 + 43);
+        let line = authorized_keys_line(&blob, "ops@precursor");
+        assert!(line.starts_with("ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAI"));
+        assert!(line.ends_with(" ops@precursor"));
+    }
+
+    #[test]
+    fn test_sign_response_verifies() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let data = b"session data";
+        let msg = sign_response(&key.sign(data).to_bytes());
+        let mut r = Reader { buf: &msg };
+        assert_eq!(r.u8(), Some(SSH_AGENT_SIGN_RESPONSE));
+        let mut sig = Reader { buf: r.string().unwrap() };
+        assert_eq!(sig.string(), Some(KEY_TYPE.as_bytes()));
+        let signature = ed25519_dalek::Signature::from_slice(sig.string().unwrap()).unwrap();
+        assert!(key.verifying_key().verify(data, &signature).is_ok());
+    }
+
+    #[test]
+    fn test_identities_answer() {
+        let msg =
+            identities_answer(&[(b"k1".to_vec(), "one".to_string()), (b"k2".to_vec(), "two".to_string())]);
+        let mut r = Reader { buf: &msg };
+        assert_eq!(r.u8(), Some(SSH_AGENT_IDENTITIES_ANSWER));
+        assert_eq!(r.u32(), Some(2));
+        assert_eq!(r.string(), Some(&b"k1"[..]));
+        assert_eq!(r.string(), Some(&b"one"[..]));
+        assert_eq!(r.string(), Some(&b"k2"[..]));
+        assert_eq!(r.string(), Some(&b"two"[..]));
+    }
+
+    #[test]
+    fn test_userauth_user() {
+        let mut data = Vec::new();
+        put_string(&mut data, &[0xAA; 32]);
+        data.push(SSH_MSG_USERAUTH_REQUEST);
+        put_string(&mut data, b"deploy");
+        put_string(&mut data, b"ssh-connection");
+        put_string(&mut data, b"publickey");
+        assert_eq!(userauth_user(&data), Some("deploy".to_string()));
+        assert_eq!(userauth_user(b"not a userauth request"), None);
+    }
+
+    #[test]
+    fn test_reply_line() {
+        assert_eq!(reply_line(3, &failure()), "SSHAGENT 3 BQ==\r\n");
+    }
+}
@@ -8,12 +8,13 @@ use std::{
 use chrono::{DateTime, NaiveDateTime, Utc};
 use ctap_crypto::Hash256;
 
-use crate::totp::TotpAlgorithm;
+use crate::totp::{OtpScheme, TotpAlgorithm};
 
 const VAULT_PASSWORD_DICT: &'static str = "vault.passwords";
 const VAULT_TOTP_DICT: &'static str = "vault.totp";
+const VAULT_SSH_DICT: &'static str = "vault.ssh";
 const VAULT_TOTP_ALLOC_HINT: usize = 128;
-const VAULT_PASSWORD_REC_VERSION: u32 = 1;
+pub(crate) const VAULT_PASSWORD_REC_VERSION: u32 = 1;
 
 // Version history TOTP record:
 //  - v1 created, basic record for TOTP
@@ -21,7 +22,12 @@ const VAULT_PASSWORD_REC_VERSION: u32 = 1;
 //    - `hotp` field added. If 1, then HOTP record. If not existent or not 1, then TOTP
 //    - If HOTP, then the `timestep` field is re-purposed as the `count` field.
 //    - v1 records read directly onto v2 records, and `hotp` is always `false` for v1 records
-const VAULT_TOTP_REC_VERSION: u32 = 2;
+//  - v3 add Steam, Yandex and mOTP support:
+//    - `scheme` field added, one of RFC/STEAM/YANDEX/MOTP. If not existent, then RFC
+//    - `pin` field added, used by the Yandex and mOTP schemes. If not existent, then empty
+//    - v2 records read directly onto v3 records as RFC records
+pub(crate) const VAULT_TOTP_REC_VERSION: u32 = 3;
+pub(crate) const VAULT_SSH_REC_VERSION: u32 = 1;
 
 #[derive(Debug)]
 #[allow(dead_code)]
@@ -29,6 +35,7 @@ pub enum Error {
     IoError(std::io::Error),
     TotpSerError(TOTPSerializationError),
     PasswordSerError(PasswordSerializationError),
+    SshSerError(SshKeySerializationError),
     KeyExists,
     DupesExist(Vec<usize>),
 }
@@ -45,6 +52,10 @@ impl From<PasswordSerializationError> for Error {
     fn from(e: PasswordSerializationError) -> Self { Self::PasswordSerError(e) }
 }
 
+impl From<SshKeySerializationError> for Error {
+    fn from(e: SshKeySerializationError) -> Self { Self::SshSerError(e) }
+}
+
 pub struct Manager {
     pddb: pddb::Pddb,
 }
@@ -69,6 +80,7 @@ pub struct ContentPDDBSettings {
 pub enum ContentKind {
     TOTP,
     Password,
+    SshKey,
 }
 
 impl ContentKind {
@@ -76,6 +88,7 @@ impl ContentKind {
         match self {
             ContentKind::TOTP => TotpRecord::default().settings(),
             ContentKind::Password => PasswordRecord::default().settings(),
+            ContentKind::SshKey => SshKeyRecord::default().settings(),
         }
     }
 }
@@ -284,6 +297,8 @@ pub struct TotpRecord {
     pub timestep: u64,
     pub ctime: u64,
     pub is_hotp: bool,
+    pub scheme: OtpScheme,
+    pub pin: String,
 }
 
 #[derive(Debug)]
@@ -294,6 +309,7 @@ pub enum TOTPSerializationError {
     BadCtime,
     BadTimestep,
     BadHotp,
+    BadScheme,
     MalformedInput,
 }
 
@@ -366,6 +382,13 @@ impl StorageContent for TotpRecord {
                             return Err(TOTPSerializationError::BadHotp)?;
                         }
                     }
+                    "scheme" => {
+                        pr.scheme = match OtpScheme::try_from(data) {
+                            Ok(s) => s,
+                            Err(_) => return Err(TOTPSerializationError::BadScheme)?,
+                        }
+                    }
+                    "pin" => pr.pin.push_str(data),
                     _ => {
                         log::warn!("unexpected tag {} encountered parsing TOTP info, ignoring", tag);
                     }
@@ -382,7 +405,7 @@ impl StorageContent for TotpRecord {
 
     fn to_vec(&self) -> Vec<u8> {
         format!(
-            "{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n",
+            "{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n",
             "version",
             self.version,
             "secret",
@@ -401,6 +424,10 @@ impl StorageContent for TotpRecord {
             if self.is_hotp { 1 } else { 0 },
             "ctime",
             self.ctime,
+            "scheme",
+            self.scheme,
+            "pin",
+            self.pin,
         )
         .into_bytes()
     }
@@ -428,6 +455,8 @@ impl TryFrom<Vec<u8>> for TotpRecord {
             ctime: 0,
             timestep: 0,
             is_hotp: false,
+            scheme: OtpScheme::Rfc,
+            pin: String::new(),
         };
         let lines = desc_str.split('\n');
         for line in lines {
@@ -486,6 +515,13 @@ impl TryFrom<Vec<u8>> for TotpRecord {
                             return Err(TOTPSerializationError::BadHotp);
                         }
                     }
+                    "scheme" => {
+                        pr.scheme = match OtpScheme::try_from(data) {
+                            Ok(s) => s,
+                            Err(_) => return Err(TOTPSerializationError::BadScheme),
+                        }
+                    }
+                    "pin" => pr.pin.push_str(data),
                     _ => {
                         log::warn!("unexpected tag {} encountered parsing TOTP info, ignoring", tag);
                     }
@@ -502,7 +538,7 @@ impl TryFrom<Vec<u8>> for TotpRecord {
 impl From<TotpRecord> for Vec<u8> {
     fn from(tr: TotpRecord) -> Self {
         format!(
-            "{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n",
+            "{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n",
             "version",
             tr.version,
             "secret",
@@ -521,6 +557,10 @@ impl From<TotpRecord> for Vec<u8> {
             if tr.is_hotp { 1 } else { 0 },
             "ctime",
             tr.ctime,
+            "scheme",
+            tr.scheme,
+            "pin",
+            tr.pin,
         )
         .into_bytes()
     }
@@ -763,6 +803,128 @@ impl From<PasswordRecord> for Vec<u8> {
     }
 }
 
+#[derive(Debug)]
+pub enum SshKeySerializationError {
+    MalformedInput,
+    BadVersion,
+    BadKeyType,
+    BadSeed,
+    BadCtime,
+    BadAtime,
+    BadCount,
+}
+
+/// An SSH key made on the device. Only the private seed is stored; the public half is derived from it.
+#[derive(Default, Clone)]
+pub struct SshKeyRecord {
+    pub version: u32,
+    /// Names the key on the device and in `ssh-add -l`, so it has to be unique
+    pub comment: String,
+    /// The SSH key type name; only `ssh-ed25519` is made for now
+    pub key_type: String,
+    /// Ed25519 seed, as 64 hex digits
+    pub seed: String,
+    pub ctime: u64,
+    /// last signature
+    pub atime: u64,
+    /// signatures made
+    pub count: u64,
+}
+
+impl SshKeyRecord {
+    pub fn seed_bytes(&self) -> Option<[u8; 32]> {
+        let mut seed = [0u8; 32];
+        if self.seed.len() != 64 {
+            return None;
+        }
+        for (i, byte) in seed.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&self.seed[i * 2..i * 2 + 2], 16).ok()?;
+        }
+        Some(seed)
+    }
+}
+
+impl StorageContent for SshKeyRecord {
+    fn settings(&self) -> ContentPDDBSettings {
+        ContentPDDBSettings { dict: VAULT_SSH_DICT.to_string(), alloc_hint: Some(VAULT_TOTP_ALLOC_HINT) }
+    }
+
+    fn set_ctime(&mut self, value: u64) { self.ctime = value; }
+
+    fn from_vec(&mut self, data: Vec<u8>) -> Result<(), Error> {
+        let desc_str = std::str::from_utf8(&data).or(Err(SshKeySerializationError::MalformedInput))?;
+        let mut kr = SshKeyRecord::default();
+        for line in desc_str.split('\n') {
+            if let Some((tag, data)) = line.split_once(':') {
+                match tag {
+                    "version" => {
+                        kr.version =
+                            u32::from_str_radix(data, 10).or(Err(SshKeySerializationError::BadVersion))?
+                    }
+                    "comment" => kr.comment.push_str(data),
+                    "type" => {
+                        if data != "ssh-ed25519" {
+                            return Err(SshKeySerializationError::BadKeyType)?;
+                        }
+                        kr.key_type.push_str(data);
+                    }
+                    "seed" => kr.seed.push_str(data),
+                    "ctime" => {
+                        kr.ctime =
+                            u64::from_str_radix(data, 10).or(Err(SshKeySerializationError::BadCtime))?
+                    }
+                    "atime" => {
+                        kr.atime =
+                            u64::from_str_radix(data, 10).or(Err(SshKeySerializationError::BadAtime))?
+                    }
+                    "count" => {
+                        kr.count =
+                            u64::from_str_radix(data, 10).or(Err(SshKeySerializationError::BadCount))?
+                    }
+                    _ => {
+                        log::warn!("unexpected tag {} encountered parsing ssh key, ignoring", tag);
+                    }
+                }
+            } else {
+                log::trace!("invalid line skipped: {:?}", line);
+            }
+        }
+        if kr.seed_bytes().is_none() {
+            return Err(SshKeySerializationError::BadSeed)?;
+        }
+        *self = kr;
+        Ok(())
+    }
+
+    fn to_vec(&self) -> Vec<u8> {
+        format!(
+            "{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n{}:{}\n",
+            "version",
+            self.version,
+            "comment",
+            self.comment,
+            "type",
+            self.key_type,
+            "seed",
+            self.seed,
+            "ctime",
+            self.ctime,
+            "atime",
+            self.atime,
+            "count",
+            self.count,
+        )
+        .into_bytes()
+    }
+
+    fn hash(&self) -> Vec<u8> {
+        let mut h = ctap_crypto::sha256::Sha256::new();
+        h.update(b"ssh:");
+        h.update(self.comment.as_bytes());
+        h.finalize().to_vec()
+    }
+}
+
 /// because we don't get Utc::now, as the crate checks your architecture and xous is not recognized as a valid
 /// target
 fn utc_now() -> DateTime<Utc> {
@@ -0,0 +1,513 @@
+//! Offline password strength estimate, after zxcvbn.
+//!
+//! Nothing about the password leaves the device. Instead of a breach lookup, the password is matched
+//! against the patterns an attacker tries first: common passwords, the entry's own name and user name,
+//! keyboard runs, sequences, repeats and years. The guess count is the cheapest way to cover the whole
+//! password with such patterns, with brute force filling the gaps.
+
+/// Small ranked list of the most common passwords; the rank is the number of guesses to reach it.
+const COMMON_PASSWORDS: [&str; 100] = [
+    "123456",
+    "password",
+    "12345678",
+    "qwerty",
+    "123456789",
+    "12345",
+    "1234",
+    "111111",
+    "1234567",
+    "dragon",
+    "123123",
+    "baseball",
+    "abc123",
+    "football",
+    "monkey",
+    "letmein",
+    "696969",
+    "shadow",
+    "master",
+    "666666",
+    "qwertyuiop",
+    "123321",
+    "mustang",
+    "1234567890",
+    "michael",
+    "654321",
+    "superman",
+    "1qaz2wsx",
+    "7777777",
+    "121212",
+    "000000",
+    "qazwsx",
+    "123qwe",
+    "killer",
+    "trustno1",
+    "jordan",
+    "jennifer",
+    "zxcvbnm",
+    "asdfgh",
+    "hunter",
+    "buster",
+    "soccer",
+    "harley",
+    "batman",
+    "andrew",
+    "tigger",
+    "sunshine",
+    "iloveyou",
+    "2000",
+    "charlie",
+    "robert",
+    "thomas",
+    "hockey",
+    "ranger",
+    "daniel",
+    "starwars",
+    "klaster",
+    "112233",
+    "george",
+    "computer",
+    "michelle",
+    "jessica",
+    "pepper",
+    "1111",
+    "zxcvbn",
+    "555555",
+    "11111111",
+    "131313",
+    "freedom",
+    "777777",
+    "pass",
+    "maggie",
+    "159753",
+    "aaaaaa",
+    "ginger",
+    "princess",
+    "joshua",
+    "cheese",
+    "amanda",
+    "summer",
+    "love",
+    "ashley",
+    "nicole",
+    "chelsea",
+    "biteme",
+    "matthew",
+    "access",
+    "yankees",
+    "987654321",
+    "dallas",
+    "austin",
+    "thunder",
+    "taylor",
+    "matrix",
+    "admin",
+    "welcome",
+    "login",
+    "hello",
+    "secret",
+    "whatever",
+];
+const KEYBOARD_ROWS: [&str; 5] = ["1234567890", "qwertyuiop", "asdfghjkl", "zxcvbnm", "!@#$%^&*()"];
+/// Number of keys a keyboard run can start on
+const KEYBOARD_STARTS: f64 = 47.0;
+const L33T: [(char, &str); 9] = [
+    ('4', "a"),
+    ('@', "a"),
+    ('3', "e"),
+    ('0', "o"),
+    ('1', "il"),
+    ('!', "i"),
+    ('$', "s"),
+    ('5', "s"),
+    ('7', "t"),
+];
+/// Recent years are the likeliest; how far the anchor drifts barely matters next to `MIN_YEAR_SPACE`.
+const REFERENCE_YEAR: i32 = 2026;
+const MIN_YEAR_SPACE: f64 = 20.0;
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_MULTI_CHAR: f64 = 50.0;
+/// Penalty for splitting the password into many small patterns
+const MIN_GUESSES_BEFORE_GROWING_SEQUENCE: f64 = 10_000.0;
+/// Longer passwords are cut here; anything this long scores the maximum anyway
+const MAX_ANALYZED_LEN: usize = 100;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Strength {
+    pub guesses_log10: f64,
+    /// 0 (trivially guessable) to 4 (very unguessable), on the zxcvbn scale
+    pub score: u8,
+}
+impl Strength {
+    /// The strength of a secret picked uniformly at random with `bits` of entropy, such as a generated
+    /// password, where the estimate would only be a guess at a number that is already known.
+    pub fn from_entropy(bits: f64) -> Strength {
+        let guesses_log10 = bits / core::f64::consts::LOG2_10;
+        Strength { guesses_log10, score: score(guesses_log10) }
+    }
+
+    pub fn bits(&self) -> f64 { self.guesses_log10 * core::f64::consts::LOG2_10 }
+}
+
+struct Match {
+    i: usize,
+    j: usize,
+    guesses: f64,
+}
+
+/// Estimates how many guesses it takes to find `password`. `user_inputs` are strings an attacker would
+/// try early for this entry, such as its description and user name.
+pub fn estimate(password: &str, user_inputs: &[&str]) -> Strength {
+    let chars: Vec<char> = password.chars().take(MAX_ANALYZED_LEN).collect();
+    let guesses = most_guessable(&chars, &user_dictionary(user_inputs), true).max(1.0);
+    let guesses_log10 = guesses.log10();
+    Strength { guesses_log10, score: score(guesses_log10) }
+}
+
+/// zxcvbn's thresholds, at 10^3, 10^6, 10^8 and 10^10 guesses
+fn score(guesses_log10: f64) -> u8 {
+    match 10f64.powf(guesses_log10) {
+        g if g < 1e3 + 5.0 => 0,
+        g if g < 1e6 + 5.0 => 1,
+        g if g < 1e8 + 5.0 => 2,
+        g if g < 1e10 + 5.0 => 3,
+        _ => 4,
+    }
+}
+
+/// Splits the user inputs into lowercase words, so that `example.com` also catches `example`.
+fn user_dictionary(user_inputs: &[&str]) -> Vec<String> {
+    let mut words = Vec::new();
+    for input in user_inputs {
+        let lower = input.to_lowercase();
+        words.push(lower.clone());
+        for token in lower.split(|c: char| !c.is_alphanumeric()).filter(|t| t.chars().count() > 1) {
+            if !words.iter().any(|w| w == token) {
+                words.push(token.to_string());
+            }
+        }
+    }
+    words
+}
+
+/// The zxcvbn search: the cheapest cover of `chars` by matches, charging `l! * product + D^(l-1)` for a
+/// cover made of `l` matches.
+fn most_guessable(chars: &[char], user_words: &[String], allow_repeats: bool) -> f64 {
+    let n = chars.len();
+    if n == 0 {
+        return 1.0;
+    }
+    let mut matches = Vec::new();
+    dictionary_matches(chars, user_words, &mut matches);
+    spatial_matches(chars, &mut matches);
+    sequence_matches(chars, &mut matches);
+    year_matches(chars, &mut matches);
+    if allow_repeats {
+        repeat_matches(chars, user_words, &mut matches);
+    }
+    for i in 0..n {
+        for j in i..n {
+            matches.push(Match { i, j, guesses: BRUTEFORCE_CARDINALITY.powi((j - i + 1) as i32) });
+        }
+    }
+
+    // best[k][l]: smallest product of guesses over covers of chars[..=k] made of l matches
+    let mut best = vec![vec![f64::INFINITY; n + 1]; n];
+    matches.sort_by_key(|m| m.j);
+    for m in matches.iter() {
+        let min = if m.j == m.i { MIN_SUBMATCH_GUESSES_SINGLE_CHAR } else { MIN_SUBMATCH_GUESSES_MULTI_CHAR };
+        let guesses = m.guesses.max(min);
+        if m.i == 0 {
+            if guesses < best[m.j][1] {
+                best[m.j][1] = guesses;
+            }
+        } else {
+            for l in 1..n {
+                let product = best[m.i - 1][l] * guesses;
+                if product < best[m.j][l + 1] {
+                    best[m.j][l + 1] = product;
+                }
+            }
+        }
+    }
+    let mut factorial = 1.0;
+    let mut result = f64::INFINITY;
+    for l in 1..=n {
+        factorial *= l as f64;
+        if best[n - 1][l].is_finite() {
+            let total = factorial * best[n - 1][l] + MIN_GUESSES_BEFORE_GROWING_SEQUENCE.powi(l as i32 - 1);
+            result = result.min(total);
+        }
+    }
+    result
+}
+
+fn n_choose_k(n: usize, k: usize) -> f64 { (0..k).fold(1.0, |acc, i| acc * (n - i) as f64 / (i + 1) as f64) }
+
+/// Extra guesses for capitalisation: none for all lowercase, two for the usual first/last/all caps.
+fn uppercase_variations(word: &[char]) -> f64 {
+    let upper = word.iter().filter(|c| c.is_uppercase()).count();
+    let lower = word.iter().filter(|c| c.is_lowercase()).count();
+    if upper == 0 {
+        return 1.0;
+    }
+    let first_only = word[0].is_uppercase() && upper == 1;
+    let last_only = word[word.len() - 1].is_uppercase() && upper == 1;
+    if lower == 0 || first_only || last_only {
+        return 2.0;
+    }
+    (1..=upper.min(lower)).map(|i| n_choose_k(upper + lower, i)).sum()
+}
+
+/// All readings of `word` with l33t characters swapped back for letters, and how many were swapped.
+fn unl33t(word: &[char]) -> Vec<(String, usize)> {
+    let mut readings = vec![(String::new(), 0usize)];
+    for c in word {
+        let c = c.to_lowercase().next().unwrap_or(*c);
+        match L33T.iter().find(|(l, _)| *l == c) {
+            Some((_, letters)) => {
+                let mut next = Vec::new();
+                for (s, subs) in readings.iter() {
+                    // keep the literal reading too, so "1234" is still found as digits
+                    next.push((format!("{}{}", s, c), *subs));
+                    for letter in letters.chars() {
+                        next.push((format!("{}{}", s, letter), subs + 1));
+                    }
+                }
+                // a run of ambiguous characters could blow up; the first readings are plenty
+                next.truncate(64);
+                readings = next;
+            }
+            None => {
+                for (s, _) in readings.iter_mut() {
+                    s.push(c);
+                }
+            }
+        }
+    }
+    readings
+}
+
+fn dictionary_rank(word: &str, user_words: &[String]) -> Option<f64> {
+    let common = COMMON_PASSWORDS.iter().position(|p| *p == word);
+    let user = user_words.iter().position(|p| p == word);
+    match (common, user) {
+        (Some(a), Some(b)) => Some((a.min(b) + 1) as f64),
+        (Some(a), None) | (None, Some(a)) => Some((a + 1) as f64),
+        (None, None) => None,
+    }
+}
+
+fn dictionary_matches(chars: &[char], user_words: &[String], matches: &mut Vec<Match>) {
+    let n = chars.len();
+    let longest = COMMON_PASSWORDS
+        .iter()
+        .map(|w| w.chars().count())
+        .chain(user_words.iter().map(|w| w.chars().count()))
+        .max()
+        .unwrap_or(0);
+    for i in 0..n {
+        for j in (i + 1)..n.min(i + longest) {
+            let word = &chars[i..=j];
+            let case = uppercase_variations(word);
+            for (reversed, candidate) in
+                [(false, word.to_vec()), (true, word.iter().rev().copied().collect())]
+            {
+                for (reading, subs) in unl33t(&candidate) {
+                    if let Some(rank) = dictionary_rank(&reading, user_words) {
+                        let l33t = if subs > 0 { 2.0 * subs as f64 } else { 1.0 };
+                        let reverse = if reversed { 2.0 } else { 1.0 };
+                        matches.push(Match { i, j, guesses: rank * case * l33t * reverse });
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn row_position(c: char) -> Option<(usize, usize)> {
+    let c = c.to_lowercase().next().unwrap_or(c);
+    KEYBOARD_ROWS.iter().enumerate().find_map(|(r, row)| row.chars().position(|k| k == c).map(|p| (r, p)))
+}
+
+/// Straight runs along a keyboard row, either direction, such as `qwer` or `lkjh`.
+fn spatial_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let n = chars.len();
+    let mut i = 0;
+    while i + 2 < n {
+        let mut j = i;
+        let mut direction = 0i32;
+        while j + 1 < n {
+            match (row_position(chars[j]), row_position(chars[j + 1])) {
+                (Some((r1, p1)), Some((r2, p2))) if r1 == r2 => {
+                    let step = p2 as i32 - p1 as i32;
+                    if step.abs() == 1 && (direction == 0 || step == direction) {
+                        direction = step;
+                        j += 1;
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            break;
+        }
+        if j - i >= 2 {
+            let shifted = if chars[i..=j].iter().any(|c| c.is_uppercase()) { 2.0 } else { 1.0 };
+            matches.push(Match { i, j, guesses: KEYBOARD_STARTS * 2.0 * (j - i + 1) as f64 * shifted });
+            i = j;
+        } else {
+            i += 1;
+        }
+    }
+}
+
+/// Evenly stepped runs such as `abcd`, `9753` or `ZYX`.
+fn sequence_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let n = chars.len();
+    let class = |c: char| {
+        if c.is_ascii_digit() {
+            Some(0)
+        } else if c.is_ascii_lowercase() {
+            Some(1)
+        } else if c.is_ascii_uppercase() {
+            Some(2)
+        } else {
+            None
+        }
+    };
+    let mut i = 0;
+    while i + 2 < n {
+        let delta = chars[i + 1] as i32 - chars[i] as i32;
+        let mut j = i + 1;
+        if class(chars[i]).is_some() && class(chars[i]) == class(chars[j]) && delta != 0 && delta.abs() <= 2 {
+            while j + 1 < n
+                && class(chars[j + 1]) == class(chars[i])
+                && chars[j + 1] as i32 - chars[j] as i32 == delta
+            {
+                j += 1;
+            }
+        }
+        if j - i >= 2 {
+            let base = match chars[i] {
+                'a' | 'A' | 'z' | 'Z' | '0' | '1' | '9' => 4.0,
+                c if c.is_ascii_digit() => 10.0,
+                _ => 26.0,
+            };
+            let descending = if delta < 0 { 2.0 } else { 1.0 };
+            matches.push(Match { i, j, guesses: base * (j - i + 1) as f64 * descending });
+            i = j;
+        } else {
+            i += 1;
+        }
+    }
+}
+
+/// Four digit years from 1900 to 2099.
+fn year_matches(chars: &[char], matches: &mut Vec<Match>) {
+    if chars.len() < 4 {
+        return;
+    }
+    for i in 0..=(chars.len() - 4) {
+        let digits: String = chars[i..i + 4].iter().collect();
+        if let Ok(year) = digits.parse::<i32>() {
+            if digits.chars().all(|c| c.is_ascii_digit()) && (1900..=2099).contains(&year) {
+                let space = ((year - REFERENCE_YEAR).abs() as f64).max(MIN_YEAR_SPACE);
+                matches.push(Match { i, j: i + 3, guesses: space });
+            }
+        }
+    }
+}
+
+/// A short base repeated back to back, like `aaaa` or `abcabc`: the base's guesses times the count.
+/// Only the shortest base starting at each position is tried, which is the one zxcvbn would pick.
+fn repeat_matches(chars: &[char], user_words: &[String], matches: &mut Vec<Match>) {
+    let n = chars.len();
+    let mut bases: Vec<(&[char], f64)> = Vec::new();
+    for i in 0..n {
+        for period in 1..=((n - i) / 2) {
+            let base = &chars[i..i + period];
+            let mut count = 1;
+            while i + (count + 1) * period <= n
+                && &chars[i + count * period..i + (count + 1) * period] == base
+            {
+                count += 1;
+            }
+            if count >= 2 && period * count >= 3 {
+                // rotations of one base show up at every offset, so remember what each one cost
+                let base_guesses = match bases.iter().find(|(b, _)| *b == base) {
+                    Some((_, guesses)) => *guesses,
+                    None => {
+                        let guesses = most_guessable(base, user_words, false);
+                        bases.push((base, guesses));
+                        guesses
+                    }
+                };
+                matches.push(Match { i, j: i + period * count - 1, guesses: base_guesses * count as f64 });
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_common_passwords_are_weak() {
+        for pw in ["password", "123456", "qwerty", "P@ssw0rd", "drowssap", "letmein1", "Iloveyou"] {
+            let s = estimate(pw, &[]);
+            assert!(s.score <= 1, "{} scored {:?}", pw, s);
+        }
+    }
+
+    #[test]
+    fn test_patterns_are_weak() {
+        for pw in
+            ["aaaaaaaaaaaa", "abcdefghij", "qwertyuiop[]", "asdfjkl;", "1987", "abcabcabcabc", "9876543210"]
+        {
+            let s = estimate(pw, &[]);
+            assert!(s.score <= 2, "{} scored {:?}", pw, s);
+        }
+    }
+
+    #[test]
+    fn test_user_inputs_count_against_the_password() {
+        let alone = estimate("Examplecorp2024", &[]);
+        let with_site = estimate("Examplecorp2024", &["examplecorp.com", "alice"]);
+        assert!(with_site.guesses_log10 < alone.guesses_log10);
+        assert!(with_site.score <= 2);
+    }
+
+    #[test]
+    fn test_random_passwords_are_strong() {
+        for pw in ["kX7#mQ2$vN9!pR4&", "correct-horse-battery-staple", "vu8Tq*Lz3wEp"] {
+            let s = estimate(pw, &[]);
+            assert_eq!(s.score, 4, "{} scored {:?}", pw, s);
+        }
+        assert!(estimate("kX7#mQ2$vN9!pR4&", &[]).bits() > 50.0);
+    }
+
+    #[test]
+    fn test_longer_is_stronger() {
+        let short = estimate("tr0ub4dor", &[]);
+        let long = estimate("tr0ub4dor&3xylophone", &[]);
+        assert!(long.guesses_log10 > short.guesses_log10);
+        assert_eq!(estimate("", &[]).score, 0);
+    }
+
+    #[test]
+    fn test_from_entropy() {
+        assert_eq!(Strength::from_entropy(6.0 * 10f64.log2()).score, 1);
+        assert_eq!(Strength::from_entropy(66.0).score, 4);
+        assert!((Strength::from_entropy(66.0).bits() - 66.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_long_input_is_bounded() {
+        let pw: String = "a1B!".repeat(200);
+        let s = estimate(&pw, &[]);
+        assert!(s.guesses_log10.is_finite());
+    }
+}
@@ -9,35 +9,78 @@ pub fn create_submenu(vault_conn: xous::CID, actions_conn: xous::CID, menu_mgr:
     let mut menu_items = Vec::<MenuItem>::new();
 
     menu_items.push(MenuItem {
-        name: String::from(t!("vault.menu_edit", locales::LANG)),
+        name: String::from(t!("vault.menu_edit", locales::lang())),
         action_conn: Some(vault_conn),
         action_opcode: VaultOp::MenuEditStage1.to_u32().unwrap(),
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
         close_on_select: true,
     });
     menu_items.push(MenuItem {
-        name: String::from(t!("vault.menu_delete", locales::LANG)),
+        name: String::from(t!("vault.menu_delete", locales::lang())),
         action_conn: Some(vault_conn),
         action_opcode: VaultOp::MenuDeleteStage1.to_u32().unwrap(),
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
         close_on_select: true,
     });
     menu_items.push(MenuItem {
-        name: String::from(t!("vault.menu_unlock_basis", locales::LANG)),
+        name: String::from(t!("vault.menu_hotp_resync", locales::lang())),
+        action_conn: Some(vault_conn),
+        action_opcode: VaultOp::MenuHotpResyncStage1.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
+    menu_items.push(MenuItem {
+        name: String::from(t!("vault.menu_manage_passkeys", locales::lang())),
+        action_conn: Some(actions_conn),
+        action_opcode: ActionOp::MenuManagePasskeys.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
+    #[cfg(feature = "ed25519")]
+    menu_items.push(MenuItem {
+        name: String::from(t!("vault.menu_ssh_keys", locales::lang())),
+        action_conn: Some(actions_conn),
+        action_opcode: ActionOp::MenuSshKeys.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
+    menu_items.push(MenuItem {
+        name: String::from(t!("vault.menu_unlock_basis", locales::lang())),
         action_conn: Some(actions_conn),
         action_opcode: ActionOp::MenuUnlockBasis.to_u32().unwrap(),
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
         close_on_select: true,
     });
     menu_items.push(MenuItem {
-        name: String::from(t!("vault.menu_manage_basis", locales::LANG)),
+        name: String::from(t!("vault.menu_manage_basis", locales::lang())),
         action_conn: Some(actions_conn),
         action_opcode: ActionOp::MenuManageBasis.to_u32().unwrap(),
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
         close_on_select: true,
     });
     menu_items.push(MenuItem {
-        name: String::from(t!("vault.menu_change_font", locales::LANG)),
+        name: String::from(t!("vault.menu_import", locales::lang())),
+        action_conn: Some(actions_conn),
+        action_opcode: ActionOp::MenuImport.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
+    menu_items.push(MenuItem {
+        name: String::from(t!("vault.menu_scan_qr", locales::lang())),
+        action_conn: Some(actions_conn),
+        action_opcode: ActionOp::MenuScanQr.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
+    menu_items.push(MenuItem {
+        name: String::from(t!("vault.menu_export", locales::lang())),
+        action_conn: Some(actions_conn),
+        action_opcode: ActionOp::MenuExport.to_u32().unwrap(),
+        action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+        close_on_select: true,
+    });
+    menu_items.push(MenuItem {
+        name: String::from(t!("vault.menu_change_font", locales::lang())),
         action_conn: Some(vault_conn),
         action_opcode: VaultOp::MenuChangeFont.to_u32().unwrap(),
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
@@ -52,28 +95,28 @@ pub fn create_submenu(vault_conn: xous::CID, actions_conn: xous::CID, menu_mgr:
         close_on_select: true,
     });
     menu_items.push(MenuItem {
-        name: String::from(t!("vault.menu_readout_mode", locales::LANG)),
+        name: String::from(t!("vault.menu_readout_mode", locales::lang())),
         action_conn: Some(vault_conn),
         action_opcode: VaultOp::MenuReadoutMode.to_u32().unwrap(),
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
         close_on_select: true,
     });
     menu_items.push(MenuItem {
-        name: String::from(t!("prefs.autotype_rate", locales::LANG)),
+        name: String::from(t!("prefs.autotype_rate", locales::lang())),
         action_conn: Some(vault_conn),
         action_opcode: VaultOp::MenuAutotypeRate.to_u32().unwrap(),
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
         close_on_select: true,
     });
     menu_items.push(MenuItem {
-        name: String::from(t!("vault.menu_set_lefty_mode", locales::LANG)),
+        name: String::from(t!("vault.menu_set_lefty_mode", locales::lang())),
         action_conn: Some(vault_conn),
         action_opcode: VaultOp::MenuLeftyMode.to_u32().unwrap(),
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
         close_on_select: true,
     });
     menu_items.push(MenuItem {
-        name: String::from(t!("vault.menu_close", locales::LANG)),
+        name: String::from(t!("vault.menu_close", locales::lang())),
         action_conn: Some(actions_conn),
         action_opcode: ActionOp::MenuClose.to_u32().unwrap(),
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
@@ -1,3 +1,4 @@
+use std::convert::TryInto;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::{
@@ -6,6 +7,7 @@ use std::{
 };
 
 use hmac::{Hmac, Mac};
+use md5::Digest;
 use num_traits::*;
 use sha1::Sha1;
 use xous::{Message, send_message};
@@ -59,18 +61,89 @@ impl core::fmt::Display for TotpAlgorithm {
     }
 }
 
+/// The code-generation scheme of a record. All of them store the secret as base32 and share the
+/// `digits`/`timestep`/`algorithm` fields; the non-RFC schemes fix most of those to what their apps use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OtpScheme {
+    /// RFC 6238 TOTP, or RFC 4226 HOTP if the record is marked as such
+    Rfc,
+    /// Steam Guard: HMAC-SHA1 TOTP, rendered as 5 characters from a 26-symbol alphabet
+    Steam,
+    /// Yandex Key: HMAC-SHA256 TOTP keyed with a hash of the PIN and secret, rendered as 8 letters
+    Yandex,
+    /// Mobile-OTP: MD5 over the time in 10-second units, the secret and the PIN, as 6 hex digits
+    Motp,
+}
+
+impl Default for OtpScheme {
+    fn default() -> Self { Self::Rfc }
+}
+
+impl OtpScheme {
+    /// Whether codes depend on a PIN stored alongside the secret
+    pub fn needs_pin(&self) -> bool { matches!(self, OtpScheme::Yandex | OtpScheme::Motp) }
+
+    /// (digits, timestep) used by the scheme's own apps
+    pub fn defaults(&self) -> (u32, u64) {
+        match self {
+            OtpScheme::Rfc => (6, 30),
+            OtpScheme::Steam => (5, 30),
+            OtpScheme::Yandex => (8, 30),
+            OtpScheme::Motp => (6, 10),
+        }
+    }
+}
+
+impl TryFrom<&str> for OtpScheme {
+    type Error = xous::Error;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "RFC" => Ok(OtpScheme::Rfc),
+            "STEAM" => Ok(OtpScheme::Steam),
+            "YANDEX" => Ok(OtpScheme::Yandex),
+            "MOTP" => Ok(OtpScheme::Motp),
+            _ => Err(xous::Error::InvalidString),
+        }
+    }
+}
+impl core::fmt::Display for OtpScheme {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            OtpScheme::Rfc => write!(f, "RFC"),
+            OtpScheme::Steam => write!(f, "STEAM"),
+            OtpScheme::Yandex => write!(f, "YANDEX"),
+            OtpScheme::Motp => write!(f, "MOTP"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct TotpEntry {
     pub step_seconds: u64,
     pub shared_secret: Vec<u8>,
     pub digit_count: u8,
     pub algorithm: TotpAlgorithm,
+    pub scheme: OtpScheme,
+    /// only used by schemes where `needs_pin()`
+    pub pin: String,
 }
 
+/// How far ahead of the stored counter an HOTP resync looks for the two codes the user typed in.
+pub const HOTP_RESYNC_WINDOW: u64 = 1000;
+
+const STEAM_ALPHABET: &[u8; 26] = b"23456789BCDFGHJKMNPQRTVWXY";
+
 pub fn get_current_unix_time() -> Result<u64, SystemTimeError> {
     SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|duration| duration.as_secs())
 }
 
+/// Seconds until the code for `unix_timestamp` rolls over.
+pub fn seconds_remaining(unix_timestamp: u64, step_seconds: u64) -> u64 {
+    let step = step_seconds.max(1);
+    step - unix_timestamp % step
+}
+
 fn unpack_u64(v: u64) -> [u8; 8] {
     let mask = 0x00000000000000ff;
     let mut bytes: [u8; 8] = [0; 8];
@@ -78,23 +151,14 @@ fn unpack_u64(v: u64) -> [u8; 8] {
     bytes
 }
 
-fn generate_hmac_bytes(unix_timestamp: u64, totp_entry: &TotpEntry) -> Result<Vec<u8>, xous::Error> {
+fn generate_hmac_bytes(key: &[u8], counter: u64, algorithm: TotpAlgorithm) -> Result<Vec<u8>, xous::Error> {
     let mut computed_hmac = Vec::new();
-    let checked_step = if totp_entry.step_seconds == 0 {
-        log::warn!(
-            "totp step_seconds was 0, this would cause a div-by-zero; forcing to 1. Check that this is not an HOTP record?"
-        );
-        1
-    } else {
-        totp_entry.step_seconds
-    };
-    match totp_entry.algorithm {
+    match algorithm {
         // The OpenTitan HMAC core does not support hmac-sha1. Fall back to
         // a software implementation.
         TotpAlgorithm::HmacSha1 => {
-            let mut mac: Hmac<Sha1> =
-                Hmac::new_from_slice(&totp_entry.shared_secret).map_err(|_| xous::Error::InternalError)?;
-            mac.update(&unpack_u64(unix_timestamp / checked_step));
+            let mut mac: Hmac<Sha1> = Hmac::new_from_slice(key).map_err(|_| xous::Error::InternalError)?;
+            mac.update(&unpack_u64(counter));
             let hash: &[u8] = &mac.finalize().into_bytes();
             computed_hmac.extend_from_slice(hash);
         }
@@ -102,15 +166,15 @@ fn generate_hmac_bytes(unix_timestamp: u64, totp_entry: &TotpEntry) -> Result<Ve
         // test against.
         TotpAlgorithm::HmacSha256 => {
             let mut mac: Hmac<sha2::Sha256> =
-                Hmac::new_from_slice(&totp_entry.shared_secret).map_err(|_| xous::Error::InternalError)?;
-            mac.update(&unpack_u64(unix_timestamp / checked_step));
+                Hmac::new_from_slice(key).map_err(|_| xous::Error::InternalError)?;
+            mac.update(&unpack_u64(counter));
             let hash: &[u8] = &mac.finalize().into_bytes();
             computed_hmac.extend_from_slice(hash);
         }
         TotpAlgorithm::HmacSha512 => {
             let mut mac: Hmac<sha2::Sha512> =
-                Hmac::new_from_slice(&totp_entry.shared_secret).map_err(|_| xous::Error::InternalError)?;
-            mac.update(&unpack_u64(unix_timestamp / checked_step));
+                Hmac::new_from_slice(key).map_err(|_| xous::Error::InternalError)?;
+            mac.update(&unpack_u64(counter));
             let hash: &[u8] = &mac.finalize().into_bytes();
             computed_hmac.extend_from_slice(hash);
         }
@@ -122,13 +186,75 @@ fn generate_hmac_bytes(unix_timestamp: u64, totp_entry: &TotpEntry) -> Result<Ve
     Ok(computed_hmac)
 }
 
-pub fn generate_totp_code(unix_timestamp: u64, totp_entry: &TotpEntry) -> Result<String, xous::Error> {
-    let hash = generate_hmac_bytes(unix_timestamp, totp_entry)?;
+/// RFC 4226 dynamic truncation: 31 bits starting at the offset given by the low nibble of the last byte.
+fn truncate(hash: &[u8]) -> u64 {
     let offset: usize = (hash.last().unwrap_or(&0) & 0xf) as usize;
-    let binary: u64 = (((hash[offset] & 0x7f) as u64) << 24)
+    (((hash[offset] & 0x7f) as u64) << 24)
         | ((hash[offset + 1] as u64) << 16)
         | ((hash[offset + 2] as u64) << 8)
-        | (hash[offset + 3] as u64);
+        | (hash[offset + 3] as u64)
+}
+
+fn steam_code(counter: u64, totp_entry: &TotpEntry) -> Result<String, xous::Error> {
+    let hash = generate_hmac_bytes(&totp_entry.shared_secret, counter, TotpAlgorithm::HmacSha1)?;
+    let mut value = truncate(&hash);
+    let mut code = String::with_capacity(5);
+    for _ in 0..5 {
+        code.push(STEAM_ALPHABET[(value % 26) as usize] as char);
+        value /= 26;
+    }
+    Ok(code)
+}
+
+fn yandex_code(counter: u64, totp_entry: &TotpEntry) -> Result<String, xous::Error> {
+    // the secret is 26 base32 characters: 16 bytes of key followed by a few checksum bits
+    let secret = totp_entry.shared_secret.get(..16).ok_or(xous::Error::InvalidString)?;
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(totp_entry.pin.as_bytes());
+    hasher.update(secret);
+    let key = hasher.finalize();
+    // quirk of the reference implementation: a leading zero byte is dropped from the key
+    let key = if key[0] == 0 { &key[1..] } else { &key[..] };
+    let hash = generate_hmac_bytes(key, counter, TotpAlgorithm::HmacSha256)?;
+    let offset = (hash[hash.len() - 1] & 0xf) as usize;
+    let mut value = u64::from_be_bytes(hash[offset..offset + 8].try_into().unwrap()) & 0x7fff_ffff_ffff_ffff;
+    value %= 26u64.pow(8);
+    let mut code = [b'a'; 8];
+    for c in code.iter_mut().rev() {
+        *c = b'a' + (value % 26) as u8;
+        value /= 26;
+    }
+    Ok(String::from_utf8_lossy(&code).into_owned())
+}
+
+fn motp_code(counter: u64, totp_entry: &TotpEntry) -> Result<String, xous::Error> {
+    // mOTP secrets are hex strings that are hashed as text, not decoded
+    let secret = std::str::from_utf8(&totp_entry.shared_secret).map_err(|_| xous::Error::InvalidString)?;
+    let hash = md5::Md5::digest(format!("{}{}{}", counter, secret, totp_entry.pin).as_bytes());
+    let digits = (totp_entry.digit_count as usize).min(hash.len() * 2);
+    Ok(hex::encode(hash)[..digits].to_string())
+}
+
+/// Generates the code for `unix_timestamp`. HOTP entries pass the counter as the timestamp, with a step
+/// of 1.
+pub fn generate_totp_code(unix_timestamp: u64, totp_entry: &TotpEntry) -> Result<String, xous::Error> {
+    let checked_step = if totp_entry.step_seconds == 0 {
+        log::warn!(
+            "totp step_seconds was 0, this would cause a div-by-zero; forcing to 1. Check that this is not an HOTP record?"
+        );
+        1
+    } else {
+        totp_entry.step_seconds
+    };
+    let counter = unix_timestamp / checked_step;
+    match totp_entry.scheme {
+        OtpScheme::Steam => return steam_code(counter, totp_entry),
+        OtpScheme::Yandex => return yandex_code(counter, totp_entry),
+        OtpScheme::Motp => return motp_code(counter, totp_entry),
+        OtpScheme::Rfc => {}
+    }
+    let hash = generate_hmac_bytes(&totp_entry.shared_secret, counter, totp_entry.algorithm)?;
+    let binary = truncate(&hash);
 
     let truncated_code = format!(
         "{:01$}",
@@ -139,6 +265,43 @@ pub fn generate_totp_code(unix_timestamp: u64, totp_entry: &TotpEntry) -> Result
     Ok(truncated_code)
 }
 
+/// Rebuilds an entry from the `extra` field of a TOTP list item, as laid out by
+/// `make_totp_item_from_record()`: `secret:digits:step:algorithm:TOTP|HOTP:scheme:pin`. HOTP entries
+/// come back with a step of 1 and their counter alongside.
+pub fn entry_from_item_extra(extra: &str) -> Option<(TotpEntry, Option<u64>)> {
+    let fields = extra.split(':').collect::<Vec<&str>>();
+    if fields.len() != 7 {
+        return None;
+    }
+    let shared_secret =
+        base32::decode(base32::Alphabet::RFC4648 { padding: false }, fields[0]).unwrap_or(vec![]);
+    let digit_count = u8::from_str_radix(fields[1], 10).unwrap_or(6);
+    let step_seconds = u64::from_str_radix(fields[2], 10).unwrap_or(30);
+    let algorithm = TotpAlgorithm::try_from(fields[3]).unwrap_or(TotpAlgorithm::HmacSha1);
+    let is_hotp = fields[4].to_uppercase() == "HOTP";
+    let scheme = OtpScheme::try_from(fields[5]).unwrap_or_default();
+    let totp = TotpEntry {
+        // step_seconds is re-used by hotp as the count
+        step_seconds: if !is_hotp { step_seconds } else { 1 },
+        shared_secret,
+        digit_count,
+        algorithm,
+        scheme,
+        pin: fields[6].to_string(),
+    };
+    Some((totp, if is_hotp { Some(step_seconds) } else { None }))
+}
+
+/// RFC 4226 section 7.4 resynchronization: looks for `first` followed by `second` at counters from
+/// `counter` up to `HOTP_RESYNC_WINDOW` ahead, and returns the counter to use next if they are found.
+/// Two consecutive codes make a false match within the window vanishingly unlikely.
+pub fn hotp_resync(totp_entry: &TotpEntry, counter: u64, first: &str, second: &str) -> Option<u64> {
+    let code = |c: u64| generate_totp_code(c, totp_entry).ok();
+    (counter..counter.saturating_add(HOTP_RESYNC_WINDOW))
+        .find(|&c| code(c).as_deref() == Some(first) && code(c + 1).as_deref() == Some(second))
+        .map(|c| c + 2)
+}
+
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
 pub(crate) enum PumpOp {
     Pump,
@@ -194,3 +357,91 @@ pub(crate) fn pumper(
         }
     });
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(secret: &[u8], scheme: OtpScheme, pin: &str, digits: u8, step: u64) -> TotpEntry {
+        TotpEntry {
+            step_seconds: step,
+            shared_secret: secret.to_vec(),
+            digit_count: digits,
+            algorithm: if scheme == OtpScheme::Yandex {
+                TotpAlgorithm::HmacSha256
+            } else {
+                TotpAlgorithm::HmacSha1
+            },
+            scheme,
+            pin: pin.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_rfc4226_vectors() {
+        let hotp = entry(b"12345678901234567890", OtpScheme::Rfc, "", 6, 1);
+        let expected = ["755224", "287082", "359152", "969429", "338314", "254676", "287922", "162583"];
+        for (count, code) in expected.iter().enumerate() {
+            assert_eq!(generate_totp_code(count as u64, &hotp).unwrap(), *code);
+        }
+        let totp = entry(b"12345678901234567890", OtpScheme::Rfc, "", 8, 30);
+        assert_eq!(generate_totp_code(59, &totp).unwrap(), "94287082");
+    }
+
+    #[test]
+    fn test_steam() {
+        let secret =
+            base32::decode(base32::Alphabet::RFC4648 { padding: false }, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ")
+                .unwrap();
+        let steam = entry(&secret, OtpScheme::Steam, "", 5, 30);
+        assert_eq!(generate_totp_code(1_600_000_000, &steam).unwrap(), "J8D72");
+    }
+
+    #[test]
+    fn test_yandex() {
+        let secret = base32::decode(
+            base32::Alphabet::RFC4648 { padding: false },
+            "6SB2IKNM6OBZPAVBVTOHDKS4FAAAAAAADFUTQMBTRY",
+        )
+        .unwrap();
+        let yandex = entry(&secret, OtpScheme::Yandex, "5239", 8, 30);
+        assert_eq!(generate_totp_code(1641559648, &yandex).unwrap(), "umozdicq");
+        let short = entry(&secret[..8], OtpScheme::Yandex, "5239", 8, 30);
+        assert!(generate_totp_code(1641559648, &short).is_err());
+    }
+
+    #[test]
+    fn test_motp() {
+        let motp = entry(b"0123456789abcdef", OtpScheme::Motp, "1234", 6, 10);
+        assert_eq!(generate_totp_code(1_600_000_000, &motp).unwrap(), "0f57e8");
+    }
+
+    #[test]
+    fn test_hotp_resync() {
+        let hotp = entry(b"12345678901234567890", OtpScheme::Rfc, "", 6, 1);
+        assert_eq!(hotp_resync(&hotp, 0, "254676", "287922"), Some(7));
+        assert_eq!(hotp_resync(&hotp, 3, "969429", "338314"), Some(5));
+        // out of order, or behind the stored count
+        assert_eq!(hotp_resync(&hotp, 0, "287922", "254676"), None);
+        assert_eq!(hotp_resync(&hotp, 6, "254676", "287922"), None);
+    }
+
+    #[test]
+    fn test_item_extra() {
+        let (totp, count) = entry_from_item_extra("GEZDGNBVGY3TQOJQ:8:30:SHA256:TOTP:YANDEX:5239").unwrap();
+        assert_eq!(count, None);
+        assert_eq!(totp.scheme, OtpScheme::Yandex);
+        assert_eq!(totp.pin, "5239");
+        let (totp, count) = entry_from_item_extra("GEZDGNBVGY3TQOJQ:6:42:SHA1:HOTP:RFC:").unwrap();
+        assert_eq!(count, Some(42));
+        assert_eq!(totp.step_seconds, 1);
+        assert!(entry_from_item_extra("GEZDGNBVGY3TQOJQ:6:30:SHA1:TOTP").is_none());
+    }
+
+    #[test]
+    fn test_seconds_remaining() {
+        assert_eq!(seconds_remaining(60, 30), 30);
+        assert_eq!(seconds_remaining(59, 30), 1);
+        assert_eq!(seconds_remaining(5, 0), 1);
+    }
+}
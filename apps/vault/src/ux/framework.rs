@@ -11,11 +11,11 @@ use graphics_server::{DrawStyle, Gid, PixelColor, Point, Rectangle, TextView};
 use locales::t;
 use num_traits::*;
 use pddb::Pddb;
-use usb_device_xous::UsbDeviceType;
+use usb_device_xous::{UsbDeviceType, UsbFunctions};
 use vault::{VaultOp, utc_now};
 
 use crate::actions::ActionOp;
-use crate::totp::{TotpAlgorithm, TotpEntry, generate_totp_code, get_current_unix_time};
+use crate::totp::{entry_from_item_extra, generate_totp_code, get_current_unix_time, seconds_remaining};
 use crate::{ItemLists, SelectedEntry, VaultMode};
 
 pub enum NavDir {
@@ -100,6 +100,8 @@ fn style_to_name(style: &GlyphStyle) -> String {
 const TITLE_HEIGHT: i16 = 26;
 const VAULT_CONFIG_DICT: &'static str = "vault.config";
 const VAULT_CONFIG_KEY_FONT: &'static str = "fontstyle";
+/// TOTP codes this close to rolling over are shown together with the code that follows
+const NEXT_CODE_PREVIEW_SECS: u64 = 8;
 
 impl VaultUx {
     pub(crate) fn new(
@@ -181,15 +183,15 @@ impl VaultUx {
     */
     pub fn swap_submenu(&mut self) {
         // always call delete on the potential optional items, to return us to a known state
-        self.menu_mgr.delete_item(t!("vault.menu_autotype", locales::LANG));
-        self.menu_mgr.delete_item(t!("vault.menu_autotype_username", locales::LANG));
-        self.menu_mgr.delete_item(t!("vault.menu_addnew", locales::LANG));
+        self.menu_mgr.delete_item(t!("vault.menu_autotype", locales::lang()));
+        self.menu_mgr.delete_item(t!("vault.menu_autotype_username", locales::lang()));
+        self.menu_mgr.delete_item(t!("vault.menu_addnew", locales::lang()));
         match *self.mode.lock().unwrap() {
             VaultMode::Fido => (),
             VaultMode::Password | VaultMode::Totp => {
                 self.menu_mgr.insert_item(
                     MenuItem {
-                        name: String::from(t!("vault.menu_addnew", locales::LANG)),
+                        name: String::from(t!("vault.menu_addnew", locales::lang())),
                         action_conn: Some(self.actions_conn),
                         action_opcode: ActionOp::MenuAddnew.to_u32().unwrap(),
                         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
@@ -199,7 +201,7 @@ impl VaultUx {
                 );
                 self.menu_mgr.insert_item(
                     MenuItem {
-                        name: String::from(t!("vault.menu_autotype_username", locales::LANG)),
+                        name: String::from(t!("vault.menu_autotype_username", locales::lang())),
                         action_conn: Some(self.main_conn),
                         action_opcode: VaultOp::MenuAutotype.to_u32().unwrap(),
                         action_payload: MenuPayload::Scalar([1, 0, 0, 0]),
@@ -209,7 +211,7 @@ impl VaultUx {
                 );
                 self.menu_mgr.insert_item(
                     MenuItem {
-                        name: String::from(t!("vault.menu_autotype", locales::LANG)),
+                        name: String::from(t!("vault.menu_autotype", locales::lang())),
                         action_conn: Some(self.main_conn),
                         action_opcode: VaultOp::MenuAutotype.to_u32().unwrap(),
                         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
@@ -507,7 +509,7 @@ impl VaultUx {
             box_text.clear_area = true;
             box_text.style = self.style;
             box_text.margin = self.margin;
-            write!(box_text, "{}", t!("vault.no_items", locales::LANG)).ok();
+            write!(box_text, "{}", t!("vault.no_items", locales::lang())).ok();
             self.gam.post_textview(&mut box_text).expect("couldn't post empty notification");
             return Ok(());
         }
@@ -543,41 +545,45 @@ impl VaultUx {
                         write!(box_text, "{}\n{}", item.name(), item.extra).ok();
                     }
                     VaultMode::Totp => {
-                        let fields = item.extra.split(':').collect::<Vec<&str>>();
-                        if fields.len() == 5 {
-                            let shared_secret =
-                                base32::decode(base32::Alphabet::RFC4648 { padding: false }, fields[0])
-                                    .unwrap_or(vec![]);
-                            let digit_count = u8::from_str_radix(fields[1], 10).unwrap_or(6);
-                            let step_seconds = u64::from_str_radix(fields[2], 10).unwrap_or(30);
-                            let algorithm =
-                                TotpAlgorithm::try_from(fields[3]).unwrap_or(TotpAlgorithm::HmacSha1);
-                            let is_hotp = fields[4].to_uppercase() == "HOTP";
-                            let totp = TotpEntry {
-                                step_seconds: if !is_hotp { step_seconds } else { 1 }, /* step_seconds is
-                                                                                        * re-used by hotp
-                                                                                        * as the code. */
-                                shared_secret,
-                                digit_count,
-                                algorithm,
-                            };
-                            if !is_hotp {
-                                let code = generate_totp_code(get_current_unix_time().unwrap_or(0), &totp)
-                                    .unwrap_or(t!("vault.error.record_error", locales::LANG).to_string());
+                        if let Some((totp, hotp_count)) = entry_from_item_extra(&item.extra) {
+                            if let Some(count) = hotp_count {
+                                let code = generate_totp_code(count, &totp)
+                                    .unwrap_or(t!("vault.error.record_error", locales::lang()).to_string());
                                 // why code on top? because the item.name can be very long, and it can wrap
                                 // which would cause the code to become
                                 // hidden.
-                                write!(box_text, "{}\n{}", code, item.name()).ok();
+                                write!(box_text, "HOTP {}\n{}", code, item.name()).ok();
                             } else {
-                                let code = generate_totp_code(step_seconds, &totp)
-                                    .unwrap_or(t!("vault.error.record_error", locales::LANG).to_string());
+                                let now = get_current_unix_time().unwrap_or(0);
+                                let code = generate_totp_code(now, &totp)
+                                    .unwrap_or(t!("vault.error.record_error", locales::lang()).to_string());
+                                let remaining = seconds_remaining(now, totp.step_seconds);
                                 // why code on top? because the item.name can be very long, and it can wrap
                                 // which would cause the code to become
                                 // hidden.
-                                write!(box_text, "HOTP {}\n{}", code, item.name()).ok();
+                                if remaining <= NEXT_CODE_PREVIEW_SECS
+                                    && totp.step_seconds > NEXT_CODE_PREVIEW_SECS
+                                {
+                                    // about to roll over: show what comes next so it can be typed in on time
+                                    let next = generate_totp_code(now + totp.step_seconds, &totp).unwrap_or(
+                                        t!("vault.error.record_error", locales::lang()).to_string(),
+                                    );
+                                    write!(
+                                        box_text,
+                                        "{} ({}s) {} {}\n{}",
+                                        code,
+                                        remaining,
+                                        t!("vault.totp.next_code", locales::lang()),
+                                        next,
+                                        item.name()
+                                    )
+                                    .ok();
+                                } else {
+                                    write!(box_text, "{}\n{}", code, item.name()).ok();
+                                }
                             }
                         } else {
-                            write!(box_text, "{}", t!("vault.error.record_error", locales::LANG)).ok();
+                            write!(box_text, "{}", t!("vault.error.record_error", locales::lang())).ok();
                         }
                     }
                 }
@@ -713,29 +719,13 @@ impl VaultUx {
             }
             VaultMode::Totp => {
                 let extra = self.item_lists.lock().unwrap().selected_extra(mode_cache);
-                let fields = extra.split(':').collect::<Vec<&str>>();
-                if fields.len() == 5 {
-                    let shared_secret =
-                        base32::decode(base32::Alphabet::RFC4648 { padding: false }, fields[0])
-                            .unwrap_or(vec![]);
-                    let digit_count = u8::from_str_radix(fields[1], 10).unwrap_or(6);
-                    let step_seconds = u64::from_str_radix(fields[2], 10).unwrap_or(30);
-                    let algorithm = TotpAlgorithm::try_from(fields[3]).unwrap_or(TotpAlgorithm::HmacSha1);
-                    let is_hotp = fields[4].to_uppercase() == "HOTP";
-                    let totp = TotpEntry {
-                        step_seconds: if !is_hotp { step_seconds } else { 1 }, /* step_seconds is re-used
-                                                                                * by hotp as the code. */
-                        shared_secret,
-                        digit_count,
-                        algorithm,
-                    };
-                    let code = if !is_hotp {
-                        generate_totp_code(get_current_unix_time().unwrap_or(0), &totp)
-                            .unwrap_or(t!("vault.error.record_error", locales::LANG).to_string())
-                    } else {
-                        generate_totp_code(step_seconds, &totp)
-                            .unwrap_or(t!("vault.error.record_error", locales::LANG).to_string())
-                    };
+                if let Some((totp, hotp_count)) = entry_from_item_extra(&extra) {
+                    let is_hotp = hotp_count.is_some();
+                    let code = generate_totp_code(
+                        hotp_count.unwrap_or_else(|| get_current_unix_time().unwrap_or(0)),
+                        &totp,
+                    )
+                    .unwrap_or(t!("vault.error.record_error", locales::lang()).to_string());
                     match self.usb_dev.send_str(&code) {
                         Ok(_) => {
                             if is_hotp {
@@ -795,12 +785,14 @@ impl VaultUx {
                                 self.item_lists.lock().unwrap().selected_update_extra(
                                     mode_cache,
                                     format!(
-                                        "{}:{}:{}:{}:{}",
+                                        "{}:{}:{}:{}:{}:{}:{}",
                                         hotp_rec.secret,
                                         hotp_rec.digits,
                                         hotp_rec.timestep,
                                         hotp_rec.algorithm,
-                                        if hotp_rec.is_hotp { "HOTP" } else { "TOTP" }
+                                        if hotp_rec.is_hotp { "HOTP" } else { "TOTP" },
+                                        hotp_rec.scheme,
+                                        hotp_rec.pin
                                     ),
                                 );
                                 // now write to disk
@@ -845,7 +837,20 @@ impl VaultUx {
         self.item_lists.lock().unwrap().selected_entry(mode)
     }
 
+    /// Claims the composite device's functions that match `usb_type`, so that the vault keeps working
+    /// when the composite device is up rather than being switched away from it.
+    fn register_usb_functions(&self) {
+        let functions = match self.usb_type {
+            UsbDeviceType::Fido => UsbFunctions::FIDO,
+            _ => UsbFunctions::FIDO | UsbFunctions::KEYBOARD,
+        };
+        if let Err(e) = self.usb_dev.register_functions(functions) {
+            log::warn!("couldn't claim {:?} of the composite USB device: {:?}", functions, e);
+        }
+    }
+
     pub(crate) fn ensure_hid(&self) {
+        self.register_usb_functions();
         self.usb_dev.ensure_core(self.usb_type).unwrap();
         self.usb_dev.restrict_debug_access(true).unwrap();
     }
@@ -859,6 +864,7 @@ impl VaultUx {
         } else {
             self.usb_type = UsbDeviceType::FidoKbd;
         }
+        self.register_usb_functions();
         self.usb_dev.ensure_core(self.usb_type).unwrap();
     }
 }
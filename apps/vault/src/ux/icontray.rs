@@ -7,6 +7,9 @@ pub(crate) const SERVER_NAME_ICONTRAY: &'static str = "_vault icon tray plugin_"
 
 const ICONS: [&'static str; 4] = ["\t FIDO", "\t⏳1234", "\t🔐****", "\t🧾🛠"];
 
+/// The word at the end of the search line, which is what the word predictor completes
+fn last_word(line: &str) -> &str { line.rsplit(char::is_whitespace).next().unwrap_or("") }
+
 pub(crate) fn icontray_server(conn_to_main: xous::CID) {
     let xns = xous_names::XousNames::new().unwrap();
     // one connection only, should be the GAM
@@ -15,8 +18,20 @@ pub(crate) fn icontray_server(conn_to_main: xous::CID) {
 
     let ime_sh_sid = xns.register_name(SERVER_NAME_ICONTRAY, None).expect("can't register server");
 
+    // the whole line is wanted as the search filter, so there are no triggers to break it into words
     let mytriggers = PredictionTriggers { newline: false, punctuation: false, whitespace: false };
 
+    // while a search is typed, the word predictor's completions of its last word take the place of the icons
+    let predictor = PredictionPlugin {
+        connection: Some(
+            xns.request_connection_blocking(ime_plugin_predict::SERVER_NAME_IME_PLUGIN_PREDICT)
+                .expect("can't connect to the word predictor"),
+        ),
+    };
+    // set while the word predictor is locked on our behalf
+    let mut predictor_token: Option<[u32; 4]> = None;
+    let mut search = String::new();
+
     let mut api_token: Option<[u32; 4]> = None;
     loop {
         let mut msg = xous::receive_message(ime_sh_sid).unwrap();
@@ -34,6 +49,7 @@ pub(crate) fn icontray_server(conn_to_main: xous::CID) {
                         ret.token = Some(new_token);
                         api_token = Some(new_token);
                     }
+                    predictor_token = predictor.acquire(api_token).ok();
                 } else {
                     ret.token = None;
                     log::warn!("attempt to acquire lock on a predictor that was already locked");
@@ -45,6 +61,10 @@ pub(crate) fn icontray_server(conn_to_main: xous::CID) {
                 if let Some(t) = api_token {
                     if t == token {
                         api_token.take();
+                        if let Some(t) = predictor_token.take() {
+                            predictor.release(t);
+                        }
+                        search.clear();
                     } else {
                         log::warn!("Release called with an invalid token");
                     }
@@ -53,24 +73,43 @@ pub(crate) fn icontray_server(conn_to_main: xous::CID) {
                 }
             }),
             Some(Opcode::Input) => {
+                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                search.clear();
+                search.push_str(buffer.as_flat::<String, _>().unwrap().as_str());
+                drop(buffer);
+                if predictor_token.is_none() && api_token.is_some() {
+                    // the lock may have still been held by whoever had the input before us
+                    predictor_token = predictor.acquire(api_token).ok();
+                }
+                if predictor_token.is_some() && last_word(&search).len() > 0 {
+                    predictor.set_input(String::from(last_word(&search))).ok();
+                }
                 msg.forward(conn_to_main, crate::VaultOp::IncrementalLine.to_usize().unwrap())
                     .expect("couldn't forward input");
             }
             Some(Opcode::Picked) => {
-                // this is ignored
+                // this is ignored: search terms aren't taught to the word predictor
             }
             Some(Opcode::Prediction) => {
-                // we don't check the API token here, because our "predictions" are just the four menu slots
                 let mut buffer =
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let mut prediction: Prediction = buffer.to_original::<Prediction, _>().unwrap();
                 // every key press, the four slots get queried
                 prediction.string.clear();
-                if prediction.index < ICONS.len() as u32 {
-                    prediction.string.push_str(ICONS[prediction.index as usize]);
-                    prediction.valid = true;
-                } else {
-                    prediction.valid = false;
+                prediction.valid = false;
+                if search.is_empty() {
+                    // the icons are the same for everyone, so the API token isn't checked for them
+                    if prediction.index < ICONS.len() as u32 {
+                        prediction.string.push_str(ICONS[prediction.index as usize]);
+                        prediction.valid = true;
+                    }
+                } else if api_token == Some(prediction.api_token) && last_word(&search).len() > 0 {
+                    if let Some(t) = predictor_token {
+                        if let Ok(Some(word)) = predictor.get_prediction(prediction.index, t) {
+                            prediction.string.push_str(&word);
+                            prediction.valid = true;
+                        }
+                    }
                 }
                 // pack our data back into the buffer to return
                 buffer.replace(Return::Prediction(prediction)).expect("couldn't return Prediction");
@@ -48,19 +48,23 @@ pub enum VaultOp {
 
     /// exit the application
     Quit,
+
+    MenuHotpResyncStage1,
+    /// select the HID core again, after the SSH agent has let go of the serial port
+    RestoreHid,
 }
 
 pub fn atime_to_str(req_atime: u64) -> String {
     let mut request_str = String::with_capacity(
         // avoid allocations to speed up this routine, it is in the inner loop of rendering lists of
         // passwords
-        t!("vault.u2f.appinfo.last_authtime", locales::LANG).len()
-            + t!("vault.u2f.appinfo.seconds_ago", locales::LANG).len()
+        t!("vault.u2f.appinfo.last_authtime", locales::lang()).len()
+            + t!("vault.u2f.appinfo.seconds_ago", locales::lang()).len()
             + 16, // space for the actual duration + some slop for translation
     );
     if req_atime == 0 {
-        request_str.push_str(t!("vault.u2f.appinfo.last_authtime", locales::LANG));
-        request_str.push_str(t!("vault.u2f.appinfo.never", locales::LANG));
+        request_str.push_str(t!("vault.u2f.appinfo.last_authtime", locales::lang()));
+        request_str.push_str(t!("vault.u2f.appinfo.never", locales::lang()));
     } else {
         let now = utc_now();
         let atime = DateTime::<Utc>::from_naive_utc_and_offset(
@@ -69,21 +73,21 @@ pub fn atime_to_str(req_atime: u64) -> String {
         );
         // avoid format! macro, it is too slow.
         if now.signed_duration_since(atime).num_days() > 1 {
-            request_str.push_str(t!("vault.u2f.appinfo.last_authtime", locales::LANG));
+            request_str.push_str(t!("vault.u2f.appinfo.last_authtime", locales::lang()));
             request_str.push_str(&now.signed_duration_since(atime).num_days().to_string());
-            request_str.push_str(t!("vault.u2f.appinfo.days_ago", locales::LANG));
+            request_str.push_str(t!("vault.u2f.appinfo.days_ago", locales::lang()));
         } else if now.signed_duration_since(atime).num_hours() > 1 {
-            request_str.push_str(t!("vault.u2f.appinfo.last_authtime", locales::LANG));
+            request_str.push_str(t!("vault.u2f.appinfo.last_authtime", locales::lang()));
             request_str.push_str(&now.signed_duration_since(atime).num_hours().to_string());
-            request_str.push_str(t!("vault.u2f.appinfo.hours_ago", locales::LANG));
+            request_str.push_str(t!("vault.u2f.appinfo.hours_ago", locales::lang()));
         } else if now.signed_duration_since(atime).num_minutes() > 1 {
-            request_str.push_str(t!("vault.u2f.appinfo.last_authtime", locales::LANG));
+            request_str.push_str(t!("vault.u2f.appinfo.last_authtime", locales::lang()));
             request_str.push_str(&now.signed_duration_since(atime).num_minutes().to_string());
-            request_str.push_str(t!("vault.u2f.appinfo.minutes_ago", locales::LANG));
+            request_str.push_str(t!("vault.u2f.appinfo.minutes_ago", locales::lang()));
         } else {
-            request_str.push_str(t!("vault.u2f.appinfo.last_authtime", locales::LANG));
+            request_str.push_str(t!("vault.u2f.appinfo.last_authtime", locales::lang()));
             request_str.push_str(&now.signed_duration_since(atime).num_seconds().to_string());
-            request_str.push_str(t!("vault.u2f.appinfo.seconds_ago", locales::LANG));
+            request_str.push_str(t!("vault.u2f.appinfo.seconds_ago", locales::lang()));
         }
     }
     request_str
@@ -6,7 +6,7 @@ use vault::ctap::hid::{ChannelID, CtapHidCommand, Message, send::HidPacketIterat
 use vault::vault_api::{COMMAND_BACKUP_TOTP_CODES, COMMAND_RESET_SESSION, COMMAND_RESTORE_TOTP_CODES};
 
 use crate::storage::{Error, PasswordRecord, TotpRecord};
-use crate::totp::TotpAlgorithm;
+use crate::totp::{OtpScheme, TotpAlgorithm};
 // TODO(gsora): add something that checks whether or not a command works.
 
 pub enum SessionError {
@@ -201,6 +201,8 @@ enum BackupError {
     PddbError(std::io::Error),
     StorageError(crate::storage::Error),
     NoMoreChunks,
+    /// an OTP record made by a newer version, whose code scheme this one doesn't know
+    UnknownScheme(String),
 }
 
 impl From<DecoderError> for BackupError {
@@ -235,6 +237,11 @@ fn handle_restore(data: Vec<u8>, xns: &xous_names::XousNames) -> Result<Vec<u8>,
             log::debug!("restoring totp");
             for (idx, elem) in totp_entries.0.into_iter().enumerate() {
                 log::debug!("restoring element {}", idx);
+                let scheme = match elem.scheme.as_str() {
+                    "" => OtpScheme::Rfc,
+                    other => OtpScheme::try_from(other)
+                        .map_err(|_| BackupError::UnknownScheme(elem.scheme.clone()))?,
+                };
                 let totp = TotpRecord {
                     version: 1,
                     name: elem.name,
@@ -247,8 +254,10 @@ fn handle_restore(data: Vec<u8>, xns: &xous_names::XousNames) -> Result<Vec<u8>,
                     digits: elem.digit_count,
                     timestep: elem.step_seconds,
                     ctime: 0, // Will be filled in later by storage::new_totp_record();
-                    notes: t!("vault.notes", locales::LANG).to_string(),
+                    notes: t!("vault.notes", locales::lang()).to_string(),
                     is_hotp: false,
+                    scheme,
+                    pin: elem.pin,
                 };
                 entries.push(Box::new(totp));
             }
@@ -330,6 +339,11 @@ fn handle_backup(xns: &xous_names::XousNames, session: &mut VendorSession) -> Re
                     },
                     name: raw_code.name,
                     hotp: raw_code.is_hotp,
+                    scheme: match raw_code.scheme {
+                        OtpScheme::Rfc => String::new(),
+                        scheme => scheme.to_string(),
+                    },
+                    pin: raw_code.pin,
                 });
             }
 
@@ -0,0 +1,88 @@
+#!/usr/bin/env python3
+"""Host side of the vault's "Import/Export over USB serial" menu items.
+
+  serial_transfer.py send <file> [--format auto|kdbx|otpauth|csv]
+  serial_transfer.py receive <output.kdbx>
+
+Start `send` after picking "Import" on the device, and `receive` before confirming "Export".
+The framing is documented in apps/vault/src/import_export.rs.
+"""
+
+import argparse
+import base64
+import binascii
+import hashlib
+import re
+import struct
+import sys
+
+import serial
+
+MAGIC = b"VAULTIMP"
+CHUNK = 128  # SERIAL_BINARY_BUFLEN on the device
+MAX_LEN = 2 * 1024 * 1024
+FORMATS = {"auto": 0, "kdbx": 1, "otpauth": 2, "csv": 3}
+BASE64_LINE = re.compile(rb"^[A-Za-z0-9+/]+={0,2}$")
+
+
+def send(port, path, fmt):
+    with open(path, "rb") as f:
+        data = f.read()
+    if len(data) == 0 or len(data) > MAX_LEN:
+        sys.exit("file must be between 1 byte and {} bytes".format(MAX_LEN))
+    frame = MAGIC + struct.pack("<II", len(data), FORMATS[fmt]) + data
+    # the device only sees full buffers, so pad the tail out
+    frame += bytes(-len(frame) % CHUNK)
+    for offset in range(0, len(frame), CHUNK):
+        port.write(frame[offset:offset + CHUNK])
+        print("\r{}%".format((offset + CHUNK) * 100 // len(frame)), end="", flush=True)
+    port.flush()
+    print("\nsent {} bytes; confirm on the device".format(len(data)))
+
+
+def receive(port, path):
+    print("waiting for export; confirm on the device")
+    body = []
+    inside = False
+    while True:
+        line = port.readline().strip()
+        if line.startswith(b"-----BEGIN VAULT EXPORT"):
+            inside = True
+            body = []
+        elif line.startswith(b"-----END VAULT EXPORT") and inside:
+            digest = line[len(b"-----END VAULT EXPORT "):].rstrip(b"-").decode()
+            break
+        elif inside and BASE64_LINE.match(line):
+            body.append(line)
+        # anything else is log output sharing the port
+    try:
+        data = base64.b64decode(b"".join(body))
+    except binascii.Error:
+        sys.exit("export was garbled in transit; try again")
+    if hashlib.sha256(data).hexdigest() != digest:
+        sys.exit("checksum mismatch; try again")
+    with open(path, "wb") as f:
+        f.write(data)
+    print("wrote {} bytes to {} (sha256 {})".format(len(data), path, digest))
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__, formatter_class=argparse.RawDescriptionHelpFormatter)
+    parser.add_argument("--port", default="/dev/ttyACM0", help="serial device of the Precursor")
+    sub = parser.add_subparsers(dest="command", required=True)
+    s = sub.add_parser("send", help="import a file into the vault")
+    s.add_argument("file")
+    s.add_argument("--format", choices=FORMATS.keys(), default="auto")
+    r = sub.add_parser("receive", help="save an export from the vault")
+    r.add_argument("file")
+    args = parser.parse_args()
+
+    with serial.Serial(args.port, 115200, timeout=None) as port:
+        if args.command == "send":
+            send(port, args.file, args.format)
+        else:
+            receive(port, args.file)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,113 @@
+#!/usr/bin/env python3
+"""Host side of the vault's SSH agent: serves SSH_AUTH_SOCK and forwards every request over USB serial.
+
+  ssh_agent_bridge.py [--port /dev/ttyACM0] [--socket PATH]
+
+Start the agent from the vault's "SSH keys" menu, run this, then point ssh at the socket it prints:
+
+  export SSH_AUTH_SOCK=/tmp/precursor-agent.sock
+  ssh-add -L   # public keys, for authorized_keys
+  ssh host     # confirm the signature on the device
+
+The framing is documented in apps/vault/src/ssh_agent.rs. Requests are handled one at a time, since the
+device asks for confirmation of each signature anyway.
+"""
+
+import argparse
+import base64
+import binascii
+import os
+import socket
+import struct
+import sys
+import threading
+
+import serial
+
+MAGIC = b"SSHAGENT"
+CHUNK = 128  # SERIAL_BINARY_BUFLEN on the device
+MAX_LEN = 16 * 1024
+SSH_AGENT_FAILURE = b"\x05"
+
+
+class Device:
+    def __init__(self, port):
+        self.port = port
+        self.seq = 0
+        self.lock = threading.Lock()
+
+    def request(self, msg):
+        with self.lock:
+            self.seq = (self.seq + 1) & 0xFFFFFFFF
+            frame = MAGIC + struct.pack("<II", len(msg), self.seq) + msg
+            # the device only sees full buffers, so pad the tail out
+            frame += bytes(-len(frame) % CHUNK)
+            self.port.write(frame)
+            self.port.flush()
+            tag = "SSHAGENT {} ".format(self.seq).encode()
+            while True:
+                line = self.port.readline().strip()
+                # anything else is log output sharing the port, or a reply we already gave up on
+                if line.startswith(tag):
+                    try:
+                        return base64.b64decode(line[len(tag):], validate=True)
+                    except binascii.Error:
+                        return SSH_AGENT_FAILURE
+
+
+def read_exact(conn, n):
+    data = b""
+    while len(data) < n:
+        chunk = conn.recv(n - len(data))
+        if not chunk:
+            return None
+        data += chunk
+    return data
+
+
+def serve_client(conn, device):
+    with conn:
+        while True:
+            header = read_exact(conn, 4)
+            if header is None:
+                return
+            (length,) = struct.unpack(">I", header)
+            if length == 0 or length > MAX_LEN:
+                return
+            msg = read_exact(conn, length)
+            if msg is None:
+                return
+            reply = device.request(msg)
+            conn.sendall(struct.pack(">I", len(reply)) + reply)
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__, formatter_class=argparse.RawDescriptionHelpFormatter)
+    parser.add_argument("--port", default="/dev/ttyACM0", help="serial device of the Precursor")
+    parser.add_argument("--socket", default="/tmp/precursor-agent.sock", help="path for SSH_AUTH_SOCK")
+    args = parser.parse_args()
+
+    if os.path.exists(args.socket):
+        os.unlink(args.socket)
+    listener = socket.socket(socket.AF_UNIX, socket.SOCK_STREAM)
+    old_umask = os.umask(0o177)  # the socket is as good as the keys, keep it to ourselves
+    listener.bind(args.socket)
+    os.umask(old_umask)
+    listener.listen()
+    print("export SSH_AUTH_SOCK={}".format(args.socket))
+
+    with serial.Serial(args.port, 115200, timeout=None) as port:
+        device = Device(port)
+        try:
+            while True:
+                conn, _ = listener.accept()
+                threading.Thread(target=serve_client, args=(conn, device), daemon=True).start()
+        except KeyboardInterrupt:
+            pass
+        finally:
+            listener.close()
+            os.unlink(args.socket)
+
+
+if __name__ == "__main__":
+    sys.exit(main())
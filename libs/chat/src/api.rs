@@ -49,6 +49,12 @@ pub enum ChatOp {
     UpdateBusyForced,
     /// exit the application
     Quit,
+    /// Select the Posts matching a search, so that ↑ & ↓ step thru them
+    PostSearch,
+    /// Take the Post the user chose to reply to, if any
+    ReplyTo,
+    /// Add a new Post with an attachment, or replying to an earlier Post, to the Dialogue
+    PostAddExtra,
 }
 
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
@@ -89,6 +95,28 @@ pub struct Post {
     pub attach_url: Option<String>,
 }
 
+/// A Post with what a plain Post can't carry
+#[derive(Archive, Serialize, Deserialize, Debug)]
+pub struct PostExtra {
+    pub post: Post,
+    pub attach: Option<Attachment>,
+    pub reply_to: Option<ReplyTo>,
+}
+
+/// A file to attach to a Post
+#[derive(Archive, Serialize, Deserialize, Debug)]
+pub struct Attachment {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// The Post that a reply answers
+#[derive(Archive, Serialize, Deserialize, Debug, Clone)]
+pub struct ReplyTo {
+    pub author: String,
+    pub timestamp: u64,
+}
+
 /// Events are sent to the Chat App when key things occur in the Chat UI
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
 pub enum Event {
@@ -6,16 +6,19 @@ use core::slice::{Iter, IterMut};
 use std::collections::HashMap;
 use std::io::{Error, ErrorKind};
 
+use attach::AttachFile;
 use author::Author;
 use gam::Gam;
-use post::Post;
+use post::{Extra, Post, Reply};
 use rkyv::{Archive, Deserialize, Serialize};
 
 use crate::ui::VisualProperties;
-use crate::{default_textview, now};
+use crate::{PostFlag, default_textview, now};
 
 // TODO do better than just allocate lots!
 pub const MAX_BYTES: usize = 65536;
+/// how much of a Post a reply quotes
+const QUOTE_CHARS: usize = 40;
 
 /// A Dialogue is a generic representation of a series of Posts
 /// This might represent a room, group, or direct-message conversation
@@ -66,21 +69,25 @@ impl Dialogue {
     /// * `author` - the name of the Author of the Post
     /// * `timestamp` - the timestamp of the Post
     /// * `text` - the text content of the Post
-    /// * `attach_url` - a url of an attachment (image for example)
+    /// * `attach` - a file attached to the Post, already saved in the pddb
+    /// * `reply_to` - the author and timestamp of the Post this one replies to
+    /// * `extras` - where the attachment and reply are kept
     /// * `vp` - the visual properties of the system - so that we can pre-compute the size extents of the post
     pub fn post_add(
         &mut self,
         author: &str,
         timestamp: u64,
         text: &str,
-        _attach_url: Option<&str>,
+        attach: Option<AttachFile>,
+        reply_to: Option<(&str, u64)>,
+        extras: &mut Extras,
         vp: Option<(&VisualProperties, &Gam)>,
     ) -> Result<(), Error> {
+        let reply = reply_to.map(|(author, timestamp)| self.reply(author, timestamp));
         match self.author_id(author) {
             Some(author_id) => {
-                let mut new = Post::new(
-                    author_id, timestamp, text, None, // TODO implement
-                );
+                extras.set(author_id, timestamp, attach, reply);
+                let mut new = Post::new(author_id, timestamp, text, None);
                 if self.posts.len() == 0 {
                     self.posts.push(new);
                     return Ok(());
@@ -92,7 +99,8 @@ impl Dialogue {
 
                 // compute the bounds for the post if visual properties are specified
                 if let Some((vp, gam)) = vp {
-                    let mut layout_bubble = default_textview(&new, false, vp);
+                    let mut layout_bubble =
+                        default_textview(&new, extras.get(author_id, timestamp), false, vp);
                     log::debug!("Computing bounds on {:?}", layout_bubble);
                     if gam.bounds_compute_textview(&mut layout_bubble).is_ok() {
                         new.bounding_box = layout_bubble.bounds_computed;
@@ -141,22 +149,60 @@ impl Dialogue {
     pub fn post_find(&self, author: &str, timestamp: u64) -> Option<usize> {
         if let Some(author_id) = self.author_lookup.get(author) {
             let i = self.posts.partition_point(|p| p.timestamp() < timestamp);
-            let last = self.posts.len() - 1;
-            for n in i..last {
-                if let Some(post) = self.posts.get(n) {
-                    if post.timestamp() == timestamp {
-                        if post.author_id() == *author_id {
-                            return Some(n);
-                        }
-                    } else {
-                        break;
-                    }
+            for (n, post) in self.posts.iter().enumerate().skip(i) {
+                if post.timestamp() != timestamp {
+                    break;
+                }
+                if post.author_id() == *author_id {
+                    return Some(n);
                 }
             }
         }
         None
     }
 
+    /// Returns the indexes of the Posts whose text or attachment name contains `query`, ignoring case,
+    /// oldest first. Deleted and hidden Posts are left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - the text to look for
+    /// * `extras` - the attachments of the Posts
+    pub fn search(&self, query: &str, extras: &Extras) -> Vec<usize> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        self.posts
+            .iter()
+            .enumerate()
+            .filter(|(_, post)| !post.flag_is(PostFlag::Deleted) && !post.flag_is(PostFlag::Hidden))
+            .filter(|(_, post)| {
+                post.text().to_lowercase().contains(&query)
+                    || extras
+                        .get(post.author_id(), post.timestamp())
+                        .and_then(|extra| extra.attach.as_ref())
+                        .is_some_and(|attach| attach.name.to_lowercase().contains(&query))
+            })
+            .map(|(n, _)| n)
+            .collect()
+    }
+
+    /// What a reply to the Post by `author` at `timestamp` shows of it.
+    fn reply(&self, author: &str, timestamp: u64) -> Reply {
+        let quote = match self.post_find(author, timestamp).and_then(|n| self.post_get(n)) {
+            Some(post) => {
+                let mut quote: String = post.text().chars().take(QUOTE_CHARS).collect();
+                if post.text().chars().count() > QUOTE_CHARS {
+                    quote.push('…');
+                }
+                quote
+            }
+            None => String::new(),
+        };
+        Reply { author: author.to_string(), timestamp, quote }
+    }
+
     /// Return Some<Post> by index in the Dialogue, or None.
     ///
     /// # Arguments
@@ -217,3 +263,142 @@ impl Dialogue {
         }
     }
 }
+
+/// The attachments and replies of the Posts in a Dialogue.
+///
+/// These are saved under a pddb key of their own, beside the Dialogue, so that Dialogues and their Posts
+/// keep the layout of those saved before Posts had them.
+#[derive(Archive, Serialize, Deserialize, Debug, Default)]
+pub struct Extras {
+    /// in timestamp order, like the Posts
+    extras: Vec<Extra>,
+}
+
+impl Extras {
+    /// Return Some<Extra> of the Post by an Author at a timestamp, or None.
+    ///
+    /// # Arguments
+    ///
+    /// * `author_id` - the id of the Author of the Post
+    /// * `timestamp` - the timestamp of the Post
+    pub fn get(&self, author_id: u16, timestamp: u64) -> Option<&Extra> {
+        let i = self.extras.partition_point(|e| e.timestamp < timestamp);
+        self.extras[i..].iter().take_while(|e| e.timestamp == timestamp).find(|e| e.author_id == author_id)
+    }
+
+    /// Set the attachment and reply of the Post by an Author at a timestamp, replacing any it had.
+    ///
+    /// # Arguments
+    ///
+    /// * `author_id` - the id of the Author of the Post
+    /// * `timestamp` - the timestamp of the Post
+    /// * `attach` - a file attached to the Post
+    /// * `reply` - the Post this one replies to
+    pub fn set(&mut self, author_id: u16, timestamp: u64, attach: Option<AttachFile>, reply: Option<Reply>) {
+        let i = self.extras.partition_point(|e| e.timestamp < timestamp);
+        let old = self.extras[i..]
+            .iter()
+            .take_while(|e| e.timestamp == timestamp)
+            .position(|e| e.author_id == author_id)
+            .map(|n| i + n);
+        let new = match (attach, reply) {
+            (None, None) => None,
+            (attach, reply) => Some(Extra { author_id, timestamp, attach, reply }),
+        };
+        match (old, new) {
+            (Some(n), Some(new)) => self.extras[n] = new,
+            (Some(n), None) => {
+                self.extras.remove(n);
+            }
+            (None, Some(new)) => self.extras.insert(i, new),
+            (None, None) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dialogue() -> (Dialogue, Extras) {
+        let mut dialogue = Dialogue::new("#test:x");
+        let mut extras = Extras::default();
+        dialogue
+            .post_add(
+                "alice",
+                10,
+                "Hello world, how is everyone this fine morning?",
+                None,
+                None,
+                &mut extras,
+                None,
+            )
+            .unwrap();
+        dialogue.post_add("bob", 20, "hi alice", None, None, &mut extras, None).unwrap();
+        let attach = AttachFile::new("World.png", "20/World.png/#test:x", b"\x89PNG\r\n\x1a\n");
+        dialogue.post_add("bob", 30, "a picture", Some(attach), None, &mut extras, None).unwrap();
+        (dialogue, extras)
+    }
+
+    #[test]
+    fn test_post_find() {
+        let (dialogue, _) = dialogue();
+        assert_eq!(dialogue.post_find("alice", 10), Some(0));
+        // the most recent Post is found too
+        assert_eq!(dialogue.post_find("bob", 30), Some(2));
+        assert_eq!(dialogue.post_find("alice", 20), None);
+        assert_eq!(dialogue.post_find("trent", 10), None);
+        assert_eq!(Dialogue::new("empty").post_find("alice", 10), None);
+    }
+
+    #[test]
+    fn test_search() {
+        let (mut dialogue, extras) = dialogue();
+        assert_eq!(dialogue.search("WORLD", &extras), [0, 2]);
+        assert_eq!(dialogue.search(" alice ", &extras), [1]);
+        assert!(dialogue.search("", &extras).is_empty());
+        assert!(dialogue.search("nobody", &extras).is_empty());
+        let hidden = dialogue.posts_as_slice_mut().first_mut().unwrap();
+        hidden.flags_set(PostFlag::Hidden.into());
+        assert_eq!(dialogue.search("world", &extras), [2]);
+    }
+
+    #[test]
+    fn test_reply() {
+        let (mut dialogue, mut extras) = dialogue();
+        dialogue.post_add("trent", 40, "me too", None, Some(("alice", 10)), &mut extras, None).unwrap();
+        dialogue.post_add("trent", 50, "what?", None, Some(("alice", 5)), &mut extras, None).unwrap();
+        let extra_of = |n: usize| {
+            let post = dialogue.post_get(n).unwrap();
+            extras.get(post.author_id(), post.timestamp())
+        };
+        let reply = extra_of(3).unwrap().reply.as_ref().unwrap();
+        assert_eq!((reply.author.as_str(), reply.timestamp), ("alice", 10));
+        assert_eq!(reply.quote, "Hello world, how is everyone this fine m…");
+        // the Post replied to may be older than the Dialogue
+        let reply = extra_of(4).unwrap().reply.as_ref().unwrap();
+        assert_eq!(reply.quote, "");
+        assert!(extra_of(1).is_none());
+        assert_eq!(extra_of(2).unwrap().attach.as_ref().unwrap().name, "World.png");
+    }
+
+    #[test]
+    fn test_extras() {
+        let mut extras = Extras::default();
+        let reply =
+            |author: &str| Some(Reply { author: author.to_string(), timestamp: 1, quote: String::new() });
+        extras.set(1, 30, None, reply("a"));
+        extras.set(1, 10, None, reply("b"));
+        extras.set(2, 10, None, reply("c"));
+        assert_eq!(extras.get(1, 10).unwrap().reply.as_ref().unwrap().author, "b");
+        assert_eq!(extras.get(2, 10).unwrap().reply.as_ref().unwrap().author, "c");
+        assert!(extras.get(3, 10).is_none());
+        // a Post replaced by one with no attachment or reply has none
+        extras.set(1, 10, None, reply("d"));
+        assert_eq!(extras.get(1, 10).unwrap().reply.as_ref().unwrap().author, "d");
+        extras.set(1, 10, None, None);
+        assert!(extras.get(1, 10).is_none());
+        assert_eq!(extras.get(1, 30).unwrap().reply.as_ref().unwrap().author, "a");
+        assert_eq!(extras.extras.len(), 2);
+    }
+}
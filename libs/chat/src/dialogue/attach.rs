@@ -7,3 +7,116 @@ pub enum Attach {
 }
 
 impl Attach {}
+
+/// The longest pddb key name
+const KEY_NAME_MAX: usize = 95;
+
+#[derive(Archive, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Png,
+    Jpg,
+    File,
+}
+
+/// A file attached to a Post.
+///
+/// The bytes are kept in their own pddb key, apart from the Dialogue, so that the Dialogue stays small
+/// enough to read in one go. The Dialogue's `Extras` hold these, as `Attach` is part of the
+/// layout of saved Posts.
+#[derive(Archive, Serialize, Deserialize, Debug)]
+pub struct AttachFile {
+    /// the name of the file, as shown
+    pub name: String,
+    /// the pddb key holding the bytes
+    pub key: String,
+    pub kind: Kind,
+    /// the number of bytes
+    pub len: usize,
+}
+
+impl AttachFile {
+    /// Describe an attachment, telling images from other files by their first bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the name of the file
+    /// * `key` - the pddb key holding the bytes
+    /// * `data` - the bytes of the file
+    pub fn new(name: &str, key: &str, data: &[u8]) -> Self {
+        let kind = if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Kind::Png
+        } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Kind::Jpg
+        } else {
+            Kind::File
+        };
+        Self { name: name.to_string(), key: key.to_string(), kind, len: data.len() }
+    }
+
+    pub fn is_image(&self) -> bool { self.kind != Kind::File }
+
+    /// The size of the attachment, for people to read.
+    pub fn size(&self) -> String {
+        match self.len {
+            len if len < 1024 => format!("{} B", len),
+            len if len < 1024 * 1024 => format!("{} kB", len / 1024),
+            len => format!("{} MB", len / (1024 * 1024)),
+        }
+    }
+
+    /// The pddb key to keep an attachment under: unique to the Dialogue, the Post and the name, and
+    /// truncated to fit a pddb key.
+    ///
+    /// # Arguments
+    ///
+    /// * `dialogue` - the pddb key of the Dialogue
+    /// * `timestamp` - the timestamp of the Post
+    /// * `name` - the name of the file
+    pub fn key(dialogue: &str, timestamp: u64, name: &str) -> String {
+        let mut key = format!("{}/{}/{}", timestamp, name, dialogue);
+        if key.len() > KEY_NAME_MAX {
+            let mut end = KEY_NAME_MAX;
+            while !key.is_char_boundary(end) {
+                end -= 1;
+            }
+            key.truncate(end);
+        }
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind() {
+        let png = AttachFile::new("cat.png", "k", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR");
+        assert_eq!(png.kind, Kind::Png);
+        let jpg = AttachFile::new("cat.jpg", "k", &[0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10]);
+        assert_eq!(jpg.kind, Kind::Jpg);
+        let txt = AttachFile::new("notes.png", "k", b"not really a png");
+        assert_eq!(txt.kind, Kind::File);
+        assert!(png.is_image() && jpg.is_image() && !txt.is_image());
+        assert_eq!(txt.len, 16);
+    }
+
+    #[test]
+    fn test_size() {
+        let mut attach = AttachFile::new("a", "k", &[0; 512]);
+        assert_eq!(attach.size(), "512 B");
+        attach.len = 3 * 1024 + 7;
+        assert_eq!(attach.size(), "3 kB");
+        attach.len = 5 * 1024 * 1024;
+        assert_eq!(attach.size(), "5 MB");
+    }
+
+    #[test]
+    fn test_key() {
+        assert_eq!(AttachFile::key("#room:x", 1_700_000_000, "cat.png"), "1700000000/cat.png/#room:x");
+        let long = "é".repeat(80);
+        let key = AttachFile::key("#room:x", 1, &long);
+        assert!(key.len() <= KEY_NAME_MAX);
+        assert!(key.starts_with("1/éé"));
+    }
+}
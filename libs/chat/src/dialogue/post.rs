@@ -2,9 +2,28 @@ use enumset::EnumSet;
 use graphics_server::Rectangle;
 use rkyv::{Archive, Deserialize, Serialize};
 
-use super::attach::Attach;
+use super::attach::{Attach, AttachFile};
 use crate::PostFlag;
 
+/// The Post that a reply answers, with enough of it to quote
+#[derive(Archive, Serialize, Deserialize, Debug)]
+pub struct Reply {
+    pub author: String,
+    pub timestamp: u64,
+    /// the start of the text replied to, or empty if that Post isn't in the Dialogue
+    pub quote: String,
+}
+
+/// What a Post has beyond its text, kept apart from the Post so that saved Posts keep their layout.
+/// The Post is the one by `author_id` at `timestamp`.
+#[derive(Archive, Serialize, Deserialize, Debug)]
+pub struct Extra {
+    pub author_id: u16,
+    pub timestamp: u64,
+    pub attach: Option<AttachFile>,
+    pub reply: Option<Reply>,
+}
+
 #[derive(Archive, Serialize, Deserialize, Debug)]
 pub struct Post {
     author_id: u16,
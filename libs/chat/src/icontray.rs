@@ -1,3 +1,5 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
 
 use ime_plugin_api::*;
@@ -10,21 +12,28 @@ pub(crate) const SERVER_NAME_ICONTRAY: &'static str = "_chat icon tray plugin_";
 #[allow(dead_code)]
 pub struct Icontray {
     cid: Option<CID>,
+    predicting: Arc<AtomicBool>,
 }
 
 impl Icontray {
     pub fn new(cid: Option<CID>, icons: [&'static str; 4]) -> Self {
         log::info!("Starting icontray handler server",);
+        let predicting = Arc::new(AtomicBool::new(false));
         let _ = thread::spawn({
+            let predicting = predicting.clone();
             move || {
-                server(cid, icons);
+                server(cid, icons, predicting);
             }
         });
-        Icontray { cid }
+        Icontray { cid, predicting }
     }
+
+    /// True while the F-key slots hold completions of the word being typed, rather than the icons. The IME
+    /// inserts the completion when an F-key is hit, so it shouldn't also act as a button.
+    pub fn predicting(&self) -> bool { self.predicting.load(Ordering::SeqCst) }
 }
 
-pub(crate) fn server(_cid: Option<CID>, icons: [&str; 4]) {
+pub(crate) fn server(_cid: Option<CID>, icons: [&str; 4], predicting: Arc<AtomicBool>) {
     let xns = xous_names::XousNames::new().unwrap();
     // one connection only, should be the GAM
     // however, because the predictor is connected only on demand -- we leave this as open-ended, which
@@ -32,7 +41,17 @@ pub(crate) fn server(_cid: Option<CID>, icons: [&str; 4]) {
 
     let ime_sh_sid = xns.register_name(SERVER_NAME_ICONTRAY, None).expect("can't register server");
 
-    let mytriggers = PredictionTriggers { newline: false, punctuation: false, whitespace: false };
+    // while a word is typed, the word predictor's completions take the place of the icons
+    let predictor = PredictionPlugin {
+        connection: Some(
+            xns.request_connection_blocking(ime_plugin_predict::SERVER_NAME_IME_PLUGIN_PREDICT)
+                .expect("can't connect to the word predictor"),
+        ),
+    };
+    let mytriggers = predictor.get_prediction_triggers().expect("couldn't get the word predictor's triggers");
+    // set while the word predictor is locked on our behalf
+    let mut predictor_token: Option<[u32; 4]> = None;
+    let mut word = String::new();
 
     let mut api_token: Option<[u32; 4]> = None;
     loop {
@@ -51,6 +70,7 @@ pub(crate) fn server(_cid: Option<CID>, icons: [&str; 4]) {
                         ret.token = Some(new_token);
                         api_token = Some(new_token);
                     }
+                    predictor_token = predictor.acquire(api_token).ok();
                 } else {
                     ret.token = None;
                     log::warn!("attempt to acquire lock on a predictor that was already locked");
@@ -62,6 +82,11 @@ pub(crate) fn server(_cid: Option<CID>, icons: [&str; 4]) {
                 if let Some(t) = api_token {
                     if t == token {
                         api_token.take();
+                        if let Some(t) = predictor_token.take() {
+                            predictor.release(t);
+                        }
+                        word.clear();
+                        predicting.store(false, Ordering::SeqCst);
                     } else {
                         log::warn!("Release called with an invalid token");
                     }
@@ -70,29 +95,59 @@ pub(crate) fn server(_cid: Option<CID>, icons: [&str; 4]) {
                 }
             }),
             Some(Opcode::Input) => {
-                log::trace!("got chat icontray Opcode::Input");
+                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                word.clear();
+                word.push_str(buffer.as_flat::<String, _>().unwrap().as_str());
+                if predictor_token.is_none() && api_token.is_some() {
+                    // the lock may have still been held by whoever had the input before us
+                    predictor_token = predictor.acquire(api_token).ok();
+                }
+                if predictor_token.is_some() {
+                    predictor.set_input(word.to_string()).ok();
+                }
             }
             Some(Opcode::Picked) => {
-                log::trace!("got chat icontray Opcode::Picked");
+                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                if predictor_token.is_some() {
+                    predictor
+                        .feedback_picked(buffer.as_flat::<String, _>().unwrap().as_str().to_string())
+                        .ok();
+                }
+                // the word is finished, so the icons come back
+                word.clear();
             }
             Some(Opcode::Prediction) => {
-                // we don't check the API token here, because our "predictions" are just the four menu slots
                 let mut buffer =
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let mut prediction: Prediction = buffer.to_original::<Prediction, _>().unwrap();
                 // every key press, the four slots get queried
                 prediction.string.clear();
-                if prediction.index < icons.len() as u32 {
-                    prediction.string.push_str(icons[prediction.index as usize]);
-                    prediction.valid = true;
-                } else {
-                    prediction.valid = false;
+                prediction.valid = false;
+                if word.is_empty() {
+                    // the icons are the same for everyone, so the API token isn't checked for them
+                    if prediction.index < icons.len() as u32 {
+                        prediction.string.push_str(icons[prediction.index as usize]);
+                        prediction.valid = true;
+                    }
+                } else if api_token == Some(prediction.api_token) {
+                    if let Some(t) = predictor_token {
+                        if let Ok(Some(completion)) = predictor.get_prediction(prediction.index, t) {
+                            prediction.string.push_str(&completion);
+                            prediction.valid = true;
+                        }
+                    }
+                }
+                if prediction.index == 0 {
+                    predicting.store(!word.is_empty() && prediction.valid, Ordering::SeqCst);
                 }
                 // pack our data back into the buffer to return
                 buffer.replace(Return::Prediction(prediction)).expect("couldn't return Prediction");
             }
             Some(Opcode::Unpick) => {
-                // ignore
+                if predictor_token.is_some() {
+                    predictor.unpick().ok();
+                }
+                word.clear();
             }
             Some(Opcode::GetPredictionTriggers) => {
                 xous::return_scalar(msg.sender, mytriggers.into())
@@ -10,8 +10,9 @@ use std::time::{SystemTime, UNIX_EPOCH};
 
 pub use api::*;
 use gam::MenuItem;
-use graphics_server::api::GlyphStyle;
+use graphics_server::api::{GlyphStyle, MAX_TEXT_SPANS, Markdown};
 use graphics_server::{Point, Rectangle, TextBounds, TextView};
+use locales::t;
 use num_traits::FromPrimitive;
 pub use ui::BUSY_ANIMATION_RATE_MS;
 use ui::VisualProperties;
@@ -21,6 +22,7 @@ use xous_ipc::Buffer;
 /// Create a TextView with the default properties common to all text bubbles.
 pub(crate) fn default_textview(
     post: &crate::dialogue::post::Post,
+    extra: Option<&crate::dialogue::post::Extra>,
     hilite: bool,
     vp: &VisualProperties,
 ) -> TextView {
@@ -43,7 +45,37 @@ pub(crate) fn default_textview(
     bubble_tv.margin = vp.bubble_margin;
     bubble_tv.ellipsis = false;
     bubble_tv.insertion = None;
-    write!(bubble_tv.text, "{}", post.text()).expect("couldn't write history text to TextView");
+    if let Some(reply) = extra.and_then(|extra| extra.reply.as_ref()) {
+        writeln!(bubble_tv.text, "> {}: {}", reply.author, reply.quote)
+            .expect("couldn't write reply to TextView");
+    }
+    // posts are read as lightweight markdown, with the spans moved along past the reply quote
+    let md = Markdown::parse(post.text());
+    let offset = bubble_tv.text.chars().count() as u32;
+    bubble_tv.text.push_str(&md.text);
+    bubble_tv.spans = md
+        .spans
+        .into_iter()
+        .take(MAX_TEXT_SPANS)
+        .map(|mut span| {
+            span.start += offset;
+            span.end += offset;
+            span
+        })
+        .collect();
+    if let Some(attach) = extra.and_then(|extra| extra.attach.as_ref()) {
+        let label = if attach.is_image() {
+            t!("chat.attach.image", locales::lang())
+        } else {
+            t!("chat.attach.file", locales::lang())
+        };
+        write!(
+            bubble_tv.text,
+            "\n{}",
+            label.replace("{name}", &attach.name).replace("{size}", &attach.size())
+        )
+        .expect("couldn't write attachment to TextView");
+    }
     bubble_tv
 }
 
@@ -54,6 +86,7 @@ pub(crate) fn default_textview(
 /// * `vp` - the visual properties to be applied to the textview
 /// * `topdown` - direction of the layout
 /// * `post` - the post to represent in a TextView bubble
+/// * `extra` - the attachment and reply of the Post, if any
 /// * `dialogue` - containing the Post for context info
 /// * `hilite` - hilite this Post on the screen (thicker border)
 /// * `anchor_y` - the vertical position on screen to draw TextView bubble
@@ -61,12 +94,13 @@ fn bubble(
     vp: &VisualProperties,
     topdown: bool,
     post: &crate::dialogue::post::Post,
+    extra: Option<&crate::dialogue::post::Extra>,
     dialogue: &crate::dialogue::Dialogue,
     hilite: bool,
     anchor_y: i16,
 ) -> TextView {
     // create a textview with all of our default properties
-    let mut bubble_tv = default_textview(post, hilite, vp);
+    let mut bubble_tv = default_textview(post, extra, hilite, vp);
 
     // set alignment of bubble left/right
     let mut align_right = false;
@@ -239,6 +273,70 @@ impl Chat {
         }
     }
 
+    /// Add a new Post with a file attached to the current Dialogue
+    ///
+    /// The file is saved in the pddb apart from the Dialogue. Images can be viewed from the Post menu.
+    ///
+    /// # Arguments
+    ///
+    /// * `author` - the name of the Author of the Post
+    /// * `timestamp` - the timestamp of the Post
+    /// * `text` - the text content of the Post
+    /// * `name` - the name of the file
+    /// * `data` - the content of the file
+    pub fn post_attach(
+        &self,
+        author: &str,
+        timestamp: u64,
+        text: &str,
+        name: &str,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let attach = Some(Attachment { name: String::from(name), data: data.to_vec() });
+        self.post_extra_send(author, timestamp, text, attach, None)
+    }
+
+    /// Add a new Post replying to an earlier Post to the current Dialogue
+    ///
+    /// The reply is shown below a quote of the start of the earlier Post, if it is in the Dialogue.
+    ///
+    /// # Arguments
+    ///
+    /// * `author` - the name of the Author of the Post
+    /// * `timestamp` - the timestamp of the Post
+    /// * `text` - the text content of the Post
+    /// * `reply_to` - the Author and timestamp of the earlier Post
+    pub fn post_reply(
+        &self,
+        author: &str,
+        timestamp: u64,
+        text: &str,
+        reply_to: ReplyTo,
+    ) -> Result<(), Error> {
+        self.post_extra_send(author, timestamp, text, None, Some(reply_to))
+    }
+
+    fn post_extra_send(
+        &self,
+        author: &str,
+        timestamp: u64,
+        text: &str,
+        attach: Option<Attachment>,
+        reply_to: Option<ReplyTo>,
+    ) -> Result<(), Error> {
+        let post = api::Post {
+            dialogue_id: String::new(),
+            author: String::from(author),
+            timestamp,
+            text: String::from(text),
+            attach_url: None,
+        };
+        match Buffer::into_buf(PostExtra { post, attach, reply_to }) {
+            Ok(buf) => buf.send(self.cid, ChatOp::PostAddExtra as u32).map(|_| ()),
+            Err(_) => Err(xous::Error::InternalError),
+        }
+    }
+
     /// Delete a Post from the current Dialogue
     ///
     /// # Arguments
@@ -282,6 +380,35 @@ impl Chat {
         Err(xous::Error::InternalError)
     }
 
+    /// Select the Posts in the current Dialogue containing `query`, so that the ↑ & ↓ keys step thru them
+    /// rather than thru every Post. An empty `query` ends the search.
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - the text to look for, ignoring case
+    pub fn post_search(&self, query: &str) -> Result<(), Error> {
+        match Buffer::into_buf(String::from(query)) {
+            Ok(buf) => buf.send(self.cid, ChatOp::PostSearch as u32).map(|_| ()),
+            Err(_) => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Returns the Post the user chose to reply to from the Post menu, or None
+    ///
+    /// A Chat App calls this on receiving a new user Post, to send it as a reply. The choice is cleared,
+    /// so the next Post is not a reply unless the user chooses again.
+    ///
+    /// Error if unable to send the msg to the Chat UI server
+    pub fn reply_to(&self) -> Result<Option<ReplyTo>, Error> {
+        match Buffer::into_buf(None::<ReplyTo>) {
+            Ok(mut buf) => match buf.lend_mut(self.cid, ChatOp::ReplyTo as u32) {
+                Ok(..) => Ok(buf.to_original::<Option<ReplyTo>, _>().unwrap()),
+                Err(_) => Err(xous::Error::InternalError),
+            },
+            Err(_) => Err(xous::Error::InternalError),
+        }
+    }
+
     /// Redraw our Chat UI.
     pub fn redraw(&self) {
         xous::send_message(self.cid, xous::Message::new_scalar(ChatOp::GamRedraw as usize, 0, 0, 0, 0))
@@ -511,6 +638,8 @@ pub fn server(
                 xous::msg_scalar_unpack!(msg, k1, k2, k3, k4, {
                     log::info!("got Chat UI RawKey :{}:{}:{}:{}:", k1, k2, k3, k4);
                     match core::char::from_u32(k1 as u32).unwrap_or('\u{0000}') {
+                        // the IME has already put the completion in the input line
+                        F1 | F2 | F3 | F4 if ui.predicting() => {}
                         F1 => {
                             log::info!("click F1 : pull request welcome!");
                             ui.event(Event::F1);
@@ -589,7 +718,8 @@ pub fn server(
                                     post.author.as_str(),
                                     post.timestamp,
                                     post.text.as_str(),
-                                    None, // TODO implement
+                                    None,
+                                    None,
                                 )
                                 .unwrap(),
                             Err(e) => log::warn!("failed to deserialize Post: {:?}", e),
@@ -598,6 +728,29 @@ pub fn server(
                     None => log::warn!("failed to PostAdd with Dialogue == None"),
                 }
             }
+            Some(ChatOp::PostAddExtra) => {
+                log::info!("ChatOp::PostAddExtra");
+                match dialogue_key {
+                    Some(ref dialogue_id) => {
+                        let buffer =
+                            unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                        match buffer.to_original::<PostExtra, _>() {
+                            Ok(extra) => ui
+                                .post_add(
+                                    &dialogue_id,
+                                    extra.post.author.as_str(),
+                                    extra.post.timestamp,
+                                    extra.post.text.as_str(),
+                                    extra.attach,
+                                    extra.reply_to,
+                                )
+                                .unwrap(),
+                            Err(e) => log::warn!("failed to deserialize PostExtra: {:?}", e),
+                        }
+                    }
+                    None => log::warn!("failed to PostAddExtra with Dialogue == None"),
+                }
+            }
             Some(ChatOp::PostDel) => {
                 xous::msg_scalar_unpack!(msg, index, _, _, _, {
                     log::info!("ChatOp::PostDel {index}");
@@ -617,6 +770,24 @@ pub fn server(
             Some(ChatOp::PostFlag) => {
                 log::warn!("ChatOp::PostFlag not implemented");
             }
+            Some(ChatOp::PostSearch) => {
+                log::info!("ChatOp::PostSearch");
+                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                match buffer.to_original::<String, _>() {
+                    Ok(query) => {
+                        ui.search(&query);
+                        if allow_redraw {
+                            ui.redraw().expect("CHAT couldn't redraw");
+                        }
+                    }
+                    Err(e) => log::warn!("failed to deserialize search: {:?}", e),
+                }
+            }
+            Some(ChatOp::ReplyTo) => {
+                log::info!("ChatOp::ReplyTo");
+                let mut buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                buffer.replace(ui.reply_take()).expect("couldn't serialize return");
+            }
             Some(ChatOp::MenuAdd) => {
                 log::warn!("ChatOp::MenuAdd not implemented");
                 let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
@@ -2,7 +2,7 @@ use std::cmp::min;
 use std::fmt::Write as TextWrite;
 use std::io::{Error, ErrorKind, Read, Write};
 
-use dialogue::{Dialogue, post::Post};
+use dialogue::{Dialogue, Extras, attach::AttachFile, attach::Kind, post::Extra, post::Post};
 use gam::{MenuMatic, UxRegistration, menu_matic};
 use graphics_server::api::GlyphStyle;
 use graphics_server::{DrawStyle, Gid, Line, PixelColor, Point, Rectangle, TextBounds, TextView};
@@ -44,6 +44,14 @@ pub(crate) struct Ui {
     pddb_dict: Option<String>,
     pddb_key: Option<String>,
     dialogue: Option<Dialogue>,
+    /// the attachments and replies of the Posts in the Dialogue
+    extras: Extras,
+
+    /// the text searched for, and the indexes of the Posts containing it; ↑ & ↓ step thru these
+    search_query: String,
+    search_matches: Vec<usize>,
+    /// the Post the user chose to reply to, until the Chat App takes it
+    reply_to: Option<ReplyTo>,
 
     // Callbacks:
     // callback to our own server
@@ -77,6 +85,9 @@ pub(crate) struct Ui {
 
     vp: VisualProperties,
 
+    /// the F1-F4 slots, which hold word completions while a word is typed
+    icontray: Icontray,
+
     // variables that define a menu
     menu_mode: bool,
     app_menu: String,
@@ -117,8 +128,8 @@ impl Ui {
         let modals = Modals::new(&xns).unwrap();
         let canvas = gam.request_content_canvas(token).expect("couldn't get content canvas");
         let screensize = gam.get_canvas_bounds(canvas).expect("couldn't get dimensions of content canvas");
-        // TODO this is a stub - implement F1-4 actions and autocompletes
-        let _icontray = Icontray::new(Some(xous::connect(sid).unwrap()), ["F1", "F2", "F3", "F4"]);
+        // TODO this is a stub - implement F1-4 actions
+        let icontray = Icontray::new(Some(xous::connect(sid).unwrap()), ["F1", "F2", "F3", "F4"]);
         let menu_mgr = menu_matic(Vec::<MenuItem>::new(), app_menu, Some(xous::create_server().unwrap()))
             .expect("couldn't create MenuMatic manager");
         let pddb = pddb::Pddb::new();
@@ -139,7 +150,7 @@ impl Ui {
         status_tv.draw_border = false;
         status_tv.clear_area = true;
         status_tv.margin = Point::new(0, 0);
-        write!(status_tv, "{}", t!("chat.status.initial", locales::LANG).to_string()).ok();
+        write!(status_tv, "{}", t!("chat.status.initial", locales::lang()).to_string()).ok();
         let tt = ticktimer_server::Ticktimer::new().unwrap();
         let status_last_update_ms = tt.elapsed_ms();
         let bubble_properties = VisualProperties {
@@ -160,6 +171,10 @@ impl Ui {
             pddb_dict: None,
             pddb_key: None,
             dialogue: None,
+            extras: Extras::default(),
+            search_query: String::new(),
+            search_matches: Vec::new(),
+            reply_to: None,
             self_cid: xous::connect(sid).unwrap(),
             app_cid,
             opcode_event,
@@ -172,11 +187,12 @@ impl Ui {
             layout_range: Vec::new(),
             layout_topdown: false,
             vp: bubble_properties,
+            icontray,
             menu_mode: true,
             app_menu: app_menu.to_owned(),
             menu_mgr,
             token,
-            status_idle_text: t!("chat.status.initial", locales::LANG).to_string(),
+            status_idle_text: t!("chat.status.initial", locales::lang()).to_string(),
         }
     }
 
@@ -212,6 +228,7 @@ impl Ui {
                                         }
                                     };
                                 log::debug!("get '{}' = '{:?}'", key, self.dialogue);
+                                self.extras = self.extras_read(dict, key);
                             }
                             Err(e) => log::warn!("failed to read {}: {e}", key),
                         }
@@ -250,6 +267,7 @@ impl Ui {
                     }
                     Err(e) => log::warn!("failed to create {}:{}\n{}", dict, key, e),
                 }
+                self.extras_save(dict, key);
                 Ok(())
             }
             _ => {
@@ -259,6 +277,47 @@ impl Ui {
         }
     }
 
+    /// Read the Extras of a Dialogue from pddb, in a dict beside the Dialogues. A Dialogue saved before
+    /// Posts had attachments and replies has none.
+    fn extras_read(&self, dict: &str, key: &str) -> Extras {
+        let dict = format!("{}.extras", dict);
+        let mut bytes = Vec::new();
+        match self
+            .pddb
+            .get(&dict, key, None, false, false, None, None::<fn()>)
+            .and_then(|mut pddb_key| pddb_key.read_to_end(&mut bytes))
+        {
+            Ok(_) => {
+                let mut aligned = rkyv::util::AlignedVec::<16>::new();
+                aligned.extend_from_slice(&bytes);
+                let archive = unsafe { rkyv::access_unchecked::<dialogue::ArchivedExtras>(&aligned) };
+                rkyv::deserialize::<Extras, rkyv::rancor::Error>(archive).unwrap_or_else(|e| {
+                    log::warn!("failed to deserialize Extras {}:{} {}", dict, key, e);
+                    Extras::default()
+                })
+            }
+            Err(_) => Extras::default(),
+        }
+    }
+
+    /// Save the Extras of the current Dialogue to pddb
+    fn extras_save(&self, dict: &str, key: &str) {
+        let dict = format!("{}.extras", dict);
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&self.extras).unwrap();
+        // the archive is read from its end, so nothing of a longer one may be left after it
+        self.pddb.delete_key(&dict, key, None).ok();
+        match self.pddb.get(&dict, key, None, true, true, Some(bytes.len()), None::<fn()>) {
+            Ok(mut pddb_key) => match pddb_key.write(&bytes) {
+                Ok(len) => {
+                    self.pddb.sync().ok();
+                    log::info!("Wrote {} bytes to {}:{}", len, dict, key);
+                }
+                Err(e) => log::warn!("Error writing {}:{}: {:?}", dict, key, e),
+            },
+            Err(e) => log::warn!("failed to create {}:{}\n{}", dict, key, e),
+        }
+    }
+
     /// Set the current Dialogue
     ///
     /// # Arguments
@@ -268,6 +327,9 @@ impl Ui {
     pub fn dialogue_set(&mut self, pddb_dict: &str, pddb_key: Option<&str>) {
         self.pddb_dict = Some(pddb_dict.to_string());
         self.pddb_key = pddb_key.map(|key| key.to_string());
+        self.extras = Extras::default();
+        self.search_end();
+        self.reply_to = None;
         if self.pddb_key.is_none() {
             self.dialogue_modal();
         }
@@ -305,11 +367,11 @@ impl Ui {
                             .add_list(keys.iter().map(|s| s.as_str()).collect())
                             .expect("failed modal add_list");
                         self.pddb_key =
-                            self.modals.get_radiobutton(t!("chat.dialogue_title", locales::LANG)).ok();
+                            self.modals.get_radiobutton(t!("chat.dialogue_title", locales::lang())).ok();
                         log::info!("selected dialogue {}:{:?}", dict, self.pddb_key);
                     } else {
                         self.modals
-                            .show_notification(t!("chat.dict_empty", locales::LANG), None)
+                            .show_notification(t!("chat.dict_empty", locales::lang()), None)
                             .expect("notification failed");
                     }
                 }
@@ -321,7 +383,7 @@ impl Ui {
     /// Show some user help
     pub fn help(&self) {
         self.modals
-            .show_notification(t!("chat.help.navigation", locales::LANG), None)
+            .show_notification(t!("chat.help.navigation", locales::lang()), None)
             .expect("notification failed");
     }
 
@@ -343,20 +405,44 @@ impl Ui {
     /// * `author` - the name of the Author of the Post
     /// * `timestamp` - the timestamp of the Post
     /// * `text` - the text content of the Post
-    /// * `attach_url` - a url of an attachment (image for example)
+    /// * `attachment` - a file attached to the Post, to be saved in the pddb
+    /// * `reply_to` - the Post this one replies to
     pub fn post_add(
         &mut self,
         dialogue_id: &str,
         author: &str,
         timestamp: u64,
         text: &str,
-        attach_url: Option<&str>,
+        attachment: Option<Attachment>,
+        reply_to: Option<ReplyTo>,
     ) -> Result<(), Error> {
+        let attach = match (&self.pddb_key, attachment) {
+            (Some(pddb_key), Some(attachment)) => {
+                let key = AttachFile::key(pddb_key, timestamp, &attachment.name);
+                match self.attach_save(&key, &attachment.data) {
+                    Ok(_) => Some(AttachFile::new(&attachment.name, &key, &attachment.data)),
+                    Err(e) => {
+                        log::warn!("failed to save attachment {}: {e}", key);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+        let reply_to = reply_to.as_ref().map(|reply| (reply.author.as_str(), reply.timestamp));
         match (&self.pddb_key, &mut self.dialogue) {
             (Some(pddb_key), Some(ref mut dialogue)) => {
                 if dialogue_id.len() == 0 || pddb_key.eq(&dialogue_id) {
                     dialogue
-                        .post_add(author, timestamp, text, attach_url, Some((&self.vp, &self.gam)))
+                        .post_add(
+                            author,
+                            timestamp,
+                            text,
+                            attach,
+                            reply_to,
+                            &mut self.extras,
+                            Some((&self.vp, &self.gam)),
+                        )
                         .unwrap();
                 } else {
                     log::warn!(
@@ -406,6 +492,12 @@ impl Ui {
         }
     }
 
+    /// Return Some<Extra> of a Post by index in the current Dialogue, or None if it has no attachment or
+    /// reply
+    fn post_extra(&self, index: usize) -> Option<&Extra> {
+        self.post_get(index).and_then(|post| self.extras.get(post.author_id(), post.timestamp()))
+    }
+
     /// Set various status flags on a Post in the current Dialogue
     ///
     /// TODO: not implemented
@@ -420,6 +512,13 @@ impl Ui {
     ///
     /// * `index` - POST_SELECT_NEXT or POST_SELECT_PREV or an arbitraty index
     pub fn post_select(&mut self, index: usize) {
+        if !self.search_matches.is_empty() {
+            if let Some(found) = search_step(&self.search_matches, self.layout_selected, index) {
+                self.layout_selected = Some(found);
+                self.search_status();
+                return;
+            }
+        }
         self.layout_selected = match &self.dialogue {
             Some(dialogue) => {
                 match dialogue.post_last() {
@@ -455,6 +554,9 @@ impl Ui {
 
     pub fn set_menu_mode(&mut self, menu_mode: bool) { self.menu_mode = menu_mode; }
 
+    /// True while F1-F4 pick a word completion, rather than raising an `Event`
+    pub fn predicting(&self) -> bool { self.icontray.predicting() }
+
     /// Send a xous scalar message with an Event to the Chat App cid/opcode
     ///
     /// # Arguments
@@ -496,9 +598,181 @@ impl Ui {
         log::info!("raised app menu");
     }
 
-    /// Show the Msg Menu (→ key)
+    /// Show the Msg Menu (→ key) for the selected Post
     pub(crate) fn raise_msg_menu(&mut self) {
-        log::warn!("msg menu not implemented - pull-requests welcome");
+        let has_attach =
+            self.layout_selected.and_then(|n| self.post_extra(n)).is_some_and(|extra| extra.attach.is_some());
+        let reply = t!("chat.menu.reply", locales::lang());
+        let open = t!("chat.menu.open", locales::lang());
+        let search = t!("chat.menu.search", locales::lang());
+        let search_end = t!("chat.menu.search_end", locales::lang());
+        let mut items = vec![reply];
+        if has_attach {
+            items.push(open);
+        }
+        items.push(search);
+        if !self.search_matches.is_empty() {
+            items.push(search_end);
+        }
+        self.modals.add_list(items).expect("failed modal add_list");
+        match self.modals.get_radiobutton(t!("chat.menu.title", locales::lang())) {
+            Ok(choice) if choice == reply => self.reply_choose(),
+            Ok(choice) if choice == open => self.attach_open(),
+            Ok(choice) if choice == search => self.search_modal(),
+            Ok(choice) if choice == search_end => {
+                self.search_end();
+                self.set_status_text(&self.status_idle_text.clone());
+            }
+            _ => {}
+        }
+        self.redraw().expect("couldn't redraw screen");
+    }
+
+    /// Save the bytes of an attachment in the pddb, in a dict beside the Dialogues
+    fn attach_save(&self, key: &str, data: &[u8]) -> Result<(), Error> {
+        let dict = match &self.pddb_dict {
+            Some(dict) => format!("{}.attach", dict),
+            None => return Err(Error::new(ErrorKind::InvalidData, "missing")),
+        };
+        let mut pddb_key = self.pddb.get(&dict, key, None, true, true, Some(data.len()), None::<fn()>)?;
+        pddb_key.write_all(data)?;
+        self.pddb.sync().ok();
+        log::info!("Wrote {} bytes to {}:{}", data.len(), dict, key);
+        Ok(())
+    }
+
+    /// Read the bytes of an attachment from the pddb
+    fn attach_read(&self, attach: &AttachFile) -> Result<Vec<u8>, Error> {
+        let dict = match &self.pddb_dict {
+            Some(dict) => format!("{}.attach", dict),
+            None => return Err(Error::new(ErrorKind::InvalidData, "missing")),
+        };
+        let mut pddb_key = self.pddb.get(&dict, &attach.key, None, false, false, None, None::<fn()>)?;
+        let mut data = Vec::with_capacity(attach.len);
+        pddb_key.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Show the attachment of the selected Post: an image in a modal, or else what the file is
+    fn attach_open(&mut self) {
+        let (name, kind, size, data) =
+            match self.layout_selected.and_then(|n| self.post_extra(n)).and_then(|e| e.attach.as_ref()) {
+                Some(attach) => (attach.name.clone(), attach.kind, attach.size(), self.attach_read(attach)),
+                None => return,
+            };
+        let data = match data {
+            Ok(data) => data,
+            Err(e) => {
+                log::warn!("failed to read attachment {}: {e}", name);
+                self.modals
+                    .show_notification(t!("chat.attach.missing", locales::lang()), None)
+                    .expect("notification failed");
+                return;
+            }
+        };
+        match kind {
+            #[cfg(feature = "ditherpunk")]
+            Kind::Png => match gam::DecodePng::new(data.as_slice()) {
+                Ok(mut png) => {
+                    const BORDER: u32 = 3;
+                    let modal_size = Point::new(
+                        (gam::IMG_MODAL_WIDTH - 2 * BORDER) as i16,
+                        (gam::IMG_MODAL_HEIGHT - 2 * BORDER) as i16,
+                    );
+                    let bm = gam::Bitmap::from_png(&mut png, Some(modal_size));
+                    self.modals.show_image(bm).expect("show image modal failed");
+                }
+                Err(e) => log::warn!("failed to decode {}: {:?}", name, e),
+            },
+            Kind::File => {
+                let note = t!("chat.attach.about", locales::lang())
+                    .replace("{name}", &name)
+                    .replace("{size}", &size);
+                self.modals.show_notification(&note, None).expect("notification failed");
+            }
+            _ => {
+                self.modals
+                    .show_notification(t!("chat.attach.unsupported", locales::lang()), None)
+                    .expect("notification failed");
+            }
+        }
+    }
+
+    /// Make the selected Post the one the user is replying to
+    fn reply_choose(&mut self) {
+        let reply_to = match (&self.dialogue, self.layout_selected) {
+            (Some(dialogue), Some(n)) => dialogue.post_get(n).map(|post| ReplyTo {
+                author: dialogue.author(post.author_id()).map_or(String::new(), |a| a.name.clone()),
+                timestamp: post.timestamp(),
+            }),
+            _ => None,
+        };
+        if let Some(reply_to) = reply_to {
+            self.set_status_text(
+                &t!("chat.reply.status", locales::lang()).replace("{author}", &reply_to.author),
+            );
+            self.reply_to = Some(reply_to);
+        }
+    }
+
+    /// Hand over the Post the user is replying to, now that their reply is written
+    pub(crate) fn reply_take(&mut self) -> Option<ReplyTo> {
+        let reply_to = self.reply_to.take();
+        if reply_to.is_some() {
+            self.set_status_text(&self.status_idle_text.clone());
+        }
+        reply_to
+    }
+
+    /// Ask the user what to search for
+    fn search_modal(&mut self) {
+        let query = match self
+            .modals
+            .alert_builder(t!("chat.search.title", locales::lang()))
+            .field_placeholder_persist(Some(self.search_query.clone()), None)
+            .build()
+        {
+            Ok(payloads) => payloads.content()[0].content.as_str().to_string(),
+            Err(_) => return,
+        };
+        self.search(&query);
+    }
+
+    /// Select the most recent Post containing `query`, with ↑ & ↓ stepping thru the others that do
+    pub(crate) fn search(&mut self, query: &str) {
+        self.search_end();
+        let matches = match &self.dialogue {
+            Some(dialogue) => dialogue.search(query, &self.extras),
+            None => Vec::new(),
+        };
+        if query.trim().is_empty() {
+            self.set_status_text(&self.status_idle_text.clone());
+        } else if matches.is_empty() {
+            self.modals
+                .show_notification(t!("chat.search.none", locales::lang()), None)
+                .expect("notification failed");
+        } else {
+            self.search_query = query.trim().to_string();
+            self.layout_selected = matches.last().copied();
+            self.search_matches = matches;
+            self.search_status();
+        }
+    }
+
+    fn search_end(&mut self) {
+        self.search_query.clear();
+        self.search_matches.clear();
+    }
+
+    /// Show which match is selected in the status bar
+    fn search_status(&mut self) {
+        let n =
+            self.search_matches.iter().position(|&m| Some(m) == self.layout_selected).map_or(0, |n| n + 1);
+        let status = t!("chat.search.status", locales::lang())
+            .replace("{n}", &n.to_string())
+            .replace("{count}", &self.search_matches.len().to_string())
+            .replace("{query}", &self.search_query);
+        self.set_status_text(&status);
     }
 
     /// Redraw posts on the screen.
@@ -634,7 +908,8 @@ impl Ui {
                         bb.height() + self.vp.bubble_space as u32 + self.vp.bubble_margin.y as u32
                     } else {
                         // if the "natural height" has not been computed, do so now.
-                        let mut layout_bubble = default_textview(post, false, &self.vp);
+                        let extra = self.extras.get(post.author_id(), post.timestamp());
+                        let mut layout_bubble = default_textview(post, extra, false, &self.vp);
                         log::debug!("compute bounds on {}", layout_bubble);
                         if self.gam.bounds_compute_textview(&mut layout_bubble).is_ok() {
                             post.bounding_box = layout_bubble.bounds_computed;
@@ -730,7 +1005,9 @@ impl Ui {
                 };
                 let highlight =
                     if let Some(selected) = self.layout_selected { selected == post_index } else { false };
-                let mut bubble_tv = bubble(&self.vp, self.layout_topdown, post, dialogue, highlight, y);
+                let extra = self.extras.get(post.author_id(), post.timestamp());
+                let mut bubble_tv =
+                    bubble(&self.vp, self.layout_topdown, post, extra, dialogue, highlight, y);
                 self.gam.post_textview(&mut bubble_tv).expect("couldn't render bubble textview");
                 // double check the actual bounds against expected bounds
                 match bubble_tv.bounds_computed {
@@ -790,3 +1067,39 @@ impl Ui {
         }
     }
 }
+
+/// The match to select on stepping from `selected` by POST_SELECTED_NEXT or POST_SELECTED_PREV, which stays
+/// put at the first and last match. None for any other index, which isn't a step.
+fn search_step(matches: &[usize], selected: Option<usize>, index: usize) -> Option<usize> {
+    match (index, selected) {
+        (POST_SELECTED_NEXT, Some(selected)) => {
+            matches.iter().find(|&&m| m > selected).or(matches.last()).copied()
+        }
+        (POST_SELECTED_PREV, Some(selected)) => {
+            matches.iter().rev().find(|&&m| m < selected).or(matches.first()).copied()
+        }
+        (POST_SELECTED_NEXT, None) => matches.first().copied(),
+        (POST_SELECTED_PREV, None) => matches.last().copied(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_step() {
+        let matches = [2, 5, 9];
+        assert_eq!(search_step(&matches, Some(5), POST_SELECTED_NEXT), Some(9));
+        assert_eq!(search_step(&matches, Some(5), POST_SELECTED_PREV), Some(2));
+        // from a Post between matches
+        assert_eq!(search_step(&matches, Some(6), POST_SELECTED_PREV), Some(5));
+        assert_eq!(search_step(&matches, Some(6), POST_SELECTED_NEXT), Some(9));
+        // the ends stay put
+        assert_eq!(search_step(&matches, Some(9), POST_SELECTED_NEXT), Some(9));
+        assert_eq!(search_step(&matches, Some(2), POST_SELECTED_PREV), Some(2));
+        assert_eq!(search_step(&matches, None, POST_SELECTED_PREV), Some(9));
+        assert_eq!(search_step(&matches, Some(5), 3), None);
+    }
+}
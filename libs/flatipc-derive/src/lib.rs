@@ -4,6 +4,8 @@ use proc_macro::TokenStream;
 use quote::{format_ident, quote};
 use syn::{DeriveInput, parse_macro_input, spanned::Spanned};
 
+mod service;
+
 fn ast_hash(ast: &syn::DeriveInput) -> usize {
     use std::hash::{Hash, Hasher};
     let mut hasher = std::collections::hash_map::DefaultHasher::new();
@@ -46,6 +48,13 @@ fn derive_transmittable_inner(
     Ok(result)
 }
 
+/// Generates `{Trait}Opcode`, a `{Trait}Client` stub and a `dispatch()` method for servers from a
+/// trait describing the API of a server. See the `flatipc` crate documentation for details.
+#[proc_macro_attribute]
+pub fn service(attr: TokenStream, item: TokenStream) -> TokenStream {
+    service::service_inner(attr.into(), item.into()).unwrap_or_else(|e| e).into()
+}
+
 #[proc_macro_derive(Ipc)]
 pub fn derive_ipc(ts: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(ts as syn::DeriveInput);
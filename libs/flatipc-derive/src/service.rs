@@ -0,0 +1,515 @@
+//! `#[flatipc::service]`: generates the opcodes, a typed client stub and a server dispatcher
+//! from a single trait that describes a server's API.
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+
+/// How a method's arguments are carried to the server
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    /// `&IpcT` lent to the server, which may read it
+    Lend,
+    /// `&mut IpcT` lent to the server, which may modify it
+    LendMut,
+    /// up to four `usize` arguments, blocking until the server returns up to two `usize`s
+    Scalar,
+    /// up to four `usize` arguments, without waiting for the server
+    Send,
+}
+
+struct Method {
+    kind: Kind,
+    opcode: usize,
+    ident: syn::Ident,
+    variant: syn::Ident,
+    /// the `Ipc` type that is lent, for `Lend` and `LendMut`
+    ipc: Option<syn::Type>,
+    /// argument names, for `Scalar` and `Send`
+    args: Vec<syn::Ident>,
+    /// number of `usize` values returned, for `Scalar`
+    returns: usize,
+}
+
+fn error(span: Span, message: &str) -> TokenStream { syn::Error::new(span, message).to_compile_error() }
+
+fn camel_case(ident: &syn::Ident) -> syn::Ident {
+    let camel: String = ident
+        .to_string()
+        .split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|c| c.to_ascii_uppercase()).into_iter().chain(chars).collect::<String>()
+        })
+        .collect();
+    syn::Ident::new(&camel, ident.span())
+}
+
+fn is_usize(ty: &syn::Type) -> bool { matches!(ty, syn::Type::Path(path) if path.path.is_ident("usize")) }
+
+/// Takes the kind attribute (and its optional `opcode = N`) off of a method
+fn take_kind(method: &mut syn::TraitItemFn) -> Result<(Kind, Option<usize>), TokenStream> {
+    let mut found = None;
+    let mut remaining = Vec::new();
+    for attr in method.attrs.drain(..) {
+        let kind = if attr.path().is_ident("lend") {
+            Kind::Lend
+        } else if attr.path().is_ident("lend_mut") {
+            Kind::LendMut
+        } else if attr.path().is_ident("scalar") {
+            Kind::Scalar
+        } else if attr.path().is_ident("send") {
+            Kind::Send
+        } else {
+            remaining.push(attr);
+            continue;
+        };
+        if found.is_some() {
+            return Err(error(attr.span(), "only one of lend, lend_mut, scalar or send may be given"));
+        }
+        let mut opcode = None;
+        if let syn::Meta::List(_) = attr.meta {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("opcode") {
+                    let value: syn::LitInt = meta.value()?.parse()?;
+                    opcode = Some(value.base10_parse::<usize>()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `opcode = N`"))
+                }
+            })
+            .map_err(|e| e.to_compile_error())?;
+        }
+        found = Some((kind, opcode));
+    }
+    method.attrs = remaining;
+    found.ok_or_else(|| {
+        error(method.sig.span(), "service methods must be marked as one of lend, lend_mut, scalar or send")
+    })
+}
+
+fn parse_method(method: &mut syn::TraitItemFn, opcode: usize, kind: Kind) -> Result<Method, TokenStream> {
+    let sig = &method.sig;
+    if method.default.is_some() {
+        return Err(error(method.span(), "service methods can't have a default implementation"));
+    }
+    match sig.receiver() {
+        Some(receiver) if receiver.reference.is_some() => {}
+        _ => return Err(error(sig.span(), "service methods must take `&self` or `&mut self`")),
+    }
+    let mut ipc = None;
+    let mut args = Vec::new();
+    for input in sig.inputs.iter().skip(1) {
+        let syn::FnArg::Typed(arg) = input else { unreachable!() };
+        match kind {
+            Kind::Lend | Kind::LendMut => {
+                let syn::Type::Reference(reference) = &*arg.ty else {
+                    return Err(error(arg.ty.span(), "lent arguments must be a reference to an `Ipc` type"));
+                };
+                if reference.mutability.is_some() != (kind == Kind::LendMut) || ipc.is_some() {
+                    return Err(error(
+                        arg.span(),
+                        "lend takes one `&IpcT` argument, and lend_mut takes one `&mut IpcT` argument",
+                    ));
+                }
+                ipc = Some((*reference.elem).clone());
+            }
+            Kind::Scalar | Kind::Send => {
+                let syn::Pat::Ident(pat) = &*arg.pat else {
+                    return Err(error(arg.pat.span(), "scalar arguments must be named"));
+                };
+                if !is_usize(&arg.ty) {
+                    return Err(error(arg.ty.span(), "scalar arguments must be `usize`"));
+                }
+                args.push(pat.ident.clone());
+            }
+        }
+    }
+    if matches!(kind, Kind::Lend | Kind::LendMut) && ipc.is_none() {
+        return Err(error(sig.span(), "lend and lend_mut methods take one `Ipc` argument"));
+    }
+    if args.len() > 4 {
+        return Err(error(sig.inputs.span(), "at most four scalar arguments may be sent"));
+    }
+    let returns = match &sig.output {
+        syn::ReturnType::Default => 0,
+        syn::ReturnType::Type(_, ty) if kind == Kind::Scalar => match &**ty {
+            ty if is_usize(ty) => 1,
+            syn::Type::Tuple(tuple) if tuple.elems.is_empty() => 0,
+            syn::Type::Tuple(tuple) if tuple.elems.len() == 2 && tuple.elems.iter().all(is_usize) => 2,
+            _ => return Err(error(ty.span(), "scalar methods may return `usize` or `(usize, usize)`")),
+        },
+        syn::ReturnType::Type(_, ty) => {
+            return Err(error(ty.span(), "only scalar methods may return a value"));
+        }
+    };
+    Ok(Method { kind, opcode, ident: sig.ident.clone(), variant: camel_case(&sig.ident), ipc, args, returns })
+}
+
+fn return_type(returns: usize) -> TokenStream {
+    match returns {
+        0 => quote! { () },
+        1 => quote! { usize },
+        _ => quote! { (usize, usize) },
+    }
+}
+
+/// The four scalar arguments of a message, padded with zeroes
+fn scalar_args(args: &[syn::Ident]) -> [TokenStream; 4] {
+    core::array::from_fn(|i| args.get(i).map(|arg| quote! { #arg }).unwrap_or(quote! { 0 }))
+}
+
+fn client_method(method: &Method, opcode_ident: &syn::Ident) -> TokenStream {
+    let Method { ident, variant, args, .. } = method;
+    let nonblocking = format_ident!("{}_nonblocking", ident);
+    let opcode = quote! { #opcode_ident::#variant as usize };
+    let ret = return_type(method.returns);
+    let blocking = match method.kind {
+        Kind::Lend => {
+            let ipc = method.ipc.as_ref().unwrap();
+            quote! {
+                pub fn #ident(&self, arg: &#ipc) -> Result<(), flatipc::Error> {
+                    flatipc::Ipc::lend(arg, self.conn, #opcode)
+                }
+            }
+        }
+        Kind::LendMut => {
+            let ipc = method.ipc.as_ref().unwrap();
+            quote! {
+                pub fn #ident(&self, arg: &mut #ipc) -> Result<(), flatipc::Error> {
+                    flatipc::Ipc::lend_mut(arg, self.conn, #opcode)
+                }
+            }
+        }
+        Kind::Scalar if cfg!(feature = "xous") => {
+            let [a1, a2, a3, a4] = scalar_args(args);
+            let send = quote! {
+                xous::send_message(
+                    self.conn,
+                    xous::Message::new_blocking_scalar(#opcode, #a1, #a2, #a3, #a4),
+                )?
+            };
+            let body = match method.returns {
+                0 => quote! {
+                    #send;
+                    Ok(())
+                },
+                1 => quote! {
+                    match #send {
+                        xous::Result::Scalar1(a) | xous::Result::Scalar2(a, _) => Ok(a),
+                        xous::Result::Scalar5(_, a, _, _, _) => Ok(a),
+                        _ => Err(flatipc::Error::InternalError),
+                    }
+                },
+                _ => quote! {
+                    match #send {
+                        xous::Result::Scalar2(a, b) | xous::Result::Scalar5(_, a, b, _, _) => Ok((a, b)),
+                        _ => Err(flatipc::Error::InternalError),
+                    }
+                },
+            };
+            quote! {
+                pub fn #ident(&self, #(#args: usize),*) -> Result<#ret, flatipc::Error> {
+                    #body
+                }
+            }
+        }
+        Kind::Send if cfg!(feature = "xous") => {
+            let [a1, a2, a3, a4] = scalar_args(args);
+            quote! {
+                pub fn #ident(&self, #(#args: usize),*) -> Result<(), flatipc::Error> {
+                    xous::send_message(self.conn, xous::Message::new_scalar(#opcode, #a1, #a2, #a3, #a4))
+                        .map(|_| ())
+                }
+            }
+        }
+        // The mock backend only models lent memory
+        Kind::Scalar | Kind::Send => quote! {
+            pub fn #ident(&self, #(#args: usize),*) -> Result<#ret, flatipc::Error> {
+                let _ = (#opcode, #(#args),*);
+                Err(flatipc::Error::Unimplemented)
+            }
+        },
+    };
+    let nonblocking = match method.kind {
+        Kind::Lend => {
+            let ipc = method.ipc.as_ref().unwrap();
+            quote! {
+                /// Lends `arg` from the client's worker thread, returning it once the server is done with it
+                pub fn #nonblocking(&self, arg: #ipc) -> flatipc::Pending<#ipc> {
+                    let client = Self::new(self.conn);
+                    self.worker.run(move || client.#ident(&arg).map(|_| arg))
+                }
+            }
+        }
+        Kind::LendMut => {
+            let ipc = method.ipc.as_ref().unwrap();
+            quote! {
+                /// Lends `arg` from the client's worker thread, returning it once the server has updated it
+                pub fn #nonblocking(&self, mut arg: #ipc) -> flatipc::Pending<#ipc> {
+                    let client = Self::new(self.conn);
+                    self.worker.run(move || client.#ident(&mut arg).map(|_| arg))
+                }
+            }
+        }
+        Kind::Scalar => quote! {
+            /// Sends the request from the client's worker thread, returning the server's response once it
+            /// replies
+            pub fn #nonblocking(&self, #(#args: usize),*) -> flatipc::Pending<#ret> {
+                let client = Self::new(self.conn);
+                self.worker.run(move || client.#ident(#(#args),*))
+            }
+        },
+        // `send` never blocks to begin with
+        Kind::Send => quote! {},
+    };
+    quote! {
+        #blocking
+        #nonblocking
+    }
+}
+
+fn dispatch_arm(method: &Method, opcode_ident: &syn::Ident) -> TokenStream {
+    let Method { ident, variant, args, .. } = method;
+    let body = match method.kind {
+        Kind::Lend if cfg!(feature = "xous") => {
+            let ipc = method.ipc.as_ref().unwrap();
+            quote! {
+                let memory = msg.body.memory_message().ok_or(flatipc::DispatchError::WrongMessageType(id))?;
+                let arg = <#ipc as flatipc::Ipc>::from_memory_message(memory)
+                    .ok_or(flatipc::DispatchError::BadSignature(id))?;
+                self.#ident(arg);
+            }
+        }
+        Kind::LendMut if cfg!(feature = "xous") => {
+            let ipc = method.ipc.as_ref().unwrap();
+            quote! {
+                let memory =
+                    msg.body.memory_message_mut().ok_or(flatipc::DispatchError::WrongMessageType(id))?;
+                let arg = <#ipc as flatipc::Ipc>::from_memory_message_mut(memory)
+                    .ok_or(flatipc::DispatchError::BadSignature(id))?;
+                self.#ident(arg);
+            }
+        }
+        // The mock backend hands over the lent buffer and its signature as they are
+        Kind::Lend => {
+            let ipc = method.ipc.as_ref().unwrap();
+            quote! {
+                let arg = match &msg.body {
+                    flatipc::backend::mock::Message::Lend { data, signature } => {
+                        <#ipc as flatipc::Ipc>::from_slice(data, *signature)
+                            .ok_or(flatipc::DispatchError::BadSignature(id))?
+                    }
+                    _ => return Err(flatipc::DispatchError::WrongMessageType(id)),
+                };
+                self.#ident(arg);
+            }
+        }
+        Kind::LendMut => {
+            let ipc = method.ipc.as_ref().unwrap();
+            quote! {
+                let arg = match &mut msg.body {
+                    flatipc::backend::mock::Message::LendMut { data, signature } => {
+                        <#ipc as flatipc::Ipc>::from_slice_mut(data, *signature)
+                            .ok_or(flatipc::DispatchError::BadSignature(id))?
+                    }
+                    _ => return Err(flatipc::DispatchError::WrongMessageType(id)),
+                };
+                self.#ident(arg);
+            }
+        }
+        Kind::Scalar | Kind::Send => {
+            let scalar = if args.is_empty() {
+                quote! { _ }
+            } else {
+                quote! { scalar }
+            };
+            let (message, values, reply) = if cfg!(feature = "xous") {
+                let message = if method.kind == Kind::Scalar {
+                    quote! { xous::Message::BlockingScalar(#scalar) }
+                } else {
+                    quote! { xous::Message::Scalar(#scalar) }
+                };
+                let values: Vec<TokenStream> =
+                    [quote! { arg1 }, quote! { arg2 }, quote! { arg3 }, quote! { arg4 }]
+                        .into_iter()
+                        .take(args.len())
+                        .map(|field| quote! { scalar.#field })
+                        .collect();
+                let reply = match (method.kind, method.returns) {
+                    (Kind::Send, _) => quote! { self.#ident(#(#args),*); },
+                    // the sender is blocked until it gets a reply, even if there's nothing to return
+                    (_, 0) => quote! {
+                        self.#ident(#(#args),*);
+                        xous::return_scalar(msg.sender, 0).map_err(flatipc::DispatchError::Reply)?;
+                    },
+                    (_, 1) => quote! {
+                        let a = self.#ident(#(#args),*);
+                        xous::return_scalar(msg.sender, a).map_err(flatipc::DispatchError::Reply)?;
+                    },
+                    _ => quote! {
+                        let (a, b) = self.#ident(#(#args),*);
+                        xous::return_scalar2(msg.sender, a, b).map_err(flatipc::DispatchError::Reply)?;
+                    },
+                };
+                (message, values, reply)
+            } else {
+                let message = if method.kind == Kind::Scalar {
+                    quote! { flatipc::backend::mock::Message::BlockingScalar(#scalar) }
+                } else {
+                    quote! { flatipc::backend::mock::Message::Scalar(#scalar) }
+                };
+                let values: Vec<TokenStream> = (0..args.len()).map(|i| quote! { scalar[#i] }).collect();
+                // the reply is kept in the envelope for the caller to look at
+                let reply = match (method.kind, method.returns) {
+                    (Kind::Send, _) => quote! { self.#ident(#(#args),*); },
+                    (_, 0) => quote! {
+                        self.#ident(#(#args),*);
+                        msg.reply = Some((0, 0));
+                    },
+                    (_, 1) => quote! {
+                        let a = self.#ident(#(#args),*);
+                        msg.reply = Some((a, 0));
+                    },
+                    _ => quote! {
+                        let (a, b) = self.#ident(#(#args),*);
+                        msg.reply = Some((a, b));
+                    },
+                };
+                (message, values, reply)
+            };
+            quote! {
+                let (#(#args,)*) = match &msg.body {
+                    #message => (#(#values,)*),
+                    _ => return Err(flatipc::DispatchError::WrongMessageType(id)),
+                };
+                #reply
+            }
+        }
+    };
+    quote! {
+        Some(#opcode_ident::#variant) => {
+            #body
+        }
+    }
+}
+
+pub(crate) fn service_inner(attr: TokenStream, item: TokenStream) -> Result<TokenStream, TokenStream> {
+    // `opcode = Name` names the opcode enum, so a server can keep the name its clients already use
+    let mut opcode_ident: Option<syn::Ident> = None;
+    let args = syn::meta::parser(|meta| {
+        if meta.path.is_ident("opcode") {
+            opcode_ident = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("the only argument to `service` is `opcode = Name`"))
+        }
+    });
+    syn::parse::Parser::parse2(args, attr).map_err(|e| e.to_compile_error())?;
+    let mut service: syn::ItemTrait = syn::parse2(item).map_err(|e| e.to_compile_error())?;
+    let vis = service.vis.clone();
+    let ident = service.ident.clone();
+    let opcode_ident = opcode_ident.unwrap_or_else(|| format_ident!("{}Opcode", ident));
+    let client_ident = format_ident!("{}Client", ident);
+
+    let mut methods: Vec<Method> = Vec::new();
+    let mut next_opcode = 0;
+    for item in service.items.iter_mut() {
+        let syn::TraitItem::Fn(method) = item else { continue };
+        let (kind, opcode) = take_kind(method)?;
+        let opcode = opcode.unwrap_or(next_opcode);
+        if let Some(other) = methods.iter().find(|other| other.opcode == opcode) {
+            return Err(error(
+                method.sig.span(),
+                &format!("opcode {} is already used by `{}`", opcode, other.ident),
+            ));
+        }
+        methods.push(parse_method(method, opcode, kind)?);
+        next_opcode = opcode + 1;
+    }
+
+    if methods.is_empty() {
+        return Err(error(service.span(), "a service needs at least one method"));
+    }
+
+    let variants = methods.iter().map(|Method { variant, opcode, .. }| quote! { #variant = #opcode });
+    let from_usize = methods.iter().map(|Method { variant, opcode, .. }| {
+        quote! { #opcode => Some(#opcode_ident::#variant) }
+    });
+    let client_methods = methods.iter().map(|method| client_method(method, &opcode_ident));
+
+    let arms = methods.iter().map(|method| dispatch_arm(method, &opcode_ident));
+    let dispatch: syn::TraitItem = if cfg!(feature = "xous") {
+        syn::parse_quote! {
+            /// Calls the method for the opcode of `msg`, and replies to blocking scalar messages with its
+            /// result. Lent memory is returned to the sender when `msg` is dropped.
+            fn dispatch(&mut self, msg: &mut xous::MessageEnvelope) -> Result<(), flatipc::DispatchError> {
+                let id = msg.body.id();
+                match #opcode_ident::from_usize(id) {
+                    #(#arms)*
+                    None => return Err(flatipc::DispatchError::UnknownOpcode(id)),
+                }
+                Ok(())
+            }
+        }
+    } else {
+        syn::parse_quote! {
+            /// Calls the method for the opcode of `msg`, and keeps the reply to a blocking scalar message
+            /// in `msg.reply`.
+            fn dispatch(
+                &mut self,
+                msg: &mut flatipc::backend::mock::MessageEnvelope<'_>,
+            ) -> Result<(), flatipc::DispatchError> {
+                let id = msg.id;
+                match #opcode_ident::from_usize(id) {
+                    #(#arms)*
+                    None => return Err(flatipc::DispatchError::UnknownOpcode(id)),
+                }
+                Ok(())
+            }
+        }
+    };
+    service.items.push(dispatch);
+
+    let opcode_doc = format!("Opcodes of the `{}` service", ident);
+    let client_doc = format!("Client for the `{}` service", ident);
+    Ok(quote! {
+        #service
+
+        #[doc = #opcode_doc]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[repr(usize)]
+        #vis enum #opcode_ident {
+            #(#variants),*
+        }
+
+        impl #opcode_ident {
+            pub fn from_usize(opcode: usize) -> Option<Self> {
+                match opcode {
+                    #(#from_usize,)*
+                    _ => None,
+                }
+            }
+        }
+
+        #[doc = #client_doc]
+        #vis struct #client_ident {
+            conn: flatipc::CID,
+            worker: flatipc::Worker,
+        }
+
+        impl #client_ident {
+            /// Wraps an existing connection to the server
+            pub fn new(conn: flatipc::CID) -> Self {
+                #client_ident { conn, worker: flatipc::Worker::new() }
+            }
+
+            pub fn conn(&self) -> flatipc::CID {
+                self.conn
+            }
+
+            #(#client_methods)*
+        }
+    })
+}
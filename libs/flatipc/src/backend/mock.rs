@@ -17,6 +17,25 @@ impl Server {
     }
 }
 
+/// A message as a server receives it, for running through the `dispatch()` of a `#[flatipc::service]`
+pub enum Message<'a> {
+    Lend { data: &'a [u8], signature: usize },
+    LendMut { data: &'a mut [u8], signature: usize },
+    Scalar([usize; 4]),
+    BlockingScalar([usize; 4]),
+}
+
+pub struct MessageEnvelope<'a> {
+    pub id: usize,
+    pub body: Message<'a>,
+    /// What the server replied to a blocking scalar message with
+    pub reply: Option<(usize, usize)>,
+}
+
+impl<'a> MessageEnvelope<'a> {
+    pub fn new(id: usize, body: Message<'a>) -> Self { MessageEnvelope { id, body, reply: None } }
+}
+
 pub struct IpcMachine {
     servers: Vec<Server>,
 }
@@ -103,6 +103,58 @@
 //!     // Do something with the object.
 //! }
 //! ```
+//!
+//! # Services
+//!
+//! Rather than writing the opcodes, client calls and the server's `match` loop by hand, a
+//! server's API can be described once as a trait marked `#[flatipc::service]`. Each method is
+//! marked with how it is sent:
+//!
+//! - `#[lend]` - lends one `&IpcT` to the server, blocking until it is returned
+//! - `#[lend_mut]` - lends one `&mut IpcT` to the server, which may modify it
+//! - `#[scalar]` - sends up to four `usize`s, blocking until the server returns `()`, `usize` or `(usize,
+//!   usize)`
+//! - `#[send]` - sends up to four `usize`s without waiting for the server
+//!
+//! Opcodes are numbered in the order the methods are declared, starting from 0, unless they are
+//! given explicitly with e.g. `#[scalar(opcode = 7)]`; subsequent methods count on from there.
+//! New methods should be added at the end, so existing clients keep working.
+//!
+//! For a trait `Foo`, this generates:
+//!
+//! - `FooOpcode`, an enum of the opcodes. A server moving to `service` can keep the name of its existing enum
+//!   with `#[flatipc::service(opcode = Opcode)]`
+//! - `FooClient`, with a method for each method of the trait, plus a `_nonblocking` variant of each blocking
+//!   method that hands the request to the client's worker thread and returns a `Pending` handle. Each client
+//!   has one worker, which takes its requests in order
+//! - `Foo::dispatch()`, which the server calls on each message it receives to unpack it, call the method and
+//!   reply to the sender
+//!
+//! ```ignore
+//! #[flatipc::service]
+//! pub trait Counter {
+//!     #[lend_mut]
+//!     fn fill(&mut self, buf: &mut IpcBuf);
+//!     #[scalar]
+//!     fn add(&mut self, a: usize, b: usize) -> usize;
+//! }
+//!
+//! // client
+//! let counter = CounterClient::new(conn);
+//! let sum = counter.add(1, 2)?;
+//! let pending = counter.fill_nonblocking(Buf::default().into_ipc());
+//! // ...do other work, then collect the buffer
+//! let buf = pending.wait()?;
+//!
+//! // server
+//! impl Counter for MyServer { ... }
+//! loop {
+//!     let mut msg = xous::receive_message(sid).unwrap();
+//!     if let Err(e) = my_server.dispatch(&mut msg) {
+//!         log::error!("couldn't handle {:?}: {:?}", msg, e);
+//!     }
+//! }
+//! ```
 
 /// An object is Sendable if it is guaranteed to be flat and contains no pointers.
 /// This trait can be placed on objects that have invalid representations such as
@@ -114,7 +166,7 @@ pub unsafe trait IpcSafe {}
 extern crate self as flatipc;
 
 // Allow doing `#[derive(flatipc::Ipc)]` instead of `#[derive(flatipc_derive::Ipc)]`
-pub use flatipc_derive::{Ipc, IpcSafe};
+pub use flatipc_derive::{Ipc, IpcSafe, service};
 #[cfg(feature = "xous")]
 mod backend {
     pub use xous::CID;
@@ -140,6 +192,22 @@ pub use string::String;
 pub mod vec;
 pub use vec::Vec;
 
+pub mod pending;
+pub use pending::{Pending, Worker};
+
+/// The reasons a message can't be handled by the `dispatch()` method of a `#[flatipc::service]`
+#[derive(Debug)]
+pub enum DispatchError {
+    /// The opcode isn't part of the service
+    UnknownOpcode(usize),
+    /// The message is not of the kind (lend, lend_mut, scalar or send) the opcode takes
+    WrongMessageType(usize),
+    /// The lent memory is too small or has the signature of a different `Ipc` type
+    BadSignature(usize),
+    /// The reply to a blocking scalar message couldn't be sent
+    Reply(Error),
+}
+
 unsafe impl IpcSafe for i8 {}
 unsafe impl IpcSafe for i16 {}
 unsafe impl IpcSafe for i32 {}
@@ -0,0 +1,88 @@
+//! Completion handles for requests that are made without blocking the caller.
+
+use std::cell::RefCell;
+use std::sync::{Mutex, mpsc};
+
+use crate::Error;
+
+type Outcome<T> = std::thread::Result<Result<T, Error>>;
+type Job = Box<dyn FnOnce() + Send>;
+
+/// The thread that carries out a client's non-blocking requests. Xous messages that expect a
+/// response block the sending thread until the server replies, so the requests are handed to a
+/// thread of their own. Xous limits how many threads a process may have, so there is one worker per
+/// client rather than one per request: it is started by the first request, takes the requests in
+/// the order they were made, and exits once the client is dropped and the queue is empty.
+#[derive(Default)]
+pub struct Worker {
+    jobs: Mutex<Option<mpsc::Sender<Job>>>,
+}
+
+impl Worker {
+    pub const fn new() -> Self { Worker { jobs: Mutex::new(None) } }
+
+    /// Queues `request`, returning a handle to its result
+    pub fn run<T, F>(&self, request: F) -> Pending<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T, Error> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let mut job: Job = Box::new(move || {
+            // a panicking request is reported to whoever waits on it, and the worker carries on
+            tx.send(std::panic::catch_unwind(std::panic::AssertUnwindSafe(request))).ok();
+        });
+        let mut jobs = self.jobs.lock().unwrap();
+        loop {
+            let sender = jobs.get_or_insert_with(|| {
+                let (sender, queue) = mpsc::channel::<Job>();
+                std::thread::spawn(move || {
+                    for job in queue {
+                        job();
+                    }
+                });
+                sender
+            });
+            match sender.send(job) {
+                Ok(()) => break,
+                // the worker couldn't be kept alive, so start another
+                Err(mpsc::SendError(unsent)) => {
+                    job = unsent;
+                    *jobs = None;
+                }
+            }
+        }
+        Pending { rx, outcome: RefCell::new(None) }
+    }
+}
+
+/// A request that is being carried out by a client's `Worker`. Its result is collected with
+/// `wait()`.
+pub struct Pending<T> {
+    rx: mpsc::Receiver<Outcome<T>>,
+    outcome: RefCell<Option<Outcome<T>>>,
+}
+
+impl<T> Pending<T> {
+    /// Returns `true` once the server has responded, after which `wait()` will not block
+    pub fn is_complete(&self) -> bool {
+        let mut outcome = self.outcome.borrow_mut();
+        if outcome.is_none() {
+            *outcome = self.rx.try_recv().ok();
+        }
+        outcome.is_some()
+    }
+
+    /// Blocks until the server has responded, and returns the result of the request
+    pub fn wait(self) -> Result<T, Error> {
+        let outcome = match self.outcome.into_inner() {
+            Some(outcome) => outcome,
+            // the job sends before it is dropped, so the channel can't close without an answer
+            None => self.rx.recv().expect("worker dropped a request"),
+        };
+        match outcome {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
@@ -268,3 +268,217 @@ fn server_test() {
     let original_inc = lendable_inc.into_original();
     println!("Original value: {}", original_inc.value);
 }
+
+#[test]
+fn service_test() {
+    use flatipc::{IntoIpc, Ipc};
+
+    #[derive(flatipc::Ipc, Debug, PartialEq)]
+    #[repr(C)]
+    pub struct Counter {
+        value: u32,
+    }
+
+    #[flatipc::service]
+    #[allow(dead_code)]
+    pub trait Tally {
+        /// Reads the counter
+        #[lend]
+        fn show(&mut self, counter: &IpcCounter);
+        #[lend_mut(opcode = 4)]
+        fn increment(&mut self, counter: &mut IpcCounter);
+        #[scalar]
+        fn add(&mut self, a: usize, b: usize) -> usize;
+        #[send]
+        fn reset(&mut self);
+    }
+
+    assert_eq!(TallyOpcode::Show as usize, 0);
+    assert_eq!(TallyOpcode::Increment as usize, 4);
+    assert_eq!(TallyOpcode::from_usize(5), Some(TallyOpcode::Add));
+    assert_eq!(TallyOpcode::from_usize(6), Some(TallyOpcode::Reset));
+    assert_eq!(TallyOpcode::from_usize(1), None);
+
+    // an existing server keeps the name of its opcode enum
+    #[flatipc::service(opcode = Opcode)]
+    #[allow(dead_code)]
+    pub trait Legacy {
+        #[send]
+        fn suspend_resume(&mut self, token: usize);
+        #[send]
+        fn quit(&mut self);
+    }
+    assert_eq!(Opcode::Quit as usize, 1);
+
+    let server = flatipc::backend::mock::Server::new(
+        Box::new(|opcode, signature, _, buffer| {
+            assert_eq!(opcode, TallyOpcode::Show as usize);
+            assert!(IpcCounter::from_slice(buffer, signature).is_some());
+            (0, 0)
+        }),
+        Box::new(|opcode, signature, _, buffer| {
+            assert_eq!(opcode, TallyOpcode::Increment as usize);
+            IpcCounter::from_slice_mut(buffer, signature).unwrap().value += 1;
+            (0, 0)
+        }),
+    );
+    let client = TallyClient::new(flatipc::backend::mock::IPC_MACHINE.lock().unwrap().add_server(server));
+
+    let mut counter = Counter { value: 1 }.into_ipc();
+    client.show(&counter).unwrap();
+    client.increment(&mut counter).unwrap();
+    assert_eq!(counter.value, 2);
+
+    // both requests go to the client's one worker
+    let first = client.increment_nonblocking(counter);
+    let second = client.increment_nonblocking(Counter { value: 10 }.into_ipc());
+    assert_eq!(*second.wait().unwrap(), Counter { value: 11 });
+    assert!(first.is_complete());
+    assert_eq!(*first.wait().unwrap(), Counter { value: 3 });
+
+    // the mock backend does not carry scalars
+    assert!(client.add(1, 2).is_err());
+    assert!(client.reset().is_err());
+}
+
+#[test]
+fn service_dispatch_test() {
+    use std::sync::{Arc, Mutex};
+
+    use flatipc::backend::mock::{Message, MessageEnvelope};
+    use flatipc::{DispatchError, IntoIpc};
+
+    #[derive(flatipc::Ipc, Debug, PartialEq)]
+    #[repr(C)]
+    pub struct Counter {
+        value: u32,
+    }
+
+    #[derive(flatipc::Ipc, Debug, PartialEq)]
+    #[repr(C)]
+    pub struct Other {
+        value: u32,
+    }
+
+    #[flatipc::service]
+    pub trait Tally {
+        #[lend]
+        fn show(&mut self, counter: &IpcCounter);
+        #[lend_mut]
+        fn increment(&mut self, counter: &mut IpcCounter);
+        #[scalar]
+        fn add(&mut self, a: usize, b: usize) -> usize;
+        #[scalar]
+        fn divide(&mut self, a: usize, b: usize) -> (usize, usize);
+        #[scalar]
+        fn clear(&mut self);
+        #[send]
+        fn set(&mut self, a: usize, b: usize, c: usize, d: usize);
+    }
+
+    /// Remembers each call and the arguments it decoded
+    #[derive(Default)]
+    struct Recorder {
+        calls: Vec<(&'static str, Vec<usize>)>,
+    }
+    impl Tally for Recorder {
+        fn show(&mut self, counter: &IpcCounter) { self.calls.push(("show", vec![counter.value as usize])); }
+
+        fn increment(&mut self, counter: &mut IpcCounter) {
+            counter.value += 1;
+            self.calls.push(("increment", vec![counter.value as usize]));
+        }
+
+        fn add(&mut self, a: usize, b: usize) -> usize {
+            self.calls.push(("add", vec![a, b]));
+            a + b
+        }
+
+        fn divide(&mut self, a: usize, b: usize) -> (usize, usize) {
+            self.calls.push(("divide", vec![a, b]));
+            (a / b, a % b)
+        }
+
+        fn clear(&mut self) { self.calls.push(("clear", vec![])); }
+
+        fn set(&mut self, a: usize, b: usize, c: usize, d: usize) {
+            self.calls.push(("set", vec![a, b, c, d]));
+        }
+    }
+
+    // scalars are unpacked in order, and blocking ones are replied to with what the method returned
+    let mut recorder = Recorder::default();
+    let mut msg = MessageEnvelope::new(TallyOpcode::Add as usize, Message::BlockingScalar([3, 4, 99, 99]));
+    recorder.dispatch(&mut msg).unwrap();
+    assert_eq!(msg.reply, Some((7, 0)));
+    let mut msg = MessageEnvelope::new(TallyOpcode::Divide as usize, Message::BlockingScalar([17, 5, 0, 0]));
+    recorder.dispatch(&mut msg).unwrap();
+    assert_eq!(msg.reply, Some((3, 2)));
+    let mut msg = MessageEnvelope::new(TallyOpcode::Clear as usize, Message::BlockingScalar([1, 2, 3, 4]));
+    recorder.dispatch(&mut msg).unwrap();
+    assert_eq!(msg.reply, Some((0, 0)));
+    let mut msg = MessageEnvelope::new(TallyOpcode::Set as usize, Message::Scalar([1, 2, 3, 4]));
+    recorder.dispatch(&mut msg).unwrap();
+    assert_eq!(msg.reply, None);
+    assert_eq!(
+        recorder.calls,
+        vec![("add", vec![3, 4]), ("divide", vec![17, 5]), ("clear", vec![]), ("set", vec![1, 2, 3, 4])]
+    );
+
+    // the wrong kind of message for an opcode, or an opcode the service doesn't have, calls nothing
+    let mut msg = MessageEnvelope::new(TallyOpcode::Add as usize, Message::Scalar([3, 4, 0, 0]));
+    assert!(matches!(recorder.dispatch(&mut msg), Err(DispatchError::WrongMessageType(2))));
+    let mut msg = MessageEnvelope::new(TallyOpcode::Set as usize, Message::BlockingScalar([1, 2, 3, 4]));
+    assert!(matches!(recorder.dispatch(&mut msg), Err(DispatchError::WrongMessageType(5))));
+    let mut msg = MessageEnvelope::new(TallyOpcode::Show as usize, Message::Scalar([0; 4]));
+    assert!(matches!(recorder.dispatch(&mut msg), Err(DispatchError::WrongMessageType(0))));
+    let mut msg = MessageEnvelope::new(6, Message::BlockingScalar([0; 4]));
+    assert!(matches!(recorder.dispatch(&mut msg), Err(DispatchError::UnknownOpcode(6))));
+    assert_eq!(msg.reply, None);
+    assert_eq!(recorder.calls.len(), 4);
+
+    // lent buffers go from the client stubs, through the mock server, to `dispatch()`
+    let recorder = Arc::new(Mutex::new(Recorder::default()));
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let server = flatipc::backend::mock::Server::new(
+        Box::new({
+            let recorder = recorder.clone();
+            let errors = errors.clone();
+            move |opcode, signature, _, data| {
+                let mut msg = MessageEnvelope::new(opcode, Message::Lend { data, signature });
+                if let Err(e) = recorder.lock().unwrap().dispatch(&mut msg) {
+                    errors.lock().unwrap().push(e);
+                }
+                (0, 0)
+            }
+        }),
+        Box::new({
+            let recorder = recorder.clone();
+            let errors = errors.clone();
+            move |opcode, signature, _, data| {
+                let mut msg = MessageEnvelope::new(opcode, Message::LendMut { data, signature });
+                if let Err(e) = recorder.lock().unwrap().dispatch(&mut msg) {
+                    errors.lock().unwrap().push(e);
+                }
+                (0, 0)
+            }
+        }),
+    );
+    let conn = flatipc::backend::mock::IPC_MACHINE.lock().unwrap().add_server(server);
+    let client = TallyClient::new(conn);
+
+    let mut counter = Counter { value: 5 }.into_ipc();
+    client.show(&counter).unwrap();
+    client.increment(&mut counter).unwrap();
+    assert_eq!(counter.value, 6);
+    assert_eq!(recorder.lock().unwrap().calls, vec![("show", vec![5]), ("increment", vec![6])]);
+    assert!(errors.lock().unwrap().is_empty());
+
+    // a buffer of another type is turned away by its signature, and lending to a scalar opcode is refused
+    let other = Other { value: 5 }.into_ipc();
+    flatipc::Ipc::lend(&other, conn, TallyOpcode::Show as usize).unwrap();
+    flatipc::Ipc::lend(&counter, conn, TallyOpcode::Add as usize).unwrap();
+    let errors = errors.lock().unwrap();
+    assert!(matches!(errors[..], [DispatchError::BadSignature(0), DispatchError::WrongMessageType(2)]));
+    assert_eq!(recorder.lock().unwrap().calls.len(), 2);
+}
@@ -21,23 +21,79 @@ pub fn shellchat<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<Option
             log::info!("starting TLS delete certificates");
             let tls = Tls::new();
             let count = tls.del_all_rota().unwrap();
-            write!(ret, "{} {}", count, t!("tls.deleteall_done", locales::LANG)).ok();
+            write!(ret, "{} {}", count, t!("tls.deleteall_done", locales::lang())).ok();
             log::info!("finished TLS delete certificates");
         }
+        // fetch and save a Certificate Revocation List
+        Some("crl") => match tokens.next() {
+            Some("deleteall") => {
+                let tls = Tls::new();
+                let count = tls.del_all_crl().unwrap_or(0);
+                write!(ret, "{} {}", count, t!("tls.crl_deleteall_done", locales::lang())).ok();
+            }
+            Some(url) => {
+                let tls = Tls::new();
+                match tls.fetch_crl(url) {
+                    Ok(count) => write!(ret, "{} {}", count, t!("tls.crl_done", locales::lang())).ok(),
+                    Err(e) => write!(ret, "{} {url}: {e}", t!("tls.crl_fail", locales::lang())).ok(),
+                };
+            }
+            None => {
+                write!(ret, "\tcrl <url>\t{}\n", t!("tls.crl_cmd", locales::lang())).ok();
+            }
+        },
+        // list trusted certificates that have expired, or will soon
+        Some("expiring") => {
+            let tls = Tls::new();
+            let now = crate::policy::now();
+            for (key, info) in tls.expiring() {
+                write!(ret, "🏛 {}\n\t{}\n", key, info.expiry(now)).ok();
+            }
+        }
         // helpful stuff
         Some("help") => {
-            write!(ret, "{}", t!("tls.cmd_help", locales::LANG)).ok();
+            write!(ret, "{}", t!("tls.cmd_help", locales::lang())).ok();
         }
         // list trusted Certificate Authority certificates
         Some("list") => {
             log::set_max_level(log::LevelFilter::Info);
             log::info!("starting TLS trusted listing");
             let tls = Tls::new();
-            for ota in tls.trusted() {
-                write!(ret, "🏛 {}\n", ota).ok();
+            let now = crate::policy::now();
+            for (_key, ota, info) in tls.anchors() {
+                match info {
+                    Some(info) => write!(ret, "🏛 {}\n\t{}\n", ota, info.expiry(now)).ok(),
+                    None => write!(ret, "🏛 {}\n", ota).ok(),
+                };
             }
             log::info!("finished TLS trusted listing");
         }
+        // view trusted certificates, and optionally delete them
+        Some("manage") => {
+            let tls = Tls::new();
+            let count = tls.manage();
+            write!(ret, "{} {}", count, t!("tls.manage_deleted", locales::lang())).ok();
+        }
+        // list the per-host SPKI pins
+        Some("pins") => {
+            let tls = Tls::new();
+            for pins in tls.pins() {
+                write!(ret, "{}\n", pins).ok();
+            }
+        }
+        // forget the SPKI pins for a host
+        Some("unpin") => match tokens.next() {
+            Some(host) => {
+                let tls = Tls::new();
+                match tls.unpin(host) {
+                    Ok(_) => write!(ret, "{} {host}", t!("tls.unpin_done", locales::lang())).ok(),
+                    Err(e) => write!(ret, "{host}: {e}").ok(),
+                };
+            }
+            None => {
+                write!(ret, "\tunpin <host>\t{}\n", t!("tls.unpin_cmd", locales::lang())).ok();
+            }
+        },
         // save/trust all Root CA's in webpki-roots en-masse
         #[cfg(feature = "rootCA")]
         Some("mozilla") => {
@@ -45,7 +101,7 @@ pub fn shellchat<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<Option
             let modals = Modals::new(&xns).unwrap();
             let mut count: u32 = webpki_roots::TLS_SERVER_ROOTS.len().try_into().unwrap();
             modals
-                .start_progress(t!("tls.mozilla_progress", locales::LANG), 0, count, 0)
+                .start_progress(t!("tls.mozilla_progress", locales::lang()), 0, count, 0)
                 .expect("no progress");
             count = 0;
             let tls = Tls::new();
@@ -56,7 +112,7 @@ pub fn shellchat<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<Option
                 count += 1;
             }
             modals.finish_progress().expect("finish progress");
-            write!(ret, "{} {}", count, t!("tls.mozilla_done", locales::LANG)).ok();
+            write!(ret, "{} {}", count, t!("tls.mozilla_done", locales::lang())).ok();
         }
         // inspect establishes a tls connection to the supplied host, extracts the
         // certificates offered and immediately closes the connection.
@@ -70,8 +126,8 @@ pub fn shellchat<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<Option
             };
             let tls = Tls::new();
             match tls.inspect(target) {
-                Ok(count) => write!(ret, "{} {}", count, t!("tls.inspect_done", locales::LANG)).ok(),
-                Err(_) => write!(ret, "{} {target}", t!("tls.inspect_fail_servername", locales::LANG)).ok(),
+                Ok(count) => write!(ret, "{} {}", count, t!("tls.inspect_done", locales::lang())).ok(),
+                Err(_) => write!(ret, "{} {target}", t!("tls.inspect_fail_servername", locales::lang())).ok(),
             };
             log::set_max_level(log::LevelFilter::Info);
         }
@@ -81,9 +137,7 @@ pub fn shellchat<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<Option
             log::info!("starting TLS run");
             log::info!("build TLS client config");
             let tls = Tls::new();
-            let config = rustls::ClientConfig::builder()
-                .with_root_certificates(tls.root_store())
-                .with_no_client_auth();
+            let config = tls.client_config();
             let target = match tokens.next() {
                 Some(target) => target,
                 None => "bunnyfoo.com",
@@ -93,7 +147,7 @@ pub fn shellchat<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<Option
             match TcpStream::connect((target, 443)) {
                 Ok(mut sock) => {
                     log::info!("tcp connected");
-                    write!(ret, "{}", t!("tls.test_success_tcp", locales::LANG)).ok();
+                    write!(ret, "{}", t!("tls.test_success_tcp", locales::lang())).ok();
                     match ServerName::try_from(target.to_owned()) {
                         Ok(server_name) => {
                             match rustls::ClientConnection::new(Arc::new(config), server_name) {
@@ -103,7 +157,8 @@ pub fn shellchat<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<Option
                                     match tls.write_all(b"GET / HTTP/1.1\r\n\r\n") {
                                         Ok(()) => {
                                             log::info!("tls accepted GET");
-                                            write!(ret, "{}", t!("tls.test_success_get", locales::LANG)).ok();
+                                            write!(ret, "{}", t!("tls.test_success_get", locales::lang()))
+                                                .ok();
                                             let mut plaintext = Vec::new();
                                             log::info!("read TLS response");
                                             match tls.read_to_end(&mut plaintext) {
@@ -112,7 +167,7 @@ pub fn shellchat<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<Option
                                                     write!(
                                                         ret,
                                                         "{} {}\n",
-                                                        t!("tls.test_success_bytes", locales::LANG),
+                                                        t!("tls.test_success_bytes", locales::lang()),
                                                         n
                                                     )
                                                     .ok();
@@ -154,14 +209,19 @@ pub fn shellchat<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Result<Option
             log::set_max_level(log::LevelFilter::Info);
         }
         None | _ => {
-            write!(ret, "{}\n", t!("tls.cmd", locales::LANG)).ok();
-            write!(ret, "\tdeleteall\t{}\n", t!("tls.deleteall_cmd", locales::LANG)).ok();
+            write!(ret, "{}\n", t!("tls.cmd", locales::lang())).ok();
+            write!(ret, "\tcrl <url>\t{}\n", t!("tls.crl_cmd", locales::lang())).ok();
+            write!(ret, "\tdeleteall\t{}\n", t!("tls.deleteall_cmd", locales::lang())).ok();
+            write!(ret, "\texpiring\t{}\n", t!("tls.expiring_cmd", locales::lang())).ok();
             write!(ret, "\thelp\n").ok();
-            write!(ret, "\tlist\t{}\n", t!("tls.list_cmd", locales::LANG)).ok();
+            write!(ret, "\tlist\t{}\n", t!("tls.list_cmd", locales::lang())).ok();
+            write!(ret, "\tmanage\t{}\n", t!("tls.manage_cmd", locales::lang())).ok();
             #[cfg(feature = "rootCA")]
-            write!(ret, "\tmozilla\t{}\n", t!("tls.mozilla_cmd", locales::LANG)).ok();
-            write!(ret, "\tinspect <host>\t{}\n", t!("tls.inspect_cmd", locales::LANG)).ok();
-            write!(ret, "\ttest <host>\t{}\n", t!("tls.test_cmd", locales::LANG)).ok();
+            write!(ret, "\tmozilla\t{}\n", t!("tls.mozilla_cmd", locales::lang())).ok();
+            write!(ret, "\tinspect <host>\t{}\n", t!("tls.inspect_cmd", locales::lang())).ok();
+            write!(ret, "\tpins\t{}\n", t!("tls.pins_cmd", locales::lang())).ok();
+            write!(ret, "\ttest <host>\t{}\n", t!("tls.test_cmd", locales::lang())).ok();
+            write!(ret, "\tunpin <host>\t{}\n", t!("tls.unpin_cmd", locales::lang())).ok();
         }
     }
     Ok(Some(ret))
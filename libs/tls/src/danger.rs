@@ -71,7 +71,7 @@ impl ServerCertVerifier for StifledCertificateVerification {
                             CertificateError::NotValidYet => {
                                 modals
                                     .show_notification(
-                                        t!("tls.probe_help_not_valid_yet", locales::LANG),
+                                        t!("tls.probe_help_not_valid_yet", locales::lang()),
                                         None,
                                     )
                                     .expect("modal failed");
@@ -82,7 +82,7 @@ impl ServerCertVerifier for StifledCertificateVerification {
                                     .show_notification(
                                         format!(
                                             "{}\n{:?}",
-                                            t!("tls.probe_invalid_certificate", locales::LANG),
+                                            t!("tls.probe_invalid_certificate", locales::lang()),
                                             e
                                         )
                                         .as_str(),
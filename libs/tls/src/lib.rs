@@ -1,6 +1,8 @@
 pub mod cmd;
 mod danger;
+mod ocsp;
 pub mod ota;
+pub mod policy;
 pub mod xtls;
 
 use std::convert::{Into, TryInto};
@@ -11,13 +13,22 @@ use std::sync::Arc;
 use locales::t;
 use modals::Modals;
 use ota::{ArchivedOwnedTrustAnchor, OwnedTrustAnchor};
-use rustls::pki_types::{CertificateDer, TrustAnchor};
+use policy::{AnchorInfo, ArchivedAnchorInfo, ArchivedSpkiPins, PinCheck, PolicyVerifier, SpkiPins};
+use rustls::pki_types::{CertificateDer, CertificateRevocationListDer, TrustAnchor};
 use rustls::{ClientConfig, ClientConnection, RootCertStore};
-use x509_parser::prelude::{FromDer, X509Certificate, parse_x509_certificate};
+use x509_parser::prelude::{FromDer, X509Certificate, parse_x509_certificate, parse_x509_crl};
 use xous_names::XousNames;
 
 /// PDDB Dict for tls trusted certificates keys
 const TLS_TRUSTED_DICT: &str = "tls.trusted";
+/// PDDB Dict for details of the tls trusted certificates (expiry etc), keyed as TLS_TRUSTED_DICT
+const TLS_INFO_DICT: &str = "tls.trusted.info";
+/// PDDB Dict for per-host SPKI pins, keyed by host
+const TLS_PINS_DICT: &str = "tls.pins";
+/// PDDB Dict for Certificate Revocation Lists, keyed by issuer
+const TLS_CRL_DICT: &str = "tls.crl";
+/// CRLs can be large - this is generous for an intermediate CA, but not for a busy public CA
+const MAX_CRL_BYTES: usize = 256 * 1024;
 
 pub struct Tls {
     pddb: pddb::Pddb,
@@ -46,16 +57,18 @@ impl Tls {
             .map(|result| result.unwrap())
             .filter(|(_fingerprint, x509)| x509.is_ca())
             .collect();
+        let now = policy::now();
         let chain: Vec<String> = certificates
             .iter()
             .map(|(fingerprint, x509)| {
                 let fp = std::str::from_utf8(*fingerprint).unwrap_or("");
-                format!("🏛 {}\n{}", &x509.subject(), open_hex(fp))
+                let expiry = AnchorInfo::from_x509(x509).expiry(now);
+                format!("🏛 {}\n{}\n{}", &x509.subject(), expiry, open_hex(fp))
             })
             .collect();
         let chain: Vec<&str> = chain.iter().map(AsRef::as_ref).collect();
         modals.add_list(chain).expect("couldn't build checkbox list");
-        match modals.get_checkbox(t!("tls.check_trust_prompt", locales::LANG)) {
+        match modals.get_checkbox(t!("tls.check_trust_prompt", locales::lang())) {
             Ok(trusted) => {
                 trusted.iter().for_each(|cert| log::info!("trusts {}", cert));
                 modals
@@ -63,15 +76,18 @@ impl Tls {
                     .unwrap()
                     .iter()
                     .map(|i| &certificates[*i].1)
-                    .map(|x509| OwnedTrustAnchor::from_x509(x509))
-                    .filter_map(|ta| ta.ok())
-                    .for_each(|ta| {
+                    .filter_map(|x509| OwnedTrustAnchor::from_x509(x509).ok().map(|ta| (ta, x509)))
+                    .for_each(|(ta, x509)| {
                         self.save_ta(&ta).unwrap_or_else(|e| {
                             log::warn!("failed to save cert: {e}");
                             modals
                                 .show_notification(format!("failed to save:\n{:?}\n{e}", &ta).as_str(), None)
                                 .expect("modal failed");
                         });
+                        if let Ok(key) = ta.pddb_key() {
+                            self.save_info(&key, &AnchorInfo::from_x509(x509))
+                                .unwrap_or_else(|e| log::warn!("failed to save cert info: {e}"));
+                        }
                     });
                 trusted.len()
             }
@@ -92,13 +108,13 @@ impl Tls {
             Ok(list) => list.len(),
             Err(_) => 0,
         };
-        match self.pddb.delete_dict(TLS_TRUSTED_DICT, None) {
-            Ok(_) => {
-                log::info!("Deleted {}\n", TLS_TRUSTED_DICT);
-                self.pddb.sync().or_else(|e| Ok::<(), Error>(log::warn!("{e}"))).ok();
+        for dict in [TLS_TRUSTED_DICT, TLS_INFO_DICT] {
+            match self.pddb.delete_dict(dict, None) {
+                Ok(_) => log::info!("Deleted {}\n", dict),
+                Err(e) => log::warn!("failed to delete {}: {:?}", dict, e),
             }
-            Err(e) => log::warn!("failed to delete {}: {:?}", TLS_TRUSTED_DICT, e),
         }
+        self.pddb.sync().or_else(|e| Ok::<(), Error>(log::warn!("{e}"))).ok();
         Ok(count)
     }
 
@@ -115,6 +131,8 @@ impl Tls {
             }
             Err(e) => log::warn!("failed to delete {}:{}: {:?}", TLS_TRUSTED_DICT, key, e),
         }
+        // trust-anchors from webpki-roots have no info, so failure here is unremarkable
+        self.pddb.delete_key(TLS_INFO_DICT, key, None).ok();
         return Ok(());
     }
 
@@ -205,6 +223,358 @@ impl Tls {
         }
     }
 
+    /// Saves the details of a trusted certificate to the pddb
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the pddb-key of the corresponding trust-anchor
+    /// * `info` - the details of the trusted certificate
+    pub fn save_info(&self, key: &str, info: &AnchorInfo) -> Result<(), Error> {
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(info)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "failed to archive AnchorInfo"))?;
+        let mut pddb_key = self.pddb.get(
+            TLS_INFO_DICT,
+            key,
+            None,
+            true,
+            true,
+            Some(policy::MAX_INFO_BYTES),
+            None::<fn()>,
+        )?;
+        pddb_key.write_all(&bytes)?;
+        self.pddb.sync().ok();
+        log::info!("Wrote {} bytes to {}:{}", bytes.len(), TLS_INFO_DICT, key);
+        Ok(())
+    }
+
+    /// Returns the details of a trusted certificate from the pddb
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the pddb-key of the corresponding trust-anchor
+    pub fn get_info(&self, key: &str) -> Option<AnchorInfo> {
+        let mut pddb_key = self.pddb.get(TLS_INFO_DICT, key, None, false, false, None, None::<fn()>).ok()?;
+        let mut bytes = [0u8; policy::MAX_INFO_BYTES];
+        match pddb_key.read(&mut bytes) {
+            Ok(pos) => {
+                let ainfo = unsafe { rkyv::access_unchecked::<ArchivedAnchorInfo>(&bytes[..pos]) };
+                rkyv::deserialize::<AnchorInfo, rkyv::rancor::Error>(ainfo).ok()
+            }
+            Err(e) => {
+                log::warn!("failed to read {}:{}: {e}", TLS_INFO_DICT, key);
+                None
+            }
+        }
+    }
+
+    /// Returns all trusted (saved) OwnedTrustAnchors with their pddb-key, and details if known
+    ///
+    /// # Returns
+    ///
+    /// a Vec of (pddb-key, OwnedTrustAnchor, Option<AnchorInfo>) sorted by pddb-key
+    pub fn anchors(&self) -> Vec<(String, OwnedTrustAnchor, Option<AnchorInfo>)> {
+        let mut keys = self.pddb.list_keys(TLS_TRUSTED_DICT, None).unwrap_or_default();
+        keys.sort();
+        keys.into_iter()
+            .filter_map(|key| {
+                let ota = self.get_ota(&key)?;
+                let info = self.get_info(&key);
+                Some((key, ota, info))
+            })
+            .collect()
+    }
+
+    /// Returns the trusted certificates that have expired, or will expire within
+    /// `policy::EXPIRY_WARN_DAYS`
+    ///
+    /// # Returns
+    ///
+    /// a Vec of (pddb-key, AnchorInfo) soonest expiry first
+    pub fn expiring(&self) -> Vec<(String, AnchorInfo)> {
+        let now = policy::now();
+        let mut expiring: Vec<(String, AnchorInfo)> = self
+            .anchors()
+            .into_iter()
+            .filter_map(|(key, _, info)| info.map(|info| (key, info)))
+            .filter(|(_, info)| info.is_expiring(now))
+            .collect();
+        expiring.sort_by_key(|(_, info)| info.not_after);
+        expiring
+    }
+
+    /// Presents a warning modal listing the trusted certificates that have expired,
+    /// or are about to expire
+    ///
+    /// # Returns
+    ///
+    /// a count of the expired and expiring certificates
+    pub fn warn_expiring(&self) -> usize {
+        let expiring = self.expiring();
+        if expiring.len() > 0 {
+            let now = policy::now();
+            let mut warning = String::from(t!("tls.expiry_warning", locales::lang()));
+            for (key, info) in &expiring {
+                log::warn!("{} {}", key, info.expiry(now));
+                warning.push_str(&format!("\n\n🏛 {}\n{}", key, info.expiry(now)));
+            }
+            let xns = XousNames::new().unwrap();
+            let modals = Modals::new(&xns).unwrap();
+            modals.show_notification(&warning, None).expect("modal failed");
+        }
+        expiring.len()
+    }
+
+    /// Presents a modal listing the trusted certificates with their expiry, and
+    /// allows the user to view the details of each one, and optionally delete it.
+    ///
+    /// # Returns
+    ///
+    /// the number of trust-anchors deleted
+    pub fn manage(&self) -> usize {
+        let xns = XousNames::new().unwrap();
+        let modals = Modals::new(&xns).unwrap();
+        let mut deleted = 0;
+        loop {
+            let now = policy::now();
+            let anchors = self.anchors();
+            if anchors.len() == 0 {
+                modals
+                    .show_notification(t!("tls.manage_empty", locales::lang()), None)
+                    .expect("modal failed");
+                break;
+            }
+            for (key, _, info) in &anchors {
+                let expiry = match info {
+                    Some(info) => info.expiry(now),
+                    None => t!("tls.expiry_unknown", locales::lang()).to_string(),
+                };
+                modals.add_list_item(&format!("🏛 {}\n{}", key, expiry)).expect("couldn't build radio list");
+            }
+            modals.add_list_item(t!("tls.manage_done", locales::lang())).expect("couldn't build radio list");
+            let index = match modals.get_radiobutton(t!("tls.manage_prompt", locales::lang())) {
+                Ok(_) => modals.get_radio_index().unwrap_or(anchors.len()),
+                Err(_) => {
+                    log::error!("get_radiobutton failed");
+                    break;
+                }
+            };
+            let (key, ota, info) = match anchors.get(index) {
+                Some(anchor) => anchor,
+                None => break,
+            };
+            let details = match info {
+                Some(info) => format!("{}", info),
+                None => format!("🏛 {}\n{}", ota, t!("tls.expiry_unknown", locales::lang())),
+            };
+            modals.add_list_item(t!("tls.manage_keep", locales::lang())).expect("couldn't build radio list");
+            modals
+                .add_list_item(t!("tls.manage_delete", locales::lang()))
+                .expect("couldn't build radio list");
+            if let Ok(_) = modals.get_radiobutton(&details) {
+                if matches!(modals.get_radio_index(), Ok(1)) {
+                    self.del_rota(key).unwrap_or_else(|e| log::warn!("{e}"));
+                    deleted += 1;
+                }
+            }
+        }
+        deleted
+    }
+
+    /// Returns the SPKI pins saved for a host
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - the host (i.e. betrusted.io)
+    pub fn get_pins(&self, host: &str) -> Option<SpkiPins> {
+        let mut pddb_key = self.pddb.get(TLS_PINS_DICT, host, None, false, false, None, None::<fn()>).ok()?;
+        let mut bytes = [0u8; policy::MAX_PIN_BYTES];
+        match pddb_key.read(&mut bytes) {
+            Ok(pos) => {
+                let apins = unsafe { rkyv::access_unchecked::<ArchivedSpkiPins>(&bytes[..pos]) };
+                rkyv::deserialize::<SpkiPins, rkyv::rancor::Error>(apins).ok()
+            }
+            Err(e) => {
+                log::warn!("failed to read {}:{}: {e}", TLS_PINS_DICT, host);
+                None
+            }
+        }
+    }
+
+    /// Saves the SPKI pins for a host, replacing any previous pins
+    ///
+    /// # Arguments
+    ///
+    /// * `pins` - the host and its pinned SPKI sha256 hashes
+    pub fn save_pins(&self, pins: &SpkiPins) -> Result<(), Error> {
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(pins)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "failed to archive SpkiPins"))?;
+        // delete first, so that a shorter list of pins does not leave stale bytes behind
+        self.pddb.delete_key(TLS_PINS_DICT, &pins.host, None).ok();
+        let mut pddb_key = self.pddb.get(
+            TLS_PINS_DICT,
+            &pins.host,
+            None,
+            true,
+            true,
+            Some(policy::MAX_PIN_BYTES),
+            None::<fn()>,
+        )?;
+        pddb_key.write_all(&bytes)?;
+        self.pddb.sync().ok();
+        log::info!("pinned {}", pins.host);
+        Ok(())
+    }
+
+    /// Returns all per-host SPKI pins
+    pub fn pins(&self) -> Vec<SpkiPins> {
+        let mut keys = self.pddb.list_keys(TLS_PINS_DICT, None).unwrap_or_default();
+        keys.sort();
+        keys.iter().filter_map(|host| self.get_pins(host)).collect()
+    }
+
+    /// Deletes the SPKI pins for a host, so that the next connection is trusted on first use
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - the host (i.e. betrusted.io)
+    pub fn unpin(&self, host: &str) -> Result<(), Error> {
+        self.pddb.delete_key(TLS_PINS_DICT, host, None)?;
+        self.pddb.sync().ok();
+        log::info!("Deleted {}:{}", TLS_PINS_DICT, host);
+        Ok(())
+    }
+
+    /// Checks the SPKIs of a certificate chain offered by host against the pins saved for the host.
+    ///
+    /// On first use the end-entity SPKI is pinned. On a mismatch, the user is asked by modal
+    /// whether to accept the changed key, which is then pinned in place of the old pins.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - the host offering the certificate chain
+    /// * `chain` - the SPKI sha256 hashes of the chain, end-entity first
+    pub fn check_pins(&self, host: &str, chain: &[[u8; 32]]) -> PinCheck {
+        let end_entity = match chain.first() {
+            Some(spki) => *spki,
+            None => return PinCheck::Rejected,
+        };
+        match self.get_pins(host) {
+            Some(pins) if pins.matches(chain) => PinCheck::Pinned,
+            Some(pins) => {
+                log::warn!("SPKI pin mismatch for {host}");
+                let xns = XousNames::new().unwrap();
+                let modals = Modals::new(&xns).unwrap();
+                modals
+                    .add_list_item(t!("tls.pin_reject", locales::lang()))
+                    .expect("couldn't build radio list");
+                modals
+                    .add_list_item(t!("tls.pin_accept", locales::lang()))
+                    .expect("couldn't build radio list");
+                let prompt = format!(
+                    "{}\n{}\n\n{}\n{}",
+                    t!("tls.pin_mismatch", locales::lang()),
+                    pins,
+                    t!("tls.pin_offered", locales::lang()),
+                    policy::hex(&end_entity)
+                );
+                match modals.get_radiobutton(&prompt) {
+                    Ok(_) if matches!(modals.get_radio_index(), Ok(1)) => {
+                        let pins = SpkiPins { host: host.to_string(), pins: vec![end_entity] };
+                        self.save_pins(&pins).unwrap_or_else(|e| log::warn!("failed to save pins: {e}"));
+                        PinCheck::Repinned
+                    }
+                    _ => PinCheck::Rejected,
+                }
+            }
+            None => {
+                let pins = SpkiPins { host: host.to_string(), pins: vec![end_entity] };
+                self.save_pins(&pins).unwrap_or_else(|e| log::warn!("failed to save pins: {e}"));
+                PinCheck::Tofu
+            }
+        }
+    }
+
+    /// Saves a DER encoded Certificate Revocation List to the pddb
+    ///
+    /// The CRL is keyed by issuer, so a newer CRL from the same issuer replaces the older.
+    ///
+    /// # Arguments
+    ///
+    /// * `der` - a DER encoded CRL
+    ///
+    /// # Returns
+    ///
+    /// the number of revoked certificates in the CRL
+    pub fn save_crl(&self, der: &[u8]) -> Result<usize, Error> {
+        let (key, count) = match parse_x509_crl(der) {
+            Ok((_, crl)) => {
+                let issuer = crl.issuer().to_string();
+                let begin = issuer.find("CN=").map(|b| b + 3).unwrap_or(0);
+                let end = issuer[begin..].find(",").map(|e| begin + e).unwrap_or(issuer.len());
+                (issuer[begin..end].to_string(), crl.iter_revoked_certificates().count())
+            }
+            Err(e) => {
+                log::warn!("failed to parse CRL: {e}");
+                return Err(Error::from(ErrorKind::InvalidData));
+            }
+        };
+        self.pddb.delete_key(TLS_CRL_DICT, &key, None).ok();
+        let mut pddb_key =
+            self.pddb.get(TLS_CRL_DICT, &key, None, true, true, Some(MAX_CRL_BYTES), None::<fn()>)?;
+        pddb_key.write_all(der)?;
+        self.pddb.sync().ok();
+        log::info!("Wrote CRL {}:{} with {} revocations", TLS_CRL_DICT, key, count);
+        Ok(count)
+    }
+
+    /// Fetches a DER encoded Certificate Revocation List and saves it to the pddb
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - the CRL distribution point (typically http)
+    ///
+    /// # Returns
+    ///
+    /// the number of revoked certificates in the CRL
+    pub fn fetch_crl(&self, url: &str) -> Result<usize, Error> {
+        let agent = ureq::builder().tls_connector(Arc::new(xtls::TlsConnector {})).build();
+        match agent.get(url).call() {
+            Ok(response) => {
+                let mut der = Vec::new();
+                response.into_reader().take(MAX_CRL_BYTES as u64).read_to_end(&mut der)?;
+                self.save_crl(&der)
+            }
+            Err(e) => {
+                log::warn!("failed to fetch CRL from {url}: {e}");
+                Err(Error::new(ErrorKind::NotFound, "failed to fetch CRL"))
+            }
+        }
+    }
+
+    /// Returns all saved Certificate Revocation Lists
+    pub fn crls(&self) -> Vec<CertificateRevocationListDer<'static>> {
+        self.pddb
+            .list_keys(TLS_CRL_DICT, None)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|key| {
+                let mut pddb_key =
+                    self.pddb.get(TLS_CRL_DICT, key, None, false, false, None, None::<fn()>).ok()?;
+                let mut der = Vec::new();
+                pddb_key.read_to_end(&mut der).ok()?;
+                Some(CertificateRevocationListDer::from(der))
+            })
+            .collect()
+    }
+
+    /// Deletes ALL saved Certificate Revocation Lists
+    pub fn del_all_crl(&self) -> Result<usize, Error> {
+        let count = self.pddb.list_keys(TLS_CRL_DICT, None).map(|list| list.len()).unwrap_or(0);
+        self.pddb.delete_dict(TLS_CRL_DICT, None).ok();
+        self.pddb.sync().ok();
+        Ok(count)
+    }
+
     /// Checks if the rustls Certificate provided is trusted (saved in pddb)
     ///
     /// # Arguments
@@ -399,8 +769,22 @@ impl Tls {
         }
     }
 
+    /// Returns a ClientConfig that verifies hosts against the trusted (saved) TrustAnchors,
+    /// any saved CRLs, stapled OCSP responses and the per-host SPKI pins.
     pub fn client_config(&self) -> ClientConfig {
-        rustls::ClientConfig::builder().with_root_certificates(self.root_store()).with_no_client_auth()
+        match PolicyVerifier::new(self.root_store(), self.crls()) {
+            Ok(verifier) => rustls::ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(verifier))
+                .with_no_client_auth(),
+            Err(e) => {
+                // typically an empty root store: the default verifier will produce the same error
+                log::warn!("failed to build PolicyVerifier: {e}");
+                rustls::ClientConfig::builder()
+                    .with_root_certificates(self.root_store())
+                    .with_no_client_auth()
+            }
+        }
     }
 
     /// Construct a tls-stream on the tcp-stream provided
@@ -0,0 +1,199 @@
+// A minimal reader for OCSP responses stapled to a tls handshake (RFC 6960)
+//
+// Only enough of the DER structure is walked to find the `certStatus` of the
+// `SingleResponse` matching the end-entity serial number. The responder signature
+// is NOT verified, so a `Good` status is informational only. A `Revoked` status is
+// acted upon regardless: a host has nothing to gain by stapling a forged revocation
+// of its own certificate.
+
+/// DER tags of interest
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_ENUMERATED: u8 = 0x0A;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_CONTEXT_0: u8 = 0xA0;
+const TAG_CONTEXT_1: u8 = 0xA1;
+const TAG_CONTEXT_2: u8 = 0xA2;
+/// `certStatus` CHOICE tags (IMPLICIT)
+const TAG_STATUS_GOOD: u8 = 0x80;
+const TAG_STATUS_REVOKED: u8 = 0xA1;
+
+/// DER encoded OID id-pkix-ocsp-basic (1.3.6.1.5.5.7.48.1.1)
+const OID_PKIX_OCSP_BASIC: [u8; 9] = [0x2B, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x01];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OcspStatus {
+    Good,
+    Revoked,
+    /// The response was absent, malformed, unsuccessful or did not cover the certificate
+    Unknown,
+}
+
+/// Returns the status of the certificate with `serial` reported by a stapled OCSP response
+///
+/// # Arguments
+///
+/// * `response` - the DER encoded OCSPResponse stapled by the host
+/// * `serial` - the raw (DER INTEGER content) serial number of the end-entity certificate
+///
+/// # Returns
+///
+/// the `OcspStatus` of the certificate, or `OcspStatus::Unknown` if it cannot be determined
+pub fn staple_status(response: &[u8], serial: &[u8]) -> OcspStatus {
+    single_responses(response)
+        .and_then(|responses| {
+            let mut responses = responses;
+            while let Some((TAG_SEQUENCE, single, rest)) = tlv(responses) {
+                responses = rest;
+                if let Some(status) = single_status(single, serial) {
+                    return Some(status);
+                }
+            }
+            None
+        })
+        .unwrap_or(OcspStatus::Unknown)
+}
+
+/// Walks OCSPResponse > responseBytes > BasicOCSPResponse > tbsResponseData
+/// and returns the contents of the `responses` SEQUENCE OF SingleResponse
+fn single_responses(response: &[u8]) -> Option<&[u8]> {
+    let (ocsp_response, _) = expect(TAG_SEQUENCE, response)?;
+    let (status, rest) = expect(TAG_ENUMERATED, ocsp_response)?;
+    if status != [0u8] {
+        log::info!("OCSP response status {:?}", status);
+        return None;
+    }
+    let (response_bytes, _) = expect(TAG_CONTEXT_0, rest)?;
+    let (response_bytes, _) = expect(TAG_SEQUENCE, response_bytes)?;
+    let (response_type, rest) = expect(TAG_OID, response_bytes)?;
+    if response_type != OID_PKIX_OCSP_BASIC {
+        log::info!("unsupported OCSP response type");
+        return None;
+    }
+    let (basic, _) = expect(TAG_OCTET_STRING, rest)?;
+    let (basic, _) = expect(TAG_SEQUENCE, basic)?;
+    let (tbs_response_data, _) = expect(TAG_SEQUENCE, basic)?;
+    // skip the optional version and the responderID CHOICE
+    let mut rest = tbs_response_data;
+    if let Some((TAG_CONTEXT_0, _, after)) = tlv(rest) {
+        rest = after;
+    }
+    rest = match tlv(rest)? {
+        (TAG_CONTEXT_1, _, after) | (TAG_CONTEXT_2, _, after) => after,
+        _ => return None,
+    };
+    let (_produced_at, rest) = expect(TAG_GENERALIZED_TIME, rest)?;
+    let (responses, _) = expect(TAG_SEQUENCE, rest)?;
+    Some(responses)
+}
+
+/// Returns the certStatus of a SingleResponse if its CertID matches `serial`
+fn single_status(single: &[u8], serial: &[u8]) -> Option<OcspStatus> {
+    let (cert_id, rest) = expect(TAG_SEQUENCE, single)?;
+    let (_hash_algorithm, cert_id) = expect(TAG_SEQUENCE, cert_id)?;
+    let (_issuer_name_hash, cert_id) = expect(TAG_OCTET_STRING, cert_id)?;
+    let (_issuer_key_hash, cert_id) = expect(TAG_OCTET_STRING, cert_id)?;
+    let (serial_number, _) = expect(TAG_INTEGER, cert_id)?;
+    if serial_number != serial {
+        return None;
+    }
+    match tlv(rest)? {
+        (TAG_STATUS_GOOD, _, _) => Some(OcspStatus::Good),
+        (TAG_STATUS_REVOKED, _, _) => Some(OcspStatus::Revoked),
+        _ => Some(OcspStatus::Unknown),
+    }
+}
+
+/// Reads a TLV with the `expected` tag, returning its contents and the remaining input
+fn expect(expected: u8, input: &[u8]) -> Option<(&[u8], &[u8])> {
+    match tlv(input)? {
+        (tag, contents, rest) if tag == expected => Some((contents, rest)),
+        _ => None,
+    }
+}
+
+/// Reads a single DER TLV, returning (tag, contents, remaining input)
+///
+/// Only single byte tags and definite lengths of up to 4 bytes are supported,
+/// which is all that appears in an OCSP response.
+fn tlv(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, input) = input.split_first()?;
+    let (&first, mut input) = input.split_first()?;
+    let len = if first < 0x80 {
+        first as usize
+    } else {
+        let count = (first & 0x7F) as usize;
+        if count == 0 || count > 4 || input.len() < count {
+            return None;
+        }
+        let len = input[..count].iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        input = &input[count..];
+        len
+    };
+    if input.len() < len {
+        return None;
+    }
+    Some((tag, &input[..len], &input[len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        if contents.len() < 0x80 {
+            out.push(contents.len() as u8);
+        } else {
+            out.push(0x82);
+            out.extend_from_slice(&(contents.len() as u16).to_be_bytes());
+        }
+        out.extend_from_slice(contents);
+        out
+    }
+
+    fn single(serial: &[u8], status: &[u8]) -> Vec<u8> {
+        let sha1 = der(TAG_SEQUENCE, &der(TAG_OID, &[0x2B, 0x0E, 0x03, 0x02, 0x1A]));
+        let cert_id = [
+            sha1,
+            der(TAG_OCTET_STRING, &[0x11; 20]),
+            der(TAG_OCTET_STRING, &[0x22; 20]),
+            der(TAG_INTEGER, serial),
+        ]
+        .concat();
+        let this_update = der(TAG_GENERALIZED_TIME, b"20240101000000Z");
+        der(TAG_SEQUENCE, &[der(TAG_SEQUENCE, &cert_id), status.to_vec(), this_update].concat())
+    }
+
+    fn response(singles: &[Vec<u8>]) -> Vec<u8> {
+        let tbs = [
+            der(TAG_CONTEXT_2, &der(TAG_OCTET_STRING, &[0x33; 20])),
+            der(TAG_GENERALIZED_TIME, b"20240101000000Z"),
+            der(TAG_SEQUENCE, &singles.concat()),
+        ]
+        .concat();
+        let basic = der(TAG_SEQUENCE, &der(TAG_SEQUENCE, &tbs));
+        let response_bytes =
+            der(TAG_SEQUENCE, &[der(TAG_OID, &OID_PKIX_OCSP_BASIC), der(TAG_OCTET_STRING, &basic)].concat());
+        der(TAG_SEQUENCE, &[der(TAG_ENUMERATED, &[0]), der(TAG_CONTEXT_0, &response_bytes)].concat())
+    }
+
+    #[test]
+    fn staple_good_and_revoked() {
+        let good = [TAG_STATUS_GOOD, 0x00];
+        let revoked = der(TAG_STATUS_REVOKED, &der(TAG_GENERALIZED_TIME, b"20240101000000Z"));
+        let staple = response(&[single(&[0x01, 0x02], &good), single(&[0x03, 0x04], &revoked)]);
+        assert_eq!(staple_status(&staple, &[0x01, 0x02]), OcspStatus::Good);
+        assert_eq!(staple_status(&staple, &[0x03, 0x04]), OcspStatus::Revoked);
+        assert_eq!(staple_status(&staple, &[0x05]), OcspStatus::Unknown);
+    }
+
+    #[test]
+    fn staple_malformed() {
+        assert_eq!(staple_status(&[], &[0x01]), OcspStatus::Unknown);
+        assert_eq!(staple_status(&[0x30, 0x03, 0x0A, 0x01, 0x01], &[0x01]), OcspStatus::Unknown);
+        assert_eq!(staple_status(&[0x30, 0x84, 0xFF, 0xFF, 0xFF, 0xFF], &[0x01]), OcspStatus::Unknown);
+    }
+}
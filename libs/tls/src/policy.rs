@@ -0,0 +1,231 @@
+// Trust policy applied on top of the rustls/webpki chain verification:
+// trust-anchor expiry, revocation (CRL & stapled OCSP) and per-host SPKI pinning.
+use std::fmt;
+use std::sync::Arc;
+
+use rkyv::{Archive, Deserialize, Serialize};
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{WebPkiSupportedAlgorithms, ring, verify_tls12_signature, verify_tls13_signature};
+use rustls::pki_types::{CertificateDer, CertificateRevocationListDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, Error, RootCertStore, SignatureScheme};
+use sha2::{Digest, Sha256};
+use x509_parser::prelude::{FromDer, X509Certificate};
+use x509_parser::time::ASN1Time;
+
+use crate::Tls;
+use crate::ocsp::{self, OcspStatus};
+
+pub const MAX_INFO_BYTES: usize = 512;
+pub const MAX_PIN_BYTES: usize = 512;
+/// trust-anchors expiring within this many days are flagged by `Tls::expiring()`
+pub const EXPIRY_WARN_DAYS: i64 = 30;
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Details of a trusted certificate that are not retained by the OwnedTrustAnchor,
+/// saved alongside it in the pddb under the same key.
+///
+/// Trust-anchors saved from webpki-roots (`net tls mozilla`) have no certificate
+/// at hand, and so have no AnchorInfo.
+#[derive(Archive, Serialize, Deserialize, Clone)]
+pub struct AnchorInfo {
+    pub subject: String,
+    pub serial: String,
+    /// seconds since the unix epoch
+    pub not_before: i64,
+    /// seconds since the unix epoch
+    pub not_after: i64,
+    pub spki_sha256: [u8; 32],
+}
+
+impl AnchorInfo {
+    pub fn from_x509(x509: &X509Certificate) -> Self {
+        Self {
+            subject: x509.subject().to_string(),
+            serial: x509.raw_serial_as_string(),
+            not_before: x509.validity().not_before.timestamp(),
+            not_after: x509.validity().not_after.timestamp(),
+            spki_sha256: spki_sha256(x509),
+        }
+    }
+
+    /// seconds until expiry at `now` (negative once expired)
+    pub fn remaining(&self, now: i64) -> i64 { self.not_after - now }
+
+    pub fn is_expired(&self, now: i64) -> bool { self.remaining(now) < 0 }
+
+    pub fn is_expiring(&self, now: i64) -> bool { self.remaining(now) < EXPIRY_WARN_DAYS * SECONDS_PER_DAY }
+
+    /// A short human readable expiry, for use in lists
+    pub fn expiry(&self, now: i64) -> String {
+        let days = self.remaining(now) / SECONDS_PER_DAY;
+        if self.is_expired(now) {
+            format!("⚠ expired {} ({} days ago)", date(self.not_after), -days)
+        } else if self.is_expiring(now) {
+            format!("⚠ expires {} ({} days)", date(self.not_after), days)
+        } else {
+            format!("expires {}", date(self.not_after))
+        }
+    }
+}
+
+impl fmt::Display for AnchorInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "🏛 {}\nserial {}\nvalid {} - {}\nSPKI {}",
+            self.subject,
+            self.serial,
+            date(self.not_before),
+            date(self.not_after),
+            hex(&self.spki_sha256)
+        )
+    }
+}
+
+/// The SPKI sha256 hashes pinned for a host, trusted on first use (TOFU).
+#[derive(Archive, Serialize, Deserialize, Clone, Default)]
+pub struct SpkiPins {
+    pub host: String,
+    pub pins: Vec<[u8; 32]>,
+}
+
+impl SpkiPins {
+    /// true if any certificate in the chain presents a pinned SPKI
+    pub fn matches(&self, chain: &[[u8; 32]]) -> bool { chain.iter().any(|spki| self.pins.contains(spki)) }
+}
+
+impl fmt::Display for SpkiPins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "📌 {}", self.host)?;
+        for pin in &self.pins {
+            write!(f, "\n\t{}", hex(pin))?;
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of checking a host's certificate chain against its SPKI pins
+#[derive(Debug, PartialEq, Eq)]
+pub enum PinCheck {
+    /// the chain presents a pinned SPKI
+    Pinned,
+    /// first use of this host, the end-entity SPKI has been pinned
+    Tofu,
+    /// the chain presents none of the pinned SPKIs, but the user accepted the change
+    Repinned,
+    /// the chain presents none of the pinned SPKIs and the user rejected the change
+    Rejected,
+}
+
+/// Verifies the certificate chain offered by a host against the trusted (saved) trust-anchors
+/// and any saved CRLs with the default rustls WebPkiVerifier, and then applies:
+/// * a revocation check against any OCSP response stapled by the host
+/// * the per-host SPKI pins, trusting a host's key on first use
+#[derive(Debug)]
+pub struct PolicyVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    supported: WebPkiSupportedAlgorithms,
+}
+
+impl PolicyVerifier {
+    pub fn new(
+        roots: RootCertStore,
+        crls: Vec<CertificateRevocationListDer<'static>>,
+    ) -> Result<Self, rustls::client::VerifierBuilderError> {
+        // CRLs are checked "when available" - the absence of a CRL for an issuer is not an error
+        let inner = WebPkiServerVerifier::builder(Arc::new(roots))
+            .with_crls(crls)
+            .allow_unknown_revocation_status()
+            .build()?;
+        Ok(Self { inner, supported: ring::default_provider().signature_verification_algorithms })
+    }
+}
+
+impl ServerCertVerifier for PolicyVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer,
+        intermediates: &[CertificateDer],
+        server_name: &ServerName,
+        ocsp: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let verified = self.inner.verify_server_cert(end_entity, intermediates, server_name, ocsp, now)?;
+        let x509 = match X509Certificate::from_der(end_entity.as_ref()) {
+            Ok((_, x509)) => x509,
+            Err(e) => {
+                log::warn!("failed to parse end-entity certificate: {e}");
+                return Err(Error::InvalidCertificate(CertificateError::BadEncoding));
+            }
+        };
+        if ocsp.len() > 0 {
+            match ocsp::staple_status(ocsp, x509.raw_serial()) {
+                OcspStatus::Revoked => {
+                    log::warn!("stapled OCSP response reports {} revoked", x509.subject());
+                    return Err(Error::InvalidCertificate(CertificateError::Revoked));
+                }
+                status => log::info!("stapled OCSP status {:?}", status),
+            }
+        }
+        let mut chain = vec![spki_sha256(&x509)];
+        chain.extend(
+            intermediates
+                .iter()
+                .filter_map(|cert| X509Certificate::from_der(cert.as_ref()).ok())
+                .map(|(_, x509)| spki_sha256(&x509)),
+        );
+        match Tls::new().check_pins(&host(server_name), &chain) {
+            PinCheck::Rejected => {
+                Err(Error::InvalidCertificate(CertificateError::ApplicationVerificationFailure))
+            }
+            _ => Ok(verified),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls12_signature(message, cert, dss, &self.supported)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls13_signature(message, cert, dss, &self.supported)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> { self.supported.supported_schemes() }
+}
+
+/// sha256 of the DER encoded SubjectPublicKeyInfo (as used by HPKP)
+pub fn spki_sha256(x509: &X509Certificate) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(x509.public_key().raw);
+    hasher.finalize().into()
+}
+
+/// current time, in seconds since the unix epoch
+pub fn now() -> i64 { ASN1Time::now().timestamp() }
+
+fn host(server_name: &ServerName) -> String {
+    match server_name {
+        ServerName::DnsName(name) => name.as_ref().to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+fn date(timestamp: i64) -> String {
+    match ASN1Time::from_timestamp(timestamp) {
+        Ok(time) => time.to_datetime().date().to_string(),
+        Err(_) => format!("{timestamp}"),
+    }
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String { bytes.iter().map(|b| format!("{:02X}", b)).collect() }
@@ -20,9 +20,7 @@ impl ureq::TlsConnector for TlsConnector {
                 loop {
                     // refresh rustls client config with current root_store
                     let tls = Tls::new();
-                    let config = rustls::ClientConfig::builder()
-                        .with_root_certificates(tls.root_store())
-                        .with_no_client_auth();
+                    let config = tls.client_config();
                     match rustls::ClientConnection::new(Arc::new(config), server_name.clone()) {
                         Ok(mut connection) => {
                             log::info!("tls handshake started");
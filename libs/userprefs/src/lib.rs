@@ -50,6 +50,10 @@ pub struct UserPrefs {
     pub headset_volume: u32,
     pub autotype_rate: usize,
     pub lefty_mode: bool,
+    /// one of `locales::LANGUAGES`; empty for the language the image was built for
+    pub language: String,
+    /// whether the GAM's screen reader speaks; images built with speech have it on until it's turned off
+    pub screen_reader: bool,
 }
 
 pub struct Manager {
@@ -84,6 +84,16 @@ fn generate_code(translations: Translations) -> proc_macro2::TokenStream {
         let mut langs = Vec::<TokenStream>::new();
         let mut needs_interpolation = false;
         let mut vars = Vec::new();
+        // the language is picked at runtime, so a string that isn't translated yet shows in English
+        // rather than taking the process down
+        let fallback = match trs.get("en") {
+            Some(tr) => {
+                let en_vars = extract_vars(tr);
+                let idents = convert_vars_to_idents(&en_vars);
+                quote! { _ => #tr#(.replace(#en_vars, $#idents))*, }
+            }
+            None => quote! { e => panic!("Missing language: {}", e) },
+        };
         for (lang, tr) in trs {
             let lang_vars = extract_vars(&tr);
             needs_interpolation = lang_vars.len() > 0;
@@ -110,7 +120,7 @@ fn generate_code(translations: Translations) -> proc_macro2::TokenStream {
                 (#key, #(#vars_ident: $#vars_ident:expr, )*$lang:expr) => {
                     match $lang.as_ref() {
                         #(#langs)*
-                        e => panic!("Missing language: {}", e)
+                        #fallback
                     }
                 };
             });
@@ -124,7 +134,7 @@ fn generate_code(translations: Translations) -> proc_macro2::TokenStream {
                 (#key, $lang:expr) => {
                     match $lang.as_ref() {
                         #(#langs)*
-                        e => panic!("Missing language: {}", e)
+                        #fallback
                     }
                 };
             });
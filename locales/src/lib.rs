@@ -1,6 +1,39 @@
 #![no_std]
 
+use core::sync::atomic::{AtomicUsize, Ordering};
+
 pub mod locale;
 pub use locale::LANG;
 
 pub mod generated;
+
+/// Every language the translations are compiled in for. Indices into this list are what gets passed
+/// between processes when the language changes, so only ever append to it.
+pub const LANGUAGES: &[&str] = &["en", "ja", "zh", "fr", "en-tts"];
+
+/// Index into `LANGUAGES` of the language selected at runtime; `usize::MAX` until one is selected.
+static CURRENT: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// The language to look strings up in: the one selected at runtime, or else the one the image was built
+/// for (`LANG`). Pass this as the second argument of `t!()`.
+pub fn lang() -> &'static str {
+    match LANGUAGES.get(CURRENT.load(Ordering::Relaxed)) {
+        Some(lang) => lang,
+        None => LANG,
+    }
+}
+
+/// Selects the language this process looks its strings up in. Returns false, and changes nothing, if
+/// the language isn't one of `LANGUAGES`.
+pub fn set_lang(lang: &str) -> bool {
+    match lang_index(lang) {
+        Some(index) => {
+            CURRENT.store(index, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Position of `lang` in `LANGUAGES`.
+pub fn lang_index(lang: &str) -> Option<usize> { LANGUAGES.iter().position(|&l| l == lang) }
@@ -92,6 +92,8 @@ fn wrapped_main() -> ! {
     let sid = xns.register_name("bao video subsystem", None).expect("can't register server");
 
     let tt = ticktimer::Ticktimer::new().unwrap();
+    // codes read here go to the apps scanning for one
+    let qr_server = ::qr::Qr::new(&xns).unwrap();
 
     let iox = cram_hal_service::IoxHal::new();
     let udma_global = cram_hal_service::UdmaGlobal::new();
@@ -150,6 +152,7 @@ fn wrapped_main() -> ! {
     // could turn on the sync_sof field and skip this step.
     while iox.get_gpio_pin_value(IoxPort::PB, 9) == IoxValue::High {}
     cam.capture_async();
+    qr_server.camera_ready().ok();
 
     let mut frames = 0;
     let mut frame = [0u8; IMAGE_WIDTH * IMAGE_HEIGHT];
@@ -350,18 +353,23 @@ fn wrapped_main() -> ! {
                                 }
                                 println!(" {:2}", y);
                             }
-                            let simple = rqrr::SimpleGrid::from_func(modules, |x, y| {
+                            match ::qr::decode::decode_modules(modules, |x, y| {
                                 grid[(modules - 1) - x + y * modules]
-                            });
-                            let grid = rqrr::Grid::new(simple);
-                            match grid.decode() {
-                                Ok((meta, content)) => {
-                                    log::info!("meta: {:?}", meta);
+                            }) {
+                                Ok(decoded) => {
+                                    let content = String::from_utf8_lossy(&decoded.data);
+                                    log::info!(
+                                        "version {}, ecc {}, mask {}",
+                                        decoded.version,
+                                        decoded.ecc_level,
+                                        decoded.mask
+                                    );
                                     log::info!("************ {} ***********", content);
                                     decode_success = true;
+                                    qr_server.scanned(&decoded.data).ok();
                                     gfx::msg(
                                         &mut sh1107,
-                                        &format!("{:?}", meta),
+                                        &format!("v{} mask {}", decoded.version, decoded.mask),
                                         Point::new(0, 0),
                                         Mono::White.into(),
                                         Mono::Black.into(),
@@ -0,0 +1,14 @@
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+pub enum Opcode {
+    /// The GAM's screen reader moved, or what it's reading changed; scalar of the cursor and node count
+    Refresh,
+    /// The display sent an input report; memory message of an `InputReport`
+    InputReport,
+    /// Exits the server
+    Quit,
+}
+
+#[derive(Debug, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct InputReport {
+    pub bytes: Vec<u8>,
+}
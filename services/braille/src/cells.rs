@@ -0,0 +1,71 @@
+//! Text to 8-dot braille cells, in North American Braille Computer Code: one cell per character, with
+//! dot 7 marking capitals and the other characters that share a 6-dot pattern with a lowercase one.
+//! Characters from the Unicode braille block pass straight through, so apps can show contracted braille
+//! or anything else they've worked out themselves.
+//!
+//! A cell is a byte with dot 1 in bit 0 through dot 8 in bit 7, which is both the order of the Unicode
+//! braille block and what HID braille displays expect.
+
+pub const DOT_7: u8 = 0x40;
+pub const DOT_8: u8 = 0x80;
+
+/// Braille ASCII: the 6-dot cells for ' ' through '_'
+const BRAILLE_ASCII: &str = "⠀⠮⠐⠼⠫⠩⠯⠄⠷⠾⠡⠬⠠⠤⠨⠌⠴⠂⠆⠒⠲⠢⠖⠶⠦⠔⠱⠰⠣⠿⠜⠹⠈⠁⠃⠉⠙⠑⠋⠛⠓⠊⠚⠅⠇⠍⠝⠕⠏⠟⠗⠎⠞⠥⠧⠺⠭⠽⠵⠪⠳⠻⠘⠸";
+
+fn braille_ascii(index: usize) -> u8 {
+    BRAILLE_ASCII.chars().nth(index).map_or(0, |ch| (ch as u32 - 0x2800) as u8)
+}
+
+/// The cell for `ch`. Characters that computer braille has no cell for show as a question mark with
+/// dot 8, so they can't be mistaken for one.
+pub fn cell(ch: char) -> u8 {
+    match ch as u32 {
+        c @ 0x2800..=0x28FF => (c - 0x2800) as u8,
+        c @ 0x20..=0x3F => braille_ascii(c as usize - 0x20),
+        c @ 0x40..=0x5F => braille_ascii(c as usize - 0x20) | DOT_7,
+        c @ 0x60..=0x7F => braille_ascii(c as usize - 0x40),
+        // tabs and the like are blank
+        0x00..=0x1F => 0,
+        _ => cell('?') | DOT_8,
+    }
+}
+
+pub fn cells(text: &str) -> Vec<u8> { text.chars().map(cell).collect() }
+
+/// Cells as Unicode braille, for showing them somewhere other than a display.
+pub fn to_unicode(cells: &[u8]) -> String {
+    cells.iter().map(|&c| char::from_u32(0x2800 + c as u32).unwrap_or(' ')).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_letters() {
+        assert_eq!(BRAILLE_ASCII.chars().count(), 64);
+        // a is dot 1, b dots 1-2, l dots 1-2-3
+        assert_eq!(cells("abl"), vec![0x01, 0x03, 0x07]);
+        assert_eq!(cell('A'), 0x01 | DOT_7);
+        assert_eq!(cell('w'), 0b0011_1010); // dots 2-4-5-6
+        assert_eq!(to_unicode(&cells("hello")), "⠓⠑⠇⠇⠕");
+    }
+
+    #[test]
+    fn test_digits_and_symbols() {
+        // digits are the lower-cell forms of a to j
+        assert_eq!(to_unicode(&cells("1290")), "⠂⠆⠔⠴");
+        assert_eq!(cell(' '), 0);
+        // the pairs that share a pattern differ by dot 7
+        assert_eq!(cell('['), cell('{') | DOT_7);
+        assert_eq!(cell('@'), cell('`') | DOT_7);
+        assert_eq!(cell('_'), cell('\u{7f}') | DOT_7);
+    }
+
+    #[test]
+    fn test_passthrough_and_unknown() {
+        assert_eq!(cells("⠁⣿"), vec![0x01, 0xFF]);
+        assert_eq!(cell('é'), cell('?') | DOT_8);
+        assert_eq!(cell('\t'), 0);
+    }
+}
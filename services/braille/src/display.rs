@@ -0,0 +1,123 @@
+//! The line of braille for the node under the screen reader's cursor, and the window of it that fits on
+//! the display. Lines longer than the display are read by panning across them.
+
+use gam::{A11yNode, A11yRole};
+
+use crate::cells;
+
+/// The node as one line of text. Roles and states are the short forms braille screen readers use, so
+/// the label gets as many cells as possible.
+pub fn line(node: &A11yNode) -> String {
+    let mut line = match (node.role, node.checked) {
+        (A11yRole::RadioButton, Some(true)) => String::from("(*) "),
+        (A11yRole::RadioButton, _) => String::from("( ) "),
+        (_, Some(true)) => String::from("[x] "),
+        (_, Some(false)) => String::from("[ ] "),
+        _ => String::new(),
+    };
+    line.push_str(&node.label);
+    let role = match node.role {
+        A11yRole::Menu => "mnu",
+        A11yRole::Dialog => "dlg",
+        A11yRole::Button => "btn",
+        A11yRole::Link => "lnk",
+        A11yRole::Image => "gra",
+        A11yRole::TextEntry if node.value.is_none() => "edt",
+        _ => "",
+    };
+    if !role.is_empty() {
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(role);
+    }
+    if let Some(value) = &node.value {
+        line.push_str(": ");
+        line.push_str(value);
+    }
+    line
+}
+
+pub struct Display {
+    width: usize,
+    line: Vec<u8>,
+    /// the first cell of the line that's on the display
+    pan: usize,
+}
+
+impl Display {
+    pub fn new(width: usize) -> Self { Display { width: width.max(1), line: Vec::new(), pan: 0 } }
+
+    /// Shows `text` from its start.
+    pub fn show(&mut self, text: &str) {
+        self.line = cells::cells(text);
+        self.pan = 0;
+    }
+
+    /// The cells on the display, blank-padded to its width.
+    pub fn window(&self) -> Vec<u8> {
+        let mut window: Vec<u8> = self.line.iter().skip(self.pan).take(self.width).copied().collect();
+        window.resize(self.width, 0);
+        window
+    }
+
+    /// Moves back a display's width. `false` if the start of the line is already showing.
+    pub fn pan_left(&mut self) -> bool {
+        if self.pan == 0 {
+            return false;
+        }
+        self.pan = self.pan.saturating_sub(self.width);
+        true
+    }
+
+    /// Moves on a display's width. `false` if the end of the line is already showing.
+    pub fn pan_right(&mut self) -> bool {
+        if self.pan + self.width >= self.line.len() {
+            return false;
+        }
+        self.pan += self.width;
+        true
+    }
+
+    /// Where in the line the cell under routing key `key` is, if there's text there.
+    pub fn routed(&self, key: usize) -> Option<usize> {
+        let at = self.pan + key;
+        if key < self.width && at < self.line.len() { Some(at) } else { None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line() {
+        assert_eq!(line(&A11yNode::new(A11yRole::Button, "Okay")), "Okay btn");
+        assert_eq!(line(&A11yNode::new(A11yRole::RadioButton, "Red").with_checked(true)), "(*) Red");
+        assert_eq!(line(&A11yNode::new(A11yRole::CheckBox, "Wifi").with_checked(false)), "[ ] Wifi");
+        assert_eq!(line(&A11yNode::new(A11yRole::TextEntry, "Name").with_value("Ann")), "Name: Ann");
+        assert_eq!(line(&A11yNode::new(A11yRole::TextEntry, "Password")), "Password edt");
+        assert_eq!(line(&A11yNode::new(A11yRole::Slider, "Volume").with_value("40%")), "Volume: 40%");
+        assert_eq!(line(&A11yNode::new(A11yRole::Image, "")), "gra");
+        assert_eq!(line(&A11yNode::new(A11yRole::Text, "hello")), "hello");
+    }
+
+    #[test]
+    fn test_panning() {
+        let mut display = Display::new(4);
+        display.show("abcdefghij");
+        assert_eq!(display.window(), cells::cells("abcd"));
+        assert!(!display.pan_left());
+        assert!(display.pan_right());
+        assert!(display.pan_right());
+        assert_eq!(display.window(), vec![cells::cell('i'), cells::cell('j'), 0, 0]);
+        assert_eq!(display.routed(1), Some(9));
+        assert_eq!(display.routed(2), None);
+        assert!(!display.pan_right());
+        assert!(display.pan_left());
+        assert_eq!(display.window(), cells::cells("efgh"));
+        display.show("xy");
+        assert_eq!(display.window(), vec![cells::cell('x'), cells::cell('y'), 0, 0]);
+        assert!(!display.pan_right());
+    }
+}
@@ -0,0 +1,444 @@
+//! The USB HID braille display protocol (usage page 0x41). A display describes itself in its report
+//! descriptor: a row of 8- or 6-dot cells it takes in an output report, and routing keys and buttons
+//! it sends in input reports. This finds those in the descriptor, builds the output report for a line
+//! of cells, and turns input reports back into key presses.
+
+pub const USAGE_PAGE_BRAILLE: u16 = 0x41;
+pub const USAGE_PAGE_BUTTON: u16 = 0x09;
+
+const CELL_8_DOT: u16 = 0x03;
+const CELL_6_DOT: u16 = 0x04;
+const ROUTER_KEY: u16 = 0x100;
+const KEYBOARD_DOT_1: u16 = 0x201;
+const KEYBOARD_DOT_8: u16 = 0x208;
+const KEYBOARD_SPACE: u16 = 0x209;
+const JOYSTICK_CENTER: u16 = 0x210;
+const JOYSTICK_UP: u16 = 0x211;
+const JOYSTICK_DOWN: u16 = 0x212;
+const JOYSTICK_LEFT: u16 = 0x213;
+const JOYSTICK_RIGHT: u16 = 0x214;
+const DPAD_CENTER: u16 = 0x215;
+const DPAD_UP: u16 = 0x216;
+const DPAD_DOWN: u16 = 0x217;
+const DPAD_LEFT: u16 = 0x218;
+const DPAD_RIGHT: u16 = 0x219;
+const PAN_LEFT: u16 = 0x21A;
+const PAN_RIGHT: u16 = 0x21B;
+const ROCKER_UP: u16 = 0x21C;
+const ROCKER_DOWN: u16 = 0x21D;
+const ROCKER_PRESS: u16 = 0x21E;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BrailleKey {
+    /// the routing key over the cell with this index
+    Router(usize),
+    PanLeft,
+    PanRight,
+    Up,
+    Down,
+    Left,
+    Right,
+    /// joystick or d-pad centre, or the rocker pressed in
+    Select,
+    /// a braille keyboard chord, as the dots of a cell
+    Chord(u8),
+    /// a button the display doesn't say the meaning of, numbered from 1
+    Button(u16),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Direction {
+    Input,
+    Output,
+}
+
+/// One Input or Output item of the descriptor: `count` fields of `size` bits each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Field {
+    direction: Direction,
+    report_id: u8,
+    /// bit offset in the report, not counting the report ID byte
+    offset: usize,
+    size: usize,
+    count: usize,
+    usage_page: u16,
+    /// the usages of a variable field, one per value; the range of an array field
+    usages: Vec<u16>,
+    usage_min: u16,
+    logical_min: i32,
+    /// arrays report which usage is active; variables report a value for each usage
+    variable: bool,
+}
+
+#[derive(Debug)]
+pub enum DescriptorError {
+    /// the descriptor ends in the middle of an item
+    Truncated,
+    /// there's no row of braille cells to write to
+    NoCells,
+}
+
+/// What a display's report descriptor says about it.
+#[derive(Debug)]
+pub struct BrailleHid {
+    fields: Vec<Field>,
+    /// the output field the cells go in
+    cells: usize,
+    /// set when the display only has 6-dot cells, which lose dots 7 and 8
+    six_dot: bool,
+    /// whether reports start with a report ID byte
+    numbered: bool,
+}
+
+#[derive(Clone, Default)]
+struct Globals {
+    usage_page: u16,
+    logical_min: i32,
+    report_size: usize,
+    report_id: u8,
+    report_count: usize,
+}
+
+impl BrailleHid {
+    pub fn from_descriptor(descriptor: &[u8]) -> Result<Self, DescriptorError> {
+        let mut fields = Vec::new();
+        let mut globals = Globals::default();
+        let mut stack: Vec<Globals> = Vec::new();
+        let mut usages: Vec<(u16, u16)> = Vec::new();
+        let mut usage_range: (Option<u16>, Option<u16>) = (None, None);
+        let mut range_page: Option<u16> = None;
+        // bits used so far, by report ID
+        let mut input_bits: Vec<(u8, usize)> = Vec::new();
+        let mut output_bits: Vec<(u8, usize)> = Vec::new();
+        let mut numbered = false;
+
+        let mut i = 0;
+        while i < descriptor.len() {
+            let prefix = descriptor[i];
+            if prefix == 0xFE {
+                // long items carry nothing for us
+                let len = *descriptor.get(i + 1).ok_or(DescriptorError::Truncated)? as usize;
+                i += 3 + len;
+                continue;
+            }
+            let size = match prefix & 0x3 {
+                3 => 4,
+                s => s as usize,
+            };
+            let data = descriptor.get(i + 1..i + 1 + size).ok_or(DescriptorError::Truncated)?;
+            let unsigned = data.iter().rev().fold(0u32, |acc, &b| (acc << 8) | b as u32);
+            let signed = match size {
+                1 => data[0] as i8 as i32,
+                2 => i16::from_le_bytes([data[0], data[1]]) as i32,
+                4 => unsigned as i32,
+                _ => 0,
+            };
+            i += 1 + size;
+            match (prefix >> 2) & 0x3 {
+                // main items
+                0 => {
+                    let tag = prefix >> 4;
+                    if tag == 0x8 || tag == 0x9 {
+                        let direction = if tag == 0x8 { Direction::Input } else { Direction::Output };
+                        let bits = if tag == 0x8 { &mut input_bits } else { &mut output_bits };
+                        let used = match bits.iter_mut().find(|(id, _)| *id == globals.report_id) {
+                            Some((_, used)) => used,
+                            None => {
+                                bits.push((globals.report_id, 0));
+                                &mut bits.last_mut().unwrap().1
+                            }
+                        };
+                        let offset = *used;
+                        *used += globals.report_size * globals.report_count;
+                        let constant = unsigned & 0x1 != 0;
+                        if !constant {
+                            let (page, field_usages, usage_min) = match usage_range {
+                                (Some(min), Some(max)) => (
+                                    range_page.unwrap_or(globals.usage_page),
+                                    (min..=max).collect::<Vec<u16>>(),
+                                    min,
+                                ),
+                                _ => (
+                                    usages.first().map_or(globals.usage_page, |&(page, _)| page),
+                                    usages.iter().map(|&(_, usage)| usage).collect(),
+                                    usages.first().map_or(0, |&(_, usage)| usage),
+                                ),
+                            };
+                            fields.push(Field {
+                                direction,
+                                report_id: globals.report_id,
+                                offset,
+                                size: globals.report_size,
+                                count: globals.report_count,
+                                usage_page: page,
+                                usages: field_usages,
+                                usage_min,
+                                logical_min: globals.logical_min,
+                                variable: unsigned & 0x2 != 0,
+                            });
+                        }
+                    }
+                    // every main item, collections included, starts the local items afresh
+                    usages.clear();
+                    usage_range = (None, None);
+                    range_page = None;
+                }
+                // global items
+                1 => match prefix >> 4 {
+                    0x0 => globals.usage_page = unsigned as u16,
+                    0x1 => globals.logical_min = signed,
+                    0x7 => globals.report_size = unsigned as usize,
+                    0x8 => {
+                        globals.report_id = unsigned as u8;
+                        numbered = true;
+                    }
+                    0x9 => globals.report_count = unsigned as usize,
+                    0xA => stack.push(globals.clone()),
+                    0xB => globals = stack.pop().unwrap_or_default(),
+                    _ => (),
+                },
+                // local items; four-byte usages carry their own page
+                2 => {
+                    let page = if size == 4 { (unsigned >> 16) as u16 } else { globals.usage_page };
+                    match prefix >> 4 {
+                        0x0 => usages.push((page, unsigned as u16)),
+                        0x1 => {
+                            usage_range.0 = Some(unsigned as u16);
+                            range_page = Some(page);
+                        }
+                        0x2 => usage_range.1 = Some(unsigned as u16),
+                        _ => (),
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let find_cells = |usage: u16| {
+            fields.iter().position(|f| {
+                f.direction == Direction::Output
+                    && f.usage_page == USAGE_PAGE_BRAILLE
+                    && f.usages.contains(&usage)
+                    && f.size == 8
+            })
+        };
+        let (cells, six_dot) = match (find_cells(CELL_8_DOT), find_cells(CELL_6_DOT)) {
+            (Some(cells), _) => (cells, false),
+            (None, Some(cells)) => (cells, true),
+            (None, None) => return Err(DescriptorError::NoCells),
+        };
+        Ok(BrailleHid { fields, cells, six_dot, numbered })
+    }
+
+    /// How many cells the display has.
+    pub fn cell_count(&self) -> usize { self.fields[self.cells].count }
+
+    /// The output report that shows `cells`, blank-padded or cut to the display's width.
+    pub fn output_report(&self, cells: &[u8]) -> Vec<u8> {
+        let field = &self.fields[self.cells];
+        let mask = if self.six_dot { 0x3F } else { 0xFF };
+        let bits: usize = self
+            .fields
+            .iter()
+            .filter(|f| f.direction == Direction::Output && f.report_id == field.report_id)
+            .map(|f| f.offset + f.size * f.count)
+            .max()
+            .unwrap_or(0);
+        let header = self.numbered as usize;
+        let mut report = vec![0u8; header + bits.div_ceil(8)];
+        if self.numbered {
+            report[0] = field.report_id;
+        }
+        // cells are whole bytes, and in every display seen so far they start on a byte boundary
+        let start = header + field.offset / 8;
+        for (slot, &cell) in report[start..start + field.count].iter_mut().zip(cells.iter()) {
+            *slot = cell & mask;
+        }
+        report
+    }
+
+    /// The keys held down in an input report.
+    pub fn decode_input(&self, report: &[u8]) -> Vec<BrailleKey> {
+        let (id, data) = if self.numbered {
+            match report.split_first() {
+                Some((&id, data)) => (id, data),
+                None => return Vec::new(),
+            }
+        } else {
+            (0, report)
+        };
+        let mut keys = Vec::new();
+        let mut chord = 0u8;
+        for field in self.fields.iter().filter(|f| f.direction == Direction::Input && f.report_id == id) {
+            for n in 0..field.count {
+                let value = read_bits(data, field.offset + n * field.size, field.size);
+                let usage = if field.variable {
+                    if value == 0 {
+                        continue;
+                    }
+                    match field.usages.get(n).or_else(|| field.usages.last()) {
+                        Some(&usage) => usage,
+                        None => continue,
+                    }
+                } else {
+                    // arrays give the index of the usage that's active; the lowest value means none
+                    let index = value as i64 - field.logical_min as i64;
+                    if value == 0 || index < 0 {
+                        continue;
+                    }
+                    field.usage_min.wrapping_add(index as u16)
+                };
+                let key = match (field.usage_page, usage) {
+                    (USAGE_PAGE_BRAILLE, ROUTER_KEY) if field.variable => Some(BrailleKey::Router(n)),
+                    (USAGE_PAGE_BRAILLE, KEYBOARD_DOT_1..=KEYBOARD_DOT_8) => {
+                        chord |= 1 << (usage - KEYBOARD_DOT_1);
+                        None
+                    }
+                    (USAGE_PAGE_BRAILLE, KEYBOARD_SPACE) => Some(BrailleKey::Chord(0)),
+                    (USAGE_PAGE_BRAILLE, PAN_LEFT) => Some(BrailleKey::PanLeft),
+                    (USAGE_PAGE_BRAILLE, PAN_RIGHT) => Some(BrailleKey::PanRight),
+                    (USAGE_PAGE_BRAILLE, JOYSTICK_UP | DPAD_UP | ROCKER_UP) => Some(BrailleKey::Up),
+                    (USAGE_PAGE_BRAILLE, JOYSTICK_DOWN | DPAD_DOWN | ROCKER_DOWN) => Some(BrailleKey::Down),
+                    (USAGE_PAGE_BRAILLE, JOYSTICK_LEFT | DPAD_LEFT) => Some(BrailleKey::Left),
+                    (USAGE_PAGE_BRAILLE, JOYSTICK_RIGHT | DPAD_RIGHT) => Some(BrailleKey::Right),
+                    (USAGE_PAGE_BRAILLE, JOYSTICK_CENTER | DPAD_CENTER | ROCKER_PRESS) => {
+                        Some(BrailleKey::Select)
+                    }
+                    (USAGE_PAGE_BUTTON, button) => Some(BrailleKey::Button(button)),
+                    _ => None,
+                };
+                keys.extend(key);
+            }
+        }
+        if chord != 0 {
+            // a chord with the space bar is the chord, not a space
+            keys.retain(|&k| k != BrailleKey::Chord(0));
+            keys.push(BrailleKey::Chord(chord));
+        }
+        keys
+    }
+}
+
+/// `size` bits from `offset`, least significant first as HID packs them; bits past the end read as 0.
+fn read_bits(data: &[u8], offset: usize, size: usize) -> u32 {
+    let mut value = 0u32;
+    for bit in 0..size.min(32) {
+        let at = offset + bit;
+        if data.get(at / 8).is_some_and(|&byte| byte & (1 << (at % 8)) != 0) {
+            value |= 1 << bit;
+        }
+    }
+    value
+}
+
+/// The report descriptor of a display with `cells` 8-dot cells and a routing key over each, plus pan
+/// keys and a rocker: report 1 is the keys in, report 2 the cells out. Used by displays that aren't
+/// real hardware.
+pub fn descriptor(cells: u8) -> Vec<u8> {
+    let router_bytes = (cells as usize).div_ceil(8);
+    let mut d = vec![
+        0x05, 0x41, // usage page (braille display)
+        0x09, 0x01, // usage (braille display)
+        0xA1, 0x01, // collection (application)
+        0x85, 0x01, //   report ID (1)
+        0x0A, 0xFA, 0x00, // usage (router set 1)
+        0xA1, 0x02, //   collection (logical)
+        0x0A, 0x00, 0x01, //     usage (router key)
+        0x15, 0x00, //     logical minimum (0)
+        0x25, 0x01, //     logical maximum (1)
+        0x75, 0x01, //     report size (1)
+        0x95, cells, //     report count (cells)
+        0x81, 0x02, //     input (data, variable, absolute)
+    ];
+    let padding = router_bytes * 8 - cells as usize;
+    if padding > 0 {
+        d.extend_from_slice(&[0x95, padding as u8, 0x81, 0x03]); // input (constant)
+    }
+    d.extend_from_slice(&[
+        0xC0, //   end collection
+        0x0A, 0x1A, 0x02, //   usage (pan left)
+        0x0A, 0x1B, 0x02, //   usage (pan right)
+        0x0A, 0x1C, 0x02, //   usage (rocker up)
+        0x0A, 0x1D, 0x02, //   usage (rocker down)
+        0x0A, 0x1E, 0x02, //   usage (rocker press)
+        0x95, 0x05, //   report count (5)
+        0x81, 0x02, //   input (data, variable, absolute)
+        0x95, 0x03, //   report count (3)
+        0x81, 0x03, //   input (constant)
+        0x85, 0x02, //   report ID (2)
+        0x09, 0x02, //   usage (braille row)
+        0xA1, 0x02, //   collection (logical)
+        0x09, 0x03, //     usage (8 dot braille cell)
+        0x15, 0x00, //     logical minimum (0)
+        0x26, 0xFF, 0x00, //     logical maximum (255)
+        0x75, 0x08, //     report size (8)
+        0x95, cells, //     report count (cells)
+        0x91, 0x02, //     output (data, variable, absolute)
+        0xC0, //   end collection
+        0xC0, // end collection
+    ]);
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_descriptor() {
+        let hid = BrailleHid::from_descriptor(&descriptor(20)).unwrap();
+        assert_eq!(hid.cell_count(), 20);
+        let report = hid.output_report(&[0x01, 0xFF, 0x07]);
+        assert_eq!(report.len(), 21);
+        assert_eq!(&report[..4], &[2, 0x01, 0xFF, 0x07]);
+        assert!(report[4..].iter().all(|&c| c == 0));
+        // longer lines are cut to the display
+        assert_eq!(hid.output_report(&[0x3F; 30]).len(), 21);
+    }
+
+    #[test]
+    fn test_input() {
+        let hid = BrailleHid::from_descriptor(&descriptor(20)).unwrap();
+        // report 1: 20 router bits padded to 3 bytes, then the five buttons
+        assert_eq!(
+            hid.decode_input(&[1, 0b0000_0100, 0, 0b0000_1000, 0]),
+            vec![BrailleKey::Router(2), BrailleKey::Router(19)]
+        );
+        assert_eq!(
+            hid.decode_input(&[1, 0, 0, 0, 0b0000_0011]),
+            vec![BrailleKey::PanLeft, BrailleKey::PanRight]
+        );
+        assert_eq!(hid.decode_input(&[1, 0, 0, 0, 0b0001_0000]), vec![BrailleKey::Select]);
+        assert!(hid.decode_input(&[2, 0, 0]).is_empty());
+        assert!(hid.decode_input(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_six_dot_array_display() {
+        // no report IDs, six-dot cells, and the keyboard as an array of dot usages
+        let d = [
+            0x05, 0x41, 0x09, 0x01, 0xA1, 0x01, // braille display application
+            0x1A, 0x01, 0x02, 0x2A, 0x09, 0x02, // usage minimum (dot 1), maximum (space)
+            0x15, 0x01, 0x25, 0x09, 0x75, 0x08, 0x95, 0x02, 0x81, 0x00, // 2 bytes, array
+            0x09, 0x04, 0x75, 0x08, 0x95, 0x0C, 0x91, 0x02, // 12 six-dot cells out
+            0xC0,
+        ];
+        let hid = BrailleHid::from_descriptor(&d).unwrap();
+        assert_eq!(hid.cell_count(), 12);
+        assert_eq!(hid.output_report(&[0xFF]), {
+            let mut r = vec![0x3F];
+            r.resize(12, 0);
+            r
+        });
+        // dots 1 and 4 held
+        assert_eq!(hid.decode_input(&[1, 4]), vec![BrailleKey::Chord(0b1001)]);
+        // space alone
+        assert_eq!(hid.decode_input(&[9, 0]), vec![BrailleKey::Chord(0)]);
+    }
+
+    #[test]
+    fn test_bad_descriptors() {
+        assert!(matches!(BrailleHid::from_descriptor(&[0x05, 0x41, 0x09]), Err(DescriptorError::Truncated)));
+        assert!(matches!(BrailleHid::from_descriptor(&[0x05, 0x01, 0xC0]), Err(DescriptorError::NoCells)));
+    }
+}
@@ -0,0 +1,117 @@
+#![cfg_attr(target_os = "none", no_std)]
+#![cfg_attr(target_os = "none", no_main)]
+
+mod api;
+mod cells;
+mod display;
+mod hid;
+mod transport;
+
+use api::*;
+use gam::ReaderCommand;
+use hid::{BrailleHid, BrailleKey};
+use num_traits::{FromPrimitive, ToPrimitive};
+use transport::Transport;
+use xous_ipc::Buffer;
+
+/// The width of the display used when none is plugged in
+const VIRTUAL_CELLS: u8 = 40;
+
+fn refresh(display: &display::Display, hid: &BrailleHid, transport: &mut dyn Transport) {
+    if let Err(e) = transport.write_report(&hid.output_report(&display.window())) {
+        log::warn!("couldn't write to the braille display: {:?}", e);
+    }
+}
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    // nobody looks this server up: the GAM and the transport are handed its SID
+    let braille_sid = xous::create_server().unwrap();
+    let self_conn = xous::connect(braille_sid).unwrap();
+
+    let mut transport = transport::VirtualDisplay::new(VIRTUAL_CELLS);
+    let hid = match BrailleHid::from_descriptor(&transport.descriptor()) {
+        Ok(hid) => hid,
+        Err(e) => {
+            log::error!("the display's report descriptor can't be used ({:?}), exiting", e);
+            xous::destroy_server(braille_sid).unwrap();
+            xous::terminate_process(1)
+        }
+    };
+    log::info!("braille display of {} cells", hid.cell_count());
+    let mut display = display::Display::new(hid.cell_count());
+    transport.start(self_conn);
+
+    let gam = gam::Gam::new(&xous_names::XousNames::new().unwrap()).expect("can't connect to GAM");
+    gam.register_accessibility_listener(braille_sid, Opcode::Refresh.to_u32().unwrap())
+        .expect("couldn't listen to the screen reader");
+
+    loop {
+        let msg = xous::receive_message(braille_sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::Refresh) => xous::msg_scalar_unpack!(msg, cursor, count, _, _, {
+                log::debug!("screen reader on node {} of {}", cursor, count);
+                match gam.accessibility_tree() {
+                    Ok(tree) => display.show(&tree.focused().map(display::line).unwrap_or_default()),
+                    Err(e) => {
+                        log::warn!("couldn't fetch the accessibility tree: {:?}", e);
+                        display.show("");
+                    }
+                }
+                refresh(&display, &hid, &mut transport);
+            }),
+            Some(Opcode::InputReport) => {
+                let report = {
+                    let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                    buffer.to_original::<InputReport, _>().unwrap()
+                };
+                for key in hid.decode_input(&report.bytes) {
+                    // panning past either end of the line moves on to the node beside it
+                    let command = match key {
+                        BrailleKey::PanLeft if display.pan_left() => None,
+                        BrailleKey::PanRight if display.pan_right() => None,
+                        BrailleKey::PanLeft | BrailleKey::Up | BrailleKey::Left => {
+                            Some(ReaderCommand::Previous)
+                        }
+                        BrailleKey::PanRight | BrailleKey::Down | BrailleKey::Right => {
+                            Some(ReaderCommand::Next)
+                        }
+                        // the line is one node, so every routing key over it reads that node
+                        BrailleKey::Router(n) if display.routed(n).is_none() => continue,
+                        BrailleKey::Select | BrailleKey::Router(_) => Some(ReaderCommand::ReadCurrent),
+                        BrailleKey::Chord(dots) => {
+                            log::debug!("no binding for the chord {}", cells::to_unicode(&[dots]));
+                            continue;
+                        }
+                        BrailleKey::Button(n) => {
+                            log::debug!("no binding for button {}", n);
+                            continue;
+                        }
+                    };
+                    match command {
+                        // the GAM sends a refresh if the cursor moves
+                        Some(command) => {
+                            gam.reader_command(command, 0).ok();
+                        }
+                        None => refresh(&display, &hid, &mut transport),
+                    }
+                }
+            }
+            Some(Opcode::Quit) => {
+                log::warn!("Quit received, goodbye world!");
+                break;
+            }
+            None => {
+                log::error!("couldn't convert opcode: {:?}", msg);
+            }
+        }
+    }
+    // clean up our program
+    log::trace!("main loop exit, destroying servers");
+    xous::destroy_server(braille_sid).unwrap();
+    log::trace!("quitting");
+    xous::terminate_process(0)
+}
@@ -0,0 +1,37 @@
+//! How reports get to and from a display. A transport hands over the display's report descriptor,
+//! writes output reports, and sends the input reports it reads to the server as `Opcode::InputReport`.
+
+use crate::cells;
+use crate::hid;
+
+pub trait Transport {
+    fn descriptor(&self) -> Vec<u8>;
+    fn write_report(&mut self, report: &[u8]) -> Result<(), xous::Error>;
+    /// Starts reading input reports, to be sent to `cid`. Displays without keys needn't.
+    fn start(&mut self, _cid: xous::CID) {}
+}
+
+/// A display that's only a line in the log, for developing without one plugged in. The USB port on a
+/// Precursor is device-only, so it's also the only display there is until there's a host transport.
+pub struct VirtualDisplay {
+    cells: u8,
+}
+
+impl VirtualDisplay {
+    pub fn new(cells: u8) -> Self { VirtualDisplay { cells } }
+}
+
+impl Transport for VirtualDisplay {
+    fn descriptor(&self) -> Vec<u8> { hid::descriptor(self.cells) }
+
+    fn write_report(&mut self, report: &[u8]) -> Result<(), xous::Error> {
+        // report ID 2 is the row of cells
+        match report.split_first() {
+            Some((2, row)) => {
+                log::info!("braille: |{}|", cells::to_unicode(row));
+                Ok(())
+            }
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+}
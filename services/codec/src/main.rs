@@ -3,10 +3,13 @@
 
 mod api;
 mod backend;
+use std::collections::HashSet;
+
 use api::*;
 use backend::Codec;
 use log::info;
 use num_traits::{FromPrimitive, ToPrimitive};
+use permissions_api::{Capability, Permissions};
 use xous::{CID, msg_scalar_unpack};
 use xous_ipc::Buffer;
 
@@ -34,6 +37,12 @@ fn wrapped_main() -> ! {
 
     let codec_conn = xous::connect(codec_sid).expect("couldn't make connection for the codec implementation");
     let mut codec = Box::new(Codec::new(codec_conn, &xns));
+    // the play and record rings are filled and drained against the audio clock, so a page fault on
+    // them is a dropout
+    #[cfg(feature = "swap")]
+    xous_swapper::Swapper::new()
+        .and_then(|swapper| swapper.advise_object(&*codec, xous_swapper::SwapAdvice::Wired))
+        .unwrap_or_else(|e| log::warn!("couldn't wire the audio buffers: {:?}", e));
 
     let ticktimer = ticktimer_server::Ticktimer::new().unwrap();
     log::trace!("ready to accept requests");
@@ -58,6 +67,9 @@ fn wrapped_main() -> ! {
     let mut speaker_analog_gain_db: f32 = -6.0;
     let mut headphone_analog_gain_db: f32 = -15.0;
     let mut audio_cb_conns: [Option<ScalarCallback>; 32] = [None; 32];
+    // apps need the `audio` capability to play or record
+    let permissions = Permissions::new();
+    let mut audio_pids: HashSet<xous::PID> = HashSet::new();
     loop {
         let mut msg = xous::receive_message(codec_sid).unwrap();
         let op: Option<api::Opcode> = FromPrimitive::from_usize(msg.body.id());
@@ -80,7 +92,9 @@ fn wrapped_main() -> ! {
                 codec.init();
             }),
             Some(api::Opcode::ResumeStream) => xous::msg_scalar_unpack!(msg, _, _, _, _, {
-                if codec.is_on() && codec.is_init() {
+                if !audio_allowed(&permissions, &mut audio_pids, msg.sender) {
+                    log::warn!("PID {:?} is not allowed to use audio, ignoring!", msg.sender.pid());
+                } else if codec.is_on() && codec.is_init() {
                     codec.audio_i2s_start();
                 } else {
                     log::error!("attempted to resume a stream on an unitialized codec, ignoring!")
@@ -114,6 +128,9 @@ fn wrapped_main() -> ! {
                 xous::return_scalar2(msg.sender, play_free, rec_avail).expect("couldn't return FreeFrames");
             }),
             Some(api::Opcode::SwapFrames) => {
+                if !audio_allowed(&permissions, &mut audio_pids, msg.sender) {
+                    continue;
+                }
                 let mut buffer =
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let mut framering = buffer.to_original::<codec::api::FrameRing, _>().unwrap();
@@ -160,6 +177,10 @@ fn wrapped_main() -> ! {
                 buffer.replace(framering).unwrap();
             }
             Some(api::Opcode::AudioStreamSubscribe) => {
+                if !audio_allowed(&permissions, &mut audio_pids, msg.sender) {
+                    log::warn!("PID {:?} is not allowed to use audio, not hooking", msg.sender.pid());
+                    continue;
+                }
                 let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
                 let hookdata = buffer.to_original::<ScalarHook, _>().unwrap();
                 log::trace!("hooking {:?}", hookdata);
@@ -263,6 +284,27 @@ fn wrapped_main() -> ! {
     xous::terminate_process(0)
 }
 
+/// Streaming swaps frames many times a second, so processes that were allowed audio are remembered
+/// rather than asked about for every frame.
+fn audio_allowed(
+    permissions: &Permissions,
+    audio_pids: &mut HashSet<xous::PID>,
+    sender: xous::MessageSender,
+) -> bool {
+    if let Some(pid) = sender.pid() {
+        if audio_pids.contains(&pid) {
+            return true;
+        }
+    }
+    if !permissions.check(sender, Capability::Audio) {
+        return false;
+    }
+    if let Some(pid) = sender.pid() {
+        audio_pids.insert(pid);
+    }
+    true
+}
+
 fn do_hook(hookdata: ScalarHook, cb_conns: &mut [Option<ScalarCallback>; 32]) {
     let (s0, s1, s2, s3) = hookdata.sid;
     let sid = xous::SID::from_u32(s0, s1, s2, s3);
@@ -8,7 +8,7 @@ pub fn create_app_menu(status_conn: xous::CID) {
     let mut menu_items = Vec::<MenuItem>::new();
 
     menu_items.push(MenuItem {
-        name: String::from(t!("appmenu.shellchat", locales::LANG)),
+        name: String::from(t!("appmenu.shellchat", locales::lang())),
         action_conn: Some(status_conn),
         action_opcode: StatusOpcode::SwitchToShellchat.to_u32().unwrap(),
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
@@ -19,7 +19,7 @@ pub fn create_app_menu(status_conn: xous::CID) {
     app_autogen::app_menu_items(&mut menu_items, status_conn);
 
     menu_items.push(MenuItem {
-        name: String::from(t!("mainmenu.closemenu", locales::LANG)),
+        name: String::from(t!("mainmenu.closemenu", locales::lang())),
         action_conn: None,
         action_opcode: 0,
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
@@ -92,6 +92,8 @@ mod usb;
 use usb::*;
 mod mbox;
 use mbox::*;
+mod swap;
+use swap::*;
 
 pub struct CmdEnv {
     common_env: CommonEnv,
@@ -103,6 +105,7 @@ pub struct CmdEnv {
     #[cfg(feature = "usb")]
     usb_cmd: Usb,
     mbox: Mbox,
+    swap: Swap,
 }
 impl CmdEnv {
     pub fn new(xns: &xous_names::XousNames) -> CmdEnv {
@@ -134,6 +137,7 @@ impl CmdEnv {
                 Usb::new()
             },
             mbox: Mbox::new(),
+            swap: Swap::new(),
         }
     }
 
@@ -158,6 +162,7 @@ impl CmdEnv {
             #[cfg(feature = "usb")]
             &mut self.usb_cmd,
             &mut self.mbox,
+            &mut self.swap,
         ];
 
         if let Some(cmdline) = maybe_cmdline {
@@ -0,0 +1,97 @@
+use String;
+
+use crate::{CommonEnv, ShellCmdApi};
+
+pub struct Swap {
+    swapper: xous_swapper::Swapper,
+}
+impl Swap {
+    pub fn new() -> Self {
+        Swap { swapper: xous_swapper::Swapper::new().expect("couldn't connect to swapper") }
+    }
+}
+
+impl<'a> ShellCmdApi<'a> for Swap {
+    cmd_api!(swap);
+
+    // inserts boilerplate for command API
+
+    fn process(&mut self, args: String, _env: &mut CommonEnv) -> Result<Option<String>, xous::Error> {
+        use core::fmt::Write;
+        let mut ret = String::new();
+        let helpstring = "swap [stats] [pid <n>] [policy <lru|clock|ws>]";
+
+        let mut tokens = args.split(' ');
+
+        if let Some(sub_cmd) = tokens.next() {
+            match sub_cmd {
+                "stats" => {
+                    let s = self.swapper.swap_stats()?;
+                    write!(
+                        ret,
+                        "slots {}/{} used\ncompressed {} pages, {}kiB saved\nhard OOMs {}\nintegrity retries {}\nsteal rejects {}\n",
+                        s.used,
+                        s.slots,
+                        s.compressed,
+                        s.bytes_saved / 1024,
+                        s.hard_ooms,
+                        s.integrity_retries,
+                        s.steal_rejected
+                    )
+                    .unwrap();
+                    // PIDs are sparse and few, so just probe them all
+                    for pid in 1..=u8::MAX {
+                        let pid = xous::PID::new(pid).unwrap();
+                        match self.swapper.process_stats(pid) {
+                            Ok(p) if p.pages_out != 0 || p.faults != 0 => {
+                                write!(
+                                    ret,
+                                    "PID{}: out {} in {} now {} lat {}/{}\n",
+                                    pid, p.pages_out, p.faults, p.swapped, p.latency_avg, p.latency_max
+                                )
+                                .unwrap();
+                            }
+                            Ok(_) => {}
+                            Err(_) => break,
+                        }
+                    }
+                }
+                "pid" => {
+                    match tokens.next().and_then(|pid| pid.parse::<u8>().ok()).and_then(xous::PID::new) {
+                        Some(pid) => {
+                            let p = self.swapper.process_stats(pid)?;
+                            write!(
+                                ret,
+                                "PID{}\npages out {}\nfaults {}\nin swap {}\nlatency avg {} max {} cycles",
+                                pid, p.pages_out, p.faults, p.swapped, p.latency_avg, p.latency_max
+                            )
+                            .unwrap();
+                        }
+                        None => write!(ret, "{}", helpstring).unwrap(),
+                    }
+                }
+                "policy" => {
+                    let policy = match tokens.next() {
+                        Some("lru") => Some(xous_swapper::PolicyKind::LruEpoch),
+                        Some("clock") => Some(xous_swapper::PolicyKind::Clock),
+                        Some("ws") => Some(xous_swapper::PolicyKind::WorkingSet),
+                        _ => None,
+                    };
+                    match policy {
+                        Some(policy) => {
+                            let previous = self.swapper.set_policy(policy)?;
+                            write!(ret, "replacement policy {:?} -> {:?}", previous, policy).unwrap();
+                        }
+                        None => write!(ret, "{}", helpstring).unwrap(),
+                    }
+                }
+                _ => {
+                    write!(ret, "{}", helpstring).unwrap();
+                }
+            }
+        } else {
+            write!(ret, "{}", helpstring).unwrap();
+        }
+        Ok(Some(ret))
+    }
+}
@@ -151,7 +151,7 @@ fn main() {
     uptime_tv.style = GlyphStyle::Tall;
     uptime_tv.draw_border = false;
     uptime_tv.margin = Point::new(3, 0);
-    write!(uptime_tv, "{}", t!("secnote.startup", locales::LANG))
+    write!(uptime_tv, "{}", t!("secnote.startup", locales::lang()))
         .expect("|status: couldn't init uptime text");
     gam.post_textview(&mut uptime_tv).expect("|status: can't draw battery stats");
 
@@ -169,7 +169,7 @@ fn main() {
     security_tv.token = gam.claim_token(gam::STATUS_BAR_NAME).expect("couldn't request token"); // this is a shared magic word to identify this process
     security_tv.clear_area = true;
     security_tv.invert = true;
-    write!(&mut security_tv, "{}", t!("secnote.startup", locales::LANG)).unwrap();
+    write!(&mut security_tv, "{}", t!("secnote.startup", locales::lang())).unwrap();
     gam.post_textview(&mut security_tv).unwrap();
     gam.draw_line(
         status_gid,
@@ -241,7 +241,7 @@ fn main() {
                     write!(
                         &mut uptime_tv,
                         " {}{}:{:02}:{:02}",
-                        t!("stats.uptime", locales::LANG),
+                        t!("stats.uptime", locales::lang()),
                         (elapsed_time / 3_600_000),
                         (elapsed_time / 60_000) % 60,
                         (elapsed_time / 1000) % 60,
@@ -283,7 +283,7 @@ fn main() {
                             }
                         }
                     } else {
-                        write!(&mut security_tv, "{}", t!("secnote.allclear", locales::LANG)).unwrap();
+                        write!(&mut security_tv, "{}", t!("secnote.allclear", locales::lang())).unwrap();
                     }
 
                     secnotes_force_redraw = false;
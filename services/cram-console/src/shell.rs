@@ -66,7 +66,7 @@ fn shell() {
                 log::trace!("shell got input line: {}", s.as_str());
                 #[cfg(feature = "tts")]
                 {
-                    let mut input = t!("shellchat.input-tts", locales::LANG).to_string();
+                    let mut input = t!("shellchat.input-tts", locales::lang()).to_string();
                     input.push_str(s.as_str());
                     tts.tts_simple(&input).unwrap();
                 }
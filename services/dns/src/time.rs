@@ -583,7 +583,7 @@ pub(crate) fn start_time_ux() {
                     Some(crate::TimeUxOp::SetTime) => xous::msg_scalar_unpack!(msg, _, _, _, _, {
                         if !pddb_poller.is_mounted_nonblocking() {
                             modals
-                                .show_notification(t!("stats.please_mount", locales::LANG), None)
+                                .show_notification(t!("stats.please_mount", locales::lang()), None)
                                 .expect("couldn't show notification");
                             continue;
                         }
@@ -610,7 +610,7 @@ pub(crate) fn start_time_ux() {
                         if !tz_set {
                             log::info!("{}RTC.TZ,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                             let tz_str = modals
-                                .alert_builder(t!("rtc.timezone", locales::LANG))
+                                .alert_builder(t!("rtc.timezone", locales::lang()))
                                 .field(None, Some(tz_ux_validator))
                                 .build()
                                 .expect("couldn't get timezone")
@@ -635,15 +635,15 @@ pub(crate) fn start_time_ux() {
                         // see if we want to try to use NTP or not
                         log::info!("{}RTC.NTP,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                         modals
-                            .add_list_item(t!("pddb.yes", locales::LANG))
+                            .add_list_item(t!("pddb.yes", locales::lang()))
                             .expect("couldn't build radio item list");
                         modals
-                            .add_list_item(t!("pddb.no", locales::LANG))
+                            .add_list_item(t!("pddb.no", locales::lang()))
                             .expect("couldn't build radio item list");
                         let mut try_ntp = true;
-                        match modals.get_radiobutton(t!("rtc.try_ntp", locales::LANG)) {
+                        match modals.get_radiobutton(t!("rtc.try_ntp", locales::lang())) {
                             Ok(selection) => {
-                                if selection == t!("pddb.no", locales::LANG) {
+                                if selection == t!("pddb.no", locales::lang()) {
                                     try_ntp = false;
                                 }
                             }
@@ -687,7 +687,7 @@ pub(crate) fn start_time_ux() {
                                     log::info!("Err: {:?}", err);
                                     log::info!("{}RTC.NTPFAIL,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                                     modals
-                                        .show_notification(t!("rtc.ntp_fail", locales::LANG), None)
+                                        .show_notification(t!("rtc.ntp_fail", locales::lang()), None)
                                         .expect("couldn't show NTP error");
                                 }
                             }
@@ -701,29 +701,29 @@ pub(crate) fn start_time_ux() {
                         let mut years: u8 = 0;
 
                         let date = modals
-                            .alert_builder(t!("rtc.set_time_modal", locales::LANG))
+                            .alert_builder(t!("rtc.set_time_modal", locales::lang()))
                             .field(
-                                Some(String::from(t!("rtc.month", locales::LANG))),
+                                Some(String::from(t!("rtc.month", locales::lang()))),
                                 Some(rtc_ux_validate_month),
                             )
                             .field(
-                                Some(String::from(t!("rtc.day", locales::LANG))),
+                                Some(String::from(t!("rtc.day", locales::lang()))),
                                 Some(rtc_ux_validate_day),
                             )
                             .field(
-                                Some(String::from(t!("rtc.year", locales::LANG))),
+                                Some(String::from(t!("rtc.year", locales::lang()))),
                                 Some(rtc_ux_validate_year),
                             )
                             .field(
-                                Some(String::from(t!("rtc.hour", locales::LANG))),
+                                Some(String::from(t!("rtc.hour", locales::lang()))),
                                 Some(rtc_ux_validate_hour),
                             )
                             .field(
-                                Some(String::from(t!("rtc.minute", locales::LANG))),
+                                Some(String::from(t!("rtc.minute", locales::lang()))),
                                 Some(rtc_ux_validate_minute),
                             )
                             .field(
-                                Some(String::from(t!("rtc.seconds", locales::LANG))),
+                                Some(String::from(t!("rtc.seconds", locales::lang()))),
                                 Some(rtc_ux_validate_seconds),
                             )
                             .build()
@@ -770,13 +770,13 @@ pub(crate) fn start_time_ux() {
                     Some(crate::TimeUxOp::SetTimeZone) => xous::msg_scalar_unpack!(msg, _, _, _, _, {
                         if !pddb_poller.is_mounted_nonblocking() {
                             modals
-                                .show_notification(t!("stats.please_mount", locales::LANG), None)
+                                .show_notification(t!("stats.please_mount", locales::lang()), None)
                                 .expect("couldn't show notification");
                             continue;
                         }
 
                         let tz_str = modals
-                            .alert_builder(t!("rtc.timezone", locales::LANG))
+                            .alert_builder(t!("rtc.timezone", locales::lang()))
                             .field(None, Some(tz_ux_validator))
                             .build()
                             .expect("couldn't get timezone")
@@ -828,10 +828,10 @@ fn tz_ux_validator(input: &TextEntryPayload) -> Option<ValidatorErr> {
     match simple_kilofloat_parse(text_str) {
         Ok(input) => {
             if input < -12_000 || input > 14_000 {
-                return Some(ValidatorErr::from(t!("rtc.range_err", locales::LANG)));
+                return Some(ValidatorErr::from(t!("rtc.range_err", locales::lang())));
             }
         }
-        _ => return Some(ValidatorErr::from(t!("rtc.integer_err", locales::LANG))),
+        _ => return Some(ValidatorErr::from(t!("rtc.integer_err", locales::lang()))),
     }
     None
 }
@@ -841,11 +841,11 @@ fn rtc_ux_validate_month(input: &TextEntryPayload) -> Option<ValidatorErr> {
 
     let input = match text_str.parse::<u32>() {
         Ok(input_int) => input_int,
-        _ => return Some(ValidatorErr::from(t!("rtc.integer_err", locales::LANG))),
+        _ => return Some(ValidatorErr::from(t!("rtc.integer_err", locales::lang()))),
     };
 
     if input < 1 || input > 12 {
-        return Some(ValidatorErr::from(t!("rtc.range_err", locales::LANG)));
+        return Some(ValidatorErr::from(t!("rtc.range_err", locales::lang())));
     }
 
     None
@@ -856,11 +856,11 @@ fn rtc_ux_validate_day(input: &TextEntryPayload) -> Option<ValidatorErr> {
 
     let input = match text_str.parse::<u32>() {
         Ok(input_int) => input_int,
-        _ => return Some(ValidatorErr::from(t!("rtc.integer_err", locales::LANG))),
+        _ => return Some(ValidatorErr::from(t!("rtc.integer_err", locales::lang()))),
     };
 
     if input < 1 || input > 31 {
-        return Some(ValidatorErr::from(t!("rtc.range_err", locales::LANG)));
+        return Some(ValidatorErr::from(t!("rtc.range_err", locales::lang())));
     }
 
     None
@@ -871,11 +871,11 @@ fn rtc_ux_validate_year(input: &TextEntryPayload) -> Option<ValidatorErr> {
 
     let input = match text_str.parse::<u32>() {
         Ok(input_int) => input_int,
-        _ => return Some(ValidatorErr::from(t!("rtc.integer_err", locales::LANG))),
+        _ => return Some(ValidatorErr::from(t!("rtc.integer_err", locales::lang()))),
     };
 
     if input > 99 {
-        return Some(ValidatorErr::from(t!("rtc.range_err", locales::LANG)));
+        return Some(ValidatorErr::from(t!("rtc.range_err", locales::lang())));
     }
 
     None
@@ -886,11 +886,11 @@ fn rtc_ux_validate_hour(input: &TextEntryPayload) -> Option<ValidatorErr> {
 
     let input = match text_str.parse::<u32>() {
         Ok(input_int) => input_int,
-        _ => return Some(ValidatorErr::from(t!("rtc.integer_err", locales::LANG))),
+        _ => return Some(ValidatorErr::from(t!("rtc.integer_err", locales::lang()))),
     };
 
     if input > 23 {
-        return Some(ValidatorErr::from(t!("rtc.range_err", locales::LANG)));
+        return Some(ValidatorErr::from(t!("rtc.range_err", locales::lang())));
     }
 
     None
@@ -901,11 +901,11 @@ fn rtc_ux_validate_minute(input: &TextEntryPayload) -> Option<ValidatorErr> {
 
     let input = match text_str.parse::<u32>() {
         Ok(input_int) => input_int,
-        _ => return Some(ValidatorErr::from(t!("rtc.integer_err", locales::LANG))),
+        _ => return Some(ValidatorErr::from(t!("rtc.integer_err", locales::lang()))),
     };
 
     if input > 59 {
-        return Some(ValidatorErr::from(t!("rtc.range_err", locales::LANG)));
+        return Some(ValidatorErr::from(t!("rtc.range_err", locales::lang())));
     }
 
     None
@@ -916,11 +916,11 @@ fn rtc_ux_validate_seconds(input: &TextEntryPayload) -> Option<ValidatorErr> {
 
     let input = match text_str.parse::<u32>() {
         Ok(input_int) => input_int,
-        _ => return Some(ValidatorErr::from(t!("rtc.integer_err", locales::LANG))),
+        _ => return Some(ValidatorErr::from(t!("rtc.integer_err", locales::lang()))),
     };
 
     if input > 59 {
-        return Some(ValidatorErr::from(t!("rtc.range_err", locales::LANG)));
+        return Some(ValidatorErr::from(t!("rtc.range_err", locales::lang())));
     }
 
     None
@@ -4,9 +4,12 @@ use String;
 #[cfg(feature = "ditherpunk")]
 use graphics_server::api::Tile;
 use graphics_server::api::{Gid, Point};
+use locales::t;
 pub use rkyv_enum::*;
 
 pub(crate) const SERVER_NAME_GAM: &str = "_Graphical Abstraction Manager_";
+/// Answers `AppIdentity` from its own thread, so that looking up a caller never waits on the GAM's UI work
+pub(crate) const SERVER_NAME_GAM_APPS: &str = "_GAM app identities_";
 
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
 pub struct GamObject {
@@ -118,6 +121,146 @@ pub struct NameRegistration {
     pub auth_token: [u32; 4],
 }
 
+/// Which app, if any, a process belongs to, and what it may use. `app` is `None` for processes that
+/// haven't registered an app's UX or been spawned by the app loader.
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Clone)]
+pub struct AppIdentity {
+    pub pid: u32,
+    pub app: Option<String>,
+    pub capabilities: Vec<String>,
+    /// whether every app in the image has registered, so that a process that isn't an app by now is one
+    /// of the services in the boot image
+    pub booted: bool,
+}
+/// Capabilities for an app registered with `RegisterName`, which isn't in the image's manifest. `pid` is
+/// the process the loader spawned for it, which hasn't run the app yet; `granted` is set on return.
+#[cfg(feature = "unsafe-app-loading")]
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Clone)]
+pub struct CapabilityGrant {
+    pub name: String,
+    pub capabilities: Vec<String>,
+    pub pid: u32,
+    pub auth_token: [u32; 4],
+    pub granted: bool,
+}
+
+/// A server to be sent `opcode`, with the new language's index in `locales::LANGUAGES` as the first
+/// argument, whenever the language changes.
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub(crate) struct LocaleListener {
+    pub sid: [u32; 4],
+    pub opcode: u32,
+}
+
+/// One thing on screen, as the screen reader and a braille display see it.
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Clone, PartialEq, Eq)]
+pub struct A11yNode {
+    pub role: A11yRole,
+    pub label: String,
+    /// what's in a text entry, or where a slider is. `None` for secrets, which are never published.
+    pub value: Option<String>,
+    /// whether a check box is ticked or a radio button is the one chosen
+    pub checked: Option<bool>,
+    /// how far the node is nested, e.g. 1 for the items of a menu
+    pub depth: u8,
+}
+impl A11yNode {
+    pub fn new(role: A11yRole, label: &str) -> Self {
+        A11yNode { role, label: String::from(label), value: None, checked: None, depth: 0 }
+    }
+
+    pub fn with_value(mut self, value: &str) -> Self {
+        self.value = Some(String::from(value));
+        self
+    }
+
+    pub fn with_checked(mut self, checked: bool) -> Self {
+        self.checked = Some(checked);
+        self
+    }
+
+    pub fn nested(mut self, depth: u8) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// The node as the screen reader says it, in the current language.
+    pub fn spoken(&self) -> String {
+        let lang = locales::lang();
+        let mut words = vec![self.label.clone()];
+        match self.role {
+            A11yRole::Menu => words.push(t!("a11y.menu", lang).to_string()),
+            A11yRole::Dialog => words.push(t!("a11y.dialog", lang).to_string()),
+            A11yRole::Button => words.push(t!("a11y.button", lang).to_string()),
+            A11yRole::TextEntry => words.push(t!("a11y.text_entry", lang).to_string()),
+            A11yRole::Slider => words.push(t!("a11y.slider", lang).to_string()),
+            A11yRole::ProgressBar => words.push(t!("a11y.progress", lang).to_string()),
+            A11yRole::Image => words.push(t!("a11y.image", lang).to_string()),
+            A11yRole::Link => words.push(t!("a11y.link", lang).to_string()),
+            _ => (),
+        }
+        match (self.role, self.checked) {
+            (A11yRole::RadioButton, Some(true)) => words.push(t!("a11y.selected", lang).to_string()),
+            (A11yRole::RadioButton, Some(false)) => words.push(t!("a11y.not_selected", lang).to_string()),
+            (_, Some(true)) => words.push(t!("a11y.checked", lang).to_string()),
+            (_, Some(false)) => words.push(t!("a11y.not_checked", lang).to_string()),
+            _ => (),
+        }
+        match self.value.as_deref() {
+            Some("") => words.push(t!("a11y.blank", lang).to_string()),
+            Some(value) => words.push(value.to_string()),
+            None => (),
+        }
+        words.retain(|w| !w.is_empty());
+        words.join(", ")
+    }
+}
+
+/// Everything a context shows, in reading order. Menus and modals publish theirs as they redraw; apps
+/// can publish their own with `Gam::publish_accessibility()`. Contexts that don't are read from the text
+/// they draw.
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Clone, Default)]
+pub struct A11yTree {
+    /// the token of the context this describes: an app token, or a menu's or modal's. It is zero in the
+    /// trees `Gam::accessibility_tree()` returns.
+    pub token: [u32; 4],
+    pub nodes: Vec<A11yNode>,
+    /// index in `nodes` of what keys act on, such as the selected menu item
+    pub focus: Option<u16>,
+}
+impl A11yTree {
+    pub fn focused(&self) -> Option<&A11yNode> { self.focus.and_then(|i| self.nodes.get(i as usize)) }
+}
+
+/// A server to be sent `opcode` whenever the node under the screen reader's cursor, or the tree it's in,
+/// changes. The arguments are the index of the node, or `usize::MAX` for none, and how many nodes there
+/// are. Fetch the tree itself with `Gam::accessibility_tree()`.
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub(crate) struct AccessibilityListener {
+    pub sid: [u32; 4],
+    pub opcode: u32,
+}
+
+/// Moves the screen reader's cursor through the focused context's accessibility tree, or switches the
+/// reader's speech on and off. Cursor moves work with speech off too, for braille displays.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+pub enum ReaderCommand {
+    /// does nothing; for asking whether the reader speaks
+    Status = 0,
+    Previous = 1,
+    Next = 2,
+    First = 3,
+    Last = 4,
+    /// says the node under the cursor again
+    ReadCurrent = 5,
+    /// says every node from the cursor on
+    ReadAll = 6,
+    /// puts the cursor on the node whose index is the argument, as a braille display's routing keys do
+    Route = 7,
+    Speak = 8,
+    Silence = 9,
+}
+
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
 pub(crate) enum Opcode {
     // clears a canvas with a given GID
@@ -227,6 +370,29 @@ pub(crate) enum Opcode {
     /// Register a name that can acquire a token. This is only intended to be used with pre-registered apps
     #[cfg(feature = "unsafe-app-loading")]
     RegisterName = 34,
+
+    /// Selects the language strings are shown in, by its index in `locales::LANGUAGES`; status process only
+    SetLanguage = 35,
+    /// Returns the index in `locales::LANGUAGES` of the language strings are shown in
+    GetLanguage = 36,
+    /// Registers the status process's server to be told when the language changes
+    RegisterLocaleListener = 37,
+
+    /// Looks up the app a PID belongs to and the capabilities its manifest declares. Sent to
+    /// `SERVER_NAME_GAM_APPS`, not the main server.
+    AppIdentity = 38,
+    /// Sets the capabilities of a name registered with `RegisterName`. Same rules as `RegisterName`.
+    #[cfg(feature = "unsafe-app-loading")]
+    GrantCapabilities = 39,
+
+    /// Replaces the accessibility tree of the context whose token is in the `A11yTree`
+    PublishAccessibility = 40,
+    /// Fills in the accessibility tree of the focused context, focused on the screen reader's cursor
+    AccessibilityTree = 41,
+    /// Runs a `ReaderCommand`; returns whether the screen reader speaks
+    ReaderCommand = 42,
+    /// Registers a server to be told when what the screen reader is on changes
+    RegisterAccessibilityListener = 43,
 }
 
 // small wart -- we have to reset the size of a modal to max size for resize computations
@@ -45,6 +45,8 @@ pub(crate) enum MenuMgrOp {
     // response must be one of these
     Ok,
     Err,
+    /// renames the item named in the request to the name given here
+    RenameItem(String),
 }
 
 #[allow(dead_code)] // here until Memory types are implemented
@@ -57,3 +59,24 @@ pub enum MenuPayload {
     /// about it for now, it's mostly apirational...
     Memory(([u8; 256], usize)),
 }
+
+/// What a node in an accessibility tree is, which decides how the screen reader announces it
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub enum A11yRole {
+    /// the whole of an app's screen; its label is the app's name
+    Window,
+    Menu,
+    MenuItem,
+    Dialog,
+    /// text that can only be read
+    Text,
+    Button,
+    CheckBox,
+    RadioButton,
+    TextEntry,
+    Slider,
+    ProgressBar,
+    Image,
+    Link,
+    Notification,
+}
@@ -32,6 +32,14 @@ mod shrink;
 pub use shrink::*;
 mod dither;
 pub use dither::*;
+mod decode_jpeg;
+pub use decode_jpeg::*;
+mod decode_gif;
+pub use decode_gif::*;
+
+/// Images of more pixels than this are refused while reading their header, as no picture worth
+/// showing on the screen needs more, and a corrupt or hostile one could otherwise exhaust the heap.
+pub const MAX_PIXELS: u64 = 4 * 1024 * 1024;
 
 #[derive(Debug)]
 pub struct Bitmap {
@@ -72,14 +80,22 @@ impl Bitmap {
     }
 
     pub fn from_png<R: Read>(png: &mut DecodePng<R>, fit: Option<Point>) -> Self {
+        Bitmap::from_png_dithered(png, fit, Dithering::Burkes)
+    }
+
+    pub fn from_png_dithered<R: Read>(
+        png: &mut DecodePng<R>,
+        fit: Option<Point>,
+        dithering: Dithering,
+    ) -> Self {
         // Png Colortypes: 0=Grey, 2=Rgb, 3=Palette, 4=GreyAlpha, 6=Rgba.
         let px_type = match (png.color_type(), png.bit_depth()) {
-            (0, 1 | 2 | 4) => PixelType::U0, // Unsupported
+            (0, 1 | 2 | 4) => PixelType::U8, // unpacked by the decoder
             (0, 8) => PixelType::U8,
             (0, 16) => PixelType::U16,
             (2, 8) => PixelType::U8x3,
             (2, 16) => PixelType::U16x3,
-            (3, 1 | 2 | 4 | 8) => PixelType::U0, // Unsupported
+            (3, 1 | 2 | 4 | 8) => PixelType::U8, // looked up by the decoder
             (4, 8) => PixelType::U8x2,
             (4, 16) => PixelType::U16x2,
             (6, 8) => PixelType::U8x4,
@@ -87,7 +103,17 @@ impl Bitmap {
             (_, _) => PixelType::U0, // Invalid combination
         };
         let px_size = Point::new(png.width().try_into().unwrap(), png.height().try_into().unwrap());
-        Bitmap::from_iter(png, px_type, px_size, fit)
+        Bitmap::from_iter_dithered(png, px_type, px_size, fit, dithering)
+    }
+
+    pub fn from_jpeg<R: Read>(jpeg: &mut DecodeJpeg<R>, fit: Option<Point>, dithering: Dithering) -> Self {
+        let px_size = Point::new(jpeg.width().try_into().unwrap(), jpeg.height().try_into().unwrap());
+        Bitmap::from_iter_dithered(jpeg, PixelType::U8, px_size, fit, dithering)
+    }
+
+    pub fn from_gif_frame(frame: &GifFrame, fit: Option<Point>, dithering: Dithering) -> Self {
+        let px_size = Point::new(frame.width.try_into().unwrap(), frame.height.try_into().unwrap());
+        Bitmap::from_iter_dithered(frame.pixels.iter().cloned(), PixelType::U8, px_size, fit, dithering)
     }
 
     pub fn from_iter<I: Iterator<Item = u8>>(
@@ -96,13 +122,32 @@ impl Bitmap {
         px_size: Point,
         fit: Option<Point>,
     ) -> Self {
-        let burkes = BURKES.to_vec();
+        Bitmap::from_iter_dithered(bytes, px_type, px_size, fit, Dithering::Burkes)
+    }
+
+    /// Converts pixels of `px_type`, row by row, to a Bitmap: shrunk and rotated to `fit` if given, then
+    /// dithered to black and white.
+    pub fn from_iter_dithered<I: Iterator<Item = u8>>(
+        bytes: I,
+        px_type: PixelType,
+        px_size: Point,
+        fit: Option<Point>,
+        dithering: Dithering,
+    ) -> Self {
+        let diffusion = match dithering {
+            Dithering::FloydSteinberg => FLOYD_STEINBERG.to_vec(),
+            _ => BURKES.to_vec(),
+        };
         let from_width: usize = px_size.x.try_into().unwrap();
         let (rotate, to_width) = match fit {
             Some(fit) => Self::fit(px_size, fit),
             None => (false, from_width),
         };
-        let words = bytes.to_grey(px_type).shrink(from_width, to_width).dither(&burkes, to_width);
+        let grey = bytes.to_grey(px_type).shrink(from_width, to_width);
+        let words: Box<dyn Iterator<Item = Word> + '_> = match dithering {
+            Dithering::Ordered => Box::new(grey.dither_ordered(to_width)),
+            _ => Box::new(grey.dither(&diffusion, to_width)),
+        };
 
         let mut mosaic: Vec<Tile> = Vec::new();
 
@@ -0,0 +1,524 @@
+/*
+ * This GIF decoder reads the frames of an image, animated or not, directly from a Reader.
+ *
+ * Each frame of a gif may cover only part of the image, and may leave pixels transparent, so each is
+ * drawn over a canvas holding the frames before it. DecodeGif keeps that canvas, in greyscale, and
+ * returns the whole of it for each frame, along with how long the frame is to be shown.
+ *
+ * The gif file format is a header, a logical screen descriptor, and a sequence of blocks.
+ * - The header is "GIF87a" or "GIF89a".
+ * - The logical screen descriptor holds the size of the canvas and, optionally, a global color table.
+ * - An image block holds the position and size of a frame, an optional local color table, a flag for
+ *   interlaced rows, and the LZW compressed color indices in sub-blocks of up to 255 bytes.
+ * - A graphic control extension, before an image, holds the delay, the transparent color index, and what
+ *   to do with the frame before drawing the next one (the disposal method).
+ * - The NETSCAPE2.0 application extension holds the number of times to loop the animation.
+ * - The trailer (0x3B) ends the file.
+ *
+ * See https://www.w3.org/Graphics/GIF/spec-gif89a.txt
+ */
+
+use std::io::{Error, ErrorKind::InvalidData, Read, Result};
+
+use super::MAX_PIXELS;
+use super::greyscale::luma;
+
+const EXTENSION: u8 = 0x21;
+const IMAGE: u8 = 0x2C;
+const TRAILER: u8 = 0x3B;
+const GRAPHIC_CONTROL: u8 = 0xF9;
+const APPLICATION: u8 = 0xFF;
+
+/// LZW codes are at most 12 bits.
+const MAX_CODES: usize = 4096;
+
+/// Uncovered and cleared parts of the canvas are left white, like paper.
+const BACKGROUND: u8 = 255;
+
+/// Browsers show frames with a delay of 0 or 10ms for 100ms, and gifs are made to suit.
+const MIN_DELAY_MS: u32 = 20;
+const DEFAULT_DELAY_MS: u32 = 100;
+
+/// A whole canvas of greyscale pixels, row by row, to be shown for `delay_ms`.
+#[derive(Debug, Clone)]
+pub struct GifFrame {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+    pub delay_ms: u32,
+}
+
+/// What to do with a frame before drawing the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Disposal {
+    Keep,
+    Background,
+    Previous,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Area {
+    left: usize,
+    top: usize,
+    width: usize,
+    height: usize,
+}
+
+pub struct DecodeGif<R: Read> {
+    reader: R,
+    width: u32,
+    height: u32,
+    /// the global color table, as the grey of each color
+    palette: Option<Vec<u8>>,
+    loop_count: Option<u16>,
+    canvas: Vec<u8>,
+    /// the disposal of the last frame, the area it covered, and the canvas before it if needed
+    disposal: Option<(Disposal, Area, Option<Vec<u8>>)>,
+    finished: bool,
+}
+
+impl<R: Read> DecodeGif<R> {
+    pub fn new(mut reader: R) -> Result<DecodeGif<R>> {
+        let mut header = [0u8; 13];
+        reader.read_exact(&mut header)?;
+        if &header[..6] != b"GIF87a" && &header[..6] != b"GIF89a" {
+            return Err(Error::new(InvalidData, "invalid gif signature"));
+        }
+        let width = u16::from_le_bytes([header[6], header[7]]) as u32;
+        let height = u16::from_le_bytes([header[8], header[9]]) as u32;
+        if width == 0 || height == 0 {
+            return Err(Error::new(InvalidData, "invalid image dimensions"));
+        }
+        if width as u64 * height as u64 > MAX_PIXELS {
+            return Err(Error::new(InvalidData, "image too large"));
+        }
+        let flags = header[10];
+        let mut gif = Self {
+            reader,
+            width,
+            height,
+            palette: None,
+            loop_count: None,
+            canvas: vec![BACKGROUND; (width * height) as usize],
+            disposal: None,
+            finished: false,
+        };
+        if flags & 0x80 != 0 {
+            gif.palette = Some(gif.read_palette(flags)?);
+        }
+        log::info!("DecodeGif ready: size({},{}) global_palette={}", width, height, gif.palette.is_some());
+        Ok(gif)
+    }
+
+    pub fn width(&self) -> u32 { self.width }
+
+    pub fn height(&self) -> u32 { self.height }
+
+    /// The number of times to play an animation, 0 being forever; known once the first frame is read.
+    pub fn loop_count(&self) -> Option<u16> { self.loop_count }
+
+    /// Reads the next frame and draws it on the canvas, or returns None at the end of the file.
+    pub fn next_frame(&mut self) -> Result<Option<GifFrame>> {
+        if self.finished {
+            return Ok(None);
+        }
+        self.dispose();
+        let mut delay_ms = DEFAULT_DELAY_MS;
+        let mut transparent: Option<u8> = None;
+        let mut disposal = Disposal::Keep;
+        loop {
+            match self.u8()? {
+                EXTENSION => match self.u8()? {
+                    GRAPHIC_CONTROL => {
+                        let block = self.sub_blocks()?;
+                        if block.len() >= 4 {
+                            disposal = match (block[0] >> 2) & 0x7 {
+                                2 => Disposal::Background,
+                                3 => Disposal::Previous,
+                                _ => Disposal::Keep,
+                            };
+                            let delay = u16::from_le_bytes([block[1], block[2]]) as u32 * 10;
+                            delay_ms = if delay < MIN_DELAY_MS { DEFAULT_DELAY_MS } else { delay };
+                            transparent = if block[0] & 0x1 != 0 { Some(block[3]) } else { None };
+                        }
+                    }
+                    APPLICATION => {
+                        let block = self.sub_blocks()?;
+                        // the identifier, then a sub-block of 1 and the loop count
+                        if block.starts_with(b"NETSCAPE2.0") && block.len() >= 14 && block[11] == 1 {
+                            self.loop_count = Some(u16::from_le_bytes([block[12], block[13]]));
+                        }
+                    }
+                    _ => {
+                        self.sub_blocks()?;
+                    }
+                },
+                IMAGE => {
+                    self.read_image(disposal, transparent)?;
+                    return Ok(Some(GifFrame {
+                        width: self.width,
+                        height: self.height,
+                        pixels: self.canvas.clone(),
+                        delay_ms,
+                    }));
+                }
+                TRAILER => {
+                    self.finished = true;
+                    return Ok(None);
+                }
+                block => {
+                    log::warn!("unknown gif block {:02x}", block);
+                    self.finished = true;
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    /// Clears the last frame from the canvas as it asked.
+    fn dispose(&mut self) {
+        match self.disposal.take() {
+            Some((Disposal::Background, area, _)) => {
+                for y in area.top..area.top + area.height {
+                    let row = y * self.width as usize;
+                    self.canvas[row + area.left..row + area.left + area.width].fill(BACKGROUND);
+                }
+            }
+            Some((Disposal::Previous, _, Some(previous))) => self.canvas = previous,
+            _ => (),
+        }
+    }
+
+    fn read_image(&mut self, disposal: Disposal, transparent: Option<u8>) -> Result<()> {
+        let mut descriptor = [0u8; 9];
+        self.reader.read_exact(&mut descriptor)?;
+        let field = |i: usize| u16::from_le_bytes([descriptor[i], descriptor[i + 1]]) as usize;
+        let (left, top, width, height) = (field(0), field(2), field(4), field(6));
+        if width as u64 * height as u64 > MAX_PIXELS {
+            return Err(Error::new(InvalidData, "frame too large"));
+        }
+        let flags = descriptor[8];
+        let local_palette = if flags & 0x80 != 0 { Some(self.read_palette(flags)?) } else { None };
+        let interlaced = flags & 0x40 != 0;
+        let min_code_size = self.u8()?;
+        let data = self.sub_blocks()?;
+        let indices = lzw_decode(&data, min_code_size, width * height)?;
+
+        // the part of the frame on the canvas
+        let canvas_width = self.width as usize;
+        let left = left.min(canvas_width);
+        let top = top.min(self.height as usize);
+        let area = Area {
+            left,
+            top,
+            width: width.min(canvas_width - left),
+            height: height.min(self.height as usize - top),
+        };
+        let previous = if disposal == Disposal::Previous { Some(self.canvas.clone()) } else { None };
+        self.disposal = Some((disposal, area, previous));
+
+        let palette = match (&local_palette, &self.palette) {
+            (Some(palette), _) | (None, Some(palette)) => palette,
+            (None, None) => return Err(Error::new(InvalidData, "gif without a color table")),
+        };
+        for (row, y) in rows(height, interlaced).enumerate() {
+            if y >= area.height {
+                continue;
+            }
+            let start = row * width;
+            let line = indices.get(start..(start + area.width).min(indices.len())).unwrap_or(&[]);
+            let offset = (area.top + y) * canvas_width + area.left;
+            for (x, &index) in line.iter().enumerate() {
+                if Some(index) != transparent {
+                    self.canvas[offset + x] = palette.get(index as usize).copied().unwrap_or(BACKGROUND);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the color table with the size given in `flags`, as the grey of each color.
+    fn read_palette(&mut self, flags: u8) -> Result<Vec<u8>> {
+        let mut rgb = vec![0u8; 3 << ((flags & 0x7) + 1)];
+        self.reader.read_exact(&mut rgb)?;
+        Ok(rgb.chunks(3).map(|c| luma(c[0], c[1], c[2])).collect())
+    }
+
+    /// Reads a sequence of sub-blocks, to the empty one that ends it.
+    fn sub_blocks(&mut self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        loop {
+            let size = self.u8()? as usize;
+            if size == 0 {
+                return Ok(data);
+            }
+            let start = data.len();
+            data.resize(start + size, 0);
+            self.reader.read_exact(&mut data[start..])?;
+        }
+    }
+
+    /// Get a u8 out of the reader.
+    fn u8(&mut self) -> Result<u8> {
+        let mut byte = [0; 1];
+        self.reader.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+}
+
+/// The row of the frame each row of data belongs to: interlaced gifs send every 8th row from 0, then
+/// every 8th from 4, every 4th from 2, and every 2nd from 1.
+fn rows(height: usize, interlaced: bool) -> Box<dyn Iterator<Item = usize>> {
+    if interlaced {
+        Box::new(
+            (0..height)
+                .step_by(8)
+                .chain((4..height).step_by(8))
+                .chain((2..height).step_by(4))
+                .chain((1..height).step_by(2)),
+        )
+    } else {
+        Box::new(0..height)
+    }
+}
+
+/// Decompresses the LZW `data` of a frame to (at most `count`) color indices.
+///
+/// Codes are packed least significant bit first, and start one bit wider than `min_code_size`. Each
+/// code after the first adds a string to the table: the previous string and the first index of this
+/// one. The code width grows as the table fills, up to 12 bits.
+fn lzw_decode(data: &[u8], min_code_size: u8, count: usize) -> Result<Vec<u8>> {
+    if !(1..=11).contains(&min_code_size) {
+        return Err(Error::new(InvalidData, "invalid lzw code size"));
+    }
+    let clear = 1usize << min_code_size;
+    let end = clear + 1;
+    // each string is the string of its prefix, then its suffix
+    let mut prefix = [0u16; MAX_CODES];
+    let mut suffix = [0u8; MAX_CODES];
+    let mut first = [0u8; MAX_CODES];
+    for code in 0..clear {
+        suffix[code] = code as u8;
+        first[code] = code as u8;
+    }
+    let mut next_code = end + 1;
+    let mut code_size = min_code_size as u32 + 1;
+    let mut previous: Option<usize> = None;
+
+    let mut indices = Vec::with_capacity(count);
+    let mut string = Vec::with_capacity(MAX_CODES);
+    let (mut bits, mut bit_count) = (0u32, 0u32);
+    let mut bytes = data.iter();
+    while indices.len() < count {
+        while bit_count < code_size {
+            match bytes.next() {
+                Some(&byte) => {
+                    bits |= (byte as u32) << bit_count;
+                    bit_count += 8;
+                }
+                None => return Ok(indices),
+            }
+        }
+        let code = (bits & ((1 << code_size) - 1)) as usize;
+        bits >>= code_size;
+        bit_count -= code_size;
+
+        if code == clear {
+            next_code = end + 1;
+            code_size = min_code_size as u32 + 1;
+            previous = None;
+            continue;
+        } else if code == end {
+            break;
+        }
+        let previous_code = match previous {
+            Some(previous_code) => previous_code,
+            None if code < clear => {
+                indices.push(code as u8);
+                previous = Some(code);
+                continue;
+            }
+            None => return Err(Error::new(InvalidData, "invalid lzw code")),
+        };
+        // a code not yet in the table is the previous string and its own first index
+        let new_first = if code < next_code {
+            first[code]
+        } else if code == next_code {
+            first[previous_code]
+        } else {
+            return Err(Error::new(InvalidData, "invalid lzw code"));
+        };
+        if next_code < MAX_CODES {
+            prefix[next_code] = previous_code as u16;
+            suffix[next_code] = new_first;
+            first[next_code] = first[previous_code];
+            next_code += 1;
+            if next_code == 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+        }
+        string.clear();
+        let mut link = code;
+        while link >= clear {
+            string.push(suffix[link]);
+            link = prefix[link] as usize;
+        }
+        string.push(link as u8);
+        indices.extend(string.iter().rev());
+        previous = Some(code);
+    }
+    indices.truncate(count);
+    Ok(indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An LZW encoder, without the table ever filling, to make test frames.
+    fn lzw_encode(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1u32 << min_code_size;
+        let mut table: std::collections::HashMap<Vec<u8>, u32> = std::collections::HashMap::new();
+        let mut next_code = clear + 2;
+        let mut code_size = min_code_size as u32 + 1;
+        let (mut out, mut bits, mut bit_count) = (Vec::new(), 0u32, 0u32);
+        let mut emit = |code: u32, size: u32, out: &mut Vec<u8>| {
+            bits |= code << bit_count;
+            bit_count += size;
+            while bit_count >= 8 {
+                out.push(bits as u8);
+                bits >>= 8;
+                bit_count -= 8;
+            }
+        };
+        emit(clear, code_size, &mut out);
+        let mut string: Vec<u8> = Vec::new();
+        for &index in indices {
+            let mut longer = string.clone();
+            longer.push(index);
+            if longer.len() == 1 || table.contains_key(&longer) {
+                string = longer;
+                continue;
+            }
+            let code = if string.len() == 1 { string[0] as u32 } else { table[&string] };
+            emit(code, code_size, &mut out);
+            table.insert(longer, next_code);
+            next_code += 1;
+            if next_code > 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+            string = vec![index];
+        }
+        let code = if string.len() == 1 { string[0] as u32 } else { table[&string] };
+        emit(code, code_size, &mut out);
+        if next_code == 1 << code_size && code_size < 12 {
+            code_size += 1;
+        }
+        emit(clear + 1, code_size, &mut out);
+        emit(0, 7, &mut out);
+        out
+    }
+
+    fn sub_blocks(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for chunk in data.chunks(255) {
+            out.push(chunk.len() as u8);
+            out.extend_from_slice(chunk);
+        }
+        out.push(0);
+        out
+    }
+
+    #[test]
+    fn test_lzw() {
+        let indices: Vec<u8> = (0..1000).map(|i| ((i * i / 7) % 5) as u8).collect();
+        let data = lzw_encode(&indices, 3);
+        assert_eq!(lzw_decode(&data, 3, indices.len()).unwrap(), indices);
+        // the code that isn't in the table yet: a run of one index
+        let run = vec![1u8; 300];
+        assert_eq!(lzw_decode(&lzw_encode(&run, 2), 2, run.len()).unwrap(), run);
+    }
+
+    #[test]
+    fn test_interlace() {
+        let order: Vec<usize> = rows(10, true).collect();
+        assert_eq!(order, vec![0, 8, 4, 2, 6, 1, 3, 5, 7, 9]);
+    }
+
+    /// A 4x4 animation over a black, grey, white and red palette: a grey frame, then a white 2x2
+    /// square with a transparent corner, cleared to the background afterwards, then a black pixel.
+    fn animation() -> Vec<u8> {
+        let mut gif = b"GIF89a\x04\x00\x04\x00\x81\x00\x00".to_vec();
+        gif.extend_from_slice(&[0, 0, 0, 128, 128, 128, 255, 255, 255, 255, 0, 0]);
+        gif.extend_from_slice(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00");
+        let mut frame = |gif: &mut Vec<u8>, control: &[u8], area: [u8; 4], indices: &[u8]| {
+            gif.extend_from_slice(&[0x21, 0xF9, 4]);
+            gif.extend_from_slice(control);
+            gif.push(0);
+            gif.extend_from_slice(&[0x2C, area[0], 0, area[1], 0, area[2], 0, area[3], 0, 0, 2]);
+            gif.extend(sub_blocks(&lzw_encode(indices, 2)));
+        };
+        frame(&mut gif, &[0, 5, 0, 0], [0, 0, 4, 4], &[1; 16]);
+        frame(&mut gif, &[2 << 2 | 1, 0, 0, 3], [1, 1, 2, 2], &[2, 2, 2, 3]);
+        frame(&mut gif, &[0, 10, 0, 0], [3, 0, 1, 1], &[0]);
+        gif.push(0x3B);
+        gif
+    }
+
+    #[test]
+    fn test_animation() {
+        let data = animation();
+        let mut gif = DecodeGif::new(&data[..]).unwrap();
+        assert_eq!((gif.width(), gif.height()), (4, 4));
+
+        let frame = gif.next_frame().unwrap().unwrap();
+        assert_eq!(gif.loop_count(), Some(0));
+        assert_eq!(frame.pixels, vec![128; 16]);
+        assert_eq!(frame.delay_ms, 50);
+
+        let frame = gif.next_frame().unwrap().unwrap();
+        #[rustfmt::skip]
+        let expected = vec![
+            128, 128, 128, 128,
+            128, 255, 255, 128,
+            128, 255, 128, 128,
+            128, 128, 128, 128,
+        ];
+        assert_eq!(frame.pixels, expected);
+        assert_eq!(frame.delay_ms, DEFAULT_DELAY_MS);
+
+        let frame = gif.next_frame().unwrap().unwrap();
+        #[rustfmt::skip]
+        let expected = vec![
+            128, 128, 128, 0,
+            128, 255, 255, 128,
+            128, 255, 255, 128,
+            128, 128, 128, 128,
+        ];
+        assert_eq!(frame.pixels, expected);
+        assert_eq!(frame.delay_ms, 100);
+
+        assert!(gif.next_frame().unwrap().is_none());
+        assert!(gif.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(DecodeGif::new(&b"GIF89b\x04\x00\x04\x00\x00\x00\x00"[..]).is_err());
+        assert!(DecodeGif::new(&b"GIF89a\x00\x00\x04\x00\x00\x00\x00"[..]).is_err());
+        // a truncated frame is an error, not a panic
+        let data = animation();
+        let mut gif = DecodeGif::new(&data[..data.len() - 20]).unwrap();
+        assert!(gif.next_frame().unwrap().is_some());
+        assert!(gif.next_frame().unwrap().is_some());
+        assert!(gif.next_frame().is_err());
+        // too many pixels for the canvas, or for a frame
+        assert!(DecodeGif::new(&b"GIF89a\xFF\xFF\xFF\xFF\x00\x00\x00"[..]).is_err());
+        let mut data = b"GIF89a\x04\x00\x04\x00\x00\x00\x00".to_vec();
+        data.extend_from_slice(&[0x2C, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0x80, 0, 0, 0, 255, 255, 255, 2]);
+        data.extend(sub_blocks(&lzw_encode(&[0], 2)));
+        data.push(0x3B);
+        let error = DecodeGif::new(&data[..]).unwrap().next_frame().err().unwrap();
+        assert!(error.to_string().contains("too large"));
+    }
+}
@@ -0,0 +1,663 @@
+/*
+ * This baseline JPEG decoder presents as an Iterator of greyscale u8 pixels read directly from a Reader.
+ *
+ * Like DecodePng, it holds only a strip of the image at a time: one row of MCUs (minimum coded units),
+ * 8 or 16 lines of pixels. Only the luminance (Y) component is kept, as that is all a black and white
+ * display can show. The colour components are entropy decoded to stay in step with the data, and dropped.
+ *
+ * The jpeg file format is a sequence of segments, each starting with a marker (0xFF and a code).
+ * - SOI starts the file and EOI ends it.
+ * - DQT segments hold quantization tables, and DHT segments Huffman tables.
+ * - The SOF (start of frame) segment holds the image size, and the components with their sampling.
+ * - DRI sets the restart interval: the number of MCUs between RST markers.
+ * - The SOS (start of scan) segment selects the tables for each component, and is followed by the entropy
+ *   coded data. A 0xFF byte in the data is followed by 0x00, to tell it from a marker.
+ * - Each MCU holds 8x8 blocks of each component: more of the luminance when the colour is subsampled.
+ * - Each block holds a DC coefficient, coded as the difference from the previous block of the component,
+ *   and 63 AC coefficients in zigzag order, coded as runs of zeros and values.
+ * - The coefficients are dequantized, and an inverse DCT turns them back into pixels.
+ *
+ * Supported: baseline and extended sequential Huffman coding (SOF0 and SOF1) of 8 bit samples, with one
+ * (greyscale) or three (YCbCr) components, any sampling factors, and restart intervals.
+ * Not supported: progressive and arithmetic coding, 12 bit samples, and CMYK.
+ *
+ * See https://www.w3.org/Graphics/JPEG/itu-t81.pdf
+ */
+
+use std::convert::TryInto;
+use std::io::{Error, ErrorKind::InvalidData, Read, Result};
+
+use super::MAX_PIXELS;
+
+// Marker codes
+const START_OF_IMAGE: u8 = 0xD8;
+const END_OF_IMAGE: u8 = 0xD9;
+const BASELINE: u8 = 0xC0;
+const EXTENDED: u8 = 0xC1;
+const HUFFMAN_TABLE: u8 = 0xC4;
+const QUANTIZATION_TABLE: u8 = 0xDB;
+const RESTART_INTERVAL: u8 = 0xDD;
+const START_OF_SCAN: u8 = 0xDA;
+const RESTART_0: u8 = 0xD0;
+const RESTART_7: u8 = 0xD7;
+
+/// The position in an 8x8 block, row by row, of each coefficient in the order they are coded.
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20, 13, 6, 7,
+    14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59, 52, 45, 38, 31, 39,
+    46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/// The inverse DCT basis: IDCT[x][u] = C(u) / 2 * cos((2x + 1) * u * pi / 16), in 4096ths, where
+/// C(0) = 1 / sqrt(2) and C(u) = 1 otherwise. Integers, as the Precursor has no FPU.
+const IDCT: [[i32; 8]; 8] = [
+    [1448, 2009, 1892, 1703, 1448, 1138, 784, 400],
+    [1448, 1703, 784, -400, -1448, -2009, -1892, -1138],
+    [1448, 1138, -784, -2009, -1448, 400, 1892, 1703],
+    [1448, 400, -1892, -1138, 1448, 1703, -784, -2009],
+    [1448, -400, -1892, 1138, 1448, -1703, -784, 2009],
+    [1448, -1138, -784, 2009, -1448, -400, 1892, -1703],
+    [1448, -1703, 784, 400, -1448, 2009, -1892, 1138],
+    [1448, -2009, 1892, -1703, 1448, -1138, 784, -400],
+];
+
+/// Coefficients are clamped to this, well outside the range of any real image, so that corrupt data
+/// can't overflow the inverse DCT.
+const COEFFICIENT_MAX: i32 = 1 << 14;
+
+/// The largest magnitude size of a DC difference and of an AC coefficient, for 8 bit samples (see
+/// Tables F.1 and F.2 of the standard).
+const DC_SIZE_MAX: u8 = 11;
+const AC_SIZE_MAX: u32 = 10;
+
+/// A Huffman table, as the canonical codes of each length (see Annex F.2.2.3 of the standard).
+#[derive(Debug, Clone, Default)]
+struct Huffman {
+    /// the largest code of each length, or -1 if there are none
+    max_code: [i32; 17],
+    /// for each length, the index in `values` of a code less the code
+    offset: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl Huffman {
+    fn new(counts: &[u8; 16], values: Vec<u8>) -> Self {
+        let mut table = Huffman { max_code: [-1; 17], offset: [0; 17], values };
+        let (mut code, mut index) = (0i32, 0i32);
+        for length in 1..=16 {
+            let count = counts[length - 1] as i32;
+            if count > 0 {
+                table.offset[length] = index - code;
+                code += count;
+                index += count;
+                table.max_code[length] = code - 1;
+            }
+            code <<= 1;
+        }
+        table
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Component {
+    id: u8,
+    /// horizontal and vertical sampling factors
+    h: usize,
+    v: usize,
+    /// quantization table
+    tq: usize,
+    /// DC and AC Huffman tables
+    td: usize,
+    ta: usize,
+}
+
+pub struct DecodeJpeg<R: Read> {
+    reader: R,
+    width: u32,
+    height: u32,
+    components: Vec<Component>,
+    /// the components in the order the scan codes them, by index in `components`
+    scan: Vec<usize>,
+    quantization: [[i32; 64]; 4],
+    dc_tables: [Huffman; 4],
+    ac_tables: [Huffman; 4],
+    restart_interval: u32,
+    // fields to read the entropy coded data
+    bits: u32,
+    bit_count: u32,
+    marker: Option<u8>,
+    predictions: Vec<i32>,
+    mcus_decoded: u32,
+    // the geometry of an MCU
+    h_max: usize,
+    v_max: usize,
+    mcus_across: usize,
+    // the luminance of the current row of MCUs, and the next pixel to return from it
+    luma: Vec<u8>,
+    luma_width: usize,
+    x: u32,
+    y: u32,
+}
+
+impl<R: Read> Iterator for DecodeJpeg<R> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.y >= self.height {
+            return None;
+        }
+        let mcu_height = 8 * self.v_max as u32;
+        let row_y = self.y % mcu_height;
+        if self.x == 0 && row_y == 0 {
+            if let Err(e) = self.decode_mcu_row() {
+                log::warn!("jpeg decode failed at line {}: {:?}", self.y, e);
+                self.y = self.height;
+                return None;
+            }
+        }
+        // the luminance may be subsampled too, in which case its pixels are stretched
+        let y_comp = &self.components[0];
+        let luma_x = self.x as usize * y_comp.h / self.h_max;
+        let luma_y = row_y as usize * y_comp.v / self.v_max;
+        let pixel = self.luma[luma_y * self.luma_width + luma_x];
+        self.x += 1;
+        if self.x >= self.width {
+            self.x = 0;
+            self.y += 1;
+        }
+        Some(pixel)
+    }
+}
+
+impl<R: Read> DecodeJpeg<R> {
+    pub fn new(reader: R) -> Result<DecodeJpeg<R>> {
+        let mut jpeg = Self {
+            reader,
+            width: 0,
+            height: 0,
+            components: Vec::new(),
+            scan: Vec::new(),
+            quantization: [[1; 64]; 4],
+            dc_tables: Default::default(),
+            ac_tables: Default::default(),
+            restart_interval: 0,
+            bits: 0,
+            bit_count: 0,
+            marker: None,
+            predictions: Vec::new(),
+            mcus_decoded: 0,
+            h_max: 1,
+            v_max: 1,
+            mcus_across: 0,
+            luma: Vec::new(),
+            luma_width: 0,
+            x: 0,
+            y: 0,
+        };
+        if jpeg.u8()? != 0xFF || jpeg.u8()? != START_OF_IMAGE {
+            return Err(Error::new(InvalidData, "invalid jpeg signature"));
+        }
+        jpeg.parse_segments()?;
+        log::info!(
+            "DecodeJpeg ready: size({},{}) components={} mcu={}x{} restart_interval={}",
+            jpeg.width,
+            jpeg.height,
+            jpeg.components.len(),
+            8 * jpeg.h_max,
+            8 * jpeg.v_max,
+            jpeg.restart_interval,
+        );
+        Ok(jpeg)
+    }
+
+    pub fn width(&self) -> u32 { self.width }
+
+    pub fn height(&self) -> u32 { self.height }
+
+    /// Reads the segments up to the start of the scan.
+    fn parse_segments(&mut self) -> Result<()> {
+        loop {
+            let marker = self.next_marker()?;
+            let length = self.u16()? as usize;
+            if length < 2 {
+                return Err(Error::new(InvalidData, "invalid segment length"));
+            }
+            let mut segment = vec![0u8; length - 2];
+            self.reader.read_exact(&mut segment)?;
+            match marker {
+                QUANTIZATION_TABLE => self.parse_quantization(&segment)?,
+                HUFFMAN_TABLE => self.parse_huffman(&segment)?,
+                RESTART_INTERVAL => {
+                    let interval = segment.get(..2).ok_or(Error::new(InvalidData, "short DRI"))?;
+                    self.restart_interval = u16::from_be_bytes([interval[0], interval[1]]) as u32;
+                }
+                BASELINE | EXTENDED => self.parse_frame(&segment)?,
+                0xC2 | 0xC6 | 0xCA | 0xCE => {
+                    return Err(Error::new(InvalidData, "progressive jpeg is not supported"));
+                }
+                0xC3 | 0xC5 | 0xC7 | 0xC9 | 0xCB | 0xCD | 0xCF => {
+                    return Err(Error::new(InvalidData, "lossless and arithmetic jpeg are not supported"));
+                }
+                START_OF_SCAN => return self.parse_scan(&segment),
+                END_OF_IMAGE => return Err(Error::new(InvalidData, "no image data")),
+                // APPn, COM and the like
+                _ => log::trace!("skipping jpeg segment {:02x} of {} bytes", marker, length),
+            }
+        }
+    }
+
+    /// Finds the next marker, skipping any fill bytes.
+    fn next_marker(&mut self) -> Result<u8> {
+        if self.u8()? != 0xFF {
+            return Err(Error::new(InvalidData, "expected a jpeg marker"));
+        }
+        loop {
+            match self.u8()? {
+                0xFF => continue,
+                marker => return Ok(marker),
+            }
+        }
+    }
+
+    fn parse_quantization(&mut self, mut segment: &[u8]) -> Result<()> {
+        while let Some((&pq_tq, rest)) = segment.split_first() {
+            let (precision, id) = (pq_tq >> 4, (pq_tq & 0x3) as usize);
+            let size = if precision == 0 { 64 } else { 128 };
+            let values = rest.get(..size).ok_or(Error::new(InvalidData, "short DQT"))?;
+            for k in 0..64 {
+                self.quantization[id][k] = if precision == 0 {
+                    values[k] as i32
+                } else {
+                    u16::from_be_bytes([values[2 * k], values[2 * k + 1]]) as i32
+                };
+            }
+            segment = &rest[size..];
+        }
+        Ok(())
+    }
+
+    fn parse_huffman(&mut self, mut segment: &[u8]) -> Result<()> {
+        while let Some((&tc_th, rest)) = segment.split_first() {
+            let (class, id) = (tc_th >> 4, (tc_th & 0x3) as usize);
+            let counts: [u8; 16] =
+                rest.get(..16).ok_or(Error::new(InvalidData, "short DHT"))?.try_into().unwrap();
+            let total: usize = counts.iter().map(|&count| count as usize).sum();
+            let values = rest.get(16..16 + total).ok_or(Error::new(InvalidData, "short DHT"))?.to_vec();
+            let table = Huffman::new(&counts, values);
+            match class {
+                0 => self.dc_tables[id] = table,
+                _ => self.ac_tables[id] = table,
+            }
+            segment = &rest[16 + total..];
+        }
+        Ok(())
+    }
+
+    fn parse_frame(&mut self, segment: &[u8]) -> Result<()> {
+        if segment.len() < 6 {
+            return Err(Error::new(InvalidData, "short SOF"));
+        }
+        if segment[0] != 8 {
+            return Err(Error::new(InvalidData, "only 8 bit jpeg is supported"));
+        }
+        self.height = u16::from_be_bytes([segment[1], segment[2]]) as u32;
+        self.width = u16::from_be_bytes([segment[3], segment[4]]) as u32;
+        let count = segment[5] as usize;
+        if self.width == 0 || self.height == 0 {
+            return Err(Error::new(InvalidData, "invalid image dimensions"));
+        }
+        if self.width as u64 * self.height as u64 > MAX_PIXELS {
+            return Err(Error::new(InvalidData, "image too large"));
+        }
+        if count != 1 && count != 3 {
+            return Err(Error::new(InvalidData, "only greyscale and YCbCr jpeg are supported"));
+        }
+        let specs = segment.get(6..6 + 3 * count).ok_or(Error::new(InvalidData, "short SOF"))?;
+        self.components = specs
+            .chunks(3)
+            .map(|spec| Component {
+                id: spec[0],
+                h: (spec[1] >> 4).clamp(1, 4) as usize,
+                v: (spec[1] & 0xF).clamp(1, 4) as usize,
+                tq: (spec[2] & 0x3) as usize,
+                td: 0,
+                ta: 0,
+            })
+            .collect();
+        if count == 1 {
+            // a lone component is coded a block at a time, whatever its sampling factors say
+            self.components[0].h = 1;
+            self.components[0].v = 1;
+        }
+        self.h_max = self.components.iter().map(|c| c.h).max().unwrap();
+        self.v_max = self.components.iter().map(|c| c.v).max().unwrap();
+        Ok(())
+    }
+
+    fn parse_scan(&mut self, segment: &[u8]) -> Result<()> {
+        if self.components.is_empty() {
+            return Err(Error::new(InvalidData, "scan before frame"));
+        }
+        let count = *segment.first().ok_or(Error::new(InvalidData, "short SOS"))? as usize;
+        let specs = segment.get(1..1 + 2 * count).ok_or(Error::new(InvalidData, "short SOS"))?;
+        if count != self.components.len() {
+            return Err(Error::new(InvalidData, "jpeg with more than one scan is not supported"));
+        }
+        for spec in specs.chunks(2) {
+            let index = self
+                .components
+                .iter()
+                .position(|c| c.id == spec[0])
+                .ok_or(Error::new(InvalidData, "scan of an unknown component"))?;
+            self.components[index].td = (spec[1] >> 4 & 0x3) as usize;
+            self.components[index].ta = (spec[1] & 0x3) as usize;
+            self.scan.push(index);
+        }
+        self.predictions = vec![0; self.components.len()];
+        let mcu_width = 8 * self.h_max;
+        self.mcus_across = (self.width as usize + mcu_width - 1) / mcu_width;
+        let y_comp = &self.components[0];
+        self.luma_width = self.mcus_across * y_comp.h * 8;
+        self.luma = vec![0u8; self.luma_width * y_comp.v * 8];
+        Ok(())
+    }
+
+    /// Decodes the next row of MCUs, keeping the luminance.
+    fn decode_mcu_row(&mut self) -> Result<()> {
+        let mut coefficients = [0i32; 64];
+        for mcu_x in 0..self.mcus_across {
+            if self.restart_interval > 0
+                && self.mcus_decoded > 0
+                && self.mcus_decoded % self.restart_interval == 0
+            {
+                self.restart()?;
+            }
+            for s in 0..self.scan.len() {
+                let index = self.scan[s];
+                let component = self.components[index];
+                for block_y in 0..component.v {
+                    for block_x in 0..component.h {
+                        let keep = index == 0;
+                        self.decode_block(index, &mut coefficients, keep)?;
+                        if keep {
+                            let x = (mcu_x * component.h + block_x) * 8;
+                            let offset = block_y * 8 * self.luma_width + x;
+                            idct(&coefficients, &mut self.luma[offset..], self.luma_width);
+                        }
+                    }
+                }
+            }
+            self.mcus_decoded += 1;
+        }
+        Ok(())
+    }
+
+    /// Decodes a block of coefficients, dequantized in natural order if they are to be kept.
+    fn decode_block(&mut self, index: usize, coefficients: &mut [i32; 64], keep: bool) -> Result<()> {
+        let component = self.components[index];
+        let size = self.decode_huffman(component.td, true)?;
+        if size > DC_SIZE_MAX {
+            return Err(Error::new(InvalidData, "jpeg dc difference out of range"));
+        }
+        let diff = if size == 0 { 0 } else { extend(self.bits(size as u32)?, size as u32) };
+        self.predictions[index] = self.predictions[index].saturating_add(diff);
+        let quantization = &self.quantization[component.tq];
+        if keep {
+            *coefficients = [0; 64];
+            coefficients[0] = dequantize(self.predictions[index], quantization[0]);
+        }
+        let mut k = 1;
+        while k < 64 {
+            let run_size = self.decode_huffman(component.ta, false)?;
+            let (run, size) = ((run_size >> 4) as usize, (run_size & 0xF) as u32);
+            if size == 0 {
+                if run == 15 {
+                    // sixteen zeros
+                    k += 16;
+                    continue;
+                }
+                // end of block: the rest are zero
+                break;
+            }
+            k += run;
+            if k > 63 || size > AC_SIZE_MAX {
+                return Err(Error::new(InvalidData, "jpeg coefficient out of range"));
+            }
+            let value = extend(self.bits(size)?, size);
+            if keep {
+                coefficients[ZIGZAG[k]] = dequantize(value, self.quantization[component.tq][k]);
+            }
+            k += 1;
+        }
+        Ok(())
+    }
+
+    fn decode_huffman(&mut self, id: usize, dc: bool) -> Result<u8> {
+        let mut code = 0i32;
+        for length in 1..=16 {
+            code = (code << 1) | self.bits(1)? as i32;
+            let table = if dc { &self.dc_tables[id] } else { &self.ac_tables[id] };
+            if code <= table.max_code[length] {
+                let index = (code + table.offset[length]) as usize;
+                return table.values.get(index).copied().ok_or(Error::new(InvalidData, "bad huffman table"));
+            }
+        }
+        Err(Error::new(InvalidData, "bad huffman code"))
+    }
+
+    /// Reads `count` bits of the entropy coded data, most significant first.
+    fn bits(&mut self, count: u32) -> Result<i32> {
+        while self.bit_count < count {
+            // once a marker is reached, the data is padded with zeros
+            let byte = match self.marker {
+                Some(_) => 0,
+                None => match self.u8()? {
+                    0xFF => match self.next_marker_code()? {
+                        0 => 0xFF,
+                        marker => {
+                            self.marker = Some(marker);
+                            0
+                        }
+                    },
+                    byte => byte,
+                },
+            };
+            self.bits = (self.bits << 8) | byte as u32;
+            self.bit_count += 8;
+        }
+        self.bit_count -= count;
+        Ok(((self.bits >> self.bit_count) & ((1 << count) - 1)) as i32)
+    }
+
+    /// The code after a 0xFF in the entropy coded data: 0 for a stuffed 0xFF byte, or a marker.
+    fn next_marker_code(&mut self) -> Result<u8> {
+        loop {
+            match self.u8()? {
+                0xFF => continue,
+                code => return Ok(code),
+            }
+        }
+    }
+
+    /// Skips to the RST marker that ends a restart interval, and resets the decoder.
+    fn restart(&mut self) -> Result<()> {
+        self.bit_count = 0;
+        loop {
+            match self.marker.take() {
+                Some(RESTART_0..=RESTART_7) => break,
+                Some(marker) => {
+                    return Err(Error::new(InvalidData, format!("unexpected marker {:02x}", marker)));
+                }
+                None => {
+                    if self.u8()? == 0xFF {
+                        match self.next_marker_code()? {
+                            0 => (),
+                            marker => self.marker = Some(marker),
+                        }
+                    }
+                }
+            }
+        }
+        self.predictions.iter_mut().for_each(|prediction| *prediction = 0);
+        Ok(())
+    }
+
+    /// Get a u8 out of the reader.
+    fn u8(&mut self) -> Result<u8> {
+        let mut byte = [0; 1];
+        self.reader.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    /// Get a big-endian u16 out of the reader.
+    fn u16(&mut self) -> Result<u16> { Ok(u16::from_be_bytes([self.u8()?, self.u8()?])) }
+}
+
+/// Turns the `size` bits of a coded value into the value: the ones starting with 0 are negative.
+fn extend(bits: i32, size: u32) -> i32 { if bits < 1 << (size - 1) { bits - (1 << size) + 1 } else { bits } }
+
+fn dequantize(value: i32, quantization: i32) -> i32 {
+    value.saturating_mul(quantization).clamp(-COEFFICIENT_MAX, COEFFICIENT_MAX)
+}
+
+/// The inverse DCT of a block of coefficients, written as 8 lines of 8 pixels `stride` apart, level
+/// shifted back to 0-255. Rows then columns, each rounded back to whole numbers.
+fn idct(coefficients: &[i32; 64], out: &mut [u8], stride: usize) {
+    let mut rows = [0i32; 64];
+    for v in 0..8 {
+        let row = &coefficients[v * 8..v * 8 + 8];
+        for x in 0..8 {
+            let sum: i32 = (0..8).map(|u| IDCT[x][u] * row[u]).sum();
+            rows[v * 8 + x] = (sum + 2048) >> 12;
+        }
+    }
+    for x in 0..8 {
+        for y in 0..8 {
+            let sum: i32 = (0..8).map(|v| IDCT[y][v] * rows[v * 8 + x]).sum();
+            out[y * stride + x] = (((sum + 2048) >> 12) + 128).clamp(0, 255) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idct_table() {
+        for x in 0..8 {
+            for u in 0..8 {
+                let c = if u == 0 { std::f64::consts::FRAC_1_SQRT_2 } else { 1.0 };
+                let expected =
+                    c / 2.0 * ((2 * x + 1) as f64 * u as f64 * std::f64::consts::PI / 16.0).cos() * 4096.0;
+                assert_eq!(IDCT[x][u], expected.round() as i32);
+            }
+        }
+    }
+
+    #[test]
+    fn test_idct() {
+        // a DC coefficient alone is a flat block: 8 times the level
+        let mut coefficients = [0i32; 64];
+        coefficients[0] = 8 * 50;
+        let mut out = [0u8; 64];
+        idct(&coefficients, &mut out, 8);
+        assert!(out.iter().all(|&p| p == 178));
+        // the first horizontal frequency darkens to the right
+        coefficients[1] = 200;
+        idct(&coefficients, &mut out, 8);
+        assert!(out[0] > out[3] && out[3] > out[7]);
+        assert_eq!(out[0], out[56]);
+    }
+
+    #[test]
+    fn test_huffman() {
+        // the standard luminance DC table (Annex K.3)
+        let counts = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+        let table = Huffman::new(&counts, (0..12).collect());
+        // 00 is 0, 010 is 1, 110 is 5, 1110 is 6, 111111110 is 11
+        assert_eq!(table.max_code[2], 0);
+        assert_eq!(table.max_code[3], 6);
+        assert_eq!((2 + table.offset[3]) as u8, 1);
+        assert_eq!((6 + table.offset[3]) as u8, 5);
+        assert_eq!((14 + table.offset[4]) as u8, 6);
+        assert_eq!((510 + table.offset[9]) as u8, 11);
+        assert_eq!(extend(0b0, 1), -1);
+        assert_eq!(extend(0b1, 1), 1);
+        assert_eq!(extend(0b010, 3), -5);
+        assert_eq!(extend(0b110, 3), 6);
+    }
+
+    /// Checks a decoded image against the pattern it was made from: a horizontal ramp from black to
+    /// white, with a dark square in the middle third.
+    fn check_pattern(jpeg: DecodeJpeg<&[u8]>, (width, height): (usize, usize)) {
+        assert_eq!((jpeg.width() as usize, jpeg.height() as usize), (width, height));
+        let pixels: Vec<u8> = jpeg.collect();
+        assert_eq!(pixels.len(), width * height);
+        let mut error = 0;
+        for y in 0..height {
+            for x in 0..width {
+                let square =
+                    (width / 3..2 * width / 3).contains(&x) && (height / 3..2 * height / 3).contains(&y);
+                let expected = if square { 20 } else { (x * 255 / (width - 1)) as i32 };
+                // lossy, and most of all at the edges of the square
+                error += (pixels[y * width + x] as i32 - expected).abs();
+            }
+        }
+        let mean = error as f32 / (width * height) as f32;
+        assert!(mean < 6.0, "mean error {}", mean);
+    }
+
+    #[test]
+    fn test_decode() {
+        // greyscale, a size that isn't a whole number of blocks
+        let grey = include_bytes!("../../testdata/grey.jpg");
+        check_pattern(DecodeJpeg::new(&grey[..]).unwrap(), (45, 30));
+        // YCbCr with 4:2:0 subsampled colour, and restart markers every 2 MCUs
+        let colour = include_bytes!("../../testdata/ycbcr420.jpg");
+        check_pattern(DecodeJpeg::new(&colour[..]).unwrap(), (61, 37));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert!(DecodeJpeg::new(&b"\x89PNG\r\n\x1a\n"[..]).is_err());
+        // a progressive frame header
+        let progressive = b"\xFF\xD8\xFF\xC2\x00\x0B\x08\x00\x10\x00\x10\x01\x01\x11\x00";
+        let error = DecodeJpeg::new(&progressive[..]).err().unwrap();
+        assert!(error.to_string().contains("progressive"));
+        // a truncated file decodes as far as it goes
+        let grey = include_bytes!("../../testdata/grey.jpg");
+        let jpeg = DecodeJpeg::new(&grey[..grey.len() - 150]).unwrap();
+        assert!(jpeg.count() < 45 * 30);
+    }
+
+    /// An 8x8 greyscale jpeg whose Huffman tables each have a single code, for the `dc` and `ac`
+    /// magnitude sizes, followed by zero bits.
+    fn single_code(dc: u8, ac: u8) -> Vec<u8> {
+        let mut jpeg = b"\xFF\xD8\xFF\xDB\x00\x43\x00".to_vec();
+        jpeg.extend_from_slice(&[1; 64]);
+        jpeg.extend_from_slice(b"\xFF\xC4\x00\x26");
+        for (class, value) in [(0x00, dc), (0x10, ac)] {
+            jpeg.extend_from_slice(&[class, 1]);
+            jpeg.extend_from_slice(&[0; 15]);
+            jpeg.push(value);
+        }
+        jpeg.extend_from_slice(b"\xFF\xC0\x00\x0B\x08\x00\x08\x00\x08\x01\x01\x11\x00");
+        jpeg.extend_from_slice(b"\xFF\xDA\x00\x08\x01\x01\x00\x00\x3F\x00\x00\x00\xFF\xD9");
+        jpeg
+    }
+
+    #[test]
+    fn test_corrupt() {
+        assert_eq!(DecodeJpeg::new(&single_code(11, 0x00)[..]).unwrap().count(), 64);
+        assert_eq!(DecodeJpeg::new(&single_code(0, 0x0A)[..]).unwrap().count(), 64);
+        // magnitude sizes beyond those of 8 bit samples end the image
+        assert_eq!(DecodeJpeg::new(&single_code(12, 0x00)[..]).unwrap().count(), 0);
+        assert_eq!(DecodeJpeg::new(&single_code(0xFF, 0x00)[..]).unwrap().count(), 0);
+        assert_eq!(DecodeJpeg::new(&single_code(0, 0x0F)[..]).unwrap().count(), 0);
+        // too many pixels to decode
+        let huge = b"\xFF\xD8\xFF\xC0\x00\x0B\x08\xFF\xFF\xFF\xFF\x01\x01\x11\x00";
+        let error = DecodeJpeg::new(&huge[..]).err().unwrap();
+        assert!(error.to_string().contains("too large"));
+    }
+}
@@ -14,7 +14,7 @@
  *   checksum (4 bytes)
  * - The IHDR chunk must be first and contains width, height, bit_depth, color_type, compression_method,
  *   filter_method, and an interlace flag.
- * - The Palette chunck is currently unsupported
+ * - The PLTE chunk holds the palette for color_type 3, and must come before the IDAT chunks.
  * - Consecutive IDAT chunks hold the image data in bytes.
  * - The IEND chunk must be last.
  * - Other chunk types are ignored in this decoder.
@@ -23,13 +23,16 @@
  * - The first byte in each scan-line representes a filter_type which is used to unfilter the remaining
  *   bytes in the scan-line. (filters is used to improve compression)
  * - The uncompressed, unfiltered bytes now represent pixels in the specified color_type (eg RGB, RGBa etc)
+ * - Pixels narrower than a byte are packed, most significant bits first, and each scan-line starts on a
+ *   fresh byte. They are unpacked here to a byte each: grey scaled up to 0-255, and palette indices looked
+ *   up and converted to grey.
  *
  * This development is gratefully based on PNG Pong - Copyright © 2019-2021 Jeron Aldaron Lau
  *
  * author: nworbnhoj
  */
 
-use std::convert::TryInto;
+use std::cmp::max;
 use std::io::{Error, ErrorKind::InvalidData, Read, Result};
 
 use miniz_oxide::{DataFormat, MZFlush, MZStatus, inflate::stream::InflateState};
@@ -38,6 +41,8 @@ use miniz_oxide::{DataFormat, MZFlush, MZStatus, inflate::stream::InflateState};
 mod color_type;
 use color_type::*;
 
+use super::greyscale::luma;
+
 // Magic bytes to start a PNG file.
 pub(super) const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
@@ -74,8 +79,12 @@ pub struct DecodePng<R: Read> {
     byte_index: usize,
     filter_type: u8,
     prior_line: Vec<u8>,
+    line: Vec<u8>,
     line_index: usize,
-    prior_px: Vec<u8>,
+    // fields to unpack pixels narrower than a byte
+    packed: u8,
+    bits_left: u8,
+    sample_x: u32,
     // fields to buffer bytes read from the idat chunk
     idat_remaining: u32,
     idat_buffer: Vec<u8>,
@@ -90,7 +99,9 @@ pub struct DecodePng<R: Read> {
 impl<R: Read> Iterator for DecodePng<R> {
     type Item = u8;
 
-    fn next(&mut self) -> Option<Self::Item> { self.unfilter() }
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bit_depth < 8 || self.palette.is_some() { self.sample() } else { self.unfilter() }
+    }
 }
 
 impl<R: Read> DecodePng<R> {
@@ -110,8 +121,11 @@ impl<R: Read> DecodePng<R> {
             byte_index: 0,
             filter_type: 0,
             prior_line: vec![0u8, 0],
+            line: vec![0u8, 0],
             line_index: 0,
-            prior_px: vec![0u8, 0],
+            packed: 0,
+            bits_left: 0,
+            sample_x: 0,
             idat_remaining: 0,
             idat_buffer: Vec::with_capacity(IDAT_BUFFER_LENGTH),
             inflate_state: InflateState::new_boxed(DataFormat::Zlib),
@@ -202,14 +216,15 @@ impl<R: Read> DecodePng<R> {
                     6 => ColorType::Rgba,
                     _ => return Err(Error::new(InvalidData, "invalid color-type")),
                 };
-                self.bytes_per_pixel = (color_type.bpp(self.bit_depth) / 8).try_into().unwrap();
-                self.bytes_per_line = 1 + self.bytes_per_pixel * self.width as usize;
-                // png filter specification calls for initial prior_line = [0u8]
+                color_type.check_png_color_validity(self.bit_depth)?;
+                let bits_per_pixel = color_type.bpp(self.bit_depth) as usize;
+                // filters work on whole bytes, so packed pixels filter against the byte before
+                self.bytes_per_pixel = max(1, bits_per_pixel / 8);
+                self.bytes_per_line = 1 + (bits_per_pixel * self.width as usize + 7) / 8;
+                // the line before the first is taken to be zeros
                 // see: https://www.w3.org/TR/PNG/#9Filters
-                // but [0u8] results in distortion???????
-                // TODO fix this properly (probably a byte overflow or bad cast)
-                self.prior_line = vec![125u8; self.bytes_per_line];
-                self.prior_px = vec![0u8; self.bytes_per_pixel];
+                self.prior_line = vec![0u8; self.bytes_per_line];
+                self.line = vec![0u8; self.bytes_per_line];
             }
             (_, _) => return Err(Error::new(InvalidData, "header chunk not first")),
         };
@@ -224,12 +239,6 @@ impl<R: Read> DecodePng<R> {
             return Err(Error::new(InvalidData, "invalid filter method"));
         } else if self.interlace {
             return Err(Error::new(InvalidData, "interlace is not supported"));
-        } else if self.bytes_per_pixel < 1 {
-            return Err(Error::new(InvalidData, "less than 8 bits per pixel is not supported"));
-        } else if self.color_type == 3 {
-            return Err(Error::new(InvalidData, "color_type = 3 palette is not supported"));
-        } else {
-            color_type.check_png_color_validity(self.bit_depth)?;
         };
         Ok(())
     }
@@ -413,8 +422,7 @@ impl<R: Read> DecodePng<R> {
     }
 
     // Get an inflated byte from the png idat
-    // Note: this fn() updates inflated_index, byte_index, line_index & filter_type
-    // a scanline of rgb begins with a filterbyte FRGBRGBRGBRGB...
+    // Note: this fn() updates inflated_index & byte_index
     fn get_inflated_byte(&mut self) -> Option<u8> {
         match self.inflated_length() {
             0 => None,
@@ -422,15 +430,6 @@ impl<R: Read> DecodePng<R> {
                 let byte = self.inflated[self.inflated_index];
                 self.inflated_index += 1;
                 self.byte_index += 1;
-                self.line_index = match self.byte_index % self.bytes_per_line {
-                    // capture the filter byte at the beginning of each png scan line
-                    0 => {
-                        self.set_filter_type();
-                        self.prior_px = vec![0u8; self.bytes_per_pixel];
-                        0
-                    }
-                    i => i - 1,
-                };
                 Some(byte)
             }
         }
@@ -440,20 +439,17 @@ impl<R: Read> DecodePng<R> {
     // note that a & c relate to the equivalent byte in the prior pixel
     // prior_line   R G B R G B c G B b G B R G B
     // current_line R G B R G B a G B x
+    // Note: this fn() updates line, prior_line, line_index & filter_type
     fn unfilter(&mut self) -> Option<u8> {
-        let filter = self.filter_type;
         let index = self.line_index;
         let bpp = self.bytes_per_pixel;
-        let fx = match self.get_inflated_byte() {
-            Some(byte) => byte,
-            None => return None,
-        };
+        let fx = self.get_inflated_byte()?;
         let (a, b, c) = if index < bpp {
             (0, self.prior_line[index], 0)
         } else {
-            (self.prior_px[index % bpp], self.prior_line[index], self.prior_line[index - bpp])
+            (self.line[index - bpp], self.prior_line[index], self.prior_line[index - bpp])
         };
-        let x = match filter {
+        let x = match self.filter_type {
             0 => fx,
             1 => fx.wrapping_add(a),
             2 => fx.wrapping_add(b),
@@ -461,12 +457,44 @@ impl<R: Read> DecodePng<R> {
             4 => fx.wrapping_add(paeth(a, b, c)),
             _ => fx,
         };
-        if index > bpp {
-            self.prior_line[index - bpp] = a;
+        self.line[index] = x;
+        self.line_index += 1;
+        // at the end of a scan line it becomes the prior line, and the next begins with its filter type
+        if self.line_index >= self.bytes_per_line - 1 {
+            std::mem::swap(&mut self.line, &mut self.prior_line);
+            self.line_index = 0;
+            self.set_filter_type();
         }
-        self.prior_px[index % bpp] = x;
         Some(x)
     }
+
+    // Unpacks the next pixel from bytes holding one or more, as a grey byte
+    // Note: this fn() updates packed, bits_left & sample_x
+    fn sample(&mut self) -> Option<u8> {
+        if self.bits_left == 0 {
+            self.packed = self.unfilter()?;
+            self.bits_left = 8;
+        }
+        self.bits_left -= self.bit_depth;
+        let mask = ((1u16 << self.bit_depth) - 1) as u8;
+        let value = (self.packed >> self.bits_left) & mask;
+        self.sample_x += 1;
+        if self.sample_x >= self.width {
+            // the rest of the byte is padding
+            self.sample_x = 0;
+            self.bits_left = 0;
+        }
+        match &self.palette {
+            Some(palette) => match palette.get(value as usize) {
+                Some(&(r, g, b)) => Some(luma(r, g, b)),
+                None => {
+                    log::warn!("png decode: palette index {} out of range", value);
+                    Some(0)
+                }
+            },
+            None => Some((value as u16 * 255 / mask as u16) as u8),
+        }
+    }
 }
 
 // in the notation from https://www.w3.org/TR/PNG/#9Filters
@@ -487,3 +515,103 @@ fn paeth(a: u8, b: u8, c: u8) -> u8 {
         c as u8
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(png: &mut Vec<u8>, name: &[u8; 4], data: &[u8]) {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        png.extend_from_slice(name);
+        png.extend_from_slice(data);
+        // the checksum is ignored
+        png.extend_from_slice(&[0; 4]);
+    }
+
+    /// Encodes `lines` of raw bytes, each filtered with the type given.
+    fn encode(ihdr: &[u8], palette: Option<&[u8]>, lines: &[(u8, Vec<u8>)], bpp: usize) -> Vec<u8> {
+        let mut filtered = Vec::new();
+        let mut prior = vec![0u8; lines[0].1.len()];
+        for (filter_type, line) in lines {
+            filtered.push(*filter_type);
+            for i in 0..line.len() {
+                let a = if i < bpp { 0 } else { line[i - bpp] };
+                let c = if i < bpp { 0 } else { prior[i - bpp] };
+                let b = prior[i];
+                let predicted = match filter_type {
+                    1 => a,
+                    2 => b,
+                    3 => average(a, b),
+                    4 => paeth(a, b, c),
+                    _ => 0,
+                };
+                filtered.push(line[i].wrapping_sub(predicted));
+            }
+            prior = line.clone();
+        }
+        let mut png = PNG_SIGNATURE.to_vec();
+        chunk(&mut png, &IMAGE_HEADER, ihdr);
+        if let Some(palette) = palette {
+            chunk(&mut png, &PALETTE, palette);
+        }
+        let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&filtered, 6);
+        // split over two idat chunks
+        let (first, second) = compressed.split_at(compressed.len() / 2);
+        chunk(&mut png, &IMAGE_DATA, first);
+        chunk(&mut png, &IMAGE_DATA, second);
+        chunk(&mut png, &IMAGE_END, &[]);
+        png
+    }
+
+    fn ihdr(width: u32, height: u32, bit_depth: u8, color_type: u8) -> Vec<u8> {
+        let mut ihdr = width.to_be_bytes().to_vec();
+        ihdr.extend_from_slice(&height.to_be_bytes());
+        ihdr.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+        ihdr
+    }
+
+    #[test]
+    fn test_rgb_filters() {
+        // 3 pixels across, so the filters must look back a whole pixel
+        let lines: Vec<(u8, Vec<u8>)> = (0..5u8)
+            .map(|y| (y, (0..9u8).map(|i| i.wrapping_mul(29).wrapping_add(y * 51)).collect()))
+            .collect();
+        let png = encode(&ihdr(3, 5, 8, 2), None, &lines, 3);
+        let decoded: Vec<u8> = DecodePng::new(&png[..]).unwrap().collect();
+        let expected: Vec<u8> = lines.iter().flat_map(|(_, line)| line.clone()).collect();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn test_packed_grey() {
+        // 2 bit pixels, 5 to a line with the last byte padded
+        let values = [[0, 1, 2, 3, 0], [3, 3, 2, 1, 1], [1, 0, 3, 2, 2]];
+        let lines: Vec<(u8, Vec<u8>)> = values
+            .iter()
+            .enumerate()
+            .map(|(y, row)| {
+                let (first, second) = (row[0] << 6 | row[1] << 4 | row[2] << 2 | row[3], row[4] << 6);
+                (y as u8 * 2 % 5, vec![first, second])
+            })
+            .collect();
+        let png = encode(&ihdr(5, 3, 2, 0), None, &lines, 1);
+        let png = DecodePng::new(&png[..]).unwrap();
+        assert_eq!((png.color_type(), png.bit_depth()), (0, 2));
+        let decoded: Vec<u8> = png.collect();
+        let expected: Vec<u8> = values.iter().flatten().map(|&v| v * 85).collect();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn test_palette() {
+        // 1 bit indices into a palette of red and white, 10 to a line
+        let palette = [255, 0, 0, 255, 255, 255];
+        let lines = vec![(0, vec![0b1010_1010, 0b1000_0000]), (1, vec![0b0000_1111, 0b0100_0000])];
+        let png = encode(&ihdr(10, 2, 1, 3), Some(&palette), &lines, 1);
+        let decoded: Vec<u8> = DecodePng::new(&png[..]).unwrap().collect();
+        let red = luma(255, 0, 0);
+        let mut expected = vec![255, red, 255, red, 255, red, 255, red, 255, red];
+        expected.extend_from_slice(&[red, red, red, red, 255, 255, 255, 255, red, 255]);
+        assert_eq!(decoded, expected);
+    }
+}
@@ -34,6 +34,32 @@ pub const BURKES: [(isize, isize, i16); 7] = [
     (2, 1, 2),
 ];
 
+/// Floyd-Steinberg dithering. Div=16.
+/// Sharper than Burkes, as the error is spread over fewer pixels, at the cost of more visible worms
+/// in flat areas.
+/// - ` .  x  7`
+/// - ` 3  5  1`
+pub const FLOYD_STEINBERG: [(isize, isize, i16); 4] = [
+    // (dx, dy, mul)
+    (1, 0, 7),
+    //
+    (-1, 1, 3),
+    (0, 1, 5),
+    (1, 1, 1),
+];
+
+/// How a greyscale image is reduced to black and white.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dithering {
+    /// error diffusion with the Burkes scheme
+    Burkes,
+    /// error diffusion with the Floyd-Steinberg scheme
+    FloydSteinberg,
+    /// a fixed 8x8 Bayer threshold pattern: a regular texture, and the same pixels every frame, which suits
+    /// animation
+    Ordered,
+}
+
 pub struct Dither<'a, I> {
     /// iterator over inbound pixels
     iter: I,
@@ -144,3 +170,111 @@ pub trait DitherIterator<'a>: Iterator<Item = u8> + Sized {
 }
 
 impl<'a, I: Iterator<Item = u8>> DitherIterator<'a> for I {}
+
+/// The 8x8 Bayer threshold matrix, in 64ths.
+const BAYER: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Ordered dithering compares each pixel with a threshold that varies with its position, rather than
+/// carrying the error forward. Words are packed as by `Dither`.
+pub struct Ordered<I> {
+    /// iterator over inbound pixels
+    iter: I,
+    // the width of the image to be dithered
+    width: usize,
+    next_x: usize,
+    next_y: usize,
+}
+
+impl<I: Iterator<Item = u8>> Ordered<I> {
+    fn new(iter: I, width: usize) -> Ordered<I> { Self { iter, width, next_x: 0, next_y: 0 } }
+
+    fn pixel(&self, grey: u8) -> PixelColor {
+        let threshold = BAYER[self.next_y % 8][self.next_x % 8] as u16 * 4 + 2;
+        if (grey as u16) < threshold { PixelColor::Dark } else { PixelColor::Light }
+    }
+}
+
+impl<I: Iterator<Item = u8>> Iterator for Ordered<I> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut word = 0;
+        for w in 0..BITS_PER_WORD {
+            match self.iter.next() {
+                Some(grey) => {
+                    let color = self.pixel(grey) as u32;
+                    word = word | (color << w);
+                }
+                None => {
+                    if w > 0 {
+                        break;
+                    } else {
+                        return None;
+                    }
+                }
+            };
+            self.next_x += 1;
+            if self.next_x >= self.width {
+                break;
+            }
+        }
+        if self.next_x >= self.width {
+            self.next_x = 0;
+            self.next_y += 1;
+        }
+        Some(word)
+    }
+}
+
+pub trait OrderedIterator: Iterator<Item = u8> + Sized {
+    fn dither_ordered(self, width: usize) -> Ordered<Self> { Ordered::new(self, width) }
+}
+
+impl<I: Iterator<Item = u8>> OrderedIterator for I {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The share of dark pixels in a dithered image of one shade of grey. Light pixels are the set bits.
+    fn coverage(words: impl Iterator<Item = u32>, pixels: usize) -> f32 {
+        let light: u32 = words.map(|word| word.count_ones()).sum();
+        1.0 - light as f32 / pixels as f32
+    }
+
+    #[test]
+    fn test_dither_coverage() {
+        // 32 pixels wide, so each line is one word
+        let (width, height) = (32, 32);
+        let burkes = BURKES.to_vec();
+        let floyd = FLOYD_STEINBERG.to_vec();
+        for grey in [0u8, 64, 128, 192, 255] {
+            let expected = 1.0 - grey as f32 / 255.0;
+            let pixels = std::iter::repeat(grey).take(width * height);
+            let burkes = coverage(pixels.clone().dither(&burkes, width), width * height);
+            let floyd = coverage(pixels.clone().dither(&floyd, width), width * height);
+            let ordered = coverage(pixels.dither_ordered(width), width * height);
+            for found in [burkes, floyd, ordered] {
+                assert!((found - expected).abs() < 0.05, "grey {} gave {} dark", grey, found);
+            }
+        }
+    }
+
+    #[test]
+    fn test_ordered_words() {
+        // 40 pixels wide: a full word, then a word of 8 pixels, on every line
+        let words: Vec<u32> = std::iter::repeat(255u8).take(40 * 3).dither_ordered(40).collect();
+        assert_eq!(words, [u32::MAX, 0xFF, u32::MAX, 0xFF, u32::MAX, 0xFF]);
+        let words: Vec<u32> = std::iter::repeat(0u8).take(40 * 3).dither_ordered(40).collect();
+        assert_eq!(words, [0; 6]);
+    }
+}
@@ -92,19 +92,22 @@ impl<I: Iterator<Item = u8>> Iterator for GreyScale<I> {
 
 // chromatic coversion from RGB to Greyscale
 fn grey(r: Option<u8>, g: Option<u8>, b: Option<u8>) -> Option<u8> {
+    match (r, g, b) {
+        (Some(r), Some(g), Some(b)) => Some(luma(r, g, b)),
+        _ => None,
+    }
+}
+
+/// The luminance of an RGB colour, with the Rec. 709 weights
+pub(crate) fn luma(r: u8, g: u8, b: u8) -> u8 {
     const R: u32 = 2126;
     const G: u32 = 7152;
     const B: u32 = 722;
     const BLACK: u32 = R + G + B;
-    if r.is_some() && g.is_some() && b.is_some() {
-        let grey_r = R * r.unwrap() as u32;
-        let grey_g = G * g.unwrap() as u32;
-        let grey_b = B * b.unwrap() as u32;
-        let grey: u8 = ((grey_r + grey_g + grey_b) / BLACK).try_into().unwrap();
-        Some(grey)
-    } else {
-        None
-    }
+    let grey_r = R * r as u32;
+    let grey_g = G * g as u32;
+    let grey_b = B * b as u32;
+    ((grey_r + grey_g + grey_b) / BLACK).try_into().unwrap()
 }
 
 pub trait GreyScaleIterator: Iterator<Item = u8> + Sized {
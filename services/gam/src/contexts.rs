@@ -154,6 +154,18 @@ impl ContextManager {
         self.tm.register_name(name, auth_token);
     }
 
+    #[cfg(feature = "unsafe-app-loading")]
+    pub(crate) fn grant_capabilities(
+        &mut self,
+        name: &str,
+        capabilities: Vec<String>,
+        auth_token: &[u32; 4],
+    ) -> bool {
+        self.tm.grant_capabilities(name, capabilities, auth_token)
+    }
+
+    pub(crate) fn capabilities(&self, name: &str) -> Option<Vec<String>> { self.tm.capabilities(name) }
+
     pub(crate) fn allow_untrusted_code(&self) -> bool { self.tm.allow_untrusted_code() }
 
     pub(crate) fn is_token_valid(&self, token: [u32; 4]) -> bool { self.tm.is_token_valid(token) }
@@ -713,6 +725,23 @@ impl ContextManager {
 
     pub(crate) fn focused_app(&self) -> Option<[u32; 4]> { self.focused_context }
 
+    pub(crate) fn context_name(&self, token: [u32; 4]) -> Option<String> { self.tm.lookup_name(&token) }
+
+    /// The canvases of the focused context that hold what it shows, in drawing order; the prediction
+    /// area is left out.
+    pub(crate) fn focused_canvases(&self) -> Vec<Gid> {
+        match self.focused_context() {
+            Some(context) => context
+                .layout
+                .get_gids()
+                .iter()
+                .filter(|record| record.canvas_type != CanvasType::ChatPreditive)
+                .map(|record| record.gid)
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
     pub(crate) fn forward_input(&self, input: String) -> Result<(), xous::Error> {
         if let Some(token) = self.focused_app() {
             if let Some(context) = self.contexts.get(&token) {
@@ -14,7 +14,7 @@ pub use apps::*;
 pub mod bitmap;
 use api::Opcode; // if you prefer to map the api into your local namespace
 #[cfg(feature = "ditherpunk")]
-pub use bitmap::{Bitmap, DecodePng, Img, PixelType};
+pub use bitmap::{Bitmap, DecodeGif, DecodeJpeg, DecodePng, Dithering, GifFrame, Img, PixelType, MAX_PIXELS};
 pub use graphics_server::api::GlyphStyle;
 pub use graphics_server::api::PixelColor;
 #[cfg(feature = "ditherpunk")]
@@ -80,8 +80,15 @@ pub enum FocusState {
     Foreground = 1,
 }
 impl FocusState {
+    /// Decodes a focus change callback's argument. Coming into the foreground also picks up the language
+    /// if it was changed while this process was in the background.
     pub fn convert_focus_change(code: usize) -> FocusState {
-        if code != 0 { FocusState::Foreground } else { FocusState::Background }
+        if code != 0 {
+            sync_language();
+            FocusState::Foreground
+        } else {
+            FocusState::Background
+        }
     }
 }
 
@@ -97,6 +104,8 @@ impl Gam {
     pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
         REFCOUNT.fetch_add(1, Ordering::Relaxed);
         let conn = xns.request_connection_blocking(api::SERVER_NAME_GAM).expect("Can't connect to GAM");
+        LANGUAGE_CONN.store(conn, Ordering::Relaxed);
+        sync_language();
         Ok(Gam { conn, callback_sid: None })
     }
 
@@ -151,11 +160,9 @@ impl Gam {
     /// the actual screen update is allowed
     ///
     /// This will also truncate any text that is too long to fit into a single paged-sized transaction, as set
-    /// by `graphics_server::api::TEXTVIEW_LEN`
+    /// by `graphics_server::api::TEXTVIEW_LEN`, along with any spans past `MAX_TEXT_SPANS`
     pub fn post_textview(&self, tv: &mut TextView) -> Result<(), xous::Error> {
-        if tv.text.len() > graphics_server::api::TEXTVIEW_LEN {
-            tv.text.truncate(graphics_server::api::TEXTVIEW_LEN);
-        }
+        tv.fit_to_page();
         tv.set_op(TextOp::Render);
         // force the clip_rect to none, in case a stale value from a previous bounds computation was hanging
         // out the bounds should /always/ come from the GAM canvas when doing a "live fire" redraw
@@ -169,6 +176,7 @@ impl Gam {
                 tv.bounds_computed = tvr.bounds_computed;
                 tv.cursor = tvr.cursor;
                 tv.overflow = tvr.overflow;
+                tv.link_regions = tvr.link_regions;
                 tv.busy_animation_state = tvr.busy_animation_state;
             }
             api::Return::NotCurrentlyDrawable => {
@@ -186,6 +194,7 @@ impl Gam {
     /// always takes the bounds from the canvas, the caller can specify a clip_rect in this tv, instead of
     /// drawing the clip_rect from the Canvas associated with the tv.
     pub fn bounds_compute_textview(&self, tv: &mut TextView) -> Result<(), xous::Error> {
+        tv.fit_to_page();
         tv.set_op(TextOp::ComputeBounds);
         let mut buf = Buffer::into_buf(tv.clone()).or(Err(xous::Error::InternalError))?;
         buf.lend_mut(self.conn, Opcode::RenderTextView.to_u32().unwrap())
@@ -196,6 +205,7 @@ impl Gam {
                 tv.bounds_computed = tvr.bounds_computed;
                 tv.cursor = tvr.cursor;
                 tv.overflow = tvr.overflow;
+                tv.link_regions = tvr.link_regions;
             }
             _ => panic!(
                 "GAM_API: bounds_compute_textview got a return value from the server that isn't expected or handled"
@@ -343,6 +353,32 @@ impl Gam {
         Ok(())
     }
 
+    /// Sets the capabilities of an app whose name was registered with `register_name()`. Only apps that
+    /// are in the image may do this, like `register_name()`.
+    #[cfg(feature = "unsafe-app-loading")]
+    /// Grants `capabilities` to the app `name`, whose process `pid` was just spawned and hasn't run it yet.
+    /// Returns false if the GAM refused, in which case the app mustn't be started.
+    pub fn grant_capabilities(
+        &self,
+        name: &str,
+        capabilities: &[String],
+        pid: u32,
+        auth_token: [u32; 4],
+    ) -> Result<bool, xous::Error> {
+        let grant = CapabilityGrant {
+            name: String::from(name),
+            capabilities: capabilities.to_vec(),
+            pid,
+            auth_token,
+            granted: false,
+        };
+        let mut buf = Buffer::into_buf(grant).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::GrantCapabilities.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        let grant = buf.to_original::<CapabilityGrant, _>().or(Err(xous::Error::InternalError))?;
+        Ok(grant.granted)
+    }
+
     pub fn set_predictor_api_token(
         &self,
         api_token: [u32; 4],
@@ -509,6 +545,111 @@ impl Gam {
         .expect("couldn't set debug level");
     }
 
+    /// Selects the language strings are shown in, device-wide. `lang` is one of `locales::LANGUAGES`.
+    /// Only the status process, which keeps the preferences, may do this; anyone else gets `AccessDenied`.
+    pub fn set_language(&self, lang: &str) -> Result<(), xous::Error> {
+        let index = locales::lang_index(lang).ok_or(xous::Error::InvalidString)?;
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::SetLanguage.to_usize().unwrap(), index, 0, 0, 0),
+        )? {
+            xous::Result::Scalar1(1) => Ok(()),
+            _ => Err(xous::Error::AccessDenied),
+        }
+    }
+
+    /// The language strings are shown in, device-wide.
+    pub fn language(&self) -> Result<&'static str, xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::GetLanguage.to_usize().unwrap(), 0, 0, 0, 0),
+        )? {
+            xous::Result::Scalar1(index) => {
+                locales::LANGUAGES.get(index).copied().ok_or(xous::Error::InternalError)
+            }
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Has `opcode` sent to `sid` whenever the language changes, with the new language's index in
+    /// `locales::LANGUAGES` as its first argument. Only the status process may listen, once, so it can
+    /// rename the menus; everyone else picks the language up with `sync_language()`. Call
+    /// `locales::set_lang()` before redrawing.
+    pub fn register_locale_listener(&self, sid: xous::SID, opcode: u32) -> Result<(), xous::Error> {
+        let listener = LocaleListener { sid: sid.to_array(), opcode };
+        let buf = Buffer::into_buf(listener).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, Opcode::RegisterLocaleListener.to_u32().unwrap()).map(|_| ())
+    }
+
+    /// Tells the screen reader what the context holding `token` shows, replacing what it said before.
+    /// `focus` is the index in `nodes` of what keys act on. Trees that don't fit in a page lose nodes from
+    /// the end. Never put secrets in a node's `value`.
+    pub fn publish_accessibility(
+        &self,
+        token: [u32; 4],
+        nodes: Vec<A11yNode>,
+        focus: Option<usize>,
+    ) -> Result<(), xous::Error> {
+        let mut tree = A11yTree { token, nodes, focus: focus.map(|f| f as u16) };
+        loop {
+            match Buffer::into_buf(tree.clone()) {
+                Ok(buf) => {
+                    return buf.lend(self.conn, Opcode::PublishAccessibility.to_u32().unwrap()).map(|_| ());
+                }
+                Err(_) if tree.nodes.len() > 1 => {
+                    tree.nodes.pop();
+                    if tree.focus.is_some_and(|f| f as usize >= tree.nodes.len()) {
+                        tree.focus = None;
+                    }
+                }
+                Err(_) => return Err(xous::Error::OutOfMemory),
+            }
+        }
+    }
+
+    /// What the focused context shows, with `focus` on the screen reader's cursor rather than the
+    /// context's own focus. Long trees come back as the part around the cursor. Only services in the boot
+    /// image may read it; apps get an empty tree.
+    pub fn accessibility_tree(&self) -> Result<A11yTree, xous::Error> {
+        let mut buf = Buffer::into_buf(A11yTree::default()).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::AccessibilityTree.to_u32().unwrap())?;
+        buf.to_original::<A11yTree, _>().or(Err(xous::Error::InternalError))
+    }
+
+    /// Runs a screen reader command; `arg` is only used by `ReaderCommand::Route`. Returns whether the
+    /// reader speaks.
+    pub fn reader_command(&self, command: ReaderCommand, arg: usize) -> Result<bool, xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(
+                Opcode::ReaderCommand.to_usize().unwrap(),
+                command.to_usize().unwrap(),
+                arg,
+                0,
+                0,
+            ),
+        )? {
+            xous::Result::Scalar1(speaks) => Ok(speaks != 0),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Switches the screen reader's speech, and its F4 key commands, on or off.
+    pub fn set_screen_reader(&self, on: bool) -> Result<(), xous::Error> {
+        self.reader_command(if on { ReaderCommand::Speak } else { ReaderCommand::Silence }, 0).map(|_| ())
+    }
+
+    /// Has `opcode` sent to `sid` whenever the screen reader's cursor moves or what it's reading changes.
+    /// The arguments are the index of the node under the cursor, or `usize::MAX` if there's none, and the
+    /// number of nodes; `accessibility_tree()` gets the rest. This is how a braille display follows along.
+    /// Only services in the boot image may listen. A listener takes effect once every app in the image has
+    /// registered, and until one has, drawn text isn't kept unless the reader speaks.
+    pub fn register_accessibility_listener(&self, sid: xous::SID, opcode: u32) -> Result<(), xous::Error> {
+        let listener = AccessibilityListener { sid: sid.to_array(), opcode };
+        let buf = Buffer::into_buf(listener).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, Opcode::RegisterAccessibilityListener.to_u32().unwrap()).map(|_| ())
+    }
+
     pub fn bytes_to_bip39(&self, bytes: &Vec<u8>) -> Result<Vec<std::string::String>, xous::Error> {
         match bytes.len() {
             16 | 20 | 24 | 28 | 32 => (),
@@ -575,6 +716,28 @@ impl Gam {
 
 use core::sync::atomic::{AtomicU32, Ordering};
 static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+/// This process's connection to the GAM, for `sync_language()`; 0, which is never a connection, until a
+/// `Gam` is made.
+static LANGUAGE_CONN: AtomicU32 = AtomicU32::new(0);
+
+/// Brings `locales::lang()` in this process up to date with the language the user picked, so that `t!()`
+/// gives strings in it. This happens whenever a `Gam` is made and whenever a UX comes into the foreground
+/// (see `FocusState::convert_focus_change`); a process that builds strings at other times, without a UX
+/// of its own, should call this first. Does nothing in a process that hasn't made a `Gam`.
+pub fn sync_language() {
+    let conn = LANGUAGE_CONN.load(Ordering::Relaxed);
+    if conn == 0 {
+        return;
+    }
+    if let Ok(xous::Result::Scalar1(index)) =
+        send_message(conn, Message::new_blocking_scalar(Opcode::GetLanguage.to_usize().unwrap(), 0, 0, 0, 0))
+    {
+        if let Some(lang) = locales::LANGUAGES.get(index) {
+            locales::set_lang(lang);
+        }
+    }
+}
+
 impl Drop for Gam {
     fn drop(&mut self) {
         if let Some(sid) = self.callback_sid.take() {
@@ -594,6 +757,7 @@ impl Drop for Gam {
             xous::destroy_server(sid).unwrap();
         }
         if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            LANGUAGE_CONN.store(0, Ordering::Relaxed);
             unsafe {
                 xous::disconnect(self.conn).unwrap();
             }
@@ -601,6 +765,33 @@ impl Drop for Gam {
     }
 }
 
+/// Looks up which app a process is, for services that check what their callers may do.
+pub struct AppIdentities {
+    conn: xous::CID,
+}
+impl AppIdentities {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        let conn = xns.request_connection_blocking(api::SERVER_NAME_GAM_APPS)?;
+        Ok(AppIdentities { conn })
+    }
+
+    /// Returns the app that `pid` belongs to, if any, the capabilities it was granted, and whether all the
+    /// apps in the image have registered yet.
+    pub fn lookup(&self, pid: u32) -> Result<AppIdentity, xous::Error> {
+        let identity = AppIdentity { pid, app: None, capabilities: Vec::new(), booted: false };
+        let mut buf = Buffer::into_buf(identity).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::AppIdentity.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        buf.to_original::<AppIdentity, _>().or(Err(xous::Error::InternalError))
+    }
+}
+impl Drop for AppIdentities {
+    fn drop(&mut self) {
+        unsafe {
+            xous::disconnect(self.conn).ok();
+        }
+    }
+}
+
 // common message forwarding infrastructure used by Menus, Modals, etc...
 #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
 struct MsgForwarder {
@@ -10,9 +10,11 @@ use layouts::*;
 mod contexts;
 use contexts::*;
 mod bip39;
+mod reader;
 
-use core::sync::atomic::{AtomicU32, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
 use api::Opcode;
 #[cfg(feature = "cramium-soc")]
@@ -117,10 +119,35 @@ fn wrapped_main() -> ! {
     // a random number we can use to identify ourselves between API calls
     let gam_token =
         [trng.get_u32().unwrap(), trng.get_u32().unwrap(), trng.get_u32().unwrap(), trng.get_u32().unwrap()];
+    // the GAM is in the path of every keypress becoming pixels, so its pages are the last to go to swap
+    #[cfg(feature = "swap")]
+    xous_swapper::Swapper::new()
+        .and_then(|swapper| swapper.advise_process(xous_swapper::SwapAdvice::Hot))
+        .unwrap_or_else(|e| log::warn!("couldn't advise the swapper: {:?}", e));
 
     let mut powerdown_requested = false;
     let mut last_time: u64 = ticktimer.elapsed_ms();
     let mut did_test = false; // allow one go at the test pattern
+    // the status process, which owns the preferences: only it may change the language, and it's the one
+    // server told when it does. Everyone else picks the language up with `GetLanguage` when they need it.
+    let mut status_pid: Option<u32> = None;
+    let mut locale_listener: Option<(xous::CID, u32)> = None;
+    // images built with speech are for people who listen to the screen, so the reader starts out speaking
+    let mut screen_reader = reader::ScreenReader::new(cfg!(feature = "tts"));
+    // listeners that registered before every app in the image did, with their PIDs, held until it can be
+    // told whether they are apps
+    let mut held_listeners: Vec<(u32, AccessibilityListener)> = Vec::new();
+    // the app each process registered a UX or claimed a token for, or was spawned as by the app loader, and
+    // what it may use, so services can check their callers
+    let app_pids: Arc<Mutex<HashMap<u32, (String, Vec<String>)>>> = Arc::new(Mutex::new(HashMap::new()));
+    // set once every context in the image has registered: until then, a process that isn't known as an app
+    // may yet turn out to be one
+    let booted = Arc::new(AtomicBool::new(false));
+    std::thread::spawn({
+        let app_pids = app_pids.clone();
+        let booted = booted.clone();
+        move || app_identity_server(app_pids, booted)
+    });
     log::trace!("entering main loop");
 
     #[cfg(not(target_os = "xous"))]
@@ -141,6 +168,7 @@ fn wrapped_main() -> ! {
             Some(Opcode::ClearCanvas) => {
                 msg_scalar_unpack!(msg, g0, g1, g2, g3, {
                     let gid = Gid::new([g0 as _, g1 as _, g2 as _, g3 as _]);
+                    screen_reader.canvas_cleared(gid);
                     match canvases.get(&gid) {
                         Some(c) => {
                             let mut rect = c.clip_rect();
@@ -199,6 +227,7 @@ fn wrapped_main() -> ! {
                     if powerdown_requested {
                         continue; // don't allow any redraws if a powerdown is requested
                     }
+                    screen_reader.follow(&context_mgr, false);
                     let elapsed_time = ticktimer.elapsed_ms();
                     if elapsed_time - last_time > gam::RATE_LIMIT_MS as u64 {
                         // rate limit updates, no point in going faster than the eye can see
@@ -236,6 +265,8 @@ fn wrapped_main() -> ! {
                 let mut buffer =
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let mut tv = buffer.to_original::<TextView, _>().unwrap();
+                // the reply goes back in the same page, with the link regions filled in
+                tv.fit_to_page();
                 log::trace!("rendertextview {:?}", tv);
                 match tv.get_op() {
                     TextOp::Nop => (),
@@ -282,7 +313,9 @@ fn wrapped_main() -> ! {
                                 tv.cursor = tv_clone.cursor;
                                 tv.bounds_computed = tv_clone.bounds_computed;
                                 tv.overflow = tv_clone.overflow;
+                                tv.link_regions = tv_clone.link_regions;
                                 tv.busy_animation_state = tv_clone.busy_animation_state;
+                                screen_reader.text_drawn(tv.get_canvas_gid(), tv.to_str());
 
                                 let ret = api::Return::RenderReturn(tv);
                                 buffer.replace(ret).unwrap();
@@ -339,6 +372,7 @@ fn wrapped_main() -> ! {
                         tv.cursor = tv_clone.cursor;
                         tv.bounds_computed = tv_clone.bounds_computed;
                         tv.overflow = tv_clone.overflow;
+                        tv.link_regions = tv_clone.link_regions;
                         tv.busy_animation_state = tv_clone.busy_animation_state;
 
                         let ret = api::Return::RenderReturn(tv);
@@ -533,6 +567,23 @@ fn wrapped_main() -> ! {
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let mut tokenclaim = buffer.to_original::<TokenClaim, _>().unwrap();
                 tokenclaim.token = context_mgr.claim_token(tokenclaim.name.as_str());
+                if tokenclaim.token.is_some() && tokenclaim.name.as_str() == gam::STATUS_BAR_NAME {
+                    status_pid = msg.sender.pid().map(|pid| pid.get() as u32);
+                }
+                if let (Some(_), Some(capabilities)) =
+                    (tokenclaim.token, context_mgr.capabilities(tokenclaim.name.as_str()))
+                {
+                    if let Some(pid) = msg.sender.pid() {
+                        app_pids
+                            .lock()
+                            .unwrap()
+                            .entry(pid.get() as u32)
+                            .or_insert((tokenclaim.name.to_string(), capabilities));
+                    }
+                }
+                if !booted.load(Ordering::SeqCst) && context_mgr.allow_untrusted_code() {
+                    booted.store(true, Ordering::SeqCst);
+                }
                 buffer.replace(tokenclaim).unwrap();
             }
             Some(Opcode::PredictorApiToken) => {
@@ -554,6 +605,7 @@ fn wrapped_main() -> ! {
 
                 let init_focus_found =
                     if registration.app_name.as_str() == INITIAL_APP_FOCUS { true } else { false };
+                let app_name = registration.app_name.to_string();
                 // note that we are currently assigning all Ux registrations a trust level consistent with a
                 // boot context (ultimately trusted) this needs to be modified later on once
                 // we allow post-boot apps to be created
@@ -564,6 +616,15 @@ fn wrapped_main() -> ! {
                 // references through compound data structures this is broken into two steps because of https://github.com/rust-lang/rust/issues/71126
                 recompute_canvases(&canvases);
 
+                // a process is only ever the first app it registers; submenus aren't apps of their own
+                if let (Some(_), Some(capabilities)) = (token, context_mgr.capabilities(&app_name)) {
+                    if let Some(pid) = msg.sender.pid() {
+                        app_pids.lock().unwrap().entry(pid.get() as u32).or_insert((app_name, capabilities));
+                    }
+                }
+                if !booted.load(Ordering::SeqCst) && context_mgr.allow_untrusted_code() {
+                    booted.store(true, Ordering::SeqCst);
+                }
                 buffer.replace(Return::UxToken(token)).unwrap();
 
                 // fire off a thread that deals with activating the initial boot context. You need this
@@ -606,7 +667,10 @@ fn wrapped_main() -> ! {
                     core::char::from_u32(k3 as u32).unwrap_or('\u{0000}'),
                     core::char::from_u32(k4 as u32).unwrap_or('\u{0000}'),
                 ];
-                context_mgr.key_event(keys, &gfx, &mut canvases);
+                let keys = screen_reader.key_event(keys, &context_mgr);
+                if keys[0] != '\u{0000}' {
+                    context_mgr.key_event(keys, &gfx, &mut canvases);
+                }
             }),
             Some(Opcode::Vibe) => msg_scalar_unpack!(msg, ena, _, _, _, {
                 if ena != 0 { context_mgr.vibe(true) } else { context_mgr.vibe(false) }
@@ -778,6 +842,112 @@ fn wrapped_main() -> ! {
                 context_mgr.allow_mainmenu();
                 xous::return_scalar(msg.sender, 0).ok();
             }
+            Some(Opcode::SetLanguage) => msg_blocking_scalar_unpack!(msg, index, _, _, _, {
+                let sender = msg.sender.pid().map(|pid| pid.get() as u32);
+                match locales::LANGUAGES.get(index) {
+                    Some(_) if status_pid.is_none() || sender != status_pid => {
+                        log::warn!(
+                            "language change from PID {:?}, which isn't the status process, refused",
+                            sender
+                        );
+                        xous::return_scalar(msg.sender, 0).ok();
+                    }
+                    Some(lang) => {
+                        log::info!("switching language to {}", lang);
+                        locales::set_lang(lang);
+                        // the graphics server picks glyph rules by language
+                        gfx.set_language(index).ok();
+                        if let Some((cid, opcode)) = locale_listener {
+                            if let Err(e) = xous::try_send_message(
+                                cid,
+                                xous::Message::new_scalar(opcode as usize, index, 0, 0, 0),
+                            ) {
+                                log::warn!("couldn't tell the status process about the language: {:?}", e);
+                            }
+                        }
+                        xous::return_scalar(msg.sender, 1).ok();
+                    }
+                    None => {
+                        log::warn!("no language with index {}", index);
+                        xous::return_scalar(msg.sender, 0).ok();
+                    }
+                }
+            }),
+            Some(Opcode::GetLanguage) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                let index = locales::lang_index(locales::lang()).unwrap_or(0);
+                xous::return_scalar(msg.sender, index).ok();
+            }),
+            Some(Opcode::RegisterLocaleListener) => {
+                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let listener = buffer.to_original::<LocaleListener, _>().unwrap();
+                let sender = msg.sender.pid().map(|pid| pid.get() as u32);
+                if status_pid.is_none() || sender != status_pid {
+                    log::warn!(
+                        "locale listener from PID {:?}, which isn't the status process, refused",
+                        sender
+                    );
+                } else if locale_listener.is_some() {
+                    log::warn!("the status process already listens for language changes");
+                } else {
+                    match xous::connect(xous::SID::from_array(listener.sid)) {
+                        Ok(cid) => locale_listener = Some((cid, listener.opcode)),
+                        Err(e) => log::warn!("couldn't connect to locale listener: {:?}", e),
+                    }
+                }
+            }
+            Some(Opcode::PublishAccessibility) => {
+                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let tree = buffer.to_original::<A11yTree, _>().unwrap();
+                if context_mgr.is_token_valid(tree.token) {
+                    screen_reader.publish(tree, &context_mgr);
+                } else {
+                    log::warn!("accessibility tree published with an invalid token, ignored");
+                }
+            }
+            Some(Opcode::AccessibilityTree) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                // the tree is whatever the focused app shows, passwords included, so only services in the
+                // boot image may read it; anyone else gets an empty one
+                let pid = msg.sender.pid().map(|pid| pid.get() as u32).unwrap_or_default();
+                let mut tree =
+                    if !booted.load(Ordering::SeqCst) || app_pids.lock().unwrap().contains_key(&pid) {
+                        log::warn!("PID {} may not read the accessibility tree", pid);
+                        A11yTree::default()
+                    } else {
+                        screen_reader.tree(&context_mgr)
+                    };
+                // the token is the context's secret
+                tree.token = [0; 4];
+                // the reply has to fit in the page that was lent
+                while buffer.replace(tree.clone()).is_err() {
+                    if !reader::shrink(&mut tree) {
+                        buffer.replace(A11yTree::default()).unwrap();
+                        break;
+                    }
+                }
+            }
+            Some(Opcode::ReaderCommand) => msg_blocking_scalar_unpack!(msg, command, arg, _, _, {
+                let was_active = screen_reader.is_active();
+                let speaks = match FromPrimitive::from_usize(command) {
+                    Some(command) => screen_reader.command(command, arg, &context_mgr),
+                    None => {
+                        log::warn!("unknown screen reader command {}", command);
+                        screen_reader.command(ReaderCommand::Status, 0, &context_mgr)
+                    }
+                };
+                if !was_active && screen_reader.is_active() {
+                    // drawn text isn't kept until someone reads it, so have the screen drawn again
+                    context_mgr.redraw().ok();
+                }
+                xous::return_scalar(msg.sender, speaks as usize).ok();
+            }),
+            Some(Opcode::RegisterAccessibilityListener) => {
+                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let listener = buffer.to_original::<AccessibilityListener, _>().unwrap();
+                let pid = msg.sender.pid().map(|pid| pid.get() as u32).unwrap_or_default();
+                held_listeners.push((pid, listener));
+            }
             #[cfg(feature = "unsafe-app-loading")]
             Some(Opcode::RegisterName) => {
                 let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
@@ -785,12 +955,52 @@ fn wrapped_main() -> ! {
                 gfx.set_devboot(true).ok(); // indicate to users that we are no longer in a codebase that is exclusively trusted code
                 context_mgr.register_name(registration.name.to_str(), &registration.auth_token);
             }
+            #[cfg(feature = "unsafe-app-loading")]
+            Some(Opcode::GrantCapabilities) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut grant = buffer.to_original::<CapabilityGrant, _>().unwrap();
+                grant.granted = context_mgr.grant_capabilities(
+                    &grant.name,
+                    grant.capabilities.clone(),
+                    &grant.auth_token,
+                );
+                // the process is known as the app from its first message, before it registers a UX
+                if grant.granted {
+                    app_pids
+                        .lock()
+                        .unwrap()
+                        .insert(grant.pid, (grant.name.to_string(), grant.capabilities.clone()));
+                }
+                buffer.replace(grant).unwrap();
+            }
             Some(Opcode::Quit) => break,
             None => {
                 log::error!("unhandled message {:?}", msg);
             }
         }
 
+        // accessibility listeners read whatever the focused app shows, so only services in the boot image
+        // may listen, and whether a process is one is only known once the apps have all registered
+        if !held_listeners.is_empty() && booted.load(Ordering::SeqCst) {
+            for (pid, listener) in held_listeners.drain(..) {
+                if app_pids.lock().unwrap().contains_key(&pid) {
+                    log::warn!("PID {} may not listen to the screen reader", pid);
+                    continue;
+                }
+                match xous::connect(xous::SID::from_array(listener.sid)) {
+                    Ok(cid) => {
+                        let was_active = screen_reader.is_active();
+                        screen_reader.register_listener(cid, listener.opcode);
+                        if !was_active {
+                            context_mgr.redraw().ok();
+                        }
+                    }
+                    Err(e) => log::warn!("couldn't connect to accessibility listener: {:?}", e),
+                }
+            }
+        }
+
         // we don't currently have a mechanism to guarantee that the default app has focus
         // right now, it just depends upon it requesting focus, and none others taking it.
         // probably something should be inserted around here to take care of that?
@@ -802,3 +1012,27 @@ fn wrapped_main() -> ! {
     log::trace!("quitting");
     xous::terminate_process(0)
 }
+
+/// Services check callers while the main loop may be busy, even waiting on one of those services (text to
+/// speech plays through the codec), so identities are served from here.
+fn app_identity_server(app_pids: Arc<Mutex<HashMap<u32, (String, Vec<String>)>>>, booted: Arc<AtomicBool>) {
+    let xns = xous_names::XousNames::new().unwrap();
+    let sid = xns.register_name(api::SERVER_NAME_GAM_APPS, None).expect("can't register server");
+    loop {
+        let mut msg = xous::receive_message(sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::AppIdentity) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut identity = buffer.to_original::<AppIdentity, _>().unwrap();
+                if let Some((app, capabilities)) = app_pids.lock().unwrap().get(&identity.pid) {
+                    identity.app = Some(app.to_string());
+                    identity.capabilities = capabilities.clone();
+                }
+                identity.booted = booted.load(Ordering::SeqCst);
+                buffer.replace(identity).unwrap();
+            }
+            _ => log::error!("unhandled message {:?}", msg),
+        }
+    }
+}
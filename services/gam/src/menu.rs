@@ -204,6 +204,18 @@ impl<'a> Menu<'a> {
         if len_before > self.items.len() { true } else { false }
     }
 
+    /// Renames an item in place, e.g. to show it in another language. The item keeps its position and
+    /// action; returns false if there's no item called `item`.
+    pub fn rename_item(&mut self, item: &str, new_name: &str) -> bool {
+        match self.items.iter_mut().find(|candidate| candidate.name.as_str() == item) {
+            Some(candidate) => {
+                candidate.name = String::from(new_name);
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn draw_item(&self, index: i16, with_marker: bool) {
         use core::fmt::Write;
         let canvas_size = self.gam.get_canvas_bounds(self.canvas).unwrap();
@@ -285,6 +297,7 @@ impl<'a> Menu<'a> {
 
             // NOTE: if we bring back the dividers, we will need to add them to this edge case here as well.
         }
+        self.publish_accessibility();
     }
 
     pub fn next_item(&mut self) {
@@ -311,6 +324,16 @@ impl<'a> Menu<'a> {
 
             // NOTE: if we bring back the dividers, we will need to add them to this edge case here as well.
         }
+        self.publish_accessibility();
+    }
+
+    /// Tells the screen reader about the items, with the selected one focused.
+    fn publish_accessibility(&self) {
+        let mut nodes = vec![A11yNode::new(A11yRole::Menu, &self.name)];
+        nodes.extend(
+            self.items.iter().map(|item| A11yNode::new(A11yRole::MenuItem, item.name.as_str()).nested(1)),
+        );
+        self.gam.publish_accessibility(self.authtoken, nodes, Some(self.index + 1)).ok();
     }
 
     pub fn redraw(&mut self) {
@@ -346,6 +369,7 @@ impl<'a> Menu<'a> {
                 self.draw_divider(cur_index as _);
             }
         }
+        self.publish_accessibility();
         log::trace!("menu redraw##");
         self.gam.redraw().unwrap();
     }
@@ -464,6 +488,24 @@ impl MenuMatic {
         if ret.op == MenuMgrOp::Ok { true } else { false }
     }
 
+    pub fn rename_item(&self, item_name: &str, new_name: &str) -> bool {
+        let mm = MenuManagement {
+            item: MenuItem {
+                name: String::from(item_name),
+                // the rest are ignored
+                action_conn: None,
+                action_opcode: 0,
+                action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
+                close_on_select: false,
+            },
+            op: MenuMgrOp::RenameItem(String::from(new_name)),
+        };
+        let mut buf = Buffer::into_buf(mm).expect("Couldn't convert to memory structure");
+        buf.lend_mut(self.cid, 0).expect("Couldn't issue management opcode");
+        let ret = buf.to_original::<MenuManagement, _>().unwrap();
+        if ret.op == MenuMgrOp::Ok { true } else { false }
+    }
+
     pub fn set_index(&self, index: usize) {
         let op = MenuManagement {
             item: MenuItem {
@@ -583,6 +625,14 @@ pub fn menu_matic(
                             }
                             buffer.replace(mgmt).unwrap();
                         }
+                        MenuMgrOp::RenameItem(ref new_name) => {
+                            if !menu.lock().unwrap().rename_item(mgmt.item.name.as_str(), new_name.as_str()) {
+                                mgmt.op = MenuMgrOp::Err;
+                            } else {
+                                mgmt.op = MenuMgrOp::Ok;
+                            }
+                            buffer.replace(mgmt).unwrap();
+                        }
                         MenuMgrOp::SetIndex(index) => {
                             log::info!("setting menu index {}", index);
                             menu.lock().unwrap().set_index(index);
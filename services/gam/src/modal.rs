@@ -56,6 +56,9 @@ pub trait ActionApi {
     /// navigation is one of '∴' | '←' | '→' | '↑' | '↓'
     fn key_action(&mut self, _key: char) -> Option<ValidatorErr> { None }
     fn set_action_opcode(&mut self, _op: u32) {}
+    /// what the action shows, for the screen reader, and the index of the node keys act on. Leave out
+    /// anything the user is typing in secret.
+    fn a11y_nodes(&self) -> (Vec<A11yNode>, Option<usize>) { (Vec::new(), None) }
 }
 
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive)]
@@ -368,7 +371,7 @@ impl<'a> Modal<'a> {
         log::debug!("requesting content canvas for modal");
         let canvas =
             gam.request_content_canvas(authtoken.unwrap()).expect("couldn't get my content canvas from GAM");
-        let line_height = if locales::LANG == "zh" {
+        let line_height = if locales::lang() == "zh" {
             // zh has no "small" style
             gam.glyph_height_hint(GlyphStyle::Regular).expect("couldn't get glyph height hint") as i16
         } else {
@@ -555,10 +558,29 @@ impl<'a> Modal<'a> {
             self.bot_dirty = false;
         }
         log::trace!("total height: {}", cur_height);
+        self.publish_accessibility();
         log::trace!("modal redraw##");
         self.gam.redraw().unwrap();
     }
 
+    /// Tells the screen reader about the modal: the top text as its title, then the action's nodes,
+    /// then the bottom text.
+    fn publish_accessibility(&self) {
+        let title = self.top_text.as_ref().map(|tv| tv.to_str()).unwrap_or(self.name.as_str());
+        let mut nodes = vec![A11yNode::new(A11yRole::Dialog, title)];
+        let (action_nodes, action_focus) = self.action.a11y_nodes();
+        // with nothing in particular focused, the title is
+        let focus = Some(action_focus.map_or(0, |index| index + 1));
+        nodes.extend(action_nodes.into_iter().map(|node| {
+            let depth = node.depth + 1;
+            node.nested(depth)
+        }));
+        if let Some(tv) = self.bot_text.as_ref() {
+            nodes.push(A11yNode::new(A11yRole::Text, tv.to_str()));
+        }
+        self.gam.publish_accessibility(self.authtoken, nodes, focus).ok();
+    }
+
     pub fn key_event(&mut self, keys: [char; 4]) {
         for &k in keys.iter() {
             if k != '\u{0}' {
@@ -42,6 +42,19 @@ impl CheckBoxes {
 impl ActionApi for CheckBoxes {
     fn set_action_opcode(&mut self, op: u32) { self.action_opcode = op }
 
+    fn a11y_nodes(&self) -> (Vec<A11yNode>, Option<usize>) {
+        let mut nodes: Vec<A11yNode> = self
+            .items
+            .iter()
+            .map(|item| {
+                A11yNode::new(A11yRole::CheckBox, item.as_str())
+                    .with_checked(self.action_payload.contains(item.as_str()))
+            })
+            .collect();
+        nodes.push(A11yNode::new(A11yRole::Button, t!("a11y.okay", locales::lang())));
+        (nodes, Some(self.select_index.max(0) as usize))
+    }
+
     fn height(&self, glyph_height: i16, margin: i16, _modal: &Modal) -> i16 {
         // account for hard line-breaks in items
         let line_break_count =
@@ -129,7 +142,7 @@ impl ActionApi for CheckBoxes {
             modal.gam.post_textview(&mut tv).expect("couldn't post tv");
             #[cfg(feature = "tts")]
             {
-                self.tts.tts_blocking(t!("checkbox.select_and_close_tts", locales::LANG)).unwrap();
+                self.tts.tts_blocking(t!("checkbox.select_and_close_tts", locales::lang())).unwrap();
                 for item in self.action_payload.clone().payload().iter() {
                     if let Some(name) = item {
                         self.tts.tts_blocking(name.as_str()).unwrap();
@@ -144,7 +157,7 @@ impl ActionApi for CheckBoxes {
             Point::new(text_x, cur_y),
             Point::new(modal.canvas_width - modal.margin, cur_y + modal.line_height),
         ));
-        write!(tv, "{}", t!("radio.select_and_close", locales::LANG)).unwrap();
+        write!(tv, "{}", t!("radio.select_and_close", locales::lang())).unwrap();
         modal.gam.post_textview(&mut tv).expect("couldn't post tv");
 
         // divider lines
@@ -185,7 +198,7 @@ impl ActionApi for CheckBoxes {
                         self.action_payload.remove(item_name);
                         #[cfg(feature = "tts")]
                         {
-                            self.tts.tts_blocking(t!("checkbox.uncheck", locales::LANG)).unwrap();
+                            self.tts.tts_blocking(t!("checkbox.uncheck", locales::lang())).unwrap();
                             self.tts.tts_blocking(item_name).unwrap();
                         }
                     } else {
@@ -198,7 +211,7 @@ impl ActionApi for CheckBoxes {
                         } else {
                             #[cfg(feature = "tts")]
                             {
-                                self.tts.tts_blocking(t!("checkbox.check", locales::LANG)).unwrap();
+                                self.tts.tts_blocking(t!("checkbox.check", locales::lang())).unwrap();
                                 self.tts.tts_blocking(item_name).unwrap();
                             }
                         }
@@ -31,6 +31,10 @@ impl ConsoleInput {
 impl ActionApi for ConsoleInput {
     fn set_action_opcode(&mut self, op: u32) { self.action_opcode = op }
 
+    fn a11y_nodes(&self) -> (Vec<A11yNode>, Option<usize>) {
+        (vec![A11yNode::new(A11yRole::TextEntry, "").with_value(self.action_payload.as_str())], Some(0))
+    }
+
     fn height(&self, _glyph_height: i16, margin: i16, _modal: &Modal) -> i16 { margin }
 
     fn redraw(&self, _at_height: i16, _modal: &Modal) {
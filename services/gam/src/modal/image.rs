@@ -26,6 +26,10 @@ impl Image {
 impl ActionApi for Image {
     fn set_action_opcode(&mut self, op: u32) { self.action_opcode = op }
 
+    fn a11y_nodes(&self) -> (Vec<A11yNode>, Option<usize>) {
+        (vec![A11yNode::new(A11yRole::Image, "")], None)
+    }
+
     fn height(&self, _glyph_height: i16, margin: i16, _modal: &Modal) -> i16 {
         let bm_height = match &self.bitmap {
             Some(bm) => bm.bound.br.y - bm.bound.tl.y,
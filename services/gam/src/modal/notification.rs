@@ -46,7 +46,7 @@ impl Notification {
             Some(setting) => {
                 let qrcode = match QrCode::new(setting) {
                     Ok(code) => code,
-                    Err(_e) => QrCode::new(t!("notification.qrcode.error", locales::LANG)).unwrap(),
+                    Err(_e) => QrCode::new(t!("notification.qrcode.error", locales::lang())).unwrap(),
                 };
                 self.qrwidth = qrcode.width();
                 log::info!("qrcode {}x{} : {} bytes ", self.qrwidth, self.qrwidth, setting.len());
@@ -80,7 +80,7 @@ impl Notification {
             Point::new(modal.margin, at_height + modal.margin * 2),
             (modal.canvas_width - modal.margin * 2) as u16,
         );
-        write!(tv, "{}", t!("notification.dismiss", locales::LANG)).unwrap();
+        write!(tv, "{}", t!("notification.dismiss", locales::lang())).unwrap();
         modal.gam.bounds_compute_textview(&mut tv).expect("couldn't simulate text size");
         let textwidth = if let Some(bounds) = tv.bounds_computed {
             bounds.br.x - bounds.tl.x
@@ -132,6 +132,21 @@ impl Notification {
 impl ActionApi for Notification {
     fn set_action_opcode(&mut self, op: u32) { self.action_opcode = op }
 
+    fn a11y_nodes(&self) -> (Vec<A11yNode>, Option<usize>) {
+        let mut nodes = Vec::new();
+        if self.qrwidth > 0 {
+            nodes.push(A11yNode::new(A11yRole::Image, t!("a11y.qrcode", locales::lang())));
+        }
+        if self.manual_dismiss {
+            let dismiss = t!("notification.dismiss", locales::lang());
+            nodes.push(A11yNode::new(
+                A11yRole::Text,
+                dismiss.trim_matches(|c| c == '[' || c == ']' || c == ' '),
+            ));
+        }
+        (nodes, None)
+    }
+
     fn height(&self, glyph_height: i16, margin: i16, _modal: &Modal) -> i16 {
         if self.manual_dismiss {
             let qr_height = if self.qrwidth > 0 { 300 } else { 0 };
@@ -53,6 +53,20 @@ impl RadioButtons {
 impl ActionApi for RadioButtons {
     fn set_action_opcode(&mut self, op: u32) { self.action_opcode = op }
 
+    fn a11y_nodes(&self) -> (Vec<A11yNode>, Option<usize>) {
+        let mut nodes: Vec<A11yNode> = self
+            .items
+            .iter()
+            .map(|item| {
+                A11yNode::new(A11yRole::RadioButton, item.as_str())
+                    .with_checked(item.as_str() == self.action_payload.as_str())
+            })
+            .collect();
+        // the cursor goes one past the items to accept the selection
+        nodes.push(A11yNode::new(A11yRole::Button, t!("a11y.okay", locales::lang())));
+        (nodes, Some(self.select_index.max(0) as usize))
+    }
+
     fn height(&self, glyph_height: i16, margin: i16, _modal: &Modal) -> i16 {
         // total items, then +1 for the "Okay" message
         (self.items.len() as i16 + 1) * glyph_height + margin * 2 + margin * 2 + 5 // +4 for some bottom margin slop
@@ -134,7 +148,7 @@ impl ActionApi for RadioButtons {
             modal.gam.post_textview(&mut tv).expect("couldn't post tv");
             #[cfg(feature = "tts")]
             {
-                self.tts.tts_blocking(t!("radio.select_and_close_tts", locales::LANG)).unwrap();
+                self.tts.tts_blocking(t!("radio.select_and_close_tts", locales::lang())).unwrap();
                 self.tts.tts_blocking(self.action_payload.as_str()).unwrap();
             }
         }
@@ -145,7 +159,7 @@ impl ActionApi for RadioButtons {
             Point::new(text_x, cur_y),
             Point::new(modal.canvas_width - modal.margin, cur_y + modal.line_height),
         ));
-        write!(tv, "{}", t!("radio.select_and_close", locales::LANG)).unwrap();
+        write!(tv, "{}", t!("radio.select_and_close", locales::lang())).unwrap();
         modal.gam.post_textview(&mut tv).expect("couldn't post tv");
 
         // divider lines
@@ -185,7 +199,7 @@ impl ActionApi for RadioButtons {
                         RadioButtonPayload::new(self.items[self.select_index as usize].as_str());
                     #[cfg(feature = "tts")]
                     {
-                        self.tts.tts_blocking(t!("radio.selection_tts", locales::LANG)).unwrap();
+                        self.tts.tts_blocking(t!("radio.selection_tts", locales::lang())).unwrap();
                         self.tts.tts_simple(self.items[self.select_index as usize].as_str()).unwrap();
                     }
                 } else {
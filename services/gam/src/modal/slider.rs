@@ -96,6 +96,12 @@ impl ActionApi for Slider {
 
     fn set_action_opcode(&mut self, op: u32) { self.action_opcode = op }
 
+    fn a11y_nodes(&self) -> (Vec<A11yNode>, Option<usize>) {
+        let units = std::str::from_utf8(&self.units[..self.units_len]).unwrap_or("");
+        let role = if self.is_progressbar { A11yRole::ProgressBar } else { A11yRole::Slider };
+        (vec![A11yNode::new(role, "").with_value(&format!("{}{}", self.action_payload, units))], Some(0))
+    }
+
     fn redraw(&self, at_height: i16, modal: &Modal) {
         let color = if self.is_password { PixelColor::Light } else { PixelColor::Dark };
         let fill_color = if self.is_password { PixelColor::Dark } else { PixelColor::Light };
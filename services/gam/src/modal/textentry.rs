@@ -135,6 +135,20 @@ impl TextEntry {
 impl ActionApi for TextEntry {
     fn set_action_opcode(&mut self, op: u32) { self.action_opcode = op }
 
+    fn a11y_nodes(&self) -> (Vec<A11yNode>, Option<usize>) {
+        // anything that isn't shown in full on screen isn't read out either
+        let secret = self.is_password || !matches!(self.visibility, TextEntryVisibility::Visible);
+        let nodes = self
+            .action_payloads
+            .iter()
+            .map(|payload| {
+                let node = A11yNode::new(A11yRole::TextEntry, payload.placeholder.as_deref().unwrap_or(""));
+                if secret { node } else { node.with_value(payload.as_str()) }
+            })
+            .collect();
+        (nodes, Some(self.selected_field.max(0) as usize))
+    }
+
     fn is_password(&self) -> bool { self.is_password }
 
     /// The total canvas height is computed with this API call
@@ -684,7 +698,7 @@ impl ActionApi for TextEntry {
                 {
                     let xns = xous_names::XousNames::new().unwrap();
                     let tts = tts_frontend::TtsFrontend::new(&xns).unwrap();
-                    tts.tts_blocking(locales::t!("input.delete-tts", locales::LANG)).unwrap();
+                    tts.tts_blocking(locales::t!("input.delete-tts", locales::lang())).unwrap();
                 }
                 if payload.placeholder_persist && payload.placeholder.is_some() && payload.content.len() == 0
                 {
@@ -0,0 +1,333 @@
+//! The screen reader. Menus, modals and apps publish accessibility trees describing what they show; for
+//! contexts that don't, the text they draw stands in. A cursor moves through the focused context's tree,
+//! either from reader commands (F4 followed by an arrow key, when speech is on) or from a braille display,
+//! and everything it lands on is spoken and passed on to accessibility listeners.
+
+use std::collections::HashMap;
+
+use graphics_server::Gid;
+use locales::t;
+
+use crate::api::*;
+use crate::contexts::ContextManager;
+
+/// How many lines of drawn text are kept per canvas, for contexts without a tree of their own
+const MAX_SCREEN_LINES: usize = 32;
+/// F4: the key after which the next one is a reader command
+const READER_KEY: char = '\u{14}';
+
+pub(crate) struct ScreenReader {
+    speaks: bool,
+    trees: HashMap<[u32; 4], A11yTree>,
+    screen_text: HashMap<Gid, Vec<String>>,
+    /// set when drawn text changed since listeners were last told
+    screen_dirty: bool,
+    cursor: Option<usize>,
+    /// the context, and its focus, that the cursor was last put on
+    following: Option<([u32; 4], Option<u16>)>,
+    /// set after the reader key, when the next key is a command
+    command_pending: bool,
+    listeners: Vec<(xous::CID, u32)>,
+    #[cfg(feature = "tts")]
+    tts: Option<tts_frontend::TtsFrontend>,
+}
+
+impl ScreenReader {
+    pub(crate) fn new(speaks: bool) -> Self {
+        ScreenReader {
+            speaks,
+            trees: HashMap::new(),
+            screen_text: HashMap::new(),
+            screen_dirty: false,
+            cursor: None,
+            following: None,
+            command_pending: false,
+            listeners: Vec::new(),
+            #[cfg(feature = "tts")]
+            tts: None,
+        }
+    }
+
+    /// Whether anyone is reading; text isn't kept otherwise.
+    pub(crate) fn is_active(&self) -> bool { self.speaks || !self.listeners.is_empty() }
+
+    pub(crate) fn register_listener(&mut self, cid: xous::CID, opcode: u32) {
+        self.listeners.push((cid, opcode));
+    }
+
+    pub(crate) fn publish(&mut self, tree: A11yTree, ctx: &ContextManager) {
+        let focused = ctx.focused_app() == Some(tree.token);
+        self.trees.insert(tree.token, tree);
+        if focused {
+            self.follow(ctx, true);
+        }
+    }
+
+    /// Notes text drawn on `gid`, in the order it first appears.
+    pub(crate) fn text_drawn(&mut self, gid: Gid, text: &str) {
+        if !self.is_active() {
+            return;
+        }
+        let lines = self.screen_text.entry(gid).or_default();
+        if record_lines(lines, text) {
+            self.screen_dirty = true;
+        }
+    }
+
+    pub(crate) fn canvas_cleared(&mut self, gid: Gid) {
+        if self.screen_text.remove(&gid).is_some() {
+            self.screen_dirty = true;
+        }
+    }
+
+    /// The focused context's tree, focused on the cursor.
+    pub(crate) fn tree(&self, ctx: &ContextManager) -> A11yTree {
+        let mut tree = self.focused_tree(ctx);
+        tree.focus = self.cursor.filter(|&c| c < tree.nodes.len()).map(|c| c as u16);
+        tree
+    }
+
+    fn focused_tree(&self, ctx: &ContextManager) -> A11yTree {
+        let token = match ctx.focused_app() {
+            Some(token) => token,
+            None => return A11yTree::default(),
+        };
+        if let Some(tree) = self.trees.get(&token) {
+            return tree.clone();
+        }
+        let name = ctx.context_name(token).unwrap_or_default();
+        let mut nodes = vec![A11yNode::new(A11yRole::Window, &name)];
+        for gid in ctx.focused_canvases() {
+            if let Some(lines) = self.screen_text.get(&gid) {
+                nodes.extend(lines.iter().map(|line| A11yNode::new(A11yRole::Text, line).nested(1)));
+            }
+        }
+        A11yTree { token, nodes, focus: None }
+    }
+
+    /// Puts the cursor on the focused context's focus when either has changed, announcing a new context.
+    /// Listeners are told about moves, and about anything else if `changed` is set.
+    pub(crate) fn follow(&mut self, ctx: &ContextManager, changed: bool) {
+        let changed = changed || core::mem::take(&mut self.screen_dirty);
+        let token = match ctx.focused_app() {
+            Some(token) => token,
+            None => return,
+        };
+        let tree = self.focused_tree(ctx);
+        let context_changed = self.following.map(|(t, _)| t) != Some(token);
+        if context_changed || self.following != Some((token, tree.focus)) {
+            self.following = Some((token, tree.focus));
+            self.cursor =
+                step(None, ReaderCommand::Route, tree.focus.unwrap_or(0) as usize, tree.nodes.len());
+            if context_changed {
+                // what's focused inside speaks for itself as it's drawn; say where we are
+                if let Some(title) = tree.nodes.first() {
+                    self.say(&title.spoken());
+                }
+            }
+        } else if !changed {
+            return;
+        }
+        self.cursor = self.cursor.filter(|&c| c < tree.nodes.len());
+        self.notify(tree.nodes.len());
+    }
+
+    /// Runs `command` on the focused context; returns whether the reader speaks.
+    pub(crate) fn command(&mut self, command: ReaderCommand, arg: usize, ctx: &ContextManager) -> bool {
+        let tree = self.focused_tree(ctx);
+        match command {
+            ReaderCommand::Status => (),
+            ReaderCommand::Speak => {
+                self.speaks = true;
+                self.say(t!("a11y.reader_on", locales::lang()));
+            }
+            ReaderCommand::Silence => {
+                self.say(t!("a11y.reader_off", locales::lang()));
+                self.speaks = false;
+            }
+            ReaderCommand::ReadCurrent => match self.cursor.and_then(|c| tree.nodes.get(c)) {
+                Some(node) => self.say(&node.spoken()),
+                None => self.say(t!("a11y.nothing", locales::lang())),
+            },
+            ReaderCommand::ReadAll => {
+                let from = self.cursor.unwrap_or(0).min(tree.nodes.len());
+                let all = tree.nodes[from..].iter().map(|node| node.spoken()).collect::<Vec<String>>();
+                if all.is_empty() {
+                    self.say(t!("a11y.nothing", locales::lang()));
+                } else {
+                    self.say(&all.join(". "));
+                }
+            }
+            _ => {
+                self.cursor = step(self.cursor, command, arg, tree.nodes.len());
+                match self.cursor.and_then(|c| tree.nodes.get(c)) {
+                    Some(node) => self.say(&node.spoken()),
+                    None => self.say(t!("a11y.nothing", locales::lang())),
+                }
+                self.notify(tree.nodes.len());
+            }
+        }
+        self.speaks
+    }
+
+    /// Takes out the keys that are reader commands, when the reader speaks, and returns the rest. The
+    /// reader key followed by ← or → moves back or forward, ↑ goes to the top, ↓ reads everything from the
+    /// cursor on, and the select key reads what's under the cursor. Pressing the reader key twice sends it
+    /// on to the app.
+    pub(crate) fn key_event(&mut self, keys: [char; 4], ctx: &ContextManager) -> [char; 4] {
+        if !self.speaks {
+            return keys;
+        }
+        let mut passed = ['\u{0000}'; 4];
+        let mut count = 0;
+        for &k in keys.iter().filter(|&&k| k != '\u{0000}') {
+            if core::mem::take(&mut self.command_pending) {
+                let command = match k {
+                    '←' => Some(ReaderCommand::Previous),
+                    '→' => Some(ReaderCommand::Next),
+                    '↑' => Some(ReaderCommand::First),
+                    '↓' => Some(ReaderCommand::ReadAll),
+                    '∴' => Some(ReaderCommand::ReadCurrent),
+                    _ => None,
+                };
+                if let Some(command) = command {
+                    self.command(command, 0, ctx);
+                    continue;
+                }
+            } else if k == READER_KEY {
+                self.command_pending = true;
+                continue;
+            }
+            passed[count] = k;
+            count += 1;
+        }
+        passed
+    }
+
+    fn say(&mut self, text: &str) {
+        if !self.speaks || text.is_empty() {
+            return;
+        }
+        #[cfg(feature = "tts")]
+        {
+            let tts = self.tts.get_or_insert_with(|| {
+                tts_frontend::TtsFrontend::new(&xous_names::XousNames::new().unwrap())
+                    .expect("couldn't connect to the TTS server")
+            });
+            tts.tts_simple(text).ok();
+        }
+        #[cfg(not(feature = "tts"))]
+        log::info!("screen reader: {}", text);
+    }
+
+    fn notify(&mut self, nodes: usize) {
+        let cursor = self.cursor.unwrap_or(usize::MAX);
+        // listeners that went away are forgotten
+        self.listeners.retain(|&(cid, opcode)| {
+            match xous::try_send_message(cid, xous::Message::new_scalar(opcode as usize, cursor, nodes, 0, 0))
+            {
+                Ok(_) => true,
+                Err(xous::Error::ServerNotFound) => false,
+                Err(e) => {
+                    log::warn!("couldn't tell an accessibility listener about a change: {:?}", e);
+                    true
+                }
+            }
+        });
+    }
+}
+
+/// Where the cursor goes for a movement command, in a tree of `len` nodes. Moves stop at either end.
+fn step(cursor: Option<usize>, command: ReaderCommand, arg: usize, len: usize) -> Option<usize> {
+    let last = len.checked_sub(1)?;
+    match command {
+        ReaderCommand::Previous => Some(cursor.map_or(last, |c| c.saturating_sub(1)).min(last)),
+        ReaderCommand::Next => Some(cursor.map_or(0, |c| c + 1).min(last)),
+        ReaderCommand::First => Some(0),
+        ReaderCommand::Last => Some(last),
+        ReaderCommand::Route => Some(arg.min(last)),
+        _ => cursor.filter(|&c| c <= last),
+    }
+}
+
+/// Adds the lines of `text` that aren't in `lines` yet, dropping the oldest past `MAX_SCREEN_LINES`.
+/// Returns whether anything was added.
+fn record_lines(lines: &mut Vec<String>, text: &str) -> bool {
+    let mut added = false;
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if !lines.iter().any(|l| l == line) {
+            lines.push(String::from(line));
+            added = true;
+        }
+    }
+    if lines.len() > MAX_SCREEN_LINES {
+        lines.drain(..lines.len() - MAX_SCREEN_LINES);
+    }
+    added
+}
+
+/// Takes the node farthest from the focus out of `tree`, for trees too big to send. Returns false if
+/// there's nothing left to take.
+pub(crate) fn shrink(tree: &mut A11yTree) -> bool {
+    if tree.nodes.len() <= 1 {
+        return false;
+    }
+    let focus = tree.focus.map_or(0, |f| f as usize);
+    if tree.nodes.len() - 1 - focus >= focus {
+        tree.nodes.pop();
+    } else {
+        tree.nodes.remove(0);
+        tree.focus = tree.focus.map(|f| f - 1);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step() {
+        assert_eq!(step(None, ReaderCommand::Next, 0, 3), Some(0));
+        assert_eq!(step(Some(1), ReaderCommand::Next, 0, 3), Some(2));
+        assert_eq!(step(Some(2), ReaderCommand::Next, 0, 3), Some(2));
+        assert_eq!(step(None, ReaderCommand::Previous, 0, 3), Some(2));
+        assert_eq!(step(Some(0), ReaderCommand::Previous, 0, 3), Some(0));
+        assert_eq!(step(Some(1), ReaderCommand::Last, 0, 3), Some(2));
+        assert_eq!(step(Some(1), ReaderCommand::Route, 7, 3), Some(2));
+        assert_eq!(step(Some(5), ReaderCommand::ReadCurrent, 0, 3), None);
+        assert_eq!(step(Some(0), ReaderCommand::Next, 0, 0), None);
+    }
+
+    #[test]
+    fn test_record_lines() {
+        let mut lines = Vec::new();
+        assert!(record_lines(&mut lines, "Inbox\n  3 new  \n\n"));
+        assert!(!record_lines(&mut lines, "3 new"));
+        assert_eq!(lines, vec!["Inbox", "3 new"]);
+        for i in 0..MAX_SCREEN_LINES {
+            record_lines(&mut lines, &format!("line {}", i));
+        }
+        assert_eq!(lines.len(), MAX_SCREEN_LINES);
+        assert_eq!(lines[0], "line 0");
+    }
+
+    #[test]
+    fn test_shrink() {
+        let nodes = (0..5).map(|i| A11yNode::new(A11yRole::Text, &i.to_string())).collect();
+        let mut tree = A11yTree { token: [0; 4], nodes, focus: Some(3) };
+        // the node farthest from the focus goes first, and the focus stays on the same node
+        assert!(shrink(&mut tree));
+        assert_eq!(tree.nodes.len(), 4);
+        assert_eq!(tree.nodes[0].label, "1");
+        assert_eq!(tree.focused().unwrap().label, "3");
+        assert!(shrink(&mut tree));
+        assert!(shrink(&mut tree));
+        assert_eq!(tree.nodes.len(), 2);
+        assert_eq!(tree.nodes[1].label, "3");
+        assert_eq!(tree.focused().unwrap().label, "3");
+        while shrink(&mut tree) {}
+        assert_eq!(tree.nodes.len(), 1);
+        assert_eq!(tree.focused().unwrap().label, "3");
+    }
+}
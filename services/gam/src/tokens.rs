@@ -2,7 +2,7 @@ use std::cell::RefCell;
 
 #[cfg(feature = "cramium-soc")]
 use cram_hal_service::trng;
-use gam::{EXPECTED_APP_CONTEXTS, EXPECTED_BOOT_CONTEXTS};
+use gam::{APP_CAPABILITIES, EXPECTED_APP_CONTEXTS, EXPECTED_BOOT_CONTEXTS};
 
 /*
     Authentication tokens to the GAM are created on a first-come, first-serve basis,
@@ -24,6 +24,9 @@ pub(crate) struct TokenManager {
     tokens: Vec<NamedToken>,
     #[cfg(feature = "unsafe-app-loading")]
     extra_names: Vec<String>,
+    /// capabilities of apps named in `extra_names`, as set by the app that registered them
+    #[cfg(feature = "unsafe-app-loading")]
+    extra_capabilities: Vec<(String, Vec<String>)>,
     trng: trng::Trng,
     tt: ticktimer_server::Ticktimer,
     last_time: RefCell<u64>,
@@ -36,6 +39,8 @@ impl<'a> TokenManager {
             tokens: Vec::new(),
             #[cfg(feature = "unsafe-app-loading")]
             extra_names: Vec::new(),
+            #[cfg(feature = "unsafe-app-loading")]
+            extra_capabilities: Vec::new(),
             trng: trng::Trng::new(&xns).unwrap(),
             tt: ticktimer_server::Ticktimer::new().unwrap(),
             last_time: RefCell::new(0),
@@ -138,6 +143,19 @@ impl<'a> TokenManager {
         None
     }
 
+    /// The capabilities declared for the app called `name`, or `None` if `name` isn't an app (this includes
+    /// the names of app submenus, which belong to the same process as their app).
+    pub(crate) fn capabilities(&self, name: &str) -> Option<Vec<String>> {
+        if let Some((_, capabilities)) = APP_CAPABILITIES.iter().find(|(app, _)| *app == name) {
+            return Some(capabilities.iter().map(|c| c.to_string()).collect());
+        }
+        #[cfg(feature = "unsafe-app-loading")]
+        if let Some((_, capabilities)) = self.extra_capabilities.iter().find(|(app, _)| app == name) {
+            return Some(capabilities.clone());
+        }
+        None
+    }
+
     /// Register a new name that can then claim a token. Note that only pre-registered applications are
     /// allowed to do this.
     #[cfg(feature = "unsafe-app-loading")]
@@ -157,4 +175,43 @@ impl<'a> TokenManager {
             log::error!("Token {:?} does not correspond with a name", auth_token);
         }
     }
+
+    /// Sets the capabilities of a name registered with `register_name()`. Only pre-registered applications
+    /// are allowed to do this, and only for names that haven't claimed a token yet: once the app is running,
+    /// what it may do is fixed. Returns whether the capabilities were set.
+    #[cfg(feature = "unsafe-app-loading")]
+    pub(crate) fn grant_capabilities(
+        &mut self,
+        name: &str,
+        capabilities: Vec<String>,
+        auth_token: &[u32; 4],
+    ) -> bool {
+        let registrant = match self.lookup_name(auth_token) {
+            Some(registrant) => registrant,
+            None => {
+                log::error!("Token {:?} does not correspond with a name", auth_token);
+                return false;
+            }
+        };
+        if EXPECTED_BOOT_CONTEXTS.iter().find(|&&context| context == registrant).is_none()
+            && EXPECTED_APP_CONTEXTS.iter().find(|&&context| context == registrant).is_none()
+        {
+            log::error!(
+                "`{}' does not have permission to grant capabilities because it is not pre-registered",
+                registrant
+            );
+            false
+        } else if self.extra_names.iter().find(|&context| context == name).is_none() {
+            log::error!("Can't grant capabilities to `{}', it was not registered as a name", name);
+            false
+        } else if self.tokens.iter().find(|&namedtoken| namedtoken.name == name).is_some() {
+            log::error!("Can't grant capabilities to `{}', it is already running", name);
+            false
+        } else {
+            log::info!("`{}' granted {:?} by `{}'", name, capabilities, registrant);
+            self.extra_capabilities.retain(|(app, _)| app != name);
+            self.extra_capabilities.push((name.to_string(), capabilities));
+            true
+        }
+    }
 }
@@ -22,6 +22,8 @@ pub mod shapes;
 pub use shapes::*;
 pub mod text;
 pub use text::*;
+pub mod richtext;
+pub use richtext::*;
 pub mod glyphstyle;
 pub use glyphstyle::*;
 pub mod blitstr2;
@@ -121,6 +123,9 @@ pub(crate) enum Opcode {
     DrawBootLogo,
 
     Quit,
+
+    /// selects the glyph rules for a language, by its index in `locales::LANGUAGES`
+    SetLanguage,
 }
 
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
@@ -32,6 +32,12 @@ pub struct GlyphSprite {
     pub double: bool,
     // flag for 32-bit wide glyph sets
     pub large: bool,
+    // slant the glyph when drawing, as there are no italic fonts
+    pub italic: bool,
+    // underline the glyph, including its kerning so an underlined word has an unbroken line
+    pub underline: bool,
+    // the link the glyph belongs to, from the `TextSpan` it was set in
+    pub link: Option<u16>,
 }
 
 /// A TypesetWord is a Word that has beet turned into sprites and placed at a specific location on the canvas,
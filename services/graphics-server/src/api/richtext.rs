@@ -0,0 +1,359 @@
+use crate::api::{GlyphStyle, Point};
+
+/// A `TextView` and its spans are lent to the graphics server in a single page, next to up to
+/// `TEXTVIEW_LEN` bytes of text, so spans past this many are dropped.
+pub const MAX_TEXT_SPANS: usize = 24;
+/// Links drawn in more pieces than this are not all reported back.
+pub const MAX_LINK_REGIONS: usize = 16;
+
+/// A run of characters in a `TextView` drawn differently from the rest of it. Offsets count characters,
+/// not bytes, the same as `TextView::insertion`.
+#[derive(Debug, Copy, Clone, PartialEq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct TextSpan {
+    pub start: u32,
+    /// exclusive
+    pub end: u32,
+    /// `None` keeps the style of the `TextView`
+    pub style: Option<GlyphStyle>,
+    /// there is no italic font, so the glyphs are slanted when drawn
+    pub italic: bool,
+    pub underline: bool,
+    /// a number chosen by the caller; where the span was drawn comes back in `TextView::link_regions`
+    pub link: Option<u16>,
+}
+impl TextSpan {
+    pub fn new(start: usize, end: usize) -> Self {
+        TextSpan {
+            start: start as u32,
+            end: end as u32,
+            style: None,
+            italic: false,
+            underline: false,
+            link: None,
+        }
+    }
+
+    pub fn styled(start: usize, end: usize, style: GlyphStyle) -> Self {
+        TextSpan { style: Some(style), ..TextSpan::new(start, end) }
+    }
+
+    pub fn contains(&self, charpos: usize) -> bool {
+        (self.start as usize) <= charpos && charpos < (self.end as usize)
+    }
+}
+
+/// Where part of a link was drawn, in the same coordinates as `TextView::bounds_computed`. A link that
+/// wraps or is split by right-to-left text has one region per piece.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct LinkRegion {
+    pub link: u16,
+    pub tl: Point,
+    pub br: Point,
+}
+impl LinkRegion {
+    pub fn contains(&self, p: Point) -> bool {
+        p.x >= self.tl.x && p.x <= self.br.x && p.y >= self.tl.y && p.y <= self.br.y
+    }
+}
+
+/// Text and spans read from a small subset of markdown:
+///   - `**bold**`, `*italic*` or `_italic_`, and `` `monospace` ``
+///   - `[label](target)` and `<https://target>` links, which are underlined
+///   - `# headings` in bold, and `-`, `*` or `+` list items drawn with a bullet
+///   - a backslash before punctuation to keep it as it is
+///
+/// Emphasis ends with the line it started on, and a marker without a partner on that line is kept as text.
+#[derive(Debug, Clone, Default)]
+pub struct Markdown {
+    pub text: String,
+    pub spans: Vec<TextSpan>,
+    /// link targets, indexed by `TextSpan::link`
+    pub links: Vec<String>,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+struct Attributes {
+    bold: bool,
+    italic: bool,
+    mono: bool,
+    link: Option<u16>,
+}
+impl Attributes {
+    fn span(&self, start: usize, end: usize) -> TextSpan {
+        TextSpan {
+            style: if self.mono {
+                Some(GlyphStyle::Monospace)
+            } else if self.bold {
+                Some(GlyphStyle::Bold)
+            } else {
+                None
+            },
+            italic: self.italic,
+            underline: self.link.is_some(),
+            link: self.link,
+            ..TextSpan::new(start, end)
+        }
+    }
+}
+
+impl Markdown {
+    pub fn parse(src: &str) -> Self {
+        let mut md = MarkdownWriter::default();
+        for (i, line) in src.split('\n').enumerate() {
+            if i > 0 {
+                md.set(Attributes::default());
+                md.push('\n');
+            }
+            let indent = line.len() - line.trim_start_matches(' ').len();
+            let rest = &line[indent..];
+            let hashes = rest.len() - rest.trim_start_matches('#').len();
+            if (1..=6).contains(&hashes) && rest[hashes..].starts_with(' ') {
+                md.set(Attributes { bold: true, ..Default::default() });
+                md.inline(rest[hashes..].trim_start());
+            } else if rest.starts_with("- ") || rest.starts_with("* ") || rest.starts_with("+ ") {
+                md.push_str(&line[..indent]);
+                md.push_str("• ");
+                md.inline(rest[2..].trim_start());
+            } else {
+                md.inline(line);
+            }
+        }
+        md.finish()
+    }
+}
+
+#[derive(Default)]
+struct MarkdownWriter {
+    md: Markdown,
+    /// characters written so far
+    len: usize,
+    attributes: Attributes,
+    run_start: usize,
+}
+impl MarkdownWriter {
+    fn push(&mut self, ch: char) {
+        self.md.text.push(ch);
+        self.len += 1;
+    }
+
+    fn push_str(&mut self, s: &str) { s.chars().for_each(|ch| self.push(ch)); }
+
+    fn set(&mut self, attributes: Attributes) {
+        if attributes != self.attributes {
+            self.end_run();
+            self.attributes = attributes;
+            self.run_start = self.len;
+        }
+    }
+
+    fn end_run(&mut self) {
+        if self.len > self.run_start && self.attributes != Attributes::default() {
+            self.md.spans.push(self.attributes.span(self.run_start, self.len));
+        }
+    }
+
+    /// Emphasis, code and links within one line, starting from the attributes of the line.
+    fn inline(&mut self, line: &str) {
+        let line_attributes = self.attributes;
+        let chars: Vec<char> = line.chars().collect();
+        // the `]` ending a link label, and where to carry on after its target
+        let mut link_end: Option<(usize, usize)> = None;
+        let mut italic_marker: Option<char> = None;
+        let mut i = 0;
+        while i < chars.len() {
+            if let Some((end, after)) = link_end {
+                if i == end {
+                    self.set(Attributes { link: None, ..self.attributes });
+                    link_end = None;
+                    i = after;
+                    continue;
+                }
+            }
+            let ch = chars[i];
+            let next = chars.get(i + 1).copied();
+            match ch {
+                '\\' if next.is_some_and(|n| n.is_ascii_punctuation()) => {
+                    self.push(next.unwrap());
+                    i += 2;
+                }
+                '`' => match find(&chars, i + 1, &['`']) {
+                    Some(close) => {
+                        let outside = self.attributes;
+                        self.set(Attributes { mono: true, ..outside });
+                        chars[i + 1..close].iter().for_each(|&ch| self.push(ch));
+                        self.set(outside);
+                        i = close + 1;
+                    }
+                    None => {
+                        self.push(ch);
+                        i += 1;
+                    }
+                },
+                '*' if next == Some('*') => {
+                    if self.attributes.bold && !line_attributes.bold {
+                        self.set(Attributes { bold: false, ..self.attributes });
+                    } else if !self.attributes.bold && find(&chars, i + 2, &['*', '*']).is_some() {
+                        self.set(Attributes { bold: true, ..self.attributes });
+                    } else {
+                        self.push_str("**");
+                    }
+                    i += 2;
+                }
+                '*' | '_' => {
+                    let before = if i > 0 { Some(chars[i - 1]) } else { None };
+                    if italic_marker == Some(ch) && (ch == '*' || !next.is_some_and(|n| n.is_alphanumeric()))
+                    {
+                        self.set(Attributes { italic: false, ..self.attributes });
+                        italic_marker = None;
+                    } else if italic_marker.is_none()
+                        && next.is_some_and(|n| !n.is_whitespace())
+                        && (ch == '*' || !before.is_some_and(|b| b.is_alphanumeric()))
+                        && find(&chars, i + 1, &[ch]).is_some()
+                    {
+                        self.set(Attributes { italic: true, ..self.attributes });
+                        italic_marker = Some(ch);
+                    } else {
+                        self.push(ch);
+                    }
+                    i += 1;
+                }
+                '[' if link_end.is_none() => {
+                    let target = find(&chars, i + 1, &[']', '('])
+                        .and_then(|label_end| Some((label_end, find(&chars, label_end + 2, &[')'])?)));
+                    match target {
+                        Some((label_end, target_end)) => {
+                            let link = self.add_link(chars[label_end + 2..target_end].iter().collect());
+                            self.set(Attributes { link: Some(link), ..self.attributes });
+                            link_end = Some((label_end, target_end + 1));
+                        }
+                        None => self.push(ch),
+                    }
+                    i += 1;
+                }
+                '<' => {
+                    let autolink = find(&chars, i + 1, &['>']).and_then(|end| {
+                        let target: String = chars[i + 1..end].iter().collect();
+                        let is_link =
+                            ["https://", "http://", "mailto:"].iter().any(|s| target.starts_with(s));
+                        if is_link && !target.contains(char::is_whitespace) {
+                            Some((end, target))
+                        } else {
+                            None
+                        }
+                    });
+                    match autolink {
+                        Some((end, target)) => {
+                            let outside = self.attributes;
+                            let link = self.add_link(target.clone());
+                            self.set(Attributes { link: Some(link), ..outside });
+                            self.push_str(&target);
+                            self.set(outside);
+                            i = end + 1;
+                        }
+                        None => {
+                            self.push(ch);
+                            i += 1;
+                        }
+                    }
+                }
+                _ => {
+                    self.push(ch);
+                    i += 1;
+                }
+            }
+        }
+        self.set(line_attributes);
+    }
+
+    fn add_link(&mut self, target: String) -> u16 {
+        self.md.links.push(target);
+        (self.md.links.len() - 1) as u16
+    }
+
+    fn finish(mut self) -> Markdown {
+        self.end_run();
+        self.md
+    }
+}
+
+/// The index in `chars`, from `start` on, where `pattern` begins.
+fn find(chars: &[char], start: usize, pattern: &[char]) -> Option<usize> {
+    if start > chars.len() {
+        return None;
+    }
+    chars[start..].windows(pattern.len()).position(|w| w == pattern).map(|p| p + start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spanned(md: &Markdown) -> Vec<(String, Option<GlyphStyle>, bool, Option<u16>)> {
+        md.spans
+            .iter()
+            .map(|s| {
+                let text = md.text.chars().skip(s.start as usize).take((s.end - s.start) as usize).collect();
+                (text, s.style, s.italic, s.link)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_emphasis() {
+        let md = Markdown::parse("a **bold** and *slanted* `co*de` or _this_ but snake_case_name");
+        assert_eq!(md.text, "a bold and slanted co*de or this but snake_case_name");
+        assert_eq!(
+            spanned(&md),
+            vec![
+                ("bold".to_string(), Some(GlyphStyle::Bold), false, None),
+                ("slanted".to_string(), None, true, None),
+                ("co*de".to_string(), Some(GlyphStyle::Monospace), false, None),
+                ("this".to_string(), None, true, None),
+            ]
+        );
+        let md = Markdown::parse("***both*** **unclosed 2 * 3");
+        assert_eq!(md.text, "both **unclosed 2 * 3");
+        assert_eq!(spanned(&md), vec![("both".to_string(), Some(GlyphStyle::Bold), true, None)]);
+        assert_eq!(Markdown::parse(r"\*not\* italic").text, "*not* italic");
+    }
+
+    #[test]
+    fn test_links() {
+        let md = Markdown::parse("see [the **docs**](https://betrusted.io) or <mailto:a@b.c>, [not a link]");
+        assert_eq!(md.text, "see the docs or mailto:a@b.c, [not a link]");
+        assert_eq!(md.links, vec!["https://betrusted.io".to_string(), "mailto:a@b.c".to_string()]);
+        assert_eq!(
+            spanned(&md),
+            vec![
+                ("the ".to_string(), None, false, Some(0)),
+                ("docs".to_string(), Some(GlyphStyle::Bold), false, Some(0)),
+                ("mailto:a@b.c".to_string(), None, false, Some(1)),
+            ]
+        );
+        assert!(md.spans.iter().all(|s| s.underline == s.link.is_some()));
+    }
+
+    #[test]
+    fn test_blocks() {
+        let md = Markdown::parse("## Help\n- first\n  * *second*\nplain # text");
+        assert_eq!(md.text, "Help\n• first\n  • second\nplain # text");
+        assert_eq!(
+            spanned(&md),
+            vec![
+                ("Help".to_string(), Some(GlyphStyle::Bold), false, None),
+                ("second".to_string(), None, true, None)
+            ]
+        );
+        assert_eq!(md.spans[0].start, 0);
+        assert_eq!(md.spans[1].start, 17);
+    }
+
+    #[test]
+    fn test_link_region() {
+        let r = LinkRegion { link: 3, tl: Point::new(10, 20), br: Point::new(40, 34) };
+        assert!(r.contains(Point::new(10, 34)));
+        assert!(!r.contains(Point::new(41, 30)));
+        assert!(TextSpan::new(2, 5).contains(4));
+        assert!(!TextSpan::new(2, 5).contains(5));
+    }
+}
@@ -2,7 +2,10 @@ use core::ops::Add;
 
 use String;
 
-use crate::api::{Cursor, Gid, GlyphStyle, Point, Rectangle};
+use crate::api::{
+    Cursor, Gid, GlyphStyle, LinkRegion, MAX_LINK_REGIONS, MAX_TEXT_SPANS, Markdown, Point, Rectangle,
+    TextSpan,
+};
 
 /// coordinates are local to the canvas, not absolute to the screen
 #[derive(Debug, Copy, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
@@ -71,7 +74,8 @@ impl From<usize> for TextOp {
     }
 }
 
-// roughly 168 bytes to represent the rest of the struct, and we want to fill out the 4096 byte page with text
+// roughly 168 bytes to represent the rest of the struct, and up to about 550 for the spans and link
+// regions; we want to fill out the 4096 byte page with text
 pub const TEXTVIEW_LEN: usize = 3072;
 pub const TEXTVIEW_DEFAULT_STYLE: GlyphStyle = GlyphStyle::Regular;
 
@@ -117,6 +121,11 @@ pub struct TextView {
     pub busy_animation_state: Option<u32>,
 
     pub text: String,
+
+    // runs of the text in other styles, up to MAX_TEXT_SPANS; the rest of the text is drawn in `style`
+    pub spans: Vec<TextSpan>,
+    // set by the drawing routine to where the links among `spans` ended up, up to MAX_LINK_REGIONS
+    pub link_regions: Vec<LinkRegion>,
 }
 impl TextView {
     pub fn new(canvas: Gid, bounds_hint: TextBounds) -> Self {
@@ -130,6 +139,8 @@ impl TextView {
             bounds_hint,
             bounds_computed: None,
             style: TEXTVIEW_DEFAULT_STYLE,
+            spans: Vec::new(),
+            link_regions: Vec::new(),
             text: String::new(),
             cursor: Cursor::new(0, 0, 0),
             insertion: None,
@@ -158,7 +169,41 @@ impl TextView {
 
     pub fn to_str(&self) -> &str { self.text.as_str() }
 
-    pub fn clear_str(&mut self) { self.text.clear() }
+    pub fn clear_str(&mut self) {
+        self.text.clear();
+        self.spans.clear();
+    }
+
+    /// Trims the text to `TEXTVIEW_LEN` bytes, the spans to `MAX_TEXT_SPANS` and the link regions to
+    /// `MAX_LINK_REGIONS`, which is what fits in the page a `TextView` is lent in. Senders do this before
+    /// lending one, and the servers again on receipt, since their reply has to fit in the same page.
+    pub fn fit_to_page(&mut self) {
+        if self.text.len() > TEXTVIEW_LEN {
+            let mut end = TEXTVIEW_LEN;
+            while !self.text.is_char_boundary(end) {
+                end -= 1;
+            }
+            self.text.truncate(end);
+        }
+        self.spans.truncate(MAX_TEXT_SPANS);
+        self.link_regions.truncate(MAX_LINK_REGIONS);
+    }
+
+    /// Replaces the text and spans with those read from lightweight markdown, see `Markdown`. Returns the
+    /// link targets, indexed by the `link` of `link_regions`.
+    pub fn set_markdown(&mut self, src: &str) -> Vec<String> {
+        let mut md = Markdown::parse(src);
+        md.spans.truncate(MAX_TEXT_SPANS);
+        self.text = md.text;
+        self.spans = md.spans;
+        self.link_regions.clear();
+        md.links
+    }
+
+    /// The link drawn at `p`, in the coordinates of `bounds_computed`, if any.
+    pub fn link_at(&self, p: Point) -> Option<u16> {
+        self.link_regions.iter().find(|region| region.contains(p)).map(|region| region.link)
+    }
 
     pub fn populate_from(&mut self, t: &TextView) {
         self.canvas = t.canvas;
@@ -169,6 +214,8 @@ impl TextView {
         self.bounds_hint = t.bounds_hint;
         self.bounds_computed = t.bounds_computed;
         self.style = t.style;
+        self.spans = t.spans.clone();
+        self.link_regions = t.link_regions.clone();
         self.text = t.text.clone();
         self.cursor = t.cursor;
         self.draw_border = t.draw_border;
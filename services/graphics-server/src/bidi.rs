@@ -0,0 +1,175 @@
+//! Just enough of the Unicode bidirectional algorithm (UAX #9) to set right-to-left words on a line.
+//!
+//! The typesetter wraps text in logical order; afterwards each typeset word on a line is given a single
+//! direction, from its first strongly directional character, and the words are reordered for display
+//! using the resolved embedding levels. Explicit embeddings and isolates are not supported, and the
+//! paragraph direction is taken from the first strong character of the whole text.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BidiClass {
+    Ltr,
+    Rtl,
+    /// digits, which keep their order even among right-to-left words
+    Number,
+    /// punctuation and symbols, which take the direction around them
+    Neutral,
+    /// like `Neutral`, except at the end of a line where it takes the paragraph direction
+    Whitespace,
+}
+
+/// Hebrew, Arabic, Syriac, Thaana, NKo, Samaritan and Mandaic, with their presentation forms, and the
+/// right-to-left blocks of the supplementary planes.
+pub fn is_rtl(ch: char) -> bool {
+    matches!(ch as u32,
+        0x0590..=0x08FF | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFF | 0x10800..=0x10FFF | 0x1E800..=0x1EFFF)
+}
+
+pub fn has_rtl(s: &str) -> bool { s.chars().any(is_rtl) }
+
+fn strong(ch: char) -> Option<BidiClass> {
+    if is_rtl(ch) {
+        Some(BidiClass::Rtl)
+    } else if ch.is_alphabetic() {
+        Some(BidiClass::Ltr)
+    } else {
+        None
+    }
+}
+
+pub fn classify<I: IntoIterator<Item = char>>(chars: I) -> BidiClass {
+    let mut class = BidiClass::Whitespace;
+    for ch in chars {
+        if let Some(strong) = strong(ch) {
+            return strong;
+        } else if ch.is_ascii_digit() {
+            class = BidiClass::Number;
+        } else if !ch.is_whitespace() && class == BidiClass::Whitespace {
+            class = BidiClass::Neutral;
+        }
+    }
+    class
+}
+
+/// The paragraph direction: right-to-left if the first strong character is.
+pub fn base_is_rtl(s: &str) -> bool { s.chars().find_map(strong) == Some(BidiClass::Rtl) }
+
+/// Embedding levels for the words of one line, in logical order: even levels run left-to-right.
+pub fn resolve_levels(classes: &[BidiClass], base_rtl: bool) -> Vec<u8> {
+    // the direction each word shows its neutral neighbours; numbers count as right-to-left unless the
+    // strong word before them is left-to-right (rules W7 and N1)
+    let mut previous_strong = base_rtl;
+    let directions: Vec<Option<bool>> = classes
+        .iter()
+        .map(|class| match class {
+            BidiClass::Ltr => {
+                previous_strong = false;
+                Some(false)
+            }
+            BidiClass::Rtl => {
+                previous_strong = true;
+                Some(true)
+            }
+            BidiClass::Number => Some(previous_strong),
+            _ => None,
+        })
+        .collect();
+    let base = base_rtl as u8;
+    let mut levels = Vec::with_capacity(classes.len());
+    for (i, class) in classes.iter().enumerate() {
+        let rtl = directions[i].unwrap_or_else(|| {
+            // a neutral takes the direction around it if both sides agree (N1), else the paragraph's (N2)
+            let before = directions[..i].iter().rev().find_map(|d| *d).unwrap_or(base_rtl);
+            let after = directions[i + 1..].iter().find_map(|d| *d).unwrap_or(base_rtl);
+            if before == after { before } else { base_rtl }
+        });
+        let level = match (class, rtl) {
+            // numbers among right-to-left text sit one level above it (I1, I2)
+            (BidiClass::Number, true) => 2,
+            (_, true) => 1,
+            (_, false) => base + base,
+        };
+        levels.push(level);
+    }
+    // trailing whitespace goes back to the paragraph level (L1)
+    for (class, level) in classes.iter().zip(levels.iter_mut()).rev() {
+        if *class != BidiClass::Whitespace {
+            break;
+        }
+        *level = base;
+    }
+    levels
+}
+
+/// The logical indices of the words in display order, left to right (rule L2).
+pub fn visual_order(levels: &[u8]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..levels.len()).collect();
+    let highest = levels.iter().copied().max().unwrap_or(0);
+    let lowest_odd = match levels.iter().copied().filter(|l| l % 2 == 1).min() {
+        Some(level) => level,
+        None => return order,
+    };
+    for level in (lowest_odd..=highest).rev() {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] >= level {
+                let start = i;
+                while i < order.len() && levels[order[i]] >= level {
+                    i += 1;
+                }
+                order[start..i].reverse();
+            } else {
+                i += 1;
+            }
+        }
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<BidiClass> {
+        // split the way the typesetter does, with each space a word of its own
+        line.split_inclusive(' ')
+            .flat_map(|w| match w.strip_suffix(' ') {
+                Some(w) if !w.is_empty() => vec![classify(w.chars()), BidiClass::Whitespace],
+                _ => vec![classify(w.chars())],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify("שלום".chars()), BidiClass::Rtl);
+        assert_eq!(classify("(مرحبا)".chars()), BidiClass::Rtl);
+        assert_eq!(classify("\"hi\"".chars()), BidiClass::Ltr);
+        assert_eq!(classify("42%".chars()), BidiClass::Number);
+        assert_eq!(classify("--".chars()), BidiClass::Neutral);
+        assert_eq!(classify(" ".chars()), BidiClass::Whitespace);
+        assert!(base_is_rtl("12 שלום world"));
+        assert!(!base_is_rtl("hi שלום"));
+        assert!(has_rtl("hi שלום") && !has_rtl("hello"));
+    }
+
+    #[test]
+    fn test_ltr_paragraph() {
+        // hello ␠ שלום ␠ עולם ␠ 42 ␠ !
+        let classes = words("hello שלום עולם 42 !");
+        let levels = resolve_levels(&classes, false);
+        assert_eq!(levels, vec![0, 0, 1, 1, 1, 1, 2, 0, 0]);
+        assert_eq!(visual_order(&levels), vec![0, 1, 6, 5, 4, 3, 2, 7, 8]);
+        // no right-to-left words, nothing moves
+        let levels = resolve_levels(&words("plain text 12"), false);
+        assert_eq!(visual_order(&levels), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_rtl_paragraph() {
+        // שלום ␠ big ␠ world ␠ עולם ␠
+        let classes = words("שלום big world עולם ");
+        let levels = resolve_levels(&classes, true);
+        assert_eq!(levels, vec![1, 1, 2, 2, 2, 1, 1, 1]);
+        assert_eq!(visual_order(&levels), vec![7, 6, 5, 2, 3, 4, 1, 0]);
+    }
+}
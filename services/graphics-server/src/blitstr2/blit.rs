@@ -19,6 +19,9 @@ pub const NULL_GLYPH_SPRITE: GlyphSprite = GlyphSprite {
     insert: false,
     double: false,
     large: false,
+    italic: false,
+    underline: false,
+    link: None,
 };
 
 /// Unicode replacement character
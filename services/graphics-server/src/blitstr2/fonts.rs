@@ -32,6 +32,9 @@ pub fn small_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     insert: false,
                     double: false,
                     large: false,
+                    italic: false,
+                    underline: false,
+                    link: None,
                 }),
                 false => Err(0),
             }
@@ -56,6 +59,9 @@ pub fn regular_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     insert: false,
                     double: false,
                     large: false,
+                    italic: false,
+                    underline: false,
+                    link: None,
                 }),
                 false => Err(0),
             }
@@ -80,6 +86,9 @@ pub fn large_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     insert: false,
                     double: true,
                     large: false,
+                    italic: false,
+                    underline: false,
+                    link: None,
                 }),
                 false => Err(0),
             }
@@ -104,6 +113,9 @@ pub fn extra_large_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     insert: false,
                     double: true,
                     large: false,
+                    italic: false,
+                    underline: false,
+                    link: None,
                 }),
                 false => Err(0),
             }
@@ -128,6 +140,9 @@ pub fn bold_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     insert: false,
                     double: false,
                     large: false,
+                    italic: false,
+                    underline: false,
+                    link: None,
                 }),
                 false => Err(0),
             }
@@ -152,6 +167,9 @@ pub fn mono_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     insert: false,
                     double: false,
                     large: false,
+                    italic: false,
+                    underline: false,
+                    link: None,
                 }),
                 false => Err(0),
             }
@@ -176,6 +194,9 @@ pub fn tall_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     insert: false,
                     double: false,
                     large: true,
+                    italic: false,
+                    underline: false,
+                    link: None,
                 }),
                 false => Err(0),
             }
@@ -200,6 +221,9 @@ pub fn emoji_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     insert: false,
                     double: false,
                     large: false,
+                    italic: false,
+                    underline: false,
+                    link: None,
                 }),
                 false => Err(0),
             }
@@ -224,6 +248,9 @@ pub fn emoji_large_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     insert: false,
                     double: true,
                     large: false,
+                    italic: false,
+                    underline: false,
+                    link: None,
                 }),
                 false => Err(0),
             }
@@ -248,6 +275,9 @@ pub fn zh_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     insert: false,
                     double: false,
                     large: false,
+                    italic: false,
+                    underline: false,
+                    link: None,
                 }),
                 false => Err(0),
             }
@@ -272,6 +302,9 @@ pub fn ja_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     insert: false,
                     double: false,
                     large: false,
+                    italic: false,
+                    underline: false,
+                    link: None,
                 }),
                 false => Err(0),
             }
@@ -296,6 +329,9 @@ pub fn kr_glyph(ch: char) -> Result<GlyphSprite, usize> {
                     insert: false,
                     double: false,
                     large: false,
+                    italic: false,
+                    underline: false,
+                    link: None,
                 }),
                 false => Err(0),
             }
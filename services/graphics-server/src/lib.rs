@@ -13,7 +13,7 @@ pub mod op;
 pub mod fontmap;
 pub use api::ArchivedBulkRead;
 pub use api::BulkRead;
-use api::{Opcode, TEXTVIEW_LEN}; // if you prefer to map the api into your local namespace
+use api::Opcode; // if you prefer to map the api into your local namespace
 pub use fontmap::*;
 use num_traits::ToPrimitive;
 use xous::{Message, send_message};
@@ -301,9 +301,7 @@ impl Gfx {
     /// gfx.flush().unwrap();
     /// ````
     pub fn draw_textview(&self, tv: &mut TextView) -> Result<(), xous::Error> {
-        if tv.text.len() > TEXTVIEW_LEN {
-            tv.text.truncate(TEXTVIEW_LEN);
-        }
+        tv.fit_to_page();
         let mut buf = Buffer::into_buf(tv.clone()).or(Err(xous::Error::InternalError))?;
         buf.lend_mut(self.conn, Opcode::DrawTextView.to_u32().unwrap())
             .or(Err(xous::Error::InternalError))?;
@@ -312,6 +310,7 @@ impl Gfx {
         tv.bounds_computed = tvr.bounds_computed;
         tv.cursor = tvr.cursor;
         tv.overflow = tvr.overflow;
+        tv.link_regions = tvr.link_regions;
         tv.busy_animation_state = tvr.busy_animation_state;
         Ok(())
     }
@@ -525,6 +524,13 @@ impl Gfx {
             .map(|_| ())
     }
 
+    /// Selects the glyph rules for the language at `index` in `locales::LANGUAGES`. Called by the GAM
+    /// when the user changes the language.
+    pub fn set_language(&self, index: usize) -> Result<(), xous::Error> {
+        send_message(self.conn, Message::new_scalar(Opcode::SetLanguage.to_usize().unwrap(), index, 0, 0, 0))
+            .map(|_| ())
+    }
+
     /// Reads the font map in bulk from the graphics server.
     ///
     /// Instead of implementing the read in the library, we hand the raw opcode to the caller.
@@ -19,6 +19,7 @@ mod sleep_note;
 
 use api::*;
 
+mod bidi;
 mod blitstr2;
 mod wordwrap;
 #[macro_use]
@@ -300,6 +301,8 @@ fn wrapped_main(main_thread_token: backend::MainThreadToken) -> ! {
                     let mut buffer =
                         unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                     let mut tv = buffer.to_original::<TextView, _>().unwrap();
+                    // the reply goes back in the same page, with the link regions filled in
+                    tv.fit_to_page();
 
                     if tv.clip_rect.is_none() {
                         continue;
@@ -342,6 +345,7 @@ fn wrapped_main(main_thread_token: backend::MainThreadToken) -> ! {
                         tv.to_str(),
                         &typeset_extent,
                         &tv.style,
+                        &tv.spans[..tv.spans.len().min(MAX_TEXT_SPANS)],
                         if let Some(i) = tv.insertion { Some(i as usize) } else { None },
                     );
                     let composition = typesetter.typeset(if tv.ellipsis {
@@ -523,6 +527,11 @@ fn wrapped_main(main_thread_token: backend::MainThreadToken) -> ! {
                     tv.cursor.pt.y = composition.final_cursor().pt.y;
                     tv.cursor.line_height = composition.final_cursor().line_height;
                     tv.overflow = Some(composition.final_overflow());
+                    tv.link_regions = if tv.spans.iter().any(|span| span.link.is_some()) {
+                        composition.link_regions(composition_top_left)
+                    } else {
+                        Vec::new()
+                    };
 
                     tv.bounds_computed = Some(clear_rect);
                     log::trace!("cursor ret {:?}, bounds ret {:?}", tv.cursor, tv.bounds_computed);
@@ -582,6 +591,11 @@ fn wrapped_main(main_thread_token: backend::MainThreadToken) -> ! {
                     display.blit_screen(&poweron::LOGO_MAP);
                     display.redraw();
                 }),
+                Some(Opcode::SetLanguage) => msg_scalar_unpack!(msg, index, _, _, _, {
+                    if let Some(lang) = locales::LANGUAGES.get(index) {
+                        locales::set_lang(lang);
+                    }
+                }),
                 Some(Opcode::Devboot) => msg_scalar_unpack!(msg, ena, _, _, _, {
                     if ena != 0 {
                         display.set_devboot(true);
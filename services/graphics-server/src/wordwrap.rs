@@ -1,4 +1,7 @@
-use crate::api::{Cursor, GlyphSprite, GlyphStyle, Point, Pt, Rectangle, TypesetWord, glyph_to_height_hint};
+use crate::api::{
+    Cursor, GlyphSprite, GlyphStyle, LinkRegion, MAX_LINK_REGIONS, Point, Pt, Rectangle, TextSpan,
+    TypesetWord, glyph_to_height_hint,
+};
 #[allow(unused_imports)]
 use crate::backend::{FB_LINES, FB_SIZE, FB_WIDTH_PIXELS};
 /// Wordwrap stratgey
@@ -15,10 +18,13 @@ use crate::backend::{FB_LINES, FB_SIZE, FB_WIDTH_PIXELS};
 /// defined by a `bounds` record.
 ///
 /// The exact GlyphSprite chosen is picked based on a hierarchy that starts with a hint based on
-/// `locales::LANG`, then rules based on the `base_style: GlyphStyle` field, which allows for all the
-/// text within a given string to be eg. small, regular, monospace, bold (mixing of different styles is
-/// not yet supported, but could be in the future if we add some sort of markup parsing to the text
-/// stream).
+/// `locales::lang()`, then rules based on the `base_style: GlyphStyle` field, which allows for all the
+/// text within a given string to be eg. small, regular, monospace, bold. Runs of characters covered by a
+/// `TextSpan` take the span's style instead, and carry its italic, underline and link flags on their
+/// GlyphSprites for the renderer.
+///
+/// Wrapping happens in logical order. If the string has any right-to-left characters, the words of each
+/// line are then reordered for display, see `bidi.rs`.
 ///
 /// The location of the GlyphSprites do a "Best effort" to fit the words within the `bounds` based on the
 /// designated rule without word-wrapping. If a single word overflows one line width, it will be broken
@@ -27,6 +33,7 @@ use crate::backend::{FB_LINES, FB_SIZE, FB_WIDTH_PIXELS};
 ///
 /// If the overall string cannot fit within the absolute bounds defined by the `max` area and/or the
 /// `bounds`, the rendering is halted, and ellipses are inserted at the end.
+use crate::bidi;
 use crate::blitstr2::{self, *};
 use crate::style_macros::*;
 
@@ -111,6 +118,8 @@ impl ComposedType {
     /// additional checks around this.
     pub fn render(&self, frbuf: &mut [u32; FB_SIZE], offset: Point, invert: bool, clip_rect: Rectangle) {
         const MAX_GLYPH_MARGIN: i16 = 16;
+        // italic glyphs are drawn in horizontal bands, each shifted a pixel right of the one below it
+        const ITALIC_BANDS: i16 = 3;
         // let mut strpos; // just for debugging insertion points
         for word in self.words.iter() {
             // strpos = word.strpos;
@@ -140,29 +149,34 @@ impl ComposedType {
                 } else {
                     let cr =
                         ClipRect::new(clip_rect.tl().x, clip_rect.tl().y, clip_rect.br().x, clip_rect.br().y);
-                    if glyph.large {
-                        blitstr2::xor_glyph_large(
-                            frbuf,
-                            &Point::new(maybe_x, maybe_y),
-                            *glyph,
-                            glyph.invert ^ invert,
-                            cr,
-                        );
-                    } else if !glyph.double {
-                        blitstr2::xor_glyph(
-                            frbuf,
-                            &Point::new(maybe_x, maybe_y),
-                            *glyph,
-                            glyph.invert ^ invert,
-                            cr,
-                        );
+                    if glyph.italic {
+                        let band_height = (glyph.high as i16 + ITALIC_BANDS - 1) / ITALIC_BANDS;
+                        for band in 0..ITALIC_BANDS {
+                            let top = cr.min.y.max(maybe_y + band * band_height);
+                            let bottom = cr.max.y.min(maybe_y + (band + 1) * band_height);
+                            if top < bottom {
+                                blit_glyph(
+                                    frbuf,
+                                    Point::new(maybe_x + ITALIC_BANDS - 1 - band, maybe_y),
+                                    glyph,
+                                    glyph.invert ^ invert,
+                                    ClipRect::new(cr.min.x, top, cr.max.x, bottom),
+                                );
+                            }
+                        }
                     } else {
-                        blitstr2::xor_glyph_2x(
+                        blit_glyph(frbuf, Point::new(maybe_x, maybe_y), glyph, glyph.invert ^ invert, cr);
+                    }
+                    if glyph.underline && !word.non_drawable {
+                        let y = maybe_y + glyph.high as i16 - 1;
+                        crate::op::line(
                             frbuf,
-                            &Point::new(maybe_x, maybe_y),
-                            *glyph,
+                            crate::api::Line::new(
+                                crate::api::Point::new(maybe_x, y),
+                                crate::api::Point::new(maybe_x + (glyph.wide + glyph.kern) as i16 - 1, y),
+                            ),
+                            Some(clip_rect),
                             glyph.invert ^ invert,
-                            cr,
                         );
                     }
                     if glyph.insert {
@@ -186,10 +200,59 @@ impl ComposedType {
         }
     }
 
+    /// Where the glyphs of each link were set, offset like `render`. Neighbouring glyphs of a link on the
+    /// same line, including the spaces between its words, make up one region.
+    pub fn link_regions(&self, offset: Point) -> Vec<LinkRegion> {
+        let mut regions: Vec<LinkRegion> = Vec::new();
+        for word in self.words.iter().filter(|w| !w.non_drawable) {
+            let mut x = offset.x + word.origin.x;
+            let y = offset.y + word.origin.y;
+            for glyph in word.gs.iter() {
+                let (left, right) = (x, x + (glyph.wide + glyph.kern) as i16 - 1);
+                x = right + 1;
+                let link = match glyph.link {
+                    Some(link) => link,
+                    None => continue,
+                };
+                let bottom = y + glyph.high as i16 - 1;
+                // right-to-left words are set with their glyphs reversed, so a link can grow either way
+                match regions
+                    .iter()
+                    .rposition(|r| r.link == link && r.tl.y == y && left <= r.br.x + 1 && right >= r.tl.x - 1)
+                {
+                    Some(i) => {
+                        let region = &mut regions[i];
+                        region.tl.x = region.tl.x.min(left);
+                        region.br.x = region.br.x.max(right);
+                        region.br.y = region.br.y.max(bottom);
+                    }
+                    None if regions.len() < MAX_LINK_REGIONS => regions.push(LinkRegion {
+                        link,
+                        tl: Point::new(left, y),
+                        br: Point::new(right, bottom),
+                    }),
+                    None => {}
+                }
+            }
+        }
+        regions
+    }
+
     pub fn final_cursor(&self) -> Cursor { self.cursor }
 
     pub fn final_overflow(&self) -> bool { self.overflow }
 }
+/// Blits one glyph with whichever of the blitters suits its size.
+fn blit_glyph(frbuf: &mut [u32; FB_SIZE], p: Point, glyph: &GlyphSprite, xor: bool, cr: ClipRect) {
+    if glyph.large {
+        blitstr2::xor_glyph_large(frbuf, &p, *glyph, xor, cr);
+    } else if !glyph.double {
+        blitstr2::xor_glyph(frbuf, &p, *glyph, xor, cr);
+    } else {
+        blitstr2::xor_glyph_2x(frbuf, &p, *glyph, xor, cr);
+    }
+}
+
 /// Typesetter takes a string and attempts to lay it out within a region defined by
 /// a single point known as the "Extent". This is the maximum extent allowable for
 /// the type.
@@ -223,12 +286,19 @@ pub(crate) struct Typesetter {
     insertion_point: Option<usize>,
     s: String,
     base_style: GlyphStyle,
+    spans: Vec<TextSpan>,
     overflow: bool,
     max_width: i16,
     last_line_height: usize, // scorecarding for the very last line on the loop exit
 }
 impl Typesetter {
-    pub fn setup(s: &str, extent: &Pt, base_style: &GlyphStyle, insertion_point: Option<usize>) -> Self {
+    pub fn setup(
+        s: &str,
+        extent: &Pt,
+        base_style: &GlyphStyle,
+        spans: &[TextSpan],
+        insertion_point: Option<usize>,
+    ) -> Self {
         let bb = ClipRect::new(0, 0, extent.x, extent.y);
         let mut space = style_glyph(' ', base_style);
         space.kern = 0;
@@ -256,6 +326,7 @@ impl Typesetter {
             ellipsis,
             large_space,
             base_style: base_style.clone(),
+            spans: spans.to_vec(),
             s: String::from(s),
             insertion_point,
             overflow: false,
@@ -381,8 +452,7 @@ impl Typesetter {
                 //    line for it.
                 // 5. The evolving word fits a line but doesn't fit this line, and there is no more space at
                 //    all.
-                let mut gs =
-                    if ch != '\t' { style_glyph(ch, &self.base_style) } else { self.large_space.clone() };
+                let mut gs = if ch != '\t' { self.styled_glyph(ch) } else { self.large_space.clone() };
                 if self.is_insert_point() {
                     gs.insert = true;
                 }
@@ -421,6 +491,7 @@ impl Typesetter {
                                 self.move_candidate_to_newline();
                                 // now set the overflowed character on the new line so our state is synched up
                                 self.candidate.push(gs_pop);
+                                self.commit_candidate_glyph(&gs_pop);
                             } else {
                                 // case 3
                                 // similar to the one-line iterator exit, but with a call to overflow at the
@@ -465,6 +536,9 @@ impl Typesetter {
         if self.candidate.gs.len() > 0 {
             self.commit_candidate_word(&mut composition);
         }
+        if bidi::has_rtl(&self.s) {
+            self.reorder_bidi(&mut composition);
+        }
         let ret = ComposedType::new(
             composition,
             ClipRect::new(
@@ -492,6 +566,47 @@ impl Typesetter {
         ret
     }
 
+    /// The glyph for `ch` at the current character position, in the style of any span over it.
+    fn styled_glyph(&self, ch: char) -> GlyphSprite {
+        match self.spans.iter().find(|span| span.contains(self.charpos)) {
+            Some(span) => {
+                let mut gs = style_glyph(ch, span.style.as_ref().unwrap_or(&self.base_style));
+                gs.italic = span.italic;
+                gs.underline = span.underline;
+                gs.link = span.link;
+                gs
+            }
+            None => style_glyph(ch, &self.base_style),
+        }
+    }
+
+    /// Sets the words of each line of `composition` in display order, and the glyphs of right-to-left
+    /// words right to left. Right-to-left paragraphs are also aligned to the right.
+    fn reorder_bidi(&self, composition: &mut Vec<TypesetWord>) {
+        let base_rtl = bidi::base_is_rtl(&self.s);
+        let mut start = 0;
+        while start < composition.len() {
+            let y = composition[start].origin.y;
+            let end = start + composition[start..].iter().take_while(|w| w.origin.y == y).count();
+            // non-drawable spaces at the start of a line stay where they are
+            let line: Vec<usize> = (start..end).filter(|&i| !composition[i].non_drawable).collect();
+            let classes: Vec<bidi::BidiClass> =
+                line.iter().map(|&i| bidi::classify(composition[i].gs.iter().map(|g| g.ch))).collect();
+            let levels = bidi::resolve_levels(&classes, base_rtl);
+            let width: i16 = line.iter().map(|&i| composition[i].width).sum();
+            let mut x = if base_rtl { self.max_width - width } else { self.bb.min.x };
+            for position in bidi::visual_order(&levels) {
+                let word = &mut composition[line[position]];
+                if levels[position] % 2 == 1 {
+                    word.gs.reverse();
+                }
+                word.origin.x = x;
+                x += word.width;
+            }
+            start = end;
+        }
+    }
+
     fn is_newline_available(&self) -> bool {
         // repeated, bare newlines will have a candidate height of 0, as it contains no glyphs. correct for
         // that.
@@ -581,15 +696,21 @@ impl Typesetter {
     /// set to our space point, because the caller will have already stashed the previously formed word
     fn try_append_space(&mut self, composition: &mut Vec<TypesetWord>) -> bool {
         assert!(self.candidate.gs.len() == 0, "self.candidate was not set to a new state prior to this call");
-        if (self.cursor.pt.x + self.space.wide as i16) < self.bb.max.x {
-            // our candidate word is "just as space"
-            let mut candidate_space = self.space.clone();
+        // our candidate word is "just as space"
+        let mut candidate_space = if self.spans.iter().any(|span| span.contains(self.charpos)) {
+            let mut space = self.styled_glyph(' ');
+            space.kern = 0;
+            space
+        } else {
+            self.space.clone()
+        };
+        if (self.cursor.pt.x + candidate_space.wide as i16) < self.bb.max.x {
             if self.is_insert_point() {
                 candidate_space.insert = true;
             }
             self.candidate.push(candidate_space);
             self.commit_candidate_glyph(&candidate_space);
-            self.cursor.line_height = self.cursor.line_height.max(self.space.high as usize);
+            self.cursor.line_height = self.cursor.line_height.max(candidate_space.high as usize);
             // if we're at the beginning of a line, mark the candidate word (that just contains a space) as
             // non-drawable
             if self.cursor.pt.x == self.bb.min.x {
@@ -625,7 +746,7 @@ fn tsw_debug(tsw: &TypesetWord) {
 
 /// Find glyph for char using latin regular, emoji, ja, zh, and kr font data
 pub fn style_glyph(ch: char, base_style: &GlyphStyle) -> GlyphSprite {
-    match locales::LANG {
+    match locales::lang() {
         "zh" => {
             style_wrapper!(zh_rules, base_style, ch)
         }
@@ -59,7 +59,8 @@ struct InputTracker {
     /// keep track if our box was grown
     was_grown: bool,
 
-    /// if set to true, the F1-F4 keys work as menu selects, and not as predictive inputs
+    /// if set to true, the F1-F4 keys work as menu selects while the line is empty, and otherwise complete
+    /// the last word on it
     menu_mode: bool,
 
     /// render the predictions. Slightly awkward because this code comes from before we had libstd
@@ -267,10 +268,29 @@ impl InputTracker {
                 }
                 self.last_trigger_char = Some(self.insertion);
                 self.insertion = self.characters;
+                // the prediction is now the word being typed, so it's what gets reported as picked
+                self.pred_phrase.clear();
+                self.pred_phrase.push_str(pred_str.as_str());
             }
         }
     }
 
+    /// In menu mode the predictor is given the whole line, so a prediction completes the last word of it
+    fn complete_word(&mut self, index: usize) {
+        let pred_str = match &self.pred_options[index] {
+            Some(s) => s.to_string(),
+            _ => return,
+        };
+        let word_len: usize =
+            self.line.chars().rev().take_while(|c| !c.is_whitespace()).map(|c| c.len_utf8()).sum();
+        self.line.truncate(self.line.len() - word_len);
+        self.line.push_str(&pred_str);
+        self.characters = self.line.chars().count();
+        self.insertion = self.characters;
+        self.pred_phrase.clear();
+        self.pred_phrase.push_str(&self.line);
+    }
+
     pub fn update(
         &mut self,
         newkeys: [char; 4],
@@ -359,6 +379,11 @@ impl InputTracker {
                         if !self.menu_mode {
                             self.insert_prediction(0);
                             do_redraw = true;
+                        } else if self.pred_phrase.len() > 0 {
+                            // while there is text on the line, the F-keys complete its last word
+                            self.complete_word(0);
+                            update_predictor = true;
+                            do_redraw = true;
                         } else {
                             retstring = Some(String::from("\u{0011}"));
                             do_redraw = true;
@@ -369,6 +394,10 @@ impl InputTracker {
                         if !self.menu_mode {
                             self.insert_prediction(1);
                             do_redraw = true;
+                        } else if self.pred_phrase.len() > 0 {
+                            self.complete_word(1);
+                            update_predictor = true;
+                            do_redraw = true;
                         } else {
                             retstring = Some(String::from("\u{0012}"));
                             do_redraw = true;
@@ -379,6 +408,10 @@ impl InputTracker {
                         if !self.menu_mode {
                             self.insert_prediction(2);
                             do_redraw = true;
+                        } else if self.pred_phrase.len() > 0 {
+                            self.complete_word(2);
+                            update_predictor = true;
+                            do_redraw = true;
                         } else {
                             retstring = Some(String::from("\u{0013}"));
                             do_redraw = true;
@@ -389,6 +422,10 @@ impl InputTracker {
                         if !self.menu_mode {
                             self.insert_prediction(3);
                             do_redraw = true;
+                        } else if self.pred_phrase.len() > 0 {
+                            self.complete_word(3);
+                            update_predictor = true;
+                            do_redraw = true;
                         } else {
                             retstring = Some(String::from("\u{0014}"));
                             do_redraw = true;
@@ -397,7 +434,7 @@ impl InputTracker {
                     '\u{0008}' => {
                         // backspace
                         #[cfg(feature = "tts")]
-                        self.tts.tts_simple(t!("input.delete-tts", locales::LANG)).unwrap();
+                        self.tts.tts_simple(t!("input.delete-tts", locales::lang())).unwrap();
                         if (self.characters > 0) && (self.insertion == self.characters) {
                             if debug1 {
                                 info!("simple backspace case")
@@ -732,7 +769,7 @@ impl InputTracker {
                 empty_tv.draw_border = false;
                 empty_tv.border_width = 1;
                 empty_tv.clear_area = true;
-                write!(empty_tv.text, "{}", t!("input.greeting", locales::LANG))
+                write!(empty_tv.text, "{}", t!("input.greeting", locales::lang()))
                     .expect("couldn't set up empty TextView");
                 if debug_canvas {
                     info!("pc canvas {:?}", pc)
@@ -0,0 +1,47 @@
+// Encodes the word lists under dict/ into the front-coded form the predictor loads.
+//
+// A word list is one word per line, most frequent first; lines starting with '#' are comments. Position
+// in the list becomes a frequency from 255 down to 1, so the lists don't need counts of their own.
+
+use std::fs;
+use std::path::PathBuf;
+
+#[allow(dead_code)]
+#[path = "src/frontcode.rs"]
+mod frontcode;
+
+fn main() {
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    let dict_dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap()).join("dict");
+    println!("cargo:rerun-if-changed={}", dict_dir.display());
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dict_dir)
+        .expect("can't read dict/")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map(|ext| ext == "txt").unwrap_or(false))
+        .collect();
+    paths.sort();
+
+    let mut table = String::from("const DICTIONARIES: &[(&str, &[u8])] = &[\n");
+    for path in paths {
+        println!("cargo:rerun-if-changed={}", path.display());
+        let lang = path.file_stem().unwrap().to_str().unwrap().to_string();
+        let list = fs::read_to_string(&path).expect("can't read word list");
+        let words: Vec<&str> = list
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+        let ranked: Vec<(String, u8)> = words
+            .iter()
+            .enumerate()
+            .map(|(rank, word)| (word.to_lowercase(), (255 - rank * 254 / words.len().max(1)) as u8))
+            .collect();
+        let encoded = frontcode::encode(&ranked);
+        let bin = out_dir.join(format!("{}.bin", lang));
+        fs::write(&bin, &encoded).expect("can't write encoded dictionary");
+        table.push_str(&format!("    ({:?}, include_bytes!({:?})),\n", lang, bin.display().to_string()));
+    }
+    table.push_str("];\n");
+    fs::write(out_dir.join("dictionaries.rs"), table).expect("can't write dictionary table");
+}
@@ -0,0 +1,93 @@
+//! Front-coded word lists: the words are sorted, and each one only stores what differs from the word
+//! before it. Neighbouring words in a sorted list share long prefixes, so this roughly halves the size of
+//! a dictionary at no cost to the linear scans the predictor does anyway.
+//!
+//! Each entry is `[shared prefix length][suffix length][suffix...][frequency]`, lengths in bytes. This
+//! file is also compiled into the build script, which encodes the word lists under `dict/`.
+
+/// Longest word kept, in bytes. Anything longer isn't worth predicting.
+pub const MAX_WORD_LEN: usize = 48;
+
+/// Encodes `(word, frequency)` pairs. Words that are empty, too long, or repeated are dropped; of the
+/// repeats, the most frequent is kept.
+pub fn encode(words: &[(String, u8)]) -> Vec<u8> {
+    let mut sorted: Vec<&(String, u8)> =
+        words.iter().filter(|(word, _)| !word.is_empty() && word.len() <= MAX_WORD_LEN).collect();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+    sorted.dedup_by(|b, a| a.0 == b.0);
+
+    let mut encoded = Vec::new();
+    let mut previous = "";
+    for (word, freq) in sorted {
+        let shared = shared_prefix_len(previous, word);
+        let suffix = &word.as_bytes()[shared..];
+        encoded.push(shared as u8);
+        encoded.push(suffix.len() as u8);
+        encoded.extend_from_slice(suffix);
+        encoded.push(*freq);
+        previous = word;
+    }
+    encoded
+}
+
+/// Decodes a list made by `encode`, in sorted order. Stops at the first malformed entry.
+pub fn decode(encoded: &[u8]) -> Vec<(String, u8)> {
+    let mut words = Vec::new();
+    let mut word: Vec<u8> = Vec::new();
+    let mut rest = encoded;
+    while rest.len() >= 3 {
+        let shared = rest[0] as usize;
+        let suffix_len = rest[1] as usize;
+        if shared > word.len() || rest.len() < 3 + suffix_len {
+            break;
+        }
+        word.truncate(shared);
+        word.extend_from_slice(&rest[2..2 + suffix_len]);
+        let freq = rest[2 + suffix_len];
+        rest = &rest[3 + suffix_len..];
+        match std::str::from_utf8(&word) {
+            Ok(s) => words.push((s.to_string(), freq)),
+            Err(_) => break,
+        }
+    }
+    words
+}
+
+/// Prefix in bytes, kept on a character boundary so every suffix is valid UTF-8 on its own.
+fn shared_prefix_len(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|((_, ca), cb)| ca != cb)
+        .map(|((i, _), _)| i)
+        .unwrap_or_else(|| a.len().min(b.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let words: Vec<(String, u8)> =
+            [("there", 200), ("the", 255), ("them", 180), ("été", 90), ("était", 120)]
+                .iter()
+                .map(|(w, f)| (w.to_string(), *f))
+                .collect();
+        let encoded = encode(&words);
+        let mut expected = words.clone();
+        expected.sort();
+        assert_eq!(decode(&encoded), expected);
+        // smaller than the words with a length and a frequency each: "them" and "there" only add to "the"
+        assert!(encoded.len() < words.iter().map(|(w, _)| w.len() + 2).sum::<usize>());
+        // "é" and "è" have their first byte in common, but not a character
+        assert_eq!(shared_prefix_len("élan", "èlan"), 0);
+        assert_eq!(shared_prefix_len("été", "était"), "ét".len());
+    }
+
+    #[test]
+    fn test_encode_filters() {
+        let long = "x".repeat(MAX_WORD_LEN + 1);
+        let words = vec![(String::new(), 1), (long, 1), ("word".to_string(), 3), ("word".to_string(), 9)];
+        assert_eq!(decode(&encode(&words)), vec![("word".to_string(), 9)]);
+    }
+}
@@ -0,0 +1,238 @@
+#![cfg_attr(target_os = "none", no_std)]
+#![cfg_attr(target_os = "none", no_main)]
+
+mod frontcode;
+mod predictor;
+
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex, mpsc};
+
+use ime_plugin_api::*;
+use num_traits::FromPrimitive;
+use predictor::{Learned, Predictor};
+use xous::msg_scalar_unpack;
+use xous_ipc::Buffer;
+
+// `DICTIONARIES`, the word lists under dict/ as encoded by the build script
+include!(concat!(env!("OUT_DIR"), "/dictionaries.rs"));
+
+/// What the user taught the predictor is kept in this dictionary, one key per language
+const LEARNED_DICT: &str = "ime.predictor";
+/// Save what was learned after this many words, so that a crash or a reboot loses little of it
+const PICKS_PER_SAVE: usize = 16;
+
+/// The built-in dictionary for the configured locale. Languages that aren't written in words separated
+/// by spaces get none, and predict only from what the user typed.
+fn dictionary(lang: &str) -> Vec<(String, u8)> {
+    let lang = lang.split('-').next().unwrap_or(lang); // "en-tts" is still English
+    match DICTIONARIES.iter().find(|(name, _)| *name == lang) {
+        Some((_, encoded)) => frontcode::decode(encoded),
+        None => {
+            log::info!("no built-in dictionary for {}", lang);
+            Vec::new()
+        }
+    }
+}
+
+enum Storage {
+    /// read back what was learned in the language
+    Load(String),
+    /// replace what was learned in the language with the serialized snapshot
+    Save(String, String),
+}
+
+/// Loads and saves what was learned, waiting for the PDDB to be mounted first. Runs on its own thread so
+/// that typing works from boot, before the PDDB is there.
+fn storage_thread(loaded: Arc<Mutex<Option<(String, Learned)>>>, requests: mpsc::Receiver<Storage>) {
+    let pddb = pddb::Pddb::new();
+    pddb.is_mounted_blocking();
+    for request in requests {
+        match request {
+            Storage::Load(lang) => {
+                let learned = match pddb.get(LEARNED_DICT, &lang, None, true, false, None, None::<fn()>) {
+                    Ok(mut key) => {
+                        let mut saved = String::new();
+                        match key.read_to_string(&mut saved) {
+                            Ok(_) => Learned::deserialize(&saved),
+                            Err(e) => {
+                                log::warn!("couldn't read learned words: {:?}", e);
+                                Learned::default()
+                            }
+                        }
+                    }
+                    Err(_) => Learned::default(), // nothing learned yet
+                };
+                *loaded.lock().unwrap() = Some((lang, learned));
+            }
+            Storage::Save(lang, snapshot) => {
+                // the old record goes away entirely, rather than being partly overwritten by a shorter one
+                pddb.delete_key(LEARNED_DICT, &lang, None).ok();
+                match pddb.get(LEARNED_DICT, &lang, None, true, true, Some(snapshot.len()), None::<fn()>) {
+                    Ok(mut key) => {
+                        if let Err(e) = key.write_all(snapshot.as_bytes()) {
+                            log::warn!("couldn't save learned words: {:?}", e);
+                        }
+                    }
+                    Err(e) => log::warn!("couldn't open the learned words for saving: {:?}", e),
+                }
+                pddb.sync().ok();
+            }
+        }
+    }
+}
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    log::info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    // one connection only, should be the GAM
+    let ime_sid = xns
+        .register_name(ime_plugin_predict::SERVER_NAME_IME_PLUGIN_PREDICT, None)
+        .expect("can't register server");
+    log::trace!("registered with NS -- {:?}", ime_sid);
+
+    // for picking up the language chosen in the preferences, with `gam::sync_language()`
+    let _gam = gam::Gam::new(&xns).expect("can't connect to GAM");
+    let mut lang = locales::lang();
+    let mut predictor = Predictor::new(dictionary(lang));
+    let loaded: Arc<Mutex<Option<(String, Learned)>>> = Arc::new(Mutex::new(None));
+    let (storage, requests) = mpsc::channel();
+    std::thread::spawn({
+        let loaded = loaded.clone();
+        move || storage_thread(loaded, requests)
+    });
+    storage.send(Storage::Load(lang.to_string())).ok();
+    // nothing gets saved until the saved words were merged in, or they'd be overwritten
+    let mut merged = false;
+    let mut unsaved_picks = 0;
+
+    // newlines are left out: the frontend already treats them as the end of a word, and a pick on every
+    // line sent would teach the predictor whatever the last word of each chat message is
+    let mytriggers = PredictionTriggers { newline: false, punctuation: true, whitespace: true };
+
+    log::trace!("ready to accept requests");
+    let mut api_token: Option<[u32; 4]> = None;
+    loop {
+        let mut msg = xous::receive_message(ime_sid).unwrap();
+        if msg.body.id() == Opcode::Acquire as usize {
+            // an app starting to type is when a language changed since the last one is picked up
+            gam::sync_language();
+        }
+        if locales::lang() != lang {
+            // each language has a dictionary and learned words of its own
+            if merged && unsaved_picks > 0 {
+                storage.send(Storage::Save(lang.to_string(), predictor.learned.serialize())).ok();
+            }
+            lang = locales::lang();
+            log::info!("switching predictions to {}", lang);
+            predictor = Predictor::new(dictionary(lang));
+            merged = false;
+            unsaved_picks = 0;
+            storage.send(Storage::Load(lang.to_string())).ok();
+        }
+        if !merged {
+            // words learned in a language that has since been switched away from are dropped
+            if let Some((loaded_lang, learned)) = loaded.lock().unwrap().take() {
+                if loaded_lang == lang {
+                    predictor.learned.merge(learned);
+                    merged = true;
+                }
+            }
+        }
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::Acquire) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut ret = buffer.to_original::<AcquirePredictor, _>().unwrap();
+                if api_token.is_none() {
+                    if let Some(token) = ret.token {
+                        api_token = Some(token);
+                    } else {
+                        let new_token = xous::create_server_id().unwrap().to_array();
+                        ret.token = Some(new_token);
+                        api_token = Some(new_token);
+                    }
+                } else {
+                    ret.token = None;
+                    log::warn!("attempt to acquire lock on a predictor that was already locked");
+                }
+                buffer.replace(ret).unwrap();
+            }
+            Some(Opcode::Release) => msg_scalar_unpack!(msg, t0, t1, t2, t3, {
+                let token = [t0 as u32, t1 as u32, t2 as u32, t3 as u32];
+                if let Some(t) = api_token {
+                    if t == token {
+                        api_token.take();
+                        // the next app shouldn't be offered words that follow from the last one's text
+                        predictor.clear_context();
+                        if merged && unsaved_picks > 0 {
+                            storage.send(Storage::Save(lang.to_string(), predictor.learned.serialize())).ok();
+                            unsaved_picks = 0;
+                        }
+                    } else {
+                        log::warn!("Release called with an invalid token");
+                    }
+                } else {
+                    log::warn!("Release called on a predictor that was in a released state");
+                }
+            }),
+            Some(Opcode::Input) => {
+                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let s = buffer.as_flat::<String, _>().unwrap();
+                predictor.set_input(s.as_str());
+            }
+            Some(Opcode::Picked) => {
+                let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                let s = buffer.as_flat::<String, _>().unwrap();
+                if predictor.picked(s.as_str()) {
+                    unsaved_picks += 1;
+                }
+                if merged && unsaved_picks >= PICKS_PER_SAVE {
+                    storage.send(Storage::Save(lang.to_string(), predictor.learned.serialize())).ok();
+                    unsaved_picks = 0;
+                }
+            }
+            Some(Opcode::Unpick) => predictor.unpick(),
+            Some(Opcode::Prediction) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut prediction: Prediction = buffer.to_original::<Prediction, _>().unwrap();
+                // predictions come from what was typed, so they're only given to whoever holds the lock
+                let word = if api_token == Some(prediction.api_token) {
+                    predictor.prediction(prediction.index as usize)
+                } else {
+                    None
+                };
+                match word {
+                    Some(word) => {
+                        prediction.string.clear();
+                        prediction.string.push_str(word);
+                        prediction.valid = true;
+                    }
+                    None => prediction.valid = false,
+                }
+                buffer.replace(Return::Prediction(prediction)).expect("couldn't return Prediction");
+            }
+            Some(Opcode::GetPredictionTriggers) => {
+                xous::return_scalar(msg.sender, mytriggers.into())
+                    .expect("couldn't return GetPredictionTriggers");
+            }
+            Some(Opcode::Quit) => {
+                if api_token.is_some() {
+                    log::error!("received quit, goodbye!");
+                    break;
+                }
+            }
+            None => {
+                log::error!("unknown Opcode");
+            }
+        }
+    }
+    log::trace!("main loop exit, destroying servers");
+    xns.unregister_server(ime_sid).unwrap();
+    xous::destroy_server(ime_sid).unwrap();
+    log::trace!("quitting");
+    xous::terminate_process(0)
+}
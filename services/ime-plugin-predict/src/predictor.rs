@@ -0,0 +1,364 @@
+//! Word completion and next-word prediction.
+//!
+//! Completions come from the built-in dictionary of the locale plus the words the user picked before;
+//! next-word predictions only come from what the user typed after the previous word. Everything the user
+//! taught it is kept in `Learned`, which is what gets saved to the PDDB.
+
+use std::collections::HashMap;
+
+/// One per F-key in the prediction bar
+pub const MAX_PREDICTIONS: usize = 4;
+/// Learned words beyond this push out the least used ones
+const MAX_LEARNED_WORDS: usize = 2000;
+/// Next-word predictions remembered for each word
+const MAX_FOLLOWERS: usize = 8;
+/// How many picks can be taken back with `unpick`
+const MAX_UNDO: usize = 8;
+/// A word the user picked once outranks a dictionary word of middling frequency; picked a few times, it
+/// outranks anything in the dictionary.
+const LEARNED_WEIGHT: u32 = 96;
+
+#[derive(Default, Debug, PartialEq)]
+pub struct Learned {
+    /// how often each word was picked, by its lower case form
+    words: HashMap<String, u32>,
+    /// how often each word followed another
+    followers: HashMap<String, HashMap<String, u32>>,
+}
+
+impl Learned {
+    fn learn(&mut self, previous: Option<&str>, word: &str) {
+        *self.words.entry(word.to_string()).or_insert(0) += 1;
+        if self.words.len() > MAX_LEARNED_WORDS {
+            if let Some(rarest) = self
+                .words
+                .iter()
+                .filter(|(w, _)| *w != word)
+                .min_by_key(|(_, &count)| count)
+                .map(|(w, _)| w.clone())
+            {
+                self.words.remove(&rarest);
+                self.followers.remove(&rarest);
+            }
+        }
+        if let Some(previous) = previous {
+            let followers = self.followers.entry(previous.to_string()).or_default();
+            *followers.entry(word.to_string()).or_insert(0) += 1;
+            if followers.len() > MAX_FOLLOWERS {
+                if let Some(rarest) = followers
+                    .iter()
+                    .filter(|(w, _)| *w != word)
+                    .min_by_key(|(_, &count)| count)
+                    .map(|(w, _)| w.clone())
+                {
+                    followers.remove(&rarest);
+                }
+            }
+        }
+    }
+
+    fn forget(&mut self, previous: Option<&str>, word: &str) {
+        if let Some(count) = self.words.get_mut(word) {
+            *count -= 1;
+            if *count == 0 {
+                self.words.remove(word);
+            }
+        }
+        if let Some(previous) = previous {
+            if let Some(followers) = self.followers.get_mut(previous) {
+                if let Some(count) = followers.get_mut(word) {
+                    *count -= 1;
+                    if *count == 0 {
+                        followers.remove(word);
+                    }
+                }
+                if followers.is_empty() {
+                    self.followers.remove(previous);
+                }
+            }
+        }
+    }
+
+    /// Adds what was learned elsewhere, e.g. words loaded from the PDDB after typing had already begun.
+    pub fn merge(&mut self, other: Learned) {
+        for (word, count) in other.words {
+            *self.words.entry(word).or_insert(0) += count;
+        }
+        for (previous, followers) in other.followers {
+            let mine = self.followers.entry(previous).or_default();
+            for (word, count) in followers {
+                *mine.entry(word).or_insert(0) += count;
+            }
+        }
+    }
+
+    /// One record per line: `w <count> <word>` for words and `f <count> <previous> <word>` for followers.
+    pub fn serialize(&self) -> String {
+        let mut s = String::new();
+        for (word, count) in self.words.iter() {
+            s.push_str(&format!("w {} {}\n", count, word));
+        }
+        for (previous, followers) in self.followers.iter() {
+            for (word, count) in followers.iter() {
+                s.push_str(&format!("f {} {} {}\n", count, previous, word));
+            }
+        }
+        s
+    }
+
+    /// Skips records it doesn't understand rather than losing everything else.
+    pub fn deserialize(s: &str) -> Learned {
+        let mut learned = Learned::default();
+        for line in s.lines() {
+            let fields: Vec<&str> = line.split(' ').collect();
+            match fields.as_slice() {
+                ["w", count, word] if is_word(word) => {
+                    if let Ok(count) = count.parse::<u32>() {
+                        learned.words.insert(word.to_string(), count);
+                    }
+                }
+                ["f", count, previous, word] if is_word(previous) && is_word(word) => {
+                    if let Ok(count) = count.parse::<u32>() {
+                        learned
+                            .followers
+                            .entry(previous.to_string())
+                            .or_default()
+                            .insert(word.to_string(), count);
+                    }
+                }
+                _ => log::debug!("skipping unrecognized learned record"),
+            }
+        }
+        learned
+    }
+}
+
+struct Pick {
+    word: String,
+    previous: Option<String>,
+}
+
+pub struct Predictor {
+    /// the built-in dictionary, sorted by word
+    dictionary: Vec<(String, u8)>,
+    pub learned: Learned,
+    /// the word being typed
+    input: String,
+    /// the word before it, for next-word predictions
+    previous: Option<String>,
+    undo: Vec<Pick>,
+    predictions: Vec<String>,
+}
+
+impl Predictor {
+    pub fn new(dictionary: Vec<(String, u8)>) -> Self {
+        Predictor {
+            dictionary,
+            learned: Learned::default(),
+            input: String::new(),
+            previous: None,
+            undo: Vec::new(),
+            predictions: Vec::new(),
+        }
+    }
+
+    pub fn set_input(&mut self, input: &str) {
+        self.input = input.trim().to_string();
+        self.update();
+    }
+
+    /// The user finished a word, by typing it out or by picking a prediction. Returns true if it was
+    /// learned.
+    pub fn picked(&mut self, picked: &str) -> bool {
+        self.input.clear();
+        let word = picked.trim().to_lowercase();
+        if !is_word(&word) {
+            // numbers, URLs and the like: don't learn them, and don't predict what follows them
+            self.previous = None;
+            self.update();
+            return false;
+        }
+        self.learned.learn(self.previous.as_deref(), &word);
+        if self.undo.len() == MAX_UNDO {
+            self.undo.remove(0);
+        }
+        self.undo.push(Pick { word: word.clone(), previous: self.previous.take() });
+        self.previous = Some(word);
+        self.update();
+        true
+    }
+
+    /// Takes back the last pick, for when the user backspaces over it.
+    pub fn unpick(&mut self) {
+        if let Some(pick) = self.undo.pop() {
+            self.learned.forget(pick.previous.as_deref(), &pick.word);
+            self.previous = pick.previous;
+        }
+        self.update();
+    }
+
+    /// Forgets what was being typed, so the next user of the predictor doesn't see it.
+    pub fn clear_context(&mut self) {
+        self.input.clear();
+        self.previous = None;
+        self.undo.clear();
+        self.update();
+    }
+
+    pub fn prediction(&self, index: usize) -> Option<&str> { self.predictions.get(index).map(|s| s.as_str()) }
+
+    fn update(&mut self) {
+        self.predictions = if self.input.is_empty() { self.next_words() } else { self.completions() };
+    }
+
+    fn completions(&self) -> Vec<String> {
+        let prefix = self.input.to_lowercase();
+        let mut scored: HashMap<&str, u32> = HashMap::new();
+        // the dictionary is sorted, so the matches are all in one run
+        let start = self.dictionary.partition_point(|(word, _)| word.as_str() < prefix.as_str());
+        for (word, freq) in self.dictionary[start..].iter().take_while(|(word, _)| word.starts_with(&prefix))
+        {
+            scored.insert(word.as_str(), *freq as u32);
+        }
+        for (word, count) in self.learned.words.iter() {
+            if word.starts_with(&prefix) {
+                *scored.entry(word.as_str()).or_insert(0) += count.saturating_mul(LEARNED_WEIGHT);
+            }
+        }
+        scored.remove(prefix.as_str());
+        let capitalize = self.input.chars().next().map(|c| c.is_uppercase()).unwrap_or(false);
+        top(scored).into_iter().map(|word| if capitalize { capitalized(&word) } else { word }).collect()
+    }
+
+    fn next_words(&self) -> Vec<String> {
+        match self.previous.as_ref().and_then(|previous| self.learned.followers.get(previous)) {
+            Some(followers) => top(followers.iter().map(|(word, count)| (word.as_str(), *count)).collect()),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// The best scoring words, ties going to the shorter and then the alphabetically first one so that
+/// predictions don't shuffle around between keystrokes.
+fn top(scored: HashMap<&str, u32>) -> Vec<String> {
+    let mut scored: Vec<(&str, u32)> = scored.into_iter().collect();
+    scored.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.len().cmp(&b.0.len())).then(a.0.cmp(b.0)));
+    scored.into_iter().take(MAX_PREDICTIONS).map(|(word, _)| word.to_string()).collect()
+}
+
+fn capitalized(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Whether something is worth learning as a word: letters, with apostrophes and hyphens inside.
+fn is_word(s: &str) -> bool {
+    s.chars().count() >= 2
+        && s.len() <= crate::frontcode::MAX_WORD_LEN
+        && s.chars().all(|c| c.is_alphabetic() || c == '\'' || c == '-')
+        && s.chars().next().map(|c| c.is_alphabetic()).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dictionary() -> Vec<(String, u8)> {
+        let mut words: Vec<(String, u8)> =
+            [("the", 255), ("there", 200), ("they", 210), ("then", 190), ("think", 150), ("this", 240)]
+                .iter()
+                .map(|(w, f)| (w.to_string(), *f))
+                .collect();
+        words.sort();
+        words
+    }
+
+    fn predictions(p: &Predictor) -> Vec<&str> {
+        (0..MAX_PREDICTIONS).filter_map(|i| p.prediction(i)).collect()
+    }
+
+    #[test]
+    fn test_completions() {
+        let mut p = Predictor::new(dictionary());
+        p.set_input("th");
+        assert_eq!(predictions(&p), vec!["the", "this", "they", "there"]);
+        // the typed word itself isn't offered back
+        p.set_input("the");
+        assert_eq!(predictions(&p), vec!["they", "there", "then"]);
+        p.set_input("The");
+        assert_eq!(predictions(&p), vec!["They", "There", "Then"]);
+        p.set_input("xyz");
+        assert!(predictions(&p).is_empty());
+    }
+
+    #[test]
+    fn test_learning() {
+        let mut p = Predictor::new(dictionary());
+        assert!(p.picked("thistle"));
+        assert!(p.picked("Thinking"));
+        assert!(!p.picked("42"));
+        assert!(!p.picked("https://betrusted.io"));
+        p.set_input("thi");
+        assert_eq!(predictions(&p), vec!["this", "think", "thistle", "thinking"]);
+        // the more often a word is picked, the further ahead it moves
+        p.picked("thinking");
+        p.set_input("thi");
+        assert_eq!(predictions(&p), vec!["this", "thinking", "think", "thistle"]);
+
+        // next-word predictions
+        p.picked("good");
+        p.picked("morning");
+        p.picked("good");
+        p.picked("night");
+        p.picked("good");
+        p.picked("morning");
+        p.picked("good");
+        assert_eq!(predictions(&p), vec!["morning", "night"]);
+
+        // backspacing over "good" takes it back, and the predictions follow "morning" again
+        p.unpick();
+        assert!(predictions(&p).contains(&"good"));
+        assert_eq!(p.learned.words["good"], 3);
+
+        p.clear_context();
+        assert!(predictions(&p).is_empty());
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut p = Predictor::new(Vec::new());
+        for word in ["see", "you", "later", "see", "you", "soon"].iter() {
+            p.picked(word);
+        }
+        let saved = p.learned.serialize();
+        assert_eq!(Learned::deserialize(&saved), p.learned);
+        let mut garbled = saved.clone();
+        garbled.push_str("w many words\nq 1 x\nw 3 \n");
+        assert_eq!(Learned::deserialize(&garbled), p.learned);
+
+        let mut merged = Learned::deserialize(&saved);
+        merged.merge(Learned::deserialize(&saved));
+        assert_eq!(merged.words["you"], 4);
+        assert_eq!(merged.followers["see"]["you"], 4);
+    }
+
+    #[test]
+    fn test_bounded() {
+        // "aa", "ab", ... so that every word is a different one
+        let word =
+            |i: usize| format!("{}{}", (b'a' + (i / 26 % 26) as u8) as char, (b'a' + (i % 26) as u8) as char);
+        let mut learned = Learned::default();
+        learned.learn(None, "keep");
+        learned.learn(None, "keep");
+        for i in 0..(MAX_LEARNED_WORDS + 10) {
+            learned.learn(Some("keep"), &format!("{}{}", word(i / 676), word(i)));
+        }
+        assert!(learned.words.len() <= MAX_LEARNED_WORDS);
+        assert!(learned.followers.values().all(|f| f.len() <= MAX_FOLLOWERS));
+        // the most used word survived the newcomers
+        assert_eq!(learned.words["keep"], 2);
+    }
+}
@@ -159,6 +159,8 @@ fn wrapped_main() -> ! {
             Some(Opcode::GetMutex) => msg_blocking_scalar_unpack!(msg, t0, t1, t2, t3, {
                 let incoming_token = [t0 as u32, t1 as u32, t2 as u32, t3 as u32];
                 if token_lock.is_none() {
+                    // each dialog is shown in the language picked when it was started
+                    gam::sync_language();
                     token_lock = Some(incoming_token);
                     xous::return_scalar(msg.sender, 1).unwrap();
                 } else {
@@ -422,7 +424,7 @@ fn wrapped_main() -> ! {
                     #[cfg(feature = "tts")]
                     {
                         if tt.elapsed_ms() - last_tick > TICK_INTERVAL {
-                            tts.tts_blocking(t!("progress.increment", locales::LANG)).unwrap();
+                            tts.tts_blocking(t!("progress.increment", locales::lang())).unwrap();
                             last_tick = tt.elapsed_ms();
                         }
                     }
@@ -514,7 +516,7 @@ fn wrapped_main() -> ! {
                         let phrase = renderer_modal
                             .gam
                             .bytes_to_bip39(&config.bip39_data[..config.bip39_len as usize].to_vec())
-                            .unwrap_or(vec![t!("bip39.invalid_bytes", locales::LANG).to_string()]);
+                            .unwrap_or(vec![t!("bip39.invalid_bytes", locales::lang()).to_string()]);
                         #[cfg(feature = "hazardous-debug")]
                         log::info!("BIP-39 phrase: {:?}", phrase);
 
@@ -616,7 +618,7 @@ fn wrapped_main() -> ! {
                         fixed_items.clear();
                         #[cfg(feature = "tts")]
                         {
-                            tts.tts_blocking(t!("modals.radiobutton", locales::LANG)).unwrap();
+                            tts.tts_blocking(t!("modals.radiobutton", locales::lang())).unwrap();
                             tts.tts_blocking(config.prompt.as_str()).unwrap();
                         }
                         renderer_modal.modify(
@@ -643,7 +645,7 @@ fn wrapped_main() -> ! {
                         fixed_items.clear();
                         #[cfg(feature = "tts")]
                         {
-                            tts.tts_blocking(t!("modals.checkbox", locales::LANG)).unwrap();
+                            tts.tts_blocking(t!("modals.checkbox", locales::lang())).unwrap();
                             tts.tts_blocking(config.prompt.as_str()).unwrap();
                         }
                         renderer_modal.modify(
@@ -1041,6 +1043,7 @@ fn next_lock(work_queue: &mut Vec<(xous::MessageSender, [u32; 4])>) -> Option<[u
         */
         let (unblock_pid, token) = work_queue.remove(0);
         log::debug!("next token: {:x?}", token);
+        gam::sync_language();
         xous::return_scalar(unblock_pid, 1).unwrap();
         Some(token)
     } else {
@@ -361,6 +361,9 @@ pub(crate) enum Opcode {
     StdTcpStreamShutdown = 46,
 
     LoopbackRx = 47,
+
+    /// [Internal] the USB network link went up (arg1 = 1) or down (arg1 = 0)
+    UsbLinkState = 48,
     // do not use any numbers higher than 0x8000 as that is reserved for the nonblocking flag
 }
 #[allow(dead_code)]
@@ -420,7 +423,7 @@ pub enum NetError {
     // Ok = 0,
     Unaddressable = 1,
     SocketInUse = 2,
-    // AccessDenied = 3,
+    AccessDenied = 3,
     Invalid = 4,
     // Finished = 5,
     LibraryError = 6,
@@ -12,6 +12,7 @@ use smoltcp::wire::{
     Ipv4Packet, Ipv4Repr, /* IpProtocol, TcpPacket, TcpRepr, IpAddress, UdpPacket, UdpRepr */
 };
 
+use crate::usb_link::{Port, USB_IPV4_ADDRESS, UsbLink};
 use crate::{IPV4_ADDRESS, MAC_ADDRESS_LSB, MAC_ADDRESS_MSB};
 
 pub struct NetPhy {
@@ -22,10 +23,11 @@ pub struct NetPhy {
     loopback_conn: xous::CID,
     // tracks the length (and count) of the loopback packets pending
     loopback_pending: Arc<Mutex<VecDeque<u16>>>,
+    usb: UsbLink,
 }
 
 impl<'a> NetPhy {
-    pub fn new(xns: &xous_names::XousNames, loopback_conn: xous::CID) -> NetPhy {
+    pub fn new(xns: &xous_names::XousNames, loopback_conn: xous::CID, usb: UsbLink) -> NetPhy {
         NetPhy {
             rx_buffer: [0; NET_MTU],
             tx_buffer: [0; NET_MTU],
@@ -33,9 +35,14 @@ impl<'a> NetPhy {
             rx_avail: None,
             loopback_conn,
             loopback_pending: Arc::new(Mutex::new(VecDeque::new())),
+            usb,
         }
     }
 
+    pub fn usb(&self) -> &UsbLink { &self.usb }
+
+    pub fn usb_mut(&mut self) -> &mut UsbLink { &mut self.usb }
+
     // returns None if there was a slot to put the availability into
     // returns Some(len) if not
     pub fn push_rx_avail(&mut self, len: u16) -> Option<u16> {
@@ -72,6 +79,7 @@ impl phy::Device for NetPhy {
                     loopback_conn: self.loopback_conn,
                     loopback_count: self.loopback_pending.clone(),
                     caps: csum_copy,
+                    usb: &self.usb,
                 },
             ))
         } else {
@@ -89,6 +97,23 @@ impl phy::Device for NetPhy {
                         loopback_conn: self.loopback_conn,
                         loopback_count: self.loopback_pending.clone(),
                         caps: csum_copy,
+                        usb: &self.usb,
+                    },
+                ))
+            } else if let Some(frame) = self.usb.take_frame() {
+                log::debug!("usb rx of {} bytes", frame.len());
+                let rx_len = frame.len().min(NET_MTU);
+                self.rx_buffer[..rx_len].copy_from_slice(&frame[..rx_len]);
+
+                Some((
+                    NetPhyRxToken { buf: &mut self.rx_buffer[..rx_len] },
+                    NetPhyTxToken {
+                        buf: &mut self.tx_buffer[..],
+                        com: &self.com,
+                        loopback_conn: self.loopback_conn,
+                        loopback_count: self.loopback_pending.clone(),
+                        caps: csum_copy,
+                        usb: &self.usb,
                     },
                 ))
             } else {
@@ -107,6 +132,7 @@ impl phy::Device for NetPhy {
             loopback_conn: self.loopback_conn,
             loopback_count: self.loopback_pending.clone(),
             caps: csum_copy,
+            usb: &self.usb,
         })
     }
 
@@ -140,6 +166,7 @@ pub struct NetPhyTxToken<'a> {
     loopback_conn: xous::CID,
     loopback_count: Arc<Mutex<VecDeque<u16>>>,
     caps: ChecksumCapabilities,
+    usb: &'a UsbLink,
 }
 impl<'a> NetPhyTxToken<'a> {
     /// Initiates the Rx side of things to read out the loopback packet that was queued
@@ -359,7 +386,8 @@ impl<'a> phy::TxToken for NetPhyTxToken<'a> {
                                 log::debug!("outgoing arp: {:?} {:?} {:?}", source_hardware_addr, source_protocol_addr, target_protocol_addr);
                                 let local_addr = IPV4_ADDRESS.load(Ordering::SeqCst).to_be_bytes();
                                 if (target_protocol_addr.as_bytes() == [127, 0, 0, 1]
-                                || target_protocol_addr.as_bytes() == local_addr)
+                                || target_protocol_addr.as_bytes() == local_addr
+                                || target_protocol_addr.as_bytes() == USB_IPV4_ADDRESS)
                                 && operation == ArpOperation::Request {
                                     log::debug!("intercepted outgoing arp for localhost: {:?}", arp_repr);
                                     let mut local_hwaddr = [0u8; 6];
@@ -397,7 +425,13 @@ impl<'a> phy::TxToken for NetPhyTxToken<'a> {
         }
         // forward the packet on if it's not a loopback (loopback will call return early and exit before
         // getting to this line)
-        self.com.wlan_send_packet(&self.buf[..len]).expect("driver error sending WLAN packet");
+        let port = self.usb.port(&self.buf[..len]);
+        if port != Port::Wlan {
+            self.usb.send(&self.buf[..len]);
+        }
+        if port != Port::Usb {
+            self.com.wlan_send_packet(&self.buf[..len]).expect("driver error sending WLAN packet");
+        }
 
         result
     }
@@ -12,10 +12,12 @@ use std_udp::*;
 mod std_tcplistener;
 use com::api::{ComIntSources, Ipv4Conf};
 use num_traits::*;
+use permissions_api::Capability;
 use std_tcplistener::*;
 
 mod connection_manager;
 mod device;
+mod usb_link;
 
 #[cfg(test)]
 mod tests;
@@ -133,6 +135,28 @@ fn set_com_ints(com_int_list: &mut Vec<ComIntSources>) {
     com_int_list.push(ComIntSources::Invalid);
 }
 
+/// Wi-Fi's address goes first, as `Interface::ipv4_addr()` is taken as "our address" wherever only one
+/// can be given; without Wi-Fi, that's the USB link's.
+fn set_ip_addrs(iface: &mut Interface, wlan_addr: Option<[u8; 4]>, usb_up: bool) {
+    iface.update_ip_addrs(|ip_addrs| {
+        ip_addrs.clear();
+        if let Some(addr) = wlan_addr {
+            ip_addrs.push(IpCidr::new(IpAddress::v4(addr[0], addr[1], addr[2], addr[3]), 24)).unwrap();
+        }
+        if usb_up {
+            let addr = usb_link::USB_IPV4_ADDRESS;
+            ip_addrs
+                .push(IpCidr::new(
+                    IpAddress::v4(addr[0], addr[1], addr[2], addr[3]),
+                    usb_link::USB_IPV4_PREFIX,
+                ))
+                .unwrap();
+        }
+        // ...and the loopback interface
+        ip_addrs.push(IpCidr::new(IpAddress::v4(127, 0, 0, 1), 8)).unwrap();
+    });
+}
+
 fn main() -> ! {
     log_server::init_wait().unwrap();
     log::set_max_level(log::LevelFilter::Info);
@@ -150,6 +174,8 @@ fn main() -> ! {
 
     // we need a trng for port numbers
     let mut trng = trng::Trng::new(&xns).unwrap();
+    // apps need the `network` capability to open sockets
+    let permissions = permissions_api::Permissions::new();
 
     // hook the COM interrupt listener
     let net_cid = xous::connect(net_sid).unwrap();
@@ -211,7 +237,7 @@ fn main() -> ! {
     };
     config.random_seed = trng.get_u64().unwrap();
 
-    let device = device::NetPhy::new(&xns, net_cid);
+    let device = device::NetPhy::new(&xns, net_cid, usb_link::UsbLink::new(net_cid));
     let mut device = Tracer::new(device, |_timestamp, _printer| {
         log::trace!("{}", _printer);
     });
@@ -353,6 +379,9 @@ fn main() -> ! {
                             &mut device,
                             Instant::from_millis(timer.elapsed_ms() as i64),
                         );
+                        if device.get_mut().usb().is_up() {
+                            set_ip_addrs(&mut iface, None, true);
+                        }
                         config_valid = true;
                     } else {
                         // else, config_valid stays false, and we try again next time around
@@ -601,6 +630,10 @@ fn main() -> ! {
             }),
 
             Some(Opcode::StdTcpConnect) => {
+                if !permissions.try_check(msg.sender, Capability::Network) {
+                    respond_with_error(msg, NetError::AccessDenied);
+                    continue;
+                }
                 // Pick a random local port using the system's TRNG
                 let local_port = (trng.get_u32().unwrap() % 16384 + 49152) as u16;
                 let pid = msg.sender.pid();
@@ -826,6 +859,10 @@ fn main() -> ! {
             }
 
             Some(Opcode::StdTcpListen) => {
+                if !permissions.try_check(msg.sender, Capability::Network) {
+                    respond_with_error(msg, NetError::AccessDenied);
+                    continue;
+                }
                 let pid = msg.sender.pid();
 
                 std_tcp_listen(msg, &mut iface, &mut sockets, process_sockets.entry(pid).or_default(), &trng);
@@ -988,6 +1025,10 @@ fn main() -> ! {
 
             Some(Opcode::StdUdpBind) => {
                 log::debug!("StdUdpBind");
+                if !permissions.try_check(msg.sender, Capability::Network) {
+                    respond_with_error(msg, NetError::AccessDenied);
+                    continue;
+                }
                 let pid = msg.sender.pid();
                 std_udp_bind(msg, &mut iface, &mut sockets, process_sockets.entry(pid).or_default());
             }
@@ -1109,24 +1150,8 @@ fn main() -> ! {
 
                                     if config.addr != [127, 0, 0, 1] {
                                         // note: ARP cache is stale. Maybe that's ok?
-                                        iface.update_ip_addrs(|ip_addrs| {
-                                            ip_addrs.clear();
-                                            ip_addrs
-                                                .push(IpCidr::new(
-                                                    IpAddress::v4(
-                                                        config.addr[0],
-                                                        config.addr[1],
-                                                        config.addr[2],
-                                                        config.addr[3],
-                                                    ),
-                                                    24,
-                                                ))
-                                                .unwrap();
-                                            // ...and the loopback interface
-                                            ip_addrs
-                                                .push(IpCidr::new(IpAddress::v4(127, 0, 0, 1), 8))
-                                                .unwrap();
-                                        });
+                                        let usb_up = device.get_mut().usb().is_up();
+                                        set_ip_addrs(&mut iface, Some(config.addr), usb_up);
                                     } else {
                                         log::warn!("Attempt to update the loopback interface! Ignoring.");
                                     }
@@ -1257,6 +1282,12 @@ fn main() -> ! {
                     }
                 }
             }),
+            Some(Opcode::UsbLinkState) => msg_scalar_unpack!(msg, up, _, _, _, {
+                log::info!("USB network link {}", if up != 0 { "up" } else { "down" });
+                device.get_mut().usb_mut().set_up(up != 0);
+                let wlan_addr = net_config.map(|config| config.addr).filter(|addr| *addr != [127, 0, 0, 1]);
+                set_ip_addrs(&mut iface, wlan_addr, up != 0);
+            }),
             Some(Opcode::NetPump) => msg_scalar_unpack!(msg, _, _, _, _, {
                 log::trace!("NetPump");
                 let now = timer.elapsed_ms();
@@ -38,6 +38,7 @@ pub(crate) fn std_tcp_listen(
     if address.as_bytes() != [0, 0, 0, 0]
         && address.as_bytes() != [127, 0, 0, 1]
         && address.as_bytes() != IPV4_ADDRESS.load(Ordering::SeqCst).to_be_bytes()
+        && address.as_bytes() != usb_link::USB_IPV4_ADDRESS
     {
         std_failure(msg, NetError::Invalid);
         return;
@@ -0,0 +1,249 @@
+//! The USB network adapter, run as a second Ethernet port of the one smoltcp interface. A second
+//! `Interface` would not keep the two apart: sockets are polled by every interface they are handed to,
+//! so instead each outgoing frame is steered to the port its destination was seen on.
+//!
+//! There is no DHCP on the cable. The device answers at `USB_IPV4_ADDRESS`, in the IPv4 link-local
+//! range, so a host that falls back to link-local addressing reaches it with no setup; otherwise give
+//! the host's end any other address in 169.254.0.0/16.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use num_traits::*;
+use smoltcp::wire::{
+    ArpPacket, ArpRepr, EthernetAddress, EthernetFrame, EthernetProtocol, Ipv4Address, Ipv4Cidr, Ipv4Packet,
+};
+use usb_device_xous::{NetEvent, UsbHid};
+
+use crate::api::Opcode;
+
+pub const USB_IPV4_ADDRESS: [u8; 4] = [169, 254, 42, 1];
+pub const USB_IPV4_PREFIX: u8 = 16;
+/// Hosts seen on the cable. Normally that's just the one at the other end, but a bridge or VMs behind it
+/// add a few more.
+const MAX_PEERS: usize = 8;
+/// Frames from the host that smoltcp hasn't picked up yet
+const RX_QUEUE_DEPTH: usize = 32;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Port {
+    Wlan,
+    Usb,
+    Both,
+}
+
+pub struct UsbLink {
+    rx: Arc<Mutex<VecDeque<Vec<u8>>>>,
+    /// connected once the link first comes up, by which time the USB server is known to be running
+    tx: Option<UsbHid>,
+    up: bool,
+    /// most recently heard from first
+    peers: Vec<EthernetAddress>,
+}
+
+impl UsbLink {
+    /// Starts listening to the USB network adapter. Frames from the host are queued here and announced
+    /// with a `NetPump`; link changes come in as `UsbLinkState`.
+    pub fn new(net_conn: xous::CID) -> UsbLink {
+        let rx = Arc::new(Mutex::new(VecDeque::new()));
+        std::thread::spawn({
+            let rx = rx.clone();
+            move || {
+                let usb = UsbHid::new();
+                loop {
+                    let opcode = match usb.net_wait_event() {
+                        Ok(NetEvent::Frame(frame)) => {
+                            let mut rx = rx.lock().unwrap();
+                            if rx.len() >= RX_QUEUE_DEPTH {
+                                log::debug!("USB rx queue full, dropping a frame");
+                                continue;
+                            }
+                            rx.push_back(frame);
+                            xous::Message::new_scalar(Opcode::NetPump.to_usize().unwrap(), 0, 0, 0, 0)
+                        }
+                        Ok(NetEvent::LinkUp) => {
+                            xous::Message::new_scalar(Opcode::UsbLinkState.to_usize().unwrap(), 1, 0, 0, 0)
+                        }
+                        Ok(NetEvent::LinkDown) => {
+                            xous::Message::new_scalar(Opcode::UsbLinkState.to_usize().unwrap(), 0, 0, 0, 0)
+                        }
+                        Err(e) => {
+                            log::warn!("USB network link unavailable: {:?}", e);
+                            return;
+                        }
+                    };
+                    match xous::try_send_message(net_conn, opcode) {
+                        Ok(_) => {}
+                        Err(xous::Error::ServerQueueFull) => {
+                            log::warn!("Our net queue runneth over, packets will be dropped.");
+                        }
+                        Err(e) => {
+                            log::error!("Unhandled error sending USB link event to self: {:?}", e);
+                        }
+                    }
+                }
+            }
+        });
+        UsbLink { rx, tx: None, up: false, peers: Vec::new() }
+    }
+
+    pub fn is_up(&self) -> bool { self.up }
+
+    pub fn set_up(&mut self, up: bool) {
+        self.up = up;
+        if up {
+            if self.tx.is_none() {
+                self.tx = Some(UsbHid::new());
+            }
+        } else {
+            self.rx.lock().unwrap().clear();
+            self.peers.clear();
+        }
+    }
+
+    /// Takes the next frame from the host, noting where its sender lives.
+    pub fn take_frame(&mut self) -> Option<Vec<u8>> {
+        let frame = self.rx.lock().unwrap().pop_front()?;
+        if let Ok(eth_frame) = EthernetFrame::new_checked(&frame[..]) {
+            learn_peer(&mut self.peers, eth_frame.src_addr());
+        }
+        Some(frame)
+    }
+
+    pub fn port(&self, frame: &[u8]) -> Port { steer(frame, &self.peers, self.up) }
+
+    pub fn send(&self, frame: &[u8]) {
+        if let Some(usb) = self.tx.as_ref() {
+            if let Err(e) = usb.net_send_frame(frame) {
+                log::debug!("couldn't send a frame over USB: {:?}", e);
+            }
+        }
+    }
+}
+
+fn learn_peer(peers: &mut Vec<EthernetAddress>, addr: EthernetAddress) {
+    if !addr.is_unicast() {
+        return;
+    }
+    peers.retain(|&peer| peer != addr);
+    peers.truncate(MAX_PEERS - 1);
+    peers.insert(0, addr);
+}
+
+/// Picks the port for an outgoing frame. Unicast follows the destination's MAC address; ARP and IPv4
+/// broadcasts follow the addresses inside; anything else is sent on both ports while the cable is up.
+pub fn steer(frame: &[u8], peers: &[EthernetAddress], usb_up: bool) -> Port {
+    if !usb_up {
+        return Port::Wlan;
+    }
+    let eth_frame = match EthernetFrame::new_checked(frame) {
+        Ok(eth_frame) => eth_frame,
+        Err(_) => return Port::Wlan,
+    };
+    let dst = eth_frame.dst_addr();
+    if dst.is_unicast() {
+        return if peers.contains(&dst) { Port::Usb } else { Port::Wlan };
+    }
+    let usb_subnet = Ipv4Cidr::new(Ipv4Address(USB_IPV4_ADDRESS), USB_IPV4_PREFIX);
+    match eth_frame.ethertype() {
+        EthernetProtocol::Arp => {
+            match ArpPacket::new_checked(eth_frame.payload()).and_then(|packet| ArpRepr::parse(&packet)) {
+                Ok(ArpRepr::EthernetIpv4 { source_protocol_addr, target_protocol_addr, .. }) => {
+                    if usb_subnet.contains_addr(&target_protocol_addr)
+                        || source_protocol_addr == Ipv4Address(USB_IPV4_ADDRESS)
+                    {
+                        Port::Usb
+                    } else {
+                        Port::Wlan
+                    }
+                }
+                _ => Port::Wlan,
+            }
+        }
+        EthernetProtocol::Ipv4 => match Ipv4Packet::new_checked(eth_frame.payload()) {
+            Ok(packet) if packet.src_addr() == Ipv4Address(USB_IPV4_ADDRESS) => Port::Usb,
+            Ok(_) => Port::Wlan,
+            Err(_) => Port::Wlan,
+        },
+        _ => Port::Both,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use smoltcp::wire::{ArpOperation, IpProtocol, Ipv4Repr};
+
+    use super::*;
+
+    const OURS: EthernetAddress = EthernetAddress([0x02, 0, 0, 0, 0, 1]);
+    const HOST: EthernetAddress = EthernetAddress([0x02, 0, 0, 0, 0, 2]);
+    const ROUTER: EthernetAddress = EthernetAddress([0x02, 0, 0, 0, 0, 3]);
+
+    fn frame(dst: EthernetAddress, ethertype: EthernetProtocol, payload: &[u8]) -> Vec<u8> {
+        let mut buf = vec![0u8; 14 + payload.len()];
+        let mut eth_frame = EthernetFrame::new_unchecked(&mut buf[..]);
+        eth_frame.set_dst_addr(dst);
+        eth_frame.set_src_addr(OURS);
+        eth_frame.set_ethertype(ethertype);
+        eth_frame.payload_mut().copy_from_slice(payload);
+        buf
+    }
+
+    fn arp_request(source: [u8; 4], target: [u8; 4]) -> Vec<u8> {
+        let repr = ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: OURS,
+            source_protocol_addr: Ipv4Address(source),
+            target_hardware_addr: EthernetAddress([0; 6]),
+            target_protocol_addr: Ipv4Address(target),
+        };
+        let mut payload = vec![0u8; repr.buffer_len()];
+        repr.emit(&mut ArpPacket::new_unchecked(&mut payload[..]));
+        frame(EthernetAddress::BROADCAST, EthernetProtocol::Arp, &payload)
+    }
+
+    fn udp_broadcast(source: [u8; 4]) -> Vec<u8> {
+        let repr = Ipv4Repr {
+            src_addr: Ipv4Address(source),
+            dst_addr: Ipv4Address::BROADCAST,
+            next_header: IpProtocol::Udp,
+            payload_len: 0,
+            hop_limit: 64,
+        };
+        let mut payload = vec![0u8; repr.buffer_len()];
+        repr.emit(&mut Ipv4Packet::new_unchecked(&mut payload[..]), &Default::default());
+        frame(EthernetAddress::BROADCAST, EthernetProtocol::Ipv4, &payload)
+    }
+
+    #[test]
+    fn test_steer() {
+        let peers = [HOST];
+        let to_host = frame(HOST, EthernetProtocol::Ipv4, &[0u8; 20]);
+        assert_eq!(steer(&to_host, &peers, true), Port::Usb);
+        assert_eq!(steer(&to_host, &peers, false), Port::Wlan);
+        assert_eq!(steer(&frame(ROUTER, EthernetProtocol::Ipv4, &[0u8; 20]), &peers, true), Port::Wlan);
+
+        assert_eq!(steer(&arp_request([169, 254, 42, 1], [169, 254, 7, 9]), &peers, true), Port::Usb);
+        assert_eq!(steer(&arp_request([10, 0, 245, 3], [10, 0, 245, 1]), &peers, true), Port::Wlan);
+        assert_eq!(steer(&udp_broadcast(USB_IPV4_ADDRESS), &peers, true), Port::Usb);
+        assert_eq!(steer(&udp_broadcast([10, 0, 245, 3]), &peers, true), Port::Wlan);
+
+        let multicast = EthernetAddress([0x33, 0x33, 0, 0, 0, 1]);
+        assert_eq!(steer(&frame(multicast, EthernetProtocol::Ipv6, &[0u8; 40]), &peers, true), Port::Both);
+        assert_eq!(steer(&frame(multicast, EthernetProtocol::Ipv6, &[0u8; 40]), &peers, false), Port::Wlan);
+    }
+
+    #[test]
+    fn test_learn_peer() {
+        let mut peers = Vec::new();
+        learn_peer(&mut peers, EthernetAddress::BROADCAST);
+        assert!(peers.is_empty());
+        for i in 0..(MAX_PEERS as u8 + 2) {
+            learn_peer(&mut peers, EthernetAddress([0x02, 0, 0, 0, 1, i]));
+        }
+        learn_peer(&mut peers, EthernetAddress([0x02, 0, 0, 0, 1, 5]));
+        assert_eq!(peers.len(), MAX_PEERS);
+        assert_eq!(peers[0], EthernetAddress([0x02, 0, 0, 0, 1, 5]));
+        assert!(!peers.contains(&EthernetAddress([0x02, 0, 0, 0, 1, 0])));
+    }
+}
@@ -440,6 +440,8 @@ pub enum PddbBulkReadCode {
     NotFound = 4,
     /// PDDB internal error
     InternalError = 5,
+    /// the app isn't allowed to use the dictionary
+    AccessDenied = 6,
 }
 
 #[derive(Default, Debug)]
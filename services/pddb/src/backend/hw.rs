@@ -882,7 +882,7 @@ impl PddbOs {
             let xns = xous_names::XousNames::new().unwrap();
             let modals = modals::Modals::new(&xns).expect("can't connect to Modals server");
             modals
-                .show_notification(t!("pddb.badpass_infallible", locales::LANG), None)
+                .show_notification(t!("pddb.badpass_infallible", locales::lang()), None)
                 .expect("notification failed");
         }
     }
@@ -1988,7 +1988,9 @@ impl PddbOs {
 
             log::info!("{}PDDB.CHECKPASS,{}", xous::BOOKEND_START, xous::BOOKEND_END);
             #[cfg(any(feature = "precursor", feature = "renode"))] // skip this dialog in hosted mode
-            modals.show_notification(t!("pddb.checkpass", locales::LANG), None).expect("notification failed");
+            modals
+                .show_notification(t!("pddb.checkpass", locales::lang()), None)
+                .expect("notification failed");
 
             self.clear_password();
             let mut checkblock_b = [0u8; BLOCK_SIZE];
@@ -1999,7 +2001,7 @@ impl PddbOs {
             } else {
                 log::info!("{}PDDB.PWFAIL,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                 modals
-                    .show_notification(t!("pddb.checkpass_fail", locales::LANG), None)
+                    .show_notification(t!("pddb.checkpass_fail", locales::lang()), None)
                     .expect("notification failed");
                 self.clear_password();
             }
@@ -2013,7 +2015,7 @@ impl PddbOs {
             // get the new password
             self.clear_password();
             modals
-                .show_notification(t!("pddb.changepin.enter_new_pin", locales::LANG), None)
+                .show_notification(t!("pddb.changepin.enter_new_pin", locales::lang()), None)
                 .map_err(|_| Error::new(ErrorKind::Other, "Internal error"))?;
             self.pw_check(modals)?;
 
@@ -2060,7 +2062,7 @@ impl PddbOs {
             if let Some(modals) = progress {
                 modals
                     .start_progress(
-                        t!("pddb.erase", locales::LANG),
+                        t!("pddb.erase", locales::lang()),
                         xous::PDDB_LOC,
                         xous::PDDB_LOC + PDDB_A_LEN as u32,
                         xous::PDDB_LOC,
@@ -2109,7 +2111,12 @@ impl PddbOs {
         // step 2. fill in the page table with junk, which marks it as cryptographically empty
         if let Some(modals) = progress {
             modals
-                .start_progress(t!("pddb.initpt", locales::LANG), 0, size_of::<PageTableInFlash>() as u32, 0)
+                .start_progress(
+                    t!("pddb.initpt", locales::lang()),
+                    0,
+                    size_of::<PageTableInFlash>() as u32,
+                    0,
+                )
                 .expect("couldn't raise progress bar");
         }
         let mut temp: [u8; PAGE_SIZE] = [0; PAGE_SIZE];
@@ -2152,7 +2159,7 @@ impl PddbOs {
         //}
         if let Some(modals) = progress {
             modals
-                .start_progress(t!("pddb.key", locales::LANG), 0, 100, 0)
+                .start_progress(t!("pddb.key", locales::lang()), 0, 100, 0)
                 .expect("couldn't raise progress bar");
             #[cfg(feature = "ux-swap-delay")]
             self.tt.sleep_ms(100).unwrap();
@@ -2218,7 +2225,7 @@ impl PddbOs {
         // pass the generator an empty cache - this causes it to treat the entire disk as free space
         if let Some(modals) = progress {
             modals
-                .start_progress(t!("pddb.fastspace", locales::LANG), 0, 100, 0)
+                .start_progress(t!("pddb.fastspace", locales::lang()), 0, 100, 0)
                 .expect("couldn't raise progress bar");
             self.tt.sleep_ms(100).unwrap();
         }
@@ -2251,7 +2258,7 @@ impl PddbOs {
         if let Some(modals) = progress {
             modals
                 .start_progress(
-                    t!("pddb.randomize", locales::LANG),
+                    t!("pddb.randomize", locales::lang()),
                     self.data_phys_base.as_u32(),
                     PDDB_A_LEN as u32,
                     self.data_phys_base.as_u32(),
@@ -2310,7 +2317,7 @@ impl PddbOs {
         // step 6. create the system basis root structure
         if let Some(modals) = progress {
             modals
-                .start_progress(t!("pddb.structure", locales::LANG), 0, 100, 0)
+                .start_progress(t!("pddb.structure", locales::lang()), 0, 100, 0)
                 .expect("couldn't raise progress bar");
             #[cfg(feature = "ux-swap-delay")]
             self.tt.sleep_ms(100).unwrap();
@@ -2481,7 +2488,7 @@ impl PddbOs {
             let xns = xous_names::XousNames::new().unwrap();
             let modals = modals::Modals::new(&xns).unwrap();
 
-            modals.start_progress(t!("pddb.rekey.keys", locales::LANG), 0, all_keys.len() as u32, 0).ok();
+            modals.start_progress(t!("pddb.rekey.keys", locales::lang()), 0, all_keys.len() as u32, 0).ok();
             // we need a map of page numbers to encryption keys. The keys are referenced by their basis name.
             let mut pagemap = HashMap::<PhysAddr, &str>::new();
             // transform the returned Vec into a HashMap that maps basis names into pre-keyed ciphers.
@@ -2553,7 +2560,7 @@ impl PddbOs {
             let entries_per_page = PAGE_SIZE / size_of::<Pte>();
             modals
                 .start_progress(
-                    t!("pddb.rekey.running", locales::LANG),
+                    t!("pddb.rekey.running", locales::lang()),
                     0,
                     (pddb_data_pages * size_of::<Pte>()) as u32,
                     0,
@@ -2675,7 +2682,7 @@ impl PddbOs {
             };
             if do_fscb {
                 log::info!("regenerating fast space...");
-                modals.dynamic_notification(Some(t!("pddb.rekey.fastspace", locales::LANG)), None).ok();
+                modals.dynamic_notification(Some(t!("pddb.rekey.fastspace", locales::lang())), None).ok();
                 // convert our used page map into the structure needed by fast_space_generate()
                 let mut page_heap = BinaryHeap::new();
                 // drain doesn't actually de-allocate memory, but it gives us an opportunity
@@ -2748,9 +2755,9 @@ impl PddbOs {
         modals
             .show_notification(
                 match self.dna_mode {
-                    DnaMode::Normal => t!("pddb.freespace.request", locales::LANG),
-                    DnaMode::Migration => t!("pddb.rekey.request", locales::LANG),
-                    DnaMode::Churn => t!("pddb.churn.request", locales::LANG),
+                    DnaMode::Normal => t!("pddb.freespace.request", locales::lang()),
+                    DnaMode::Migration => t!("pddb.rekey.request", locales::lang()),
+                    DnaMode::Churn => t!("pddb.churn.request", locales::lang()),
                 },
                 None,
             )
@@ -2759,7 +2766,7 @@ impl PddbOs {
         self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
 
         // 0.5 display the Bases that we know
-        let mut blist = String::from(t!("pddb.freespace.currentlist", locales::LANG));
+        let mut blist = String::from(t!("pddb.freespace.currentlist", locales::lang()));
         for (_key, name) in ret.iter() {
             blist.push_str("\n");
             blist.push_str(name);
@@ -2769,11 +2776,11 @@ impl PddbOs {
         self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
 
         // 1. prompt user to enter any name/password combos for other basis we want to keep
-        while self.yes_no_approval(&modals, t!("pddb.freespace.enumerate_another", locales::LANG)) {
+        while self.yes_no_approval(&modals, t!("pddb.freespace.enumerate_another", locales::lang())) {
             #[cfg(feature = "ux-swap-delay")]
             self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
 
-            match modals.alert_builder(t!("pddb.freespace.name", locales::LANG)).field(None, None).build() {
+            match modals.alert_builder(t!("pddb.freespace.name", locales::lang())).field(None, None).build() {
                 Ok(bname) => {
                     let name = bname.first().as_str().to_string();
                     let request =
@@ -2808,7 +2815,7 @@ impl PddbOs {
                     if let Some((basis_key, name)) = maybe_entry {
                         ret.push((basis_key, name));
                     } else {
-                        modals.show_notification(t!("pddb.freespace.badpass", locales::LANG), None).ok();
+                        modals.show_notification(t!("pddb.freespace.badpass", locales::lang()), None).ok();
                         #[cfg(feature = "ux-swap-delay")]
                         self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
                     }
@@ -2818,7 +2825,7 @@ impl PddbOs {
             #[cfg(feature = "ux-swap-delay")]
             self.tt.sleep_ms(SWAP_DELAY_MS).unwrap();
             // 4. repeat summary print-out
-            let mut blist = String::from(t!("pddb.freespace.currentlist", locales::LANG));
+            let mut blist = String::from(t!("pddb.freespace.currentlist", locales::lang()));
             for (_key, name) in ret.iter() {
                 blist.push_str("\n");
                 blist.push_str(name);
@@ -2831,9 +2838,9 @@ impl PddbOs {
         if self.yes_no_approval(
             &modals,
             match self.dna_mode {
-                DnaMode::Normal => t!("pddb.freespace.finished", locales::LANG),
-                DnaMode::Migration => t!("pddb.rekey.finished", locales::LANG),
-                DnaMode::Churn => t!("pddb.churn.finished", locales::LANG),
+                DnaMode::Normal => t!("pddb.freespace.finished", locales::lang()),
+                DnaMode::Migration => t!("pddb.rekey.finished", locales::lang()),
+                DnaMode::Churn => t!("pddb.churn.finished", locales::lang()),
             },
         ) {
             Some(ret)
@@ -2844,11 +2851,11 @@ impl PddbOs {
 
     fn yes_no_approval(&self, modals: &modals::Modals, request: &str) -> bool {
         modals
-            .add_list(vec![t!("pddb.yes", locales::LANG), t!("pddb.no", locales::LANG)])
+            .add_list(vec![t!("pddb.yes", locales::lang()), t!("pddb.no", locales::lang())])
             .expect("couldn't build confirmation dialog");
         match modals.get_radiobutton(request) {
             Ok(response) => {
-                if &response == t!("pddb.yes", locales::LANG) {
+                if &response == t!("pddb.yes", locales::lang()) {
                     true
                 } else {
                     false
@@ -2943,7 +2950,7 @@ impl PddbOs {
         let mut checksums = root_keys::api::Checksums::default();
         let pddb = unsafe { self.pddb_mr.as_slice() };
         if let Some(m) = modals {
-            m.start_progress(t!("pddb.checksums", locales::LANG), 0, checksums.checksums.len() as u32, 0)
+            m.start_progress(t!("pddb.checksums", locales::lang()), 0, checksums.checksums.len() as u32, 0)
                 .ok();
         }
         for (index, region) in
@@ -3428,14 +3435,14 @@ impl PddbOs {
                 );
                 loop {
                     modals
-                        .add_list_item(t!("pddb.yes", locales::LANG))
+                        .add_list_item(t!("pddb.yes", locales::lang()))
                         .expect("couldn't build radio item list");
                     modals
-                        .add_list_item(t!("pddb.no", locales::LANG))
+                        .add_list_item(t!("pddb.no", locales::lang()))
                         .expect("couldn't build radio item list");
                     match modals.get_radiobutton(&prompt) {
                         Ok(response) => {
-                            if response.as_str() == t!("pddb.yes", locales::LANG) {
+                            if response.as_str() == t!("pddb.yes", locales::lang()) {
                                 match modals
                                     .alert_builder("Enter the Basis name")
                                     .field(Some("My Secret Basis".to_string()), None)
@@ -3526,7 +3533,7 @@ impl PddbOs {
                                         prompt.push_str("Error unlocking Basis, retry?");
                                     }
                                 }
-                            } else if response.as_str() == t!("pddb.no", locales::LANG) {
+                            } else if response.as_str() == t!("pddb.no", locales::lang()) {
                                 break;
                             } else {
                                 log::warn!("Got unexpected return from radiobutton: {}", response);
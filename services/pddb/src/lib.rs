@@ -350,6 +350,9 @@ impl Pddb {
             PddbRequestCode::DuplicateEntry => {
                 Err(Error::new(ErrorKind::AlreadyExists, "Basis already exists"))
             }
+            PddbRequestCode::AccessDenied => {
+                Err(Error::new(ErrorKind::PermissionDenied, "App is not allowed to use this basis"))
+            }
             _ => {
                 log::error!("Invalid return code");
                 panic!("Invalid return code");
@@ -369,7 +372,7 @@ impl Pddb {
         match ret.code {
             PddbRequestCode::NoErr => Ok(()),
             PddbRequestCode::AccessDenied => {
-                Err(Error::new(ErrorKind::PermissionDenied, "Authentication error"))
+                Err(Error::new(ErrorKind::PermissionDenied, "Authentication error, or app is not allowed"))
             }
             PddbRequestCode::InternalError => {
                 Err(Error::new(ErrorKind::Other, "Internal error creating basis"))
@@ -394,6 +397,9 @@ impl Pddb {
         match ret.code {
             PddbRequestCode::NoErr => Ok(()),
             PddbRequestCode::NotFound => Err(Error::new(ErrorKind::NotFound, "Basis not found")),
+            PddbRequestCode::AccessDenied => {
+                Err(Error::new(ErrorKind::PermissionDenied, "App is not allowed to use this basis"))
+            }
             PddbRequestCode::InternalError => {
                 Err(Error::new(ErrorKind::Other, "Internal error closing basis"))
             }
@@ -417,6 +423,9 @@ impl Pddb {
         match ret.code {
             PddbRequestCode::NoErr => Ok(()),
             PddbRequestCode::NotFound => Err(Error::new(ErrorKind::NotFound, "Basis not found")),
+            PddbRequestCode::AccessDenied => {
+                Err(Error::new(ErrorKind::PermissionDenied, "App is not allowed to use this basis"))
+            }
             PddbRequestCode::InternalError => {
                 Err(Error::new(ErrorKind::Other, "Internal error deleting basis"))
             }
@@ -568,6 +577,9 @@ impl Pddb {
             PddbRequestCode::NotFound => {
                 Err(Error::new(ErrorKind::NotFound, "Dictionary or key was not found"))
             }
+            PddbRequestCode::AccessDenied => {
+                Err(Error::new(ErrorKind::PermissionDenied, "App is not allowed to use this dictionary"))
+            }
             _ => Err(Error::new(ErrorKind::Other, "Internal error")),
         }
     }
@@ -678,6 +690,9 @@ impl Pddb {
             PddbRequestCode::NotFound => {
                 Err(Error::new(ErrorKind::NotFound, "Dictionary or key was not found"))
             }
+            PddbRequestCode::AccessDenied => {
+                Err(Error::new(ErrorKind::PermissionDenied, "App is not allowed to use this dictionary"))
+            }
             _ => Err(Error::new(ErrorKind::Other, "Internal error")),
         }
     }
@@ -774,7 +789,10 @@ impl Pddb {
             PddbRequestCode::NoErr => (),
             PddbRequestCode::NotFound => return Err(Error::new(ErrorKind::NotFound, "dictionary not found")),
             PddbRequestCode::AccessDenied => {
-                return Err(Error::new(ErrorKind::PermissionDenied, "concurrent operation in progress"));
+                return Err(Error::new(
+                    ErrorKind::PermissionDenied,
+                    "concurrent operation in progress, or app is not allowed to use this dictionary",
+                ));
             }
             PddbRequestCode::Uninit => {
                 return Err(Error::new(
@@ -1136,6 +1154,13 @@ impl Pddb {
                     xous::unmap_memory(pages).unwrap();
                     return Err(Error::new(ErrorKind::TimedOut, "PDDB server busy or request timed out"));
                 }
+                PddbBulkReadCode::AccessDenied => {
+                    xous::unmap_memory(pages).unwrap();
+                    return Err(Error::new(
+                        ErrorKind::PermissionDenied,
+                        format!("Bulk Read: app is not allowed to use dictionary '{}'", dict),
+                    ));
+                }
                 PddbBulkReadCode::Streaming | PddbBulkReadCode::Last => {
                     // --- unpack the received data
                     // stash the token for future iterations
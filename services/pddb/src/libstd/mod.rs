@@ -38,6 +38,36 @@ fn get_fd(
     Ok(fd)
 }
 
+/// The dictionary that a libstd request is about, for checking that its sender may use it. Dictionaries
+/// nest, and the grant for one covers those nested in it, so this is the first component of the path after
+/// the basis. Requests about the whole PDDB, like listing the root, are about `*`.
+pub(crate) fn requested_dict(mem: &xous::MemoryMessage, tag: [u8; 4]) -> String {
+    dict_in_request(mem.buf.as_slice(), tag)
+}
+
+fn dict_in_request(data: &[u8], tag: [u8; 4]) -> String {
+    let backing = match senres::Message::from_slice(data) {
+        Ok(backing) => backing,
+        Err(_) => return "*".to_owned(),
+    };
+    let path = match backing.reader(tag) {
+        // the dictionary is the only path in a key listing, and follows the basis
+        Some(reader) if &tag == b"LiKQ" => reader
+            .try_get_from::<Option<String>>()
+            .and_then(|_| reader.try_get_ref_from::<str>())
+            .map(|dict| dict.to_owned()),
+        Some(reader) => reader.try_get_ref_from::<str>().map(|path| path.to_owned()),
+        None => return "*".to_owned(),
+    };
+    match path.ok().and_then(|path| utils::split_basis_and_dict(&path, || None).ok()) {
+        Some((_, Some(dict))) => match dict.split(utils::MAIN_SEP).next() {
+            Some(first) if !first.is_empty() => first.to_owned(),
+            _ => "*".to_owned(),
+        },
+        _ => "*".to_owned(),
+    }
+}
+
 pub(crate) fn stat_path(
     mem: &mut xous::MemoryMessage,
     pddb_os: &mut PddbOs,
@@ -658,10 +688,12 @@ pub(crate) fn read_key(
     Err(retcode)
 }
 
+/// Lists the dictionaries in a basis, leaving out those that `allowed` says the caller may not see
 pub(crate) fn list_dict(
     mem: &mut xous::MemoryMessage,
     pddb_os: &mut PddbOs,
     basis_cache: &mut BasisCache,
+    allowed: impl Fn(&str) -> bool,
 ) -> Result<(), crate::PddbRetcode> {
     // Safety: the memory message must be aligned, and we test for validity here
     let mut backing = unsafe { senres::Message::from_mut_slice(mem.buf.as_slice_mut()) }
@@ -674,7 +706,11 @@ pub(crate) fn list_dict(
 
     let mut writer = backing.writer(*b"LiDR").ok_or(crate::PddbRetcode::InternalError)?;
 
-    let dict_list = basis_cache.dict_list(pddb_os, bname.as_deref());
+    let dict_list: Vec<String> = basis_cache
+        .dict_list(pddb_os, bname.as_deref())
+        .into_iter()
+        .filter(|dict| allowed(dict.split(utils::MAIN_SEP).next().unwrap_or(dict)))
+        .collect();
     writer.append(dict_list.len() as u32);
     for dict in dict_list.iter() {
         writer.append(dict.as_str());
@@ -780,3 +816,45 @@ pub(crate) fn create_dict(
 
     Ok(())
 }
+
+#[cfg(test)]
+fn request(tag: [u8; 4], path: &str) -> senres::Stack {
+    let mut stack = senres::Stack::new();
+    {
+        let mut writer = stack.writer(tag).unwrap();
+        if &tag == b"LiKQ" {
+            writer.append(None::<&str>);
+        }
+        writer.append(path);
+    }
+    stack
+}
+
+#[test]
+fn dict_of_std_paths() {
+    // std::fs paths that mtxcli uses, which its manifest grants with `pddb-dict:mtxcli`
+    for (tag, path) in [
+        (*b"StaQ", "mtxcli"),
+        (*b"NuDQ", "mtxcli"),
+        (*b"KyOQ", "mtxcli:user"),
+        (*b"RmKQ", "mtxcli:token"),
+        (*b"KyOQ", ":.System:mtxcli:user"),
+        (*b"PthQ", "mtxcli:nested:key"),
+        (*b"LiKQ", "mtxcli"),
+    ] {
+        let dict = dict_in_request(request(tag, path).as_slice(), tag);
+        assert_eq!(dict, "mtxcli", "{}", path);
+        let capability = permissions_api::Capability::PddbDict(&dict).to_string();
+        assert!(permissions_api::grant_matches("pddb-dict:mtxcli", &capability));
+        assert!(!permissions_api::grant_matches("pddb-dict:vault.*", &capability));
+    }
+    // the root and the bases are the whole PDDB, which the grant for one dictionary doesn't cover
+    for path in ["", ":", ":.System"] {
+        let dict = dict_in_request(request(*b"PthQ", path).as_slice(), *b"PthQ");
+        assert_eq!(dict, "*", "{}", path);
+        let capability = permissions_api::Capability::PddbDict(&dict).to_string();
+        assert!(!permissions_api::grant_matches("pddb-dict:mtxcli", &capability));
+    }
+    // a request that isn't the one expected
+    assert_eq!(dict_in_request(request(*b"KyOQ", "mtxcli:user").as_slice(), *b"RmDQ"), "*");
+}
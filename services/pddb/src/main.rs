@@ -401,6 +401,7 @@ use std::thread;
 
 use locales::t;
 use num_traits::*;
+use permissions_api::Capability;
 use rkyv::{
     Archive, Place, Serialize,
     api::low::LowSerializer,
@@ -505,6 +506,8 @@ fn wrapped_main() -> ! {
 
     // for less-secured user prompts (everything but password entry)
     let modals = modals::Modals::new(&xns).expect("can't connect to Modals server");
+    // apps need a grant for each dictionary and basis they use
+    let permissions = permissions_api::Permissions::new();
 
     // our very own password modal. Password modals are precious and privately owned, to avoid
     // other processes from crafting them.
@@ -874,6 +877,10 @@ fn wrapped_main() -> ! {
                 }
             }
             Opcode::ListPathStd => {
+                if !std_allowed(&permissions, &msg, *b"PthQ") {
+                    std_access_denied(&mut msg);
+                    continue;
+                }
                 if let Some(mem) = msg.body.memory_message_mut() {
                     mem.offset = None;
                     if let Err(e) = libstd::list_path(mem, &mut pddb_os, &mut basis_cache) {
@@ -882,6 +889,10 @@ fn wrapped_main() -> ! {
                 }
             }
             Opcode::StatPathStd => {
+                if !std_allowed(&permissions, &msg, *b"StaQ") {
+                    std_access_denied(&mut msg);
+                    continue;
+                }
                 if let Some(mem) = msg.body.memory_message_mut() {
                     mem.offset = None;
                     if let Err(e) = libstd::stat_path(mem, &mut pddb_os, &mut basis_cache) {
@@ -923,6 +934,11 @@ fn wrapped_main() -> ! {
                 let mut buffer =
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let mut mgmt = buffer.to_original::<PddbBasisRequest, _>().unwrap();
+                if !permissions.try_check(msg.sender, Capability::PddbBasis(mgmt.name.as_str())) {
+                    mgmt.code = PddbRequestCode::AccessDenied;
+                    buffer.replace(mgmt).unwrap();
+                    continue;
+                }
                 match mgmt.code {
                     PddbRequestCode::Create => {
                         let request =
@@ -962,6 +978,11 @@ fn wrapped_main() -> ! {
                 let mut buffer =
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let mut mgmt = buffer.to_original::<PddbBasisRequest, _>().unwrap();
+                if !permissions.try_check(msg.sender, Capability::PddbBasis(mgmt.name.as_str())) {
+                    mgmt.code = PddbRequestCode::AccessDenied;
+                    buffer.replace(mgmt).unwrap();
+                    continue;
+                }
                 match mgmt.code {
                     PddbRequestCode::Open => {
                         let mut finished = false;
@@ -984,7 +1005,7 @@ fn wrapped_main() -> ! {
                                             .expect("couldn't unmount previously mounted basis of same name");
                                         modals
                                             .show_notification(
-                                                t!("pddb.unmount_previous", locales::LANG),
+                                                t!("pddb.unmount_previous", locales::lang()),
                                                 None,
                                             )
                                             .expect("notification failed");
@@ -1012,17 +1033,17 @@ fn wrapped_main() -> ! {
                                         xous::BOOKEND_END
                                     );
                                     modals
-                                        .add_list_item(t!("pddb.yes", locales::LANG))
+                                        .add_list_item(t!("pddb.yes", locales::lang()))
                                         .expect("couldn't build radio item list");
                                     modals
-                                        .add_list_item(t!("pddb.no", locales::LANG))
+                                        .add_list_item(t!("pddb.no", locales::lang()))
                                         .expect("couldn't build radio item list");
-                                    match modals.get_radiobutton(t!("pddb.badpass", locales::LANG)) {
+                                    match modals.get_radiobutton(t!("pddb.badpass", locales::lang())) {
                                         Ok(response) => {
-                                            if response.as_str() == t!("pddb.yes", locales::LANG) {
+                                            if response.as_str() == t!("pddb.yes", locales::lang()) {
                                                 finished = false;
                                                 // this will cause just another go-around
-                                            } else if response.as_str() == t!("pddb.no", locales::LANG) {
+                                            } else if response.as_str() == t!("pddb.no", locales::lang()) {
                                                 finished = true;
                                                 mgmt.code = PddbRequestCode::AccessDenied; // this will cause a return of AccessDenied
                                             } else {
@@ -1049,6 +1070,11 @@ fn wrapped_main() -> ! {
                 let mut buffer =
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let mut mgmt = buffer.to_original::<PddbBasisRequest, _>().unwrap();
+                if !permissions.try_check(msg.sender, Capability::PddbBasis(mgmt.name.as_str())) {
+                    mgmt.code = PddbRequestCode::AccessDenied;
+                    buffer.replace(mgmt).unwrap();
+                    continue;
+                }
                 notify_of_disconnect(&mut pddb_os, &token_dict, &mut basis_cache);
                 match mgmt.code {
                     PddbRequestCode::Close => {
@@ -1078,6 +1104,11 @@ fn wrapped_main() -> ! {
                 let mut buffer =
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let mut mgmt = buffer.to_original::<PddbBasisRequest, _>().unwrap();
+                if !permissions.try_check(msg.sender, Capability::PddbBasis(mgmt.name.as_str())) {
+                    mgmt.code = PddbRequestCode::AccessDenied;
+                    buffer.replace(mgmt).unwrap();
+                    continue;
+                }
                 notify_of_disconnect(&mut pddb_os, &token_dict, &mut basis_cache);
                 match mgmt.code {
                     PddbRequestCode::Delete => {
@@ -1103,6 +1134,16 @@ fn wrapped_main() -> ! {
                     3,
                     std::line!(),
                 );
+                {
+                    let mut buffer =
+                        unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                    let mut req: PddbKeyRequest = buffer.to_original::<PddbKeyRequest, _>().unwrap();
+                    if !permissions.try_check(msg.sender, Capability::PddbDict(req.dict.as_str())) {
+                        req.result = PddbRequestCode::AccessDenied;
+                        buffer.replace(req).unwrap();
+                        continue;
+                    }
+                }
                 for basis in basis_cache.access_list().iter() {
                     let mut buffer =
                         unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
@@ -1231,6 +1272,10 @@ fn wrapped_main() -> ! {
                 );
             }
             Opcode::OpenKeyStd => {
+                if !std_allowed(&permissions, &msg, *b"KyOQ") {
+                    std_access_denied(&mut msg);
+                    continue;
+                }
                 if let Some(mem) = msg.body.memory_message_mut() {
                     mem.offset = None;
                     if let Err(e) = libstd::open_key(
@@ -1299,6 +1344,11 @@ fn wrapped_main() -> ! {
                 let mut buffer =
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let mut req: PddbKeyRequest = buffer.to_original::<PddbKeyRequest, _>().unwrap();
+                if !permissions.try_check(msg.sender, Capability::PddbDict(req.dict.as_str())) {
+                    req.result = PddbRequestCode::AccessDenied;
+                    buffer.replace(req).unwrap();
+                    continue;
+                }
                 let bname = if req.basis_specified { Some(req.basis.as_str()) } else { None };
                 let dict = req.dict.as_str();
                 let key = req.key.as_str();
@@ -1344,6 +1394,11 @@ fn wrapped_main() -> ! {
                 let mut buffer =
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let mut req = buffer.to_original::<PddbDeleteList, _>().unwrap();
+                if !permissions.try_check(msg.sender, Capability::PddbDict(req.dict.as_str())) {
+                    req.retcode = PddbRetcode::AccessDenied;
+                    buffer.replace(req).ok();
+                    continue;
+                }
                 let mut key_list = Vec::<String>::new();
                 // the [u8] data is structured as a packed list of u8-len + u8 data slice. The max length of
                 // a PDDB key name is guaranteed to be shorter than a u8. If the length field is 0, then this
@@ -1382,6 +1437,10 @@ fn wrapped_main() -> ! {
                 buffer.replace(req).ok();
             }
             Opcode::DeleteKeyStd => {
+                if !std_allowed(&permissions, &msg, *b"RmKQ") {
+                    std_access_denied(&mut msg);
+                    continue;
+                }
                 if let Some(mem) = msg.body.memory_message_mut() {
                     mem.offset = None;
                     if let Err(err) = libstd::delete_key(mem, &mut pddb_os, &mut basis_cache, &mut fd_mapping)
@@ -1394,6 +1453,11 @@ fn wrapped_main() -> ! {
                 let mut buffer =
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let mut req: PddbKeyRequest = buffer.to_original::<PddbKeyRequest, _>().unwrap();
+                if !permissions.try_check(msg.sender, Capability::PddbDict(req.dict.as_str())) {
+                    req.result = PddbRequestCode::AccessDenied;
+                    buffer.replace(req).unwrap();
+                    continue;
+                }
                 let bname = if req.basis_specified { Some(req.basis.as_str()) } else { None };
                 let dict = req.dict.as_str();
                 log::debug!("attempting to remove dict {} basis {:?}", dict, bname);
@@ -1436,6 +1500,10 @@ fn wrapped_main() -> ! {
                 buffer.replace(req).unwrap();
             }
             Opcode::DeleteDictStd => {
+                if !std_allowed(&permissions, &msg, *b"RmDQ") {
+                    std_access_denied(&mut msg);
+                    continue;
+                }
                 if let Some(mem) = msg.body.memory_message_mut() {
                     mem.offset = None;
                     if let Err(err) = libstd::delete_dict(mem, &mut pddb_os, &mut basis_cache) {
@@ -1444,6 +1512,10 @@ fn wrapped_main() -> ! {
                 }
             }
             Opcode::CreateDictStd => {
+                if !std_allowed(&permissions, &msg, *b"NuDQ") {
+                    std_access_denied(&mut msg);
+                    continue;
+                }
                 if let Some(mem) = msg.body.memory_message_mut() {
                     mem.offset = None;
                     if let Err(err) = libstd::create_dict(mem, &mut pddb_os, &mut basis_cache) {
@@ -1484,6 +1556,11 @@ fn wrapped_main() -> ! {
                 let mut buffer =
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let mut req = buffer.to_original::<PddbDictRequest, _>().unwrap();
+                if !permissions.try_check(msg.sender, Capability::PddbDict(req.dict.as_str())) {
+                    req.code = PddbRequestCode::AccessDenied;
+                    buffer.replace(req).unwrap();
+                    continue;
+                }
                 if key_token.is_some() {
                     log::debug!("key list already in progress");
                     req.code = PddbRequestCode::AccessDenied;
@@ -1583,6 +1660,10 @@ fn wrapped_main() -> ! {
                 );
             }
             Opcode::ListKeyStd => {
+                if !std_allowed(&permissions, &msg, *b"LiKQ") {
+                    std_access_denied(&mut msg);
+                    continue;
+                }
                 if let Some(mem) = msg.body.memory_message_mut() {
                     mem.offset = None;
                     if let Err(e) = libstd::list_key(mem, &mut pddb_os, &mut basis_cache) {
@@ -1642,9 +1723,13 @@ fn wrapped_main() -> ! {
                 buffer.replace(req).unwrap();
             }
             Opcode::ListDictStd => {
+                let sender = msg.sender;
                 if let Some(mem) = msg.body.memory_message_mut() {
                     mem.offset = None;
-                    if let Err(e) = libstd::list_dict(mem, &mut pddb_os, &mut basis_cache) {
+                    // apps only see the dictionaries they may use
+                    if let Err(e) = libstd::list_dict(mem, &mut pddb_os, &mut basis_cache, |dict| {
+                        permissions.try_check(sender, Capability::PddbDict(dict))
+                    }) {
                         mem.offset = xous::MemoryAddress::new(e as usize);
                     }
                 }
@@ -1743,6 +1828,10 @@ fn wrapped_main() -> ! {
                 };
                 let bulk_descriptor = rkyv::deserialize::<PddbDictRequest, rkyv::rancor::Error>(r)
                     .expect("malformed DictBulkRead request");
+                if !permissions.try_check(msg.sender, Capability::PddbDict(bulk_descriptor.dict.as_str())) {
+                    buf[..4].copy_from_slice(&(PddbBulkReadCode::AccessDenied as u32).to_le_bytes());
+                    continue;
+                }
                 // check for a timeout; retire state if we did timeout
                 #[cfg(feature = "perfcounter")]
                 pddb_os.perf_entry(FILE_ID_SERVICES_PDDB_SRC_MAIN, perflib::PERFMETA_NONE, 5, std::line!());
@@ -2144,7 +2233,7 @@ fn wrapped_main() -> ! {
 
             Opcode::MenuListBasis => {
                 let bases = basis_cache.basis_list();
-                let mut note = String::from(t!("pddb.menu.listbasis_response", locales::LANG));
+                let mut note = String::from(t!("pddb.menu.listbasis_response", locales::lang()));
                 for basis in bases.iter() {
                     note.push_str(basis);
                     note.push_str("\n");
@@ -2154,18 +2243,18 @@ fn wrapped_main() -> ! {
             Opcode::MenuChangePin => {
                 if basis_cache.basis_count() == 0 {
                     modals
-                        .show_notification(t!("pddb.changepin.mountfirst", locales::LANG), None)
+                        .show_notification(t!("pddb.changepin.mountfirst", locales::lang()), None)
                         .expect("couldn't show notification");
                     continue;
                 }
                 match pddb_os.pddb_change_pin(&modals) {
                     Ok(_) => modals
-                        .show_notification(t!("pddb.changepin.success", locales::LANG), None)
+                        .show_notification(t!("pddb.changepin.success", locales::lang()), None)
                         .expect("couldn't show notification"),
                     Err(e) => {
                         log::error!("Error changing PIN: {:?}", e);
                         modals
-                            .show_notification(t!("pddb.changepin.nochange", locales::LANG), None)
+                            .show_notification(t!("pddb.changepin.nochange", locales::lang()), None)
                             .expect("couldn't show notification");
                     }
                 }
@@ -2329,6 +2418,26 @@ fn wrapped_main() -> ! {
     xous::terminate_process(0)
 }
 
+/// Whether the sender of a libstd request may use the dictionary that the request's path is in
+fn std_allowed(
+    permissions: &permissions_api::Permissions,
+    msg: &xous::MessageEnvelope,
+    tag: [u8; 4],
+) -> bool {
+    let dict = match msg.body.memory_message() {
+        Some(mem) => libstd::requested_dict(mem, tag),
+        None => "*".to_owned(),
+    };
+    permissions.try_check(msg.sender, Capability::PddbDict(&dict))
+}
+
+/// Fails a libstd request from a process that isn't allowed to make it
+fn std_access_denied(msg: &mut xous::MessageEnvelope) {
+    if let Some(mem) = msg.body.memory_message_mut() {
+        mem.offset = xous::MemoryAddress::new(PddbRetcode::AccessDenied as usize);
+    }
+}
+
 fn ensure_password(modals: &modals::Modals, pddb_os: &mut PddbOs, _pw_cid: xous::CID) -> PasswordState {
     log::info!("Requesting login password");
     loop {
@@ -2343,23 +2452,24 @@ fn ensure_password(modals: &modals::Modals, pddb_os: &mut PddbOs, _pw_cid: xous:
                 } else {
                     // check if the user wants to re-try or not.
                     modals
-                        .add_list_item(t!("pddb.yes", locales::LANG))
+                        .add_list_item(t!("pddb.yes", locales::lang()))
                         .expect("couldn't build radio item list");
                     modals
-                        .add_list_item(t!("pddb.no", locales::LANG))
+                        .add_list_item(t!("pddb.no", locales::lang()))
                         .expect("couldn't build radio item list");
                     let fail_string = format!(
                         "{}\n{}",
-                        t!("pddb.badpass", locales::LANG),
-                        t!("pddb.failed_attempts", locales::LANG).replace("{fails}", &failcount.to_string())
+                        t!("pddb.badpass", locales::lang()),
+                        t!("pddb.failed_attempts", locales::lang())
+                            .replace("{fails}", &failcount.to_string())
                     );
                     let prompt =
-                        if failcount == 0 { t!("pddb.badpass", locales::LANG) } else { &fail_string };
+                        if failcount == 0 { t!("pddb.badpass", locales::lang()) } else { &fail_string };
                     match modals.get_radiobutton(prompt) {
                         Ok(response) => {
-                            if response.as_str() == t!("pddb.yes", locales::LANG) {
+                            if response.as_str() == t!("pddb.yes", locales::lang()) {
                                 continue;
-                            } else if response.as_str() == t!("pddb.no", locales::LANG) {
+                            } else if response.as_str() == t!("pddb.no", locales::lang()) {
                                 return PasswordState::Incorrect(failcount);
                             } else {
                                 panic!("Got unexpected return from radiobutton");
@@ -2398,7 +2508,7 @@ fn try_mount_or_format(
 ) -> bool {
     log::info!("Attempting to mount the PDDB");
     if pw_state == PasswordState::Correct {
-        modals.dynamic_notification(Some(t!("pddb.waitmount", locales::LANG)), None).unwrap();
+        modals.dynamic_notification(Some(t!("pddb.waitmount", locales::lang())), None).unwrap();
         if let Some(sys_basis) = pddb_os.pddb_mount() {
             log::info!("PDDB mount operation finished successfully");
             basis_cache.basis_add(sys_basis);
@@ -2415,15 +2525,15 @@ fn try_mount_or_format(
         #[cfg(any(feature = "precursor", feature = "renode", feature = "test-rekey"))]
         {
             log::debug!("PDDB did not mount; requesting format");
-            modals.add_list_item(t!("pddb.okay", locales::LANG)).expect("couldn't build radio item list");
-            modals.add_list_item(t!("pddb.cancel", locales::LANG)).expect("couldn't build radio item list");
+            modals.add_list_item(t!("pddb.okay", locales::lang())).expect("couldn't build radio item list");
+            modals.add_list_item(t!("pddb.cancel", locales::lang())).expect("couldn't build radio item list");
             let do_format: bool;
             log::info!("{}PDDB.REQFMT,{}", xous::BOOKEND_START, xous::BOOKEND_END);
-            match modals.get_radiobutton(t!("pddb.requestformat", locales::LANG)) {
+            match modals.get_radiobutton(t!("pddb.requestformat", locales::lang())) {
                 Ok(response) => {
-                    if response.as_str() == t!("pddb.okay", locales::LANG) {
+                    if response.as_str() == t!("pddb.okay", locales::lang()) {
                         do_format = true;
-                    } else if response.as_str() == t!("pddb.cancel", locales::LANG) {
+                    } else if response.as_str() == t!("pddb.cancel", locales::lang()) {
                         log::info!("PDDB format aborted by user");
                         do_format = false;
                     } else {
@@ -2436,16 +2546,16 @@ fn try_mount_or_format(
                 let fast: bool;
                 if false {
                     modals
-                        .add_list_item(t!("pddb.no", locales::LANG))
+                        .add_list_item(t!("pddb.no", locales::lang()))
                         .expect("couldn't build radio item list");
                     modals
-                        .add_list_item(t!("pddb.yes", locales::LANG))
+                        .add_list_item(t!("pddb.yes", locales::lang()))
                         .expect("couldn't build radio item list");
-                    match modals.get_radiobutton(t!("pddb.devbypass", locales::LANG)) {
+                    match modals.get_radiobutton(t!("pddb.devbypass", locales::lang())) {
                         Ok(response) => {
-                            if response.as_str() == t!("pddb.yes", locales::LANG) {
+                            if response.as_str() == t!("pddb.yes", locales::lang()) {
                                 fast = true;
-                            } else if response.as_str() == t!("pddb.no", locales::LANG) {
+                            } else if response.as_str() == t!("pddb.no", locales::lang()) {
                                 fast = false;
                             } else {
                                 panic!("Got unexpected return from radiobutton");
@@ -2472,7 +2582,7 @@ fn try_mount_or_format(
                     ),
                 )
                 .expect("couldn't reset time");
-                modals.dynamic_notification(Some(t!("pddb.waitmount", locales::LANG)), None).unwrap();
+                modals.dynamic_notification(Some(t!("pddb.waitmount", locales::lang())), None).unwrap();
                 if let Some(sys_basis) = pddb_os.pddb_mount() {
                     log::info!("PDDB mount operation finished successfully");
                     basis_cache.basis_add(sys_basis);
@@ -2484,7 +2594,7 @@ fn try_mount_or_format(
                 } else {
                     modals.dynamic_notification_close().unwrap();
                     log::error!("Despite formatting, no PDDB was found!");
-                    let mut err = String::from(t!("pddb.internalerror", locales::LANG));
+                    let mut err = String::from(t!("pddb.internalerror", locales::lang()));
                     err.push_str(" #1"); // punt and leave an error code, because this "should" be rare
                     modals.show_notification(err.as_str(), None).expect("notification failed");
                     false
@@ -2511,7 +2621,7 @@ fn try_mount_or_format(
                 true
             } else {
                 log::error!("Despite formatting, no PDDB was found!");
-                let mut err = String::from(t!("pddb.internalerror", locales::LANG));
+                let mut err = String::from(t!("pddb.internalerror", locales::lang()));
                 err.push_str(" #1"); // punt and leave an error code, because this "should" be rare
                 modals.show_notification(err.as_str(), None).expect("notification failed");
                 false
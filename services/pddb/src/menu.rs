@@ -9,21 +9,21 @@ pub(crate) fn pddb_menu(conn: xous::CID) {
     let mut menu_items = Vec::<MenuItem>::new();
 
     menu_items.push(MenuItem {
-        name: String::from(t!("pddb.menu.listbasis", locales::LANG)),
+        name: String::from(t!("pddb.menu.listbasis", locales::lang())),
         action_conn: Some(conn),
         action_opcode: Opcode::MenuListBasis.to_u32().unwrap(),
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
         close_on_select: true,
     });
     menu_items.push(MenuItem {
-        name: String::from(t!("pddb.menu.change_unlock_pin", locales::LANG)),
+        name: String::from(t!("pddb.menu.change_unlock_pin", locales::lang())),
         action_conn: Some(conn),
         action_opcode: Opcode::MenuChangePin.to_u32().unwrap(),
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
         close_on_select: true,
     });
     menu_items.push(MenuItem {
-        name: String::from(t!("mainmenu.closemenu", locales::LANG)),
+        name: String::from(t!("mainmenu.closemenu", locales::lang())),
         action_conn: None,
         action_opcode: 0,
         action_payload: MenuPayload::Scalar([0, 0, 0, 0]),
@@ -74,7 +74,7 @@ pub(crate) fn password_ux_manager(
     let mut pddb_modal = Modal::new(
         gam::PDDB_MODAL_NAME,
         ActionType::TextEntry(password_action.clone()),
-        Some(t!("pddb.password", locales::LANG)),
+        Some(t!("pddb.password", locales::lang())),
         None,
         gam::SYSTEM_STYLE,
         8,
@@ -102,10 +102,11 @@ pub(crate) fn password_ux_manager(
                 };
                 pddb_modal.modify(
                     Some(ActionType::TextEntry(password_action.clone())),
-                    Some(t!("pddb.password", locales::LANG)),
+                    Some(t!("pddb.password", locales::lang())),
                     false,
                     Some(
-                        format!("{}'{}'", t!("pddb.password_for", locales::LANG), db_name.as_str()).as_str(),
+                        format!("{}'{}'", t!("pddb.password_for", locales::lang()), db_name.as_str())
+                            .as_str(),
                     ),
                     false,
                     None,
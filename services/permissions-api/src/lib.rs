@@ -0,0 +1,206 @@
+#![cfg_attr(target_os = "none", no_std)]
+
+//! Checks of what an app may do, for the services that hold the resources.
+//!
+//! Apps declare capabilities in `apps/manifest.json` (or, for loaded apps, in their package), and the
+//! build bakes them into the GAM. A service that hands out something sensitive asks the permissions
+//! server whether the process that sent a message may have it:
+//!
+//! ```ignore
+//! if !permissions.check(msg.sender, Capability::Network) {
+//!     // refuse the request
+//! }
+//! ```
+//!
+//! Processes that aren't apps -- the services in the boot image -- are always allowed. Apps are known
+//! from when the app loader spawns them, or when they register a UX or claim a GAM token, and until every
+//! app in the image has done so, a process that isn't known is held (or refused by `try_check`). An app
+//! is allowed only what it declared, and the user is asked the first time it uses each declared
+//! capability.
+//!
+//! The server is asked once per check, and may show a dialog, so a service making a check stalls until
+//! the user answers it. Other checks are answered meanwhile. Services whose main loop mustn't stall, like
+//! the PDDB (which the dialog itself may need) and the network stack, use `try_check` instead: it refuses
+//! while the user is being asked, and later checks get the user's answer.
+
+use core::cell::Cell;
+
+use num_traits::ToPrimitive;
+use xous::CID;
+use xous_ipc::Buffer;
+
+pub const SERVER_NAME_PERMISSIONS: &str = "_App permissions_";
+
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+pub enum Opcode {
+    /// Decide if a process may use a capability
+    Check,
+    /// The user answered a prompt; sent by the server to itself
+    Answer,
+    /// Every app in the image has registered; sent by the server to itself
+    Booted,
+    /// Exits the server
+    Quit,
+}
+
+#[derive(Debug, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct PermissionCheck {
+    pub pid: u32,
+    pub capability: String,
+    pub allowed: bool,
+    /// whether to wait for the user to be asked, rather than be refused meanwhile
+    pub wait: bool,
+}
+
+/// Something an app has to be granted before it can use it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Capability<'a> {
+    /// Opening TCP and UDP sockets
+    Network,
+    /// Typing keystrokes to the host over USB, and raw HID reports
+    UsbHid,
+    /// Playing and recording sound
+    Audio,
+    Camera,
+    /// A PDDB dictionary, by name
+    PddbDict(&'a str),
+    /// Creating, opening, closing or deleting a PDDB basis, by name
+    PddbBasis(&'a str),
+}
+
+impl<'a> core::fmt::Display for Capability<'a> {
+    /// The form capabilities take in manifests.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Capability::Network => write!(f, "network"),
+            Capability::UsbHid => write!(f, "usb-hid"),
+            Capability::Audio => write!(f, "audio"),
+            Capability::Camera => write!(f, "camera"),
+            Capability::PddbDict(name) => write!(f, "pddb-dict:{}", name),
+            Capability::PddbBasis(name) => write!(f, "pddb-basis:{}", name),
+        }
+    }
+}
+
+/// Whether a capability in a manifest, `grant`, covers a `request` made by an app. A grant ending in
+/// `*` covers every request it is a prefix of, so `pddb-dict:vault.*` covers `pddb-dict:vault.passwords`,
+/// while `pddb-dict:*` covers every dictionary.
+pub fn grant_matches(grant: &str, request: &str) -> bool {
+    match grant.strip_suffix('*') {
+        Some(prefix) => request.starts_with(prefix),
+        None => grant == request,
+    }
+}
+
+pub struct Permissions {
+    xns: xous_names::XousNames,
+    conn: Cell<Option<CID>>,
+    warned: Cell<bool>,
+}
+impl Permissions {
+    /// The connection to the server is made on the first check, so services that start before it don't
+    /// wait for it.
+    pub fn new() -> Self {
+        Permissions {
+            xns: xous_names::XousNames::new().expect("couldn't connect to XousNames"),
+            conn: Cell::new(None),
+            warned: Cell::new(false),
+        }
+    }
+
+    /// Decides if the sender of a message may use `capability`. This blocks while the user is asked.
+    pub fn check(&self, sender: xous::MessageSender, capability: Capability) -> bool {
+        self.request(sender, capability, true)
+    }
+
+    /// Like `check`, but doesn't wait for the user: if they haven't answered for `capability` yet, they
+    /// are asked, and it's refused until they allow it.
+    pub fn try_check(&self, sender: xous::MessageSender, capability: Capability) -> bool {
+        self.request(sender, capability, false)
+    }
+
+    fn request(&self, sender: xous::MessageSender, capability: Capability, wait: bool) -> bool {
+        let pid = match sender.pid() {
+            Some(pid) => pid.get() as u32,
+            // messages from the kernel
+            None => return true,
+        };
+        if pid == xous::process::id() {
+            return true;
+        }
+        let conn = match self.conn.get() {
+            Some(conn) => conn,
+            None => loop {
+                match self.xns.request_connection(SERVER_NAME_PERMISSIONS) {
+                    Ok(conn) => {
+                        self.conn.set(Some(conn));
+                        break conn;
+                    }
+                    // The permissions server registers as soon as it starts, well before the trusted boot
+                    // services have all connected to each other. After that, this is an image without it
+                    // (development images without a GUI), which has no apps to check.
+                    Err(_) if self.xns.trusted_init_done().unwrap_or(false) => {
+                        if !self.warned.replace(true) {
+                            log::warn!("no permissions server, {} allowed to PID {}", capability, pid);
+                        }
+                        return true;
+                    }
+                    Err(_) if !wait => {
+                        log::warn!("permissions server not up yet, {} refused to PID {}", capability, pid);
+                        return false;
+                    }
+                    Err(_) => std::thread::sleep(std::time::Duration::from_millis(100)),
+                }
+            },
+        };
+        let request = PermissionCheck { pid, capability: capability.to_string(), allowed: false, wait };
+        let mut buf = match Buffer::into_buf(request) {
+            Ok(buf) => buf,
+            Err(_) => return false,
+        };
+        if buf.lend_mut(conn, Opcode::Check.to_u32().unwrap()).is_err() {
+            log::error!("couldn't ask the permissions server about {} for PID {}", capability, pid);
+            return false;
+        }
+        buf.to_original::<PermissionCheck, _>().map(|response| response.allowed).unwrap_or(false)
+    }
+}
+
+impl Drop for Permissions {
+    fn drop(&mut self) {
+        // every Permissions makes its own connection, so there's no need to count references
+        if let Some(conn) = self.conn.get() {
+            unsafe {
+                xous::disconnect(conn).ok();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grant_matches() {
+        assert!(grant_matches("network", "network"));
+        assert!(!grant_matches("network", "usb-hid"));
+        assert!(grant_matches("pddb-dict:vault.*", "pddb-dict:vault.passwords"));
+        assert!(grant_matches("pddb-dict:vault.*", "pddb-dict:vault."));
+        assert!(!grant_matches("pddb-dict:vault.*", "pddb-dict:vaultx"));
+        assert!(!grant_matches("pddb-dict:vault.*", "pddb-basis:vault.x"));
+        assert!(grant_matches("pddb-dict:*", "pddb-dict:anything"));
+        // asking for everything is only granted by a grant of everything
+        assert!(grant_matches("pddb-dict:*", "pddb-dict:*"));
+        assert!(!grant_matches("pddb-dict:vault.*", "pddb-dict:*"));
+        assert!(!grant_matches("pddb-dict:pgpcard", "pddb-dict:pgpcard2"));
+    }
+
+    #[test]
+    fn test_capability_names() {
+        assert_eq!(Capability::Network.to_string(), "network");
+        assert_eq!(Capability::UsbHid.to_string(), "usb-hid");
+        assert_eq!(Capability::PddbDict("mtxcli").to_string(), "pddb-dict:mtxcli");
+        assert_eq!(Capability::PddbBasis("travel").to_string(), "pddb-basis:travel");
+    }
+}
@@ -0,0 +1,270 @@
+#![cfg_attr(target_os = "none", no_std)]
+#![cfg_attr(target_os = "none", no_main)]
+
+use std::collections::HashMap;
+
+use locales::t;
+use log::info;
+use num_traits::{FromPrimitive, ToPrimitive};
+use permissions_api::*;
+use xous::msg_scalar_unpack;
+use xous_ipc::Buffer;
+
+/// An app, and the grant in its manifest that a check fell under.
+type Grant = (String, String);
+
+/// Why a check can't be answered yet.
+enum Pending {
+    /// the user has to be asked about the grant first
+    Ask(Grant),
+    /// not every app in the image has registered, and the process may yet turn out to be one of them
+    Boot,
+}
+
+/// Decides what apps may use. The GAM knows which app a process is and what its manifest grants; what
+/// the user said about each grant is remembered until the next reboot.
+struct Broker {
+    identities: gam::AppIdentities,
+    /// processes known to be apps. Other processes are looked up again every time, as the app loader may
+    /// have spawned them since.
+    apps: HashMap<u32, (String, Vec<String>)>,
+    /// the user's answer for each app and grant
+    answers: HashMap<Grant, bool>,
+}
+
+impl Broker {
+    /// Whether `pid` may use `capability`, or why that can't be told yet.
+    fn check(&mut self, pid: u32, capability: &str) -> Result<bool, Pending> {
+        if !self.apps.contains_key(&pid) {
+            match self.identities.lookup(pid) {
+                Ok(identity) => match identity.app {
+                    Some(app) => {
+                        self.apps.insert(pid, (app, identity.capabilities));
+                    }
+                    // apps are known from when they're spawned or register, so this is a boot service
+                    None if identity.booted => return Ok(true),
+                    None => return Err(Pending::Boot),
+                },
+                Err(e) => {
+                    log::error!("couldn't look up PID {}: {:?}", pid, e);
+                    return Ok(false);
+                }
+            }
+        }
+        let (app, grants) = self.apps.get(&pid).unwrap();
+        let grant = match grants.iter().find(|grant| grant_matches(grant, capability)) {
+            Some(grant) => grant.to_string(),
+            None => {
+                log::warn!("`{}' (PID {}) denied {}: not in its manifest", app, pid, capability);
+                return Ok(false);
+            }
+        };
+        // The codec waits for this answer, and in text-to-speech builds the prompt would be spoken
+        // through the codec. Declaring audio is enough.
+        if capability == "audio" {
+            return Ok(true);
+        }
+        let key = (app.to_string(), grant);
+        match self.answers.get(&key) {
+            Some(&allowed) => Ok(allowed),
+            None => Err(Pending::Ask(key)),
+        }
+    }
+}
+
+/// Shows the prompt for `grant` on its own thread, which sends the answer back as `Opcode::Answer`. The
+/// main loop keeps answering checks meanwhile, including those the modals server makes on its behalf.
+fn ask(grant: &Grant, answer_conn: xous::CID) {
+    let (app, grant) = grant.clone();
+    std::thread::spawn(move || {
+        let xns = xous_names::XousNames::new().unwrap();
+        let modals = modals::Modals::new(&xns).expect("can't connect to modals");
+        let what = match grant.split_once(':') {
+            Some(("pddb-dict", name)) => format!("{} {}", t!("permissions.pddb_dict", locales::lang()), name),
+            Some(("pddb-basis", name)) => {
+                format!("{} {}", t!("permissions.pddb_basis", locales::lang()), name)
+            }
+            _ => match grant.as_str() {
+                "network" => t!("permissions.network", locales::lang()).to_string(),
+                "usb-hid" => t!("permissions.usb_hid", locales::lang()).to_string(),
+                "camera" => t!("permissions.camera", locales::lang()).to_string(),
+                _ => grant.to_string(),
+            },
+        };
+        let prompt =
+            t!("permissions.prompt", locales::lang()).replace("{app}", &app).replace("{what}", &what);
+        modals
+            .add_list(vec![t!("permissions.allow", locales::lang()), t!("permissions.deny", locales::lang())])
+            .expect("couldn't build list");
+        let allowed = match modals.get_radiobutton(&prompt) {
+            Ok(choice) => choice == t!("permissions.allow", locales::lang()),
+            Err(e) => {
+                log::error!("couldn't ask about {} for `{}': {:?}", grant, app, e);
+                false
+            }
+        };
+        xous::send_message(
+            answer_conn,
+            xous::Message::new_scalar(Opcode::Answer.to_usize().unwrap(), allowed as usize, 0, 0, 0),
+        )
+        .expect("couldn't send answer");
+    });
+}
+
+/// Tells the main loop with `Opcode::Booted` once every app in the image has registered, so that the
+/// checks held until then can be answered.
+fn watch_boot(answer_conn: xous::CID) {
+    std::thread::spawn(move || {
+        let xns = xous_names::XousNames::new().unwrap();
+        let identities = gam::AppIdentities::new(&xns).expect("can't connect to GAM");
+        let tt = ticktimer_server::Ticktimer::new().unwrap();
+        while !identities.lookup(0).map(|identity| identity.booted).unwrap_or(false) {
+            tt.sleep_ms(100).ok();
+        }
+        xous::send_message(
+            answer_conn,
+            xous::Message::new_scalar(Opcode::Booted.to_usize().unwrap(), 0, 0, 0, 0),
+        )
+        .expect("couldn't send booted");
+    });
+}
+
+/// Answers a `Check`; the caller is unblocked when `msg` is dropped.
+fn reply(mut msg: xous::MessageEnvelope, allowed: bool) {
+    let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+    let mut request = buffer.to_original::<PermissionCheck, _>().unwrap();
+    request.allowed = allowed;
+    buffer.replace(request).unwrap();
+}
+
+/// Answers a `Check` if it can be, or holds it until it can: for the user to be asked, or for the apps in
+/// the image to register. Checks that don't wait are refused instead, but the user is still asked.
+fn check(
+    broker: &mut Broker,
+    msg: xous::MessageEnvelope,
+    asking: &mut Option<Grant>,
+    waiting: &mut Vec<(Grant, Option<xous::MessageEnvelope>)>,
+    booting: &mut Vec<xous::MessageEnvelope>,
+    answer_conn: xous::CID,
+) {
+    let request = {
+        let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+        buffer.to_original::<PermissionCheck, _>().unwrap()
+    };
+    match broker.check(request.pid, &request.capability) {
+        Ok(allowed) => reply(msg, allowed),
+        Err(Pending::Boot) => {
+            if request.wait {
+                booting.push(msg);
+            } else {
+                log::info!(
+                    "PID {} refused {} until the apps have registered",
+                    request.pid,
+                    request.capability
+                );
+                reply(msg, false);
+            }
+        }
+        Err(Pending::Ask(grant)) => {
+            if asking.is_none() {
+                ask(&grant, answer_conn);
+                *asking = Some(grant.clone());
+            }
+            let queued = asking.as_ref() == Some(&grant) || waiting.iter().any(|(key, _)| *key == grant);
+            if request.wait {
+                waiting.push((grant, Some(msg)));
+            } else {
+                reply(msg, false);
+                if !queued {
+                    waiting.push((grant, None));
+                }
+            }
+        }
+    }
+}
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    // unlimited connections allowed: any service may need to check a caller
+    let permissions_sid = xns.register_name(SERVER_NAME_PERMISSIONS, None).expect("can't register server");
+    log::trace!("registered with NS -- {:?}", permissions_sid);
+    let answer_conn = xous::connect(permissions_sid).unwrap();
+
+    let mut broker = Broker {
+        identities: gam::AppIdentities::new(&xns).expect("can't connect to GAM"),
+        apps: HashMap::new(),
+        answers: HashMap::new(),
+    };
+    // the grant the user is being asked about, and the grants still to ask about with the checks waiting
+    // for their answers, oldest first. Checks that don't wait were refused already, and leave only the
+    // grant to ask about.
+    let mut asking: Option<Grant> = None;
+    let mut waiting: Vec<(Grant, Option<xous::MessageEnvelope>)> = Vec::new();
+    // checks from processes that may yet turn out to be apps, held until the apps in the image registered
+    let mut booting: Vec<xous::MessageEnvelope> = Vec::new();
+    watch_boot(answer_conn);
+
+    log::trace!("ready to accept requests");
+    loop {
+        let msg = xous::receive_message(permissions_sid).unwrap();
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::Check) => {
+                check(&mut broker, msg, &mut asking, &mut waiting, &mut booting, answer_conn);
+            }
+            Some(Opcode::Booted) => {
+                if msg.sender.pid().map(|pid| pid.get() as u32) != Some(xous::process::id()) {
+                    log::error!("ignoring booted sent by PID {:?}", msg.sender.pid());
+                    continue;
+                }
+                for msg in std::mem::take(&mut booting) {
+                    check(&mut broker, msg, &mut asking, &mut waiting, &mut booting, answer_conn);
+                }
+            }
+            Some(Opcode::Answer) => msg_scalar_unpack!(msg, allowed, _, _, _, {
+                if msg.sender.pid().map(|pid| pid.get() as u32) != Some(xous::process::id()) {
+                    log::error!("ignoring an answer sent by PID {:?}", msg.sender.pid());
+                    continue;
+                }
+                if let Some(grant) = asking.take() {
+                    let allowed = allowed != 0;
+                    log::info!(
+                        "`{}' {} {} by the user",
+                        grant.0,
+                        if allowed { "allowed" } else { "denied" },
+                        grant.1
+                    );
+                    let (answered, rest): (Vec<_>, Vec<_>) =
+                        waiting.drain(..).partition(|(key, _)| *key == grant);
+                    waiting = rest;
+                    for (_, msg) in answered {
+                        if let Some(msg) = msg {
+                            reply(msg, allowed);
+                        }
+                    }
+                    broker.answers.insert(grant, allowed);
+                }
+                if let Some((next, _)) = waiting.first() {
+                    ask(next, answer_conn);
+                    asking = Some(next.clone());
+                }
+            }),
+            Some(Opcode::Quit) => {
+                log::warn!("Quit received, goodbye world!");
+                break;
+            }
+            None => {
+                log::error!("couldn't convert opcode: {:?}", msg);
+            }
+        }
+    }
+    // clean up our program
+    log::trace!("main loop exit, destroying servers");
+    xns.unregister_server(permissions_sid).unwrap();
+    xous::destroy_server(permissions_sid).unwrap();
+    log::trace!("quitting");
+    xous::terminate_process(0)
+}
@@ -0,0 +1,49 @@
+pub const SERVER_NAME_QR: &str = "_QR code scanner_";
+
+/// How long a scan waits for a code before giving up
+pub const SCAN_TIMEOUT_MS: usize = 30_000;
+
+#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+pub enum Opcode {
+    /// Wait for the camera to read a code; answered when one is read or the scan times out
+    Scan,
+    /// A camera read a code
+    Scanned,
+    /// A process with a camera is ready to report codes
+    CameraReady,
+    /// A scan waited too long; sent by the server to itself
+    Timeout,
+    /// Exits the server
+    Quit,
+}
+
+/// Why a scan didn't return a code.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub enum ScanError {
+    /// There is no camera on this device, or it isn't running
+    NoCamera,
+    /// The app may not use the camera
+    Denied,
+    /// No code was read in time
+    TimedOut,
+    /// The QR server couldn't be reached
+    Internal,
+}
+
+impl core::fmt::Display for ScanError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ScanError::NoCamera => write!(f, "no camera"),
+            ScanError::Denied => write!(f, "camera denied"),
+            ScanError::TimedOut => write!(f, "no code read in time"),
+            ScanError::Internal => write!(f, "QR server unreachable"),
+        }
+    }
+}
+
+/// A scan request, and its answer.
+#[derive(Debug, Clone, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Scan {
+    pub payload: Vec<u8>,
+    pub error: Option<ScanError>,
+}
@@ -0,0 +1,109 @@
+use crate::pgm::Greymap;
+
+/// A code read from an image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decoded {
+    pub data: Vec<u8>,
+    /// 1 to 40
+    pub version: usize,
+    /// the error correction level, as the code stores it: 0 is M, 1 is L, 2 is H and 3 is Q
+    pub ecc_level: u16,
+    pub mask: u16,
+}
+
+impl Decoded {
+    /// The data, if it is text.
+    pub fn text(&self) -> Option<&str> { core::str::from_utf8(&self.data).ok() }
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    /// Nothing in the image looked like a code
+    NotFound,
+    /// A code was found, but couldn't be read even with error correction
+    Unreadable(rqrr::DeQRError),
+}
+
+/// Finds the codes in a greyscale image, one byte per pixel row by row, and reads the first that can be
+/// read. The image is thresholded locally, so uneven lighting is fine, and codes may be rotated or seen
+/// at an angle.
+pub fn decode(width: usize, height: usize, pixels: &[u8]) -> Result<Decoded, DecodeError> {
+    let mut image = rqrr::PreparedImage::prepare_from_greyscale(width, height, |x, y| pixels[y * width + x]);
+    let mut error = DecodeError::NotFound;
+    for grid in image.detect_grids() {
+        match read(&grid) {
+            Ok(decoded) => return Ok(decoded),
+            Err(e) => {
+                log::debug!("found a code but couldn't read it: {:?}", e);
+                error = e;
+            }
+        }
+    }
+    Err(error)
+}
+
+pub fn decode_greymap(image: &Greymap) -> Result<Decoded, DecodeError> {
+    decode(image.width, image.height, &image.pixels)
+}
+
+/// Reads a code already sampled into its modules, `modules` on a side, as a camera pipeline that finds
+/// and straightens codes itself produces. `is_dark(x, y)` tells a module's colour.
+pub fn decode_modules(
+    modules: usize,
+    is_dark: impl Fn(usize, usize) -> bool,
+) -> Result<Decoded, DecodeError> {
+    read(&rqrr::Grid::new(rqrr::SimpleGrid::from_func(modules, is_dark)))
+}
+
+fn read<G: rqrr::BitGrid>(grid: &rqrr::Grid<G>) -> Result<Decoded, DecodeError> {
+    let mut data = Vec::new();
+    let meta = grid.decode_to(&mut data).map_err(DecodeError::Unreadable)?;
+    Ok(Decoded { data, version: meta.version.0, ecc_level: meta.ecc_level, mask: meta.mask })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::{Ecc, encode};
+
+    #[test]
+    fn test_round_trip() {
+        let data: Vec<u8> = (0..=255).collect();
+        // the encoder picks a different mask for each, and small to large versions
+        for (len, ecc) in [(5, Ecc::Low), (40, Ecc::Medium), (120, Ecc::Quartile), (256, Ecc::High)] {
+            let code = encode(&data[..len], ecc).unwrap();
+            let (side, pixels) = code.to_pixels(4, 4);
+            let decoded = decode(side, side, &pixels).unwrap();
+            assert_eq!(decoded.data, &data[..len]);
+            assert_eq!(decoded.version, code.version());
+
+            let decoded = decode_modules(code.width(), |x, y| code.is_dark(x, y)).unwrap();
+            assert_eq!(decoded.data, &data[..len]);
+        }
+    }
+
+    #[test]
+    fn test_not_found() {
+        assert!(matches!(decode(64, 64, &[255; 64 * 64]), Err(DecodeError::NotFound)));
+        let noise: Vec<u8> = (0..64 * 64u32).map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8).collect();
+        assert!(decode(64, 64, &noise).is_err());
+    }
+
+    #[test]
+    fn test_files() {
+        // a straight code, as an otpauth URI
+        let image = Greymap::from_pgm(include_bytes!("../testdata/otpauth.pgm")).unwrap();
+        let decoded = decode_greymap(&image).unwrap();
+        assert_eq!(
+            decoded.text(),
+            Some("otpauth://totp/Example:alice@example.com?secret=JBSWY3DPEHPK3PXP&issuer=Example")
+        );
+
+        // turned, on a grey background with noise, and with a blot over part of the data: only error
+        // correction reads it
+        let image = Greymap::from_pgm(include_bytes!("../testdata/wifi.pgm")).unwrap();
+        let decoded = decode_greymap(&image).unwrap();
+        assert_eq!(decoded.text(), Some("WIFI:T:WPA;S:Home \\; Office;P:correct horse;;"));
+        assert_eq!(decoded.ecc_level, 2);
+    }
+}
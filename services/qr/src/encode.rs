@@ -0,0 +1,103 @@
+use qrcode::types::QrError;
+use qrcode::{Color, EcLevel, QrCode};
+
+/// How much of a code can be damaged and still be read: about 7%, 15%, 25% and 30% of it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Ecc {
+    Low,
+    Medium,
+    Quartile,
+    High,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EncodeError {
+    /// More data than the largest code holds at the chosen error correction
+    TooLong,
+    /// The encoder rejected the data
+    Invalid,
+}
+
+/// The modules of a QR code, without the quiet zone around it. `true` is dark.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QrMatrix {
+    width: usize,
+    modules: Vec<bool>,
+}
+
+impl QrMatrix {
+    /// The number of modules on each side.
+    pub fn width(&self) -> usize { self.width }
+
+    /// The modules row by row, starting at the top left.
+    pub fn modules(&self) -> &[bool] { &self.modules }
+
+    pub fn is_dark(&self, x: usize, y: usize) -> bool { self.modules[y * self.width + x] }
+
+    /// The version of the code, 1 to 40.
+    pub fn version(&self) -> usize { (self.width - 17) / 4 }
+
+    /// Renders the code as a square greyscale image, 0 for dark and 255 for light, with `scale` pixels
+    /// per module and `quiet` light modules of margin on every side. Returns the width of the image and
+    /// its pixels, row by row.
+    pub fn to_pixels(&self, scale: usize, quiet: usize) -> (usize, Vec<u8>) {
+        let side = (self.width + 2 * quiet) * scale;
+        let mut pixels = vec![255u8; side * side];
+        for (i, _) in self.modules.iter().enumerate().filter(|(_, &dark)| dark) {
+            let (x, y) = ((i % self.width + quiet) * scale, (i / self.width + quiet) * scale);
+            for row in pixels[y * side..].chunks_mut(side).take(scale) {
+                row[x..x + scale].iter_mut().for_each(|p| *p = 0);
+            }
+        }
+        (side, pixels)
+    }
+}
+
+/// Encodes `data` in the smallest code that holds it, choosing the mask the standard prefers.
+pub fn encode(data: &[u8], ecc: Ecc) -> Result<QrMatrix, EncodeError> {
+    let level = match ecc {
+        Ecc::Low => EcLevel::L,
+        Ecc::Medium => EcLevel::M,
+        Ecc::Quartile => EcLevel::Q,
+        Ecc::High => EcLevel::H,
+    };
+    let code = QrCode::with_error_correction_level(data, level).map_err(|e| match e {
+        QrError::DataTooLong => EncodeError::TooLong,
+        _ => EncodeError::Invalid,
+    })?;
+    let modules = code.to_colors().iter().map(|&color| color == Color::Dark).collect();
+    Ok(QrMatrix { width: code.width(), modules })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let code = encode(b"hello", Ecc::Medium).unwrap();
+        assert_eq!(code.width(), 21);
+        assert_eq!(code.version(), 1);
+        // the finder pattern in the top left corner
+        assert!((0..7).all(|i| code.is_dark(i, 0) && code.is_dark(0, i) && code.is_dark(6, i)));
+        assert!((1..6).all(|i| !code.is_dark(i, 1)));
+        assert!(code.is_dark(3, 3));
+
+        let long = encode(&[0xA5; 1000], Ecc::Low).unwrap();
+        assert!(long.version() > 20);
+        assert_eq!(encode(&[0xA5; 3000], Ecc::High), Err(EncodeError::TooLong));
+    }
+
+    #[test]
+    fn test_to_pixels() {
+        let code = encode(b"hello", Ecc::Low).unwrap();
+        let (side, pixels) = code.to_pixels(3, 4);
+        assert_eq!(side, (21 + 8) * 3);
+        assert_eq!(pixels.len(), side * side);
+        // quiet zone, then the corner of the finder pattern
+        assert_eq!(pixels[side * 11 + 11], 255);
+        assert_eq!(pixels[side * 12 + 12], 0);
+        assert_eq!(pixels[side * 14 + 14], 0);
+        assert_eq!(pixels[side * 15 + 15], 255);
+    }
+}
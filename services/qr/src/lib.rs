@@ -0,0 +1,92 @@
+#![cfg_attr(target_os = "none", no_std)]
+
+//! QR codes: making them, reading them from images, and scanning them with the camera.
+//!
+//! Encoding and decoding are plain functions that run in the caller's process, so they work the same on
+//! the device and on a Linux host, where the decoder can be pointed at image files:
+//!
+//! ```ignore
+//! let image = qr::pgm::Greymap::from_pgm(&std::fs::read("code.pgm")?).unwrap();
+//! let decoded = qr::decode::decode_greymap(&image)?;
+//! ```
+//!
+//! Scanning goes through the QR server, which hands codes read by whichever service owns the camera to
+//! the apps waiting for one. Apps need the `camera` capability in their manifest to scan:
+//!
+//! ```ignore
+//! let qr = qr::Qr::new(&xns)?;
+//! match qr.scan() {
+//!     Ok(data) => ..., // the first code read
+//!     Err(ScanError::NoCamera) => ..., // e.g. on a Precursor
+//!     Err(e) => ...,
+//! }
+//! ```
+
+pub mod api;
+pub mod decode;
+pub mod encode;
+pub mod payload;
+pub mod pgm;
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+pub use api::ScanError;
+use api::*;
+use num_traits::*;
+use xous::{CID, Message, send_message};
+use xous_ipc::Buffer;
+
+pub struct Qr {
+    conn: CID,
+}
+impl Qr {
+    pub fn new(xns: &xous_names::XousNames) -> Result<Self, xous::Error> {
+        REFCOUNT.fetch_add(1, Ordering::Relaxed);
+        let conn = xns.request_connection_blocking(api::SERVER_NAME_QR).expect("Can't connect to QR server");
+        Ok(Qr { conn })
+    }
+
+    /// Waits for the camera to read a code, and returns what it holds. This blocks until a code is read,
+    /// for at most `api::SCAN_TIMEOUT_MS`; the first time an app scans, the user is asked whether it may
+    /// use the camera.
+    pub fn scan(&self) -> Result<Vec<u8>, ScanError> {
+        let mut buf =
+            Buffer::into_buf(Scan { payload: Vec::new(), error: None }).or(Err(ScanError::Internal))?;
+        buf.lend_mut(self.conn, Opcode::Scan.to_u32().unwrap()).or(Err(ScanError::Internal))?;
+        let scan = buf.to_original::<Scan, _>().or(Err(ScanError::Internal))?;
+        match scan.error {
+            Some(e) => Err(e),
+            None => Ok(scan.payload),
+        }
+    }
+
+    /// Like `scan`, for codes holding text. Other codes are returned with invalid UTF-8 replaced.
+    pub fn scan_text(&self) -> Result<String, ScanError> {
+        self.scan().map(|data| String::from_utf8_lossy(&data).into_owned())
+    }
+
+    /// Tells the server this process has a camera, and will report the codes it reads with `scanned`.
+    pub fn camera_ready(&self) -> Result<(), xous::Error> {
+        send_message(self.conn, Message::new_scalar(Opcode::CameraReady.to_usize().unwrap(), 0, 0, 0, 0))
+            .map(|_| ())
+    }
+
+    /// Reports a code read by the camera to the apps waiting for one.
+    pub fn scanned(&self, payload: &[u8]) -> Result<(), xous::Error> {
+        let buf = Buffer::into_buf(Scan { payload: payload.to_vec(), error: None })
+            .or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, Opcode::Scanned.to_u32().unwrap()).map(|_| ())
+    }
+}
+
+static REFCOUNT: AtomicU32 = AtomicU32::new(0);
+impl Drop for Qr {
+    fn drop(&mut self) {
+        // the connection is shared by every Qr in the process, so only the last one disconnects
+        if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
+            unsafe {
+                xous::disconnect(self.conn).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+#![cfg_attr(target_os = "none", no_std)]
+#![cfg_attr(target_os = "none", no_main)]
+
+use log::info;
+use num_traits::{FromPrimitive, ToPrimitive};
+use permissions_api::{Capability, Permissions};
+use qr::api::*;
+use xous::msg_scalar_unpack;
+use xous_ipc::Buffer;
+
+/// Answers a `Scan`; the caller is unblocked when `msg` is dropped.
+fn reply(mut msg: xous::MessageEnvelope, result: Result<&[u8], ScanError>) {
+    let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+    let answer = match result {
+        Ok(payload) => Scan { payload: payload.to_vec(), error: None },
+        Err(e) => Scan { payload: Vec::new(), error: Some(e) },
+    };
+    buffer.replace(answer).unwrap();
+}
+
+/// Sends `Opcode::Timeout` for scan `id` once it has waited too long.
+fn time_out(id: usize, self_conn: xous::CID) {
+    std::thread::spawn(move || {
+        let tt = ticktimer_server::Ticktimer::new().unwrap();
+        tt.sleep_ms(SCAN_TIMEOUT_MS).unwrap();
+        xous::send_message(
+            self_conn,
+            xous::Message::new_scalar(Opcode::Timeout.to_usize().unwrap(), id, 0, 0, 0),
+        )
+        .expect("couldn't time out scan");
+    });
+}
+
+fn main() -> ! {
+    log_server::init_wait().unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+    info!("my PID is {}", xous::process::id());
+
+    let xns = xous_names::XousNames::new().unwrap();
+    // unlimited connections allowed: any app may scan
+    let qr_sid = xns.register_name(SERVER_NAME_QR, None).expect("can't register server");
+    log::trace!("registered with NS -- {:?}", qr_sid);
+    let self_conn = xous::connect(qr_sid).unwrap();
+    let permissions = Permissions::new();
+
+    // the process that owns the camera, once it says so
+    let mut camera: Option<u32> = None;
+    // scans waiting for a code, by the id their timeout refers to
+    let mut waiting: Vec<(usize, xous::MessageEnvelope)> = Vec::new();
+    let mut next_id = 0;
+
+    log::trace!("ready to accept requests");
+    loop {
+        let msg = xous::receive_message(qr_sid).unwrap();
+        let sender_pid = msg.sender.pid().map(|pid| pid.get() as u32);
+        match FromPrimitive::from_usize(msg.body.id()) {
+            Some(Opcode::Scan) => {
+                if camera.is_none() {
+                    reply(msg, Err(ScanError::NoCamera));
+                } else if !permissions.check(msg.sender, Capability::Camera) {
+                    reply(msg, Err(ScanError::Denied));
+                } else {
+                    time_out(next_id, self_conn);
+                    waiting.push((next_id, msg));
+                    next_id += 1;
+                }
+            }
+            Some(Opcode::Scanned) => {
+                if sender_pid.is_none() || sender_pid != camera {
+                    log::error!("ignoring a code reported by PID {:?}, which isn't the camera", sender_pid);
+                    continue;
+                }
+                let scan = {
+                    let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                    buffer.to_original::<Scan, _>().unwrap()
+                };
+                log::info!("code of {} bytes read, {} scans waiting", scan.payload.len(), waiting.len());
+                for (_, msg) in waiting.drain(..) {
+                    reply(msg, Ok(&scan.payload));
+                }
+            }
+            Some(Opcode::CameraReady) => {
+                log::info!("camera ready in PID {:?}", sender_pid);
+                if camera.is_some() && camera != sender_pid {
+                    log::warn!("replacing the camera in PID {:?}", camera);
+                }
+                camera = sender_pid;
+            }
+            Some(Opcode::Timeout) => msg_scalar_unpack!(msg, id, _, _, _, {
+                if sender_pid != Some(xous::process::id()) {
+                    log::error!("ignoring a timeout sent by PID {:?}", sender_pid);
+                    continue;
+                }
+                // scans already answered have left the list
+                if let Some(i) = waiting.iter().position(|(waiting_id, _)| *waiting_id == id) {
+                    let (_, msg) = waiting.remove(i);
+                    reply(msg, Err(ScanError::TimedOut));
+                }
+            }),
+            Some(Opcode::Quit) => {
+                log::warn!("Quit received, goodbye world!");
+                break;
+            }
+            None => {
+                log::error!("couldn't convert opcode: {:?}", msg);
+            }
+        }
+    }
+    // clean up our program
+    log::trace!("main loop exit, destroying servers");
+    for (_, msg) in waiting.drain(..) {
+        reply(msg, Err(ScanError::NoCamera));
+    }
+    xns.unregister_server(qr_sid).unwrap();
+    xous::destroy_server(qr_sid).unwrap();
+    log::trace!("quitting");
+    xous::terminate_process(0)
+}
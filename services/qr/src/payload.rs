@@ -0,0 +1,142 @@
+//! The formats phones and routers put in QR codes. `otpauth://` URIs are read by the vault's interchange
+//! library, which imports them.
+
+/// How a Wi-Fi network is secured, from the `T` field of a `WIFI:` code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WifiAuth {
+    Open,
+    Wep,
+    /// WPA, WPA2 or WPA3 with a passphrase
+    Wpa,
+    /// anything else, e.g. WPA2-EAP, by name
+    Other(String),
+}
+
+/// A Wi-Fi network shared as `WIFI:T:WPA;S:<ssid>;P:<password>;;`. Fields come in any order, and `\`
+/// escapes `\`, `;`, `,`, `:` and `"` in their values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wifi {
+    pub ssid: String,
+    pub password: String,
+    pub auth: WifiAuth,
+    pub hidden: bool,
+}
+
+impl Wifi {
+    pub fn parse(text: &str) -> Option<Self> {
+        let fields = text.strip_prefix("WIFI:")?;
+        let mut wifi =
+            Wifi { ssid: String::new(), password: String::new(), auth: WifiAuth::Wpa, hidden: false };
+        let mut auth = None;
+        let mut chars = fields.chars();
+        loop {
+            let mut name = String::new();
+            let mut value = String::new();
+            let mut in_value = false;
+            while let Some(c) = chars.next() {
+                match c {
+                    ';' => break,
+                    ':' if !in_value => in_value = true,
+                    '\\' if in_value => value.extend(chars.next()),
+                    c if in_value => value.push(c),
+                    c => name.push(c),
+                }
+            }
+            match name.as_str() {
+                // the terminating `;;`, or the end of a code that left it off
+                "" => break,
+                "S" => wifi.ssid = value,
+                "P" => wifi.password = value,
+                "T" => auth = Some(value),
+                "H" => wifi.hidden = value.eq_ignore_ascii_case("true"),
+                _ => log::debug!("ignoring field {} of a WIFI code", name),
+            }
+        }
+        if wifi.ssid.is_empty() {
+            return None;
+        }
+        wifi.auth = match auth.as_deref() {
+            Some("nopass") => WifiAuth::Open,
+            None | Some("") if wifi.password.is_empty() => WifiAuth::Open,
+            None | Some("") | Some("WPA") | Some("WPA2") | Some("WPA3") | Some("SAE") => WifiAuth::Wpa,
+            Some("WEP") => WifiAuth::Wep,
+            Some(other) => WifiAuth::Other(other.to_string()),
+        };
+        Some(wifi)
+    }
+}
+
+impl core::fmt::Display for Wifi {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let escape = |s: &str| {
+            s.chars().fold(String::new(), |mut escaped, c| {
+                if "\\;,:\"".contains(c) {
+                    escaped.push('\\');
+                }
+                escaped.push(c);
+                escaped
+            })
+        };
+        let auth = match &self.auth {
+            WifiAuth::Open => "nopass",
+            WifiAuth::Wep => "WEP",
+            WifiAuth::Wpa => "WPA",
+            WifiAuth::Other(name) => name,
+        };
+        write!(f, "WIFI:T:{};S:{};", auth, escape(&self.ssid))?;
+        if self.auth != WifiAuth::Open {
+            write!(f, "P:{};", escape(&self.password))?;
+        }
+        if self.hidden {
+            write!(f, "H:true;")?;
+        }
+        write!(f, ";")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wifi_parse() {
+        let wifi = Wifi::parse("WIFI:T:WPA;S:Home \\; Office;P:correct horse;;").unwrap();
+        assert_eq!(wifi.ssid, "Home ; Office");
+        assert_eq!(wifi.password, "correct horse");
+        assert_eq!(wifi.auth, WifiAuth::Wpa);
+        assert!(!wifi.hidden);
+
+        let wifi = Wifi::parse(r#"WIFI:P:a\:b\\c\"d;H:true;S:x,y;T:WEP"#).unwrap();
+        assert_eq!((wifi.ssid.as_str(), wifi.password.as_str()), ("x,y", r#"a:b\c"d"#));
+        assert_eq!(wifi.auth, WifiAuth::Wep);
+        assert!(wifi.hidden);
+
+        let wifi = Wifi::parse("WIFI:S:cafe;T:nopass;P:;;").unwrap();
+        assert_eq!(wifi.auth, WifiAuth::Open);
+        let wifi = Wifi::parse("WIFI:S:cafe;P:secret;;").unwrap();
+        assert_eq!(wifi.auth, WifiAuth::Wpa);
+        let wifi = Wifi::parse("WIFI:T:WPA2-EAP;S:corp;P:x;;").unwrap();
+        assert_eq!(wifi.auth, WifiAuth::Other("WPA2-EAP".to_string()));
+
+        assert_eq!(Wifi::parse("WIFI:T:WPA;P:nossid;;"), None);
+        assert_eq!(Wifi::parse("otpauth://totp/x?secret=AAAA"), None);
+    }
+
+    #[test]
+    fn test_wifi_round_trip() {
+        let wifi = Wifi {
+            ssid: "a;b:c".to_string(),
+            password: "p\\w,\"d".to_string(),
+            auth: WifiAuth::Wpa,
+            hidden: true,
+        };
+        let text = wifi.to_string();
+        assert_eq!(text, r#"WIFI:T:WPA;S:a\;b\:c;P:p\\w\,\"d;H:true;;"#);
+        assert_eq!(Wifi::parse(&text), Some(wifi));
+
+        let open =
+            Wifi { ssid: "cafe".to_string(), password: String::new(), auth: WifiAuth::Open, hidden: false };
+        assert_eq!(open.to_string(), "WIFI:T:nopass;S:cafe;;");
+        assert_eq!(Wifi::parse(&open.to_string()), Some(open));
+    }
+}
@@ -0,0 +1,102 @@
+//! Netpbm greymaps, the simplest image files to hand the decoder when testing it away from a camera.
+//! Any image viewer or converter reads and writes them, e.g. `convert photo.jpg code.pgm`.
+
+/// A greyscale image, one byte per pixel, row by row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Greymap {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Greymap {
+    /// Reads a binary (`P5`) or plain (`P2`) greymap. Pixels deeper than 8 bits are scaled down.
+    pub fn from_pgm(data: &[u8]) -> Option<Self> {
+        let mut pos = 0;
+        let magic = next_token(data, &mut pos)?;
+        let width = parse(next_token(data, &mut pos)?)?;
+        let height = parse(next_token(data, &mut pos)?)?;
+        let maxval = parse(next_token(data, &mut pos)?)?;
+        if width == 0 || height == 0 || maxval == 0 || maxval > 65535 {
+            return None;
+        }
+        let len = width.checked_mul(height)?;
+        let scale = |value: usize| (value.min(maxval) * 255 / maxval) as u8;
+        let pixels = match magic {
+            b"P5" => {
+                // exactly one whitespace byte separates the header from the pixels
+                let raster = data.get(pos + 1..)?;
+                if maxval < 256 {
+                    raster.get(..len)?.iter().map(|&p| scale(p as usize)).collect()
+                } else {
+                    let raster = raster.get(..len * 2)?;
+                    raster.chunks(2).map(|p| scale((p[0] as usize) << 8 | p[1] as usize)).collect()
+                }
+            }
+            b"P2" => {
+                let mut pixels = Vec::with_capacity(len);
+                for _ in 0..len {
+                    pixels.push(scale(parse(next_token(data, &mut pos)?)?));
+                }
+                pixels
+            }
+            _ => return None,
+        };
+        Some(Greymap { width, height, pixels })
+    }
+
+    /// Writes the image as a binary greymap.
+    pub fn to_pgm(&self) -> Vec<u8> {
+        let mut data = format!("P5\n{} {}\n255\n", self.width, self.height).into_bytes();
+        data.extend_from_slice(&self.pixels);
+        data
+    }
+}
+
+/// The next whitespace-separated token of a header, skipping `#` comments, leaving `pos` on the byte
+/// after it.
+fn next_token<'a>(data: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    loop {
+        match data.get(*pos)? {
+            b'#' => {
+                while *data.get(*pos)? != b'\n' {
+                    *pos += 1;
+                }
+            }
+            c if c.is_ascii_whitespace() => *pos += 1,
+            _ => break,
+        }
+    }
+    let start = *pos;
+    while data.get(*pos).map_or(false, |c| !c.is_ascii_whitespace() && *c != b'#') {
+        *pos += 1;
+    }
+    Some(&data[start..*pos])
+}
+
+fn parse(token: &[u8]) -> Option<usize> { core::str::from_utf8(token).ok()?.parse().ok() }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pgm() {
+        let plain = b"P2\n# a comment\n3 2\n# another\n15\n0 15 7\n15 0\n3\n";
+        let map = Greymap::from_pgm(plain).unwrap();
+        assert_eq!((map.width, map.height), (3, 2));
+        assert_eq!(map.pixels, vec![0, 255, 119, 255, 0, 51]);
+
+        let binary = map.to_pgm();
+        assert!(binary.starts_with(b"P5\n3 2\n255\n"));
+        assert_eq!(Greymap::from_pgm(&binary), Some(map));
+
+        // 16 bit pixels are big-endian
+        let deep = b"P5 2 1 65535\n\xff\xff\x80\x00";
+        assert_eq!(Greymap::from_pgm(deep).unwrap().pixels, vec![255, 127]);
+
+        assert_eq!(Greymap::from_pgm(b"P5\n3 2\n255\n\0\0"), None);
+        assert_eq!(Greymap::from_pgm(b"P6\n1 1\n255\n\0\0\0"), None);
+        assert_eq!(Greymap::from_pgm(b"P2\n1 1\n"), None);
+    }
+}
@@ -283,7 +283,7 @@ pub enum BackupLanguage {
 }
 impl Default for BackupLanguage {
     fn default() -> Self {
-        match locales::LANG {
+        match locales::lang() {
             "en" => BackupLanguage::En,
             "en-tts" => BackupLanguage::EnTts,
             "ja" => BackupLanguage::Ja,
@@ -1107,7 +1107,7 @@ impl<'a> RootKeys {
         // now show the init wait note...
         rootkeys_modal.modify(
             Some(ActionType::Slider(progress_action)),
-            Some(t!("rootkeys.setup_wait", locales::LANG)),
+            Some(t!("rootkeys.setup_wait", locales::lang())),
             false,
             None,
             true,
@@ -1295,12 +1295,12 @@ impl<'a> RootKeys {
         }
 
         // sign the kernel
-        pb.update_text(t!("rootkeys.init.signing_kernel", locales::LANG));
+        pb.update_text(t!("rootkeys.init.signing_kernel", locales::lang()));
         pb.set_percentage(15);
         let (kernel_sig, kernel_len) = self.sign_kernel(&keypair);
 
         // sign the loader
-        pb.update_text(t!("rootkeys.init.signing_loader", locales::LANG));
+        pb.update_text(t!("rootkeys.init.signing_loader", locales::lang()));
         pb.rebase_subtask_percentage(20, 30);
         let (loader_sig, loader_len) = self.sign_loader(&keypair, Some(&mut pb));
         log::debug!("loader signature: {:x?}", loader_sig.to_bytes());
@@ -1322,7 +1322,7 @@ impl<'a> RootKeys {
         // Because we're initializing keys for the *first* time, make a backup copy of the bitstream to
         // the staging area. Note that if we're doing an update, the update target would already be
         // in the staging area, so this step should be skipped.
-        pb.update_text(t!("rootkeys.init.backup_gateware", locales::LANG));
+        pb.update_text(t!("rootkeys.init.backup_gateware", locales::lang()));
         pb.rebase_subtask_percentage(30, 50);
         self.make_gateware_backup(Some(&mut pb), false)?;
 
@@ -1343,7 +1343,7 @@ impl<'a> RootKeys {
 
         // compute the keyrom patch set for the bitstream
         // at this point the KEYROM as replicated in sensitive_slice should have all its assets in place
-        pb.update_text(t!("rootkeys.init.patching_keys", locales::LANG));
+        pb.update_text(t!("rootkeys.init.patching_keys", locales::lang()));
         pb.rebase_subtask_percentage(50, 70);
 
         self.gateware_copy_and_patch(&src_oracle, &dst_oracle, Some(&mut pb))?;
@@ -1359,12 +1359,12 @@ impl<'a> RootKeys {
             .map_err(|_| RootkeyResult::FlashError)?;
 
         // verify that the patch worked
-        pb.update_text(t!("rootkeys.init.verifying_gateware", locales::LANG));
+        pb.update_text(t!("rootkeys.init.verifying_gateware", locales::lang()));
         pb.rebase_subtask_percentage(70, 90);
         self.verify_gateware(&dst_oracle, Some(&mut pb))?;
 
         // sign the image, commit the signature
-        pb.update_text(t!("rootkeys.init.commit_signatures", locales::LANG));
+        pb.update_text(t!("rootkeys.init.commit_signatures", locales::lang()));
         self.commit_signature(loader_sig, loader_len, SignatureType::Loader)?;
         log::debug!("loader {} bytes, sig: {:x?}", loader_len, loader_sig.to_bytes());
         pb.set_percentage(92);
@@ -1452,7 +1452,7 @@ impl<'a> RootKeys {
             // now show the init wait note...
             rootkeys_modal.modify(
                 Some(ActionType::Slider(progress_action)),
-                Some(t!("rootkeys.gwup_starting", locales::LANG)),
+                Some(t!("rootkeys.gwup_starting", locales::lang())),
                 false,
                 None,
                 true,
@@ -1634,17 +1634,20 @@ impl<'a> RootKeys {
                 } else if update_type == UpdateType::EfuseProvision {
                     // share the backup key with the user so it can be saved somewhere safe
                     modals
-                        .show_bip39(Some(t!("rootkeys.backup_key", locales::LANG)), &pcache.fpga_key.to_vec())
+                        .show_bip39(
+                            Some(t!("rootkeys.backup_key", locales::lang())),
+                            &pcache.fpga_key.to_vec(),
+                        )
                         .ok();
                     loop {
-                        modals.add_list_item(t!("rootkeys.gwup.yes", locales::LANG)).expect("modals error");
-                        modals.add_list_item(t!("rootkeys.gwup.no", locales::LANG)).expect("modals error");
+                        modals.add_list_item(t!("rootkeys.gwup.yes", locales::lang())).expect("modals error");
+                        modals.add_list_item(t!("rootkeys.gwup.no", locales::lang())).expect("modals error");
                         log::info!("{}ROOTKEY.CONFIRM,{}", xous::BOOKEND_START, xous::BOOKEND_END);
-                        match modals.get_radiobutton(t!("rootkeys.backup_verify", locales::LANG)) {
+                        match modals.get_radiobutton(t!("rootkeys.backup_verify", locales::lang())) {
                             Ok(response) => {
-                                if response == t!("rootkeys.gwup.yes", locales::LANG) {
+                                if response == t!("rootkeys.gwup.yes", locales::lang()) {
                                     match modals
-                                        .input_bip39(Some(t!("rootkeys.backup_key_enter", locales::LANG)))
+                                        .input_bip39(Some(t!("rootkeys.backup_key_enter", locales::lang())))
                                     {
                                         Ok(verify) => {
                                             log::debug!("got bip39 verification: {:x?}", verify);
@@ -1652,7 +1655,7 @@ impl<'a> RootKeys {
                                                 log::debug!("verify succeeded");
                                                 modals
                                                     .show_notification(
-                                                        t!("rootkeys.backup_key_match", locales::LANG),
+                                                        t!("rootkeys.backup_key_match", locales::lang()),
                                                         None,
                                                     )
                                                     .ok();
@@ -1663,7 +1666,7 @@ impl<'a> RootKeys {
                                                     .show_bip39(
                                                         Some(t!(
                                                             "rootkeys.backup_key_mismatch",
-                                                            locales::LANG
+                                                            locales::lang()
                                                         )),
                                                         &pcache.fpga_key.to_vec(),
                                                     )
@@ -1674,7 +1677,7 @@ impl<'a> RootKeys {
                                             log::debug!("bip39 verification aborted");
                                             modals
                                                 .show_bip39(
-                                                    Some(t!("rootkeys.backup_key_mismatch", locales::LANG)),
+                                                    Some(t!("rootkeys.backup_key_mismatch", locales::lang())),
                                                     &pcache.fpga_key.to_vec(),
                                                 )
                                                 .ok();
@@ -1695,7 +1698,7 @@ impl<'a> RootKeys {
             // *now* show the progress bar...
             rootkeys_modal.modify(
                 Some(ActionType::Slider(progress_action)),
-                Some(t!("rootkeys.gwup_starting", locales::LANG)),
+                Some(t!("rootkeys.gwup_starting", locales::lang())),
                 false,
                 None,
                 true,
@@ -1744,7 +1747,7 @@ impl<'a> RootKeys {
 
         let mut next_progress =
             if (update_type == UpdateType::BbramProvision) || (update_type == UpdateType::EfuseProvision) {
-                pb.update_text(t!("rootkeys.init.backup_gateware", locales::LANG));
+                pb.update_text(t!("rootkeys.init.backup_gateware", locales::lang()));
                 pb.rebase_subtask_percentage(5, 25);
                 self.make_gateware_backup(Some(&mut pb), false)?;
                 25
@@ -1781,7 +1784,7 @@ impl<'a> RootKeys {
         log::info!("destination key type: {:?}", dst_oracle.get_target_key_type());
 
         pb.set_percentage(next_progress);
-        pb.update_text(t!("rootkeys.init.patching_keys", locales::LANG));
+        pb.update_text(t!("rootkeys.init.patching_keys", locales::lang()));
         pb.rebase_subtask_percentage(next_progress, 60);
         self.gateware_copy_and_patch(&src_oracle, &dst_oracle, Some(&mut pb))?;
 
@@ -1796,7 +1799,7 @@ impl<'a> RootKeys {
             .map_err(|_| RootkeyResult::FlashError)?;
 
         // verify that the patch worked
-        pb.update_text(t!("rootkeys.init.verifying_gateware", locales::LANG));
+        pb.update_text(t!("rootkeys.init.verifying_gateware", locales::lang()));
         pb.rebase_subtask_percentage(60, 75);
         log::debug!("making verification oracle");
         let verify_oracle = match BitstreamOracle::new(
@@ -1817,23 +1820,23 @@ impl<'a> RootKeys {
 
         // commit signatures
         let keypair = if let Some(kp) = keypair {
-            pb.update_text(t!("rootkeys.init.signing_gateware", locales::LANG));
+            pb.update_text(t!("rootkeys.init.signing_gateware", locales::lang()));
             let (gateware_sig, gateware_len) = self.sign_gateware(&kp);
             log::debug!("gateware signature ({}): {:x?}", gateware_len, gateware_sig.to_bytes());
             self.commit_signature(gateware_sig, gateware_len, SignatureType::Gateware)?;
 
             // sign the kernel
-            pb.update_text(t!("rootkeys.init.signing_kernel", locales::LANG));
+            pb.update_text(t!("rootkeys.init.signing_kernel", locales::lang()));
             pb.set_percentage(80);
             let (kernel_sig, kernel_len) = self.sign_kernel(&kp);
 
             // sign the loader
-            pb.update_text(t!("rootkeys.init.signing_loader", locales::LANG));
+            pb.update_text(t!("rootkeys.init.signing_loader", locales::lang()));
             pb.rebase_subtask_percentage(85, 92);
             let (loader_sig, loader_len) = self.sign_loader(&kp, Some(&mut pb));
 
             // commit the signatures
-            pb.update_text(t!("rootkeys.init.commit_signatures", locales::LANG));
+            pb.update_text(t!("rootkeys.init.commit_signatures", locales::lang()));
             self.commit_signature(loader_sig, loader_len, SignatureType::Loader)?;
             log::debug!("loader {} bytes, sig: {:x?}", loader_len, loader_sig.to_bytes());
             pb.set_percentage(88);
@@ -1895,12 +1898,12 @@ impl<'a> RootKeys {
             // been rebooted
             self.ticktimer.sleep_ms(10_000).unwrap();
 
-            pb.update_text(t!("rootkeys.bbram.failed_restore", locales::LANG));
+            pb.update_text(t!("rootkeys.bbram.failed_restore", locales::lang()));
             pb.set_percentage(0);
             pb.rebase_subtask_percentage(0, 100);
             self.make_gateware_backup(Some(&mut pb), true)?;
         } else if update_type == UpdateType::EfuseProvision {
-            pb.update_text(t!("rootkeys.efuse_burning", locales::LANG));
+            pb.update_text(t!("rootkeys.efuse_burning", locales::lang()));
             self.ticktimer.sleep_ms(300).ok();
             pb.set_percentage(93);
             self.ticktimer.sleep_ms(300).ok();
@@ -1919,7 +1922,7 @@ impl<'a> RootKeys {
             match self.jtag.efuse_key_burn(pcache.fpga_key) {
                 Ok(result) => {
                     if !result {
-                        pb.update_text(t!("rootkeys.efuse_burn_fail", locales::LANG));
+                        pb.update_text(t!("rootkeys.efuse_burn_fail", locales::lang()));
                         self.ticktimer.sleep_ms(2000).ok();
                         return Err(RootkeyResult::KeyError);
                     } else {
@@ -1929,7 +1932,7 @@ impl<'a> RootKeys {
                 Err(e) => {
                     pb.update_text(&format!(
                         "{}\n{:?}",
-                        t!("rootkeys.efuse_internal_error", locales::LANG),
+                        t!("rootkeys.efuse_internal_error", locales::lang()),
                         e
                     ));
                     self.ticktimer.sleep_ms(2000).ok();
@@ -1950,13 +1953,13 @@ impl<'a> RootKeys {
             }
 
             // seal the device from key readout, force encrypted boot
-            pb.update_text(t!("rootkeys.efuse_sealing", locales::LANG));
+            pb.update_text(t!("rootkeys.efuse_sealing", locales::lang()));
             pb.set_percentage(96);
             log::info!("{}EFUSE.SEAL,{}", xous::BOOKEND_START, xous::BOOKEND_END);
             match self.jtag.seal_device() {
                 Ok(result) => {
                     if !result {
-                        pb.update_text(t!("rootkeys.efuse_seal_fail", locales::LANG));
+                        pb.update_text(t!("rootkeys.efuse_seal_fail", locales::lang()));
                         self.ticktimer.sleep_ms(2000).ok();
                         return Err(RootkeyResult::FlashError);
                     }
@@ -1964,7 +1967,7 @@ impl<'a> RootKeys {
                 Err(e) => {
                     pb.update_text(&format!(
                         "{}\n{:?}",
-                        t!("rootkeys.efuse_internal_error", locales::LANG),
+                        t!("rootkeys.efuse_internal_error", locales::lang()),
                         e
                     ));
                     self.ticktimer.sleep_ms(2000).ok();
@@ -2009,7 +2012,7 @@ impl<'a> RootKeys {
         // now show the init wait note...
         rootkeys_modal.modify(
             Some(ActionType::Slider(progress_action)),
-            Some(t!("rootkeys.gwup_starting", locales::LANG)),
+            Some(t!("rootkeys.gwup_starting", locales::lang())),
             false,
             None,
             true,
@@ -2077,19 +2080,19 @@ impl<'a> RootKeys {
         // a TOCTOU by not re-verifying them.
 
         // sign the kernel
-        pb.update_text(t!("rootkeys.init.signing_kernel", locales::LANG));
+        pb.update_text(t!("rootkeys.init.signing_kernel", locales::lang()));
         pb.set_percentage(35);
         let (kernel_sig, kernel_len) = self.sign_kernel(&keypair);
 
         // sign the loader
-        pb.update_text(t!("rootkeys.init.signing_loader", locales::LANG));
+        pb.update_text(t!("rootkeys.init.signing_loader", locales::lang()));
         pb.rebase_subtask_percentage(35, 85);
         let (loader_sig, loader_len) = self.sign_loader(&keypair, Some(&mut pb));
         log::info!("loader signature: {:x?}", loader_sig.to_bytes());
         log::info!("loader len: {} bytes", loader_len);
 
         // commit the signatures
-        pb.update_text(t!("rootkeys.init.commit_signatures", locales::LANG));
+        pb.update_text(t!("rootkeys.init.commit_signatures", locales::lang()));
         self.commit_signature(loader_sig, loader_len, SignatureType::Loader)?;
         log::debug!("loader {} bytes, sig: {:x?}", loader_len, loader_sig.to_bytes());
         pb.set_percentage(90);
@@ -2127,7 +2130,7 @@ impl<'a> RootKeys {
         // now show the init wait note...
         rootkeys_modal.modify(
             Some(ActionType::Slider(progress_action)),
-            Some(t!("rootkeys.setup_wait", locales::LANG)),
+            Some(t!("rootkeys.setup_wait", locales::lang())),
             false,
             None,
             true,
@@ -2239,7 +2242,7 @@ impl<'a> RootKeys {
         progress_action.set_is_password(true);
         rootkeys_modal.modify(
             Some(ActionType::Slider(progress_action)),
-            Some(t!("rootkeys.gwup_starting", locales::LANG)),
+            Some(t!("rootkeys.gwup_starting", locales::lang())),
             false,
             None,
             true,
@@ -3150,7 +3153,7 @@ impl<'a> RootKeys {
             progress_action.set_is_password(true);
             rootkeys_modal.modify(
                 Some(ActionType::Slider(progress_action)),
-                Some(t!("rootkeys.gwup_starting", locales::LANG)),
+                Some(t!("rootkeys.gwup_starting", locales::lang())),
                 false,
                 None,
                 true,
@@ -214,7 +214,7 @@ mod implementation {
             main_cid: xous::CID,
         ) -> Result<(), RootkeyResult> {
             self.xous_init_interlock();
-            self.fake_progress(rootkeys_modal, main_cid, t!("rootkeys.setup_wait", locales::LANG))
+            self.fake_progress(rootkeys_modal, main_cid, t!("rootkeys.setup_wait", locales::lang()))
         }
 
         pub fn do_gateware_update(
@@ -225,7 +225,7 @@ mod implementation {
             _updatetype: UpdateType,
         ) -> Result<(), RootkeyResult> {
             self.xous_init_interlock();
-            self.fake_progress(rootkeys_modal, main_cid, t!("rootkeys.gwup_starting", locales::LANG))
+            self.fake_progress(rootkeys_modal, main_cid, t!("rootkeys.gwup_starting", locales::lang()))
         }
 
         pub fn do_gateware_provision_uninitialized(
@@ -233,7 +233,7 @@ mod implementation {
             rootkeys_modal: &mut Modal,
             main_cid: xous::CID,
         ) -> Result<(), RootkeyResult> {
-            self.fake_progress(rootkeys_modal, main_cid, t!("rootkeys.gwup_starting", locales::LANG))
+            self.fake_progress(rootkeys_modal, main_cid, t!("rootkeys.gwup_starting", locales::lang()))
         }
 
         pub fn do_sign_xous(
@@ -242,7 +242,7 @@ mod implementation {
             main_cid: xous::CID,
         ) -> Result<(), RootkeyResult> {
             self.xous_init_interlock();
-            self.fake_progress(rootkeys_modal, main_cid, t!("rootkeys.init.signing_kernel", locales::LANG))
+            self.fake_progress(rootkeys_modal, main_cid, t!("rootkeys.init.signing_kernel", locales::lang()))
         }
 
         pub fn purge_password(&mut self, _ptype: PasswordType) {}
@@ -612,7 +612,7 @@ fn main() -> ! {
     {
         let mut menu_items = Vec::<MenuItem>::new();
         menu_items.push(MenuItem {
-            name: String::from(t!("rootkeys.policy_keep", locales::LANG)),
+            name: String::from(t!("rootkeys.policy_keep", locales::lang())),
             action_conn: Some(main_cid),
             action_opcode: Opcode::UxPolicyReturn.to_u32().unwrap(),
             action_payload: MenuPayload::Scalar([
@@ -624,7 +624,7 @@ fn main() -> ! {
             close_on_select: true,
         });
         menu_items.push(MenuItem {
-            name: String::from(t!("rootkeys.policy_suspend", locales::LANG)),
+            name: String::from(t!("rootkeys.policy_suspend", locales::lang())),
             action_conn: Some(main_cid),
             action_opcode: Opcode::UxPolicyReturn.to_u32().unwrap(),
             action_payload: MenuPayload::Scalar([
@@ -636,7 +636,7 @@ fn main() -> ! {
             close_on_select: true,
         });
         menu_items.push(MenuItem {
-            name: String::from(t!("rootkeys.policy_clear", locales::LANG)),
+            name: String::from(t!("rootkeys.policy_clear", locales::lang())),
             action_conn: Some(main_cid),
             action_opcode: Opcode::UxPolicyReturn.to_u32().unwrap(),
             action_payload: MenuPayload::Scalar([
@@ -668,7 +668,7 @@ fn main() -> ! {
     let mut rootkeys_modal = Modal::new(
         gam::ROOTKEY_MODAL_NAME,
         ActionType::TextEntry(password_action.clone()),
-        Some(t!("rootkeys.updatefirstpass", locales::LANG)),
+        Some(t!("rootkeys.updatefirstpass", locales::lang())),
         None,
         gam::SYSTEM_STYLE,
         8,
@@ -776,10 +776,10 @@ fn main() -> ! {
 
                     if keys.is_initialized() {
                         modals
-                            .show_notification(t!("rootkeys.already_init", locales::LANG), None)
+                            .show_notification(t!("rootkeys.already_init", locales::lang()), None)
                             .expect("modals error");
                         #[cfg(feature = "tts")]
-                        tts.tts_blocking(t!("rootkeys.already_init", locales::LANG)).unwrap();
+                        tts.tts_blocking(t!("rootkeys.already_init", locales::lang())).unwrap();
                         keys.set_ux_password_type(None);
                         if let Some(dr) = deferred_response.take() {
                             xous::return_scalar(dr, 0).unwrap();
@@ -787,31 +787,33 @@ fn main() -> ! {
                         continue;
                     } else {
                         modals
-                            .add_list_item(t!("rootkeys.confirm.yes", locales::LANG))
+                            .add_list_item(t!("rootkeys.confirm.yes", locales::lang()))
+                            .expect("modals error");
+                        modals
+                            .add_list_item(t!("rootkeys.confirm.no", locales::lang()))
                             .expect("modals error");
-                        modals.add_list_item(t!("rootkeys.confirm.no", locales::LANG)).expect("modals error");
                         if deferred_response.is_some() {
                             modals
-                                .add_list_item(t!("rootkeys.confirm.dont_ask", locales::LANG))
+                                .add_list_item(t!("rootkeys.confirm.dont_ask", locales::lang()))
                                 .expect("modals error");
                             log::info!("{}ROOTKEY.INITQ3,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                         } else {
                             log::info!("{}ROOTKEY.CONFIRM,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                         }
-                        match modals.get_radiobutton(t!("rootkeys.confirm", locales::LANG)) {
+                        match modals.get_radiobutton(t!("rootkeys.confirm", locales::lang())) {
                             Ok(response) => {
-                                if response == t!("rootkeys.confirm.no", locales::LANG) {
+                                if response == t!("rootkeys.confirm.no", locales::lang()) {
                                     if let Some(dr) = deferred_response.take() {
                                         xous::return_scalar(dr, 0).unwrap();
                                     }
                                     continue;
-                                } else if response == t!("rootkeys.confirm.dont_ask", locales::LANG) {
+                                } else if response == t!("rootkeys.confirm.dont_ask", locales::lang()) {
                                     keys.set_dont_ask_init();
                                     if let Some(dr) = deferred_response.take() {
                                         xous::return_scalar(dr, 0).unwrap();
                                     }
                                     continue;
-                                } else if response != t!("rootkeys.confirm.yes", locales::LANG) {
+                                } else if response != t!("rootkeys.confirm.yes", locales::lang()) {
                                     log::error!("Got unexpected response: {:?}", response);
                                     if let Some(dr) = deferred_response.take() {
                                         xous::return_scalar(dr, 0).unwrap();
@@ -834,14 +836,14 @@ fn main() -> ! {
                         .set_action_opcode(Opcode::UxInitUpdateFirstPasswordReturn.to_u32().unwrap());
                     rootkeys_modal.modify(
                         Some(ActionType::TextEntry(password_action.clone())),
-                        Some(t!("rootkeys.updatefirstpass", locales::LANG)),
+                        Some(t!("rootkeys.updatefirstpass", locales::lang())),
                         false,
                         None,
                         true,
                         None,
                     );
                     #[cfg(feature = "tts")]
-                    tts.tts_blocking(t!("rootkeys.updatefirstpass", locales::LANG)).unwrap();
+                    tts.tts_blocking(t!("rootkeys.updatefirstpass", locales::lang())).unwrap();
                     log::info!("{}ROOTKEY.UPDPW,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                     rootkeys_modal.activate();
                 }
@@ -862,14 +864,14 @@ fn main() -> ! {
                 password_action.set_action_opcode(Opcode::UxInitUpdatePasswordReturn.to_u32().unwrap());
                 rootkeys_modal.modify(
                     Some(ActionType::TextEntry(password_action.clone())),
-                    Some(t!("rootkeys.updatepass", locales::LANG)),
+                    Some(t!("rootkeys.updatepass", locales::lang())),
                     false,
                     None,
                     true,
                     None,
                 );
                 #[cfg(feature = "tts")]
-                tts.tts_blocking(t!("rootkeys.updatepass", locales::LANG)).unwrap();
+                tts.tts_blocking(t!("rootkeys.updatepass", locales::lang())).unwrap();
                 log::info!("{}ROOTKEY.UPDPW,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                 rootkeys_modal.activate();
             }
@@ -879,11 +881,11 @@ fn main() -> ! {
 
                 if !keys.hash_and_save_password(plaintext_pw.first().as_str(), true) {
                     // password did not verify
-                    modals.add_list_item(t!("rootkeys.gwup.yes", locales::LANG)).expect("modals error");
-                    modals.add_list_item(t!("rootkeys.gwup.no", locales::LANG)).expect("modals error");
-                    match modals.get_radiobutton(t!("rootkeys.updatepass_fail", locales::LANG)) {
+                    modals.add_list_item(t!("rootkeys.gwup.yes", locales::lang())).expect("modals error");
+                    modals.add_list_item(t!("rootkeys.gwup.no", locales::lang())).expect("modals error");
+                    match modals.get_radiobutton(t!("rootkeys.updatepass_fail", locales::lang())) {
                         Ok(response) => {
-                            if response == t!("rootkeys.gwup.yes", locales::LANG) {
+                            if response == t!("rootkeys.gwup.yes", locales::lang()) {
                                 keys.purge_password(PasswordType::Update);
                                 // request the update password first
                                 keys.set_ux_password_type(Some(PasswordType::Update));
@@ -893,14 +895,14 @@ fn main() -> ! {
                                 );
                                 rootkeys_modal.modify(
                                     Some(ActionType::TextEntry(password_action.clone())),
-                                    Some(t!("rootkeys.updatefirstpass", locales::LANG)),
+                                    Some(t!("rootkeys.updatefirstpass", locales::lang())),
                                     false,
                                     None,
                                     true,
                                     None,
                                 );
                                 #[cfg(feature = "tts")]
-                                tts.tts_blocking(t!("rootkeys.updatefirstpass", locales::LANG)).unwrap();
+                                tts.tts_blocking(t!("rootkeys.updatefirstpass", locales::lang())).unwrap();
                                 log::info!("{}ROOTKEY.UPDPW,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                                 rootkeys_modal.activate();
                                 continue;
@@ -960,27 +962,27 @@ fn main() -> ! {
                     }
                     Err(RootkeyResult::AlignmentError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_alignment", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_alignment", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::KeyError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_key", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_key", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::IntegrityError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_verify", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_verify", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::FlashError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_burn", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_burn", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::StateError) => {
                         modals
-                            .show_notification(t!("rootkeys.wrong_state", locales::LANG), None)
+                            .show_notification(t!("rootkeys.wrong_state", locales::lang()), None)
                             .expect("modals error");
                     }
                 }
@@ -1002,7 +1004,7 @@ fn main() -> ! {
                     // 1.5V
                     // if power is plugged in, request that it be removed
                     modals
-                        .show_notification(t!("rootkeys.init.unplug_power", locales::LANG), None)
+                        .show_notification(t!("rootkeys.init.unplug_power", locales::lang()), None)
                         .expect("modals error");
                     log::info!("vbus is high, holding off on reboot");
                     send_message(
@@ -1013,7 +1015,7 @@ fn main() -> ! {
                 } else {
                     log::info!("initiating reboot");
                     modals
-                        .dynamic_notification(Some(t!("rootkeys.init.finished", locales::LANG)), None)
+                        .dynamic_notification(Some(t!("rootkeys.init.finished", locales::lang())), None)
                         .expect("modals error");
                     xous::yield_slice(); // these are necessary to get the messages in place to do a full redraw before the reboot happens
                     log::info!("going to reboot state");
@@ -1050,14 +1052,14 @@ fn main() -> ! {
                 .expect("couldn't initiate dialog box");
             }
             Some(Opcode::UxBlindCopy) => {
-                modals.add_list_item(t!("rootkeys.gwup.yes", locales::LANG)).expect("modals error");
-                modals.add_list_item(t!("rootkeys.gwup.no", locales::LANG)).expect("modals error");
-                match modals.get_radiobutton(t!("rootkeys.blind_update", locales::LANG)) {
+                modals.add_list_item(t!("rootkeys.gwup.yes", locales::lang())).expect("modals error");
+                modals.add_list_item(t!("rootkeys.gwup.no", locales::lang())).expect("modals error");
+                match modals.get_radiobutton(t!("rootkeys.blind_update", locales::lang())) {
                     Ok(response) => {
-                        if response == t!("rootkeys.gwup.no", locales::LANG) {
+                        if response == t!("rootkeys.gwup.no", locales::lang()) {
                             continue;
                         }
-                        if response != t!("rootkeys.gwup.yes", locales::LANG) {
+                        if response != t!("rootkeys.gwup.yes", locales::lang()) {
                             log::error!("got unexpected response from radio box: {:?}", response);
                             continue;
                         } else {
@@ -1078,34 +1080,34 @@ fn main() -> ! {
                 match result {
                     Ok(_) => {
                         modals
-                            .show_notification(t!("rootkeys.gwup.finished", locales::LANG), None)
+                            .show_notification(t!("rootkeys.gwup.finished", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::AlignmentError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_alignment", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_alignment", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::KeyError) => {
                         // probably a bad password, purge it, so the user can try again
                         keys.purge_password(PasswordType::Update);
                         modals
-                            .show_notification(t!("rootkeys.init.fail_key", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_key", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::IntegrityError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_verify", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_verify", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::FlashError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_burn", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_burn", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::StateError) => {
                         modals
-                            .show_notification(t!("rootkeys.wrong_state", locales::LANG), None)
+                            .show_notification(t!("rootkeys.wrong_state", locales::lang()), None)
                             .expect("modals error");
                     }
                 }
@@ -1126,19 +1128,19 @@ fn main() -> ! {
                 //  - proceed with update question "Proceed with update? (yes/no)"
                 //  - do the update
                 modals
-                    .dynamic_notification(Some(t!("rootkeys.gwup.inspecting", locales::LANG)), None)
+                    .dynamic_notification(Some(t!("rootkeys.gwup.inspecting", locales::lang())), None)
                     .expect("modals error");
 
                 let prompt = match keys.check_gateware_signature(GatewareRegion::Staging) {
-                    SignatureResult::SelfSignOk => t!("rootkeys.gwup.viewinfo_ss", locales::LANG),
-                    SignatureResult::ThirdPartyOk => t!("rootkeys.gwup.viewinfo_tp", locales::LANG),
-                    SignatureResult::DevKeyOk => t!("rootkeys.gwup.viewinfo_dk", locales::LANG),
+                    SignatureResult::SelfSignOk => t!("rootkeys.gwup.viewinfo_ss", locales::lang()),
+                    SignatureResult::ThirdPartyOk => t!("rootkeys.gwup.viewinfo_tp", locales::lang()),
+                    SignatureResult::DevKeyOk => t!("rootkeys.gwup.viewinfo_dk", locales::lang()),
                     SignatureResult::MalformedSignature
                     | SignatureResult::InvalidPubKey
                     | SignatureResult::InvalidSignatureType => {
                         modals.dynamic_notification_close().expect("modals error");
                         modals
-                            .show_notification(t!("rootkeys.gwup.sig_problem", locales::LANG), None)
+                            .show_notification(t!("rootkeys.gwup.sig_problem", locales::lang()), None)
                             .expect("modals error");
                         if let Some(dr) = deferred_response.take() {
                             xous::return_scalar(dr, 0).unwrap();
@@ -1148,7 +1150,7 @@ fn main() -> ! {
                     _ => {
                         modals.dynamic_notification_close().expect("modals error");
                         modals
-                            .show_notification(t!("rootkeys.gwup.no_update_found", locales::LANG), None)
+                            .show_notification(t!("rootkeys.gwup.no_update_found", locales::lang()), None)
                             .expect("modals error");
                         if let Some(dr) = deferred_response.take() {
                             xous::return_scalar(dr, 0).unwrap();
@@ -1158,9 +1160,9 @@ fn main() -> ! {
                 };
                 modals.dynamic_notification_close().expect("modals error");
 
-                modals.add_list_item(t!("rootkeys.gwup.short", locales::LANG)).expect("modals error");
-                modals.add_list_item(t!("rootkeys.gwup.details", locales::LANG)).expect("modals error");
-                modals.add_list_item(t!("rootkeys.gwup.none", locales::LANG)).expect("modals error");
+                modals.add_list_item(t!("rootkeys.gwup.short", locales::lang())).expect("modals error");
+                modals.add_list_item(t!("rootkeys.gwup.details", locales::lang())).expect("modals error");
+                modals.add_list_item(t!("rootkeys.gwup.none", locales::lang())).expect("modals error");
 
                 let gw_info = keys.fetch_gw_metadata(GatewareRegion::Staging);
                 let info = if gw_info.git_commit == 0 && gw_info.git_additional == 0 {
@@ -1190,9 +1192,9 @@ fn main() -> ! {
                 log::info!("{}ROOTKEY.GWUP,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                 match modals.get_radiobutton(prompt) {
                     Ok(response) => {
-                        if response == t!("rootkeys.gwup.short", locales::LANG) {
+                        if response == t!("rootkeys.gwup.short", locales::lang()) {
                             modals.show_notification(info.as_str(), None).expect("modals error");
-                        } else if response == t!("rootkeys.gwup.details", locales::LANG) {
+                        } else if response == t!("rootkeys.gwup.details", locales::lang()) {
                             modals.show_notification(info.as_str(), None).expect("modals error");
                             let gw_info = keys.fetch_gw_metadata(GatewareRegion::Staging);
                             // truncate the message to better fit in the rendering box
@@ -1220,17 +1222,17 @@ fn main() -> ! {
                     }
                 }
                 if !skip_confirmation {
-                    modals.add_list_item(t!("rootkeys.gwup.yes", locales::LANG)).expect("modals error");
-                    modals.add_list_item(t!("rootkeys.gwup.no", locales::LANG)).expect("modals error");
-                    match modals.get_radiobutton(t!("rootkeys.gwup.proceed_confirm", locales::LANG)) {
+                    modals.add_list_item(t!("rootkeys.gwup.yes", locales::lang())).expect("modals error");
+                    modals.add_list_item(t!("rootkeys.gwup.no", locales::lang())).expect("modals error");
+                    match modals.get_radiobutton(t!("rootkeys.gwup.proceed_confirm", locales::lang())) {
                         Ok(response) => {
-                            if response == t!("rootkeys.gwup.no", locales::LANG) {
+                            if response == t!("rootkeys.gwup.no", locales::lang()) {
                                 if let Some(dr) = deferred_response.take() {
                                     xous::return_scalar(dr, 0).unwrap();
                                 }
                                 continue;
                             }
-                            if response != t!("rootkeys.gwup.yes", locales::LANG) {
+                            if response != t!("rootkeys.gwup.yes", locales::lang()) {
                                 log::error!("got unexpected response from radio box: {:?}", response);
                                 if let Some(dr) = deferred_response.take() {
                                     xous::return_scalar(dr, 0).unwrap();
@@ -1256,7 +1258,8 @@ fn main() -> ! {
 
                 if keys.is_pcache_update_password_valid() {
                     // indicate that there should be no change to the policy
-                    let payload = gam::RadioButtonPayload::new(t!("rootkeys.policy_suspend", locales::LANG));
+                    let payload =
+                        gam::RadioButtonPayload::new(t!("rootkeys.policy_suspend", locales::lang()));
                     let buf = Buffer::into_buf(payload).expect("couldn't convert message to payload");
                     buf.send(main_cid, Opcode::UxUpdateGwRun.to_u32().unwrap())
                         .map(|_| ())
@@ -1266,14 +1269,14 @@ fn main() -> ! {
                     password_action.set_action_opcode(Opcode::UxUpdateGwPasswordReturn.to_u32().unwrap());
                     rootkeys_modal.modify(
                         Some(ActionType::TextEntry(password_action.clone())),
-                        Some(t!("rootkeys.get_update_password", locales::LANG)),
+                        Some(t!("rootkeys.get_update_password", locales::lang())),
                         false,
                         None,
                         true,
                         None,
                     );
                     #[cfg(feature = "tts")]
-                    tts.tts_blocking(t!("rootkeys.get_update_password", locales::LANG)).unwrap();
+                    tts.tts_blocking(t!("rootkeys.get_update_password", locales::lang())).unwrap();
                     log::info!("{}ROOTKEY.UPDPW,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                     rootkeys_modal.activate();
                 }
@@ -1286,7 +1289,7 @@ fn main() -> ! {
                 plaintext_pw.first().volatile_clear(); // ensure the data is destroyed after sending to the keys enclave
                 buf.volatile_clear();
                 // indicate that there should be no change to the policy
-                let payload = gam::RadioButtonPayload::new(t!("rootkeys.policy_suspend", locales::LANG));
+                let payload = gam::RadioButtonPayload::new(t!("rootkeys.policy_suspend", locales::lang()));
                 let buf = Buffer::into_buf(payload).expect("couldn't convert message to payload");
                 buf.send(main_cid, Opcode::UxUpdateGwRun.to_u32().unwrap())
                     .map(|_| ())
@@ -1301,9 +1304,9 @@ fn main() -> ! {
                 {
                     let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
                     let payload = buffer.to_original::<RadioButtonPayload, _>().unwrap();
-                    if payload.as_str() == t!("rootkeys.policy_keep", locales::LANG) {
+                    if payload.as_str() == t!("rootkeys.policy_keep", locales::lang()) {
                         keys.update_policy(Some(PasswordRetentionPolicy::AlwaysKeep));
-                    } else if payload.as_str() == t!("rootkeys.policy_suspend", locales::LANG) {
+                    } else if payload.as_str() == t!("rootkeys.policy_suspend", locales::lang()) {
                         keys.update_policy(Some(PasswordRetentionPolicy::EraseOnSuspend));
                     } else if payload.as_str() == "no change" {
                         // don't change the policy
@@ -1327,34 +1330,34 @@ fn main() -> ! {
                         )
                         .expect("couldn't initiate dialog box");
                         // just do the reboot now.
-                        // modals.show_notification(t!("rootkeys.gwup.finished", locales::LANG),
+                        // modals.show_notification(t!("rootkeys.gwup.finished", locales::lang()),
                         // None).expect("modals error");
                     }
                     Err(RootkeyResult::AlignmentError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_alignment", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_alignment", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::KeyError) => {
                         // probably a bad password, purge it, so the user can try again
                         keys.purge_password(PasswordType::Update);
                         modals
-                            .show_notification(t!("rootkeys.init.fail_key", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_key", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::IntegrityError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_verify", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_verify", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::FlashError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_burn", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_burn", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::StateError) => {
                         modals
-                            .show_notification(t!("rootkeys.wrong_state", locales::LANG), None)
+                            .show_notification(t!("rootkeys.wrong_state", locales::lang()), None)
                             .expect("modals error");
                     }
                 }
@@ -1365,7 +1368,8 @@ fn main() -> ! {
             Some(Opcode::UxSelfSignXous) => {
                 if keys.is_pcache_update_password_valid() {
                     // set a default policy
-                    let payload = gam::RadioButtonPayload::new(t!("rootkeys.policy_suspend", locales::LANG));
+                    let payload =
+                        gam::RadioButtonPayload::new(t!("rootkeys.policy_suspend", locales::lang()));
                     let buf = Buffer::into_buf(payload).expect("couldn't convert message to payload");
                     buf.send(main_cid, Opcode::UxSignXousRun.to_u32().unwrap())
                         .map(|_| ())
@@ -1375,14 +1379,14 @@ fn main() -> ! {
                     password_action.set_action_opcode(Opcode::UxSignXousPasswordReturn.to_u32().unwrap());
                     rootkeys_modal.modify(
                         Some(ActionType::TextEntry(password_action.clone())),
-                        Some(t!("rootkeys.get_signing_password", locales::LANG)),
+                        Some(t!("rootkeys.get_signing_password", locales::lang())),
                         false,
                         None,
                         true,
                         None,
                     );
                     #[cfg(feature = "tts")]
-                    tts.tts_blocking(t!("rootkeys.get_signing_password", locales::LANG)).unwrap();
+                    tts.tts_blocking(t!("rootkeys.get_signing_password", locales::lang())).unwrap();
                     log::info!("{}ROOTKEY.UPDPW,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                     rootkeys_modal.activate();
                 }
@@ -1395,7 +1399,7 @@ fn main() -> ! {
                 plaintext_pw.first().volatile_clear(); // ensure the data is destroyed after sending to the keys enclave
                 buf.volatile_clear();
 
-                let payload = gam::RadioButtonPayload::new(t!("rootkeys.policy_suspend", locales::LANG));
+                let payload = gam::RadioButtonPayload::new(t!("rootkeys.policy_suspend", locales::lang()));
                 let buf = Buffer::into_buf(payload).expect("couldn't convert message to payload");
                 buf.send(main_cid, Opcode::UxSignXousRun.to_u32().unwrap())
                     .map(|_| ())
@@ -1407,9 +1411,9 @@ fn main() -> ! {
                     // legacy code to set policy, if it were to be inserted in the flow
                     let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
                     let payload = buffer.to_original::<RadioButtonPayload, _>().unwrap();
-                    if payload.as_str() == t!("rootkeys.policy_keep", locales::LANG) {
+                    if payload.as_str() == t!("rootkeys.policy_keep", locales::lang()) {
                         keys.update_policy(Some(PasswordRetentionPolicy::AlwaysKeep));
-                    } else if payload.as_str() == t!("rootkeys.policy_suspend", locales::LANG) {
+                    } else if payload.as_str() == t!("rootkeys.policy_suspend", locales::lang()) {
                         keys.update_policy(Some(PasswordRetentionPolicy::EraseOnSuspend));
                     } else if payload.as_str() == "no change" {
                         // don't change the policy
@@ -1427,34 +1431,34 @@ fn main() -> ! {
                 match result {
                     Ok(_) => {
                         modals
-                            .show_notification(t!("rootkeys.signxous.finished", locales::LANG), None)
+                            .show_notification(t!("rootkeys.signxous.finished", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::AlignmentError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_alignment", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_alignment", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::KeyError) => {
                         // probably a bad password, purge it, so the user can try again
                         keys.purge_password(PasswordType::Update);
                         modals
-                            .show_notification(t!("rootkeys.init.fail_key", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_key", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::IntegrityError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_verify", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_verify", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::FlashError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_burn", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_burn", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::StateError) => {
                         modals
-                            .show_notification(t!("rootkeys.wrong_state", locales::LANG), None)
+                            .show_notification(t!("rootkeys.wrong_state", locales::lang()), None)
                             .expect("modals error");
                     }
                 }
@@ -1478,14 +1482,14 @@ fn main() -> ! {
                     password_action.set_action_opcode(Opcode::UxAesEnsureReturn.to_u32().unwrap());
                     rootkeys_modal.modify(
                         Some(ActionType::TextEntry(password_action.clone())),
-                        Some(t!("rootkeys.get_login_password", locales::LANG)),
+                        Some(t!("rootkeys.get_login_password", locales::lang())),
                         false,
                         None,
                         true,
                         None,
                     );
                     #[cfg(feature = "tts")]
-                    tts.tts_blocking(t!("rootkeys.get_login_password", locales::LANG)).unwrap();
+                    tts.tts_blocking(t!("rootkeys.get_login_password", locales::lang())).unwrap();
                     log::info!("{}ROOTKEY.BOOTPW,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                     rootkeys_modal.activate();
                     // note that the scalar is *not* yet returned, it will be returned by the opcode called by
@@ -1500,7 +1504,7 @@ fn main() -> ! {
 
                     // otherwise, an invalid password request
                     modals
-                        .show_notification(t!("rootkeys.bad_password_request", locales::LANG), None)
+                        .show_notification(t!("rootkeys.bad_password_request", locales::lang()), None)
                         .expect("modals error");
 
                     xous::return_scalar(msg.sender, 0).unwrap();
@@ -1519,20 +1523,20 @@ fn main() -> ! {
                 let mut confirm_radiobox =
                     gam::modal::RadioButtons::new(main_cid, Opcode::UxAesEnsureReturn.to_u32().unwrap());
                 confirm_radiobox.is_password = true;
-                confirm_radiobox.add_item(ItemName::new(t!("rootkeys.policy_suspend", locales::LANG)));
-                // confirm_radiobox.add_item(ItemName::new(t!("rootkeys.policy_clear", locales::LANG))); //
+                confirm_radiobox.add_item(ItemName::new(t!("rootkeys.policy_suspend", locales::lang())));
+                // confirm_radiobox.add_item(ItemName::new(t!("rootkeys.policy_clear", locales::lang()))); //
                 // this policy makes no sense in the use case of the key
-                confirm_radiobox.add_item(ItemName::new(t!("rootkeys.policy_keep", locales::LANG)));
+                confirm_radiobox.add_item(ItemName::new(t!("rootkeys.policy_keep", locales::lang())));
                 rootkeys_modal.modify(
                     Some(ActionType::RadioButtons(confirm_radiobox)),
-                    Some(t!("rootkeys.policy_request", locales::LANG)),
+                    Some(t!("rootkeys.policy_request", locales::lang())),
                     false,
                     None,
                     true,
                     None,
                 );
                 #[cfg(feature = "tts")]
-                tts.tts_blocking(t!("rootkeys.policy_request", locales::LANG)).unwrap();
+                tts.tts_blocking(t!("rootkeys.policy_request", locales::lang())).unwrap();
                 rootkeys_modal.activate();
             }
             Some(Opcode::UxAesEnsureReturn) => {
@@ -1544,9 +1548,9 @@ fn main() -> ! {
                         let buffer =
                             unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
                         let payload = buffer.to_original::<RadioButtonPayload, _>().unwrap();
-                        if payload.as_str() == t!("rootkeys.policy_keep", locales::LANG) {
+                        if payload.as_str() == t!("rootkeys.policy_keep", locales::lang()) {
                             keys.update_policy(Some(PasswordRetentionPolicy::AlwaysKeep));
-                        } else if payload.as_str() == t!("rootkeys.policy_suspend", locales::LANG) {
+                        } else if payload.as_str() == t!("rootkeys.policy_suspend", locales::lang()) {
                             keys.update_policy(Some(PasswordRetentionPolicy::EraseOnSuspend));
                         } else if payload.as_str() == "no change" {
                             // don't change the policy
@@ -1607,20 +1611,20 @@ fn main() -> ! {
 
             Some(Opcode::BbramProvision) => {
                 modals
-                    .show_notification(t!("rootkeys.bbram.confirm", locales::LANG), None)
+                    .show_notification(t!("rootkeys.bbram.confirm", locales::lang()), None)
                     .expect("modals error");
                 let console_input =
                     gam::modal::ConsoleInput::new(main_cid, Opcode::UxBbramCheckReturn.to_u32().unwrap());
                 rootkeys_modal.modify(
                     Some(ActionType::ConsoleInput(console_input)),
-                    Some(t!("rootkeys.console_input", locales::LANG)),
+                    Some(t!("rootkeys.console_input", locales::lang())),
                     false,
                     None,
                     true,
                     None,
                 );
                 #[cfg(feature = "tts")]
-                tts.tts_blocking(t!("rootkeys.console_input", locales::LANG)).unwrap();
+                tts.tts_blocking(t!("rootkeys.console_input", locales::lang())).unwrap();
                 rootkeys_modal.activate();
                 log::info!("{}check_conn", CONSOLE_SENTINEL);
             }
@@ -1642,19 +1646,19 @@ fn main() -> ! {
                         password_action.set_action_opcode(Opcode::UxBbramPasswordReturn.to_u32().unwrap());
                         rootkeys_modal.modify(
                             Some(ActionType::TextEntry(password_action.clone())),
-                            Some(t!("rootkeys.get_signing_password", locales::LANG)),
+                            Some(t!("rootkeys.get_signing_password", locales::lang())),
                             false,
                             None,
                             true,
                             None,
                         );
                         #[cfg(feature = "tts")]
-                        tts.tts_blocking(t!("rootkeys.get_signing_password", locales::LANG)).unwrap();
+                        tts.tts_blocking(t!("rootkeys.get_signing_password", locales::lang())).unwrap();
                         rootkeys_modal.activate();
                     }
                 } else {
                     modals
-                        .show_notification(t!("rootkeys.bbram.no_helper", locales::LANG), None)
+                        .show_notification(t!("rootkeys.bbram.no_helper", locales::lang()), None)
                         .expect("modals error");
                     continue;
                 }
@@ -1687,34 +1691,34 @@ fn main() -> ! {
                 match result {
                     Ok(_) => {
                         modals
-                            .show_notification(t!("rootkeys.bbram.finished", locales::LANG), None)
+                            .show_notification(t!("rootkeys.bbram.finished", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::AlignmentError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_alignment", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_alignment", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::KeyError) => {
                         // probably a bad password, purge it, so the user can try again
                         keys.purge_password(PasswordType::Update);
                         modals
-                            .show_notification(t!("rootkeys.init.fail_key", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_key", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::IntegrityError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_verify", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_verify", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::FlashError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_burn", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_burn", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::StateError) => {
                         modals
-                            .show_notification(t!("rootkeys.wrong_state", locales::LANG), None)
+                            .show_notification(t!("rootkeys.wrong_state", locales::lang()), None)
                             .expect("modals error");
                     }
                 }
@@ -1792,14 +1796,14 @@ fn main() -> ! {
                 password_action.set_action_opcode(Opcode::UxCreateBackupPwReturn.to_u32().unwrap());
                 rootkeys_modal.modify(
                     Some(ActionType::TextEntry(password_action.clone())),
-                    Some(t!("rootkeys.get_update_password_backup", locales::LANG)),
+                    Some(t!("rootkeys.get_update_password_backup", locales::lang())),
                     false,
                     None,
                     true,
                     None,
                 );
                 #[cfg(feature = "tts")]
-                tts.tts_blocking(t!("rootkeys.get_update_password_backup", locales::LANG)).unwrap();
+                tts.tts_blocking(t!("rootkeys.get_update_password_backup", locales::lang())).unwrap();
                 log::info!("{}ROOTKEY.UPDPW,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                 rootkeys_modal.activate();
             }
@@ -1817,18 +1821,18 @@ fn main() -> ! {
                     // this gets shown in an "insecure" modal but -- we're expatriating this data anyways, so
                     // meh?
                     modals
-                        .show_bip39(Some(t!("rootkeys.backup_key", locales::LANG)), &fpga_key.0.to_vec())
+                        .show_bip39(Some(t!("rootkeys.backup_key", locales::lang())), &fpga_key.0.to_vec())
                         .ok();
                     // let the user confirm the key, or skip it. YOLO!
                     loop {
-                        modals.add_list_item(t!("rootkeys.gwup.yes", locales::LANG)).expect("modals error");
-                        modals.add_list_item(t!("rootkeys.gwup.no", locales::LANG)).expect("modals error");
+                        modals.add_list_item(t!("rootkeys.gwup.yes", locales::lang())).expect("modals error");
+                        modals.add_list_item(t!("rootkeys.gwup.no", locales::lang())).expect("modals error");
                         log::info!("{}ROOTKEY.CONFIRM,{}", xous::BOOKEND_START, xous::BOOKEND_END);
-                        match modals.get_radiobutton(t!("rootkeys.backup_verify", locales::LANG)) {
+                        match modals.get_radiobutton(t!("rootkeys.backup_verify", locales::lang())) {
                             Ok(response) => {
-                                if response == t!("rootkeys.gwup.yes", locales::LANG) {
+                                if response == t!("rootkeys.gwup.yes", locales::lang()) {
                                     match modals
-                                        .input_bip39(Some(t!("rootkeys.backup_key_enter", locales::LANG)))
+                                        .input_bip39(Some(t!("rootkeys.backup_key_enter", locales::lang())))
                                     {
                                         Ok(verify) => {
                                             log::debug!("got bip39 verification: {:x?}", verify);
@@ -1836,7 +1840,7 @@ fn main() -> ! {
                                                 log::debug!("verify succeeded");
                                                 modals
                                                     .show_notification(
-                                                        t!("rootkeys.backup_key_match", locales::LANG),
+                                                        t!("rootkeys.backup_key_match", locales::lang()),
                                                         None,
                                                     )
                                                     .ok();
@@ -1847,7 +1851,7 @@ fn main() -> ! {
                                                     .show_bip39(
                                                         Some(t!(
                                                             "rootkeys.backup_key_mismatch",
-                                                            locales::LANG
+                                                            locales::lang()
                                                         )),
                                                         &fpga_key.0.to_vec(),
                                                     )
@@ -1858,7 +1862,7 @@ fn main() -> ! {
                                             log::debug!("bip39 verification aborted");
                                             modals
                                                 .show_bip39(
-                                                    Some(t!("rootkeys.backup_key_mismatch", locales::LANG)),
+                                                    Some(t!("rootkeys.backup_key_mismatch", locales::lang())),
                                                     &fpga_key.0.to_vec(),
                                                 )
                                                 .ok();
@@ -1879,7 +1883,7 @@ fn main() -> ! {
                             // this switchover takes a couple seconds, give some user feedback
                             modals
                                 .dynamic_notification(
-                                    Some(t!("rootkeys.backup_prepwait", locales::LANG)),
+                                    Some(t!("rootkeys.backup_prepwait", locales::lang())),
                                     None,
                                 )
                                 .ok();
@@ -1892,22 +1896,24 @@ fn main() -> ! {
                             log::info!("{}BACKUP.STAGED,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                             modals
                                 .show_notification(
-                                    t!("rootkeys.backup_staged", locales::LANG),
+                                    t!("rootkeys.backup_staged", locales::lang()),
                                     Some("https://github.com/betrusted-io/betrusted-wiki/wiki/Backups"),
                                 )
                                 .ok();
                             // this informs users who dismiss the QR code that they must reboot to resume
                             // normal operation
-                            modals.show_notification(t!("rootkeys.backup_waiting", locales::LANG), None).ok();
+                            modals
+                                .show_notification(t!("rootkeys.backup_waiting", locales::lang()), None)
+                                .ok();
                         }
                         Err(_) => {
                             modals
-                                .show_notification(t!("rootkeys.backup_staging_error", locales::LANG), None)
+                                .show_notification(t!("rootkeys.backup_staging_error", locales::lang()), None)
                                 .ok();
                         }
                     }
                 } else {
-                    modals.show_notification(t!("rootkeys.backup_badpass", locales::LANG), None).ok();
+                    modals.show_notification(t!("rootkeys.backup_badpass", locales::lang()), None).ok();
                     // Prompt the user again for the password. It will do this until a correct password is
                     // entered. maybe it would be thoughtful to add a yes/no box for
                     // rebooting in case someone decides they don't want to run the backup
@@ -1916,14 +1922,14 @@ fn main() -> ! {
                     password_action.set_action_opcode(Opcode::UxCreateBackupPwReturn.to_u32().unwrap());
                     rootkeys_modal.modify(
                         Some(ActionType::TextEntry(password_action.clone())),
-                        Some(t!("rootkeys.get_update_password_backup", locales::LANG)),
+                        Some(t!("rootkeys.get_update_password_backup", locales::lang())),
                         false,
                         None,
                         true,
                         None,
                     );
                     #[cfg(feature = "tts")]
-                    tts.tts_blocking(t!("rootkeys.get_update_password_backup", locales::LANG)).unwrap();
+                    tts.tts_blocking(t!("rootkeys.get_update_password_backup", locales::lang())).unwrap();
                     log::info!("{}ROOTKEY.UPDPW,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                     rootkeys_modal.activate();
                 }
@@ -1932,14 +1938,14 @@ fn main() -> ! {
                 // check to see if the device was already initialized. If so, warn the user.
                 deferred_response = Some(msg.sender);
                 if keys.is_initialized() {
-                    modals.add_list_item(t!("rootkeys.confirm.yes", locales::LANG)).expect("modals error");
-                    modals.add_list_item(t!("rootkeys.confirm.no", locales::LANG)).expect("modals error");
+                    modals.add_list_item(t!("rootkeys.confirm.yes", locales::lang())).expect("modals error");
+                    modals.add_list_item(t!("rootkeys.confirm.no", locales::lang())).expect("modals error");
                     log::info!("{}ROOTKEY.CONFIRM,{}", xous::BOOKEND_START, xous::BOOKEND_END);
-                    match modals.get_radiobutton(t!("rootkeys.restore_already_init", locales::LANG)) {
+                    match modals.get_radiobutton(t!("rootkeys.restore_already_init", locales::lang())) {
                         Ok(response) => {
-                            if response == t!("rootkeys.confirm.no", locales::LANG) {
+                            if response == t!("rootkeys.confirm.no", locales::lang()) {
                                 modals
-                                    .show_notification(t!("rootkeys.restore_abort", locales::LANG), None)
+                                    .show_notification(t!("rootkeys.restore_abort", locales::lang()), None)
                                     .ok();
                                 if let Some(sender) = deferred_response {
                                     xous::return_scalar(sender, 1).ok();
@@ -1955,7 +1961,7 @@ fn main() -> ! {
                 if let Ok((mut header, ct)) = keys.read_backup() {
                     if header.op != BackupOp::Restore {
                         log::warn!("Header op was not Restore. Found {:?} instead. Aborting!", header.op);
-                        modals.show_notification(t!("rootkeys.restore_corrupt", locales::LANG), None).ok();
+                        modals.show_notification(t!("rootkeys.restore_corrupt", locales::lang()), None).ok();
                         if let Some(sender) = deferred_response {
                             xous::return_scalar(sender, 1).ok();
                         }
@@ -1965,20 +1971,20 @@ fn main() -> ! {
                     // trial restore from 0 key to see if we can skip password entry.
                     if backups::restore_backup(&restore_key, &ct).is_none() {
                         // the '0' key didn't work. get the key from the user
-                        match modals.input_bip39(Some(t!("rootkeys.backup_key_enter", locales::LANG))) {
+                        match modals.input_bip39(Some(t!("rootkeys.backup_key_enter", locales::lang()))) {
                             Ok(key) => {
                                 restore_key.0.copy_from_slice(&key);
                             }
                             _ => {
                                 // key entry failed, aborting.
                                 modals
-                                    .show_notification(t!("rootkeys.restore_badpass", locales::LANG), None)
+                                    .show_notification(t!("rootkeys.restore_badpass", locales::lang()), None)
                                     .ok();
                             }
                         }
                     } else {
                         // notify of zero key
-                        modals.show_notification(t!("rootkeys.restore_zero_key", locales::LANG), None).ok();
+                        modals.show_notification(t!("rootkeys.restore_zero_key", locales::lang()), None).ok();
                         showed_zero_key_notice = true;
                     }
                     // actual restore
@@ -1991,7 +1997,7 @@ fn main() -> ! {
                             log::info!("plaintext header: {:?}", pt.header);
                             log::info!("encrypted header: {:?}", header);
                             modals
-                                .show_notification(t!("rootkeys.restore_corrupt", locales::LANG), None)
+                                .show_notification(t!("rootkeys.restore_corrupt", locales::lang()), None)
                                 .ok();
                             if let Some(sender) = deferred_response {
                                 xous::return_scalar(sender, 1).ok();
@@ -2030,7 +2036,7 @@ fn main() -> ! {
                             if !showed_zero_key_notice {
                                 modals
                                     .show_notification(
-                                        t!("rootkeys.restore_needs_keyburn", locales::LANG),
+                                        t!("rootkeys.restore_needs_keyburn", locales::lang()),
                                         None,
                                     )
                                     .ok();
@@ -2044,19 +2050,20 @@ fn main() -> ! {
                         password_action.set_action_opcode(Opcode::UxDoRestorePwReturn.to_u32().unwrap());
                         rootkeys_modal.modify(
                             Some(ActionType::TextEntry(password_action.clone())),
-                            Some(t!("rootkeys.get_update_password_restore", locales::LANG)),
+                            Some(t!("rootkeys.get_update_password_restore", locales::lang())),
                             false,
                             None,
                             true,
                             None,
                         );
                         #[cfg(feature = "tts")]
-                        tts.tts_blocking(t!("rootkeys.get_update_password_restore", locales::LANG)).unwrap();
+                        tts.tts_blocking(t!("rootkeys.get_update_password_restore", locales::lang()))
+                            .unwrap();
                         log::info!("{}ROOTKEY.UPDPW,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                         rootkeys_modal.activate();
                     } else {
                         // decryption error
-                        modals.show_notification(t!("rootkeys.restore_corrupt", locales::LANG), None).ok();
+                        modals.show_notification(t!("rootkeys.restore_corrupt", locales::lang()), None).ok();
                     }
                 } else {
                     log::error!("internal error: couldn't read out the restore region");
@@ -2112,7 +2119,7 @@ fn main() -> ! {
                         keys.finish_key_init();
 
                         modals
-                            .show_notification(t!("rootkeys.init.fail_alignment", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_alignment", locales::lang()), None)
                             .expect("modals error");
                         if let Some(sender) = deferred_response {
                             xous::return_scalar(sender, 1).ok();
@@ -2120,13 +2127,13 @@ fn main() -> ! {
                     }
                     Err(RootkeyResult::KeyError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_key", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_key", locales::lang()), None)
                             .expect("modals error");
-                        modals.add_list_item(t!("rootkeys.gwup.yes", locales::LANG)).expect("modals error");
-                        modals.add_list_item(t!("rootkeys.gwup.no", locales::LANG)).expect("modals error");
-                        match modals.get_radiobutton(t!("rootkeys.try_again", locales::LANG)) {
+                        modals.add_list_item(t!("rootkeys.gwup.yes", locales::lang())).expect("modals error");
+                        modals.add_list_item(t!("rootkeys.gwup.no", locales::lang())).expect("modals error");
+                        match modals.get_radiobutton(t!("rootkeys.try_again", locales::lang())) {
                             Ok(response) => {
-                                if response == t!("rootkeys.gwup.no", locales::LANG) {
+                                if response == t!("rootkeys.gwup.no", locales::lang()) {
                                     // clear all the state, re-enable suspend/resume
                                     keys.finish_key_init();
 
@@ -2134,14 +2141,14 @@ fn main() -> ! {
                                         xous::return_scalar(dr, 1).unwrap();
                                     }
                                     continue;
-                                } else if response == t!("rootkeys.gwup.yes", locales::LANG) {
+                                } else if response == t!("rootkeys.gwup.yes", locales::lang()) {
                                     // get the update password, so we can encrypt the FPGA key to it.
                                     keys.set_ux_password_type(Some(PasswordType::Update));
                                     password_action
                                         .set_action_opcode(Opcode::UxDoRestorePwReturn.to_u32().unwrap());
                                     rootkeys_modal.modify(
                                         Some(ActionType::TextEntry(password_action.clone())),
-                                        Some(t!("rootkeys.get_update_password_restore", locales::LANG)),
+                                        Some(t!("rootkeys.get_update_password_restore", locales::lang())),
                                         false,
                                         None,
                                         true,
@@ -2150,7 +2157,7 @@ fn main() -> ! {
                                     #[cfg(feature = "tts")]
                                     tts.tts_blocking(t!(
                                         "rootkeys.get_update_password_restore",
-                                        locales::LANG
+                                        locales::lang()
                                     ))
                                     .unwrap();
                                     log::info!("{}ROOTKEY.UPDPW,{}", xous::BOOKEND_START, xous::BOOKEND_END);
@@ -2170,7 +2177,7 @@ fn main() -> ! {
                         keys.finish_key_init();
 
                         modals
-                            .show_notification(t!("rootkeys.init.fail_verify", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_verify", locales::lang()), None)
                             .expect("modals error");
                         if let Some(sender) = deferred_response {
                             xous::return_scalar(sender, 1).ok();
@@ -2181,7 +2188,7 @@ fn main() -> ! {
                         keys.finish_key_init();
 
                         modals
-                            .show_notification(t!("rootkeys.init.fail_burn", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_burn", locales::lang()), None)
                             .expect("modals error");
                         if let Some(sender) = deferred_response {
                             xous::return_scalar(sender, 1).ok();
@@ -2192,7 +2199,7 @@ fn main() -> ! {
                         keys.finish_key_init();
 
                         modals
-                            .show_notification(t!("rootkeys.wrong_state", locales::LANG), None)
+                            .show_notification(t!("rootkeys.wrong_state", locales::lang()), None)
                             .expect("modals error");
                         if let Some(sender) = deferred_response {
                             xous::return_scalar(sender, 1).ok();
@@ -2243,7 +2250,7 @@ fn main() -> ! {
                 // confirm that we can read out our JTAG ID.
                 if !keys.is_jtag_working() {
                     modals
-                        .show_notification(t!("rootkeys.efuse_jtag_fail", locales::LANG), None)
+                        .show_notification(t!("rootkeys.efuse_jtag_fail", locales::lang()), None)
                         .expect("modals error");
                     continue;
                 }
@@ -2259,14 +2266,14 @@ fn main() -> ! {
                     password_action.set_action_opcode(Opcode::EfusePasswordReturn.to_u32().unwrap());
                     rootkeys_modal.modify(
                         Some(ActionType::TextEntry(password_action.clone())),
-                        Some(t!("rootkeys.get_signing_password", locales::LANG)),
+                        Some(t!("rootkeys.get_signing_password", locales::lang())),
                         false,
                         None,
                         true,
                         None,
                     );
                     #[cfg(feature = "tts")]
-                    tts.tts_blocking(t!("rootkeys.get_signing_password", locales::LANG)).unwrap();
+                    tts.tts_blocking(t!("rootkeys.get_signing_password", locales::lang())).unwrap();
                     rootkeys_modal.activate();
                 }
             }
@@ -2301,7 +2308,7 @@ fn main() -> ! {
                     Ok(_) => {
                         log::info!("{}EFUSE.OK,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                         modals
-                            .show_notification(t!("rootkeys.efuse_finished", locales::LANG), None)
+                            .show_notification(t!("rootkeys.efuse_finished", locales::lang()), None)
                             .expect("modals error");
                         send_message(
                             main_cid,
@@ -2311,29 +2318,29 @@ fn main() -> ! {
                     }
                     Err(RootkeyResult::AlignmentError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_alignment", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_alignment", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::KeyError) => {
                         // probably a bad password, purge it, so the user can try again
                         keys.purge_password(PasswordType::Update);
                         modals
-                            .show_notification(t!("rootkeys.init.fail_key", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_key", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::IntegrityError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_verify", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_verify", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::FlashError) => {
                         modals
-                            .show_notification(t!("rootkeys.init.fail_burn", locales::LANG), None)
+                            .show_notification(t!("rootkeys.init.fail_burn", locales::lang()), None)
                             .expect("modals error");
                     }
                     Err(RootkeyResult::StateError) => {
                         modals
-                            .show_notification(t!("rootkeys.wrong_state", locales::LANG), None)
+                            .show_notification(t!("rootkeys.wrong_state", locales::lang()), None)
                             .expect("modals error");
                     }
                 }
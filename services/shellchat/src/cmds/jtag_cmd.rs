@@ -71,13 +71,13 @@ impl<'a> ShellCmdApi<'a> for JtagCmd {
                     match self.jtag.seal_device() {
                         Ok(result) => {
                             if !result {
-                                log::info!("{}", t!("rootkeys.efuse_seal_fail", locales::LANG));
+                                log::info!("{}", t!("rootkeys.efuse_seal_fail", locales::lang()));
                             } else {
                                 log::info!("eFuse sealing success!");
                             }
                         }
                         Err(e) => {
-                            log::info!("{}", &format!("{}\n{:?}", t!("rootkeys.efuse_internal_error", locales::LANG), e));
+                            log::info!("{}", &format!("{}\n{:?}", t!("rootkeys.efuse_internal_error", locales::lang()), e));
                         }
                     }
                     log::info!("{}EFUSE.SEAL_OK,{}", xous::BOOKEND_START, xous::BOOKEND_END);
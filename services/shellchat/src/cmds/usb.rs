@@ -20,9 +20,9 @@ impl<'a> ShellCmdApi<'a> for Usb {
     fn process(&mut self, args: String, _env: &mut CommonEnv) -> Result<Option<String>, xous::Error> {
         let mut ret = String::new();
         #[cfg(not(feature = "mass-storage"))]
-        let helpstring = "usb [hid] [fido] [debug] [send <string>] [status] [leds] [lock] [unlock] [kbdtest]";
+        let helpstring = "usb [hid] [fido] [net] [composite] [debug] [send <string>] [status] [leds] [lock] [unlock] [kbdtest]";
         #[cfg(feature = "mass-storage")]
-        let helpstring = "usb [hid] [fido] [ms] [debug] [send <string>] [status] [leds] [lock] [unlock] [kbdtest] [console] [noconsole]";
+        let helpstring = "usb [hid] [fido] [ms] [net] [composite] [debug] [send <string>] [status] [leds] [lock] [unlock] [kbdtest] [console] [noconsole]";
 
         let mut tokens = args.split(' ');
 
@@ -41,16 +41,33 @@ impl<'a> ShellCmdApi<'a> for Usb {
                     self.usb_dev.ensure_core(usb_device_xous::UsbDeviceType::Fido).unwrap();
                     write!(ret, "USB connected to FIDO-only core").unwrap();
                 }
+                "net" => {
+                    self.usb_dev.ensure_core(usb_device_xous::UsbDeviceType::Net).unwrap();
+                    write!(ret, "USB connected to network adapter core, reachable at 169.254.42.1").unwrap();
+                }
+                "composite" => {
+                    self.usb_dev.ensure_core(usb_device_xous::UsbDeviceType::Composite).unwrap();
+                    match self.usb_dev.composite_functions() {
+                        Ok(functions) if !functions.is_empty() => {
+                            write!(ret, "USB connected to composite core with {:?}", functions).unwrap()
+                        }
+                        _ => write!(ret, "No app registered any functions for the composite core").unwrap(),
+                    }
+                }
                 "debug" => {
                     self.usb_dev.switch_to_core(usb_device_xous::UsbDeviceType::Debug).unwrap();
                     self.usb_dev.debug_usb(Some(false)).unwrap();
                     write!(ret, "USB connected to Debug core, secrets readable!").unwrap();
                 }
                 "serial" => {
+                    // claims the serial function, so a composite device that's up offers it alongside the
+                    // rest
+                    self.usb_dev.register_functions(usb_device_xous::UsbFunctions::SERIAL).ok();
                     self.usb_dev.ensure_core(usb_device_xous::UsbDeviceType::Serial).unwrap();
                     write!(ret, "USB connected to serial core").unwrap();
                 }
                 "console" => {
+                    self.usb_dev.register_functions(usb_device_xous::UsbFunctions::SERIAL).ok();
                     self.usb_dev.ensure_core(usb_device_xous::UsbDeviceType::Serial).unwrap();
                     // this will enable input injection mode
                     self.usb_dev.serial_console_input_injection();
@@ -59,9 +76,11 @@ impl<'a> ShellCmdApi<'a> for Usb {
                 "noconsole" => {
                     // this will disable any hooks (including the console input hook)
                     self.usb_dev.serial_clear_input_hooks();
+                    self.usb_dev.unregister_functions().ok();
                     write!(ret, "USB console disconnected.").ok();
                 }
                 "trng" => {
+                    self.usb_dev.register_functions(usb_device_xous::UsbFunctions::SERIAL).ok();
                     self.usb_dev.ensure_core(usb_device_xous::UsbDeviceType::Serial).unwrap();
                     let mode = if let Some(sub_cmd) = tokens.next() {
                         match sub_cmd {
@@ -80,6 +99,7 @@ impl<'a> ShellCmdApi<'a> for Usb {
                 }
                 "notrng" => {
                     self.usb_dev.serial_clear_input_hooks();
+                    self.usb_dev.unregister_functions().ok();
                     write!(ret, "USB TRNG serial sending should be stopped.").ok();
                 }
                 "send" => match self.usb_dev.get_current_core() {
@@ -125,6 +145,24 @@ impl<'a> ShellCmdApi<'a> for Usb {
                     },
                     #[cfg(feature = "mass-storage")]
                     Ok(UsbDeviceType::MassStorage) => write!(ret, "USB mass storage connected").unwrap(),
+                    Ok(UsbDeviceType::Net) => match self.usb_dev.status() {
+                        UsbDeviceState::Configured => {
+                            write!(ret, "Network adapter connected to host").unwrap()
+                        }
+                        UsbDeviceState::Suspend => write!(ret, "Network adapter in suspend").unwrap(),
+                        _ => write!(ret, "Network adapter not connected to USB host").unwrap(),
+                    },
+                    Ok(UsbDeviceType::Composite) => {
+                        let functions = self.usb_dev.composite_functions().unwrap_or_default();
+                        match self.usb_dev.status() {
+                            UsbDeviceState::Configured => {
+                                write!(ret, "Composite core with {:?} connected to host", functions).unwrap()
+                            }
+                            UsbDeviceState::Suspend => write!(ret, "Composite core in suspend").unwrap(),
+                            _ => write!(ret, "Composite core with {:?} not connected to USB host", functions)
+                                .unwrap(),
+                        }
+                    }
                     _ => write!(ret, "Invalid response checking status").unwrap(),
                 },
                 "leds" => match self.usb_dev.get_current_core() {
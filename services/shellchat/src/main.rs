@@ -236,7 +236,7 @@ impl Repl {
             if let Some(res) = self.env.dispatch(Some(local), None).expect("command dispatch failed") {
                 #[cfg(feature = "tts")]
                 {
-                    let mut output = t!("shellchat.output-tts", locales::LANG).to_string();
+                    let mut output = t!("shellchat.output-tts", locales::lang()).to_string();
                     output.push_str(res.as_str());
                     self.tts.tts_simple(&output).unwrap();
                 }
@@ -250,7 +250,7 @@ impl Repl {
             if let Some(res) = self.env.dispatch(None, Some(msg)).expect("callback failed") {
                 #[cfg(feature = "tts")]
                 {
-                    let mut output = t!("shellchat.output-tts", locales::LANG).to_string();
+                    let mut output = t!("shellchat.output-tts", locales::lang()).to_string();
                     output.push_str(res.as_str());
                     self.tts.tts_simple(&output).unwrap();
                 }
@@ -305,7 +305,7 @@ impl Repl {
             );
             init_tv.style = graphics_server::GlyphStyle::Bold;
             init_tv.draw_border = false;
-            write!(init_tv.text, "{}", t!("shellchat.bootwait", locales::LANG)).ok();
+            write!(init_tv.text, "{}", t!("shellchat.bootwait", locales::lang())).ok();
             self.gam.post_textview(&mut init_tv).expect("couldn't render wait text");
             self.gam.redraw().expect("couldn't redraw screen");
         }
@@ -425,6 +425,13 @@ fn wrapped_main() -> ! {
                 xous::Message::new_scalar(ShellOpcode::Redraw.to_usize().unwrap(), 0, 0, 0, 0),
             )
             .ok();
+            // the trusted certificates live in the PDDB, so they can only be checked once it's mounted
+            #[cfg(feature = "tls")]
+            {
+                if pddb.is_mounted_nonblocking() {
+                    tls::Tls::new().warn_expiring();
+                }
+            }
         }
     });
 
@@ -445,7 +452,7 @@ fn wrapped_main() -> ! {
                 log::trace!("shell got input line: {}", s.as_str());
                 #[cfg(feature = "tts")]
                 {
-                    let mut input = t!("shellchat.input-tts", locales::LANG).to_string();
+                    let mut input = t!("shellchat.input-tts", locales::lang()).to_string();
                     input.push_str(s.as_str());
                     tts.tts_simple(&input).unwrap();
                 }
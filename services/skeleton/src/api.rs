@@ -1,10 +1,17 @@
 pub(crate) const SERVER_NAME_CODEC: &str     = "_Any descriptive and unique name under 64 chars_";
 
-#[allow(dead_code)]
-#[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
-pub(crate) enum Opcode {
+/// The API of the server. `#[flatipc::service]` generates the opcodes (`Opcode`), a typed
+/// client (`CodecApiClient`) and the `dispatch()` used by the server loop from this one definition,
+/// so adding a call is a matter of adding a method here and implementing it in the server.
+///
+/// Methods are marked with how their arguments are sent: `#[lend]` / `#[lend_mut]` for `Ipc` types,
+/// `#[scalar]` for blocking calls with up to four `usize` arguments, and `#[send]` for non-blocking ones.
+#[flatipc::service(opcode = Opcode)]
+pub trait CodecApi {
     /// Suspend/resume callback
-    SuspendResume,
+    #[send]
+    fn suspend_resume(&mut self, token: usize);
     /// Exits the server
-    Quit,
+    #[send]
+    fn quit(&mut self);
 }
@@ -2,11 +2,9 @@
 
 pub mod api;
 pub use api::*;
-use xous::{CID, send_message};
-use num_traits::ToPrimitive;
 
 pub struct Codec {
-    conn: CID,
+    api: CodecApiClient,
 }
 impl Codec {
     pub fn new() -> Self {
@@ -14,7 +12,7 @@ impl Codec {
         REFCOUNT.fetch_add(1, Ordering::Relaxed);
         let conn = xns.request_connection_blocking(api::SERVER_NAME_CODEC).expect("Can't connect to Codec server");
         Codec {
-            conn
+            api: CodecApiClient::new(conn)
         }
     }
 }
@@ -27,7 +25,7 @@ impl Drop for Codec {
         // a single process do not end up de-allocating the CID on other threads before they go out of scope.
         // Note to future me: you want this. Don't get rid of it because you think, "nah, nobody will ever make more than one copy of this object".
         if REFCOUNT.fetch_sub(1, Ordering::Relaxed) == 1 {
-            unsafe{xous::disconnect(self.conn).unwrap();}
+            unsafe{xous::disconnect(self.api.conn()).unwrap();}
         }
         // if there was object-specific state (such as a one-time use server for async callbacks, specific to the object instance),
         // de-allocate those items here. They don't need a reference count because they are object-specific
@@ -4,8 +4,6 @@
 mod api;
 use api::*;
 
-use num_traits::FromPrimitive;
-
 use log::info;
 
 
@@ -78,6 +76,26 @@ mod implementation {
 }
 
 
+/// The server state that the API calls operate on
+struct CodecServer {
+    codec: implementation::Codec,
+    susres: susres::Susres,
+    running: bool,
+}
+
+impl CodecApi for CodecServer {
+    fn suspend_resume(&mut self, token: usize) {
+        self.codec.suspend();
+        self.susres.suspend_until_resume(token).expect("couldn't execute suspend/resume");
+        self.codec.resume();
+    }
+
+    fn quit(&mut self) {
+        log::warn!("Quit received, goodbye world!");
+        self.running = false;
+    }
+}
+
 fn main() -> ! {
     use crate::implementation::Codec;
 
@@ -89,29 +107,19 @@ fn main() -> ! {
     let codec_sid = xns.register_name(api::SERVER_NAME_CODEC, None).expect("can't register server");
     log::trace!("registered with NS -- {:?}", codec_sid);
 
-    let mut codec = Codec::new();
+    let codec = Codec::new();
 
     log::trace!("ready to accept requests");
 
     // register a suspend/resume listener
     let sr_cid = xous::connect(codec_sid).expect("couldn't create suspend callback connection");
-    let mut susres = susres::Susres::new(None, &xns, api::Opcode::SuspendResume as u32, sr_cid).expect("couldn't create suspend/resume object");
-
-    loop {
-        let msg = xous::receive_message(codec_sid).unwrap();
-        match FromPrimitive::from_usize(msg.body.id()) {
-            Some(Opcode::SuspendResume) => xous::msg_scalar_unpack!(msg, token, _, _, _, {
-                codec.suspend();
-                susres.suspend_until_resume(token).expect("couldn't execute suspend/resume");
-                codec.resume();
-            }),
-            Some(Opcode::Quit) => {
-                log::warn!("Quit received, goodbye world!");
-                break;
-            },
-            None => {
-                log::error!("couldn't convert opcode: {:?}", msg);
-            }
+    let susres = susres::Susres::new(None, &xns, Opcode::SuspendResume as u32, sr_cid).expect("couldn't create suspend/resume object");
+
+    let mut server = CodecServer { codec, susres, running: true };
+    while server.running {
+        let mut msg = xous::receive_message(codec_sid).unwrap();
+        if let Err(e) = server.dispatch(&mut msg) {
+            log::error!("couldn't dispatch {:?}: {:?}", msg, e);
         }
     }
     // clean up our program
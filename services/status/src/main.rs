@@ -97,6 +97,9 @@ pub(crate) enum StatusOpcode {
     /// Raise the preferences menu
     Preferences,
     Quit,
+
+    /// The GAM switched languages; the argument is the new one's index in `locales::LANGUAGES`
+    LocaleChanged,
 }
 
 static mut CB_TO_MAIN_CONN: Option<CID> = None;
@@ -314,7 +317,7 @@ fn wrapped_main() -> ! {
     uptime_tv.style = GlyphStyle::Regular;
     uptime_tv.draw_border = false;
     uptime_tv.margin = Point::new(3, 0);
-    write!(uptime_tv, "{}", t!("secnote.startup", locales::LANG))
+    write!(uptime_tv, "{}", t!("secnote.startup", locales::lang()))
         .expect("|status: couldn't init uptime text");
     gam.post_textview(&mut uptime_tv).expect("|status: can't draw battery stats");
     log::debug!("|status: screensize as reported: {:?}", screensize);
@@ -344,7 +347,7 @@ fn wrapped_main() -> ! {
     security_tv.token = gam.claim_token(gam::STATUS_BAR_NAME).expect("couldn't request token"); // this is a shared magic word to identify this process
     security_tv.clear_area = true;
     security_tv.invert = true;
-    write!(&mut security_tv, "{}", t!("secnote.startup", locales::LANG)).unwrap();
+    write!(&mut security_tv, "{}", t!("secnote.startup", locales::lang())).unwrap();
     gam.post_textview(&mut security_tv).unwrap();
     gam.draw_line(
         status_gid,
@@ -366,7 +369,7 @@ fn wrapped_main() -> ! {
         sec_notes
             .lock()
             .unwrap()
-            .insert("secnote.usb_unlock".to_string(), t!("secnote.usb_unlock", locales::LANG).to_string());
+            .insert("secnote.usb_unlock".to_string(), t!("secnote.usb_unlock", locales::lang()).to_string());
     }
 
     // this is used by the main loop to get the localtime to show on the status bar
@@ -388,6 +391,10 @@ fn wrapped_main() -> ! {
     let main_menu_sid = xous::create_server().unwrap();
     let status_cid = xous::connect(status_sid).unwrap();
     let menu_manager = create_main_menu(keys.clone(), main_menu_sid, status_cid, &com);
+    // menus keep the names they were built with, so they're renamed when the language changes
+    let mut menu_lang = locales::lang();
+    gam.register_locale_listener(status_sid, StatusOpcode::LocaleChanged.to_u32().unwrap())
+        .expect("couldn't register for language changes");
     create_app_menu(xous::connect(status_sid).unwrap());
     let kbd = Arc::new(Mutex::new(keyboard::Keyboard::new(&xns).unwrap()));
 
@@ -458,7 +465,7 @@ fn wrapped_main() -> ! {
                     log::info!("EC update check: nothing to do, firmware is up to date.")
                 }
                 Some(ecup::UpdateResult::Abort) => {
-                    modals.show_notification(t!("ecup.abort", locales::LANG), None).unwrap();
+                    modals.show_notification(t!("ecup.abort", locales::lang()), None).unwrap();
                 }
                 // note: invalid package triggers a pop-up in the update procedure, so we don't need to pop
                 // one up here.
@@ -518,7 +525,7 @@ fn wrapped_main() -> ! {
                         Err(e) => {
                             modals
                                 .show_notification(
-                                    &format!("{}{:?}", t!("rekey.fail", locales::LANG), e),
+                                    &format!("{}{:?}", t!("rekey.fail", locales::lang()), e),
                                     None,
                                 )
                                 .ok();
@@ -563,7 +570,7 @@ fn wrapped_main() -> ! {
         sec_notes
             .lock()
             .unwrap()
-            .insert("secnotes.no_keys".to_string(), t!("secnote.no_keys", locales::LANG).to_string());
+            .insert("secnotes.no_keys".to_string(), t!("secnote.no_keys", locales::lang()).to_string());
         if !restore_running {
             if let Some(staged) = staged_sv {
                 let soc = llio.soc_gitrev().expect("error querying SoC gitrev; this is fatal");
@@ -597,14 +604,14 @@ fn wrapped_main() -> ! {
                         let mut sn = clone.lock().unwrap();
                         sn.insert(
                             "secnotes.gateware_fail".to_string(),
-                            t!("secnote.gateware_fail", locales::LANG).to_string(),
+                            t!("secnote.gateware_fail", locales::lang()).to_string(),
                         );
                     }
                 } else {
                     let mut sn = clone.lock().unwrap();
                     sn.insert(
                         "secnotes.state_fail".to_string(),
-                        t!("secnote.state_fail", locales::LANG).to_string(),
+                        t!("secnote.state_fail", locales::lang()).to_string(),
                     );
                 }
             }
@@ -616,20 +623,20 @@ fn wrapped_main() -> ! {
                 if keys.lock().unwrap().prompt_for_update() {
                     // prompt to apply the update
                     modals
-                        .add_list_item(t!("rootkeys.gwup.yes", locales::LANG))
+                        .add_list_item(t!("rootkeys.gwup.yes", locales::lang()))
                         .expect("couldn't build radio item list");
                     modals
-                        .add_list_item(t!("rootkeys.gwup.no", locales::LANG))
+                        .add_list_item(t!("rootkeys.gwup.no", locales::lang()))
                         .expect("couldn't build radio item list");
                     modals
-                        .add_list_item(t!("socup.ignore", locales::LANG))
+                        .add_list_item(t!("socup.ignore", locales::lang()))
                         .expect("couldn't build radio item list");
-                    match modals.get_radiobutton(t!("socup.candidate", locales::LANG)) {
+                    match modals.get_radiobutton(t!("socup.candidate", locales::lang())) {
                         Ok(response) => {
-                            if response.as_str() == t!("rootkeys.gwup.yes", locales::LANG) {
+                            if response.as_str() == t!("rootkeys.gwup.yes", locales::lang()) {
                                 keys.lock().unwrap().do_update_gw_ux_flow_blocking();
                                 soc_updated = true;
-                            } else if response.as_str() == t!("socup.ignore", locales::LANG) {
+                            } else if response.as_str() == t!("socup.ignore", locales::lang()) {
                                 keys.lock().unwrap().set_update_prompt(false);
                             }
                         }
@@ -708,7 +715,7 @@ fn wrapped_main() -> ! {
             true => {
                 sec_notes.lock().unwrap().insert(
                     "secnote.zero_key".to_string(),
-                    t!("secnote.zero_key", locales::LANG).to_string(),
+                    t!("secnote.zero_key", locales::lang()).to_string(),
                 );
             }
             false => {}
@@ -732,7 +739,7 @@ fn wrapped_main() -> ! {
                 Ok(_) => {}
                 Err(xous::Error::Timeout) => {
                     // TODO: maybe this branch needs a different log message/flow?
-                    modals.show_notification(t!("suspend.fail", locales::LANG), None).unwrap();
+                    modals.show_notification(t!("suspend.fail", locales::lang()), None).unwrap();
                 }
                 Err(_e) => {
                     panic!("Unhandled error on suspend request");
@@ -767,7 +774,7 @@ fn wrapped_main() -> ! {
                         let modals = modals::Modals::new(&xns).unwrap();
                         modals
                             .show_notification(
-                                &t!("login.fail", locales::LANG).replace("{fails}", &count.to_string()),
+                                &t!("login.fail", locales::lang()).replace("{fails}", &count.to_string()),
                                 None,
                             )
                             .ok();
@@ -845,6 +852,23 @@ fn wrapped_main() -> ! {
                 xous::Result::Ok
             });
 
+            // the GAM keeps the language, but only learns the user's choice once the PDDB is mounted
+            if !all_prefs.language.is_empty() {
+                let gam = gam::Gam::new(&xous_names::XousNames::new().unwrap()).unwrap();
+                if let Err(e) = gam.set_language(&all_prefs.language) {
+                    log::error!("cannot set language {}: {:?}", all_prefs.language, e);
+                }
+            }
+            // and the screen reader, which images built with speech have on unless it was turned off
+            match prefs.screen_reader_or_value(cfg!(feature = "tts")) {
+                Ok(on) => {
+                    let gam = gam::Gam::new(&xous_names::XousNames::new().unwrap()).unwrap();
+                    gam.set_screen_reader(on)
+                        .unwrap_or_else(|e| log::error!("cannot set screen reader: {:?}", e));
+                }
+                Err(e) => log::error!("cannot read screen reader preference: {:?}", e),
+            }
+
             // keyboard mapping is restored directly by the keyboard hardware
             #[cfg(not(feature = "no-codec"))]
             {
@@ -961,7 +985,7 @@ fn wrapped_main() -> ! {
 
                 // only if there are more bases open than just the .System basis, insert a new key
                 if basis_list_vec.len() > 1 {
-                    let mut new_list_str = t!("secnote.basis", locales::LANG).to_string();
+                    let mut new_list_str = t!("secnote.basis", locales::lang()).to_string();
                     // initially, just concatenate all the basis names...
                     basis_list_vec.reverse(); // reverse the order so the highest priority basis is on the left.
                     for basis in basis_list_vec {
@@ -995,6 +1019,24 @@ fn wrapped_main() -> ! {
                 autobacklight_duration_secs
                     .store(p.autobacklight_timeout_or_value(10).unwrap() as u32, Ordering::SeqCst);
             }
+            Some(StatusOpcode::LocaleChanged) => msg_scalar_unpack!(msg, index, _, _, _, {
+                if let Some(lang) = locales::LANGUAGES.get(index) {
+                    locales::set_lang(lang);
+                    relabel_main_menu(&menu_manager, menu_lang, lang);
+                    menu_lang = lang;
+                    send_message(
+                        prefs_cid,
+                        Message::new_scalar(
+                            PrefsMenuUpdateOp::UpdateMenuLanguage.to_usize().unwrap(),
+                            0,
+                            0,
+                            0,
+                            0,
+                        ),
+                    )
+                    .ok();
+                }
+            }),
             Some(StatusOpcode::EnableAutomaticBacklight) => {
                 if *autobacklight_enabled.lock().unwrap() {
                     // already enabled, don't re-enable
@@ -1003,8 +1045,8 @@ fn wrapped_main() -> ! {
                 *autobacklight_enabled.lock().unwrap() = true;
 
                 // second: delete the first three elements off the menu
-                menu_manager.delete_item(t!("mainmenu.backlighton", locales::LANG));
-                menu_manager.delete_item(t!("mainmenu.backlightoff", locales::LANG));
+                menu_manager.delete_item(t!("mainmenu.backlighton", locales::lang()));
+                menu_manager.delete_item(t!("mainmenu.backlightoff", locales::lang()));
             }
             Some(StatusOpcode::DisableAutomaticBacklight) => {
                 if !(*autobacklight_enabled.lock().unwrap()) {
@@ -1017,14 +1059,14 @@ fn wrapped_main() -> ! {
                 // third: construct an array of the new elements to add to the menu.
                 let new_elems = [
                     gam::MenuItem {
-                        name: String::from(t!("mainmenu.backlighton", locales::LANG)),
+                        name: String::from(t!("mainmenu.backlighton", locales::lang())),
                         action_conn: Some(com.conn()),
                         action_opcode: com.getop_backlight(),
                         action_payload: gam::MenuPayload::Scalar([191 >> 3, 191 >> 3, 0, 0]),
                         close_on_select: true,
                     },
                     gam::MenuItem {
-                        name: String::from(t!("mainmenu.backlightoff", locales::LANG)),
+                        name: String::from(t!("mainmenu.backlightoff", locales::lang())),
                         action_conn: Some(com.conn()),
                         action_opcode: com.getop_backlight(),
                         action_payload: gam::MenuPayload::Scalar([0, 0, 0, 0]),
@@ -1062,7 +1104,7 @@ fn wrapped_main() -> ! {
                 || stats.voltage == 0xdddd || stats.voltage == 0xffff
                 || stats.soc == 0xdd || stats.soc == 0xff
                 {
-                    write!(&mut battstats_tv, "{}", t!("stats.measuring", locales::LANG)).unwrap();
+                    write!(&mut battstats_tv, "{}", t!("stats.measuring", locales::lang())).unwrap();
                 } else {
                     // toggle between two views of the data every time we have a status update
                     let mut wattage_mw = (stats.current as i32 * stats.voltage as i32) / 1000i32;
@@ -1094,9 +1136,10 @@ fn wrapped_main() -> ! {
                             write!(&mut battstats_tv, "{}", ssid.name.as_str(),).unwrap();
                         } else {
                             if wifi_status.link_state == com_rs::LinkState::ResetHold {
-                                write!(&mut battstats_tv, "{}", t!("stats.wifi_off", locales::LANG)).unwrap();
+                                write!(&mut battstats_tv, "{}", t!("stats.wifi_off", locales::lang()))
+                                    .unwrap();
                             } else {
-                                write!(&mut battstats_tv, "{}", t!("stats.disconnected", locales::LANG))
+                                write!(&mut battstats_tv, "{}", t!("stats.disconnected", locales::lang()))
                                     .unwrap();
                             }
                         }
@@ -1166,7 +1209,7 @@ fn wrapped_main() -> ! {
                         } else {
                             sec_notes.lock().unwrap().insert(
                                 "secnotes.usb_unlock".to_string(),
-                                t!("secnote.usb_unlock", locales::LANG).to_string(),
+                                t!("secnote.usb_unlock", locales::lang()).to_string(),
                             );
                         }
                         debug_locked = is_locked;
@@ -1189,7 +1232,7 @@ fn wrapped_main() -> ! {
                             }
                         }
                     } else {
-                        write!(&mut security_tv, "{}", t!("secnote.allclear", locales::LANG)).unwrap();
+                        write!(&mut security_tv, "{}", t!("secnote.allclear", locales::lang())).unwrap();
                     }
 
                     secnotes_force_redraw = false;
@@ -1249,9 +1292,9 @@ fn wrapped_main() -> ! {
                         }
                     } else {
                         if pddb_poller.is_mounted_nonblocking() {
-                            write!(&mut uptime_tv, "{}", t!("stats.set_time", locales::LANG)).unwrap();
+                            write!(&mut uptime_tv, "{}", t!("stats.set_time", locales::lang())).unwrap();
                         } else {
-                            write!(&mut uptime_tv, "{}", t!("stats.mount_pddb", locales::LANG)).unwrap();
+                            write!(&mut uptime_tv, "{}", t!("stats.mount_pddb", locales::lang())).unwrap();
                         }
                     }
                     // use ticktimer, not stats_phase, because stats_phase encodes some phase drift due to
@@ -1259,7 +1302,7 @@ fn wrapped_main() -> ! {
                     write!(
                         &mut uptime_tv,
                         " {}{}:{:02}:{:02}",
-                        t!("stats.uptime", locales::LANG),
+                        t!("stats.uptime", locales::lang()),
                         (elapsed_time / 3_600_000),
                         (elapsed_time / 60_000) % 60,
                         (elapsed_time / 1000) % 60,
@@ -1288,7 +1331,7 @@ fn wrapped_main() -> ! {
                 let pddb = pddb::Pddb::new();
                 if !pddb.try_unmount() {
                     // sync the pddb prior to lock
-                    modals.show_notification(t!("socup.unmount_fail", locales::LANG), None).ok();
+                    modals.show_notification(t!("socup.unmount_fail", locales::lang()), None).ok();
                 } else {
                     early_settings.set_early_sleep(true).unwrap();
 
@@ -1336,7 +1379,7 @@ fn wrapped_main() -> ! {
             Some(StatusOpcode::TrySuspend) => {
                 if llio.is_plugged_in() {
                     modals
-                        .show_notification(t!("mainmenu.cant_sleep", locales::LANG), None)
+                        .show_notification(t!("mainmenu.cant_sleep", locales::lang()), None)
                         .expect("couldn't notify that power is plugged in");
                 } else {
                     // reset the last key hit timer, so that when we wake up we get a full timeout period
@@ -1345,7 +1388,7 @@ fn wrapped_main() -> ! {
                     match susres.initiate_suspend() {
                         Ok(_) => {}
                         Err(xous::Error::Timeout) => {
-                            modals.show_notification(t!("suspend.fail", locales::LANG), None).unwrap();
+                            modals.show_notification(t!("suspend.fail", locales::lang()), None).unwrap();
                         }
                         Err(_e) => {
                             panic!("Unhandled error on suspend request");
@@ -1363,20 +1406,20 @@ fn wrapped_main() -> ! {
                 // to the next stage (see `PrepareBackup` implementation for a template).
                 if llio.is_plugged_in() {
                     modals
-                        .show_notification(t!("mainmenu.cant_sleep", locales::LANG), None)
+                        .show_notification(t!("mainmenu.cant_sleep", locales::lang()), None)
                         .expect("couldn't notify that power is plugged in");
                 } else {
                     // show a note to inform the user that you can't turn it on without an external power
                     // source...
                     modals
-                        .add_list_item(t!("rootkeys.gwup.yes", locales::LANG))
+                        .add_list_item(t!("rootkeys.gwup.yes", locales::lang()))
                         .expect("couldn't build radio item list");
                     modals
-                        .add_list_item(t!("rootkeys.gwup.no", locales::LANG))
+                        .add_list_item(t!("rootkeys.gwup.no", locales::lang()))
                         .expect("couldn't build radio item list");
-                    match modals.get_radiobutton(t!("mainmenu.shutdown_confirm", locales::LANG)) {
+                    match modals.get_radiobutton(t!("mainmenu.shutdown_confirm", locales::lang())) {
                         Ok(response) => {
-                            if response.as_str() == t!("rootkeys.gwup.yes", locales::LANG) {
+                            if response.as_str() == t!("rootkeys.gwup.yes", locales::lang()) {
                                 {}
                             } else {
                                 // abort the flow now by returning to the main dispatch handler
@@ -1388,7 +1431,7 @@ fn wrapped_main() -> ! {
                     // unmount things before shutting down
                     let pddb = pddb::Pddb::new();
                     if !pddb.try_unmount() {
-                        modals.show_notification(t!("socup.unmount_fail", locales::LANG), None).ok();
+                        modals.show_notification(t!("socup.unmount_fail", locales::lang()), None).ok();
                     } else {
                         pddb.pddb_halt();
                         gam.shipmode_blank_request().ok();
@@ -1454,36 +1497,37 @@ fn wrapped_main() -> ! {
                         let xns = xous_names::XousNames::new().unwrap();
                         let modals = modals::Modals::new(&xns).unwrap();
                         modals
-                            .add_list_item(t!("burnkey.bbram", locales::LANG))
+                            .add_list_item(t!("burnkey.bbram", locales::lang()))
                             .expect("couldn't build radio item list");
                         modals
-                            .add_list_item(t!("burnkey.efuse", locales::LANG))
+                            .add_list_item(t!("burnkey.efuse", locales::lang()))
                             .expect("couldn't build radio item list");
                         modals
-                            .add_list_item(t!("wlan.cancel", locales::LANG))
+                            .add_list_item(t!("wlan.cancel", locales::lang()))
                             .expect("couldn't build radio item list");
-                        match modals.get_radiobutton(t!("burnkey.type", locales::LANG)) {
+                        match modals.get_radiobutton(t!("burnkey.type", locales::lang())) {
                             Ok(response) => {
-                                if response.as_str() == t!("burnkey.bbram", locales::LANG) {
+                                if response.as_str() == t!("burnkey.bbram", locales::lang()) {
                                     // do BBRAM flow
                                     log::info!("{}BBRAM.CONFIRM,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                                     // punts to a script-driven flow on the pi
                                     modals.show_notification(
-                                        t!("burnkey.bbram_exec", locales::LANG),
+                                        t!("burnkey.bbram_exec", locales::lang()),
                                         Some("https://github.com/betrusted-io/betrusted-wiki/wiki/FAQ:-FPGA-AES-Encryption-Key-(eFuse-BBRAM)"),
                                     ).ok();
-                                } else if response.as_str() == t!("burnkey.efuse", locales::LANG) {
+                                } else if response.as_str() == t!("burnkey.efuse", locales::lang()) {
                                     log::info!("{}EFUSE.CONFIRM,{}", xous::BOOKEND_START, xous::BOOKEND_END);
                                     // do eFuse flow
                                     modals
-                                        .add_list_item(t!("rootkeys.gwup.yes", locales::LANG))
+                                        .add_list_item(t!("rootkeys.gwup.yes", locales::lang()))
                                         .expect("couldn't build radio item list");
                                     modals
-                                        .add_list_item(t!("rootkeys.gwup.no", locales::LANG))
+                                        .add_list_item(t!("rootkeys.gwup.no", locales::lang()))
                                         .expect("couldn't build radio item list");
-                                    match modals.get_radiobutton(t!("burnkey.efuse_confirm", locales::LANG)) {
+                                    match modals.get_radiobutton(t!("burnkey.efuse_confirm", locales::lang()))
+                                    {
                                         Ok(response) => {
-                                            if response.as_str() == t!("rootkeys.gwup.yes", locales::LANG) {
+                                            if response.as_str() == t!("rootkeys.gwup.yes", locales::lang()) {
                                                 // do the efuse burn
                                                 keys.lock().unwrap().do_efuse_burn();
                                             } else {
@@ -1508,15 +1552,15 @@ fn wrapped_main() -> ! {
                         let xns = xous_names::XousNames::new().unwrap();
                         let modals = modals::Modals::new(&xns).unwrap();
                         modals
-                            .add_list_item(t!("rootkeys.gwup.yes", locales::LANG))
+                            .add_list_item(t!("rootkeys.gwup.yes", locales::lang()))
                             .expect("couldn't build radio item list");
                         modals
-                            .add_list_item(t!("rootkeys.gwup.no", locales::LANG))
+                            .add_list_item(t!("rootkeys.gwup.no", locales::lang()))
                             .expect("couldn't build radio item list");
                         log::info!("{}BACKUP.CONFIRM,{}", xous::BOOKEND_START, xous::BOOKEND_END);
-                        match modals.get_radiobutton(t!("backup.confirm", locales::LANG)) {
+                        match modals.get_radiobutton(t!("backup.confirm", locales::lang())) {
                             Ok(response) => {
-                                if response.as_str() == t!("rootkeys.gwup.yes", locales::LANG) {
+                                if response.as_str() == t!("rootkeys.gwup.yes", locales::lang()) {
                                     send_message(
                                         cb_cid,
                                         Message::new_scalar(
@@ -1565,7 +1609,7 @@ fn wrapped_main() -> ! {
                         if !pddb.try_unmount() {
                             let xns = xous_names::XousNames::new().unwrap();
                             let modals = modals::Modals::new(&xns).unwrap();
-                            modals.show_notification(t!("socup.unmount_fail", locales::LANG), None).ok();
+                            modals.show_notification(t!("socup.unmount_fail", locales::lang()), None).ok();
                         } else {
                             *checksums.lock().unwrap() = Some(pddb.compute_checksums());
                             // PDDB is halted forever. However, the system is reset after a backup is run.
@@ -1652,7 +1696,7 @@ fn wrapped_main() -> ! {
                                         let xns = xous_names::XousNames::new().unwrap();
                                         let modals = modals::Modals::new(&xns).unwrap();
                                         modals
-                                            .show_notification(t!("ecup.abort", locales::LANG), None)
+                                            .show_notification(t!("ecup.abort", locales::lang()), None)
                                             .unwrap();
                                     }
                                     // note: invalid package triggers a pop-up in the update procedure, so we
@@ -30,10 +30,13 @@ enum DevicePrefsOp {
     AudioOff,
     HeadsetVolume,
     EarpieceVolume,
+    Language,
+    ScreenReader,
 
     // Those are reserved for internal use
     UpdateMenuAudioEnabled = 399,
     UpdateMenuAudioDisabled,
+    UpdateMenuLanguage,
 }
 
 #[derive(Debug, num_derive::FromPrimitive, num_derive::ToPrimitive, PartialEq, PartialOrd)]
@@ -41,31 +44,41 @@ pub enum PrefsMenuUpdateOp {
     // Those are reserved for internal use
     UpdateMenuAudioEnabled = 399,
     UpdateMenuAudioDisabled,
+    UpdateMenuLanguage,
 }
 
-impl Display for DevicePrefsOp {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+impl DevicePrefsOp {
+    /// The menu label for this item in `lang`.
+    fn label(&self, lang: &str) -> &'static str {
         match self {
-            Self::AutobacklightOnBoot => write!(f, "{}", t!("prefs.autobacklight_enable", locales::LANG)),
-            Self::AutobacklightTimeout => write!(f, "{}", t!("prefs.autobacklight_duration", locales::LANG)),
-            Self::AutoSleepTimeout => write!(f, "{}", t!("prefs.autosleep_duration", locales::LANG)),
-            Self::RebootOnAutoSleep => write!(f, "{}", t!("prefs.autosleep_reboot", locales::LANG)),
-            Self::ConnectKnownNetworksOnBoot => write!(f, "{}", t!("prefs.wifi_connect_auto", locales::LANG)),
-            Self::WifiKill => write!(f, "{}", t!("prefs.wifi_kill", locales::LANG)),
-            Self::KeyboardLayout => write!(f, "{}", t!("prefs.keyboard_layout", locales::LANG)),
-            Self::WLANMenu => write!(f, "{}", t!("prefs.wifi_setting", locales::LANG)),
-            Self::SetTime => write!(f, "{}", t!("mainmenu.set_rtc", locales::LANG)),
-            Self::SetTimezone => write!(f, "{}", t!("mainmenu.set_tz", locales::LANG)),
-            Self::AudioOn => write!(f, "{}", t!("prefs.enable_audio", locales::LANG)),
-            Self::AudioOff => write!(f, "{}", t!("prefs.disable_audio", locales::LANG)),
-            Self::HeadsetVolume => write!(f, "{}", t!("prefs.headphone_volume", locales::LANG)),
-            Self::EarpieceVolume => write!(f, "{}", t!("prefs.speaker_volume", locales::LANG)),
+            Self::AutobacklightOnBoot => t!("prefs.autobacklight_enable", lang),
+            Self::AutobacklightTimeout => t!("prefs.autobacklight_duration", lang),
+            Self::AutoSleepTimeout => t!("prefs.autosleep_duration", lang),
+            Self::RebootOnAutoSleep => t!("prefs.autosleep_reboot", lang),
+            Self::ConnectKnownNetworksOnBoot => t!("prefs.wifi_connect_auto", lang),
+            Self::WifiKill => t!("prefs.wifi_kill", lang),
+            Self::KeyboardLayout => t!("prefs.keyboard_layout", lang),
+            Self::Language => t!("prefs.language", lang),
+            Self::ScreenReader => t!("prefs.screen_reader", lang),
+            Self::WLANMenu => t!("prefs.wifi_setting", lang),
+            Self::SetTime => t!("mainmenu.set_rtc", lang),
+            Self::SetTimezone => t!("mainmenu.set_tz", lang),
+            Self::AudioOn => t!("prefs.enable_audio", lang),
+            Self::AudioOff => t!("prefs.disable_audio", lang),
+            Self::HeadsetVolume => t!("prefs.headphone_volume", lang),
+            Self::EarpieceVolume => t!("prefs.speaker_volume", lang),
 
             _ => unimplemented!("should not end up here!"),
         }
     }
 }
 
+impl Display for DevicePrefsOp {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.label(locales::lang()))
+    }
+}
+
 #[derive(Debug)]
 enum DevicePrefsError {
     PrefsError(userprefs::Error),
@@ -104,6 +117,8 @@ struct DevicePrefs {
     menu_global_conn: xous::CID,
     status_cid: xous::CID,
     netmgr: net::NetManager,
+    /// The language the menu items were last labelled in
+    menu_lang: &'static str,
 }
 
 impl PrefHandler for DevicePrefs {
@@ -111,10 +126,14 @@ impl PrefHandler for DevicePrefs {
         if match FromPrimitive::from_usize(op) {
             Some(other) => {
                 let other: PrefsMenuUpdateOp = other;
-                #[cfg(not(feature = "no-codec"))]
                 match other {
+                    #[cfg(not(feature = "no-codec"))]
                     PrefsMenuUpdateOp::UpdateMenuAudioEnabled => self.alter_menu_audio_off(),
+                    #[cfg(not(feature = "no-codec"))]
                     PrefsMenuUpdateOp::UpdateMenuAudioDisabled => self.alter_menu_audio_on(),
+                    PrefsMenuUpdateOp::UpdateMenuLanguage => self.relabel_menu(),
+                    #[allow(unreachable_patterns)]
+                    _ => {}
                 }
                 true
             }
@@ -163,7 +182,7 @@ impl PrefHandler for DevicePrefs {
             .collect::<Vec<gam::MenuItem>>();
 
         menus.push(gam::MenuItem {
-            name: String::from(t!("mainmenu.closemenu", locales::LANG)),
+            name: String::from(t!("mainmenu.closemenu", locales::lang())),
             action_conn: None,
             action_opcode: 0,
             action_payload: gam::MenuPayload::Scalar([0, 0, 0, 0]),
@@ -196,6 +215,7 @@ impl DevicePrefs {
             menu_global_conn: menu_conn,
             status_cid: status_conn,
             netmgr: net::NetManager::new(),
+            menu_lang: locales::lang(),
         }
     }
 
@@ -211,6 +231,8 @@ impl DevicePrefs {
             AutoSleepTimeout,
             RebootOnAutoSleep,
             KeyboardLayout,
+            Language,
+            ScreenReader,
             // Note: this vec sets the order of items in the preferences menu
             // The CI system assumes that the time setting items are always at
             // the bottom of the preferences menu, in this particular order.
@@ -249,6 +271,8 @@ impl DevicePrefs {
             AutoSleepTimeout => self.autosleep_timeout(),
             RebootOnAutoSleep => self.reboot_on_autosleep(),
             KeyboardLayout => self.keyboard_layout(),
+            Language => self.language(),
+            ScreenReader => self.screen_reader(),
             WLANMenu => self.wlan_menu(),
             SetTime => self.set_time_menu(),
             SetTimezone => self.set_timezone_menu(),
@@ -269,7 +293,7 @@ impl DevicePrefs {
 
     fn show_error_modal(&self, e: DevicePrefsError) {
         self.modals
-            .show_notification(format!("{}: {}", t!("wlan.error", locales::LANG), e).as_str(), None)
+            .show_notification(format!("{}: {}", t!("wlan.error", locales::lang()), e).as_str(), None)
             .unwrap()
     }
 }
@@ -278,13 +302,15 @@ impl DevicePrefs {
     fn autobacklight_on_boot(&mut self) -> Result<(), DevicePrefsError> {
         let cv = self.up.autobacklight_on_boot_or_value(true)?;
 
-        self.modals.add_list(vec![t!("prefs.yes", locales::LANG), t!("prefs.no", locales::LANG)]).unwrap();
+        self.modals
+            .add_list(vec![t!("prefs.yes", locales::lang()), t!("prefs.no", locales::lang())])
+            .unwrap();
 
         let new_result = yes_no_to_bool(
             self.modals
                 .get_radiobutton(&format!(
                     "{} {}",
-                    t!("prefs.current_setting", locales::LANG),
+                    t!("prefs.current_setting", locales::lang()),
                     bool_to_yes_no(cv)
                 ))
                 .unwrap()
@@ -341,12 +367,12 @@ impl DevicePrefs {
 
         let raw_timeout = self
             .modals
-            .alert_builder(t!("prefs.autobacklight_duration_in_secs", locales::LANG))
+            .alert_builder(t!("prefs.autobacklight_duration_in_secs", locales::lang()))
             .field(
                 Some(cv.to_string()),
                 Some(|tf| match tf.as_str().parse::<u64>() {
                     Ok(_) => None,
-                    Err(_) => Some(String::from(t!("prefs.autobacklight_err", locales::LANG))),
+                    Err(_) => Some(String::from(t!("prefs.autobacklight_err", locales::lang()))),
                 }),
             )
             .build()
@@ -362,12 +388,12 @@ impl DevicePrefs {
 
         let raw_timeout = self
             .modals
-            .alert_builder(t!("prefs.autosleep_duration_in_mins", locales::LANG))
+            .alert_builder(t!("prefs.autosleep_duration_in_mins", locales::lang()))
             .field(
                 Some(cv.to_string()),
                 Some(|tf| match tf.as_str().parse::<u64>() {
                     Ok(_) => None,
-                    Err(_) => Some(String::from(t!("prefs.autobacklight_err", locales::LANG))),
+                    Err(_) => Some(String::from(t!("prefs.autobacklight_err", locales::lang()))),
                 }),
             )
             .build()
@@ -381,12 +407,14 @@ impl DevicePrefs {
     fn reboot_on_autosleep(&self) -> Result<(), DevicePrefsError> {
         let cv = self.up.reboot_on_autosleep_or_default()?;
 
-        self.modals.add_list(vec![t!("prefs.yes", locales::LANG), t!("prefs.no", locales::LANG)]).unwrap();
+        self.modals
+            .add_list(vec![t!("prefs.yes", locales::lang()), t!("prefs.no", locales::lang())])
+            .unwrap();
         let new_result = yes_no_to_bool(
             self.modals
                 .get_radiobutton(&format!(
                     "{} {}",
-                    t!("prefs.current_setting", locales::LANG),
+                    t!("prefs.current_setting", locales::lang()),
                     bool_to_yes_no(cv)
                 ))
                 .unwrap()
@@ -399,13 +427,15 @@ impl DevicePrefs {
     fn wifi_kill(&mut self) -> Result<(), DevicePrefsError> {
         let cv = self.up.wifi_kill_or_default()?;
 
-        self.modals.add_list(vec![t!("prefs.yes", locales::LANG), t!("prefs.no", locales::LANG)]).unwrap();
+        self.modals
+            .add_list(vec![t!("prefs.yes", locales::lang()), t!("prefs.no", locales::lang())])
+            .unwrap();
 
         let new_result = yes_no_to_bool(
             self.modals
                 .get_radiobutton(&format!(
                     "{} {}",
-                    t!("prefs.current_setting", locales::LANG),
+                    t!("prefs.current_setting", locales::lang()),
                     bool_to_yes_no(cv)
                 ))
                 .unwrap()
@@ -427,13 +457,15 @@ impl DevicePrefs {
     fn connect_known_networks_on_boot(&mut self) -> Result<(), DevicePrefsError> {
         let cv = self.up.connect_known_networks_on_boot_or_value(true)?;
 
-        self.modals.add_list(vec![t!("prefs.yes", locales::LANG), t!("prefs.no", locales::LANG)]).unwrap();
+        self.modals
+            .add_list(vec![t!("prefs.yes", locales::lang()), t!("prefs.no", locales::lang())])
+            .unwrap();
 
         let new_result = yes_no_to_bool(
             self.modals
                 .get_radiobutton(&format!(
                     "{} {}",
-                    t!("prefs.current_setting", locales::LANG),
+                    t!("prefs.current_setting", locales::lang()),
                     bool_to_yes_no(cv)
                 ))
                 .unwrap()
@@ -490,7 +522,7 @@ impl DevicePrefs {
             .modals
             .get_radiobutton(&format!(
                 "{} {}",
-                t!("prefs.current_setting", locales::LANG),
+                t!("prefs.current_setting", locales::lang()),
                 keyboard::KeyMap::from(kl)
             ))
             .unwrap();
@@ -505,6 +537,85 @@ impl DevicePrefs {
         Ok(())
     }
 
+    fn language(&mut self) -> Result<(), DevicePrefsError> {
+        // each language is offered in its own words, so it can be found whatever the current one is
+        let names = vec!["English", "日本語", "中文", "Français", "English (speech)"];
+
+        self.modals.add_list(names.clone()).unwrap();
+
+        let current = locales::lang_index(locales::lang()).and_then(|i| names.get(i)).unwrap_or(&"");
+        let new_result = self
+            .modals
+            .get_radiobutton(&format!("{} {}", t!("prefs.current_setting", locales::lang()), current))
+            .unwrap();
+
+        let lang = match names.iter().position(|&elem| elem == new_result.as_str()) {
+            Some(val) => locales::LANGUAGES[val],
+            None => return Ok(()),
+        };
+
+        self.gam.set_language(lang)?;
+        Ok(self.up.set_language(lang.to_string())?)
+    }
+
+    fn screen_reader(&mut self) -> Result<(), DevicePrefsError> {
+        let cv = self.up.screen_reader_or_value(cfg!(feature = "tts"))?;
+
+        self.modals
+            .add_list(vec![t!("prefs.yes", locales::lang()), t!("prefs.no", locales::lang())])
+            .unwrap();
+        let new_result = yes_no_to_bool(
+            self.modals
+                .get_radiobutton(&format!(
+                    "{} {}",
+                    t!("prefs.current_setting", locales::lang()),
+                    bool_to_yes_no(cv)
+                ))
+                .unwrap()
+                .as_str(),
+        );
+        self.gam.set_screen_reader(new_result)?;
+
+        Ok(self.up.set_screen_reader(new_result)?)
+    }
+
+    /// Renames the menu items from the language they were labelled in to the current one. Items that
+    /// aren't in the menu right now, like the audio ones that come and go, are skipped by the menu.
+    fn relabel_menu(&mut self) {
+        use DevicePrefsOp::*;
+
+        let lang = locales::lang();
+        let menu = match self.menu.as_ref() {
+            Some(menu) => menu,
+            None => return,
+        };
+        let items = [
+            WifiKill,
+            ConnectKnownNetworksOnBoot,
+            AutobacklightOnBoot,
+            AutobacklightTimeout,
+            AutoSleepTimeout,
+            RebootOnAutoSleep,
+            KeyboardLayout,
+            Language,
+            ScreenReader,
+            WLANMenu,
+            SetTime,
+            SetTimezone,
+            AudioOn,
+            AudioOff,
+            HeadsetVolume,
+            EarpieceVolume,
+        ];
+        for item in items.iter() {
+            if item.label(self.menu_lang) != item.label(lang) {
+                menu.rename_item(item.label(self.menu_lang), item.label(lang));
+            }
+        }
+        menu.rename_item(t!("mainmenu.closemenu", self.menu_lang), t!("mainmenu.closemenu", lang));
+        self.menu_lang = lang;
+    }
+
     #[cfg(not(feature = "no-codec"))]
     fn audio_on(&mut self) -> Result<(), DevicePrefsError> {
         self.codec.setup_8k_stream()?;
@@ -519,7 +630,7 @@ impl DevicePrefs {
     fn alter_menu_audio_on(&mut self) {
         let menu = self.menu.as_ref().unwrap();
 
-        menu.delete_item(t!("mainmenu.closemenu", locales::LANG));
+        menu.delete_item(t!("mainmenu.closemenu", locales::lang()));
         menu.delete_item(&DevicePrefsOp::AudioOn.to_string());
         menu.add_item(gam::MenuItem {
             name: String::from(&DevicePrefsOp::AudioOff.to_string()),
@@ -544,7 +655,7 @@ impl DevicePrefs {
         });
 
         menu.add_item(gam::MenuItem {
-            name: String::from(t!("mainmenu.closemenu", locales::LANG)),
+            name: String::from(t!("mainmenu.closemenu", locales::lang())),
             action_conn: None,
             action_opcode: 0,
             action_payload: gam::MenuPayload::Scalar([0, 0, 0, 0]),
@@ -566,7 +677,7 @@ impl DevicePrefs {
     fn alter_menu_audio_off(&mut self) {
         let menu = self.menu.as_ref().unwrap();
         // hide volume toggles
-        menu.delete_item(t!("mainmenu.closemenu", locales::LANG));
+        menu.delete_item(t!("mainmenu.closemenu", locales::lang()));
         menu.delete_item(&DevicePrefsOp::AudioOff.to_string());
         menu.delete_item(&DevicePrefsOp::EarpieceVolume.to_string());
         menu.delete_item(&DevicePrefsOp::HeadsetVolume.to_string());
@@ -578,7 +689,7 @@ impl DevicePrefs {
             close_on_select: true,
         });
         menu.add_item(gam::MenuItem {
-            name: String::from(t!("mainmenu.closemenu", locales::LANG)),
+            name: String::from(t!("mainmenu.closemenu", locales::lang())),
             action_conn: None,
             action_opcode: 0,
             action_payload: gam::MenuPayload::Scalar([0, 0, 0, 0]),
@@ -615,7 +726,7 @@ impl DevicePrefs {
     #[cfg(not(feature = "no-codec"))]
     fn headset_volume(&mut self) -> Result<(), DevicePrefsError> {
         // headset -> headphone
-        let (db_val, slider_val) = self.volume_slider(t!("prefs.headphone_volume", locales::LANG), true)?;
+        let (db_val, slider_val) = self.volume_slider(t!("prefs.headphone_volume", locales::lang()), true)?;
         self.codec.set_headphone_volume(codec::VolumeOps::Set, Some(db_val as f32))?;
         self.up.set_headset_volume(slider_val)?;
 
@@ -625,7 +736,7 @@ impl DevicePrefs {
     #[cfg(not(feature = "no-codec"))]
     fn earpiece_volume(&mut self) -> Result<(), DevicePrefsError> {
         // earpiece -> speaker
-        let (db_val, slider_val) = self.volume_slider(t!("prefs.speaker_volume", locales::LANG), false)?;
+        let (db_val, slider_val) = self.volume_slider(t!("prefs.speaker_volume", locales::lang()), false)?;
         self.codec.set_speaker_volume(codec::VolumeOps::Set, Some(db_val as f32))?;
         self.up.set_earpiece_volume(slider_val)?;
         Ok(())
@@ -639,9 +750,9 @@ pub fn percentage_to_db(value: u32) -> i32 {
 }
 
 fn yes_no_to_bool(val: &str) -> bool {
-    if val == t!("prefs.yes", locales::LANG) {
+    if val == t!("prefs.yes", locales::lang()) {
         true
-    } else if val == t!("prefs.no", locales::LANG) {
+    } else if val == t!("prefs.no", locales::lang()) {
         false
     } else {
         unreachable!("cannot go here!");
@@ -650,8 +761,8 @@ fn yes_no_to_bool(val: &str) -> bool {
 
 fn bool_to_yes_no(val: bool) -> String {
     match val {
-        true => t!("prefs.yes", locales::LANG).to_owned(),
-        false => t!("prefs.no", locales::LANG).to_owned(),
+        true => t!("prefs.yes", locales::lang()).to_owned(),
+        false => t!("prefs.no", locales::lang()).to_owned(),
     }
 }
 
@@ -4,6 +4,7 @@ use std::io::Write;
 use locales::t;
 use net::ScanState;
 use num_traits::*;
+use qr::payload::WifiAuth;
 
 use crate::preferences::PrefHandler;
 
@@ -14,16 +15,18 @@ pub enum WlanManOp {
     AddNetworkManually,
     KnownNetworks,
     DeleteNetwork,
+    ScanQrCode,
 }
 
 impl Display for WlanManOp {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
-            Self::AddNetworkManually => write!(f, "{}", t!("wlan.manual_add", locales::LANG)),
-            Self::ScanForNetworks => write!(f, "{}", t!("wlan.scan", locales::LANG)),
-            Self::Status => write!(f, "{}", t!("wlan.status", locales::LANG)),
-            Self::DeleteNetwork => write!(f, "{}", t!("wlan.delete", locales::LANG)),
-            Self::KnownNetworks => write!(f, "{}", t!("wlan.list_known", locales::LANG)),
+            Self::AddNetworkManually => write!(f, "{}", t!("wlan.manual_add", locales::lang())),
+            Self::ScanForNetworks => write!(f, "{}", t!("wlan.scan", locales::lang())),
+            Self::Status => write!(f, "{}", t!("wlan.status", locales::lang())),
+            Self::DeleteNetwork => write!(f, "{}", t!("wlan.delete", locales::lang())),
+            Self::KnownNetworks => write!(f, "{}", t!("wlan.list_known", locales::lang())),
+            Self::ScanQrCode => write!(f, "{}", t!("wlan.qr_add", locales::lang())),
         }
     }
 }
@@ -95,7 +98,7 @@ impl PrefHandler for WLANMan {
             .collect::<Vec<gam::MenuItem>>();
 
         menus.push(gam::MenuItem {
-            name: String::from(t!("mainmenu.closemenu", locales::LANG)),
+            name: String::from(t!("mainmenu.closemenu", locales::lang())),
             action_conn: None,
             action_opcode: 0,
             action_payload: gam::MenuPayload::Scalar([0, 0, 0, 0]),
@@ -119,7 +122,7 @@ impl WLANMan {
     pub fn actions(&self) -> Vec<WlanManOp> {
         use WlanManOp::*;
 
-        vec![ScanForNetworks, Status, AddNetworkManually, KnownNetworks, DeleteNetwork]
+        vec![ScanForNetworks, Status, AddNetworkManually, ScanQrCode, KnownNetworks, DeleteNetwork]
     }
 
     #[allow(dead_code)] // just in case we need this later
@@ -141,7 +144,7 @@ impl WLANMan {
     fn add_new_ssid(&mut self) -> Result<(), WLANError> {
         let connection_data = self
             .modals
-            .alert_builder(t!("wlan.ssid_entry", locales::LANG))
+            .alert_builder(t!("wlan.ssid_entry", locales::lang()))
             .field(
                 Some("SSID".to_string()),
                 Some(|text| {
@@ -152,7 +155,7 @@ impl WLANMan {
                     None
                 }),
             )
-            .field(Some(t!("wlan.password", locales::LANG).to_string()), None)
+            .field(Some(t!("wlan.password", locales::lang()).to_string()), None)
             .build()
             .unwrap();
 
@@ -161,6 +164,31 @@ impl WLANMan {
         self.store_connection_info(content[0].as_str(), content[1].as_str())
     }
 
+    /// Adds the network in a `WIFI:` QR code, as routers and phones show to share one.
+    fn add_ssid_from_qr(&mut self) -> Result<(), WLANError> {
+        let xns = xous_names::XousNames::new().unwrap();
+        let qr = qr::Qr::new(&xns)?;
+        self.modals.dynamic_notification(Some(t!("wlan.qr_scanning", locales::lang())), None).ok();
+        let scanned = qr.scan_text();
+        self.modals.dynamic_notification_close().ok();
+        let note = match scanned.as_deref().map(qr::payload::Wifi::parse) {
+            Ok(Some(wifi)) => match wifi.auth {
+                WifiAuth::Open | WifiAuth::Wpa => {
+                    self.store_connection_info(&wifi.ssid, &wifi.password)?;
+                    t!("wlan.qr_added", locales::lang()).replace("{ssid}", &wifi.ssid)
+                }
+                WifiAuth::Wep | WifiAuth::Other(_) => {
+                    t!("wlan.qr_unsupported", locales::lang()).replace("{ssid}", &wifi.ssid)
+                }
+            },
+            Ok(None) => t!("wlan.qr_not_wifi", locales::lang()).to_string(),
+            Err(qr::ScanError::NoCamera) => t!("wlan.qr_no_camera", locales::lang()).to_string(),
+            Err(e) => format!("{}: {}", t!("wlan.qr_failed", locales::lang()), e),
+        };
+        self.modals.show_notification(&note, None)?;
+        Ok(())
+    }
+
     fn store_connection_info(&mut self, ssid: &str, pass: &str) -> Result<(), WLANError> {
         self.netmgr.connection_manager_stop().unwrap();
         match self.pddb.get(
@@ -211,7 +239,7 @@ impl WLANMan {
             // accumulate the results.
             match state {
                 ScanState::Updating => {
-                    let mut progress = t!("wlan.ssid_scanning", locales::LANG).to_string();
+                    let mut progress = t!("wlan.ssid_scanning", locales::lang()).to_string();
                     let networks: Vec<&str> = networks.iter().map(|s| s.as_str()).collect();
                     progress.push_str("\n\n");
                     for network in networks {
@@ -231,7 +259,7 @@ impl WLANMan {
                     if showing_wait {
                         self.modals.dynamic_notification_close().unwrap();
                     }
-                    self.modals.show_notification(t!("wlan.ssid_off_error", locales::LANG), None).unwrap();
+                    self.modals.show_notification(t!("wlan.ssid_off_error", locales::lang()), None).unwrap();
                     return Ok(());
                 }
             }
@@ -251,17 +279,17 @@ impl WLANMan {
         networks.truncate(max_entries);
 
         if networks.is_empty() {
-            self.modals.show_notification(t!("wlan.no_networks", locales::LANG), None).unwrap();
+            self.modals.show_notification(t!("wlan.no_networks", locales::lang()), None).unwrap();
             return Ok(());
         }
 
-        networks.push(t!("wlan.cancel", locales::LANG));
+        networks.push(t!("wlan.cancel", locales::lang()));
 
         self.modals.add_list(networks).unwrap();
 
-        let ssid = self.modals.get_radiobutton(t!("wlan.ssid_choose", locales::LANG)).unwrap();
+        let ssid = self.modals.get_radiobutton(t!("wlan.ssid_choose", locales::lang())).unwrap();
 
-        if ssid == t!("wlan.cancel", locales::LANG) {
+        if ssid == t!("wlan.cancel", locales::lang()) {
             return Ok(());
         }
 
@@ -271,8 +299,8 @@ impl WLANMan {
     fn fill_password_for_ssid(&mut self, ssid: &str) -> Result<(), WLANError> {
         let connection_data = self
             .modals
-            .alert_builder(&t!("wlan.ssid_password", locales::LANG).replace("{ssid}", ssid))
-            .field(Some(t!("wlan.password", locales::LANG).to_string()), None)
+            .alert_builder(&t!("wlan.ssid_password", locales::lang()).replace("{ssid}", ssid))
+            .field(Some(t!("wlan.password", locales::lang()).to_string()), None)
             .build()
             .unwrap();
 
@@ -285,7 +313,7 @@ impl WLANMan {
         let status = self.com.wlan_status()?;
         let ssid = match status.ssid {
             Some(s) => s.name.to_string(),
-            None => t!("stats.disconnected", locales::LANG).to_string(),
+            None => t!("stats.disconnected", locales::lang()).to_string(),
         };
 
         let ls = status.link_state;
@@ -316,14 +344,14 @@ impl WLANMan {
             Err(_) => Vec::new(),
         };
 
-        let mut networks_string = String::from(t!("wlan.no_known_networks", locales::LANG));
+        let mut networks_string = String::from(t!("wlan.no_known_networks", locales::lang()));
 
         if networks.is_empty() {
             self.modals.show_notification(&networks_string, None).unwrap();
             return Ok(());
         }
 
-        networks_string = String::from(t!("wlan.known_networks", locales::LANG));
+        networks_string = String::from(t!("wlan.known_networks", locales::lang()));
 
         networks_string += &networks.iter().map(|s| format!(" ▪ {}", s)).collect::<Vec<String>>().join("\n");
 
@@ -339,16 +367,16 @@ impl WLANMan {
         };
 
         if networks.is_empty() {
-            self.modals.show_notification(t!("wlan.no_known_networks", locales::LANG), None).unwrap();
+            self.modals.show_notification(t!("wlan.no_known_networks", locales::lang()), None).unwrap();
             return Ok(());
         }
 
-        let cancel_item = t!("wlan.cancel", locales::LANG);
+        let cancel_item = t!("wlan.cancel", locales::lang());
         self.modals.add_list(networks.iter().map(|s| s.as_str()).collect()).unwrap();
         self.modals.add_list_item(cancel_item).unwrap();
 
         let ssid_to_be_deleted =
-            self.modals.get_radiobutton(t!("wlan.choose_delete", locales::LANG)).unwrap();
+            self.modals.get_radiobutton(t!("wlan.choose_delete", locales::lang())).unwrap();
 
         if ssid_to_be_deleted.eq(cancel_item) {
             return Ok(());
@@ -368,6 +396,7 @@ impl WLANMan {
             WlanManOp::Status => self.network_status(),
             WlanManOp::DeleteNetwork => self.delete_network(),
             WlanManOp::KnownNetworks => self.known_networks(),
+            WlanManOp::ScanQrCode => self.add_ssid_from_qr(),
         };
 
         resp.unwrap_or_else(|error| self.show_error_modal(error));
@@ -375,7 +404,7 @@ impl WLANMan {
 
     fn show_error_modal(&self, e: WLANError) {
         self.modals
-            .show_notification(format!("{}: {}", t!("wlan.error", locales::LANG), e).as_str(), None)
+            .show_notification(format!("{}: {}", t!("wlan.error", locales::lang()), e).as_str(), None)
             .unwrap()
     }
 }
@@ -77,6 +77,29 @@ pub(crate) enum Opcode {
     /// Unset HID descriptor and reset HIDv2 state
     HIDUnsetDescriptor = 1030,
 
+    /// Claim functions of the composite device for the caller, replacing its earlier claim
+    CompositeRegister = 1280,
+    /// Returns the functions the composite device offers
+    CompositeFunctions = 1281,
+
+    /// Put a virtual card into the CCID reader slot
+    CcidInsert = 1536,
+    /// Take the virtual card out of the CCID reader slot
+    CcidRemove = 1537,
+    /// Blocks the caller, waiting for a CCID event
+    CcidRxDeferred = 1538,
+    /// Answer the pending CCID command
+    CcidTx = 1539,
+    /// A bump from the time extension process, or from the hosted CCID transport
+    CcidPump = 1540,
+
+    /// Send an Ethernet frame to the host over the network link
+    NetTx = 1792,
+    /// Blocks the caller, waiting for a frame from the host or a change of link state
+    NetRxDeferred = 1793,
+    /// A bump from the hosted network transport
+    NetPump = 1794,
+
     /// Handle the USB interrupt
     UsbIrqHandler = 2048,
     /// Suspend/resume callback
@@ -127,6 +150,9 @@ pub enum UsbDeviceType {
     MassStorage = 3,
     Serial = 4,
     HIDv2 = 5,
+    Ccid = 6,
+    Net = 7,
+    Composite = 8,
 }
 use std::convert::TryFrom;
 
@@ -142,11 +168,55 @@ impl TryFrom<usize> for UsbDeviceType {
             3 => Ok(UsbDeviceType::MassStorage),
             4 => Ok(UsbDeviceType::Serial),
             5 => Ok(UsbDeviceType::HIDv2),
+            6 => Ok(UsbDeviceType::Ccid),
+            7 => Ok(UsbDeviceType::Net),
+            8 => Ok(UsbDeviceType::Composite),
             _ => Err("Invalid UsbDeviceType specifier"),
         }
     }
 }
 
+/// A set of functions of the composite device. Each one is owned by the process that registered it, and
+/// the device offers whatever has been registered.
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+pub struct UsbFunctions(pub u32);
+
+impl UsbFunctions {
+    /// FIDO HID
+    pub const FIDO: UsbFunctions = UsbFunctions(1 << 1);
+    /// Boot keyboard, for autotype
+    pub const KEYBOARD: UsbFunctions = UsbFunctions(1 << 0);
+    /// Mass storage, backed by the block device set with `set_block_device()`
+    #[cfg(feature = "mass-storage")]
+    pub const MASS_STORAGE: UsbFunctions = UsbFunctions(1 << 3);
+    pub const NONE: UsbFunctions = UsbFunctions(0);
+    /// CDC-ACM serial port
+    pub const SERIAL: UsbFunctions = UsbFunctions(1 << 2);
+
+    /// Everything this build can offer
+    pub fn supported() -> UsbFunctions {
+        #[cfg(feature = "mass-storage")]
+        return UsbFunctions::KEYBOARD
+            | UsbFunctions::FIDO
+            | UsbFunctions::SERIAL
+            | UsbFunctions::MASS_STORAGE;
+        #[cfg(not(feature = "mass-storage"))]
+        return UsbFunctions::KEYBOARD | UsbFunctions::FIDO | UsbFunctions::SERIAL;
+    }
+
+    pub fn contains(self, other: UsbFunctions) -> bool { self.0 & other.0 == other.0 }
+
+    pub fn intersects(self, other: UsbFunctions) -> bool { self.0 & other.0 != 0 }
+
+    pub fn is_empty(self) -> bool { self.0 == 0 }
+}
+
+impl core::ops::BitOr for UsbFunctions {
+    type Output = UsbFunctions;
+
+    fn bitor(self, rhs: UsbFunctions) -> UsbFunctions { UsbFunctions(self.0 | rhs.0) }
+}
+
 pub const SERIAL_BINARY_BUFLEN: usize = 128;
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Clone)]
 pub struct UsbSerialAscii {
@@ -181,6 +251,105 @@ pub struct HIDReportMessage {
     pub data: Option<HIDReport>,
 }
 
+/// Largest command or response APDU carried between the CCID slot and the card application. Short APDUs
+/// top out at 261 bytes; longer exchanges are chained by the card protocol.
+pub const CCID_APDU_BUFLEN: usize = 512;
+/// In hosted mode the CCID slot is served on this localhost TCP port. The stream carries the same
+/// PC_to_RDR / RDR_to_PC messages the bulk endpoints do, so a software CCID driver can talk to it.
+pub const CCID_HOSTED_PORT: u16 = 35964;
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct CcidMsgIpc {
+    pub data: [u8; CCID_APDU_BUFLEN],
+    pub len: usize,
+    /// Encodes the state of the message
+    pub code: CcidCode,
+}
+
+impl CcidMsgIpc {
+    /// `data` beyond `CCID_APDU_BUFLEN` is dropped; callers check the length first.
+    pub fn new(code: CcidCode, data: &[u8]) -> Self {
+        let len = data.len().min(CCID_APDU_BUFLEN);
+        let mut msg = CcidMsgIpc { data: [0u8; CCID_APDU_BUFLEN], len, code };
+        msg.data[..len].copy_from_slice(&data[..len]);
+        msg
+    }
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone, Eq, PartialEq)]
+pub enum CcidCode {
+    Insert,
+    RxWait,
+    Apdu,
+    VerifyPin,
+    ModifyPin,
+    Reset,
+    Tx,
+    TxAck,
+    Denied,
+}
+
+/// Something the host asked of the card in the CCID slot.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CcidEvent {
+    /// A command APDU. Answer it with `ccid_respond()`.
+    Apdu(Vec<u8>),
+    /// The host asked for a PIN to be entered on the device (a "pinpad" verify). Carries CLA INS P1 P2 of
+    /// the VERIFY to complete with the PIN; answer with `ccid_respond()`.
+    VerifyPin([u8; 4]),
+    /// As `VerifyPin`, for commands that set a new PIN (CHANGE REFERENCE DATA, RESET RETRY COUNTER).
+    ModifyPin([u8; 4]),
+    /// The card was powered on or off; any security state should be dropped.
+    Reset,
+}
+
+/// Largest Ethernet frame carried over the network link: 1500 bytes of payload behind the 14 byte header.
+/// There is no frame check sequence on USB.
+pub const NET_FRAME_BUFLEN: usize = 1514;
+/// In hosted mode the network link is served on this localhost TCP port. Each frame goes over the
+/// stream as a big-endian u16 length followed by the frame, which is easy to bridge onto a tap device.
+pub const NET_HOSTED_PORT: u16 = 35965;
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone)]
+pub struct NetMsgIpc {
+    pub data: [u8; NET_FRAME_BUFLEN],
+    pub len: usize,
+    /// Encodes the state of the message
+    pub code: NetCode,
+}
+
+impl NetMsgIpc {
+    /// `data` beyond `NET_FRAME_BUFLEN` is dropped; callers check the length first.
+    pub fn new(code: NetCode, data: &[u8]) -> Self {
+        let len = data.len().min(NET_FRAME_BUFLEN);
+        let mut msg = NetMsgIpc { data: [0u8; NET_FRAME_BUFLEN], len, code };
+        msg.data[..len].copy_from_slice(&data[..len]);
+        msg
+    }
+}
+
+#[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Copy, Clone, Eq, PartialEq)]
+pub enum NetCode {
+    RxWait,
+    Frame,
+    LinkUp,
+    LinkDown,
+    Tx,
+    TxAck,
+    Denied,
+}
+
+/// Something that happened on the network link.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum NetEvent {
+    /// An Ethernet frame from the host, without the frame check sequence.
+    Frame(Vec<u8>),
+    /// The host brought its side of the link up; frames sent from now on are delivered.
+    LinkUp,
+    /// The host went away, or the USB core was switched to something else.
+    LinkDown,
+}
+
 /// this structure is used to register a USB listener.
 #[derive(Debug, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Clone)]
 pub(crate) struct UsbListenerRegistration {
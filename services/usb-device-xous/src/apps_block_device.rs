@@ -25,6 +25,7 @@ struct RwOp {
     max_lba_id: usize,
 }
 
+#[derive(Clone)]
 pub struct AppsBlockDevice {
     app_cid: Arc<Mutex<Option<xous::CID>>>,
     rw_ids: Arc<Mutex<RwOp>>,
@@ -0,0 +1,487 @@
+//! CCID (USB smart card reader class) slot logic, shared by the hardware and hosted backends.
+//!
+//! The reader has a single slot that holds at most one virtual card. The card itself lives in an
+//! application process: this module answers the reader-level messages (power, status, parameters) on its
+//! own and turns everything addressed to the card into a `CcidMsgIpc` event for the application's
+//! `ccid_wait_event()` call. The application's answer comes back through `Slot::data_block()`.
+//!
+//! Only the short APDU level of exchange is offered, so each XfrBlock carries one whole command APDU.
+
+use std::collections::VecDeque;
+
+use xous_ipc::Buffer;
+
+use crate::api::{CCID_APDU_BUFLEN, CcidCode, CcidMsgIpc};
+
+pub(crate) const CCID_HEADER_LEN: usize = 10;
+/// Header plus the longest short command APDU (4 byte header, Lc, 255 data bytes, Le).
+pub(crate) const CCID_MAX_MSG_LEN: usize = CCID_HEADER_LEN + 261;
+
+// PC_to_RDR message types
+const PC_TO_RDR_ICC_POWER_ON: u8 = 0x62;
+const PC_TO_RDR_ICC_POWER_OFF: u8 = 0x63;
+const PC_TO_RDR_GET_SLOT_STATUS: u8 = 0x65;
+const PC_TO_RDR_XFR_BLOCK: u8 = 0x6F;
+const PC_TO_RDR_GET_PARAMETERS: u8 = 0x6C;
+const PC_TO_RDR_RESET_PARAMETERS: u8 = 0x6D;
+const PC_TO_RDR_SET_PARAMETERS: u8 = 0x61;
+const PC_TO_RDR_ESCAPE: u8 = 0x6B;
+const PC_TO_RDR_SECURE: u8 = 0x69;
+const PC_TO_RDR_ABORT: u8 = 0x72;
+const PC_TO_RDR_SET_DATA_RATE_AND_CLOCK: u8 = 0x73;
+// RDR_to_PC message types
+const RDR_TO_PC_DATA_BLOCK: u8 = 0x80;
+const RDR_TO_PC_SLOT_STATUS: u8 = 0x81;
+const RDR_TO_PC_PARAMETERS: u8 = 0x82;
+const RDR_TO_PC_ESCAPE: u8 = 0x83;
+const RDR_TO_PC_DATA_RATE_AND_CLOCK: u8 = 0x84;
+const RDR_TO_PC_NOTIFY_SLOT_CHANGE: u8 = 0x50;
+
+// bmICCStatus
+const ICC_ACTIVE: u8 = 0;
+const ICC_INACTIVE: u8 = 1;
+const ICC_ABSENT: u8 = 2;
+// bmCommandStatus, already shifted into place
+const CMD_FAILED: u8 = 1 << 6;
+const CMD_TIME_EXTENSION: u8 = 2 << 6;
+// bError
+const ERR_CMD_NOT_SUPPORTED: u8 = 0x00;
+const ERR_SLOT_DOES_NOT_EXIST: u8 = 0x05;
+const ERR_BAD_PIN_OPERATION: u8 = 0x0A; // offset of abData[0], where bPINOperation sits
+const ERR_ICC_MUTE: u8 = 0xFE;
+const ERR_CMD_SLOT_BUSY: u8 = 0xE0;
+
+/// Offset of the PIN APDU within the abData of a PC_to_RDR_Secure verification: bPINOperation and the
+/// rest of the 15-byte PIN verification data structure come first.
+const SECURE_VERIFY_APDU_OFFSET: usize = 15;
+/// Offset of bNumberMessage in a modification. bMsgIndex2 and bMsgIndex3 are only there when that many
+/// messages are, which moves the APDU.
+const SECURE_MODIFY_NUMBER_MESSAGE: usize = 11;
+const SECURE_MODIFY_APDU_OFFSET_MIN: usize = 18;
+
+/// T=1 protocol data reported by GetParameters: Fi/Di = 372/1, checksum LRC, no guard time, BWI 4 and
+/// CWI 5, clock stop not allowed, IFSC 254, NAD 0.
+const T1_PARAMETERS: [u8; 7] = [0x11, 0x10, 0x00, 0x45, 0x00, 0xFE, 0x00];
+
+/// Gathers bulk OUT data until a whole CCID message is available.
+#[derive(Default)]
+pub(crate) struct Assembler {
+    buf: Vec<u8>,
+}
+
+impl Assembler {
+    pub(crate) fn extend(&mut self, data: &[u8]) { self.buf.extend_from_slice(data); }
+
+    /// Returns the next complete message, if there is one. A header announcing more than the reader
+    /// accepts can't be resynchronised from, so everything buffered is dropped.
+    pub(crate) fn next_message(&mut self) -> Option<Vec<u8>> {
+        if self.buf.len() < CCID_HEADER_LEN {
+            return None;
+        }
+        let len = u32::from_le_bytes([self.buf[1], self.buf[2], self.buf[3], self.buf[4]]) as usize;
+        if len > CCID_MAX_MSG_LEN - CCID_HEADER_LEN {
+            log::warn!("CCID message of {} bytes is too long, dropping input", len);
+            self.buf.clear();
+            return None;
+        }
+        if self.buf.len() < CCID_HEADER_LEN + len {
+            return None;
+        }
+        Some(self.buf.drain(..CCID_HEADER_LEN + len).collect())
+    }
+
+    #[allow(dead_code)] // only the USB class resets mid-stream
+    pub(crate) fn clear(&mut self) { self.buf.clear(); }
+}
+
+/// What to do after the slot has looked at a message from the host.
+#[derive(Default)]
+pub(crate) struct Outcome {
+    /// Message to send back to the host right away
+    pub reply: Option<Vec<u8>>,
+    /// Event for the card application
+    pub event: Option<CcidMsgIpc>,
+}
+
+pub(crate) struct Slot {
+    /// Answer-to-reset of the card in the slot; `None` while the slot is empty
+    atr: Option<Vec<u8>>,
+    powered: bool,
+    /// Sequence number of the command the card application is working on
+    pending: Option<u8>,
+    /// A card was inserted or removed since the host was last told
+    changed: bool,
+}
+
+impl Slot {
+    pub(crate) fn new() -> Self { Slot { atr: None, powered: false, pending: None, changed: false } }
+
+    fn icc_status(&self) -> u8 {
+        match (&self.atr, self.powered) {
+            (None, _) => ICC_ABSENT,
+            (Some(_), false) => ICC_INACTIVE,
+            (Some(_), true) => ICC_ACTIVE,
+        }
+    }
+
+    /// True while the card application owes the host an answer.
+    #[allow(dead_code)] // the hosted transport has no time extensions to send
+    pub(crate) fn is_busy(&self) -> bool { self.pending.is_some() }
+
+    pub(crate) fn insert(&mut self, atr: &[u8]) {
+        self.atr = Some(atr.to_vec());
+        self.powered = false;
+        self.changed = true;
+    }
+
+    /// Empties the slot. If a command was still with the card, returns the failure to send for it.
+    pub(crate) fn remove(&mut self) -> Option<Vec<u8>> {
+        self.atr = None;
+        self.powered = false;
+        self.changed = true;
+        self.pending
+            .take()
+            .map(|seq| message(RDR_TO_PC_SLOT_STATUS, seq, CMD_FAILED | ICC_ABSENT, ERR_ICC_MUTE, 0, &[]))
+    }
+
+    /// Powers the card down and drops any transaction in flight, e.g. when the host goes away. Returns
+    /// true if the card was in use, in which case the card application needs a reset event.
+    pub(crate) fn reset(&mut self) -> bool {
+        let was_active = self.powered || self.pending.is_some();
+        self.powered = false;
+        self.pending = None;
+        was_active
+    }
+
+    /// The RDR_to_PC_NotifySlotChange message for the interrupt endpoint, if the host hasn't seen the
+    /// latest insertion or removal yet.
+    pub(crate) fn slot_change(&mut self) -> Option<[u8; 2]> {
+        if !self.changed {
+            return None;
+        }
+        self.changed = false;
+        // bit 0: card present, bit 1: changed since last notification
+        Some([RDR_TO_PC_NOTIFY_SLOT_CHANGE, if self.atr.is_some() { 0b11 } else { 0b10 }])
+    }
+
+    /// Wraps the card application's response APDU for the command it was handed.
+    pub(crate) fn data_block(&mut self, response: &[u8]) -> Option<Vec<u8>> {
+        let seq = self.pending.take()?;
+        Some(message(RDR_TO_PC_DATA_BLOCK, seq, self.icc_status(), 0, 0, response))
+    }
+
+    /// Asks the host for more time while the card application works, e.g. while someone types a PIN.
+    pub(crate) fn time_extension(&self) -> Option<Vec<u8>> {
+        // bError is the BWT multiplier being requested
+        self.pending
+            .map(|seq| message(RDR_TO_PC_DATA_BLOCK, seq, CMD_TIME_EXTENSION | self.icc_status(), 1, 0, &[]))
+    }
+
+    pub(crate) fn process(&mut self, msg: &[u8]) -> Outcome {
+        let ty = msg[0];
+        let slot = msg[5];
+        let seq = msg[6];
+        let data = &msg[CCID_HEADER_LEN..];
+        let reply_ty = match ty {
+            PC_TO_RDR_ICC_POWER_ON | PC_TO_RDR_XFR_BLOCK | PC_TO_RDR_SECURE => RDR_TO_PC_DATA_BLOCK,
+            PC_TO_RDR_GET_PARAMETERS | PC_TO_RDR_RESET_PARAMETERS | PC_TO_RDR_SET_PARAMETERS => {
+                RDR_TO_PC_PARAMETERS
+            }
+            PC_TO_RDR_ESCAPE => RDR_TO_PC_ESCAPE,
+            PC_TO_RDR_SET_DATA_RATE_AND_CLOCK => RDR_TO_PC_DATA_RATE_AND_CLOCK,
+            _ => RDR_TO_PC_SLOT_STATUS,
+        };
+        let fail = |status: u8, error: u8| Outcome {
+            reply: Some(message(reply_ty, seq, CMD_FAILED | status, error, 0, &[])),
+            event: None,
+        };
+        let reply = |status: u8, param: u8, data: &[u8]| Outcome {
+            reply: Some(message(reply_ty, seq, status, 0, param, data)),
+            event: None,
+        };
+
+        if slot != 0 {
+            return fail(self.icc_status(), ERR_SLOT_DOES_NOT_EXIST);
+        }
+        if self.pending.is_some() && ty != PC_TO_RDR_ABORT {
+            return fail(self.icc_status(), ERR_CMD_SLOT_BUSY);
+        }
+        match ty {
+            PC_TO_RDR_ICC_POWER_ON => match self.atr.clone() {
+                Some(atr) => {
+                    self.powered = true;
+                    let mut outcome = reply(ICC_ACTIVE, 0, &atr);
+                    outcome.event = Some(CcidMsgIpc::new(CcidCode::Reset, &[]));
+                    outcome
+                }
+                None => fail(ICC_ABSENT, ERR_ICC_MUTE),
+            },
+            PC_TO_RDR_ICC_POWER_OFF => {
+                let was_powered = self.powered;
+                self.powered = false;
+                let mut outcome = reply(self.icc_status(), 0, &[]);
+                if was_powered {
+                    outcome.event = Some(CcidMsgIpc::new(CcidCode::Reset, &[]));
+                }
+                outcome
+            }
+            PC_TO_RDR_GET_SLOT_STATUS => reply(self.icc_status(), 0, &[]),
+            PC_TO_RDR_XFR_BLOCK | PC_TO_RDR_SECURE if !self.powered => fail(self.icc_status(), ERR_ICC_MUTE),
+            PC_TO_RDR_XFR_BLOCK => {
+                if data.len() > CCID_APDU_BUFLEN {
+                    return fail(self.icc_status(), ERR_ICC_MUTE);
+                }
+                self.pending = Some(seq);
+                Outcome { reply: None, event: Some(CcidMsgIpc::new(CcidCode::Apdu, data)) }
+            }
+            PC_TO_RDR_SECURE => {
+                let (code, offset) = match (data.first(), data.get(SECURE_MODIFY_NUMBER_MESSAGE)) {
+                    (Some(0), _) => (CcidCode::VerifyPin, SECURE_VERIFY_APDU_OFFSET),
+                    (Some(1), Some(&messages)) => (
+                        CcidCode::ModifyPin,
+                        SECURE_MODIFY_APDU_OFFSET_MIN + messages.clamp(1, 3) as usize - 1,
+                    ),
+                    _ => return fail(self.icc_status(), ERR_BAD_PIN_OPERATION),
+                };
+                if data.len() < offset + 4 {
+                    return fail(self.icc_status(), ERR_BAD_PIN_OPERATION);
+                }
+                self.pending = Some(seq);
+                Outcome { reply: None, event: Some(CcidMsgIpc::new(code, &data[offset..offset + 4])) }
+            }
+            PC_TO_RDR_GET_PARAMETERS | PC_TO_RDR_RESET_PARAMETERS | PC_TO_RDR_SET_PARAMETERS => {
+                // the protocol is fixed, so a SetParameters just gets the current values back
+                reply(self.icc_status(), 1, &T1_PARAMETERS)
+            }
+            PC_TO_RDR_ABORT => {
+                // the card application may still answer; `data_block()` then has nowhere to send it
+                self.pending = None;
+                reply(self.icc_status(), 0, &[])
+            }
+            _ => {
+                log::debug!("unsupported CCID message {:x}", ty);
+                fail(self.icc_status(), ERR_CMD_NOT_SUPPORTED)
+            }
+        }
+    }
+}
+
+/// Builds an RDR_to_PC message for slot 0. `param` is the message-specific last header byte
+/// (bChainParameter, bClockStatus or bProtocolNum).
+fn message(ty: u8, seq: u8, status: u8, error: u8, param: u8, data: &[u8]) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(CCID_HEADER_LEN + data.len());
+    msg.push(ty);
+    msg.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    msg.extend_from_slice(&[0, seq, status, error, param]);
+    msg.extend_from_slice(data);
+    msg
+}
+
+/// The process that owns the card slot, and its pending `ccid_wait_event()` call.
+#[derive(Default)]
+pub(crate) struct Listener {
+    // under the theory that PIDs cannot be forged, as with the U2F listener
+    pid: Option<xous::PID>,
+    waiting: Option<xous::MessageEnvelope>,
+    queue: VecDeque<CcidMsgIpc>,
+}
+
+impl Listener {
+    /// The slot is locked to the first process that touches it.
+    pub(crate) fn is_owner(&mut self, sender: xous::MessageSender) -> bool {
+        if self.pid.is_none() {
+            self.pid = sender.pid();
+        }
+        self.pid == sender.pid()
+    }
+
+    pub(crate) fn wait(&mut self, mut msg: xous::MessageEnvelope) {
+        if !self.is_owner(msg.sender) {
+            log::warn!("CCID slot is locked on first use; additional servers are ignored: {:?}", msg.sender);
+            respond(&mut msg, CcidMsgIpc::new(CcidCode::Denied, &[]));
+            return;
+        }
+        if self.waiting.is_some() {
+            log::error!(
+                "Double-listener request detected for the CCID slot; the earlier request is dropped."
+            );
+        }
+        match self.queue.pop_front() {
+            Some(event) => respond(&mut msg, event),
+            None => self.waiting = Some(msg),
+        }
+    }
+
+    pub(crate) fn deliver(&mut self, event: CcidMsgIpc) {
+        match self.waiting.take() {
+            Some(mut msg) => respond(&mut msg, event),
+            None => self.queue.push_back(event),
+        }
+    }
+}
+
+/// Replaces the contents of a lent `CcidMsgIpc`; the envelope replies to the caller once dropped.
+pub(crate) fn respond(msg: &mut xous::MessageEnvelope, reply: CcidMsgIpc) {
+    let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+    buffer.replace(reply).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(ty: u8, seq: u8, data: &[u8]) -> Vec<u8> {
+        let mut msg = vec![ty];
+        msg.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        msg.extend_from_slice(&[0, seq, 0, 0, 0]);
+        msg.extend_from_slice(data);
+        msg
+    }
+
+    #[test]
+    fn test_assembler() {
+        let mut asm = Assembler::default();
+        let apdu = [0u8; 100];
+        let msg = command(PC_TO_RDR_XFR_BLOCK, 1, &apdu);
+        asm.extend(&msg[..64]);
+        assert!(asm.next_message().is_none());
+        asm.extend(&msg[64..]);
+        // a second message in the same read stays buffered
+        asm.extend(&command(PC_TO_RDR_GET_SLOT_STATUS, 2, &[]));
+        assert_eq!(asm.next_message().unwrap(), msg);
+        assert_eq!(asm.next_message().unwrap()[6], 2);
+        assert!(asm.next_message().is_none());
+
+        let mut bogus = command(PC_TO_RDR_XFR_BLOCK, 3, &[]);
+        bogus[1..5].copy_from_slice(&4096u32.to_le_bytes());
+        asm.extend(&bogus);
+        assert!(asm.next_message().is_none());
+        assert!(asm.buf.is_empty());
+    }
+
+    #[test]
+    fn test_power_and_status() {
+        let mut slot = Slot::new();
+        let out = slot.process(&command(PC_TO_RDR_ICC_POWER_ON, 1, &[]));
+        let reply = out.reply.unwrap();
+        assert_eq!(reply[0], RDR_TO_PC_DATA_BLOCK);
+        assert_eq!(reply[7], CMD_FAILED | ICC_ABSENT);
+        assert_eq!(reply[8], ERR_ICC_MUTE);
+        assert!(out.event.is_none());
+
+        slot.insert(&[0x3B, 0x00]);
+        assert_eq!(slot.slot_change(), Some([RDR_TO_PC_NOTIFY_SLOT_CHANGE, 0b11]));
+        assert_eq!(slot.slot_change(), None);
+        let reply = slot.process(&command(PC_TO_RDR_GET_SLOT_STATUS, 2, &[])).reply.unwrap();
+        assert_eq!(reply[0], RDR_TO_PC_SLOT_STATUS);
+        assert_eq!(reply[7], ICC_INACTIVE);
+
+        let out = slot.process(&command(PC_TO_RDR_ICC_POWER_ON, 3, &[]));
+        assert_eq!(out.reply.unwrap(), vec![0x80, 2, 0, 0, 0, 0, 3, ICC_ACTIVE, 0, 0, 0x3B, 0x00]);
+        assert_eq!(out.event.unwrap().code, CcidCode::Reset);
+
+        let reply = slot.process(&command(PC_TO_RDR_GET_PARAMETERS, 4, &[])).reply.unwrap();
+        assert_eq!(reply[0], RDR_TO_PC_PARAMETERS);
+        assert_eq!(reply[9], 1);
+        assert_eq!(&reply[CCID_HEADER_LEN..], &T1_PARAMETERS);
+
+        let out = slot.process(&command(PC_TO_RDR_ICC_POWER_OFF, 5, &[]));
+        assert_eq!(out.reply.unwrap()[7], ICC_INACTIVE);
+        assert_eq!(out.event.unwrap().code, CcidCode::Reset);
+
+        let reply = slot.process(&command(0x6E, 6, &[])).reply.unwrap(); // IccClock
+        assert_eq!(reply[0], RDR_TO_PC_SLOT_STATUS);
+        assert_eq!(reply[7], CMD_FAILED | ICC_INACTIVE);
+        assert_eq!(reply[8], ERR_CMD_NOT_SUPPORTED);
+
+        let mut wrong_slot = command(PC_TO_RDR_GET_SLOT_STATUS, 7, &[]);
+        wrong_slot[5] = 1;
+        assert_eq!(slot.process(&wrong_slot).reply.unwrap()[8], ERR_SLOT_DOES_NOT_EXIST);
+    }
+
+    #[test]
+    fn test_xfr_block() {
+        let mut slot = Slot::new();
+        slot.insert(&[0x3B, 0x00]);
+        // not powered yet
+        let reply = slot.process(&command(PC_TO_RDR_XFR_BLOCK, 1, &[0, 0xA4, 4, 0])).reply.unwrap();
+        assert_eq!(reply[7], CMD_FAILED | ICC_INACTIVE);
+        slot.process(&command(PC_TO_RDR_ICC_POWER_ON, 2, &[]));
+
+        let out = slot.process(&command(PC_TO_RDR_XFR_BLOCK, 3, &[0, 0xA4, 4, 0]));
+        assert!(out.reply.is_none());
+        let event = out.event.unwrap();
+        assert_eq!(event.code, CcidCode::Apdu);
+        assert_eq!(&event.data[..event.len], &[0, 0xA4, 4, 0]);
+        assert!(slot.is_busy());
+
+        let reply = slot.process(&command(PC_TO_RDR_GET_SLOT_STATUS, 4, &[])).reply.unwrap();
+        assert_eq!(reply[8], ERR_CMD_SLOT_BUSY);
+        let ext = slot.time_extension().unwrap();
+        assert_eq!((ext[6], ext[7], ext[8]), (3, CMD_TIME_EXTENSION | ICC_ACTIVE, 1));
+
+        assert_eq!(
+            slot.data_block(&[0x90, 0x00]).unwrap(),
+            vec![0x80, 2, 0, 0, 0, 0, 3, ICC_ACTIVE, 0, 0, 0x90, 0x00]
+        );
+        assert!(!slot.is_busy());
+        assert!(slot.data_block(&[0x90, 0x00]).is_none());
+        assert!(slot.time_extension().is_none());
+
+        // removal fails whatever was outstanding
+        slot.process(&command(PC_TO_RDR_XFR_BLOCK, 5, &[0, 0xCA, 0, 0x6E]));
+        let reply = slot.remove().unwrap();
+        assert_eq!((reply[0], reply[6], reply[7]), (RDR_TO_PC_SLOT_STATUS, 5, CMD_FAILED | ICC_ABSENT));
+        assert_eq!(slot.slot_change(), Some([RDR_TO_PC_NOTIFY_SLOT_CHANGE, 0b10]));
+        assert!(!slot.reset());
+
+        slot.insert(&[0x3B, 0x00]);
+        slot.process(&command(PC_TO_RDR_ICC_POWER_ON, 6, &[]));
+        assert!(slot.reset());
+        assert!(!slot.reset());
+    }
+
+    #[test]
+    fn test_secure() {
+        let mut slot = Slot::new();
+        slot.insert(&[0x3B, 0x00]);
+        slot.process(&command(PC_TO_RDR_ICC_POWER_ON, 1, &[]));
+
+        // the 15-byte verification structure, bPINOperation included, then the VERIFY APDU template
+        let mut verify = vec![0u8; SECURE_VERIFY_APDU_OFFSET];
+        verify.extend_from_slice(&[0x00, 0x20, 0x00, 0x81, 0x00]);
+        let event = slot.process(&command(PC_TO_RDR_SECURE, 2, &verify)).event.unwrap();
+        assert_eq!(event.code, CcidCode::VerifyPin);
+        assert_eq!(&event.data[..event.len], &[0x00, 0x20, 0x00, 0x81]);
+        slot.data_block(&[0x90, 0x00]).unwrap();
+
+        // with three messages, both optional message indexes are there
+        let mut modify = vec![1u8];
+        modify.extend_from_slice(&[0u8; SECURE_MODIFY_APDU_OFFSET_MIN + 2 - 1]);
+        modify[SECURE_MODIFY_NUMBER_MESSAGE] = 3;
+        modify.extend_from_slice(&[0x00, 0x24, 0x00, 0x83]);
+        let event = slot.process(&command(PC_TO_RDR_SECURE, 3, &modify)).event.unwrap();
+        assert_eq!(event.code, CcidCode::ModifyPin);
+        assert_eq!(&event.data[..event.len], &[0x00, 0x24, 0x00, 0x83]);
+        slot.data_block(&[0x90, 0x00]).unwrap();
+        let mut modify = vec![1u8];
+        modify.extend_from_slice(&[0u8; SECURE_MODIFY_APDU_OFFSET_MIN - 1]);
+        modify[SECURE_MODIFY_NUMBER_MESSAGE] = 1;
+        modify.extend_from_slice(&[0x00, 0x2C, 0x02, 0x81]);
+        let event = slot.process(&command(PC_TO_RDR_SECURE, 4, &modify)).event.unwrap();
+        assert_eq!(&event.data[..event.len], &[0x00, 0x2C, 0x02, 0x81]);
+        slot.data_block(&[0x90, 0x00]).unwrap();
+
+        let out = slot.process(&command(PC_TO_RDR_SECURE, 5, &[4]));
+        assert!(out.event.is_none());
+        let reply = out.reply.unwrap();
+        assert_eq!((reply[0], reply[8]), (RDR_TO_PC_DATA_BLOCK, ERR_BAD_PIN_OPERATION));
+
+        // abort frees the slot even though the card never answered
+        slot.process(&command(PC_TO_RDR_SECURE, 6, &verify));
+        assert!(slot.is_busy());
+        let reply = slot.process(&command(PC_TO_RDR_ABORT, 6, &[])).reply.unwrap();
+        assert_eq!(reply[7], ICC_ACTIVE);
+        assert!(!slot.is_busy());
+    }
+}
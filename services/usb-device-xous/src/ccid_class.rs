@@ -0,0 +1,171 @@
+use std::collections::VecDeque;
+
+use usb_device::class_prelude::*;
+use usb_device::control::{Recipient, RequestType};
+
+use crate::ccid::{Assembler, CCID_MAX_MSG_LEN};
+
+const USB_CLASS_CCID: u8 = 0x0B;
+const CCID_FUNCTIONAL_DESCRIPTOR: u8 = 0x21;
+/// Class-specific request to cancel a command, paired with a PC_to_RDR_Abort on the bulk pipe
+const REQUEST_ABORT: u8 = 0x01;
+const PACKET_SIZE: u16 = 64;
+
+// dwFeatures
+const FEATURE_AUTO_CONFIG_ATR: u32 = 0x0000_0002;
+const FEATURE_AUTO_ACTIVATION: u32 = 0x0000_0004;
+const FEATURE_AUTO_VOLTAGE: u32 = 0x0000_0008;
+const FEATURE_AUTO_CLOCK: u32 = 0x0000_0010;
+const FEATURE_AUTO_BAUD: u32 = 0x0000_0020;
+const FEATURE_AUTO_PPS_NEGOTIATION: u32 = 0x0000_0040;
+const FEATURE_AUTO_PPS_CURRENT: u32 = 0x0000_0080;
+const FEATURE_SHORT_APDU: u32 = 0x0002_0000;
+
+/// A single-slot CCID reader with a built-in PIN pad. This only moves messages on and off the wire; the
+/// slot logic is in `ccid::Slot`.
+pub struct CcidClass<'a, B: UsbBus> {
+    interface: InterfaceNumber,
+    bulk_out: EndpointOut<'a, B>,
+    bulk_in: EndpointIn<'a, B>,
+    interrupt_in: EndpointIn<'a, B>,
+    rx: Assembler,
+    /// messages waiting for the bulk IN pipe
+    tx_queue: VecDeque<Vec<u8>>,
+    /// message being sent, and how much of it has gone out
+    tx: Option<(Vec<u8>, usize)>,
+}
+
+impl<'a, B: UsbBus> CcidClass<'a, B> {
+    pub fn new(alloc: &'a UsbBusAllocator<B>) -> Self {
+        CcidClass {
+            interface: alloc.interface(),
+            bulk_out: alloc.bulk(PACKET_SIZE),
+            bulk_in: alloc.bulk(PACKET_SIZE),
+            interrupt_in: alloc.interrupt(8, 255),
+            rx: Assembler::default(),
+            tx_queue: VecDeque::new(),
+            tx: None,
+        }
+    }
+
+    /// Reads whatever the host has sent and returns the next complete PC_to_RDR message, if any.
+    pub fn read_message(&mut self) -> Option<Vec<u8>> {
+        let mut packet = [0u8; PACKET_SIZE as usize];
+        loop {
+            match self.bulk_out.read(&mut packet) {
+                Ok(len) => self.rx.extend(&packet[..len]),
+                Err(UsbError::WouldBlock) => break,
+                Err(e) => {
+                    log::warn!("CCID read error: {:?}", e);
+                    break;
+                }
+            }
+        }
+        self.rx.next_message()
+    }
+
+    /// Queues an RDR_to_PC message for the host.
+    pub fn write_message(&mut self, msg: Vec<u8>) {
+        self.tx_queue.push_back(msg);
+        if self.tx.is_none() {
+            self.flush();
+        }
+    }
+
+    /// Sends a RDR_to_PC_NotifySlotChange on the interrupt pipe. Losing one is harmless, as the host
+    /// polls slot status anyway.
+    pub fn notify(&mut self, notification: &[u8]) { self.interrupt_in.write(notification).ok(); }
+
+    /// Pushes the next packet of the current message, moving on to the next queued message once done.
+    /// A message that is an exact multiple of the packet size is terminated with a zero-length packet.
+    fn flush(&mut self) {
+        if self.tx.is_none() {
+            self.tx = self.tx_queue.pop_front().map(|msg| (msg, 0));
+        }
+        let (msg, sent) = match self.tx.as_mut() {
+            Some(tx) => tx,
+            None => return,
+        };
+        if *sent > msg.len() {
+            // the terminating packet went out last time
+            self.tx = None;
+            self.flush();
+            return;
+        }
+        let end = (*sent + PACKET_SIZE as usize).min(msg.len());
+        match self.bulk_in.write(&msg[*sent..end]) {
+            Ok(_) => {
+                // a short packet ends the transfer; a full one needs a follow-up, even if empty
+                *sent = if end - *sent < PACKET_SIZE as usize { msg.len() + 1 } else { end };
+            }
+            Err(UsbError::WouldBlock) => (),
+            Err(e) => log::warn!("CCID write error: {:?}", e),
+        }
+    }
+}
+
+impl<B: UsbBus> UsbClass<B> for CcidClass<'_, B> {
+    fn get_configuration_descriptors(&self, writer: &mut DescriptorWriter) -> usb_device::Result<()> {
+        writer.interface(self.interface, USB_CLASS_CCID, 0, 0)?;
+        let features = FEATURE_AUTO_CONFIG_ATR
+            | FEATURE_AUTO_ACTIVATION
+            | FEATURE_AUTO_VOLTAGE
+            | FEATURE_AUTO_CLOCK
+            | FEATURE_AUTO_BAUD
+            | FEATURE_AUTO_PPS_NEGOTIATION
+            | FEATURE_AUTO_PPS_CURRENT
+            | FEATURE_SHORT_APDU;
+        let mut desc = Vec::<u8>::new();
+        desc.extend_from_slice(&0x0110u16.to_le_bytes()); // bcdCCID 1.10
+        desc.push(0); // bMaxSlotIndex
+        desc.push(0x07); // bVoltageSupport: 5V, 3V, 1.8V
+        desc.extend_from_slice(&2u32.to_le_bytes()); // dwProtocols: T=1
+        desc.extend_from_slice(&4000u32.to_le_bytes()); // dwDefaultClock, kHz
+        desc.extend_from_slice(&4000u32.to_le_bytes()); // dwMaximumClock
+        desc.push(0); // bNumClockSupported
+        desc.extend_from_slice(&10752u32.to_le_bytes()); // dwDataRate, bps
+        desc.extend_from_slice(&10752u32.to_le_bytes()); // dwMaxDataRate
+        desc.push(0); // bNumDataRatesSupported
+        desc.extend_from_slice(&254u32.to_le_bytes()); // dwMaxIFSD
+        desc.extend_from_slice(&0u32.to_le_bytes()); // dwSynchProtocols
+        desc.extend_from_slice(&0u32.to_le_bytes()); // dwMechanical
+        desc.extend_from_slice(&features.to_le_bytes()); // dwFeatures
+        desc.extend_from_slice(&(CCID_MAX_MSG_LEN as u32).to_le_bytes()); // dwMaxCCIDMessageLength
+        desc.push(0xFF); // bClassGetResponse: echo the APDU's class
+        desc.push(0xFF); // bClassEnvelope
+        desc.extend_from_slice(&0u16.to_le_bytes()); // wLcdLayout: the PIN prompt is on our own screen
+        desc.push(0x03); // bPINSupport: verification and modification
+        desc.push(1); // bMaxCCIDBusySlots
+        writer.write(CCID_FUNCTIONAL_DESCRIPTOR, &desc)?;
+        writer.endpoint(&self.bulk_out)?;
+        writer.endpoint(&self.bulk_in)?;
+        writer.endpoint(&self.interrupt_in)?;
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.rx.clear();
+        self.tx_queue.clear();
+        self.tx = None;
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        let req = xfer.request();
+        if req.request_type == RequestType::Class
+            && req.recipient == Recipient::Interface
+            && req.index == u8::from(self.interface) as u16
+        {
+            match req.request {
+                // the matching PC_to_RDR_Abort clears the slot
+                REQUEST_ABORT => xfer.accept().ok(),
+                _ => xfer.reject().ok(),
+            };
+        }
+    }
+
+    fn endpoint_in_complete(&mut self, addr: EndpointAddress) {
+        if addr == self.bulk_in.address() {
+            self.flush();
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Bookkeeping for the composite device, shared by the hardware and hosted backends.
+//!
+//! Processes claim the functions they drive, and the composite device offers the union of the claims.
+//! A function has one owner at a time, so that e.g. two password managers can't both type on the
+//! keyboard; the hardware side (`composite_device`) builds and selects the matching descriptors.
+
+use crate::api::UsbFunctions;
+
+#[derive(Default)]
+pub(crate) struct Registrations {
+    claims: Vec<(xous::PID, UsbFunctions)>,
+}
+
+impl Registrations {
+    /// Replaces what `pid` claimed with `functions`, which may be `NONE` to let go of everything. Nothing
+    /// changes if another process holds one of them.
+    pub(crate) fn register(&mut self, pid: xous::PID, functions: UsbFunctions) -> Result<(), UsbFunctions> {
+        let taken = self
+            .claims
+            .iter()
+            .filter(|(owner, claim)| *owner != pid && claim.intersects(functions))
+            .fold(UsbFunctions::NONE, |taken, (_, claim)| taken | *claim);
+        if !taken.is_empty() {
+            return Err(UsbFunctions(taken.0 & functions.0));
+        }
+        self.claims.retain(|(owner, _)| *owner != pid);
+        if !functions.is_empty() {
+            self.claims.push((pid, functions));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn functions(&self) -> UsbFunctions {
+        self.claims.iter().fold(UsbFunctions::NONE, |all, (_, claim)| all | *claim)
+    }
+
+    /// Whether `pid` is the one to talk to the host through `function`.
+    pub(crate) fn owns(&self, pid: Option<xous::PID>, function: UsbFunctions) -> bool {
+        self.claims.iter().any(|(owner, claim)| Some(*owner) == pid && claim.contains(function))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registrations() {
+        let vault = xous::PID::new(5).unwrap();
+        let shell = xous::PID::new(6).unwrap();
+        let mut regs = Registrations::default();
+        assert!(regs.functions().is_empty());
+
+        regs.register(vault, UsbFunctions::FIDO | UsbFunctions::KEYBOARD).unwrap();
+        regs.register(shell, UsbFunctions::SERIAL).unwrap();
+        assert_eq!(regs.functions(), UsbFunctions::FIDO | UsbFunctions::KEYBOARD | UsbFunctions::SERIAL);
+
+        // one owner per function, and a refused claim leaves the old one in place
+        assert_eq!(
+            regs.register(shell, UsbFunctions::SERIAL | UsbFunctions::KEYBOARD),
+            Err(UsbFunctions::KEYBOARD)
+        );
+        assert!(regs.functions().contains(UsbFunctions::SERIAL));
+        assert!(regs.owns(Some(vault), UsbFunctions::KEYBOARD));
+        assert!(!regs.owns(Some(shell), UsbFunctions::KEYBOARD));
+        assert!(!regs.owns(None, UsbFunctions::SERIAL));
+
+        // a new claim replaces the old one
+        regs.register(vault, UsbFunctions::FIDO).unwrap();
+        assert_eq!(regs.functions(), UsbFunctions::FIDO | UsbFunctions::SERIAL);
+        regs.register(shell, UsbFunctions::SERIAL | UsbFunctions::KEYBOARD).unwrap();
+
+        regs.register(vault, UsbFunctions::NONE).unwrap();
+        regs.register(shell, UsbFunctions::NONE).unwrap();
+        assert!(regs.functions().is_empty());
+        assert!(regs.claims.is_empty());
+    }
+}
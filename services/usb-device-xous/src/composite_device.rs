@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use frunk_core::hlist::{HCons, HNil};
+use usb_device::class_prelude::*;
+use usb_device::prelude::*;
+use usbd_serial::SerialPort;
+use xous_usb_hid::device::fido::{RawFido, RawFidoConfig};
+use xous_usb_hid::device::keyboard::{NKROBootKeyboard, NKROBootKeyboardConfig};
+use xous_usb_hid::prelude::*;
+use xous_usb_hid::usb_class::UsbHidClass;
+
+use crate::SpinalUsbDevice;
+use crate::api::UsbFunctions;
+#[cfg(feature = "mass-storage")]
+use crate::apps_block_device::AppsBlockDevice;
+
+/// Miscellaneous device class, protocol "interface association": the functions are described by the
+/// IADs in the configuration, which is what lets Windows bind the serial port inside a composite.
+const USB_CLASS_MISC: u8 = 0xEF;
+const MISC_SUBCLASS_COMMON: u8 = 0x02;
+const MISC_PROTOCOL_IAD: u8 = 0x01;
+
+/// One build of the composite device, offering some set of functions.
+pub(crate) struct CompositeView<'a> {
+    pub device: UsbDevice<'a, SpinalUsbDevice>,
+    pub keyboard:
+        Option<UsbHidClass<'a, SpinalUsbDevice, HCons<NKROBootKeyboard<'a, SpinalUsbDevice>, HNil>>>,
+    pub fido: Option<UsbHidClass<'a, SpinalUsbDevice, HCons<RawFido<'a, SpinalUsbDevice>, HNil>>>,
+    pub serial: Option<SerialPort<'a, SpinalUsbDevice>>,
+    #[cfg(feature = "mass-storage")]
+    pub ums: Option<usbd_scsi::Scsi<'a, SpinalUsbDevice, AppsBlockDevice>>,
+}
+
+impl<'a> CompositeView<'a> {
+    pub fn poll(&mut self) -> bool {
+        let mut classes: Vec<&mut dyn UsbClass<SpinalUsbDevice>> = Vec::new();
+        if let Some(keyboard) = self.keyboard.as_mut() {
+            classes.push(keyboard);
+        }
+        if let Some(fido) = self.fido.as_mut() {
+            classes.push(fido);
+        }
+        if let Some(serial) = self.serial.as_mut() {
+            classes.push(serial);
+        }
+        #[cfg(feature = "mass-storage")]
+        if let Some(ums) = self.ums.as_mut() {
+            classes.push(ums);
+        }
+        self.device.poll(&mut classes)
+    }
+
+    pub fn keyboard(&mut self) -> Option<&mut NKROBootKeyboard<'a, SpinalUsbDevice>> {
+        self.keyboard.as_mut().map(|class| class.device::<NKROBootKeyboard<'a, _>, _>())
+    }
+
+    pub fn fido(&mut self) -> Option<&mut RawFido<'a, SpinalUsbDevice>> {
+        self.fido.as_mut().map(|class| class.device::<RawFido<'a, _>, _>())
+    }
+}
+
+/// The composite device, in every combination of functions asked for so far. Descriptors are fixed once
+/// a `UsbDevice` is built, so each combination gets a build of its own, selected the same way the
+/// single-function views are.
+pub(crate) struct CompositeViews<'a> {
+    sid: xous::SID,
+    usb: xous::MemoryRange,
+    csr: xous::MemoryRange,
+    serial_number: &'a str,
+    #[cfg(feature = "mass-storage")]
+    block_device: AppsBlockDevice,
+    views: HashMap<u32, CompositeView<'a>>,
+    active: UsbFunctions,
+}
+
+impl<'a> CompositeViews<'a> {
+    pub fn new(
+        sid: xous::SID,
+        usb: xous::MemoryRange,
+        csr: xous::MemoryRange,
+        serial_number: &'a str,
+        #[cfg(feature = "mass-storage")] block_device: AppsBlockDevice,
+    ) -> Self {
+        CompositeViews {
+            sid,
+            usb,
+            csr,
+            serial_number,
+            #[cfg(feature = "mass-storage")]
+            block_device,
+            views: HashMap::new(),
+            active: UsbFunctions::NONE,
+        }
+    }
+
+    /// The functions of the selected build.
+    pub fn active(&self) -> UsbFunctions { self.active }
+
+    /// Selects the build offering `functions`, making it on first use. Returns true if that's a
+    /// different set than before, in which case the host has to enumerate the device again.
+    pub fn select(&mut self, functions: UsbFunctions) -> bool {
+        if !functions.is_empty() && !self.views.contains_key(&functions.0) {
+            let view = self.build(functions);
+            self.views.insert(functions.0, view);
+        }
+        std::mem::replace(&mut self.active, functions) != functions
+    }
+
+    pub fn view(&mut self) -> Option<&mut CompositeView<'a>> { self.views.get_mut(&self.active.0) }
+
+    pub fn state(&mut self) -> UsbDeviceState {
+        self.view().map(|view| view.device.state()).unwrap_or(UsbDeviceState::Default)
+    }
+
+    pub fn keyboard(&mut self) -> Option<&mut NKROBootKeyboard<'a, SpinalUsbDevice>> {
+        self.view().and_then(|view| view.keyboard())
+    }
+
+    pub fn fido(&mut self) -> Option<&mut RawFido<'a, SpinalUsbDevice>> {
+        self.view().and_then(|view| view.fido())
+    }
+
+    pub fn serial(&mut self) -> Option<&mut SerialPort<'a, SpinalUsbDevice>> {
+        self.view().and_then(|view| view.serial.as_mut())
+    }
+
+    fn build(&self, functions: UsbFunctions) -> CompositeView<'a> {
+        let dev = SpinalUsbDevice::new(self.sid, self.usb.clone(), self.csr.clone());
+        dev.init();
+        // Builds live as long as the server does, like the allocators of the other views. There are only
+        // so many combinations of functions, and each is made at most once.
+        let alloc: &'a UsbBusAllocator<SpinalUsbDevice> = Box::leak(Box::new(UsbBusAllocator::new(dev)));
+
+        // the keyboard goes first, as some BIOSes only look at the first interface for a boot keyboard
+        let keyboard = if functions.contains(UsbFunctions::KEYBOARD) {
+            Some(UsbHidClassBuilder::new().add_device(NKROBootKeyboardConfig::default()).build(alloc))
+        } else {
+            None
+        };
+        let fido = if functions.contains(UsbFunctions::FIDO) {
+            Some(UsbHidClassBuilder::new().add_device(RawFidoConfig::default()).build(alloc))
+        } else {
+            None
+        };
+        let serial =
+            if functions.contains(UsbFunctions::SERIAL) { Some(SerialPort::new(alloc)) } else { None };
+        #[cfg(feature = "mass-storage")]
+        let ums = if functions.contains(UsbFunctions::MASS_STORAGE) {
+            Some(usbd_scsi::Scsi::new(
+                alloc,
+                64,
+                self.block_device.clone(),
+                "Kosagi".as_bytes(),
+                "Kosagi Precursor".as_bytes(),
+                "1".as_bytes(),
+            ))
+        } else {
+            None
+        };
+
+        let device = UsbDeviceBuilder::new(alloc, UsbVidPid(0x1209, 0x3613))
+            .manufacturer("Kosagi")
+            .product("Precursor")
+            .serial_number(self.serial_number)
+            .device_class(USB_CLASS_MISC)
+            .device_sub_class(MISC_SUBCLASS_COMMON)
+            .device_protocol(MISC_PROTOCOL_IAD)
+            .self_powered(false)
+            .max_power(500)
+            .build();
+        log::info!("built composite device with {:?}", functions);
+        CompositeView {
+            device,
+            keyboard,
+            fido,
+            serial,
+            #[cfg(feature = "mass-storage")]
+            ums,
+        }
+    }
+}
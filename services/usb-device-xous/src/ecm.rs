@@ -0,0 +1,210 @@
+//! Network link logic, shared by the hardware and hosted backends.
+//!
+//! On hardware the link is a CDC-ECM function (`ecm_class`): the host sees an Ethernet adapter, and each
+//! Ethernet frame crosses a bulk pipe as-is, ended by a short packet. This module puts frames back
+//! together and queues them, along with link changes, for the process that owns the link (the net
+//! service), which picks them up through `net_wait_event()`.
+
+use std::collections::VecDeque;
+
+use xous_ipc::Buffer;
+
+use crate::api::{NET_FRAME_BUFLEN, NetCode, NetMsgIpc};
+
+/// Bulk packet size at full speed
+pub(crate) const PACKET_SIZE: usize = 64;
+const ETHERNET_HEADER_LEN: usize = 14;
+/// Frames the owner hasn't picked up yet. As on any NIC, what doesn't fit is dropped and left to the
+/// protocols above to recover.
+const RX_QUEUE_DEPTH: usize = 16;
+
+/// Gathers bulk OUT packets until a short one ends the frame.
+#[derive(Default)]
+pub(crate) struct FrameAssembler {
+    buf: Vec<u8>,
+    /// the frame outgrew `NET_FRAME_BUFLEN`; the rest of it is skipped
+    overrun: bool,
+}
+
+impl FrameAssembler {
+    /// Takes one packet, and returns the frame it completes, if any. Runts and oversized frames are
+    /// dropped.
+    pub(crate) fn push(&mut self, packet: &[u8]) -> Option<Vec<u8>> {
+        if !self.overrun {
+            if self.buf.len() + packet.len() > NET_FRAME_BUFLEN {
+                self.overrun = true;
+                self.buf.clear();
+            } else {
+                self.buf.extend_from_slice(packet);
+            }
+        }
+        if packet.len() == PACKET_SIZE {
+            return None;
+        }
+        let frame = std::mem::take(&mut self.buf);
+        if std::mem::replace(&mut self.overrun, false) || frame.len() < ETHERNET_HEADER_LEN {
+            log::debug!("dropping malformed frame from the host");
+            None
+        } else {
+            Some(frame)
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.buf.clear();
+        self.overrun = false;
+    }
+}
+
+/// The host's end of the link needs a MAC address of its own, reported in the ECM descriptors. It is
+/// derived from the serial number so that a host keeps recognising the same device, and marked locally
+/// administered.
+pub(crate) fn host_mac(serial_number: &str) -> [u8; 6] {
+    // FNV-1a: this only has to spread the serial number out, not be hard to invert
+    let hash = serial_number
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3));
+    let bytes = hash.to_be_bytes();
+    [0x02, bytes[0], bytes[1], bytes[2], bytes[3], bytes[4]]
+}
+
+#[derive(Default)]
+pub(crate) struct Link {
+    // under the theory that PIDs cannot be forged, as with the U2F listener
+    pid: Option<xous::PID>,
+    waiting: Option<xous::MessageEnvelope>,
+    queue: VecDeque<NetMsgIpc>,
+    up: bool,
+}
+
+impl Link {
+    /// The link is locked to the first process that touches it.
+    pub(crate) fn is_owner(&mut self, sender: xous::MessageSender) -> bool {
+        if self.pid.is_none() {
+            self.pid = sender.pid();
+        }
+        self.pid == sender.pid()
+    }
+
+    pub(crate) fn is_up(&self) -> bool { self.up }
+
+    pub(crate) fn wait(&mut self, mut msg: xous::MessageEnvelope) {
+        if !self.is_owner(msg.sender) {
+            log::warn!(
+                "network link is locked on first use; additional servers are ignored: {:?}",
+                msg.sender
+            );
+            respond(&mut msg, NetMsgIpc::new(NetCode::Denied, &[]));
+            return;
+        }
+        if self.waiting.is_some() {
+            log::error!(
+                "Double-listener request detected for the network link; the earlier request is dropped."
+            );
+        }
+        match self.queue.pop_front() {
+            Some(event) => respond(&mut msg, event),
+            None => self.waiting = Some(msg),
+        }
+    }
+
+    /// Passes a frame from the host on to the owner, or drops it if too many are already waiting.
+    pub(crate) fn deliver_frame(&mut self, frame: &[u8]) {
+        if self.waiting.is_none()
+            && self.queue.iter().filter(|ev| ev.code == NetCode::Frame).count() >= RX_QUEUE_DEPTH
+        {
+            log::debug!("network rx queue full, dropping a frame");
+            return;
+        }
+        self.deliver(NetMsgIpc::new(NetCode::Frame, frame));
+    }
+
+    /// Tracks the state of the link, telling the owner when it changes. Frames still queued when the link
+    /// goes down are stale by the time it comes back, so they are dropped.
+    pub(crate) fn set_up(&mut self, up: bool) {
+        if up == self.up {
+            return;
+        }
+        self.up = up;
+        if up {
+            log::info!("network link up");
+            self.deliver(NetMsgIpc::new(NetCode::LinkUp, &[]));
+        } else {
+            log::info!("network link down");
+            self.queue.retain(|ev| ev.code != NetCode::Frame);
+            self.deliver(NetMsgIpc::new(NetCode::LinkDown, &[]));
+        }
+    }
+
+    fn deliver(&mut self, event: NetMsgIpc) {
+        match self.waiting.take() {
+            Some(mut msg) => respond(&mut msg, event),
+            None => self.queue.push_back(event),
+        }
+    }
+}
+
+/// Replaces the contents of a lent `NetMsgIpc`; the envelope replies to the caller once dropped.
+pub(crate) fn respond(msg: &mut xous::MessageEnvelope, reply: NetMsgIpc) {
+    let mut buffer = unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+    buffer.replace(reply).unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_assembler() {
+        let mut asm = FrameAssembler::default();
+        let frame: Vec<u8> = (0..100u8).collect();
+        assert!(asm.push(&frame[..64]).is_none());
+        assert_eq!(asm.push(&frame[64..]).unwrap(), frame);
+
+        // a frame that fills whole packets is ended by a zero-length packet
+        let frame = vec![0xA5u8; 128];
+        assert!(asm.push(&frame[..64]).is_none());
+        assert!(asm.push(&frame[64..]).is_none());
+        assert_eq!(asm.push(&[]).unwrap(), frame);
+        assert!(asm.push(&[]).is_none());
+
+        // too short to be Ethernet
+        assert!(asm.push(&[0u8; 10]).is_none());
+
+        // an oversized frame is skipped up to its end, and the next one comes through
+        let packet = [0u8; PACKET_SIZE];
+        for _ in 0..(NET_FRAME_BUFLEN / PACKET_SIZE + 2) {
+            assert!(asm.push(&packet).is_none());
+        }
+        assert!(asm.push(&[1u8; 20]).is_none());
+        assert_eq!(asm.push(&[2u8; 20]).unwrap(), vec![2u8; 20]);
+    }
+
+    #[test]
+    fn test_host_mac() {
+        let mac = host_mac("8a7c6f51e00d1c2");
+        assert_eq!(mac[0], 0x02);
+        assert_eq!(mac, host_mac("8a7c6f51e00d1c2"));
+        assert_ne!(mac, host_mac("8a7c6f51e00d1c3"));
+    }
+
+    #[test]
+    fn test_link_events() {
+        let mut link = Link::default();
+        link.set_up(false);
+        assert!(link.queue.is_empty());
+        link.set_up(true);
+        link.set_up(true);
+        assert!(link.is_up());
+        for i in 0..(RX_QUEUE_DEPTH + 4) {
+            link.deliver_frame(&[i as u8; 60]);
+        }
+        assert_eq!(link.queue.len(), 1 + RX_QUEUE_DEPTH);
+        assert_eq!(link.queue[0].code, NetCode::LinkUp);
+        assert_eq!(&link.queue[1].data[..link.queue[1].len], &[0u8; 60]);
+
+        link.set_up(false);
+        let codes: Vec<NetCode> = link.queue.iter().map(|ev| ev.code).collect();
+        assert_eq!(codes, vec![NetCode::LinkUp, NetCode::LinkDown]);
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::VecDeque;
+
+use usb_device::class_prelude::*;
+use usb_device::control::{Recipient, RequestType};
+
+use crate::api::NET_FRAME_BUFLEN;
+use crate::ecm::{FrameAssembler, PACKET_SIZE};
+
+pub(crate) const USB_CLASS_CDC: u8 = 0x02;
+const USB_CLASS_CDC_DATA: u8 = 0x0A;
+const CDC_SUBCLASS_ECM: u8 = 0x06;
+const CS_INTERFACE: u8 = 0x24;
+const CDC_TYPE_HEADER: u8 = 0x00;
+const CDC_TYPE_UNION: u8 = 0x06;
+const CDC_TYPE_ETHERNET: u8 = 0x0F;
+const REQUEST_SET_ETHERNET_PACKET_FILTER: u8 = 0x43;
+const NOTIFY_NETWORK_CONNECTION: u8 = 0x00;
+const NOTIFY_CONNECTION_SPEED_CHANGE: u8 = 0x2A;
+/// What a full speed bus can carry at best, reported to the host as the link speed
+const LINK_SPEED_BPS: u32 = 12_000_000;
+/// Frames waiting for the bulk IN pipe. Past this the host isn't reading, and frames are dropped.
+const TX_QUEUE_DEPTH: usize = 16;
+
+/// A CDC-ECM Ethernet adapter. The data interface only has endpoints in its alternate setting 1, and
+/// selecting that setting is how the host brings the link up. This only moves frames on and off the wire;
+/// queueing for the net service is in `ecm::Link`.
+pub struct EcmClass<'a, B: UsbBus> {
+    comm_if: InterfaceNumber,
+    data_if: InterfaceNumber,
+    interrupt_in: EndpointIn<'a, B>,
+    bulk_out: EndpointOut<'a, B>,
+    bulk_in: EndpointIn<'a, B>,
+    mac_string: StringIndex,
+    /// the host's MAC address as 12 hex digits, which is how ECM reports it
+    host_mac: String,
+    data_alt: u8,
+    rx: FrameAssembler,
+    rx_frames: VecDeque<Vec<u8>>,
+    tx_queue: VecDeque<Vec<u8>>,
+    /// frame being sent, and how much of it has gone out
+    tx: Option<(Vec<u8>, usize)>,
+    notifications: VecDeque<Vec<u8>>,
+    notifying: bool,
+}
+
+impl<'a, B: UsbBus> EcmClass<'a, B> {
+    pub fn new(alloc: &'a UsbBusAllocator<B>, host_mac: [u8; 6]) -> Self {
+        EcmClass {
+            comm_if: alloc.interface(),
+            data_if: alloc.interface(),
+            interrupt_in: alloc.interrupt(16, 32),
+            bulk_out: alloc.bulk(PACKET_SIZE as u16),
+            bulk_in: alloc.bulk(PACKET_SIZE as u16),
+            mac_string: alloc.string(),
+            host_mac: host_mac.iter().map(|b| format!("{:02X}", b)).collect(),
+            data_alt: 0,
+            rx: FrameAssembler::default(),
+            rx_frames: VecDeque::new(),
+            tx_queue: VecDeque::new(),
+            tx: None,
+            notifications: VecDeque::new(),
+            notifying: false,
+        }
+    }
+
+    /// True once the host has selected the data interface's active setting.
+    pub fn is_connected(&self) -> bool { self.data_alt == 1 }
+
+    /// Returns the next frame the host sent, if any.
+    pub fn read_frame(&mut self) -> Option<Vec<u8>> { self.rx_frames.pop_front() }
+
+    /// Queues a frame for the host.
+    pub fn write_frame(&mut self, frame: &[u8]) {
+        if !self.is_connected() || frame.len() > NET_FRAME_BUFLEN {
+            return;
+        }
+        if self.tx_queue.len() >= TX_QUEUE_DEPTH {
+            log::debug!("network tx queue full, dropping a frame");
+            return;
+        }
+        self.tx_queue.push_back(frame.to_vec());
+        if self.tx.is_none() {
+            self.flush();
+        }
+    }
+
+    /// Pushes the next packet of the current frame, moving on to the next queued frame once done. A frame
+    /// that is an exact multiple of the packet size is terminated with a zero-length packet.
+    fn flush(&mut self) {
+        if self.tx.is_none() {
+            self.tx = self.tx_queue.pop_front().map(|frame| (frame, 0));
+        }
+        let (frame, sent) = match self.tx.as_mut() {
+            Some(tx) => tx,
+            None => return,
+        };
+        if *sent > frame.len() {
+            // the terminating packet went out last time
+            self.tx = None;
+            self.flush();
+            return;
+        }
+        let end = (*sent + PACKET_SIZE).min(frame.len());
+        match self.bulk_in.write(&frame[*sent..end]) {
+            Ok(_) => {
+                // a short packet ends the transfer; a full one needs a follow-up, even if empty
+                *sent = if end - *sent < PACKET_SIZE { frame.len() + 1 } else { end };
+            }
+            Err(UsbError::WouldBlock) => (),
+            Err(e) => log::warn!("ECM write error: {:?}", e),
+        }
+    }
+
+    /// Tells the host whether the cable is "plugged in", followed by the link speed when it is.
+    fn notify_connection(&mut self, connected: bool) {
+        let comm_if = u8::from(self.comm_if) as u16;
+        let mut connection = vec![0xA1, NOTIFY_NETWORK_CONNECTION];
+        connection.extend_from_slice(&(connected as u16).to_le_bytes());
+        connection.extend_from_slice(&comm_if.to_le_bytes());
+        connection.extend_from_slice(&0u16.to_le_bytes());
+        self.notifications.push_back(connection);
+        if connected {
+            let mut speed = vec![0xA1, NOTIFY_CONNECTION_SPEED_CHANGE, 0, 0];
+            speed.extend_from_slice(&comm_if.to_le_bytes());
+            speed.extend_from_slice(&8u16.to_le_bytes());
+            speed.extend_from_slice(&LINK_SPEED_BPS.to_le_bytes()); // downstream
+            speed.extend_from_slice(&LINK_SPEED_BPS.to_le_bytes()); // upstream
+            self.notifications.push_back(speed);
+        }
+        if !self.notifying {
+            self.next_notification();
+        }
+    }
+
+    fn next_notification(&mut self) {
+        self.notifying = false;
+        if let Some(notification) = self.notifications.front() {
+            match self.interrupt_in.write(notification) {
+                Ok(_) => {
+                    self.notifications.pop_front();
+                    self.notifying = true;
+                }
+                Err(UsbError::WouldBlock) => (),
+                Err(e) => log::warn!("ECM notification error: {:?}", e),
+            }
+        }
+    }
+}
+
+impl<B: UsbBus> UsbClass<B> for EcmClass<'_, B> {
+    fn get_configuration_descriptors(&self, writer: &mut DescriptorWriter) -> usb_device::Result<()> {
+        writer.interface(self.comm_if, USB_CLASS_CDC, CDC_SUBCLASS_ECM, 0)?;
+        writer.write(CS_INTERFACE, &[CDC_TYPE_HEADER, 0x10, 0x01])?; // bcdCDC 1.10
+        writer.write(CS_INTERFACE, &[CDC_TYPE_UNION, self.comm_if.into(), self.data_if.into()])?;
+        let mut ethernet = vec![CDC_TYPE_ETHERNET, self.mac_string.into()];
+        ethernet.extend_from_slice(&0u32.to_le_bytes()); // bmEthernetStatistics: none kept
+        ethernet.extend_from_slice(&(NET_FRAME_BUFLEN as u16).to_le_bytes()); // wMaxSegmentSize
+        ethernet.extend_from_slice(&0u16.to_le_bytes()); // wNumberMCFilters: multicast is not filtered
+        ethernet.push(0); // bNumberPowerFilters
+        writer.write(CS_INTERFACE, &ethernet)?;
+        writer.endpoint(&self.interrupt_in)?;
+        // no endpoints until the host turns the link on
+        writer.interface_alt(self.data_if, 0, USB_CLASS_CDC_DATA, 0, 0, None)?;
+        writer.interface_alt(self.data_if, 1, USB_CLASS_CDC_DATA, 0, 0, None)?;
+        writer.endpoint(&self.bulk_out)?;
+        writer.endpoint(&self.bulk_in)?;
+        Ok(())
+    }
+
+    fn get_string(&self, index: StringIndex, _lang_id: u16) -> Option<&str> {
+        if index == self.mac_string { Some(&self.host_mac) } else { None }
+    }
+
+    fn get_alt_setting(&mut self, interface: InterfaceNumber) -> Option<u8> {
+        if interface == self.data_if { Some(self.data_alt) } else { None }
+    }
+
+    fn set_alt_setting(&mut self, interface: InterfaceNumber, alternative: u8) -> bool {
+        if interface != self.data_if || alternative > 1 {
+            return false;
+        }
+        if alternative != self.data_alt {
+            self.data_alt = alternative;
+            self.rx.clear();
+            self.tx_queue.clear();
+            self.tx = None;
+            self.notify_connection(self.is_connected());
+        }
+        true
+    }
+
+    fn reset(&mut self) {
+        self.data_alt = 0;
+        self.rx.clear();
+        self.rx_frames.clear();
+        self.tx_queue.clear();
+        self.tx = None;
+        self.notifications.clear();
+        self.notifying = false;
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        let req = xfer.request();
+        if req.request_type == RequestType::Class
+            && req.recipient == Recipient::Interface
+            && req.index == u8::from(self.comm_if) as u16
+        {
+            match req.request {
+                // everything is passed up regardless; smoltcp sorts out what's for us
+                REQUEST_SET_ETHERNET_PACKET_FILTER => xfer.accept().ok(),
+                _ => xfer.reject().ok(),
+            };
+        }
+    }
+
+    fn endpoint_out(&mut self, addr: EndpointAddress) {
+        if addr != self.bulk_out.address() {
+            return;
+        }
+        let mut packet = [0u8; PACKET_SIZE];
+        loop {
+            match self.bulk_out.read(&mut packet) {
+                Ok(len) => {
+                    if let Some(frame) = self.rx.push(&packet[..len]) {
+                        self.rx_frames.push_back(frame);
+                    }
+                }
+                Err(UsbError::WouldBlock) => break,
+                Err(e) => {
+                    log::warn!("ECM read error: {:?}", e);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn endpoint_in_complete(&mut self, addr: EndpointAddress) {
+        if addr == self.bulk_in.address() {
+            self.flush();
+        } else if addr == self.interrupt_in.address() {
+            self.next_notification();
+        }
+    }
+}
@@ -121,6 +121,10 @@ impl UsbHid {
                 #[cfg(feature = "mass-storage")]
                 3 => Ok(UsbDeviceType::MassStorage),
                 4 => Ok(UsbDeviceType::Serial),
+                5 => Ok(UsbDeviceType::HIDv2),
+                6 => Ok(UsbDeviceType::Ccid),
+                7 => Ok(UsbDeviceType::Net),
+                8 => Ok(UsbDeviceType::Composite),
                 _ => Err(xous::Error::InternalError),
             },
             _ => panic!("Internal error: illegal return type"),
@@ -339,6 +343,15 @@ impl UsbHid {
             .map(|_| ())
     }
 
+    /// Writes `s` to the host over serial, blocking until it has been handed to the USB stack. This goes
+    /// through the logger's path, so it is only delivered while the serial core is active, and is
+    /// silently dropped otherwise.
+    pub fn serial_send(&self, s: &str) -> Result<(), xous::Error> {
+        let serializer = UsbString { s: String::from(s), sent: None };
+        let buf = Buffer::into_buf(serializer).or(Err(xous::Error::InternalError))?;
+        buf.lend(self.conn, Opcode::LogString.to_u32().unwrap()).map(|_| ())
+    }
+
     /// Inject serial input over USB to the debug console. Dangerous!
     /// This will also override/discard any existing hooked listeners.
     pub fn serial_console_input_injection(&self) {
@@ -445,6 +458,148 @@ impl UsbHid {
         Ok(())
     }
 
+    /// Claims `functions` of the composite device for this process, replacing whatever it claimed
+    /// before. Fails with `AccessDenied` if another process already holds one of them. The device offers
+    /// what every process has claimed; if it is up when that changes, it re-enumerates with the new set.
+    /// With nothing claimed it falls back to the FIDO + keyboard core. Select it with
+    /// `ensure_core(UsbDeviceType::Composite)`; while it is up, `ensure_core()` for a core whose functions
+    /// it already offers leaves it alone.
+    pub fn register_functions(&self, functions: UsbFunctions) -> Result<(), xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(
+                Opcode::CompositeRegister.to_usize().unwrap(),
+                functions.0 as usize,
+                0,
+                0,
+                0,
+            ),
+        ) {
+            Ok(xous::Result::Scalar1(code)) => match code {
+                0 => Ok(()),
+                _ => Err(xous::Error::AccessDenied),
+            },
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Gives up every function of the composite device this process claimed.
+    pub fn unregister_functions(&self) -> Result<(), xous::Error> {
+        self.register_functions(UsbFunctions::NONE)
+    }
+
+    /// The functions the composite device offers, as claimed by all processes together.
+    pub fn composite_functions(&self) -> Result<UsbFunctions, xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::CompositeFunctions.to_usize().unwrap(), 0, 0, 0, 0),
+        ) {
+            Ok(xous::Result::Scalar1(functions)) => Ok(UsbFunctions(functions as u32)),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Puts a virtual smart card answering with `atr` into the CCID reader slot. Like the U2F interface,
+    /// the slot is locked to the first process that uses it.
+    pub fn ccid_insert_card(&self, atr: &[u8]) -> Result<(), xous::Error> {
+        if atr.len() > CCID_APDU_BUFLEN {
+            return Err(xous::Error::OutOfMemory);
+        }
+        let req = CcidMsgIpc::new(CcidCode::Insert, atr);
+        let mut buf = Buffer::into_buf(req).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::CcidInsert.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        let ack = buf.to_original::<CcidMsgIpc, _>().unwrap();
+        match ack.code {
+            CcidCode::TxAck => Ok(()),
+            CcidCode::Denied => Err(xous::Error::AccessDenied),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Takes the virtual card out of the CCID reader slot. A command still waiting on the card is failed
+    /// back to the host.
+    pub fn ccid_remove_card(&self) -> Result<(), xous::Error> {
+        match send_message(
+            self.conn,
+            Message::new_blocking_scalar(Opcode::CcidRemove.to_usize().unwrap(), 0, 0, 0, 0),
+        ) {
+            Ok(xous::Result::Scalar1(code)) => match code {
+                0 => Ok(()),
+                _ => Err(xous::Error::AccessDenied),
+            },
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Blocks until the host has something for the card in the CCID slot. Every `Apdu`, `VerifyPin` and
+    /// `ModifyPin` event must be answered with `ccid_respond()` before the next command arrives.
+    pub fn ccid_wait_event(&self) -> Result<CcidEvent, xous::Error> {
+        let req = CcidMsgIpc::new(CcidCode::RxWait, &[]);
+        let mut buf = Buffer::into_buf(req).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::CcidRxDeferred.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        let ev = buf.to_original::<CcidMsgIpc, _>().unwrap();
+        match ev.code {
+            CcidCode::Apdu => Ok(CcidEvent::Apdu(ev.data[..ev.len].to_vec())),
+            CcidCode::VerifyPin => Ok(CcidEvent::VerifyPin([ev.data[0], ev.data[1], ev.data[2], ev.data[3]])),
+            CcidCode::ModifyPin => Ok(CcidEvent::ModifyPin([ev.data[0], ev.data[1], ev.data[2], ev.data[3]])),
+            CcidCode::Reset => Ok(CcidEvent::Reset),
+            CcidCode::Denied => Err(xous::Error::AccessDenied),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Answers the command most recently returned by `ccid_wait_event()` with a response APDU (data
+    /// followed by SW1 SW2).
+    pub fn ccid_respond(&self, response: &[u8]) -> Result<(), xous::Error> {
+        if response.len() > CCID_APDU_BUFLEN {
+            return Err(xous::Error::OutOfMemory);
+        }
+        let req = CcidMsgIpc::new(CcidCode::Tx, response);
+        let mut buf = Buffer::into_buf(req).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::CcidTx.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        let ack = buf.to_original::<CcidMsgIpc, _>().unwrap();
+        match ack.code {
+            CcidCode::TxAck => Ok(()),
+            CcidCode::Denied => Err(xous::Error::AccessDenied),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Blocks until a frame arrives on the network link, or the link goes up or down. Like the CCID slot,
+    /// the link is locked to the first process that uses it.
+    pub fn net_wait_event(&self) -> Result<NetEvent, xous::Error> {
+        let req = NetMsgIpc::new(NetCode::RxWait, &[]);
+        let mut buf = Buffer::into_buf(req).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::NetRxDeferred.to_u32().unwrap())
+            .or(Err(xous::Error::InternalError))?;
+        let ev = buf.to_original::<NetMsgIpc, _>().unwrap();
+        match ev.code {
+            NetCode::Frame => Ok(NetEvent::Frame(ev.data[..ev.len].to_vec())),
+            NetCode::LinkUp => Ok(NetEvent::LinkUp),
+            NetCode::LinkDown => Ok(NetEvent::LinkDown),
+            NetCode::Denied => Err(xous::Error::AccessDenied),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Sends an Ethernet frame to the host. Frames sent while the link is down are dropped, as they
+    /// would be on a cable that isn't plugged in.
+    pub fn net_send_frame(&self, frame: &[u8]) -> Result<(), xous::Error> {
+        if frame.len() > NET_FRAME_BUFLEN {
+            return Err(xous::Error::OutOfMemory);
+        }
+        let req = NetMsgIpc::new(NetCode::Tx, frame);
+        let mut buf = Buffer::into_buf(req).or(Err(xous::Error::InternalError))?;
+        buf.lend_mut(self.conn, Opcode::NetTx.to_u32().unwrap()).or(Err(xous::Error::InternalError))?;
+        let ack = buf.to_original::<NetMsgIpc, _>().unwrap();
+        match ack.code {
+            NetCode::TxAck => Ok(()),
+            NetCode::Denied => Err(xous::Error::AccessDenied),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
     /// Attempts to set the logging level of the USB server
     pub fn set_log_level(&self, level: LogLevel) {
         send_message(
@@ -24,6 +24,15 @@ use spinal_udc::*;
 #[cfg(all(any(feature = "precursor", feature = "renode"), feature = "mass-storage"))]
 mod apps_block_device;
 
+mod ccid;
+#[cfg(any(feature = "precursor", feature = "renode"))]
+mod ccid_class;
+mod composite;
+#[cfg(any(feature = "precursor", feature = "renode"))]
+mod composite_device;
+mod ecm;
+#[cfg(any(feature = "precursor", feature = "renode"))]
+mod ecm_class;
 #[cfg(any(feature = "precursor", feature = "renode"))]
 mod hid;
 #[cfg(not(target_os = "xous"))]
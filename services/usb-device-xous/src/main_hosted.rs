@@ -1,7 +1,9 @@
 use core::num::NonZeroU8;
 use core::sync::atomic::AtomicUsize;
 use std::collections::VecDeque;
-use std::sync::Arc;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
 
 use num_traits::*;
 use xous::{msg_blocking_scalar_unpack, msg_scalar_unpack};
@@ -93,6 +95,111 @@ pub(crate) fn main_hosted() -> ! {
 
     let mut lockstatus_force_update = true; // some state to track if we've been through a susupend/resume, to help out the status thread with its UX update after a restart-from-cold
 
+    // There is no USB bus in hosted mode, so the CCID reader is served over TCP instead. Complete
+    // messages are queued in `ccid_inbox` and the main loop is pumped to handle them.
+    let mut ccid_slot = ccid::Slot::new();
+    let mut ccid_listener = ccid::Listener::default();
+    let ccid_inbox = Arc::new(Mutex::new(VecDeque::<Vec<u8>>::new()));
+    let ccid_stream: Arc<Mutex<Option<TcpStream>>> = Arc::new(Mutex::new(None));
+    std::thread::spawn({
+        let ccid_inbox = ccid_inbox.clone();
+        let ccid_stream = ccid_stream.clone();
+        move || {
+            let listener = match TcpListener::bind(("127.0.0.1", CCID_HOSTED_PORT)) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    log::warn!("hosted CCID transport unavailable: {:?}", e);
+                    return;
+                }
+            };
+            log::info!("hosted CCID reader on 127.0.0.1:{}", CCID_HOSTED_PORT);
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                *ccid_stream.lock().unwrap() = stream.try_clone().ok();
+                let mut asm = ccid::Assembler::default();
+                let mut data = [0u8; 512];
+                // one driver at a time; a new connection waits until this one hangs up
+                while let Ok(len) = stream.read(&mut data) {
+                    if len == 0 {
+                        break;
+                    }
+                    asm.extend(&data[..len]);
+                    while let Some(command) = asm.next_message() {
+                        ccid_inbox.lock().unwrap().push_back(command);
+                        xous::send_message(
+                            cid,
+                            xous::Message::new_scalar(Opcode::CcidPump.to_usize().unwrap(), 0, 0, 0, 0),
+                        )
+                        .ok();
+                    }
+                }
+                *ccid_stream.lock().unwrap() = None;
+                // the driver going away is the host unplugging the reader
+                xous::send_message(
+                    cid,
+                    xous::Message::new_scalar(Opcode::CcidPump.to_usize().unwrap(), 1, 0, 0, 0),
+                )
+                .ok();
+            }
+        }
+    });
+    let ccid_write = |msg: &[u8]| {
+        if let Some(stream) = ccid_stream.lock().unwrap().as_mut() {
+            stream.write_all(msg).ok();
+        }
+    };
+
+    // The network link is served the same way: a client connecting is the cable being plugged in, and
+    // frames go back and forth with a length prefix.
+    let mut net_link = ecm::Link::default();
+    let mut composite_registrations = composite::Registrations::default();
+    let net_inbox = Arc::new(Mutex::new(VecDeque::<Vec<u8>>::new()));
+    let net_stream: Arc<Mutex<Option<TcpStream>>> = Arc::new(Mutex::new(None));
+    std::thread::spawn({
+        let net_inbox = net_inbox.clone();
+        let net_stream = net_stream.clone();
+        move || {
+            let listener = match TcpListener::bind(("127.0.0.1", NET_HOSTED_PORT)) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    log::warn!("hosted network transport unavailable: {:?}", e);
+                    return;
+                }
+            };
+            log::info!("hosted network link on 127.0.0.1:{}", NET_HOSTED_PORT);
+            let pump = |state: usize| {
+                xous::send_message(
+                    cid,
+                    xous::Message::new_scalar(Opcode::NetPump.to_usize().unwrap(), state, 0, 0, 0),
+                )
+                .ok();
+            };
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => continue,
+                };
+                *net_stream.lock().unwrap() = stream.try_clone().ok();
+                pump(1);
+                let mut len = [0u8; 2];
+                let mut frame = [0u8; NET_FRAME_BUFLEN];
+                while stream.read_exact(&mut len).is_ok() {
+                    let len = u16::from_be_bytes(len) as usize;
+                    if len > NET_FRAME_BUFLEN || stream.read_exact(&mut frame[..len]).is_err() {
+                        break;
+                    }
+                    net_inbox.lock().unwrap().push_back(frame[..len].to_vec());
+                    pump(0);
+                }
+                *net_stream.lock().unwrap() = None;
+                pump(2);
+            }
+        }
+    });
+
     loop {
         let mut msg = xous::receive_message(usbdev_sid).unwrap();
         let opcode: Option<Opcode> = FromPrimitive::from_usize(msg.body.id());
@@ -197,6 +304,112 @@ pub(crate) fn main_hosted() -> ! {
                 }
                 buffer.replace(u2f_ipc).unwrap();
             }
+            Some(Opcode::CcidInsert) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut ccid_ipc = buffer.to_original::<CcidMsgIpc, _>().unwrap();
+                if ccid_listener.is_owner(msg.sender) {
+                    ccid_slot.insert(&ccid_ipc.data[..ccid_ipc.len]);
+                    // no interrupt pipe here; the driver polls the slot status
+                    ccid_slot.slot_change();
+                    ccid_ipc.code = CcidCode::TxAck;
+                } else {
+                    ccid_ipc.code = CcidCode::Denied;
+                }
+                buffer.replace(ccid_ipc).unwrap();
+            }
+            Some(Opcode::CcidRemove) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                if ccid_listener.is_owner(msg.sender) {
+                    if let Some(failure) = ccid_slot.remove() {
+                        ccid_write(&failure);
+                    }
+                    ccid_slot.slot_change();
+                    xous::return_scalar(msg.sender, 0).unwrap();
+                } else {
+                    xous::return_scalar(msg.sender, 1).unwrap();
+                }
+            }),
+            Some(Opcode::CcidRxDeferred) => ccid_listener.wait(msg),
+            Some(Opcode::CcidTx) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut ccid_ipc = buffer.to_original::<CcidMsgIpc, _>().unwrap();
+                if ccid_listener.is_owner(msg.sender) {
+                    assert_eq!(ccid_ipc.code, CcidCode::Tx, "Expected CcidCode::Tx in wrapper");
+                    if let Some(block) = ccid_slot.data_block(&ccid_ipc.data[..ccid_ipc.len]) {
+                        ccid_write(&block);
+                    }
+                    ccid_ipc.code = CcidCode::TxAck;
+                } else {
+                    ccid_ipc.code = CcidCode::Denied;
+                }
+                buffer.replace(ccid_ipc).unwrap();
+            }
+            Some(Opcode::CcidPump) => msg_scalar_unpack!(msg, hangup, _, _, _, {
+                if hangup != 0 {
+                    if ccid_slot.reset() {
+                        ccid_listener.deliver(CcidMsgIpc::new(CcidCode::Reset, &[]));
+                    }
+                } else {
+                    let commands: Vec<Vec<u8>> = ccid_inbox.lock().unwrap().drain(..).collect();
+                    for command in commands {
+                        let outcome = ccid_slot.process(&command);
+                        if let Some(reply) = outcome.reply {
+                            ccid_write(&reply);
+                        }
+                        if let Some(event) = outcome.event {
+                            ccid_listener.deliver(event);
+                        }
+                    }
+                }
+            }),
+            Some(Opcode::NetRxDeferred) => net_link.wait(msg),
+            Some(Opcode::NetTx) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut net_ipc = buffer.to_original::<NetMsgIpc, _>().unwrap();
+                if net_link.is_owner(msg.sender) {
+                    assert_eq!(net_ipc.code, NetCode::Tx, "Expected NetCode::Tx in wrapper");
+                    if let Some(stream) = net_stream.lock().unwrap().as_mut() {
+                        let mut framed = (net_ipc.len as u16).to_be_bytes().to_vec();
+                        framed.extend_from_slice(&net_ipc.data[..net_ipc.len]);
+                        stream.write_all(&framed).ok();
+                    }
+                    net_ipc.code = NetCode::TxAck;
+                } else {
+                    net_ipc.code = NetCode::Denied;
+                }
+                buffer.replace(net_ipc).unwrap();
+            }
+            Some(Opcode::NetPump) => msg_scalar_unpack!(msg, state, _, _, _, {
+                match state {
+                    1 => net_link.set_up(true),
+                    2 => net_link.set_up(false),
+                    _ => {
+                        let frames: Vec<Vec<u8>> = net_inbox.lock().unwrap().drain(..).collect();
+                        for frame in frames {
+                            net_link.deliver_frame(&frame);
+                        }
+                    }
+                }
+            }),
+            Some(Opcode::CompositeRegister) => msg_blocking_scalar_unpack!(msg, functions, _, _, _, {
+                let functions = UsbFunctions(functions as u32 & UsbFunctions::supported().0);
+                let result = match msg.sender.pid() {
+                    Some(pid) => composite_registrations.register(pid, functions),
+                    None => Err(functions),
+                };
+                match result {
+                    Ok(()) => xous::return_scalar(msg.sender, 0).unwrap(),
+                    Err(taken) => {
+                        log::warn!("{:?} already claimed by another process", taken);
+                        xous::return_scalar(msg.sender, 1).unwrap();
+                    }
+                }
+            }),
+            Some(Opcode::CompositeFunctions) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                xous::return_scalar(msg.sender, composite_registrations.functions().0 as usize).unwrap();
+            }),
             Some(Opcode::UsbIrqHandler) => {}
             Some(Opcode::SwitchCores) => msg_blocking_scalar_unpack!(msg, core, _, _, _, {
                 if core == 1 {
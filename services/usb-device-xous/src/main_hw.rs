@@ -8,6 +8,7 @@ use std::sync::Arc;
 #[cfg(all(not(feature = "minimal"), any(feature = "renode", feature = "precursor")))]
 use keyboard::KeyMap;
 use num_traits::*;
+use permissions_api::Capability;
 use usb_device::class_prelude::*;
 use usb_device::prelude::*;
 use usb_device_xous::KeyboardLedsReport;
@@ -42,6 +43,9 @@ enum Views {
     MassStorage = 2,
     Serial = 3,
     HIDv2 = 4,
+    Ccid = 5,
+    Net = 6,
+    Composite = 7,
 }
 
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
@@ -51,6 +55,13 @@ enum TimeoutOp {
     Quit,
 }
 
+/// length of the internal character buffer. This is not the *hardware* buffer; this is a buffer we maintain
+/// in the driver to improve performance
+const SERIAL_BUF_LEN: usize = 1024;
+
+/// How often the host is asked for more time while the card application works on a CCID command
+const CCID_TIME_EXTENSION_MS: usize = 1000;
+
 #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
 enum TrngOp {
     Pump,
@@ -89,6 +100,8 @@ pub(crate) fn main_hw() -> ! {
     let tt = ticktimer_server::Ticktimer::new().unwrap();
     #[cfg(not(feature = "minimal"))]
     let native_kbd = keyboard::Keyboard::new(&xns).unwrap();
+    // apps need the `usb-hid` capability to type on the host or send HID reports
+    let permissions = permissions_api::Permissions::new();
 
     #[cfg(all(not(feature = "minimal"), any(feature = "precursor", feature = "renode")))]
     let serial_number = format!("{:x}", llio.soc_dna().unwrap());
@@ -242,6 +255,9 @@ pub(crate) fn main_hw() -> ! {
     let abd = apps_block_device::AppsBlockDevice::new();
     #[cfg(feature = "mass-storage")]
     let abdcid = abd.conn();
+    // the composite device reaches the same block device
+    #[cfg(feature = "mass-storage")]
+    let composite_abd = abd.clone();
     #[cfg(feature = "mass-storage")]
     let mut ums = usbd_scsi::Scsi::new(
         &ums_alloc,
@@ -262,7 +278,6 @@ pub(crate) fn main_hw() -> ! {
         .build();
 
     // Serial
-    const SERIAL_BUF_LEN: usize = 1024; // length of the internal character buffer. This is not the *hardware* buffer; this is a buffer we maintain in the driver to improve performance
     let serial_alloc = UsbBusAllocator::new(serial_dev);
     // this will create a default port with 128 bytes of backing store
     let mut serial_port = SerialPort::new(&serial_alloc);
@@ -299,6 +314,73 @@ pub(crate) fn main_hw() -> ! {
         AppHIDConfig::default(),
         100, // 100 * 64 bytes = 6.4kb, quite the backlog
     );
+
+    // CCID smart card reader
+    #[cfg(any(feature = "renode", feature = "precursor"))]
+    let ccid_dev = SpinalUsbDevice::new(usbdev_sid, usb.clone(), csr.clone());
+    #[cfg(any(feature = "renode", feature = "precursor"))]
+    ccid_dev.init();
+    let ccid_alloc = UsbBusAllocator::new(ccid_dev);
+    let mut ccid_class = ccid_class::CcidClass::new(&ccid_alloc);
+    let mut ccid_device = UsbDeviceBuilder::new(&ccid_alloc, UsbVidPid(0x1209, 0x3613))
+        .manufacturer("Kosagi")
+        .product("Precursor")
+        .serial_number(&serial_number)
+        .self_powered(false)
+        .max_power(500)
+        .build();
+    let mut ccid_slot = ccid::Slot::new();
+    let mut ccid_listener = ccid::Listener::default();
+    // while the card application holds a command, this thread keeps asking the host for more time so
+    // that a PIN prompt doesn't make the host give up on the reader
+    let (ccid_busy_tx, ccid_busy_rx) = std::sync::mpsc::channel::<()>();
+    let ccid_busy = Arc::new(AtomicBool::new(false));
+    std::thread::spawn({
+        let cid = cid;
+        let ccid_busy = ccid_busy.clone();
+        move || {
+            let tt = ticktimer_server::Ticktimer::new().unwrap();
+            while ccid_busy_rx.recv().is_ok() {
+                loop {
+                    tt.sleep_ms(CCID_TIME_EXTENSION_MS).ok();
+                    if !ccid_busy.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    xous::try_send_message(
+                        cid,
+                        xous::Message::new_scalar(Opcode::CcidPump.to_usize().unwrap(), 0, 0, 0, 0),
+                    )
+                    .ok();
+                }
+            }
+        }
+    });
+    // CDC-ECM network adapter
+    #[cfg(any(feature = "renode", feature = "precursor"))]
+    let ecm_dev = SpinalUsbDevice::new(usbdev_sid, usb.clone(), csr.clone());
+    #[cfg(any(feature = "renode", feature = "precursor"))]
+    ecm_dev.init();
+    let ecm_alloc = UsbBusAllocator::new(ecm_dev);
+    let mut ecm_class = ecm_class::EcmClass::new(&ecm_alloc, ecm::host_mac(&serial_number));
+    let mut ecm_device = UsbDeviceBuilder::new(&ecm_alloc, UsbVidPid(0x1209, 0x3613))
+        .manufacturer("Kosagi")
+        .product("Precursor")
+        .serial_number(&serial_number)
+        .device_class(ecm_class::USB_CLASS_CDC)
+        .self_powered(false)
+        .max_power(500)
+        .build();
+    let mut net_link = ecm::Link::default();
+    // Composite device, offering whatever functions apps registered for
+    let mut composite_views = composite_device::CompositeViews::new(
+        usbdev_sid,
+        usb.clone(),
+        csr.clone(),
+        &serial_number,
+        #[cfg(feature = "mass-storage")]
+        composite_abd,
+    );
+    let mut composite_registrations = composite::Registrations::default();
     // track which view is visible on the device core
     #[cfg(all(not(feature = "minimal")))]
     let mut view = Views::FidoWithKbd;
@@ -374,11 +456,7 @@ pub(crate) fn main_hw() -> ! {
     let mut view = Views::MassStorage;
     #[cfg(feature = "minimal")]
     {
-        usbmgmt.ll_reset(true);
-        tt.sleep_ms(1000).ok();
-        usbmgmt.ll_connect_device_core(true);
-        tt.sleep_ms(EXTENDED_CORE_RESET_MS).ok();
-        usbmgmt.ll_reset(false);
+        reenumerate(&mut usbmgmt, &tt);
     }
     // manage FIDO Rx timeouts -- not tested yet
     let to_server = xous::create_server().unwrap();
@@ -557,6 +635,22 @@ pub(crate) fn main_hw() -> ! {
                         Err(e) => log::warn!("USB reset on resume failed: {:?}", e),
                         _ => (),
                     },
+                    Views::Ccid => match ccid_device.force_reset() {
+                        Err(e) => log::warn!("USB reset on resume failed: {:?}", e),
+                        _ => (),
+                    },
+                    Views::Net => match ecm_device.force_reset() {
+                        Err(e) => log::warn!("USB reset on resume failed: {:?}", e),
+                        _ => (),
+                    },
+                    Views::Composite => {
+                        if let Some(composite_view) = composite_views.view() {
+                            match composite_view.device.force_reset() {
+                                Err(e) => log::warn!("USB reset on resume failed: {:?}", e),
+                                _ => (),
+                            }
+                        }
+                    }
                 }
                 // resume2 brings us to our last application state
                 usbmgmt.xous_resume2();
@@ -675,6 +769,17 @@ pub(crate) fn main_hw() -> ! {
                         Views::MassStorage => panic!("did not expect u2f tx when in mass storage mode!"),
                         Views::Serial => panic!("did not expect u2f tx while in serial mode!"),
                         Views::HIDv2 => panic!("did not expect u2f tx while in hidv2 mode!"),
+                        Views::Ccid => panic!("did not expect u2f tx while in ccid mode!"),
+                        Views::Net => panic!("did not expect u2f tx while in net mode!"),
+                        // the composite device may have just dropped FIDO, so this isn't a bug
+                        Views::Composite => match composite_views.fido() {
+                            Some(u2f) => u2f,
+                            None => {
+                                u2f_ipc.code = U2fCode::Denied;
+                                buffer.replace(u2f_ipc).unwrap();
+                                continue;
+                            }
+                        },
                     };
                     u2f.write_report(&u2f_msg).ok();
                     log::debug!("sent U2F packet {:x?}", u2f_ipc.data);
@@ -716,115 +821,14 @@ pub(crate) fn main_hw() -> ! {
                     }
                     Views::Serial => {
                         if serial_device.poll(&mut [&mut serial_port]) {
-                            let mut data: [u8; SERIAL_BUF_LEN] = [0u8; SERIAL_BUF_LEN];
-                            match serial_listen_mode {
-                                SerialListenMode::NoListener => match serial_port.read(&mut data) {
-                                    Ok(len) => match std::str::from_utf8(&data[..len]) {
-                                        Ok(s) => log::debug!("No listener ascii: {}", s),
-                                        Err(_) => {
-                                            log::debug!("No listener binary: {:x?}", &data[..len]);
-                                        }
-                                    },
-                                    Err(e) => {
-                                        log::debug!("No listener: {:?}", e);
-                                    }
-                                },
-                                SerialListenMode::ConsoleListener => match serial_port.read(&mut data) {
-                                    Ok(len) => match std::str::from_utf8(&data[..len]) {
-                                        Ok(s) => {
-                                            for c in s.chars() {
-                                                native_kbd.inject_key(c);
-                                            }
-                                        }
-                                        Err(_) => {
-                                            log::info!("Non UTF-8 received on console: {:x?}", &data[..len]);
-                                        }
-                                    },
-                                    Err(e) => {
-                                        log::info!("Serial read error: {:?}", e);
-                                    }
-                                },
-                                SerialListenMode::AsciiListener(maybe_delimiter) => {
-                                    let readlen = serial_port.read(&mut data).unwrap_or(0);
-                                    if readlen == 0 {
-                                        continue;
-                                    }
-                                    if let Some(delimiter) = maybe_delimiter {
-                                        if !delimiter.is_ascii() {
-                                            log::warn!(
-                                                "Chosen ASCII delimiter {} is not ASCII. Serial receive will not function properly.",
-                                                delimiter
-                                            );
-                                        }
-                                        if !serial_rx_trigger {
-                                            // once true, sticks as true
-                                            serial_rx_trigger = data[..readlen]
-                                                .iter()
-                                                .find(|&&c| c == (delimiter as u8))
-                                                .is_some();
-                                        }
-                                    } else {
-                                        serial_rx_trigger = true;
-                                    }
-                                    // append the incoming data to the main buffer
-                                    for &d in &data[..readlen] {
-                                        serial_buf.push(d);
-                                    }
-                                    // now see if we should pass it back to the listener (if it is hooked)
-                                    if serial_rx_trigger && serial_listener.is_some() {
-                                        let mut rx_msg = serial_listener.take().unwrap();
-                                        let mut response = unsafe {
-                                            Buffer::from_memory_message_mut(
-                                                rx_msg.body.memory_message_mut().unwrap(),
-                                            )
-                                        };
-                                        let mut buf = response.to_original::<UsbSerialAscii, _>().unwrap();
-                                        use std::fmt::Write; // is this really the best way to do it? probably not.
-                                        write!(
-                                            buf.s,
-                                            "{}",
-                                            std::string::String::from_utf8_lossy(&serial_buf)
-                                        )
-                                        .ok();
-
-                                        response.replace(buf).unwrap();
-                                        // the rx_msg will drop and respond to the listener
-                                        serial_rx_trigger = false;
-                                    }
-                                }
-                                SerialListenMode::BinaryListener => {
-                                    let readlen = serial_port.read(&mut data).unwrap_or(0);
-                                    if readlen == 0 {
-                                        continue;
-                                    }
-                                    // append the incoming data to the main buffer
-                                    for &d in &data[..readlen] {
-                                        serial_buf.push(d);
-                                    }
-                                    if serial_buf.len() >= SERIAL_BINARY_BUFLEN {
-                                        match serial_listener.take() {
-                                            Some(mut rx_msg) => {
-                                                let mut response = unsafe {
-                                                    Buffer::from_memory_message_mut(
-                                                        rx_msg.body.memory_message_mut().unwrap(),
-                                                    )
-                                                };
-                                                let mut buf =
-                                                    response.to_original::<UsbSerialBinary, _>().unwrap();
-                                                buf.d.copy_from_slice(
-                                                    serial_buf.drain(..SERIAL_BINARY_BUFLEN).as_slice(),
-                                                );
-                                                buf.len = SERIAL_BINARY_BUFLEN;
-                                                response.replace(buf).unwrap();
-                                                // the rx_msg will drop and respond to the listener
-                                            }
-                                            None => {
-                                                // do nothing, keep queuing data...
-                                            }
-                                        }
-                                    }
-                                }
-                            }
+                            serial_rx(
+                                &mut serial_port,
+                                &serial_listen_mode,
+                                &mut serial_listener,
+                                &mut serial_buf,
+                                &mut serial_rx_trigger,
+                                &native_kbd,
+                            );
                         }
                         None
                     }
@@ -836,6 +840,68 @@ pub(crate) fn main_hw() -> ! {
 
                         None
                     }
+                    Views::Ccid => {
+                        if ccid_device.poll(&mut [&mut ccid_class]) {
+                            while let Some(command) = ccid_class.read_message() {
+                                let outcome = ccid_slot.process(&command);
+                                if let Some(reply) = outcome.reply {
+                                    ccid_class.write_message(reply);
+                                }
+                                if let Some(event) = outcome.event {
+                                    ccid_listener.deliver(event);
+                                }
+                            }
+                            if ccid_slot.is_busy() && !ccid_busy.swap(true, Ordering::SeqCst) {
+                                ccid_busy_tx.send(()).ok();
+                            }
+                        }
+                        // a host that goes away must not leave a PIN verified for the next one
+                        if ccid_device.state() != UsbDeviceState::Configured && ccid_slot.reset() {
+                            ccid_busy.store(false, Ordering::SeqCst);
+                            ccid_listener.deliver(CcidMsgIpc::new(CcidCode::Reset, &[]));
+                        }
+                        None
+                    }
+                    Views::Net => {
+                        if ecm_device.poll(&mut [&mut ecm_class]) {
+                            while let Some(frame) = ecm_class.read_frame() {
+                                net_link.deliver_frame(&frame);
+                            }
+                        }
+                        net_link.set_up(
+                            ecm_device.state() == UsbDeviceState::Configured && ecm_class.is_connected(),
+                        );
+                        None
+                    }
+                    Views::Composite => match composite_views.view() {
+                        Some(composite_view) => {
+                            if composite_view.poll() {
+                                if let Some(keyboard) = composite_view.keyboard() {
+                                    match keyboard.read_report() {
+                                        Ok(l) => {
+                                            log::info!("keyboard LEDs: {:?}", l);
+                                            led_state = l;
+                                        }
+                                        Err(e) => log::trace!("KEYB ERR: {:?}", e),
+                                    }
+                                }
+                                if let Some(serial) = composite_view.serial.as_mut() {
+                                    serial_rx(
+                                        serial,
+                                        &serial_listen_mode,
+                                        &mut serial_listener,
+                                        &mut serial_buf,
+                                        &mut serial_rx_trigger,
+                                        &native_kbd,
+                                    );
+                                }
+                                composite_view.fido()
+                            } else {
+                                None
+                            }
+                        }
+                        None => None,
+                    },
                 };
                 if let Some(u2f) = maybe_u2f {
                     match u2f.read_report() {
@@ -869,6 +935,9 @@ pub(crate) fn main_hw() -> ! {
                     Views::MassStorage => ums_device.state() == UsbDeviceState::Suspend,
                     Views::Serial => serial_device.state() == UsbDeviceState::Suspend,
                     Views::HIDv2 => hidv2.state() == UsbDeviceState::Suspend,
+                    Views::Ccid => ccid_device.state() == UsbDeviceState::Suspend,
+                    Views::Net => ecm_device.state() == UsbDeviceState::Suspend,
+                    Views::Composite => composite_views.state() == UsbDeviceState::Suspend,
                 };
                 if is_suspend {
                     log::info!("suspend detected");
@@ -894,34 +963,20 @@ pub(crate) fn main_hw() -> ! {
             // always triggers a reset when called
             Some(Opcode::SwitchCores) => msg_blocking_scalar_unpack!(msg, core, _, _, _, {
                 // ensure unhook the logger if it's connected to serial
-                let log_conn = xous::connect(xous::SID::from_bytes(b"xous-log-server ").unwrap()).unwrap();
-                // it is never harmful to double-unhook this
-                xous::send_message(
-                    log_conn,
-                    xous::Message::new_blocking_scalar(
-                        log_server::api::Opcode::UnhookUsbMirror.to_usize().unwrap(),
-                        0,
-                        0,
-                        0,
-                        0,
-                    ),
-                )
-                .ok();
-                // reset any serial listeners that may have been set
-                serial_listen_mode = SerialListenMode::NoListener;
-                serial_listener.take();
-                // shut down the TRNG sender if it's set
-                if let Some(trng_cid) = serial_trng_cid.take() {
-                    serial_trng_interval.store(0, Ordering::SeqCst);
-                    xous::send_message(
-                        trng_cid,
-                        xous::Message::new_blocking_scalar(TrngOp::Quit.to_usize().unwrap(), 0, 0, 0, 0),
-                    )
-                    .ok();
-                    trng.set_test_mode(trng::api::TrngTestMode::None);
-                }
+                unhook_serial(
+                    &mut serial_listen_mode,
+                    &mut serial_listener,
+                    &mut serial_trng_cid,
+                    &serial_trng_interval,
+                    &trng,
+                );
 
                 let devtype: UsbDeviceType = core.try_into().unwrap();
+                if view == Views::Ccid && ccid_slot.reset() {
+                    ccid_busy.store(false, Ordering::SeqCst);
+                    ccid_listener.deliver(CcidMsgIpc::new(CcidCode::Reset, &[]));
+                }
+                net_link.set_up(false);
                 match devtype {
                     UsbDeviceType::Debug => {
                         log::info!("Connecting debug core; disconnecting USB device core");
@@ -933,11 +988,7 @@ pub(crate) fn main_hw() -> ! {
                             Views::FidoWithKbd => usbmgmt.connect_device_core(true),
                             _ => {
                                 view = Views::FidoWithKbd;
-                                usbmgmt.ll_reset(true);
-                                tt.sleep_ms(1000).ok();
-                                usbmgmt.ll_connect_device_core(true);
-                                tt.sleep_ms(EXTENDED_CORE_RESET_MS).ok();
-                                usbmgmt.ll_reset(false);
+                                reenumerate(&mut usbmgmt, &tt);
                             }
                         }
                         let keyboard = composite.device::<NKROBootKeyboard<'_, _>, _>();
@@ -950,11 +1001,7 @@ pub(crate) fn main_hw() -> ! {
                             Views::FidoOnly => usbmgmt.connect_device_core(true),
                             _ => {
                                 view = Views::FidoOnly;
-                                usbmgmt.ll_reset(true);
-                                tt.sleep_ms(1000).ok();
-                                usbmgmt.ll_connect_device_core(true);
-                                tt.sleep_ms(EXTENDED_CORE_RESET_MS).ok();
-                                usbmgmt.ll_reset(false);
+                                reenumerate(&mut usbmgmt, &tt);
                             }
                         }
                     }
@@ -965,11 +1012,7 @@ pub(crate) fn main_hw() -> ! {
                             Views::MassStorage => usbmgmt.connect_device_core(true),
                             _ => {
                                 view = Views::MassStorage;
-                                usbmgmt.ll_reset(true);
-                                tt.sleep_ms(1000).ok();
-                                usbmgmt.ll_connect_device_core(true);
-                                tt.sleep_ms(EXTENDED_CORE_RESET_MS).ok();
-                                usbmgmt.ll_reset(false);
+                                reenumerate(&mut usbmgmt, &tt);
                             }
                         }
                     }
@@ -979,11 +1022,7 @@ pub(crate) fn main_hw() -> ! {
                             Views::Serial => usbmgmt.connect_device_core(true),
                             _ => {
                                 view = Views::Serial;
-                                usbmgmt.ll_reset(true);
-                                tt.sleep_ms(1000).ok();
-                                usbmgmt.ll_connect_device_core(true);
-                                tt.sleep_ms(EXTENDED_CORE_RESET_MS).ok();
-                                usbmgmt.ll_reset(false);
+                                reenumerate(&mut usbmgmt, &tt);
                             }
                         }
                     }
@@ -993,11 +1032,54 @@ pub(crate) fn main_hw() -> ! {
                             Views::HIDv2 => usbmgmt.connect_device_core(true),
                             _ => {
                                 view = Views::HIDv2;
-                                usbmgmt.ll_reset(true);
-                                tt.sleep_ms(1000).ok();
-                                usbmgmt.ll_connect_device_core(true);
-                                tt.sleep_ms(EXTENDED_CORE_RESET_MS).ok();
-                                usbmgmt.ll_reset(false);
+                                reenumerate(&mut usbmgmt, &tt);
+                            }
+                        }
+                    }
+                    UsbDeviceType::Ccid => {
+                        log::info!("Connecting CCID smart card reader");
+                        match view {
+                            Views::Ccid => usbmgmt.connect_device_core(true),
+                            _ => {
+                                view = Views::Ccid;
+                                reenumerate(&mut usbmgmt, &tt);
+                            }
+                        }
+                    }
+                    UsbDeviceType::Net => {
+                        log::info!("Connecting network adapter");
+                        match view {
+                            Views::Net => usbmgmt.connect_device_core(true),
+                            _ => {
+                                view = Views::Net;
+                                reenumerate(&mut usbmgmt, &tt);
+                            }
+                        }
+                    }
+                    UsbDeviceType::Composite => {
+                        let functions = composite_registrations.functions();
+                        log::info!("Connecting composite device with {:?}", functions);
+                        composite_views.select(functions);
+                        if functions.is_empty() {
+                            log::warn!("No functions registered, connecting the FIDO + kbd device instead");
+                            match view {
+                                Views::FidoWithKbd => usbmgmt.connect_device_core(true),
+                                _ => {
+                                    view = Views::FidoWithKbd;
+                                    reenumerate(&mut usbmgmt, &tt);
+                                }
+                            }
+                        } else {
+                            match view {
+                                Views::Composite => usbmgmt.connect_device_core(true),
+                                _ => {
+                                    view = Views::Composite;
+                                    reenumerate(&mut usbmgmt, &tt);
+                                }
+                            }
+                            if let Some(keyboard) = composite_views.keyboard() {
+                                keyboard.write_report([Keyboard::NoEventIndicated]).ok(); // queues an "all key-up" for the interface
+                                keyboard.tick().ok();
                             }
                         }
                     }
@@ -1007,35 +1089,44 @@ pub(crate) fn main_hw() -> ! {
             // does not trigger a reset if we're already on the core
             Some(Opcode::EnsureCore) => msg_blocking_scalar_unpack!(msg, core, _, _, _, {
                 let devtype: UsbDeviceType = core.try_into().unwrap();
-                // if we are switching away from serial, unhook any possible listeners, and the logger
-                if view == Views::Serial && devtype != UsbDeviceType::Serial {
-                    let log_conn =
-                        xous::connect(xous::SID::from_bytes(b"xous-log-server ").unwrap()).unwrap();
-                    // it is never harmful to double-unhook this
-                    xous::send_message(
-                        log_conn,
-                        xous::Message::new_blocking_scalar(
-                            log_server::api::Opcode::UnhookUsbMirror.to_usize().unwrap(),
-                            0,
-                            0,
-                            0,
-                            0,
-                        ),
-                    )
-                    .ok();
-                    // reset any serial listeners that may have been set
-                    serial_listen_mode = SerialListenMode::NoListener;
-                    serial_listener.take();
-                    // shut down the TRNG sender if it's set
-                    if let Some(trng_cid) = serial_trng_cid.take() {
-                        serial_trng_interval.store(0, Ordering::SeqCst);
-                        xous::send_message(
-                            trng_cid,
-                            xous::Message::new_blocking_scalar(TrngOp::Quit.to_usize().unwrap(), 0, 0, 0, 0),
-                        )
-                        .ok();
-                        trng.set_test_mode(trng::api::TrngTestMode::None);
+                if view == Views::Composite
+                    && usbmgmt.is_device_connected()
+                    && composite_covers(devtype, composite_views.active())
+                {
+                    log::info!(
+                        "Composite device with {:?} already covers the core, leaving it up",
+                        composite_views.active()
+                    );
+                    if devtype == UsbDeviceType::FidoKbd {
+                        if let Some(keyboard) = composite_views.keyboard() {
+                            keyboard.write_report([Keyboard::NoEventIndicated]).ok(); // queues an "all key-up" for the interface
+                            keyboard.tick().ok();
+                        }
                     }
+                    xous::return_scalar(msg.sender, 0).unwrap();
+                    continue;
+                }
+                if view == Views::Ccid && devtype != UsbDeviceType::Ccid && ccid_slot.reset() {
+                    ccid_busy.store(false, Ordering::SeqCst);
+                    ccid_listener.deliver(CcidMsgIpc::new(CcidCode::Reset, &[]));
+                }
+                if devtype != UsbDeviceType::Net {
+                    net_link.set_up(false);
+                }
+                // if we are switching away from serial, unhook any possible listeners, and the logger
+                let had_serial = view == Views::Serial
+                    || (view == Views::Composite && composite_views.active().contains(UsbFunctions::SERIAL));
+                let keeps_serial = devtype == UsbDeviceType::Serial
+                    || (devtype == UsbDeviceType::Composite
+                        && composite_registrations.functions().contains(UsbFunctions::SERIAL));
+                if had_serial && !keeps_serial {
+                    unhook_serial(
+                        &mut serial_listen_mode,
+                        &mut serial_listener,
+                        &mut serial_trng_cid,
+                        &serial_trng_interval,
+                        &trng,
+                    );
                 }
 
                 match devtype {
@@ -1053,11 +1144,7 @@ pub(crate) fn main_hw() -> ! {
                         } else {
                             if view != Views::FidoWithKbd {
                                 view = Views::FidoWithKbd;
-                                usbmgmt.ll_reset(true);
-                                tt.sleep_ms(1000).ok();
-                                usbmgmt.ll_connect_device_core(true);
-                                tt.sleep_ms(EXTENDED_CORE_RESET_MS).ok();
-                                usbmgmt.ll_reset(false);
+                                reenumerate(&mut usbmgmt, &tt);
                             } else {
                                 // type matches, do nothing
                             }
@@ -1074,11 +1161,7 @@ pub(crate) fn main_hw() -> ! {
                         } else {
                             if view != Views::FidoOnly {
                                 view = Views::FidoOnly;
-                                usbmgmt.ll_reset(true);
-                                tt.sleep_ms(1000).ok();
-                                usbmgmt.ll_connect_device_core(true);
-                                tt.sleep_ms(EXTENDED_CORE_RESET_MS).ok();
-                                usbmgmt.ll_reset(false);
+                                reenumerate(&mut usbmgmt, &tt);
                             } else {
                                 // type matches, do nothing
                             }
@@ -1093,11 +1176,7 @@ pub(crate) fn main_hw() -> ! {
                         } else {
                             if view != Views::MassStorage {
                                 view = Views::MassStorage;
-                                usbmgmt.ll_reset(true);
-                                tt.sleep_ms(1000).ok();
-                                usbmgmt.ll_connect_device_core(true);
-                                tt.sleep_ms(EXTENDED_CORE_RESET_MS).ok();
-                                usbmgmt.ll_reset(false);
+                                reenumerate(&mut usbmgmt, &tt);
                             } else {
                                 // type matches, do nothing
                             }
@@ -1111,11 +1190,7 @@ pub(crate) fn main_hw() -> ! {
                         } else {
                             if view != Views::Serial {
                                 view = Views::Serial;
-                                usbmgmt.ll_reset(true);
-                                tt.sleep_ms(1000).ok();
-                                usbmgmt.ll_connect_device_core(true);
-                                tt.sleep_ms(EXTENDED_CORE_RESET_MS).ok();
-                                usbmgmt.ll_reset(false);
+                                reenumerate(&mut usbmgmt, &tt);
                             }
                         }
                     }
@@ -1127,11 +1202,60 @@ pub(crate) fn main_hw() -> ! {
                         } else {
                             if view != Views::HIDv2 {
                                 view = Views::HIDv2;
-                                usbmgmt.ll_reset(true);
-                                tt.sleep_ms(1000).ok();
-                                usbmgmt.ll_connect_device_core(true);
-                                tt.sleep_ms(EXTENDED_CORE_RESET_MS).ok();
-                                usbmgmt.ll_reset(false);
+                                reenumerate(&mut usbmgmt, &tt);
+                            }
+                        }
+                    }
+                    UsbDeviceType::Ccid => {
+                        log::info!("Ensuring CCID device");
+                        if !usbmgmt.is_device_connected() {
+                            view = Views::Ccid;
+                            usbmgmt.connect_device_core(true);
+                        } else {
+                            if view != Views::Ccid {
+                                view = Views::Ccid;
+                                reenumerate(&mut usbmgmt, &tt);
+                            }
+                        }
+                    }
+                    UsbDeviceType::Net => {
+                        log::info!("Ensuring network adapter");
+                        if !usbmgmt.is_device_connected() {
+                            view = Views::Net;
+                            usbmgmt.connect_device_core(true);
+                        } else {
+                            if view != Views::Net {
+                                view = Views::Net;
+                                reenumerate(&mut usbmgmt, &tt);
+                            }
+                        }
+                    }
+                    UsbDeviceType::Composite => {
+                        let functions = composite_registrations.functions();
+                        log::info!("Ensuring composite device with {:?}", functions);
+                        let changed = composite_views.select(functions);
+                        if functions.is_empty() {
+                            log::warn!("No functions registered, ensuring the FIDO + kbd device instead");
+                            if !usbmgmt.is_device_connected() {
+                                view = Views::FidoWithKbd;
+                                usbmgmt.connect_device_core(true);
+                            } else if view != Views::FidoWithKbd {
+                                view = Views::FidoWithKbd;
+                                reenumerate(&mut usbmgmt, &tt);
+                            }
+                        } else {
+                            if !usbmgmt.is_device_connected() {
+                                view = Views::Composite;
+                                usbmgmt.connect_device_core(true);
+                            } else {
+                                if view != Views::Composite || changed {
+                                    view = Views::Composite;
+                                    reenumerate(&mut usbmgmt, &tt);
+                                }
+                            }
+                            if let Some(keyboard) = composite_views.keyboard() {
+                                keyboard.write_report([Keyboard::NoEventIndicated]).ok(); // queues an "all key-up" for the interface
+                                keyboard.tick().ok();
                             }
                         }
                     }
@@ -1157,6 +1281,11 @@ pub(crate) fn main_hw() -> ! {
                         Views::HIDv2 => {
                             xous::return_scalar(msg.sender, UsbDeviceType::HIDv2 as usize).unwrap()
                         }
+                        Views::Ccid => xous::return_scalar(msg.sender, UsbDeviceType::Ccid as usize).unwrap(),
+                        Views::Net => xous::return_scalar(msg.sender, UsbDeviceType::Net as usize).unwrap(),
+                        Views::Composite => {
+                            xous::return_scalar(msg.sender, UsbDeviceType::Composite as usize).unwrap()
+                        }
                     }
                 } else {
                     xous::return_scalar(msg.sender, UsbDeviceType::Debug as usize).unwrap();
@@ -1210,90 +1339,95 @@ pub(crate) fn main_hw() -> ! {
                     }
                     Views::Serial => xous::return_scalar(msg.sender, serial_device.state() as usize).unwrap(),
                     Views::HIDv2 => xous::return_scalar(msg.sender, hidv2.state() as usize).unwrap(),
+                    Views::Ccid => xous::return_scalar(msg.sender, ccid_device.state() as usize).unwrap(),
+                    Views::Net => xous::return_scalar(msg.sender, ecm_device.state() as usize).unwrap(),
+                    Views::Composite => {
+                        xous::return_scalar(msg.sender, composite_views.state() as usize).unwrap()
+                    }
                 }
             }),
             Some(Opcode::SendKeyCode) => msg_blocking_scalar_unpack!(msg, code0, code1, code2, autoup, {
-                match view {
-                    Views::FidoWithKbd => {
-                        if usb_dev.state() == UsbDeviceState::Configured {
-                            let native_map = native_kbd.get_keymap().unwrap();
-                            let mut codes = Vec::<Keyboard>::new();
-                            if code0 != 0 {
-                                codes.push(match native_map {
-                                    KeyMap::Dvorak => {
-                                        mappings::char_to_hid_code_dvorak(code0 as u8 as char)[0]
-                                    }
-                                    _ => mappings::char_to_hid_code_us101(code0 as u8 as char)[0],
-                                });
-                            }
-                            if code1 != 0 {
-                                codes.push(match native_map {
-                                    KeyMap::Dvorak => {
-                                        mappings::char_to_hid_code_dvorak(code1 as u8 as char)[0]
-                                    }
-                                    _ => mappings::char_to_hid_code_us101(code1 as u8 as char)[0],
-                                });
-                            }
-                            if code2 != 0 {
-                                codes.push(match native_map {
-                                    KeyMap::Dvorak => {
-                                        mappings::char_to_hid_code_dvorak(code2 as u8 as char)[0]
-                                    }
-                                    _ => mappings::char_to_hid_code_us101(code2 as u8 as char)[0],
-                                });
-                            }
-                            let auto_up = if autoup == 1 { true } else { false };
-                            let keyboard = composite.device::<NKROBootKeyboard<'_, _>, _>();
-                            keyboard.write_report(codes).ok();
-                            keyboard.tick().ok();
-                            tt.sleep_ms(autotype_delay_ms).ok();
-                            if auto_up {
-                                keyboard.write_report([Keyboard::NoEventIndicated]).ok(); // this is the key-up
-                                keyboard.tick().ok();
-                                tt.sleep_ms(autotype_delay_ms).ok();
-                            }
-                            xous::return_scalar(msg.sender, 0).unwrap();
-                        } else {
-                            xous::return_scalar(msg.sender, 1).unwrap();
-                        }
+                let keyboard = match view {
+                    Views::FidoWithKbd if usb_dev.state() == UsbDeviceState::Configured => {
+                        Some(composite.device::<NKROBootKeyboard<'_, _>, _>())
                     }
-                    _ => {
-                        xous::return_scalar(msg.sender, 1).unwrap();
+                    Views::Composite
+                        if composite_views.state() == UsbDeviceState::Configured
+                            && composite_registrations.owns(msg.sender.pid(), UsbFunctions::KEYBOARD) =>
+                    {
+                        composite_views.keyboard()
+                    }
+                    _ => None,
+                };
+                if let Some(keyboard) = keyboard.filter(|_| permissions.check(msg.sender, Capability::UsbHid))
+                {
+                    let native_map = native_kbd.get_keymap().unwrap();
+                    let mut codes = Vec::<Keyboard>::new();
+                    if code0 != 0 {
+                        codes.push(match native_map {
+                            KeyMap::Dvorak => mappings::char_to_hid_code_dvorak(code0 as u8 as char)[0],
+                            _ => mappings::char_to_hid_code_us101(code0 as u8 as char)[0],
+                        });
                     }
+                    if code1 != 0 {
+                        codes.push(match native_map {
+                            KeyMap::Dvorak => mappings::char_to_hid_code_dvorak(code1 as u8 as char)[0],
+                            _ => mappings::char_to_hid_code_us101(code1 as u8 as char)[0],
+                        });
+                    }
+                    if code2 != 0 {
+                        codes.push(match native_map {
+                            KeyMap::Dvorak => mappings::char_to_hid_code_dvorak(code2 as u8 as char)[0],
+                            _ => mappings::char_to_hid_code_us101(code2 as u8 as char)[0],
+                        });
+                    }
+                    let auto_up = if autoup == 1 { true } else { false };
+                    keyboard.write_report(codes).ok();
+                    keyboard.tick().ok();
+                    tt.sleep_ms(autotype_delay_ms).ok();
+                    if auto_up {
+                        keyboard.write_report([Keyboard::NoEventIndicated]).ok(); // this is the key-up
+                        keyboard.tick().ok();
+                        tt.sleep_ms(autotype_delay_ms).ok();
+                    }
+                    xous::return_scalar(msg.sender, 0).unwrap();
+                } else {
+                    xous::return_scalar(msg.sender, 1).unwrap();
                 }
             }),
             Some(Opcode::LogString) => {
                 // the logger API is "best effort" only. Because retries and response codes can cause problems
                 // in the logger API, if anything goes wrong, we prefer to discard characters rather than get
                 // the whole subsystem stuck in some awful recursive error handling hell.
-                match view {
-                    Views::Serial => {
-                        let buffer =
-                            unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
-                        let usb_send = buffer.to_original::<api::UsbString, _>().unwrap();
-                        // this is implemented as a "blocking write": the routine will block until the data
-                        // has all been written.
-                        let send_data = usb_send.s.as_bytes();
-                        let to_send = usb_send.s.len();
-                        let mut sent = 0;
-                        while sent < to_send {
-                            match serial_port.write(&send_data[sent..to_send]) {
-                                Ok(written) => {
-                                    sent += written;
-                                }
-                                Err(_) => {
-                                    // just drop characters
-                                }
+                let port = match view {
+                    Views::Serial => Some(&mut serial_port),
+                    Views::Composite => composite_views.serial(),
+                    _ => None, // do nothing; don't fail, don't report any error.
+                };
+                if let Some(port) = port {
+                    let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
+                    let usb_send = buffer.to_original::<api::UsbString, _>().unwrap();
+                    // this is implemented as a "blocking write": the routine will block until the data
+                    // has all been written.
+                    let send_data = usb_send.s.as_bytes();
+                    let to_send = usb_send.s.len();
+                    let mut sent = 0;
+                    while sent < to_send {
+                        match port.write(&send_data[sent..to_send]) {
+                            Ok(written) => {
+                                sent += written;
                             }
-                            match serial_port.flush() {
-                                Ok(_) => {}
-                                Err(_) => {
-                                    // just drop characters
-                                }
+                            Err(_) => {
+                                // just drop characters
+                            }
+                        }
+                        match port.flush() {
+                            Ok(_) => {}
+                            Err(_) => {
+                                // just drop characters
                             }
                         }
                     }
-                    _ => {} // do nothing; don't fail, don't report any error.
                 }
             }
             Some(Opcode::SetAutotypeRate) => msg_scalar_unpack!(msg, rate, _, _, _, {
@@ -1311,51 +1445,39 @@ pub(crate) fn main_hw() -> ! {
                 let mut sent = 0;
                 #[cfg(feature = "minimal")]
                 let sent = 0;
+                let sender = msg.sender.pid();
                 match view {
                     #[cfg(not(feature = "minimal"))]
                     Views::FidoWithKbd => {
-                        // check keymap on every call because we may need to toggle this for e.g. plugging
-                        // into a new host with a different map
-                        let native_map = native_kbd.get_keymap().unwrap();
-                        for ch in usb_send.s.as_str().chars() {
-                            // ASSUME: user's keyboard type matches the preference on their Precursor device.
-                            let codes = match native_map {
-                                KeyMap::Dvorak => mappings::char_to_hid_code_dvorak(ch),
-                                _ => mappings::char_to_hid_code_us101(ch),
-                            };
+                        if permissions.check(msg.sender, Capability::UsbHid) {
                             let keyboard = composite.device::<NKROBootKeyboard<'_, _>, _>();
-                            keyboard.write_report(codes).ok();
-                            keyboard.tick().ok();
-                            tt.sleep_ms(autotype_delay_ms).ok();
-                            keyboard.write_report([Keyboard::NoEventIndicated]).ok(); // this is the key-up
-                            keyboard.tick().ok();
-                            tt.sleep_ms(autotype_delay_ms).ok();
-                            sent += 1;
+                            sent =
+                                autotype(keyboard, usb_send.s.as_str(), &native_kbd, &tt, autotype_delay_ms);
                         }
                     }
                     Views::Serial => {
-                        // this is implemented as a "blocking write": the routine will block until the data
-                        // has all been written.
-                        let send_data = usb_send.s.as_bytes();
-                        let to_send = usb_send.s.len();
-                        // log::debug!("serial RTS: {:?}", serial_port.rts());
-                        // log::debug!("serial DTR: {:?}", serial_port.dtr());
-                        while sent < to_send {
-                            match serial_port.write(&send_data[sent..to_send]) {
-                                Ok(written) => {
-                                    sent += written;
-                                }
-                                Err(_) => {
-                                    log::warn!("Serial send is blocking. Delaying and trying again.");
-                                    tt.sleep_ms(100).ok();
-                                }
+                        sent = serial_write(&mut serial_port, usb_send.s.as_bytes(), &tt);
+                    }
+                    // the composite device types for the keyboard's owner, and writes to the serial port
+                    // for the port's owner
+                    #[cfg(not(feature = "minimal"))]
+                    Views::Composite => {
+                        if composite_registrations.owns(sender, UsbFunctions::KEYBOARD) {
+                            if let Some(keyboard) = composite_views
+                                .keyboard()
+                                .filter(|_| permissions.check(msg.sender, Capability::UsbHid))
+                            {
+                                sent = autotype(
+                                    keyboard,
+                                    usb_send.s.as_str(),
+                                    &native_kbd,
+                                    &tt,
+                                    autotype_delay_ms,
+                                );
                             }
-                            match serial_port.flush() {
-                                Ok(_) => {}
-                                Err(_) => {
-                                    log::warn!("Serial port reported WouldBlock on flush");
-                                    tt.sleep_ms(100).ok();
-                                }
+                        } else if composite_registrations.owns(sender, UsbFunctions::SERIAL) {
+                            if let Some(port) = composite_views.serial() {
+                                sent = serial_write(port, usb_send.s.as_bytes(), &tt);
                             }
                         }
                     }
@@ -1409,36 +1531,26 @@ pub(crate) fn main_hw() -> ! {
                 }
             }),
             Some(Opcode::SerialClearHooks) => {
-                let log_conn = xous::connect(xous::SID::from_bytes(b"xous-log-server ").unwrap()).unwrap();
-                // it is never harmful to double-unhook this
-                xous::send_message(
-                    log_conn,
-                    xous::Message::new_blocking_scalar(
-                        log_server::api::Opcode::UnhookUsbMirror.to_usize().unwrap(),
-                        0,
-                        0,
-                        0,
-                        0,
-                    ),
-                )
-                .ok();
-
-                serial_listen_mode = SerialListenMode::NoListener;
-                serial_listener.take();
-                // shut down the TRNG sender if it's set
-                if let Some(trng_cid) = serial_trng_cid.take() {
-                    serial_trng_interval.store(0, Ordering::SeqCst);
-                    xous::send_message(
-                        trng_cid,
-                        xous::Message::new_blocking_scalar(TrngOp::Quit.to_usize().unwrap(), 0, 0, 0, 0),
-                    )
-                    .ok();
-                    trng.set_test_mode(trng::api::TrngTestMode::None);
-                }
+                unhook_serial(
+                    &mut serial_listen_mode,
+                    &mut serial_listener,
+                    &mut serial_trng_cid,
+                    &serial_trng_interval,
+                    &trng,
+                );
             }
             Some(Opcode::SerialFlush) => msg_scalar_unpack!(msg, _, _, _, _, {
                 // this will hardware flush any pending items in usb_serial driver
-                serial_port.flush().ok();
+                match view {
+                    Views::Composite => {
+                        if let Some(port) = composite_views.serial() {
+                            port.flush().ok();
+                        }
+                    }
+                    _ => {
+                        serial_port.flush().ok();
+                    }
+                }
                 // this tries to return any data that's pending within the main loop's buffers
                 match serial_listen_mode {
                     SerialListenMode::BinaryListener => {
@@ -1480,7 +1592,9 @@ pub(crate) fn main_hw() -> ! {
                 }
             }),
             Some(Opcode::SerialHookTrngSender) => msg_scalar_unpack!(msg, trng_mode_code, _, _, _, {
-                if view != Views::Serial {
+                if view != Views::Serial
+                    && !(view == Views::Composite && composite_views.active().contains(UsbFunctions::SERIAL))
+                {
                     log::error!("USB is not in serial mode. Ignoring request to hook TRNG sender");
                     continue;
                 }
@@ -1625,8 +1739,15 @@ pub(crate) fn main_hw() -> ! {
                     // stale request from previously configured TRNG system
                     continue;
                 }
+                let port = match view {
+                    Views::Composite => match composite_views.serial() {
+                        Some(port) => port,
+                        None => continue,
+                    },
+                    _ => &mut serial_port,
+                };
                 let mut sent = false;
-                if serial_port.dtr() {
+                if port.dtr() {
                     if serial_trng_buf.len() < TRNG_PKT_SIZE {
                         match trng.get_test_data() {
                             Ok(data) => {
@@ -1640,10 +1761,10 @@ pub(crate) fn main_hw() -> ! {
                     }
                     // at this point, we should have data we can copy to the buffer. Pull it from the end of
                     // the buffer so the Vec can efficiently de-allocate data.
-                    match serial_port.flush() {
+                    match port.flush() {
                         Ok(_) => {
                             let available = serial_trng_buf.len();
-                            match serial_port.write(&serial_trng_buf[available - TRNG_PKT_SIZE..available]) {
+                            match port.write(&serial_trng_buf[available - TRNG_PKT_SIZE..available]) {
                                 Ok(_) => {
                                     serial_trng_buf.drain(available - TRNG_PKT_SIZE..available);
                                     sent = true;
@@ -1670,6 +1791,9 @@ pub(crate) fn main_hw() -> ! {
                 }
             }
             Some(Opcode::HIDSetDescriptor) => {
+                if !permissions.check(msg.sender, Capability::UsbHid) {
+                    continue;
+                }
                 let buffer =
                     unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
                 let data = buffer.to_original::<HIDReportDescriptorMessage, _>().unwrap();
@@ -1689,6 +1813,9 @@ pub(crate) fn main_hw() -> ! {
                 Err(error) => log::error!("cannot reset hidv2 device report: {:?}", error),
             },
             Some(Opcode::HIDReadReport) => {
+                if !permissions.check(msg.sender, Capability::UsbHid) {
+                    continue;
+                }
                 if !hidv2.descriptor_set() {
                     log::warn!("trying to read a HID report with no descriptor set!");
                     continue;
@@ -1704,6 +1831,9 @@ pub(crate) fn main_hw() -> ! {
                 buffer.replace(data).expect("couldn't serialize return");
             }
             Some(Opcode::HIDWriteReport) => {
+                if !permissions.check(msg.sender, Capability::UsbHid) {
+                    continue;
+                }
                 if !hidv2.descriptor_set() {
                     log::warn!("trying to write a HID report with no descriptor set!");
                     continue;
@@ -1716,6 +1846,153 @@ pub(crate) fn main_hw() -> ! {
 
                 hidv2.write_report(data);
             }
+            Some(Opcode::CcidInsert) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut ccid_ipc = buffer.to_original::<CcidMsgIpc, _>().unwrap();
+                if ccid_listener.is_owner(msg.sender) {
+                    ccid_slot.insert(&ccid_ipc.data[..ccid_ipc.len]);
+                    if let Some(notification) = ccid_slot.slot_change() {
+                        if view == Views::Ccid {
+                            ccid_class.notify(&notification);
+                        }
+                    }
+                    ccid_ipc.code = CcidCode::TxAck;
+                } else {
+                    ccid_ipc.code = CcidCode::Denied;
+                }
+                buffer.replace(ccid_ipc).unwrap();
+            }
+            Some(Opcode::CcidRemove) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                if ccid_listener.is_owner(msg.sender) {
+                    if let Some(failure) = ccid_slot.remove() {
+                        ccid_class.write_message(failure);
+                    }
+                    if let Some(notification) = ccid_slot.slot_change() {
+                        if view == Views::Ccid {
+                            ccid_class.notify(&notification);
+                        }
+                    }
+                    ccid_busy.store(false, Ordering::SeqCst);
+                    xous::return_scalar(msg.sender, 0).unwrap();
+                } else {
+                    xous::return_scalar(msg.sender, 1).unwrap();
+                }
+            }),
+            Some(Opcode::CcidRxDeferred) => {
+                // notify the event listener, if any
+                if observer_conn.is_some() && observer_op.is_some() {
+                    xous::try_send_message(
+                        observer_conn.unwrap(),
+                        xous::Message::new_scalar(observer_op.unwrap(), 0, 0, 0, 0),
+                    )
+                    .ok();
+                }
+                ccid_listener.wait(msg);
+            }
+            Some(Opcode::CcidTx) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut ccid_ipc = buffer.to_original::<CcidMsgIpc, _>().unwrap();
+                if ccid_listener.is_owner(msg.sender) {
+                    assert_eq!(ccid_ipc.code, CcidCode::Tx, "Expected CcidCode::Tx in wrapper");
+                    ccid_busy.store(false, Ordering::SeqCst);
+                    match ccid_slot.data_block(&ccid_ipc.data[..ccid_ipc.len]) {
+                        Some(block) => ccid_class.write_message(block),
+                        None => log::debug!("CCID response arrived after the command was aborted"),
+                    }
+                    ccid_ipc.code = CcidCode::TxAck;
+                } else {
+                    ccid_ipc.code = CcidCode::Denied;
+                }
+                buffer.replace(ccid_ipc).unwrap();
+            }
+            Some(Opcode::CcidPump) => {
+                if let Some(extension) = ccid_slot.time_extension() {
+                    ccid_class.write_message(extension);
+                }
+            }
+            Some(Opcode::NetRxDeferred) => net_link.wait(msg),
+            Some(Opcode::NetTx) => {
+                let mut buffer =
+                    unsafe { Buffer::from_memory_message_mut(msg.body.memory_message_mut().unwrap()) };
+                let mut net_ipc = buffer.to_original::<NetMsgIpc, _>().unwrap();
+                if net_link.is_owner(msg.sender) {
+                    assert_eq!(net_ipc.code, NetCode::Tx, "Expected NetCode::Tx in wrapper");
+                    if view == Views::Net && net_link.is_up() {
+                        ecm_class.write_frame(&net_ipc.data[..net_ipc.len]);
+                    }
+                    net_ipc.code = NetCode::TxAck;
+                } else {
+                    net_ipc.code = NetCode::Denied;
+                }
+                buffer.replace(net_ipc).unwrap();
+            }
+            Some(Opcode::CompositeRegister) => msg_blocking_scalar_unpack!(msg, functions, _, _, _, {
+                let functions = UsbFunctions(functions as u32 & UsbFunctions::supported().0);
+                let pid = match msg.sender.pid() {
+                    Some(pid) => pid,
+                    None => {
+                        xous::return_scalar(msg.sender, 1).unwrap();
+                        continue;
+                    }
+                };
+                // FIDO keeps going to whoever used it first, like it does outside the composite device
+                if functions.contains(UsbFunctions::FIDO)
+                    && fido_listener_pid.is_some()
+                    && fido_listener_pid != Some(pid)
+                {
+                    log::warn!("FIDO is locked to another process, refusing the claim of {:?}", pid);
+                    xous::return_scalar(msg.sender, 1).unwrap();
+                    continue;
+                }
+                match composite_registrations.register(pid, functions) {
+                    Ok(()) => {
+                        if functions.contains(UsbFunctions::FIDO) {
+                            fido_listener_pid = Some(pid);
+                        }
+                        let offered = composite_registrations.functions();
+                        if view == Views::Composite && usbmgmt.is_device_connected() {
+                            if composite_views.active().contains(UsbFunctions::SERIAL)
+                                && !offered.contains(UsbFunctions::SERIAL)
+                            {
+                                unhook_serial(
+                                    &mut serial_listen_mode,
+                                    &mut serial_listener,
+                                    &mut serial_trng_cid,
+                                    &serial_trng_interval,
+                                    &trng,
+                                );
+                            }
+                            if composite_views.select(offered) {
+                                if offered.is_empty() {
+                                    // rather than leave the bus, fall back to the device offered by default
+                                    log::info!(
+                                        "Composite device has nothing left to offer, switching to FIDO + kbd"
+                                    );
+                                    view = Views::FidoWithKbd;
+                                    reenumerate(&mut usbmgmt, &tt);
+                                } else {
+                                    log::info!("Composite device now offers {:?}", offered);
+                                    reenumerate(&mut usbmgmt, &tt);
+                                    if let Some(keyboard) = composite_views.keyboard() {
+                                        keyboard.write_report([Keyboard::NoEventIndicated]).ok();
+                                        keyboard.tick().ok();
+                                    }
+                                }
+                            }
+                        }
+                        xous::return_scalar(msg.sender, 0).unwrap();
+                    }
+                    Err(taken) => {
+                        log::warn!("{:?} already claimed by another process, refusing {:?}", taken, pid);
+                        xous::return_scalar(msg.sender, 1).unwrap();
+                    }
+                }
+            }),
+            Some(Opcode::CompositeFunctions) => msg_blocking_scalar_unpack!(msg, _, _, _, _, {
+                xous::return_scalar(msg.sender, composite_registrations.functions().0 as usize).unwrap();
+            }),
             Some(Opcode::RegisterUsbObserver) => {
                 let buffer = unsafe { Buffer::from_memory_message(msg.body.memory_message().unwrap()) };
                 let ur = buffer.as_flat::<UsbListenerRegistration, _>().unwrap();
@@ -1759,3 +2036,230 @@ pub(crate) fn main_hw() -> ! {
     log::trace!("quitting");
     xous::terminate_process(0)
 }
+
+/// Takes what the host sent over a serial port, and hands it to whoever listens.
+fn serial_rx(
+    port: &mut SerialPort<'_, SpinalUsbDevice>,
+    serial_listen_mode: &SerialListenMode,
+    serial_listener: &mut Option<xous::MessageEnvelope>,
+    serial_buf: &mut Vec<u8>,
+    serial_rx_trigger: &mut bool,
+    native_kbd: &keyboard::Keyboard,
+) {
+    let mut data: [u8; SERIAL_BUF_LEN] = [0u8; SERIAL_BUF_LEN];
+    match serial_listen_mode {
+        SerialListenMode::NoListener => match port.read(&mut data) {
+            Ok(len) => match std::str::from_utf8(&data[..len]) {
+                Ok(s) => log::debug!("No listener ascii: {}", s),
+                Err(_) => {
+                    log::debug!("No listener binary: {:x?}", &data[..len]);
+                }
+            },
+            Err(e) => {
+                log::debug!("No listener: {:?}", e);
+            }
+        },
+        SerialListenMode::ConsoleListener => match port.read(&mut data) {
+            Ok(len) => match std::str::from_utf8(&data[..len]) {
+                Ok(s) => {
+                    for c in s.chars() {
+                        native_kbd.inject_key(c);
+                    }
+                }
+                Err(_) => {
+                    log::info!("Non UTF-8 received on console: {:x?}", &data[..len]);
+                }
+            },
+            Err(e) => {
+                log::info!("Serial read error: {:?}", e);
+            }
+        },
+        SerialListenMode::AsciiListener(maybe_delimiter) => {
+            let readlen = port.read(&mut data).unwrap_or(0);
+            if readlen == 0 {
+                return;
+            }
+            if let Some(delimiter) = maybe_delimiter {
+                if !delimiter.is_ascii() {
+                    log::warn!(
+                        "Chosen ASCII delimiter {} is not ASCII. Serial receive will not function properly.",
+                        delimiter
+                    );
+                }
+                if !*serial_rx_trigger {
+                    // once true, sticks as true
+                    *serial_rx_trigger = data[..readlen].iter().find(|&&c| c == (delimiter as u8)).is_some();
+                }
+            } else {
+                *serial_rx_trigger = true;
+            }
+            // append the incoming data to the main buffer
+            for &d in &data[..readlen] {
+                serial_buf.push(d);
+            }
+            // now see if we should pass it back to the listener (if it is hooked)
+            if *serial_rx_trigger && serial_listener.is_some() {
+                let mut rx_msg = serial_listener.take().unwrap();
+                let mut response =
+                    unsafe { Buffer::from_memory_message_mut(rx_msg.body.memory_message_mut().unwrap()) };
+                let mut buf = response.to_original::<UsbSerialAscii, _>().unwrap();
+                use std::fmt::Write; // is this really the best way to do it? probably not.
+                write!(buf.s, "{}", std::string::String::from_utf8_lossy(&serial_buf)).ok();
+
+                response.replace(buf).unwrap();
+                // the rx_msg will drop and respond to the listener
+                *serial_rx_trigger = false;
+            }
+        }
+        SerialListenMode::BinaryListener => {
+            let readlen = port.read(&mut data).unwrap_or(0);
+            if readlen == 0 {
+                return;
+            }
+            // append the incoming data to the main buffer
+            for &d in &data[..readlen] {
+                serial_buf.push(d);
+            }
+            if serial_buf.len() >= SERIAL_BINARY_BUFLEN {
+                match serial_listener.take() {
+                    Some(mut rx_msg) => {
+                        let mut response = unsafe {
+                            Buffer::from_memory_message_mut(rx_msg.body.memory_message_mut().unwrap())
+                        };
+                        let mut buf = response.to_original::<UsbSerialBinary, _>().unwrap();
+                        buf.d.copy_from_slice(serial_buf.drain(..SERIAL_BINARY_BUFLEN).as_slice());
+                        buf.len = SERIAL_BINARY_BUFLEN;
+                        response.replace(buf).unwrap();
+                        // the rx_msg will drop and respond to the listener
+                    }
+                    None => {
+                        // do nothing, keep queuing data...
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A "blocking write": returns once all of `data` has been written.
+fn serial_write(
+    port: &mut SerialPort<'_, SpinalUsbDevice>,
+    data: &[u8],
+    tt: &ticktimer_server::Ticktimer,
+) -> usize {
+    let mut sent = 0;
+    // log::debug!("serial RTS: {:?}", port.rts());
+    // log::debug!("serial DTR: {:?}", port.dtr());
+    while sent < data.len() {
+        match port.write(&data[sent..]) {
+            Ok(written) => {
+                sent += written;
+            }
+            Err(_) => {
+                log::warn!("Serial send is blocking. Delaying and trying again.");
+                tt.sleep_ms(100).ok();
+            }
+        }
+        match port.flush() {
+            Ok(_) => {}
+            Err(_) => {
+                log::warn!("Serial port reported WouldBlock on flush");
+                tt.sleep_ms(100).ok();
+            }
+        }
+    }
+    sent
+}
+
+/// Types `s` on the keyboard, returning the number of characters typed.
+#[cfg(not(feature = "minimal"))]
+fn autotype(
+    keyboard: &mut NKROBootKeyboard<'_, SpinalUsbDevice>,
+    s: &str,
+    native_kbd: &keyboard::Keyboard,
+    tt: &ticktimer_server::Ticktimer,
+    autotype_delay_ms: usize,
+) -> usize {
+    // check keymap on every call because we may need to toggle this for e.g. plugging
+    // into a new host with a different map
+    let native_map = native_kbd.get_keymap().unwrap();
+    let mut sent = 0;
+    for ch in s.chars() {
+        // ASSUME: user's keyboard type matches the preference on their Precursor device.
+        let codes = match native_map {
+            KeyMap::Dvorak => mappings::char_to_hid_code_dvorak(ch),
+            _ => mappings::char_to_hid_code_us101(ch),
+        };
+        keyboard.write_report(codes).ok();
+        keyboard.tick().ok();
+        tt.sleep_ms(autotype_delay_ms).ok();
+        keyboard.write_report([Keyboard::NoEventIndicated]).ok(); // this is the key-up
+        keyboard.tick().ok();
+        tt.sleep_ms(autotype_delay_ms).ok();
+        sent += 1;
+    }
+    sent
+}
+
+/// Unhooks the logger and any serial listeners, and stops the TRNG sender. It is never harmful to call
+/// this twice.
+fn unhook_serial(
+    serial_listen_mode: &mut SerialListenMode,
+    serial_listener: &mut Option<xous::MessageEnvelope>,
+    serial_trng_cid: &mut Option<xous::CID>,
+    serial_trng_interval: &AtomicU32,
+    trng: &trng::Trng,
+) {
+    let log_conn = xous::connect(xous::SID::from_bytes(b"xous-log-server ").unwrap()).unwrap();
+    // it is never harmful to double-unhook this
+    xous::send_message(
+        log_conn,
+        xous::Message::new_blocking_scalar(
+            log_server::api::Opcode::UnhookUsbMirror.to_usize().unwrap(),
+            0,
+            0,
+            0,
+            0,
+        ),
+    )
+    .ok();
+    // reset any serial listeners that may have been set
+    *serial_listen_mode = SerialListenMode::NoListener;
+    serial_listener.take();
+    // shut down the TRNG sender if it's set
+    if let Some(trng_cid) = serial_trng_cid.take() {
+        serial_trng_interval.store(0, Ordering::SeqCst);
+        xous::send_message(
+            trng_cid,
+            xous::Message::new_blocking_scalar(TrngOp::Quit.to_usize().unwrap(), 0, 0, 0, 0),
+        )
+        .ok();
+        trng.set_test_mode(trng::api::TrngTestMode::None);
+    }
+}
+
+/// Whether a composite device offering `active` already does the job of the single-function core
+/// `devtype`, so asking for that core needn't take the other functions off the bus.
+fn composite_covers(devtype: UsbDeviceType, active: UsbFunctions) -> bool {
+    match devtype {
+        UsbDeviceType::FidoKbd => active.contains(UsbFunctions::FIDO | UsbFunctions::KEYBOARD),
+        // the FIDO-only core exists to hide the keyboard from the host
+        UsbDeviceType::Fido => {
+            active.contains(UsbFunctions::FIDO) && !active.contains(UsbFunctions::KEYBOARD)
+        }
+        UsbDeviceType::Serial => active.contains(UsbFunctions::SERIAL),
+        #[cfg(feature = "mass-storage")]
+        UsbDeviceType::MassStorage => active.contains(UsbFunctions::MASS_STORAGE),
+        _ => false,
+    }
+}
+
+/// Drops off the bus long enough for the host to forget what it saw, then comes back with the device
+/// core. Hosts get confused by the same VID/PID reappearing with other endpoints, hence the long wait.
+fn reenumerate(usbmgmt: &mut SpinalUsbMgmt, tt: &ticktimer_server::Ticktimer) {
+    usbmgmt.ll_reset(true);
+    tt.sleep_ms(1000).ok();
+    usbmgmt.ll_connect_device_core(true);
+    tt.sleep_ms(EXTENDED_CORE_RESET_MS).ok();
+    usbmgmt.ll_reset(false);
+}
@@ -0,0 +1,134 @@
+//! A page compressor that is cheap enough to run inside the blocking swap handler.
+//!
+//! The handler runs with interrupts disabled and may not allocate, so a general-purpose
+//! LZ compressor is out of the question. Instead, pages are run-length coded at word
+//! granularity: a bitmap with one bit per 32-bit word records whether that word differs from
+//! the word before it, and only the words that differ are stored as literals. This captures
+//! the common cases of swapped-out pages -- zero-filled heap, partially used stacks and
+//! `memset()` patterns -- at the cost of one pass over the page.
+//!
+//! Compressed layout:
+//!
+//! ```text
+//! | change bitmap (PAGE_WORDS / 8 bytes) | literal words, little-endian ... |
+//! ```
+//!
+//! The word "before" word 0 is taken to be 0, so an all-zero page compresses to the bitmap alone.
+
+use core::convert::TryInto;
+
+const WORD: usize = core::mem::size_of::<u32>();
+
+/// Compresses `page` into `out`.
+///
+/// # Returns
+///
+/// `Some(len)`, the number of bytes written into `out`, if the compressed form is smaller
+/// than `limit` bytes; otherwise `None`, and the contents of `out` are undefined.
+pub fn compress(page: &[u8], out: &mut [u8], limit: usize) -> Option<usize> {
+    assert!(page.len() % WORD == 0);
+    let words = page.len() / WORD;
+    let bitmap_len = bitmap_len(words);
+    if bitmap_len >= limit || out.len() < limit {
+        return None;
+    }
+    out[..bitmap_len].fill(0);
+    let mut len = bitmap_len;
+    let mut prev = 0u32;
+    for (i, word) in page.chunks_exact(WORD).enumerate() {
+        let w = u32::from_le_bytes(word.try_into().unwrap());
+        if w != prev {
+            if len + WORD >= limit {
+                return None;
+            }
+            out[i / 8] |= 1 << (i % 8);
+            out[len..len + WORD].copy_from_slice(word);
+            len += WORD;
+            prev = w;
+        }
+    }
+    Some(len)
+}
+
+/// Decompresses `compressed` into `page`, which must be the size of the original page.
+///
+/// # Returns
+///
+/// `Err(())` if `compressed` is inconsistent with the length of `page`.
+pub fn decompress(compressed: &[u8], page: &mut [u8]) -> Result<(), ()> {
+    assert!(page.len() % WORD == 0);
+    let words = page.len() / WORD;
+    let bitmap_len = bitmap_len(words);
+    if compressed.len() < bitmap_len {
+        return Err(());
+    }
+    let (bitmap, literals) = compressed.split_at(bitmap_len);
+    let mut literals = literals.chunks_exact(WORD);
+    let mut prev = [0u8; WORD];
+    for (i, word) in page.chunks_exact_mut(WORD).enumerate() {
+        if bitmap[i / 8] & (1 << (i % 8)) != 0 {
+            prev.copy_from_slice(literals.next().ok_or(())?);
+        }
+        word.copy_from_slice(&prev);
+    }
+    if literals.next().is_some() || !literals.remainder().is_empty() { Err(()) } else { Ok(()) }
+}
+
+fn bitmap_len(words: usize) -> usize { (words + 7) / 8 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: usize = 4096;
+
+    fn roundtrip(page: &[u8]) -> Option<usize> {
+        let mut compressed = [0u8; PAGE];
+        let len = compress(page, &mut compressed, PAGE)?;
+        let mut restored = [0xA5u8; PAGE];
+        decompress(&compressed[..len], &mut restored).unwrap();
+        assert_eq!(page, &restored[..]);
+        Some(len)
+    }
+
+    #[test]
+    fn zero_page() {
+        assert_eq!(roundtrip(&[0u8; PAGE]), Some(PAGE / WORD / 8));
+    }
+
+    #[test]
+    fn fill_page() {
+        assert_eq!(roundtrip(&[0xFFu8; PAGE]), Some(PAGE / WORD / 8 + WORD));
+    }
+
+    #[test]
+    fn sparse_page() {
+        let mut page = [0u8; PAGE];
+        page[100..200].copy_from_slice(&[0x42; 100]);
+        page[4000] = 1;
+        assert!(roundtrip(&page).unwrap() < PAGE / 2);
+    }
+
+    #[test]
+    fn incompressible_page() {
+        let mut page = [0u8; PAGE];
+        let mut x = 0x1234_5678u32;
+        for b in page.iter_mut() {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            *b = x as u8;
+        }
+        assert_eq!(roundtrip(&page), None);
+    }
+
+    #[test]
+    fn malformed() {
+        let mut page = [0u8; PAGE];
+        assert!(decompress(&[0u8; 10], &mut page).is_err());
+        let mut compressed = [0u8; PAGE / WORD / 8];
+        compressed[0] = 1;
+        assert!(decompress(&compressed, &mut page).is_err());
+        assert!(decompress(&[&[0u8; PAGE / WORD / 8][..], &[1, 2, 3, 4]].concat(), &mut page).is_err());
+    }
+}
@@ -8,10 +8,87 @@ pub enum Opcode {
     #[cfg(feature = "swap-userspace-testing")]
     Test0,
     None,
+    // numbered from past `None`, so they keep their values with or without `swap-userspace-testing`
+    /// Query pages out, faults and pages resident in swap for a PID
+    ProcessStats = 3,
+    /// Query the average and worst-case swap fault latency for a PID
+    ProcessLatency,
+    /// Query swap slot occupancy and compression savings
+    SwapStats,
+    /// Query hard OOM, integrity and eviction failure counters
+    SwapEvents,
+    /// Advise the swapper how a range of the sender's pages will be used
+    Advise,
+    /// Advise the swapper how all of the sender's pages will be used
+    AdviseProcess,
+    /// Select the page replacement policy; returns the previous policy
+    SetPolicy,
 }
 
 pub const SWAPPER_PUBLIC_NAME: &'static str = "_swapper server_";
 
+/// Advice a process can give about its own pages. Advice only affects which pages are chosen for
+/// eviction; pages are still swapped in on demand as usual.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+#[repr(usize)]
+pub enum SwapAdvice {
+    /// Rank the pages by the replacement policy (clears any advice)
+    Normal = 0,
+    /// The pages are unlikely to be used again soon: evict them before any others
+    Cold = 1,
+    /// The pages are used often: only evict them once no other page can be evicted
+    Hot = 2,
+    /// Never evict the pages, e.g. for buffers that are used with real-time deadlines. Use sparingly:
+    /// wired pages can't be used to relieve memory pressure.
+    Wired = 3,
+}
+
+/// Page replacement policies selectable with `Swapper::set_policy()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, num_derive::FromPrimitive, num_derive::ToPrimitive)]
+#[repr(usize)]
+pub enum PolicyKind {
+    /// Least-recently-used, by page table epoch
+    LruEpoch = 0,
+    /// CLOCK (second chance)
+    Clock = 1,
+    /// Protect the recently used working set of pages
+    WorkingSet = 2,
+}
+
+/// Swap statistics for a single process
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ProcessSwapStats {
+    /// pages written out to swap
+    pub pages_out: usize,
+    /// pages faulted back in from swap
+    pub faults: usize,
+    /// pages currently resident in swap
+    pub swapped: usize,
+    /// average cycles to service a fault (0 unless the swapper is built with `swap-latency`)
+    pub latency_avg: usize,
+    /// worst-case cycles to service a fault (0 unless the swapper is built with `swap-latency`)
+    pub latency_max: usize,
+}
+
+/// Swap-wide statistics
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SwapSummary {
+    /// swap slots available, one page each
+    pub slots: usize,
+    /// swap slots in use
+    pub used: usize,
+    /// pages in swap that are stored compressed
+    pub compressed: usize,
+    /// bytes not encrypted or transferred thanks to compression, over the pages in swap
+    pub bytes_saved: usize,
+    /// hard OOM invocations
+    pub hard_ooms: usize,
+    /// swap pages that failed MAC verification once, but decrypted on a retry
+    pub integrity_retries: usize,
+    /// eviction candidates that could not be taken from their owner
+    pub steal_rejected: usize,
+}
+
 pub struct Swapper {
     conn: xous::CID,
 }
@@ -38,6 +115,118 @@ impl Swapper {
         }
         // no result is given, but the call blocks until the GC call has completed in the swapper.
     }
+
+    /// Returns the swap statistics for `pid`
+    pub fn process_stats(&self, pid: xous::PID) -> Result<ProcessSwapStats, xous::Error> {
+        let mut stats = ProcessSwapStats::default();
+        match xous::send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(Opcode::ProcessStats as usize, pid.get() as usize, 0, 0, 0),
+        )? {
+            xous::Result::Scalar5(_, pages_out, faults, swapped, _) => {
+                stats.pages_out = pages_out;
+                stats.faults = faults;
+                stats.swapped = swapped;
+            }
+            _ => return Err(xous::Error::InternalError),
+        }
+        match xous::send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(Opcode::ProcessLatency as usize, pid.get() as usize, 0, 0, 0),
+        )? {
+            xous::Result::Scalar5(_, latency_avg, latency_max, _, _) => {
+                stats.latency_avg = latency_avg;
+                stats.latency_max = latency_max;
+            }
+            _ => return Err(xous::Error::InternalError),
+        }
+        Ok(stats)
+    }
+
+    /// Returns swap-wide occupancy, compression and integrity statistics
+    pub fn swap_stats(&self) -> Result<SwapSummary, xous::Error> {
+        let mut summary = SwapSummary::default();
+        match xous::send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(Opcode::SwapStats as usize, 0, 0, 0, 0),
+        )? {
+            xous::Result::Scalar5(_, slots, used, compressed, bytes_saved) => {
+                summary.slots = slots;
+                summary.used = used;
+                summary.compressed = compressed;
+                summary.bytes_saved = bytes_saved;
+            }
+            _ => return Err(xous::Error::InternalError),
+        }
+        match xous::send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(Opcode::SwapEvents as usize, 0, 0, 0, 0),
+        )? {
+            xous::Result::Scalar5(_, hard_ooms, integrity_retries, steal_rejected, _) => {
+                summary.hard_ooms = hard_ooms;
+                summary.integrity_retries = integrity_retries;
+                summary.steal_rejected = steal_rejected;
+            }
+            _ => return Err(xous::Error::InternalError),
+        }
+        Ok(summary)
+    }
+
+    /// Advises the swapper how the pages in `range` of the calling process will be used. `range`
+    /// must be page aligned. Later advice takes precedence over earlier advice where they overlap.
+    pub fn advise(&self, range: xous::MemoryRange, advice: SwapAdvice) -> Result<(), xous::Error> {
+        self.advise_inner(Opcode::Advise, range.as_ptr() as usize, range.len(), advice)
+    }
+
+    /// Advises the swapper how all of the pages of the calling process will be used; advice given
+    /// for a range with `advise()` takes precedence. Latency-critical services, such as the graphics
+    /// and audio servers, can use this to keep themselves out of swap.
+    pub fn advise_process(&self, advice: SwapAdvice) -> Result<(), xous::Error> {
+        self.advise_inner(Opcode::AdviseProcess, 0, 0, advice)
+    }
+
+    /// Advises the swapper how the pages holding `object` will be used, e.g. to wire a buffer that's
+    /// serviced against a deadline. The range is rounded out to whole pages, so whatever shares those
+    /// pages with `object` gets the same advice.
+    pub fn advise_object<T: ?Sized>(&self, object: &T, advice: SwapAdvice) -> Result<(), xous::Error> {
+        const PAGE_SIZE: usize = 4096;
+        let start = object as *const T as *const u8 as usize;
+        let end = start + core::mem::size_of_val(object).max(1);
+        let base = start & !(PAGE_SIZE - 1);
+        let top = (end + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+        self.advise_inner(Opcode::Advise, base, top - base, advice)
+    }
+
+    fn advise_inner(
+        &self,
+        op: Opcode,
+        addr: usize,
+        len: usize,
+        advice: SwapAdvice,
+    ) -> Result<(), xous::Error> {
+        match xous::send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(op as usize, addr, len, advice as usize, 0),
+        )? {
+            xous::Result::Scalar5(_, 0, _, _, _) => Ok(()),
+            xous::Result::Scalar5(_, err, _, _, _) => Err(xous::Error::from_usize(err)),
+            _ => Err(xous::Error::InternalError),
+        }
+    }
+
+    /// Selects the page replacement policy used to choose pages for eviction, returning the
+    /// previous policy.
+    pub fn set_policy(&self, policy: PolicyKind) -> Result<PolicyKind, xous::Error> {
+        match xous::send_message(
+            self.conn,
+            xous::Message::new_blocking_scalar(Opcode::SetPolicy as usize, policy as usize, 0, 0, 0),
+        )? {
+            xous::Result::Scalar5(_, previous, _, _, _) => {
+                num_traits::FromPrimitive::from_usize(previous).ok_or(xous::Error::InternalError)
+            }
+            _ => Err(xous::Error::InternalError),
+        }
+    }
 }
 
 use core::sync::atomic::{AtomicU32, Ordering};
@@ -47,9 +47,32 @@
 //! the current `MEMORY_ALLOCATIONS` table into a pre-allocated BinaryHeap in the shared state structure,
 //! indexed by the timestamp. At this point, the blocking userspace handler can work through a sorted vector
 //! of allocations to pick the pages it wants to remove.
+//!
+//! == Replacement Policy ==
+//!
+//! The order in which pages are evicted is decided by a `ReplacementPolicy` (see `policy.rs`):
+//! least-recently-used by epoch (the default), CLOCK, or working-set. The policy can be changed at
+//! run time with the `SetPolicy` opcode. On top of the policy, processes can advise the swapper
+//! that ranges of their own pages, or all of their pages, are cold (evict first), hot (evict last)
+//! or wired (never evict) with the `Advise` and `AdviseProcess` opcodes. The sender of the advice
+//! is identified by the kernel, so a process can only advise on its own pages.
+//!
+//! == Compression & Statistics ==
+//!
+//! With the `swap-compress` feature, evicted pages are compressed (see `compress.rs`) before they are
+//! encrypted, and only the compressed bytes are encrypted and written to swap. Each page still occupies
+//! one swap slot, because the per-slot nonce counter and MAC table are laid out by the loader; the saving
+//! is in the bytes encrypted and moved over the swap bus on every eviction and fault.
+//!
+//! Per-process counts of pages out, faults and fault latency, and swap-wide occupancy, compression and
+//! integrity counters are kept in `SwapAccounting` (see `stats.rs`) and can be queried through the
+//! `ProcessStats`, `ProcessLatency`, `SwapStats` and `SwapEvents` opcodes.
 
+mod compress;
 mod debug;
 mod platform;
+mod policy;
+mod stats;
 use core::fmt::Write;
 use std::collections::BinaryHeap;
 use std::fmt::Debug;
@@ -58,8 +81,10 @@ use debug::*;
 use loader::swap::{SWAP_CFG_VADDR, SWAP_COUNT_VADDR, SWAP_PT_VADDR, SWAP_RPT_VADDR, SwapAlloc, SwapSpec};
 use num_traits::*;
 use platform::{PAGE_SIZE, SwapHal};
+use policy::{Candidate, LruEpoch, Replacement, ReplacementPolicy};
+use stats::SwapAccounting;
 use xous::{MemoryFlags, MemoryRange, PID, Result};
-use xous_swapper::Opcode;
+use xous_swapper::{Opcode, PolicyKind, SwapAdvice};
 
 /// Target of pages to free in case of a Hard OOM. Note that the PAGE_TARGET numbers
 /// are imprecise, in that there is a chance that one target is active during another
@@ -73,6 +98,10 @@ const OOM_DOOM_PAGE_TARGET: usize = 48;
 /// competition with other processes that probably use even-second multiples for polling.
 #[cfg(feature = "oom-doom")]
 const OOM_DOOM_POLL_INTERVAL_MS: u64 = 1057;
+/// Pages are only stored compressed if it saves at least this fraction of a page; otherwise
+/// the cost of decompressing on every fault isn't worth the bus traffic saved.
+#[cfg(feature = "swap-compress")]
+const COMPRESS_LIMIT: usize = PAGE_SIZE - PAGE_SIZE / 8;
 
 /// userspace swapper -> kernel ABI
 /// This ABI is copy-paste synchronized with what's in the kernel. It's left out of
@@ -168,7 +197,7 @@ pub struct SwapperSharedState {
     /// starting from the free swap search origin. The unit of this variable is in pages, so it
     /// can be used to directly index the `sct` `SwapCountTracker`.
     pub free_swap_search_origin: usize,
-    pub hard_oom_alloc_heap: Option<BinaryHeap<SwapAlloc>>,
+    pub hard_oom_alloc_heap: Option<BinaryHeap<Candidate>>,
     /// Reserve some memory to be freed by the hard OOM manager. These pages are needed to do things
     /// like create L1 page table entries for the swapper to track evicted pages.
     pub hard_oom_reserved_page: Option<MemoryRange>,
@@ -176,6 +205,12 @@ pub struct SwapperSharedState {
     /// number of pages to free in the OOM routine. Note that this value is imprecise: it can
     /// be mutated by the userspace soft-OOM handler at any time.
     pub pages_to_free: usize,
+    /// Swap statistics and the compressed length of each swap slot. Allocated by the main thread,
+    /// so it is `None` for the first few calls into the handler.
+    pub acct: Option<SwapAccounting>,
+    /// The page replacement policy and the advice given by processes. Allocated by the main thread;
+    /// until then, pages are evicted least-recently-used first.
+    pub repl: Option<Replacement>,
 }
 impl SwapperSharedState {
     pub fn pt_walk(&self, pid: u8, va: usize, mark_free: bool) -> Option<usize> {
@@ -372,13 +407,30 @@ fn write_to_swap_inner(
         // add a PT mapping for the swap entry
         map_swap(ss, free_page_number * PAGE_SIZE, candidate.vaddr(), candidate.raw_pid());
 
+        // the compressed image replaces the head of `buf`, and only that is encrypted to swap
+        #[cfg(feature = "swap-compress")]
+        let len = match ss.acct.as_mut() {
+            Some(acct) => match compress::compress(buf, &mut acct.scratch, COMPRESS_LIMIT) {
+                Some(len) => {
+                    buf[..len].copy_from_slice(&acct.scratch[..len]);
+                    len
+                }
+                None => PAGE_SIZE,
+            },
+            None => PAGE_SIZE,
+        };
+        #[cfg(not(feature = "swap-compress"))]
+        let len = PAGE_SIZE;
         ss.hal.encrypt_swap_to(
-            buf,
+            &mut buf[..len],
             count,
             free_page_number * PAGE_SIZE,
             candidate.vaddr(),
             candidate.raw_pid(),
         );
+        if let Some(acct) = ss.acct.as_mut() {
+            acct.record_out(candidate.raw_pid(), free_page_number, len);
+        }
     } else {
         writeln!(DebugUart {}, "OOM detected, dumping all swap allocs:").ok();
         for (i, &entry) in ss.sct.counts.iter().enumerate() {
@@ -484,6 +536,8 @@ fn swap_handler(
             report_full_rpt: true,
             hard_oom_reserved_page: Some(reserved),
             pages_to_free: HARD_OOM_PAGE_TARGET + HARD_OOM_RESERVED_PAGES,
+            acct: None,
+            repl: None,
         });
     }
     let ss = sss.inner.as_mut().expect("Shared state should be initialized");
@@ -496,6 +550,7 @@ fn swap_handler(
             let pid = a2 as u8;
             let vaddr_in_pid = a3;
             let vaddr_in_swap = a4;
+            let start = stats::cycles();
             // walk the PT to find the swap data, and remove it from the swap PT
             let paddr_in_swap = match ss.pt_walk(pid as u8, vaddr_in_pid, true) {
                 Some(paddr) => paddr,
@@ -521,13 +576,15 @@ fn swap_handler(
             // safety: this is only safe because the pointer we're passed from the kernel is guaranteed to be
             // a valid u8-page in memory
             let buf = unsafe { core::slice::from_raw_parts_mut(vaddr_in_swap as *mut u8, PAGE_SIZE) };
+            let len =
+                ss.acct.as_ref().map(|acct| acct.stored_len(paddr_in_swap / PAGE_SIZE)).unwrap_or(PAGE_SIZE);
             // this is in a retry loop because the SPIM interface can timeout during high bus congestion
             // periods.
             const TIMEOUT_RETRIES: usize = 3;
             let mut retries = 0;
             while retries < TIMEOUT_RETRIES {
                 match ss.hal.decrypt_swap_from(
-                    buf,
+                    &mut buf[..len],
                     ss.sct.counts[paddr_in_swap / PAGE_SIZE],
                     paddr_in_swap,
                     vaddr_in_pid,
@@ -538,6 +595,9 @@ fn swap_handler(
                     }
                     Err(e) => {
                         retries += 1;
+                        if let Some(acct) = ss.acct.as_mut() {
+                            acct.events.integrity_retries += 1;
+                        }
                         writeln!(
                             DebugUart {},
                             "Decryption error: swap image corrupted, the tag does not match the data! {:?} (try {}/{})",
@@ -554,9 +614,23 @@ fn swap_handler(
                     }
                 }
             }
+            if len < PAGE_SIZE {
+                // a compressed length is only ever recorded in `acct`, so it must exist
+                let acct = ss.acct.as_mut().expect("compressed page without swap accounting");
+                acct.scratch[..len].copy_from_slice(&buf[..len]);
+                if compress::decompress(&acct.scratch[..len], buf).is_err() {
+                    writeln!(DebugUart {}, "Swap page PID{} VA {:x} failed to decompress", pid, vaddr_in_pid)
+                        .ok();
+                    panic!("Swap page failed to decompress");
+                }
+            }
+            if let Some(acct) = ss.acct.as_mut() {
+                acct.record_fault(pid, stats::cycles().wrapping_sub(start));
+            }
             // at this point, the `buf` has our desired data, we're done, modulo updating the count.
         }
-        // HardOom handling will evict any and all pages that it can -- it does no filtering.
+        // HardOom handling will evict any and all pages that it can, in the order given by the replacement
+        // policy. The only pages that are filtered out are those that are wired, or advised as wired.
         Some(KernelOp::HardOom) => {
             // parse the arguments (none, currently)
 
@@ -584,14 +658,34 @@ fn swap_handler(
             let rpt = unsafe {
                 core::slice::from_raw_parts(SWAP_RPT_VADDR as *const SwapAlloc, ss.sram_size / PAGE_SIZE)
             };
-            for (_i, &entry) in rpt.iter().enumerate() {
+            let mut lru = LruEpoch {};
+            if let Some(repl) = ss.repl.as_mut() {
+                repl.advice.forget_exited(rpt);
+                repl.prepare(rpt);
+            }
+            for (frame, &entry) in rpt.iter().enumerate() {
+                let rank = match ss.repl.as_mut() {
+                    Some(repl) => repl.rank(frame, &entry),
+                    None => Some(lru.rank(frame, &entry)),
+                };
                 // filter out invalid, wired, or kernel/swapper candidates
-                if (!entry.is_wired() && entry.is_valid() && entry.raw_pid() != 1 && entry.raw_pid() != 2)
-                // report_full_rpt is used to force the heap to reserve all the data we might need in a future oom
-                    || ss.report_full_rpt
-                {
-                    //  writeln!(DebugUart {}, "Pushing {:x?}", entry).ok();
-                    alloc_heap.push(entry);
+                let swappable =
+                    !entry.is_wired() && entry.is_valid() && entry.raw_pid() != 1 && entry.raw_pid() != 2;
+                match rank {
+                    Some(rank) if swappable => {
+                        //  writeln!(DebugUart {}, "Pushing {:x?}", entry).ok();
+                        alloc_heap.push(Candidate { rank, frame: frame as u32, alloc: entry });
+                    }
+                    // report_full_rpt is used to force the heap to reserve all the data we might need in a
+                    // future oom. Entries that were only pushed for that are marked wired so they are
+                    // skipped.
+                    _ if ss.report_full_rpt => {
+                        let mut alloc = entry;
+                        alloc.set_wired();
+                        let rank = rank.unwrap_or_else(|| lru.rank(frame, &entry));
+                        alloc_heap.push(Candidate { rank, frame: frame as u32, alloc });
+                    }
+                    _ => {}
                 }
             }
             // Inside the interrupt context, evict pages. No progress on any other process is made until this
@@ -604,7 +698,7 @@ fn swap_handler(
             let mut wired: usize = 0;
 
             while pages_to_free > 0 {
-                if let Some(candidate) = alloc_heap.pop() {
+                if let Some(Candidate { frame, alloc: candidate, .. }) = alloc_heap.pop() {
                     if candidate.is_wired()
                         || !candidate.is_valid()
                         || candidate.raw_pid() == 1
@@ -612,8 +706,12 @@ fn swap_handler(
                     {
                         wired += 1;
                     } else {
-                        // error is ignored because the correct behavior on error is to try another page
-                        write_to_swap_inner(ss, candidate, &mut errs, &mut pages_to_free).ok();
+                        // on error, the correct behavior is to try another page
+                        if write_to_swap_inner(ss, candidate, &mut errs, &mut pages_to_free).is_ok() {
+                            if let Some(repl) = ss.repl.as_mut() {
+                                repl.evicted(frame as usize);
+                            }
+                        }
                     }
                 } else {
                     writeln!(
@@ -626,6 +724,10 @@ fn swap_handler(
             }
             // put the alloc heap back into the shared state
             ss.hard_oom_alloc_heap = Some(alloc_heap);
+            if let Some(acct) = ss.acct.as_mut() {
+                acct.events.hard_ooms += 1;
+                acct.events.steal_rejected += errs as u32;
+            }
             writeln!(
                 DebugUart {},
                 "Exiting HARD OOM swap free loop: freed {} pages; {} requests rejected, {} wired",
@@ -676,6 +778,13 @@ fn main() {
     let total_ram = sss.inner.as_ref().unwrap().sram_size;
     // Binary heap for storing the view of the memory allocations.
     sss.inner.as_mut().unwrap().hard_oom_alloc_heap = Some(BinaryHeap::with_capacity(total_ram / PAGE_SIZE));
+    // Swap accounting is likewise allocated here, because the handler can't allocate.
+    let pid_count = sss.inner.as_ref().unwrap().pts.roots.len();
+    let slot_count = sss.inner.as_ref().unwrap().sct.counts.len();
+    sss.inner.as_mut().unwrap().acct = Some(SwapAccounting::new(pid_count, slot_count));
+    // As is the replacement policy state.
+    sss.inner.as_mut().unwrap().repl =
+        Some(Replacement::new(PolicyKind::LruEpoch, pid_count, total_ram / PAGE_SIZE));
 
     // Do a single invocation at boot with 0 pages to free, to ensure that the page maps are set up,
     // and sufficient heap has been allocated for the swapper to run in case of a hard OOM. Failure to
@@ -752,6 +861,107 @@ fn main() {
                     scalar.arg1 = free_pages;
                 }
             }
+            Some(Opcode::ProcessStats) => {
+                if let Some(scalar) = msg.body.scalar_message_mut() {
+                    let ss = sss.inner.as_ref().unwrap();
+                    let stats = ss
+                        .acct
+                        .as_ref()
+                        .and_then(|acct| acct.processes.get(scalar.arg1).copied())
+                        .unwrap_or_default();
+                    scalar.arg1 = stats.pages_out as usize;
+                    scalar.arg2 = stats.faults as usize;
+                    scalar.arg3 = stats.swapped as usize;
+                    scalar.arg4 = 0;
+                }
+            }
+            Some(Opcode::ProcessLatency) => {
+                if let Some(scalar) = msg.body.scalar_message_mut() {
+                    let ss = sss.inner.as_ref().unwrap();
+                    let stats = ss
+                        .acct
+                        .as_ref()
+                        .and_then(|acct| acct.processes.get(scalar.arg1).copied())
+                        .unwrap_or_default();
+                    scalar.arg1 = if stats.faults != 0 {
+                        (stats.latency_total / stats.faults as u64) as usize
+                    } else {
+                        0
+                    };
+                    scalar.arg2 = stats.latency_max as usize;
+                    scalar.arg3 = 0;
+                    scalar.arg4 = 0;
+                }
+            }
+            Some(Opcode::SwapStats) => {
+                if let Some(scalar) = msg.body.scalar_message_mut() {
+                    let ss = sss.inner.as_ref().unwrap();
+                    let used =
+                        ss.sct.counts.iter().filter(|&&count| count & loader::FLG_SWAP_USED != 0).count();
+                    let (compressed, saved) =
+                        ss.acct.as_ref().map(|acct| acct.compression(&ss.sct.counts)).unwrap_or((0, 0));
+                    scalar.arg1 = ss.sct.counts.len();
+                    scalar.arg2 = used;
+                    scalar.arg3 = compressed;
+                    scalar.arg4 = saved;
+                }
+            }
+            Some(Opcode::SwapEvents) => {
+                if let Some(scalar) = msg.body.scalar_message_mut() {
+                    let ss = sss.inner.as_ref().unwrap();
+                    let events = ss.acct.as_ref().map(|acct| acct.events).unwrap_or_default();
+                    scalar.arg1 = events.hard_ooms as usize;
+                    scalar.arg2 = events.integrity_retries as usize;
+                    scalar.arg3 = events.steal_rejected as usize;
+                    scalar.arg4 = 0;
+                }
+            }
+            Some(Opcode::Advise) | Some(Opcode::AdviseProcess) => {
+                let pid = msg.sender.pid();
+                if let Some(scalar) = msg.body.scalar_message_mut() {
+                    let advice: Option<SwapAdvice> = FromPrimitive::from_usize(scalar.arg3);
+                    let ss = sss.inner.as_mut().unwrap();
+                    let rpt = unsafe {
+                        core::slice::from_raw_parts(
+                            SWAP_RPT_VADDR as *const SwapAlloc,
+                            ss.sram_size / PAGE_SIZE,
+                        )
+                    };
+                    let result = match (pid, advice, ss.repl.as_mut()) {
+                        (Some(pid), Some(advice), Some(repl)) => {
+                            repl.advice.forget_exited(rpt);
+                            if advice == SwapAdvice::Wired {
+                                log::info!("PID {} wiring {:x}+{:x}", pid.get(), scalar.arg1, scalar.arg2);
+                            }
+                            if let Some(Opcode::Advise) = op {
+                                repl.advice.advise(pid.get(), scalar.arg1, scalar.arg2, advice)
+                            } else {
+                                repl.advice.advise_process(pid.get(), advice)
+                            }
+                        }
+                        (_, None, _) => Err(xous::Error::InvalidLimit),
+                        _ => Err(xous::Error::InternalError),
+                    };
+                    if let Err(e) = result {
+                        log::warn!("Couldn't apply swap advice: {:?}", e);
+                    }
+                    scalar.arg1 = match result {
+                        Ok(()) => 0,
+                        Err(e) => e.to_usize(),
+                    };
+                }
+            }
+            Some(Opcode::SetPolicy) => {
+                if let Some(scalar) = msg.body.scalar_message_mut() {
+                    let repl = sss.inner.as_mut().unwrap().repl.as_mut().unwrap();
+                    let previous = repl.kind;
+                    if let Some(kind) = FromPrimitive::from_usize(scalar.arg1) {
+                        log::info!("Replacement policy {:?} -> {:?}", previous, kind);
+                        repl.kind = kind;
+                    }
+                    scalar.arg1 = previous as usize;
+                }
+            }
             #[cfg(feature = "swap-userspace-testing")]
             Some(Opcode::Test0) => {
                 log::info!("Free mem: {}kiB", get_free_pages() * PAGE_SIZE / 1024);
@@ -55,7 +55,8 @@ impl SwapHal {
         }
     }
 
-    /// `buf` contents are replaced with encrypted data
+    /// `buf` contents are replaced with encrypted data. `buf` may be shorter than a page if it
+    /// holds a compressed page, in which case fewer bytes are sent over the SPIM bus.
     pub fn encrypt_swap_to(
         &mut self,
         buf: &mut [u8],
@@ -64,7 +65,7 @@ impl SwapHal {
         src_vaddr: usize,
         src_pid: u8,
     ) {
-        assert!(buf.len() == PAGE_SIZE);
+        assert!(buf.len() <= PAGE_SIZE);
         assert!(dest_offset & (PAGE_SIZE - 1) == 0);
         let mut nonce = [0u8; size_of::<Nonce>()];
         nonce[0..4].copy_from_slice(&swap_count.to_be_bytes()); // this is the `swap_count` field
@@ -73,7 +74,8 @@ impl SwapHal {
         nonce[6..9].copy_from_slice(&(ppage_masked as u32).to_be_bytes()[..3]);
         let vpage_masked = src_vaddr & !(PAGE_SIZE - 1);
         nonce[9..12].copy_from_slice(&(vpage_masked as u32).to_be_bytes()[..3]);
-        let aad: &[u8] = &[];
+        let (aad_bytes, aad_len) = crate::platform::swap_aad(buf.len());
+        let aad: &[u8] = &aad_bytes[..aad_len];
         match self.cipher.encrypt_in_place_detached(Nonce::from_slice(&nonce), aad, buf) {
             Ok(tag) => {
                 self.ram_spim.mem_ram_write(dest_offset as u32, buf, false);
@@ -88,7 +90,8 @@ impl SwapHal {
     }
 
     /// Swap is assumed to start at offset 0 in the target device, allowing src_offset to be used
-    /// by the offset tracker (outside this crate) directly
+    /// by the offset tracker (outside this crate) directly. The length of `buf` must match the
+    /// length it was encrypted with.
     pub fn decrypt_swap_from(
         &mut self,
         buf: &mut [u8],
@@ -98,7 +101,7 @@ impl SwapHal {
         dst_pid: u8,
     ) -> Result<(), Error> {
         assert!(src_offset & (PAGE_SIZE - 1) == 0);
-        assert!(buf.len() == PAGE_SIZE);
+        assert!(buf.len() <= PAGE_SIZE);
         let mut nonce = [0u8; size_of::<Nonce>()];
         nonce[0..4].copy_from_slice(&swap_count.to_be_bytes()); // this is the `swap_count` field
         nonce[5] = dst_pid;
@@ -106,7 +109,8 @@ impl SwapHal {
         nonce[6..9].copy_from_slice(&(ppage_masked as u32).to_be_bytes()[..3]);
         let vpage_masked = dst_vaddr & !(PAGE_SIZE - 1);
         nonce[9..12].copy_from_slice(&(vpage_masked as u32).to_be_bytes()[..3]);
-        let aad: &[u8] = &[];
+        let (aad_bytes, aad_len) = crate::platform::swap_aad(buf.len());
+        let aad: &[u8] = &aad_bytes[..aad_len];
         let mut tag = [0u8; size_of::<Tag>()];
         if !self.ram_spim.mem_read(
             (self.swap_mac_start + (src_offset / PAGE_SIZE) * size_of::<Tag>()) as u32,
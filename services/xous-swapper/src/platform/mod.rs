@@ -7,3 +7,10 @@ pub use cramium::hw::*;
 pub mod precursor;
 #[cfg(any(feature = "precursor", feature = "renode"))]
 pub use precursor::hw::*;
+
+/// Additional authenticated data for a page stored in swap. Compressed pages bind their
+/// ciphertext length, so that a truncated or extended page fails authentication. Full pages
+/// have no AAD, which leaves their encoding unchanged from before compression was introduced.
+pub fn swap_aad(len: usize) -> ([u8; 2], usize) {
+    if len < PAGE_SIZE { ((len as u16).to_le_bytes(), 2) } else { ([0u8; 2], 0) }
+}
@@ -37,7 +37,7 @@ impl SwapHal {
     }
 
     /// The data to be encrypted is provided in `buf`, and is replaced with part of the encrypted data upon
-    /// completion of the routine.
+    /// completion of the routine. `buf` may be shorter than a page if it holds a compressed page.
     pub fn encrypt_swap_to(
         &mut self,
         buf: &mut [u8],
@@ -57,7 +57,7 @@ impl SwapHal {
             )
             .ok();
         */
-        assert!(buf.len() == PAGE_SIZE);
+        assert!(buf.len() <= PAGE_SIZE);
         assert!(dest_offset & (PAGE_SIZE - 1) == 0);
         let mut nonce = [0u8; size_of::<Nonce>()];
         nonce[0..4].copy_from_slice(&swap_count.to_be_bytes()); // this is the `swap_count` field
@@ -66,12 +66,13 @@ impl SwapHal {
         nonce[6..9].copy_from_slice(&(ppage_masked as u32).to_be_bytes()[..3]);
         let vpage_masked = src_vaddr & !(PAGE_SIZE - 1);
         nonce[9..12].copy_from_slice(&(vpage_masked as u32).to_be_bytes()[..3]);
-        let aad: &[u8] = &[];
+        let (aad_bytes, aad_len) = crate::platform::swap_aad(buf.len());
+        let aad: &[u8] = &aad_bytes[..aad_len];
         // writeln!(DebugUart {}, "bef enc: nonce {:x?} aad {:x?} buf {:x?}", &nonce, aad, &buf[..32]).ok();
         match self.cipher.encrypt_in_place_detached(Nonce::from_slice(&nonce), aad, buf) {
             Ok(tag) => {
                 // writeln!(DebugUart {}, "Nonce: {:x?}, tag: {:x?}", &nonce, tag.as_slice()).ok();
-                self.dst_data_area[dest_offset..dest_offset + PAGE_SIZE].copy_from_slice(buf);
+                self.dst_data_area[dest_offset..dest_offset + buf.len()].copy_from_slice(buf);
                 let mac_offset = (dest_offset / PAGE_SIZE) * size_of::<Tag>();
                 self.dst_mac_area[mac_offset..mac_offset + size_of::<Tag>()].copy_from_slice(tag.as_slice());
                 // writeln!(DebugUart {}, "dst_mac_area: {:x?}", &self.dst_mac_area[..32]).ok();
@@ -85,7 +86,8 @@ impl SwapHal {
 
     /// Used to examine contents of swap RAM. Decrypted data is returned as a slice.
     /// Swap is a 0-offset slice, allowing src_offset to be used by the offset
-    /// tracker (outside this crate) directly
+    /// tracker (outside this crate) directly. The length of `buf` must match the length it was
+    /// encrypted with.
     pub fn decrypt_swap_from(
         &mut self,
         buf: &mut [u8],
@@ -100,7 +102,7 @@ impl SwapHal {
         // writeln!(DebugUart {}, "  offset: {:x}, vaddr: {:x}, pid: {}", src_offset, dst_vaddr,
         // dst_pid).ok();
         assert!(src_offset & (PAGE_SIZE - 1) == 0);
-        assert!(buf.len() == PAGE_SIZE);
+        assert!(buf.len() <= PAGE_SIZE);
 
         let mut nonce = [0u8; size_of::<Nonce>()];
         nonce[0..4].copy_from_slice(&swap_count.to_be_bytes()); // this is the `swap_count` field
@@ -109,12 +111,13 @@ impl SwapHal {
         nonce[6..9].copy_from_slice(&(ppage_masked as u32).to_be_bytes()[..3]);
         let vpage_masked = dst_vaddr & !(PAGE_SIZE - 1);
         nonce[9..12].copy_from_slice(&(vpage_masked as u32).to_be_bytes()[..3]);
-        let aad: &[u8] = &[];
+        let (aad_bytes, aad_len) = crate::platform::swap_aad(buf.len());
+        let aad: &[u8] = &aad_bytes[..aad_len];
         let mut tag = [0u8; size_of::<Tag>()];
         let mac_offset = (src_offset / PAGE_SIZE) * size_of::<Tag>();
         tag.copy_from_slice(&self.dst_mac_area[mac_offset..mac_offset + size_of::<Tag>()]);
         // writeln!(DebugUart {}, "dst_mac_area: {:x?}", &self.dst_mac_area[..32]).ok();
-        buf.copy_from_slice(&self.dst_data_area[src_offset..src_offset + PAGE_SIZE]);
+        buf.copy_from_slice(&self.dst_data_area[src_offset..src_offset + buf.len()]);
         // writeln!(DebugUart {}, "Nonce: {:x?}, tag: {:x?}", &nonce, &tag).ok();
         let result =
             self.cipher.decrypt_in_place_detached(Nonce::from_slice(&nonce), aad, buf, (&tag).into());
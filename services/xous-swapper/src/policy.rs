@@ -0,0 +1,345 @@
+//! Page replacement policies.
+//!
+//! On a hard OOM, every page in the `MEMORY_ALLOCATIONS` table is ranked by the selected
+//! `ReplacementPolicy`, the rank is adjusted by any advice the owning process has given about the
+//! page, and the page is pushed onto a heap from which pages are evicted lowest `Rank` first.
+//!
+//! All of the storage here is allocated by the main thread before it is handed to the blocking swap
+//! handler, which must not allocate. Advice is recorded by the main thread, which can be interrupted
+//! by the handler at any point; so an advice entry is only marked valid once it has been fully
+//! written, and is marked invalid before it is overwritten.
+
+use core::cmp::Ordering;
+use core::sync::atomic::{Ordering as AtomicOrdering, compiler_fence};
+
+use loader::swap::SwapAlloc;
+use xous_swapper::{PolicyKind, SwapAdvice};
+
+use crate::platform::PAGE_SIZE;
+
+/// Maximum number of address ranges that can be advised across all processes
+pub const MAX_ADVICE: usize = 32;
+
+/// Tier for pages advised `Cold`, evicted before any other page
+const TIER_COLD: u8 = 0;
+/// Pages without advice are ranked by the policy in tiers starting from here
+const TIER_NORMAL: u8 = 1;
+/// Tier for pages advised `Hot`, evicted only once every other page is gone
+const TIER_HOT: u8 = u8::MAX;
+
+/// The eviction order of a page: pages are evicted in ascending order of `tier`, and then of `order`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Rank {
+    pub tier: u8,
+    pub order: u32,
+}
+
+pub trait ReplacementPolicy {
+    /// Called at the start of every hard OOM, before any pages are ranked
+    fn prepare(&mut self, _rpt: &[SwapAlloc]) {}
+    /// Ranks the page held in physical `frame`, as described by `entry`. Policies may use at most
+    /// two tiers, 0 and 1.
+    fn rank(&mut self, frame: usize, entry: &SwapAlloc) -> Rank;
+    /// Called for every page that is evicted
+    fn evicted(&mut self, _frame: usize) {}
+}
+
+/// Least-recently-used, going by the epoch timestamp of the page. This is the original swapper
+/// policy, and the one used until the main thread has set up the other policies.
+pub struct LruEpoch {}
+impl ReplacementPolicy for LruEpoch {
+    fn rank(&mut self, _frame: usize, entry: &SwapAlloc) -> Rank {
+        Rank { tier: 0, order: entry.timestamp() }
+    }
+}
+
+/// CLOCK (second chance). There is no hardware reference bit, so a page is considered referenced
+/// if its epoch timestamp has moved since the last sweep. Referenced pages are given a second
+/// chance by ranking them after every unreferenced page; within a tier, pages are taken in the
+/// order they are met by the hand, which advances past every evicted page.
+pub struct Clock {
+    hand: usize,
+    /// the timestamp of each frame at the last sweep
+    seen: Vec<u32>,
+}
+impl Clock {
+    pub fn new(frames: usize) -> Self { Self { hand: 0, seen: vec![0; frames] } }
+}
+impl ReplacementPolicy for Clock {
+    fn rank(&mut self, frame: usize, entry: &SwapAlloc) -> Rank {
+        let frames = self.seen.len();
+        match self.seen.get_mut(frame) {
+            Some(seen) => {
+                let referenced = *seen != entry.timestamp();
+                *seen = entry.timestamp();
+                Rank { tier: referenced as u8, order: ((frame + frames - self.hand) % frames) as u32 }
+            }
+            None => Rank { tier: 1, order: u32::MAX },
+        }
+    }
+
+    fn evicted(&mut self, frame: usize) {
+        if !self.seen.is_empty() {
+            self.hand = (frame + 1) % self.seen.len();
+        }
+    }
+}
+
+/// Working set: pages touched within the most recent `1 / WORKING_SET_DIVISOR` of the epoch span
+/// covered by the allocation table are taken to be the working set, and are only evicted once
+/// every page outside of it is gone. Measuring the window relative to the span keeps it meaningful
+/// across the kernel's renormalization of the epoch.
+#[derive(Default)]
+pub struct WorkingSet {
+    window_start: u32,
+}
+const WORKING_SET_DIVISOR: u32 = 4;
+impl ReplacementPolicy for WorkingSet {
+    fn prepare(&mut self, rpt: &[SwapAlloc]) {
+        let (oldest, newest) = rpt
+            .iter()
+            .filter(|entry| entry.is_valid() && !entry.is_wired())
+            .fold((u32::MAX, 0), |(oldest, newest), entry| {
+                (oldest.min(entry.timestamp()), newest.max(entry.timestamp()))
+            });
+        self.window_start =
+            if newest >= oldest { newest - (newest - oldest) / WORKING_SET_DIVISOR } else { 0 };
+    }
+
+    fn rank(&mut self, _frame: usize, entry: &SwapAlloc) -> Rank {
+        Rank { tier: (entry.timestamp() >= self.window_start) as u8, order: entry.timestamp() }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct RangeAdvice {
+    valid: bool,
+    pid: u8,
+    start: usize,
+    end: usize,
+    advice: SwapAdvice,
+    /// later advice takes precedence over earlier, overlapping advice
+    seq: u32,
+}
+const NO_ADVICE: RangeAdvice =
+    RangeAdvice { valid: false, pid: 0, start: 0, end: 0, advice: SwapAdvice::Normal, seq: 0 };
+
+/// Advice given by processes about their own pages
+pub struct AdviceTable {
+    ranges: [RangeAdvice; MAX_ADVICE],
+    /// advice covering the whole of a process, indexed by PID
+    processes: Vec<SwapAdvice>,
+    seq: u32,
+}
+impl AdviceTable {
+    pub fn new(pid_count: usize) -> Self {
+        Self { ranges: [NO_ADVICE; MAX_ADVICE], processes: vec![SwapAdvice::Normal; pid_count + 1], seq: 0 }
+    }
+
+    /// Records `advice` for `len` bytes at `vaddr` in `pid`. Any earlier advice that falls entirely
+    /// within the range is replaced; advice that partly overlaps it is overridden where they overlap.
+    /// `SwapAdvice::Normal` clears advice for the range.
+    pub fn advise(
+        &mut self,
+        pid: u8,
+        vaddr: usize,
+        len: usize,
+        advice: SwapAdvice,
+    ) -> Result<(), xous::Error> {
+        if vaddr & (PAGE_SIZE - 1) != 0 || len == 0 {
+            return Err(xous::Error::BadAlignment);
+        }
+        let end = vaddr.checked_add(len).ok_or(xous::Error::BadAddress)?;
+        let mut overlapped = false;
+        for range in self.ranges.iter_mut().filter(|range| range.valid && range.pid == pid) {
+            if range.start >= vaddr && range.end <= end {
+                range.valid = false;
+            } else if range.start < end && range.end > vaddr {
+                overlapped = true;
+            }
+        }
+        // `Normal` advice only needs to be recorded where it overrides earlier advice
+        if advice == SwapAdvice::Normal && !overlapped {
+            return Ok(());
+        }
+        let seq = self.seq.wrapping_add(1);
+        let slot = self.ranges.iter_mut().find(|range| !range.valid).ok_or(xous::Error::OutOfMemory)?;
+        *slot = RangeAdvice { valid: false, pid, start: vaddr, end, advice, seq };
+        compiler_fence(AtomicOrdering::Release);
+        slot.valid = true;
+        self.seq = seq;
+        Ok(())
+    }
+
+    /// Records `advice` for all of the pages of `pid` not covered by range advice
+    pub fn advise_process(&mut self, pid: u8, advice: SwapAdvice) -> Result<(), xous::Error> {
+        *self.processes.get_mut(pid as usize).ok_or(xous::Error::ProcessNotFound)? = advice;
+        Ok(())
+    }
+
+    /// Drops the advice of every process that owns no memory. The kernel doesn't tell the swapper when
+    /// a process exits, so this is how its advice is found to be stale; it's run before new advice is
+    /// recorded and at each hard OOM, so that the table doesn't fill up with the advice of processes that
+    /// are gone, and a PID that's reused doesn't inherit it.
+    pub fn forget_exited(&mut self, rpt: &[SwapAlloc]) {
+        let mut live = [false; 256];
+        for entry in rpt.iter().filter(|entry| entry.is_valid()) {
+            live[entry.raw_pid() as usize] = true;
+        }
+        for range in self.ranges.iter_mut().filter(|range| range.valid && !live[range.pid as usize]) {
+            range.valid = false;
+        }
+        for (pid, advice) in self.processes.iter_mut().enumerate() {
+            if !live.get(pid).copied().unwrap_or(false) {
+                *advice = SwapAdvice::Normal;
+            }
+        }
+    }
+
+    pub fn lookup(&self, pid: u8, vaddr: usize) -> SwapAdvice {
+        self.ranges
+            .iter()
+            .filter(|range| range.valid && range.pid == pid && vaddr >= range.start && vaddr < range.end)
+            .max_by_key(|range| range.seq)
+            .map(|range| range.advice)
+            .unwrap_or_else(|| self.processes.get(pid as usize).copied().unwrap_or(SwapAdvice::Normal))
+    }
+}
+
+/// The selected replacement policy, together with the advice that applies on top of it
+pub struct Replacement {
+    pub kind: PolicyKind,
+    lru: LruEpoch,
+    clock: Clock,
+    working_set: WorkingSet,
+    pub advice: AdviceTable,
+}
+impl Replacement {
+    pub fn new(kind: PolicyKind, pid_count: usize, frames: usize) -> Self {
+        Self {
+            kind,
+            lru: LruEpoch {},
+            clock: Clock::new(frames),
+            working_set: WorkingSet::default(),
+            advice: AdviceTable::new(pid_count),
+        }
+    }
+
+    fn policy(&mut self) -> &mut dyn ReplacementPolicy {
+        match self.kind {
+            PolicyKind::LruEpoch => &mut self.lru,
+            PolicyKind::Clock => &mut self.clock,
+            PolicyKind::WorkingSet => &mut self.working_set,
+        }
+    }
+
+    pub fn prepare(&mut self, rpt: &[SwapAlloc]) { self.policy().prepare(rpt) }
+
+    /// Ranks a page for eviction, or returns `None` if its owner has wired it
+    pub fn rank(&mut self, frame: usize, entry: &SwapAlloc) -> Option<Rank> {
+        // the policy sees every page, so that stateful policies keep track of pages that are skipped
+        let rank = self.policy().rank(frame, entry);
+        match self.advice.lookup(entry.raw_pid(), entry.vaddr()) {
+            SwapAdvice::Wired => None,
+            SwapAdvice::Cold => Some(Rank { tier: TIER_COLD, ..rank }),
+            SwapAdvice::Normal => Some(Rank { tier: TIER_NORMAL + rank.tier, ..rank }),
+            SwapAdvice::Hot => Some(Rank { tier: TIER_HOT, ..rank }),
+        }
+    }
+
+    pub fn evicted(&mut self, frame: usize) { self.policy().evicted(frame) }
+}
+
+/// An entry in the hard OOM eviction heap
+#[derive(Clone, Copy)]
+pub struct Candidate {
+    pub rank: Rank,
+    pub frame: u32,
+    pub alloc: SwapAlloc,
+}
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool { self.rank == other.rank }
+}
+impl Eq for Candidate {}
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+impl Ord for Candidate {
+    // BinaryHeap is a max-heap; reverse the order so the lowest rank is popped first
+    fn cmp(&self, other: &Self) -> Ordering { other.rank.cmp(&self.rank) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advice_precedence() {
+        let mut table = AdviceTable::new(4);
+        table.advise_process(3, SwapAdvice::Hot).unwrap();
+        table.advise(3, 0x2000_0000, 0x4000, SwapAdvice::Wired).unwrap();
+        table.advise(3, 0x2000_1000, 0x1000, SwapAdvice::Cold).unwrap();
+        assert_eq!(table.lookup(3, 0x2000_0000), SwapAdvice::Wired);
+        assert_eq!(table.lookup(3, 0x2000_1000), SwapAdvice::Cold);
+        assert_eq!(table.lookup(3, 0x2000_4000), SwapAdvice::Hot);
+        assert_eq!(table.lookup(2, 0x2000_0000), SwapAdvice::Normal);
+        // partly overlapping advice is overridden where it overlaps
+        table.advise(3, 0x2000_0000, 0x2000, SwapAdvice::Normal).unwrap();
+        assert_eq!(table.lookup(3, 0x2000_0000), SwapAdvice::Normal);
+        assert_eq!(table.lookup(3, 0x2000_2000), SwapAdvice::Wired);
+        // advice that is entirely covered is replaced
+        table.advise(3, 0x2000_0000, 0x4000, SwapAdvice::Normal).unwrap();
+        assert_eq!(table.lookup(3, 0x2000_2000), SwapAdvice::Hot);
+        assert!(table.ranges.iter().all(|range| !range.valid));
+    }
+
+    #[test]
+    fn advice_limits() {
+        let mut table = AdviceTable::new(4);
+        assert_eq!(table.advise(3, 0x2000_0800, 0x1000, SwapAdvice::Hot), Err(xous::Error::BadAlignment));
+        assert_eq!(table.advise(3, 0xFFFF_F000, usize::MAX, SwapAdvice::Hot), Err(xous::Error::BadAddress));
+        assert_eq!(table.advise_process(9, SwapAdvice::Hot), Err(xous::Error::ProcessNotFound));
+        for i in 0..MAX_ADVICE {
+            table.advise(3, i * PAGE_SIZE, PAGE_SIZE, SwapAdvice::Cold).unwrap();
+        }
+        assert_eq!(
+            table.advise(3, MAX_ADVICE * PAGE_SIZE, PAGE_SIZE, SwapAdvice::Cold),
+            Err(xous::Error::OutOfMemory)
+        );
+    }
+
+    #[test]
+    fn advice_of_exited_processes() {
+        let mut table = AdviceTable::new(4);
+        table.advise_process(2, SwapAdvice::Hot).unwrap();
+        table.advise_process(3, SwapAdvice::Hot).unwrap();
+        for i in 0..MAX_ADVICE {
+            table.advise(3, i * PAGE_SIZE, PAGE_SIZE, SwapAdvice::Wired).unwrap();
+        }
+        // only PID 2 still owns a page
+        let rpt = [SwapAlloc::from(2)];
+        table.forget_exited(&rpt);
+        assert_eq!(table.lookup(2, 0), SwapAdvice::Hot);
+        assert_eq!(table.lookup(3, 0), SwapAdvice::Normal);
+        assert!(table.ranges.iter().all(|range| !range.valid));
+        table.advise(3, 0, PAGE_SIZE, SwapAdvice::Cold).unwrap();
+    }
+
+    #[test]
+    fn candidate_order() {
+        let mut heap = std::collections::BinaryHeap::new();
+        for &(tier, order) in [(1, 5), (0, 9), (1, 2), (TIER_HOT, 0)].iter() {
+            heap.push(Candidate { rank: Rank { tier, order }, frame: 0, alloc: SwapAlloc::from(3) });
+        }
+        let popped: Vec<Rank> = std::iter::from_fn(|| heap.pop().map(|c| c.rank)).collect();
+        assert_eq!(
+            popped,
+            [
+                Rank { tier: 0, order: 9 },
+                Rank { tier: 1, order: 2 },
+                Rank { tier: 1, order: 5 },
+                Rank { tier: TIER_HOT, order: 0 }
+            ]
+        );
+    }
+}
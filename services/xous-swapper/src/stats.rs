@@ -0,0 +1,106 @@
+//! Swap accounting. All of the storage here is allocated by the main thread before it is handed to
+//! the blocking swap handler, which must not allocate. The handler updates the counters; the main
+//! thread only reads them in response to a query, so the occasional torn read of a counter that is
+//! being updated is tolerated in exchange for not having to lock inside the handler.
+
+use crate::platform::PAGE_SIZE;
+
+/// Per-process swap statistics
+#[derive(Default, Clone, Copy, Debug)]
+pub struct ProcessStats {
+    /// pages written out to swap
+    pub pages_out: u32,
+    /// pages faulted back in from swap
+    pub faults: u32,
+    /// pages currently resident in swap. This can drift upwards over time because
+    /// lent pages that are swapped out and then released are not reported to the swapper.
+    pub swapped: u32,
+    /// total cycles spent servicing faults, see `cycles()`
+    pub latency_total: u64,
+    /// worst-case cycles spent servicing a single fault
+    pub latency_max: u32,
+}
+
+/// Swap-wide event counters
+#[derive(Default, Clone, Copy, Debug)]
+pub struct SwapEvents {
+    /// number of hard OOM invocations
+    pub hard_ooms: u32,
+    /// pages whose MAC did not match on read, but which decrypted correctly on a retry
+    pub integrity_retries: u32,
+    /// candidate pages that could not be stolen from their owner for eviction
+    pub steal_rejected: u32,
+}
+
+pub struct SwapAccounting {
+    /// indexed by PID
+    pub processes: Vec<ProcessStats>,
+    /// ciphertext length for each swap slot; 0 indicates an uncompressed page
+    pub slot_len: Vec<u16>,
+    /// scratch space for page (de)compression
+    pub scratch: Vec<u8>,
+    pub events: SwapEvents,
+}
+impl SwapAccounting {
+    pub fn new(pid_count: usize, slot_count: usize) -> Self {
+        Self {
+            processes: vec![ProcessStats::default(); pid_count + 1],
+            slot_len: vec![0; slot_count],
+            scratch: vec![0; PAGE_SIZE],
+            events: SwapEvents::default(),
+        }
+    }
+
+    pub fn process(&mut self, pid: u8) -> Option<&mut ProcessStats> { self.processes.get_mut(pid as usize) }
+
+    pub fn record_out(&mut self, pid: u8, slot: usize, len: usize) {
+        if let Some(stats) = self.process(pid) {
+            stats.pages_out = stats.pages_out.wrapping_add(1);
+            stats.swapped = stats.swapped.saturating_add(1);
+        }
+        if let Some(slot_len) = self.slot_len.get_mut(slot) {
+            *slot_len = if len < PAGE_SIZE { len as u16 } else { 0 };
+        }
+    }
+
+    pub fn record_fault(&mut self, pid: u8, cycles: u32) {
+        if let Some(stats) = self.process(pid) {
+            stats.faults = stats.faults.wrapping_add(1);
+            stats.swapped = stats.swapped.saturating_sub(1);
+            stats.latency_total = stats.latency_total.wrapping_add(cycles as u64);
+            stats.latency_max = stats.latency_max.max(cycles);
+        }
+    }
+
+    /// The length of the ciphertext stored in `slot`
+    pub fn stored_len(&self, slot: usize) -> usize {
+        match self.slot_len.get(slot) {
+            Some(&len) if len != 0 => len as usize,
+            _ => PAGE_SIZE,
+        }
+    }
+
+    /// Returns (compressed pages, bytes saved) over the slots that are marked as used in `counts`
+    pub fn compression(&self, counts: &[u32]) -> (usize, usize) {
+        self.slot_len.iter().zip(counts.iter()).filter(|(_, &count)| count & loader::FLG_SWAP_USED != 0).fold(
+            (0, 0),
+            |(pages, saved), (&len, _)| {
+                if len != 0 { (pages + 1, saved + PAGE_SIZE - len as usize) } else { (pages, saved) }
+            },
+        )
+    }
+}
+
+/// A free-running cycle count used to measure fault latency, which has to be read without
+/// making any calls to other processes. Reading the `cycle` CSR traps on cores that do not
+/// expose it to user mode, so it is only used if the `swap-latency` feature is selected;
+/// otherwise latency reads as 0.
+#[cfg(all(feature = "swap-latency", target_arch = "riscv32"))]
+pub fn cycles() -> u32 {
+    let cycles: u32;
+    // safety: reads a CSR into a register, with no other side effects
+    unsafe { core::arch::asm!("rdcycle {0}", out(reg) cycles) };
+    cycles
+}
+#[cfg(not(all(feature = "swap-latency", target_arch = "riscv32")))]
+pub fn cycles() -> u32 { 0 }
@@ -0,0 +1,17 @@
+#![cfg_attr(rustfmt, rustfmt_skip)]
+// Versioning information is kept in a separate file, attached to a small, well-known server in the Xous System
+// This is a trade-off between rebuild times and flexibility.
+// This was autogenerated by xtask/src/main.rs:print_header(). Do not edit manually.
+
+pub(crate) fn get_version() -> crate::api::VersionString {
+    let mut v = crate::api::VersionString {
+        version: String::new()
+    };
+    v.version.push_str(SEMVER);
+    v.version.push_str("\n");
+    v.version.push_str(TIMESTAMP);
+    v
+}
+#[allow(dead_code)]
+pub const TIMESTAMP: &'static str = "Mon, 19 Oct 2026 04:43:23 +0000";
+pub const SEMVER: &'static str = "";
@@ -25,8 +25,9 @@ class XousAppServer(BaseHTTPRequestHandler):
 
             self.wfile.write(l.encode())
         else:
+            # apps are served as signed packages, made by `cargo run --bin sign-app`
             name = self.context_to_app[unquote(path)]
-            with open('target/{}/{}/{}'.format(self.target, self.profile, name), 'rb') as f:
+            with open('target/{}/{}/{}.xapp'.format(self.target, self.profile, name), 'rb') as f:
                 data = f.read()
                 self.send_response(200)
                 self.send_header('Content-Type', 'application/octet-stream')
@@ -0,0 +1,121 @@
+use std::io::Write;
+
+use clap::{App, Arg, crate_version};
+use ed25519_dalek::SigningKey;
+use pkcs8::PrivateKeyInfo;
+use pkcs8::der::Decodable;
+use tools::sign_image::load_pem;
+
+// the package format is defined by the app loader, which checks what this writes
+#[allow(dead_code)]
+#[path = "../../../apps/app-loader/src/package.rs"]
+mod package;
+
+const DEVKEY_PATH: &str = "devkey/dev.key";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let matches = App::new("sign-app")
+        .version(crate_version!())
+        .about("Package and sign an app for the Precursor app loader")
+        .arg(
+            Arg::with_name("elf")
+                .long("elf")
+                .help("app ELF, as built by `cargo xtask compile-apps`")
+                .value_name("elf")
+                .takes_value(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("name")
+                .long("name")
+                .help("context name of the app, as in apps/manifest.json")
+                .value_name("name")
+                .takes_value(true)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("app-version")
+                .long("app-version")
+                .help("version shown to the user when installing")
+                .value_name("version")
+                .takes_value(true)
+                .default_value("0.1.0"),
+        )
+        .arg(
+            Arg::with_name("submenus")
+                .long("submenus")
+                .help("number of submenus the app registers, as in apps/manifest.json")
+                .value_name("count")
+                .takes_value(true)
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("permission")
+                .long("permission")
+                .help("a permission the app asks for; may be repeated")
+                .value_name("permission")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("key")
+                .long("key")
+                .help("app signing key")
+                .value_name("signing key")
+                .takes_value(true)
+                .default_value(DEVKEY_PATH),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .help("package to write; defaults to the ELF's path with an .xapp extension")
+                .value_name("output")
+                .takes_value(true),
+        )
+        .get_matches();
+
+    let elf_path = matches.value_of("elf").unwrap();
+    let elf = std::fs::read(elf_path)?;
+
+    let pkey = load_pem(matches.value_of("key").unwrap())?;
+    if pkey.tag != "PRIVATE KEY" {
+        println!("App key was a {}, not a PRIVATE KEY", pkey.tag);
+        Err("invalid app private key type")?;
+    }
+    let private_key = PrivateKeyInfo::from_der(&pkey.contents).map_err(|e| format!("{}", e))?;
+    // First 2 bytes of the `private_key` are a record specifier and length field. Check they are correct.
+    if private_key.private_key.len() != 34
+        || private_key.private_key[0] != 0x4
+        || private_key.private_key[1] != 0x20
+    {
+        Err("app key is not an ed25519 key")?;
+    }
+    let mut secbytes = [0u8; 32];
+    secbytes.copy_from_slice(&private_key.private_key[2..]);
+    let signing_key = SigningKey::from_bytes(&secbytes);
+
+    let manifest = package::Manifest {
+        name: matches.value_of("name").unwrap().to_string(),
+        version: matches.value_of("app-version").unwrap().to_string(),
+        submenus: matches.value_of("submenus").unwrap().parse()?,
+        permissions: matches
+            .values_of("permission")
+            .map(|p| p.map(String::from).collect())
+            .unwrap_or_default(),
+    };
+    let data = package::build(&manifest, &elf, &signing_key);
+
+    let output = match matches.value_of("output") {
+        Some(output) => output.to_string(),
+        None => format!("{}.xapp", elf_path),
+    };
+    std::fs::File::create(&output)?.write_all(&data)?;
+    println!(
+        "Wrote {} ({} bytes), signed by {}",
+        output,
+        data.len(),
+        package::fingerprint(signing_key.verifying_key().as_bytes())
+    );
+    Ok(())
+}
@@ -15,6 +15,9 @@ struct AppManifest {
     context_name: String,
     menu_name: HashMap<String, HashMap<String, String>>,
     submenu: Option<u8>,
+    /// What the app may use: `network`, `usb-hid`, `audio`, `camera`, `pddb-dict:<name>` and
+    /// `pddb-basis:<name>`, where a name may end in `*`. Services check these at runtime.
+    capabilities: Option<Vec<String>>,
 }
 #[derive(Deserialize, Serialize, Debug)]
 struct Locales {
@@ -92,6 +95,18 @@ pub(crate) fn generate_app_menus(apps: &Vec<String>) {
         }
     }
     writeln!(gam_tokens, "];").unwrap();
+    writeln!(gam_tokens, "\npub const APP_CAPABILITIES: &[(&'static str, &[&'static str])] = &[").unwrap();
+    for (app_name, manifest) in working_set.iter() {
+        let capabilities = manifest.capabilities.as_deref().unwrap_or(&[]);
+        writeln!(
+            gam_tokens,
+            "    (APP_NAME_{}, &[{}]),",
+            app_name.to_uppercase().replace('-', "_"),
+            capabilities.iter().map(|c| format!("\"{}\"", c)).collect::<Vec<String>>().join(", "),
+        )
+        .unwrap();
+    }
+    writeln!(gam_tokens, "];").unwrap();
     overwrite_if_changed(&gam_tokens, "services/gam/src/apps.rs");
 
     // construct the app menu
@@ -155,7 +170,7 @@ pub(crate) fn app_index_to_name(index: usize) -> Result<&'static str, AppDispatc
     .unwrap();
     for (index, (_, _manifest)) in working_set.iter().enumerate() {
         for name in _manifest.menu_name.keys() {
-            writeln!(menu, "        {} => Ok(t!(\"{}\", locales::LANG)),", index, name,).unwrap();
+            writeln!(menu, "        {} => Ok(t!(\"{}\", locales::lang())),", index, name,).unwrap();
         }
     }
     writeln!(
@@ -172,7 +187,7 @@ pub(crate) fn app_menu_items(menu_items: &mut Vec::<MenuItem>, status_conn: u32)
         writeln!(menu, "    menu_items.push(MenuItem {{",).unwrap();
         assert_eq!(manifest.menu_name.len(), 1, "Improper menu name record entry");
         for name in manifest.menu_name.keys() {
-            writeln!(menu, "        name: String::from(t!(\"{}\", locales::LANG)),", name).unwrap();
+            writeln!(menu, "        name: String::from(t!(\"{}\", locales::lang())),", name).unwrap();
         }
         writeln!(menu, "        action_conn: Some(status_conn),",).unwrap();
         writeln!(menu, "        action_opcode: StatusOpcode::SwitchToApp.to_u32().unwrap(),",).unwrap();
@@ -0,0 +1,1124 @@
+// IPC interface checker
+//
+// Opcodes are numbered by hand in `#[derive(FromPrimitive)]` enums, and messages travel as rkyv- or
+// flatipc-encoded structures whose layout both ends have to agree on. The compiler checks neither
+// across crates: a discriminant that is only duplicated under a particular combination of `cfg` flags
+// builds fine until somebody enables both, and a server that renumbers an opcode or reorders a message
+// field keeps building while its clients silently go out of step.
+//
+// `cargo xtask ipc-check` scans the tracked sources with a small lexer -- just enough Rust to find items
+// and their attributes, so xtask doesn't have to carry a full parser -- and reports:
+//
+//   - duplicate discriminants within an opcode enum, including ones that are separated by `cfg` gates
+//   - implicit discriminants whose value shifts depending on whether an earlier `cfg`-gated variant exists
+//
+// Given `--base <rev>`, it also extracts the interfaces at that revision, compares them against
+// `--head <rev>` (or the working tree, if no head is given) and reports ABI-breaking changes: opcodes that
+// were removed or renumbered, opcode values that now mean something else, and message types whose layout
+// changed.
+//
+// Problems that have been looked at and accepted are listed in `ALLOWED`, each with its reason; they are
+// still printed, but don't fail the check.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Command, Stdio};
+
+use crate::{DynError, project_root};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Literal(String),
+    Punct(char),
+}
+
+/// Splits Rust source into identifiers, literals and single-character punctuation, dropping comments.
+/// Multi-character operators come out as runs of `Punct`, which is all the item scanner needs.
+fn tokenize(src: &str) -> Vec<Token> {
+    let chars: Vec<char> = src.chars().collect();
+    let len = chars.len();
+    let at = |i: usize| chars.get(i).copied().unwrap_or('\0');
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < len {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && at(i + 1) == '/' {
+            while i < len && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && at(i + 1) == '*' {
+            let mut depth = 0;
+            while i < len {
+                if chars[i] == '/' && at(i + 1) == '*' {
+                    depth += 1;
+                    i += 2;
+                } else if chars[i] == '*' && at(i + 1) == '/' {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+        } else if (c == 'r' || (c == 'b' && at(i + 1) == 'r')) && raw_string_start(&chars, i).is_some() {
+            let (hashes, mut j) = raw_string_start(&chars, i).unwrap();
+            while j < len {
+                if chars[j] == '"' && (1..=hashes).all(|h| at(j + h) == '#') {
+                    j += 1 + hashes;
+                    break;
+                }
+                j += 1;
+            }
+            let j = j.min(len);
+            tokens.push(Token::Literal(chars[i..j].iter().collect()));
+            i = j;
+        } else if c == '"' || (c == 'b' && at(i + 1) == '"') {
+            let mut j = if c == 'b' { i + 2 } else { i + 1 };
+            while j < len && chars[j] != '"' {
+                if chars[j] == '\\' {
+                    j += 1;
+                }
+                j += 1;
+            }
+            let j = (j + 1).min(len);
+            tokens.push(Token::Literal(chars[i..j].iter().collect()));
+            i = j;
+        } else if c == '\'' || (c == 'b' && at(i + 1) == '\'') {
+            let mut j = if c == 'b' { i + 2 } else { i + 1 };
+            if at(j) == '\\' {
+                j += 2;
+                while j < len && chars[j] != '\'' {
+                    j += 1;
+                }
+            } else if at(j + 1) == '\'' {
+                j += 1;
+            } else {
+                // a lifetime; the identifier that follows is lexed on its own
+                tokens.push(Token::Punct('\''));
+                i += 1;
+                continue;
+            }
+            let j = (j + 1).min(len);
+            tokens.push(Token::Literal(chars[i..j].iter().collect()));
+            i = j;
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < len && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < len
+                && (chars[i].is_alphanumeric()
+                    || chars[i] == '_'
+                    || (chars[i] == '.' && at(i + 1).is_ascii_digit()))
+            {
+                i += 1;
+            }
+            tokens.push(Token::Literal(chars[start..i].iter().collect()));
+        } else {
+            tokens.push(Token::Punct(c));
+            i += 1;
+        }
+    }
+    tokens
+}
+
+/// If a raw string literal starts at `i`, returns its number of `#`s and the index just past the
+/// opening quote.
+fn raw_string_start(chars: &[char], i: usize) -> Option<(usize, usize)> {
+    let mut j = if chars[i] == 'b' { i + 2 } else { i + 1 };
+    let mut hashes = 0;
+    while chars.get(j) == Some(&'#') {
+        hashes += 1;
+        j += 1;
+    }
+    if chars.get(j) == Some(&'"') { Some((hashes, j + 1)) } else { None }
+}
+
+/// Renders tokens back to text with whitespace normalized, so two spellings of the same
+/// declaration compare equal.
+fn render(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    let mut prev_word = false;
+    for token in tokens {
+        match token {
+            Token::Ident(s) | Token::Literal(s) => {
+                if prev_word {
+                    out.push(' ');
+                }
+                out.push_str(s);
+                prev_word = true;
+            }
+            Token::Punct(c) => {
+                out.push(*c);
+                prev_word = false;
+            }
+        }
+    }
+    out
+}
+
+fn is_punct(token: Option<&Token>, c: char) -> bool { token == Some(&Token::Punct(c)) }
+
+fn is_ident(token: Option<&Token>, name: &str) -> bool { matches!(token, Some(Token::Ident(s)) if s == name) }
+
+/// Index of the delimiter closing the group opened at `open`.
+fn group_end(tokens: &[Token], open: usize) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(open) {
+        match token {
+            Token::Punct('(') | Token::Punct('[') | Token::Punct('{') => depth += 1,
+            Token::Punct(')') | Token::Punct(']') | Token::Punct('}') => {
+                depth -= 1;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len().saturating_sub(1)
+}
+
+/// Splits the contents of a group at its top-level commas. Struct fields can carry generic types, so
+/// `angles` also treats `<...>` as nesting; enum bodies leave it off so that shifts in discriminant
+/// expressions aren't mistaken for generics.
+fn split_top_level(tokens: &[Token], angles: bool) -> Vec<&[Token]> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut angle = 0;
+    let mut start = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Punct('(') | Token::Punct('[') | Token::Punct('{') => depth += 1,
+            Token::Punct(')') | Token::Punct(']') | Token::Punct('}') => depth -= 1,
+            Token::Punct('<') if angles && depth == 0 => angle += 1,
+            Token::Punct('>')
+                if angles && depth == 0 && angle > 0 && !is_punct(tokens.get(i.wrapping_sub(1)), '-') =>
+            {
+                angle -= 1
+            }
+            Token::Punct(',') if depth == 0 && angle == 0 => {
+                parts.push(&tokens[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < tokens.len() {
+        parts.push(&tokens[start..]);
+    }
+    parts
+}
+
+/// Peels the outer attributes off the front of `tokens`, returning their rendered contents
+/// (e.g. `cfg(feature="foo")`) and the remainder.
+fn strip_attrs(tokens: &[Token]) -> (Vec<String>, &[Token]) {
+    let mut attrs = Vec::new();
+    let mut i = 0;
+    while is_punct(tokens.get(i), '#') && is_punct(tokens.get(i + 1), '[') {
+        let end = group_end(tokens, i + 1);
+        attrs.push(render(&tokens[i + 2..end]));
+        i = end + 1;
+    }
+    (attrs, &tokens[i..])
+}
+
+/// Drops a leading `pub`, `pub(crate)` or `pub(in path)`.
+fn strip_visibility(tokens: &[Token]) -> &[Token] {
+    if !is_ident(tokens.first(), "pub") {
+        return tokens;
+    }
+    if is_punct(tokens.get(1), '(') {
+        return &tokens[group_end(tokens, 1) + 1..];
+    }
+    &tokens[1..]
+}
+
+fn cfg_of(attrs: &[String]) -> Option<String> {
+    attrs.iter().find(|a| a.starts_with("cfg(")).map(|a| a["cfg(".len()..a.len() - 1].to_string())
+}
+
+/// The final path segment of every trait named in a `#[derive(...)]`.
+fn derives(attrs: &[String]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter(|a| a.starts_with("derive(") && a.ends_with(')'))
+        .flat_map(|a| {
+            a["derive(".len()..a.len() - 1].split(',').map(|d| d.rsplit("::").next().unwrap().to_string())
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Discriminant {
+    Value(u64),
+    /// An expression this checker doesn't evaluate; compared textually.
+    Expr(String),
+}
+
+impl fmt::Display for Discriminant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Discriminant::Value(v) if *v > 0xFFFF => write!(f, "{:#x}", v),
+            Discriminant::Value(v) => write!(f, "{}", v),
+            Discriminant::Expr(e) => write!(f, "`{}`", e),
+        }
+    }
+}
+
+fn parse_int(literal: &str) -> Option<u64> {
+    let digits: String = literal.chars().filter(|&c| c != '_').collect();
+    let (radix, digits) = if let Some(d) = digits.strip_prefix("0x") {
+        (16, d)
+    } else if let Some(d) = digits.strip_prefix("0o") {
+        (8, d)
+    } else if let Some(d) = digits.strip_prefix("0b") {
+        (2, d)
+    } else {
+        (10, &digits[..])
+    };
+    // drop a type suffix such as `u32`; hex digits never include `u` or `i`
+    let end = digits.find(&['u', 'i'][..]).unwrap_or(digits.len());
+    u64::from_str_radix(&digits[..end], radix).ok()
+}
+
+/// Evaluates the forms discriminants take in practice: integer literals, `uN::MAX`, and those combined
+/// with `<<`, `|` and `+`. A trailing cast is ignored. Anything else is kept as an expression.
+fn eval(expr: &[Token]) -> Discriminant {
+    let expr = match expr.iter().position(|t| is_ident(Some(t), "as")) {
+        Some(cast) => &expr[..cast],
+        None => expr,
+    };
+    let atom = |i: usize| -> Option<(u64, usize)> {
+        match (expr.get(i), expr.get(i + 1), expr.get(i + 2), expr.get(i + 3)) {
+            (Some(Token::Literal(l)), ..) => parse_int(l).map(|v| (v, i + 1)),
+            (
+                Some(Token::Ident(ty)),
+                Some(Token::Punct(':')),
+                Some(Token::Punct(':')),
+                Some(Token::Ident(max)),
+            ) if max == "MAX" => {
+                let v = match ty.as_str() {
+                    "u8" => u8::MAX as u64,
+                    "u16" => u16::MAX as u64,
+                    // Xous targets are 32-bit
+                    "u32" | "usize" => u32::MAX as u64,
+                    "u64" => u64::MAX,
+                    _ => return None,
+                };
+                Some((v, i + 4))
+            }
+            _ => None,
+        }
+    };
+    let fallback = Discriminant::Expr(render(expr));
+    let (mut value, mut i) = match atom(0) {
+        Some(a) => a,
+        None => return fallback,
+    };
+    while i < expr.len() {
+        let (op, next) = match (&expr[i], expr.get(i + 1)) {
+            (Token::Punct('<'), Some(Token::Punct('<'))) => ('<', i + 2),
+            (Token::Punct('|'), _) => ('|', i + 1),
+            (Token::Punct('+'), _) => ('+', i + 1),
+            _ => return fallback,
+        };
+        let (rhs, after) = match atom(next) {
+            Some(a) => a,
+            None => return fallback,
+        };
+        value = match op {
+            '<' => value.checked_shl(rhs as u32).unwrap_or(0),
+            '|' => value | rhs,
+            _ => value.wrapping_add(rhs),
+        };
+        i = after;
+    }
+    Discriminant::Value(value)
+}
+
+#[derive(Clone, Debug)]
+struct Variant {
+    name: String,
+    value: Discriminant,
+    cfg: Option<String>,
+    /// Implicitly numbered after a `cfg`-gated variant, so the value depends on the build configuration.
+    shifts_with: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+struct OpcodeEnum {
+    path: String,
+    name: String,
+    variants: Vec<Variant>,
+}
+
+#[derive(Clone, Debug)]
+struct Message {
+    path: String,
+    name: String,
+    /// One entry per field or variant, plus any `repr`, normalized so that layouts compare as strings.
+    layout: Vec<String>,
+}
+
+#[derive(Default, Debug)]
+struct Interfaces {
+    opcodes: Vec<OpcodeEnum>,
+    messages: Vec<Message>,
+}
+
+impl Interfaces {
+    fn scan(&mut self, path: &str, src: &str) {
+        if !(src.contains("FromPrimitive")
+            || src.contains("Archive")
+            || src.contains("Ipc")
+            || src.contains("service"))
+        {
+            return;
+        }
+        let tokens = tokenize(src);
+        let mut attrs: Vec<String> = Vec::new();
+        let mut i = 0;
+        while i < tokens.len() {
+            match &tokens[i] {
+                Token::Punct('#') if is_punct(tokens.get(i + 1), '[') => {
+                    let end = group_end(&tokens, i + 1);
+                    attrs.push(render(&tokens[i + 2..end]));
+                    i = end + 1;
+                }
+                Token::Punct('#') if is_punct(tokens.get(i + 1), '!') => {
+                    // inner attribute: belongs to the enclosing module, not the next item
+                    i = group_end(&tokens, i + 2) + 1;
+                }
+                Token::Ident(kw) if kw == "enum" || kw == "struct" || kw == "trait" => {
+                    let kw = kw.clone();
+                    let name = match tokens.get(i + 1) {
+                        Some(Token::Ident(name)) => name.clone(),
+                        _ => {
+                            i += 1;
+                            continue;
+                        }
+                    };
+                    let item_attrs = std::mem::take(&mut attrs);
+                    i = self.item(path, &kw, name, &item_attrs, &tokens, i + 2);
+                }
+                Token::Punct(';') | Token::Punct('{') | Token::Punct('}') => {
+                    attrs.clear();
+                    i += 1;
+                }
+                _ => i += 1,
+            }
+        }
+    }
+
+    /// Records the item whose name has just been read, returning the index past its body.
+    fn item(
+        &mut self,
+        path: &str,
+        kw: &str,
+        name: String,
+        attrs: &[String],
+        tokens: &[Token],
+        start: usize,
+    ) -> usize {
+        // find the body, stepping over generics and bounds
+        let mut angle = 0;
+        let mut open = start;
+        while open < tokens.len() {
+            match tokens[open] {
+                Token::Punct('<') => angle += 1,
+                Token::Punct('>') if !is_punct(tokens.get(open.wrapping_sub(1)), '-') => angle -= 1,
+                Token::Punct('{') | Token::Punct('(') if angle <= 0 => break,
+                Token::Punct(';') if angle <= 0 => return open + 1,
+                _ => {}
+            }
+            open += 1;
+        }
+        if open >= tokens.len() {
+            return open;
+        }
+        let close = group_end(tokens, open);
+        let body = &tokens[open + 1..close];
+        let derives = derives(attrs);
+        let derived = |t: &str| derives.iter().any(|d| d == t);
+        match kw {
+            "enum" if derived("FromPrimitive") => self.opcodes.push(OpcodeEnum {
+                path: path.into(),
+                name: name.clone(),
+                variants: enum_variants(body),
+            }),
+            "trait" => {
+                if let Some(opcode) = attrs.iter().find_map(|a| service_opcode(a, &name)) {
+                    self.opcodes.push(OpcodeEnum {
+                        path: path.into(),
+                        name: opcode,
+                        variants: service_methods(body),
+                    })
+                }
+            }
+            _ => {}
+        }
+        if (kw == "struct" || kw == "enum") && (derived("Archive") || derived("Ipc")) {
+            let mut layout: Vec<String> = attrs.iter().filter(|a| a.starts_with("repr(")).cloned().collect();
+            let tuple = is_punct(tokens.get(open), '(');
+            for (index, part) in split_top_level(body, kw == "struct").into_iter().enumerate() {
+                let (field_attrs, rest) = strip_attrs(part);
+                let rest = strip_visibility(rest);
+                if rest.is_empty() {
+                    continue;
+                }
+                let text = if tuple { format!("{}: {}", index, render(rest)) } else { render(rest) };
+                layout.push(match cfg_of(&field_attrs) {
+                    Some(cfg) => format!("#[cfg({})] {}", cfg, text),
+                    None => text,
+                });
+            }
+            self.messages.push(Message { path: path.into(), name, layout });
+        }
+        close + 1
+    }
+}
+
+fn enum_variants(body: &[Token]) -> Vec<Variant> {
+    let mut variants: Vec<Variant> = Vec::new();
+    let mut gated_since: Option<String> = None;
+    for part in split_top_level(body, false) {
+        let (attrs, rest) = strip_attrs(part);
+        let name = match rest.first() {
+            Some(Token::Ident(name)) => name.clone(),
+            _ => continue,
+        };
+        let cfg = cfg_of(&attrs);
+        let (value, shifts_with) = match rest.iter().position(|t| *t == Token::Punct('=')) {
+            Some(eq) => {
+                gated_since = None;
+                (eval(&rest[eq + 1..]), None)
+            }
+            None => {
+                let value = match variants.last().map(|v| &v.value) {
+                    None => Discriminant::Value(0),
+                    Some(Discriminant::Value(v)) => Discriminant::Value(v + 1),
+                    Some(Discriminant::Expr(e)) => Discriminant::Expr(format!("{} + 1", e)),
+                };
+                (value, gated_since.clone())
+            }
+        };
+        if cfg.is_some() && gated_since.is_none() {
+            gated_since = Some(name.clone());
+        }
+        variants.push(Variant { name, value, cfg, shifts_with });
+    }
+    variants
+}
+
+/// The name of the opcode enum that the attribute `attr` generates for the trait `name`, if it is
+/// `#[flatipc::service]`: `<name>Opcode`, unless it is named with `opcode = ...`.
+fn service_opcode(attr: &str, name: &str) -> Option<String> {
+    let args = attr.strip_prefix("flatipc::").unwrap_or(attr).strip_prefix("service")?;
+    if args.is_empty() {
+        return Some(format!("{}Opcode", name));
+    }
+    let opcode = args.strip_prefix("(opcode=")?.strip_suffix(')')?;
+    Some(opcode.to_string())
+}
+
+/// Numbers the methods of a `#[flatipc::service]` trait the same way the attribute does: sequentially,
+/// with `(opcode = N)` restarting the count.
+fn service_methods(body: &[Token]) -> Vec<Variant> {
+    let mut variants = Vec::new();
+    let mut next = 0;
+    let mut kind: Option<Option<u64>> = None;
+    let mut i = 0;
+    while i < body.len() {
+        if is_punct(body.get(i), '#') && is_punct(body.get(i + 1), '[') {
+            let end = group_end(body, i + 1);
+            let attr = &body[i + 2..end];
+            if let Some(Token::Ident(k)) = attr.first() {
+                if ["lend", "lend_mut", "scalar", "send"].contains(&k.as_str()) {
+                    let opcode = match attr.iter().position(|t| *t == Token::Punct('=')) {
+                        Some(eq) => attr.get(eq + 1).and_then(|t| match t {
+                            Token::Literal(l) => parse_int(l),
+                            _ => None,
+                        }),
+                        None => None,
+                    };
+                    kind = Some(opcode);
+                }
+            }
+            i = end + 1;
+            continue;
+        }
+        if is_ident(body.get(i), "fn") {
+            if let (Some(Token::Ident(name)), Some(opcode)) = (body.get(i + 1), kind.take()) {
+                let opcode = opcode.unwrap_or(next);
+                variants.push(Variant {
+                    name: camel_case(name),
+                    value: Discriminant::Value(opcode),
+                    cfg: None,
+                    shifts_with: None,
+                });
+                next = opcode + 1;
+            }
+            // skip to the end of the declaration or default body
+            while i < body.len() && !is_punct(body.get(i), ';') && !is_punct(body.get(i), '{') {
+                if is_punct(body.get(i), '(') {
+                    i = group_end(body, i);
+                }
+                i += 1;
+            }
+            if is_punct(body.get(i), '{') {
+                i = group_end(body, i);
+            }
+        }
+        i += 1;
+    }
+    variants
+}
+
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// The crate an interface belongs to, used to match definitions across revisions even if they move
+/// between files.
+fn crate_of(path: &str) -> &str {
+    match path.find("/src/") {
+        Some(pos) => &path[..pos],
+        None => path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or(""),
+    }
+}
+
+fn wanted(path: &str, crates: &[String]) -> bool {
+    path.ends_with(".rs")
+        && (crates.is_empty() || crates.iter().any(|c| crate_of(path).rsplit('/').next() == Some(c.as_str())))
+}
+
+fn git(args: &[&str]) -> Result<String, DynError> {
+    let output = Command::new("git").current_dir(project_root()).args(args).output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+fn working_tree(crates: &[String]) -> Result<Interfaces, DynError> {
+    let mut interfaces = Interfaces::default();
+    for path in git(&["ls-files", "*.rs"])?.lines().filter(|p| wanted(p, crates)) {
+        // files deleted but not yet staged still show up in the index
+        if let Ok(src) = std::fs::read_to_string(project_root().join(path)) {
+            interfaces.scan(path, &src);
+        }
+    }
+    Ok(interfaces)
+}
+
+fn at_revision(rev: &str, crates: &[String]) -> Result<Interfaces, DynError> {
+    let commit = format!("{}^{{commit}}", rev);
+    git(&["rev-parse", "--verify", "--quiet", &commit]).map_err(|_| format!("unknown revision `{}`", rev))?;
+    let mut blobs = Vec::new();
+    for line in git(&["ls-tree", "-r", rev])?.lines() {
+        // <mode> SP <type> SP <object> TAB <path>
+        let (meta, path) = match line.split_once('\t') {
+            Some(entry) => entry,
+            None => continue,
+        };
+        let meta: Vec<&str> = meta.split(' ').collect();
+        if meta.len() == 3 && meta[1] == "blob" && wanted(path, crates) {
+            blobs.push((meta[2].to_string(), path.to_string()));
+        }
+    }
+
+    // stream every blob through a single `git cat-file` rather than spawning one process per file
+    let mut child = Command::new("git")
+        .current_dir(project_root())
+        .args(["cat-file", "--batch"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let ids: Vec<String> = blobs.iter().map(|(id, _)| id.clone()).collect();
+    let writer = std::thread::spawn(move || -> std::io::Result<()> {
+        for id in ids {
+            writeln!(stdin, "{}", id)?;
+        }
+        Ok(())
+    });
+    let mut reader = BufReader::new(child.stdout.take().unwrap());
+    let mut interfaces = Interfaces::default();
+    for (_, path) in blobs.iter() {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let size: usize =
+            header.trim_end().rsplit(' ').next().and_then(|s| s.parse().ok()).ok_or("bad cat-file header")?;
+        let mut contents = vec![0u8; size + 1]; // the object is followed by a newline
+        reader.read_exact(&mut contents)?;
+        contents.truncate(size);
+        interfaces.scan(path, &String::from_utf8_lossy(&contents));
+    }
+    writer.join().unwrap()?;
+    child.wait()?;
+    Ok(interfaces)
+}
+
+/// `cfg(x)` and `cfg(not(x))` can never both be enabled.
+fn exclusive(a: &Option<String>, b: &Option<String>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => *a == format!("not({})", b) || *b == format!("not({})", a),
+        _ => false,
+    }
+}
+
+fn describe(variant: &Variant) -> String {
+    match &variant.cfg {
+        Some(cfg) => format!("`{}` (cfg({}))", variant.name, cfg),
+        None => format!("`{}`", variant.name),
+    }
+}
+
+/// Returns (errors, warnings) found within a single revision.
+fn check_duplicates(interfaces: &Interfaces) -> (Vec<String>, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    for opcodes in interfaces.opcodes.iter() {
+        for (index, a) in opcodes.variants.iter().enumerate() {
+            for b in opcodes.variants[index + 1..].iter() {
+                if a.value == b.value && !exclusive(&a.cfg, &b.cfg) {
+                    errors.push(format!(
+                        "{}: `{}` assigns {} to both {} and {}",
+                        opcodes.path,
+                        opcodes.name,
+                        a.value,
+                        describe(a),
+                        describe(b)
+                    ));
+                }
+            }
+            // report each run of shifted variants once, at its first member
+            let first_of_run = index == 0 || opcodes.variants[index - 1].shifts_with != a.shifts_with;
+            if let (Some(gated), true) = (&a.shifts_with, first_of_run) {
+                let run =
+                    opcodes.variants[index..].iter().take_while(|v| v.shifts_with == a.shifts_with).count();
+                warnings.push(format!(
+                    "{}: `{}::{}`{} implicitly numbered after cfg-gated `{}`, so the value{} on the build \
+                     configuration",
+                    opcodes.path,
+                    opcodes.name,
+                    a.name,
+                    if run > 1 {
+                        format!(" and the {} variants after it are", run - 1)
+                    } else {
+                        " is".to_string()
+                    },
+                    gated,
+                    if run > 1 { "s depend" } else { " depends" }
+                ));
+            }
+        }
+    }
+    (errors, warnings)
+}
+
+fn by_crate<'a, T>(
+    items: &'a [T],
+    key: impl Fn(&'a T) -> (&'a str, &'a str),
+) -> BTreeMap<(String, String), Vec<&'a T>> {
+    let mut map: BTreeMap<(String, String), Vec<&T>> = BTreeMap::new();
+    for item in items {
+        let (path, name) = key(item);
+        map.entry((crate_of(path).to_string(), name.to_string())).or_default().push(item);
+    }
+    map
+}
+
+/// Lists the ABI-breaking differences between `base` and `head`.
+fn compare(base: &Interfaces, head: &Interfaces) -> Vec<String> {
+    let mut breaks = Vec::new();
+
+    let head_opcodes = by_crate(&head.opcodes, |o| (&o.path, &o.name));
+    for ((krate, name), base_defs) in by_crate(&base.opcodes, |o| (&o.path, &o.name)) {
+        let head_defs = match head_opcodes.get(&(krate.clone(), name.clone())) {
+            Some(defs) => defs,
+            None => {
+                breaks.push(format!("{}: opcode enum `{}` was removed", krate, name));
+                continue;
+            }
+        };
+        for old in base_defs {
+            // the same enum can be defined more than once under different cfgs; pair them up by file
+            let new = head_defs.iter().find(|d| d.path == old.path).unwrap_or(&head_defs[0]);
+            for ov in old.variants.iter() {
+                match new.variants.iter().find(|nv| nv.name == ov.name) {
+                    None => breaks
+                        .push(format!("{}: `{}::{}` (= {}) was removed", new.path, name, ov.name, ov.value)),
+                    Some(nv) if nv.value != ov.value => breaks.push(format!(
+                        "{}: `{}::{}` was renumbered from {} to {}",
+                        new.path, name, ov.name, ov.value, nv.value
+                    )),
+                    Some(_) => {}
+                }
+            }
+            for nv in new.variants.iter().filter(|nv| old.variants.iter().all(|ov| ov.name != nv.name)) {
+                if let Some(ov) = old.variants.iter().find(|ov| ov.value == nv.value) {
+                    breaks.push(format!(
+                        "{}: `{}` value {} now means `{}`, but clients built against the base send it as `{}`",
+                        new.path, name, nv.value, nv.name, ov.name
+                    ));
+                }
+            }
+        }
+    }
+
+    let head_messages = by_crate(&head.messages, |m| (&m.path, &m.name));
+    for ((krate, name), base_defs) in by_crate(&base.messages, |m| (&m.path, &m.name)) {
+        let head_defs = match head_messages.get(&(krate.clone(), name.clone())) {
+            Some(defs) => defs,
+            None => {
+                breaks.push(format!("{}: message type `{}` was removed", krate, name));
+                continue;
+            }
+        };
+        for old in base_defs {
+            let new = head_defs.iter().find(|d| d.path == old.path).unwrap_or(&head_defs[0]);
+            if old.layout != new.layout {
+                breaks.push(format!(
+                    "{}: layout of message type `{}` changed\n    was: {{ {} }}\n    now: {{ {} }}",
+                    new.path,
+                    name,
+                    old.layout.join(", "),
+                    new.layout.join(", ")
+                ));
+            }
+        }
+    }
+    breaks
+}
+
+/// Problems that are known and accepted, as the start of the report and the reason it's accepted. Any
+/// reported problem that begins with one of these is listed, but doesn't fail the check.
+const ALLOWED: &[(&str, &str)] = &[
+    (
+        "services/pddb/src/api.rs: `Opcode` assigns 26 to both `BasisTesting`",
+        "predates the checker; `BasisTesting` only exists in pddbtest+autobasis test images",
+    ),
+    (
+        "services/gam/src/api/rkyv_enum.rs: layout of message type `MenuMgrOp` changed",
+        "`RenameItem` is appended, and a menu is only ever managed from the process that created it, so \
+         both ends are always the same build",
+    ),
+    (
+        "services/pddb/src/api.rs: layout of message type `PddbBulkReadCode` changed",
+        "`AccessDenied` is appended, and clients built before it read unknown codes as `InternalError`",
+    ),
+    (
+        "services/graphics-server/src/api/text.rs: layout of message type `TextView` changed",
+        "rich text spans and link regions are appended after `text`; rkyv can't add fields compatibly, so apps \
+         loaded with the app loader have to be rebuilt against this release",
+    ),
+];
+
+/// The reason `problem` is accepted, if it is.
+fn allowed(problem: &str) -> Option<&'static str> {
+    ALLOWED.iter().find(|(start, _)| problem.starts_with(start)).map(|(_, reason)| *reason)
+}
+
+/// Entry point for `cargo xtask ipc-check [crates] [--base <rev>] [--head <rev>]`.
+pub(crate) fn ipc_check(crates: &[String], base: Option<&str>, head: Option<&str>) -> Result<(), DynError> {
+    let current = match head {
+        Some(rev) => at_revision(rev, crates)?,
+        None => working_tree(crates)?,
+    };
+    let head_name = head.unwrap_or("the working tree");
+    println!(
+        "Checked {} opcode enums and {} message types in {}",
+        current.opcodes.len(),
+        current.messages.len(),
+        head_name
+    );
+
+    let (mut problems, warnings) = check_duplicates(&current);
+    for warning in warnings.iter() {
+        println!("warning: {}", warning);
+    }
+    for error in problems.iter().filter(|error| allowed(error).is_none()) {
+        println!("error: {}", error);
+    }
+
+    if let Some(base) = base {
+        let breaks = compare(&at_revision(base, crates)?, &current);
+        if breaks.is_empty() {
+            println!("No ABI-breaking changes between {} and {}", base, head_name);
+        }
+        for b in breaks.iter().filter(|b| allowed(b).is_none()) {
+            println!("ABI break: {}", b);
+        }
+        problems.extend(breaks);
+    }
+
+    let (accepted, problems): (Vec<String>, Vec<String>) =
+        problems.into_iter().partition(|problem| allowed(problem).is_some());
+    for problem in accepted.iter() {
+        println!(
+            "allowed: {}\n    because {}",
+            problem.lines().next().unwrap_or(""),
+            allowed(problem).unwrap()
+        );
+    }
+
+    if !problems.is_empty() {
+        return Err(format!("ipc-check found {} problem(s)", problems.len()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATH: &str = "services/example/src/api.rs";
+
+    fn scan(src: &str) -> Interfaces {
+        let mut interfaces = Interfaces::default();
+        interfaces.scan(PATH, src);
+        interfaces
+    }
+
+    fn values(interfaces: &Interfaces) -> Vec<(String, Discriminant)> {
+        interfaces.opcodes[0].variants.iter().map(|v| (v.name.clone(), v.value.clone())).collect()
+    }
+
+    const BASE: &str = "
+        #[derive(num_derive::FromPrimitive, num_derive::ToPrimitive, Debug)]
+        pub(crate) enum Opcode {
+            /// Draws
+            Draw,
+            Clear = 4,
+            Flush,
+            Quit,
+        }
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+        pub struct Post {
+            pub author: String,
+            pub text: String,
+        }
+    ";
+
+    #[test]
+    fn test_numbering() {
+        let interfaces = scan(
+            "
+            #[derive(FromPrimitive)]
+            enum Opcode {
+                A,
+                B = 0x10,
+                C,
+                D = 1 << 4 | 2,
+                E = u8::MAX as usize,
+                F = SOME_CONST,
+                G,
+            }
+        ",
+        );
+        assert_eq!(
+            values(&interfaces),
+            vec![
+                ("A".to_string(), Discriminant::Value(0)),
+                ("B".to_string(), Discriminant::Value(16)),
+                ("C".to_string(), Discriminant::Value(17)),
+                ("D".to_string(), Discriminant::Value(18)),
+                ("E".to_string(), Discriminant::Value(255)),
+                ("F".to_string(), Discriminant::Expr("SOME_CONST".to_string())),
+                ("G".to_string(), Discriminant::Expr("SOME_CONST + 1".to_string())),
+            ]
+        );
+        let (errors, warnings) = check_duplicates(&interfaces);
+        assert!(errors.is_empty() && warnings.is_empty());
+    }
+
+    #[test]
+    fn test_service_numbering() {
+        let interfaces = scan(
+            "
+            #[flatipc::service(opcode = Opcode)]
+            pub trait Codec {
+                #[send]
+                fn suspend_resume(&mut self, token: usize);
+                #[scalar(opcode = 7)]
+                fn volume(&mut self, level: usize) -> usize { level }
+                #[lend]
+                fn play(&mut self, frames: &IpcFrames);
+            }
+        ",
+        );
+        assert_eq!(interfaces.opcodes[0].name, "Opcode");
+        assert_eq!(
+            values(&interfaces),
+            vec![
+                ("SuspendResume".to_string(), Discriminant::Value(0)),
+                ("Volume".to_string(), Discriminant::Value(7)),
+                ("Play".to_string(), Discriminant::Value(8)),
+            ]
+        );
+        assert_eq!(scan("#[flatipc::service] trait Codec {}").opcodes[0].name, "CodecOpcode");
+    }
+
+    #[test]
+    fn test_cfg_gated_variants() {
+        let interfaces = scan(
+            "
+            #[derive(FromPrimitive)]
+            enum Opcode {
+                A,
+                #[cfg(feature = \"test\")]
+                Test,
+                B,
+                C,
+                D = 10,
+                #[cfg(feature = \"hw\")]
+                Hw = 11,
+                #[cfg(not(feature = \"hw\"))]
+                Hosted = 11,
+                #[cfg(feature = \"debug\")]
+                Debug = 10,
+            }
+        ",
+        );
+        let (errors, warnings) = check_duplicates(&interfaces);
+        // `Hw` and `Hosted` can't both exist, but `Debug` collides with `D` whenever it does
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].contains("`D`") && errors[0].contains("`Debug`"));
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(warnings[0].contains("`Opcode::B` and the 1 variants after it are"));
+        assert!(warnings[0].contains("cfg-gated `Test`"));
+    }
+
+    #[test]
+    fn test_unchanged() {
+        assert!(compare(&scan(BASE), &scan(BASE)).is_empty());
+        // appending an opcode, reformatting and adding comments are all fine
+        let head = "
+            #[derive(Debug, num_derive::ToPrimitive, num_derive::FromPrimitive)]
+            pub(crate) enum Opcode {
+                Draw,
+                // the next four are spaced out so that Draw can grow
+                Clear = 4, Flush, Quit,
+                Resize,
+            }
+            #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+            pub struct Post { pub author: String, pub text: String }
+        ";
+        assert!(compare(&scan(BASE), &scan(head)).is_empty());
+    }
+
+    #[test]
+    fn test_renumbering() {
+        let head = BASE.replace("Clear = 4", "Clear = 5");
+        let breaks = compare(&scan(BASE), &scan(&head));
+        assert_eq!(
+            breaks,
+            vec![
+                format!("{}: `Opcode::Clear` was renumbered from 4 to 5", PATH),
+                format!("{}: `Opcode::Flush` was renumbered from 5 to 6", PATH),
+                format!("{}: `Opcode::Quit` was renumbered from 6 to 7", PATH),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mid_enum_insertion() {
+        let head = BASE.replace("Flush,", "Flush,\n Resize,");
+        let breaks = compare(&scan(BASE), &scan(&head));
+        assert_eq!(
+            breaks,
+            vec![
+                format!("{}: `Opcode::Quit` was renumbered from 6 to 7", PATH),
+                format!(
+                    "{}: `Opcode` value 6 now means `Resize`, but clients built against the base send it as `Quit`",
+                    PATH
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_removals() {
+        let head = BASE.replace("Quit,", "");
+        assert_eq!(
+            compare(&scan(BASE), &scan(&head)),
+            vec![format!("{}: `Opcode::Quit` (= 6) was removed", PATH)]
+        );
+        let head = BASE.replace("enum Opcode", "enum Op");
+        assert_eq!(
+            compare(&scan(BASE), &scan(&head)),
+            vec!["services/example: opcode enum `Opcode` was removed".to_string()]
+        );
+        let head = BASE.replace("struct Post", "struct Message");
+        assert_eq!(
+            compare(&scan(BASE), &scan(&head)),
+            vec!["services/example: message type `Post` was removed".to_string()]
+        );
+        // moving an interface to another file of the same crate isn't a removal
+        let mut moved = Interfaces::default();
+        moved.scan("services/example/src/lib.rs", BASE);
+        assert!(compare(&scan(BASE), &moved).is_empty());
+    }
+
+    #[test]
+    fn test_layout_changes() {
+        for head in [
+            BASE.replace("pub text: String", "pub text: String, pub reply_to: Option<u32>"),
+            BASE.replace(
+                "pub author: String,\n            pub text: String",
+                "pub text: String, pub author: String",
+            ),
+            BASE.replace("pub text: String", "pub text: xous_ipc::String<1024>"),
+            BASE.replace("pub struct Post", "#[repr(C)] pub struct Post"),
+        ] {
+            let breaks = compare(&scan(BASE), &scan(&head));
+            assert_eq!(breaks.len(), 1, "{}", head);
+            assert!(breaks[0].starts_with(&format!("{}: layout of message type `Post` changed", PATH)));
+        }
+    }
+
+    #[test]
+    fn test_allowed() {
+        let src = "
+            #[derive(Debug, num_derive::ToPrimitive, num_derive::FromPrimitive)]
+            pub enum Opcode {
+                #[cfg(all(feature=\"pddbtest\",feature=\"autobasis\"))]
+                BasisTesting = 26,
+                ListBasisStd = 26,
+            }
+        ";
+        let mut pddb = Interfaces::default();
+        pddb.scan("services/pddb/src/api.rs", src);
+        let (errors, _) = check_duplicates(&pddb);
+        assert_eq!(errors.len(), 1);
+        assert!(allowed(&errors[0]).is_some());
+        // the same collision anywhere else isn't
+        let (errors, _) = check_duplicates(&scan(src));
+        assert_eq!(errors.len(), 1);
+        assert!(allowed(&errors[0]).is_none());
+    }
+}
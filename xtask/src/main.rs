@@ -6,6 +6,7 @@ mod utils;
 use utils::*;
 mod builder;
 use builder::*;
+mod ipc_check;
 mod verifier;
 use std::env;
 
@@ -93,8 +94,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             "gam",
             "ime-frontend",
             "ime-plugin-shell",
+            "ime-plugin-predict",
             "codec",
             "modals",
+            "permissions", // what apps may use; net, pddb, codec and usb-device-xous ask it
+            "qr",          // hands codes read by a camera to the apps scanning
             // security
             "root-keys",
             "trng",
@@ -469,6 +473,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let mut pkgs = user_pkgs.to_vec();
             pkgs.push("tts-frontend");
             pkgs.push("ime-plugin-tts");
+            // the braille display reads the same accessibility tree the screen reader speaks
+            pkgs.push("braille");
             pkgs.retain(|&pkg| pkg != "ime-plugin-shell");
 
             builder.add_services(&pkgs)
@@ -590,7 +596,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             //   - [planned] pddb server
             //   - [planned] vault application
             let bao_rram_pkgs = ["xous-ticktimer", "xous-log", "xous-names" /* "usb-cramium" */].to_vec(); /* "usb-cramium" */
-            let bao_swap_pkgs = ["cram-hal-service", "bao-console", "bao-video"].to_vec(); /* "bao-video" */
+            let bao_swap_pkgs = ["cram-hal-service", "bao-console", "qr", "bao-video"].to_vec(); /* "bao-video" */
             if !builder.is_swap_set() {
                 builder.set_swap(0, 8 * 1024 * 1024);
             }
@@ -655,6 +661,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         Some("generate-locales") => generate_locales()?,
         Some("wycheproof-import") => wycheproof_import()?,
         Some("dummy-template") => generate_app_menus(&Vec::new()),
+        Some("ipc-check") => ipc_check::ipc_check(
+            &get_cratespecs(),
+            get_flag("--base")?.first().map(|s| s.as_str()),
+            get_flag("--head")?.first().map(|s| s.as_str()),
+        )?,
         _ => print_help(),
     }
     builder.build()?;
@@ -826,6 +837,9 @@ Other commands:
  install-toolkit         installs Xous toolkit with no prompt, useful in CI. Specify `--force` to remove existing toolchains
  compile-apps            Just compiles the apps specified in [cratespecs], for example in order to use app server
  dummy-template          Generate dummy templates for formatting and checking purposes
+ ipc-check               Check service opcodes for duplicate discriminants. [cratespecs] limit the check to those crates.
+                         With `--base <rev>`, also report ABI-breaking opcode and message changes between <rev>
+                         and `--head <rev>` (default: the working tree)
 
 Note: By default, the `ticktimer` will get rebuilt every time. You can skip this by appending `--no-timestamp` to the command.
 "